@@ -0,0 +1,226 @@
+//! Database inspection.
+//!
+//! `/admin/db` lists every tree in the database with its key count and the
+//! bytes its keys and values take, and purges the caches in `PURGES` one at
+//! a time, so a bad cache entry doesn't mean deleting the whole database.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::auth::is_logged_in;
+use crate::config::DbBackend;
+use crate::notes::html_escape;
+use crate::store::{Db, DEFAULT_TREE};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// A cache that can be emptied from `/admin/db`.
+pub struct Purge {
+    pub id: &'static str,
+    pub label: &'static str,
+    /// Trees cleared; an empty list means only the in-memory caches.
+    pub trees: &'static [&'static str],
+    pub description: &'static str,
+}
+
+pub const PURGES: &[Purge] = &[
+    Purge {
+        id: "sessions",
+        label: "Sessions",
+        trees: &["sessions", "csrf_tokens"],
+        description: "Logs everyone out, this browser included",
+    },
+    Purge {
+        id: "lookup",
+        label: "Lookup cache",
+        trees: &["page_cache"],
+        description: "Pages fetched for Smart Add lookups and link checks; fetched again on next use",
+    },
+    Purge {
+        id: "previews",
+        label: "Link previews",
+        trees: &["link_previews"],
+        description: "Link cards, fetched again the next time their note is viewed",
+    },
+    Purge {
+        id: "render",
+        label: "Render cache",
+        trees: &[],
+        description: "Parsed notes and the related-notes index, rebuilt from disk on the next request",
+    },
+];
+
+/// Key count and size of one tree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TreeStats {
+    pub name: String,
+    pub keys: usize,
+    /// Bytes of keys plus values, before the backend's own overhead.
+    pub bytes: u64,
+}
+
+/// Stats for every tree, largest first.
+pub fn tree_stats(db: &Db) -> Vec<TreeStats> {
+    let mut stats: Vec<TreeStats> = db
+        .tree_names()
+        .into_iter()
+        .filter_map(|name| {
+            let tree = db.open_tree(&name).ok()?;
+            let (keys, bytes) = tree
+                .iter()
+                .flatten()
+                .fold((0, 0), |(keys, bytes), (k, v)| (keys + 1, bytes + (k.len() + v.len()) as u64));
+            let name = if name == DEFAULT_TREE {
+                "(default)".to_string()
+            } else {
+                String::from_utf8_lossy(&name).into_owned()
+            };
+            Some(TreeStats { name, keys, bytes })
+        })
+        .collect();
+    stats.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.name.cmp(&b.name)));
+    stats
+}
+
+/// Empty the trees of `purge`; returns the number of entries removed.
+pub fn purge_trees(db: &Db, purge: &Purge) -> Result<usize, String> {
+    let mut removed = 0;
+    for name in purge.trees {
+        let tree = db.open_tree(name).map_err(|e| e.to_string())?;
+        removed += tree.len();
+        tree.clear().map_err(|e| e.to_string())?;
+    }
+    db.flush().map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /admin/db`: trees, sizes and purge buttons.
+pub async fn db_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let stats = tree_stats(&state.db);
+    let backend = match state.config.db_backend {
+        DbBackend::Sled => "sled",
+        DbBackend::Sqlite => "SQLite",
+    };
+    let mut html = format!(
+        r#"<h1>Database</h1>
+        <p class="time-breadcrumb">{} at {} · {} on disk · {} trees, {} keys</p>"#,
+        backend,
+        html_escape(&state.config.db_path.display().to_string()),
+        state.db.size_on_disk().map_or("?".to_string(), format_bytes),
+        stats.len(),
+        stats.iter().map(|s| s.keys).sum::<usize>(),
+    );
+
+    html.push_str(r#"<h2>Caches</h2><table class="time-table"><tr><th>Cache</th><th>Trees</th><th></th><th></th></tr>"#);
+    for purge in PURGES {
+        html.push_str(&format!(
+            r#"<tr><td>{}</td><td>{}</td><td>{}</td><td><button onclick="purgeCache('{}', '{}', this)">Purge</button></td></tr>"#,
+            purge.label,
+            if purge.trees.is_empty() { "in memory".to_string() } else { purge.trees.join(", ") },
+            purge.description,
+            purge.id,
+            purge.description,
+        ));
+    }
+    html.push_str("</table>");
+
+    html.push_str(r#"<h2>Trees</h2><table class="time-table"><tr><th>Tree</th><th>Keys</th><th>Size</th></tr>"#);
+    for s in &stats {
+        html.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+            html_escape(&s.name),
+            s.keys,
+            format_bytes(s.bytes),
+        ));
+    }
+    html.push_str("</table>");
+    html.push_str(
+        r#"<script>
+        async function purgeCache(id, what, btn) {
+            if (!confirm('Purge this cache? ' + what + '.')) return;
+            btn.disabled = true;
+            const resp = await fetch('/api/admin/db/purge/' + id, {method: 'POST'});
+            if (!resp.ok) { alert('Purge failed: ' + await resp.text()); btn.disabled = false; return; }
+            window.location.reload();
+        }
+        </script>"#,
+    );
+
+    Html(base_html("Database", &html, None, true)).into_response()
+}
+
+/// `POST /api/admin/db/purge/{id}`: empty one of `PURGES`; returns
+/// `{removed}`, the number of entries deleted.
+pub async fn purge_handler(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let Some(purge) = PURGES.iter().find(|p| p.id == id) else {
+        return (StatusCode::NOT_FOUND, "No such cache").into_response();
+    };
+
+    let db = state.db.clone();
+    let result = tokio::task::spawn_blocking(move || purge_trees(&db, purge))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    match result {
+        Ok(removed) => {
+            if purge.id == "render" {
+                state.invalidate_notes_cache();
+            }
+            tracing::info!(cache = purge.id, removed, "Purged cache");
+            axum::Json(serde_json::json!({ "removed": removed })).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_and_purge() {
+        let db = Db::temporary();
+        db.insert(b"k", b"v").unwrap();
+        db.open_tree("sessions").unwrap().insert(b"abc", b"12345678").unwrap();
+        db.open_tree("csrf_tokens").unwrap().insert(b"t", b"1").unwrap();
+        db.open_tree("kg:nodes").unwrap().insert(b"note", b"{}").unwrap();
+
+        let stats = tree_stats(&db);
+        assert_eq!(stats[0], TreeStats { name: "sessions".to_string(), keys: 1, bytes: 11 });
+        assert!(stats.contains(&TreeStats { name: "(default)".to_string(), keys: 1, bytes: 2 }));
+
+        let sessions = PURGES.iter().find(|p| p.id == "sessions").unwrap();
+        assert_eq!(purge_trees(&db, sessions).unwrap(), 2);
+        assert!(db.open_tree("sessions").unwrap().is_empty());
+        assert_eq!(db.open_tree("kg:nodes").unwrap().len(), 1);
+        assert_eq!(format_bytes(2048), "2.0 KB");
+    }
+}
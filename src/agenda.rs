@@ -0,0 +1,161 @@
+//! The agenda.
+//!
+//! `/agenda` lists what is due: notes with a `due:` date and open tasks
+//! with an `@due(...)` annotation, split into overdue, today and the next
+//! seven days. Archived notes are left out.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{Days, NaiveDate};
+
+use crate::archive::is_archived;
+use crate::auth::is_logged_in;
+use crate::models::{Note, Task};
+use crate::notes::html_escape;
+use crate::tasks::extract_tasks;
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Days after today that count as this week.
+const WEEK_DAYS: u64 = 7;
+
+/// A due note, or an open task in it when `task` is set.
+#[derive(Debug, Clone)]
+pub struct AgendaItem<'a> {
+    pub due: NaiveDate,
+    pub note: &'a Note,
+    pub task: Option<Task>,
+}
+
+#[derive(Debug, Default)]
+pub struct Agenda<'a> {
+    pub overdue: Vec<AgendaItem<'a>>,
+    pub today: Vec<AgendaItem<'a>>,
+    pub week: Vec<AgendaItem<'a>>,
+}
+
+/// Everything due up to a week from `today`, soonest first.
+pub fn agenda(notes: &[Note], today: NaiveDate) -> Agenda<'_> {
+    let horizon = today + Days::new(WEEK_DAYS);
+    let mut items: Vec<AgendaItem> = Vec::new();
+    for note in notes.iter().filter(|n| !is_archived(n)) {
+        if let Some(due) = note.due {
+            items.push(AgendaItem { due, note, task: None });
+        }
+        items.extend(extract_tasks(note).into_iter().filter(|t| !t.done).filter_map(|t| {
+            Some(AgendaItem {
+                due: t.due?,
+                note,
+                task: Some(t),
+            })
+        }));
+    }
+    items.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.note.title.cmp(&b.note.title)));
+
+    let mut agenda = Agenda::default();
+    for item in items {
+        if item.due < today {
+            agenda.overdue.push(item);
+        } else if item.due == today {
+            agenda.today.push(item);
+        } else if item.due <= horizon {
+            agenda.week.push(item);
+        }
+    }
+    agenda
+}
+
+fn section(heading: &str, items: &[AgendaItem], today: NaiveDate) -> String {
+    let mut html = format!("<h2>{} ({})</h2>", heading, items.len());
+    if items.is_empty() {
+        html.push_str("<p>Nothing.</p>");
+        return html;
+    }
+    html.push_str(r#"<table class="time-table"><tr><th>Due</th><th>Item</th><th>Note</th></tr>"#);
+    for item in items {
+        let when = match (today - item.due).num_days() {
+            0 => "today".to_string(),
+            1 => "yesterday".to_string(),
+            -1 => "tomorrow".to_string(),
+            late if late > 0 => format!("{} days ago", late),
+            _ => item.due.format("%a %b %-d").to_string(),
+        };
+        let what = match item.task {
+            Some(ref task) => html_escape(&task.text),
+            None => "<em>note due</em>".to_string(),
+        };
+        html.push_str(&format!(
+            r#"<tr><td title="{}">{}</td><td>{}</td><td><a href="/note/{}">{}</a></td></tr>"#,
+            item.due.format("%Y-%m-%d"),
+            when,
+            what,
+            item.note.key,
+            html_escape(&item.note.title),
+        ));
+    }
+    html.push_str("</table>");
+    html
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /agenda`: overdue items, today's, and the next week's.
+pub async fn agenda_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let today = crate::timezone::today();
+    let agenda = agenda(&notes, today);
+
+    let mut html = String::from("<h1>Agenda</h1>");
+    html.push_str(
+        r#"<p class="time-breadcrumb">Notes with <code>due:</code> and open tasks with <code>@due(YYYY-MM-DD)</code>. <a href="/tasks">All tasks</a></p>"#,
+    );
+    html.push_str(&section("Overdue", &agenda.overdue, today));
+    html.push_str(&section("Today", &agenda.today, today));
+    html.push_str(&section("This week", &agenda.week, today));
+
+    Html(base_html("Agenda", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_agenda_buckets() {
+        let dir = TempDir::new("agenda");
+        fs::write(
+            dir.join("grant.md"),
+            "---\ntitle: Grant\ndue: 2026-10-20\n---\n\n- [ ] budget @due(2026-10-17)\n\
+             - [x] letters @due(2026-10-01)\n- [ ] draft @due(2026-10-10)\n- [ ] someday @due(2027-01-01)\n",
+        )
+        .unwrap();
+        fs::write(dir.join("old.md"), "---\ntitle: Old\ndue: 2026-01-01\nstatus: archived\n---\n").unwrap();
+
+        let notes = load_all_notes(&dir);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let agenda = agenda(&notes, today);
+        let texts = |items: &[AgendaItem]| -> Vec<String> {
+            items
+                .iter()
+                .map(|i| i.task.as_ref().map_or(format!("note {}", i.note.title), |t| t.text.clone()))
+                .collect()
+        };
+        assert_eq!(texts(&agenda.overdue), vec!["draft"]);
+        assert_eq!(texts(&agenda.today), vec!["budget"]);
+        assert_eq!(texts(&agenda.week), vec!["note Grant"]);
+    }
+}
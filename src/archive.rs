@@ -0,0 +1,184 @@
+//! Archival of stale notes.
+//!
+//! With `archive_after_months` set, notes (not papers) whose files haven't
+//! changed in that many months are flagged on the index and listed on
+//! `/archive`, where one click marks a note `status: archived`. Archiving
+//! only sets the field; the note stays where it is.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Months, Utc};
+
+use crate::auth::is_logged_in;
+use crate::handlers::rewrite_note_file;
+use crate::models::{Note, NoteType};
+use crate::notes::{html_escape, set_frontmatter_field};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// The `status:` of an archived note.
+pub const ARCHIVED: &str = "archived";
+
+pub fn is_archived(note: &Note) -> bool {
+    note.status.as_deref() == Some(ARCHIVED)
+}
+
+/// Notes untouched for `months` months that aren't papers or archived
+/// already, least recently modified first. Empty when `months` is 0.
+pub fn stale_notes(notes: &[Note], months: u32, now: DateTime<Utc>) -> Vec<&Note> {
+    if months == 0 {
+        return vec![];
+    }
+    let Some(cutoff) = now.checked_sub_months(Months::new(months)) else {
+        return vec![];
+    };
+    let mut stale: Vec<&Note> = notes
+        .iter()
+        .filter(|n| matches!(n.note_type, NoteType::Note) && !is_archived(n) && n.modified < cutoff)
+        .collect();
+    stale.sort_by_key(|n| n.modified);
+    stale
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /archive`: review the notes the policy flags.
+pub async fn archive_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let months = state.config.archive_after_months;
+    let mut html = String::from("<h1>Archive</h1>");
+    if months == 0 {
+        html.push_str(
+            "<p>No archival policy is set. Set <code>archive_after_months</code> in notes.toml \
+             to list notes that haven't changed in that many months.</p>",
+        );
+        return Html(base_html("Archive", &html, None, true)).into_response();
+    }
+
+    let notes = state.load_notes();
+    let stale = stale_notes(&notes, months, Utc::now());
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">Notes untouched for {} months or more. Archiving sets <code>status: archived</code>.</p>"#,
+        months
+    ));
+    if stale.is_empty() {
+        html.push_str("<p>Nothing to review.</p>");
+    } else {
+        html.push_str(&format!(
+            r#"<p><button onclick="archiveAll(this)">Archive all {}</button></p>
+            <table class="time-table"><tr><th>Title</th><th>Path</th><th>Last modified</th><th></th></tr>"#,
+            stale.len()
+        ));
+        for note in &stale {
+            html.push_str(&format!(
+                r#"<tr data-key="{key}"><td><a href="/note/{key}">{}</a></td><td>{}</td><td>{}</td><td>
+                <button onclick="archiveNote('{key}', this)">Archive</button></td></tr>"#,
+                html_escape(&note.title),
+                html_escape(&note.path.to_string_lossy()),
+                crate::timezone::format(note.modified, "%Y-%m-%d"),
+                key = note.key,
+            ));
+        }
+        html.push_str("</table>");
+    }
+    html.push_str(
+        r#"<script>
+        async function archiveNote(key, btn) {
+            const resp = await fetch('/api/note/' + key + '/archive', {method: 'POST'});
+            if (!resp.ok) { alert('Failed to archive: ' + await resp.text()); return false; }
+            btn.closest('tr').remove();
+            return true;
+        }
+        async function archiveAll(btn) {
+            if (!confirm('Archive every note listed here?')) return;
+            btn.disabled = true;
+            for (const row of document.querySelectorAll('tr[data-key]')) {
+                if (!await archiveNote(row.dataset.key, row.querySelector('button'))) break;
+            }
+            btn.remove();
+        }
+        </script>"#,
+    );
+
+    Html(base_html("Archive", &html, None, true)).into_response()
+}
+
+/// `POST /api/note/{key}/archive`: set `status: archived`.
+pub async fn archive_note(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let note = match notes_map.get(&key) {
+        Some(n) => n,
+        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+    };
+
+    let now = crate::timezone::now();
+    let commit_msg = format!("archived '{}': {}", note.title, now.format("%a %b %d, %-I:%M%p"));
+    match rewrite_note_file(&state, note, commit_msg, |c| {
+        set_frontmatter_field(c, "status", Some(ARCHIVED))
+    }) {
+        Ok(_) => axum::Json(serde_json::json!({ "status": ARCHIVED })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PaperMeta;
+    use std::path::PathBuf;
+
+    fn note(key: &str, modified: &str, status: Option<&str>, paper: bool) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: key.to_string(),
+            note_type: if paper {
+                NoteType::Paper(PaperMeta {
+                    bibtex_entries: vec![],
+                    canonical_key: None,
+                    sources: vec![],
+                    versions: vec![],
+                })
+            } else {
+                NoteType::Note
+            },
+            status: status.map(str::to_string),
+            modified: modified.parse().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stale_notes() {
+        let notes = vec![
+            note("recent", "2026-09-01T00:00:00Z", None, false),
+            note("old", "2026-01-10T00:00:00Z", None, false),
+            note("older", "2025-06-01T00:00:00Z", None, false),
+            note("archived", "2025-01-01T00:00:00Z", Some("archived"), false),
+            note("paper", "2025-01-01T00:00:00Z", None, true),
+        ];
+        let now: DateTime<Utc> = "2026-10-17T12:00:00Z".parse().unwrap();
+        let keys: Vec<&str> = stale_notes(&notes, 6, now).iter().map(|n| n.key.as_str()).collect();
+        assert_eq!(keys, vec!["older", "old"]);
+        assert!(stale_notes(&notes, 0, now).is_empty());
+    }
+}
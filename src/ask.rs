@@ -0,0 +1,220 @@
+//! Questions answered from the notes.
+//!
+//! `POST /api/ask` finds the notes most relevant to a question (by meaning
+//! when `[embeddings]` is configured, else by TF-IDF), hands excerpts of
+//! them to the language model with instructions to cite them as `[@key]`,
+//! and returns the answer rendered with links to its sources. `/ask` is a
+//! page for it.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::is_logged_in;
+use crate::embeddings;
+use crate::models::Note;
+use crate::notes::{process_crosslinks, render_markdown};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Notes handed to the model.
+const MAX_SOURCES: usize = 6;
+
+/// Characters of each note's body included.
+const EXCERPT_CHARS: usize = 3_000;
+
+/// Keys of the notes most relevant to `question`, best first.
+async fn retrieve(state: &AppState, question: &str) -> Vec<String> {
+    if embeddings::is_enabled(&state.config.embeddings) {
+        match embeddings::semantic_search(state, question).await {
+            Ok(ranked) => return ranked.into_iter().take(MAX_SOURCES).map(|(k, _)| k).collect(),
+            Err(e) => tracing::warn!(error = %e, "Semantic search failed, using TF-IDF"),
+        }
+    }
+    state
+        .related_index()
+        .search(question, MAX_SOURCES)
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect()
+}
+
+/// The notes as the model sees them: key, title and the start of the body.
+fn context(sources: &[&Note]) -> String {
+    sources
+        .iter()
+        .map(|n| {
+            let body = n.raw_content.trim();
+            let excerpt = match body.char_indices().nth(EXCERPT_CHARS) {
+                Some((i, _)) => format!("{}...", &body[..i]),
+                None => body.to_string(),
+            };
+            format!("## [@{}] {}\n\n{}\n", n.key, n.title, excerpt)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn prompt(question: &str) -> String {
+    format!(
+        "Answer the question below using only the notes provided. Each note starts with a \
+         heading giving its citation, like [@3fa9c01b22d4]; cite the notes you use that way after the \
+         sentences they support. If the notes don't answer the question, say so. Reply in markdown.\n\n\
+         Question: {}",
+        question
+    )
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct AskRequest {
+    pub question: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AskSource {
+    pub key: String,
+    pub title: String,
+    /// Whether the answer cites it.
+    pub cited: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AskResponse {
+    pub answer: String,
+    /// `answer` rendered, citations linked.
+    pub answer_html: String,
+    pub sources: Vec<AskSource>,
+}
+
+/// `POST /api/ask`: `{question}` → `{answer, answer_html, sources}`.
+pub async fn ask(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<AskRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let question = req.question.trim();
+    if question.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Ask a question").into_response();
+    }
+
+    let keys = retrieve(&state, question).await;
+    let notes_map = state.notes_map();
+    let sources: Vec<&Note> = keys.iter().filter_map(|k| notes_map.get(k)).collect();
+    if sources.is_empty() {
+        return (StatusCode::NOT_FOUND, "No notes match the question").into_response();
+    }
+
+    let answer = match crate::llm::complete(&state.http, prompt(question), Some(context(&sources))).await {
+        Ok(answer) => answer.trim().to_string(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Answering failed");
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+    };
+    let answer_html = render_markdown(&process_crosslinks(&answer, &notes_map));
+    let sources = sources
+        .into_iter()
+        .map(|n| AskSource {
+            key: n.key.clone(),
+            title: n.title.clone(),
+            cited: answer.contains(&format!("[@{}]", n.key)),
+        })
+        .collect();
+    axum::Json(AskResponse {
+        answer,
+        answer_html,
+        sources,
+    })
+    .into_response()
+}
+
+/// `GET /ask`: a question box over `POST /api/ask`.
+pub async fn ask_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let html = r#"<h1>Ask your notes</h1>
+<p class="time-breadcrumb">Answered by AI from the most relevant notes, with links to them.</p>
+<form id="ask-form" onsubmit="askQuestion(event)">
+    <textarea id="ask-question" rows="3" style="width: 100%" placeholder="What did I conclude about ...?" required></textarea>
+    <button type="submit" id="ask-btn">Ask</button>
+</form>
+<div id="ask-answer" class="note-content"></div>
+<div id="ask-sources" class="sub-notes"></div>
+<script>
+async function askQuestion(event) {
+    event.preventDefault();
+    const btn = document.getElementById('ask-btn');
+    const answer = document.getElementById('ask-answer');
+    const sources = document.getElementById('ask-sources');
+    btn.disabled = true;
+    answer.textContent = 'Thinking...';
+    sources.innerHTML = '';
+    try {
+        const resp = await fetch('/api/ask', {
+            method: 'POST',
+            headers: {'Content-Type': 'application/json'},
+            body: JSON.stringify({question: document.getElementById('ask-question').value}),
+        });
+        if (!resp.ok) {
+            answer.textContent = 'Failed: ' + await resp.text();
+            return;
+        }
+        const data = await resp.json();
+        answer.innerHTML = data.answer_html;
+        const list = document.createElement('ul');
+        for (const s of data.sources) {
+            const li = document.createElement('li');
+            const a = document.createElement('a');
+            a.href = '/note/' + s.key;
+            a.textContent = s.title;
+            li.append(a, s.cited ? ' (cited)' : '');
+            list.append(li);
+        }
+        const heading = document.createElement('h3');
+        heading.textContent = 'Notes consulted';
+        sources.append(heading, list);
+    } finally {
+        btn.disabled = false;
+    }
+}
+</script>"#;
+    Html(base_html("Ask", html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_context() {
+        let dir = TempDir::new("ask");
+        fs::write(dir.join("a.md"), "---\ntitle: Blame\nid: aaaaaaaaaaaa\n---\n\nBlame tracks casts.\n").unwrap();
+        fs::write(dir.join("b.md"), format!("---\ntitle: Long\nid: bbbbbbbbbbbb\n---\n\n{}\n", "x".repeat(5000)))
+            .unwrap();
+        let notes = load_all_notes(&dir);
+        let mut sources: Vec<&Note> = notes.iter().collect();
+        sources.sort_by_key(|n| &n.key);
+
+        let text = context(&sources);
+        assert!(text.starts_with("## [@aaaaaaaaaaaa] Blame\n\nBlame tracks casts.\n\n## [@bbbbbbbbbbbb] Long\n\n"));
+        assert!(text.ends_with(&format!("{}...\n", "x".repeat(EXCERPT_CHARS))));
+    }
+}
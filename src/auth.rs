@@ -2,7 +2,12 @@
 //!
 //! Uses Argon2id for password hashing and sled for server-side sessions.
 //! Authentication is optional and enabled by setting the NOTES_PASSWORD
-//! environment variable.
+//! environment variable. Read-only mode (`--read-only`, a vault's
+//! `Config::read_only`) overrides both and rejects every mutating request.
+//!
+//! `read_only_guard` answers every non-GET/HEAD request with 403 and scopes
+//! the mode for `is_read_only`/`is_published`; GET routes that change state
+//! are marked with `mutating` in `main`.
 
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
@@ -13,6 +18,12 @@ use chrono::Utc;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
 
 /// Session cookie name
 pub const SESSION_COOKIE: &str = "notes_session";
@@ -60,6 +71,62 @@ pub fn verify_password(attempt: &str, password_hash: &str) -> bool {
         .is_ok()
 }
 
+// ============================================================================
+// Read-Only Mode
+// ============================================================================
+
+/// The serving vault's `read_only` and `publish` settings, scoped to each
+/// request by `read_only_guard` for code that has no `AppState` at hand
+/// (templates, `is_logged_in`).
+#[derive(Debug, Clone, Copy, Default)]
+struct Mode {
+    read_only: bool,
+    published: bool,
+}
+
+tokio::task_local! {
+    static MODE: Mode;
+}
+
+fn mode() -> Mode {
+    MODE.try_with(|m| *m).unwrap_or_default()
+}
+
+/// Check if the request is served in read-only mode (`Config::read_only`).
+pub fn is_read_only() -> bool {
+    mode().read_only
+}
+
+/// Check if the request is served by a public viewer started by `notes
+/// publish`. Pages then leave out links to routes that aren't served.
+pub fn is_published() -> bool {
+    mode().published
+}
+
+fn read_only_response() -> Response {
+    (StatusCode::FORBIDDEN, "Read-only mode").into_response()
+}
+
+/// Middleware: scope the vault's mode for `is_read_only`/`is_published`
+/// and, in read-only mode, reject every method but GET and HEAD.
+pub async fn read_only_guard(State(state): State<Arc<crate::AppState>>, request: Request, next: Next) -> Response {
+    let mode = Mode { read_only: state.config.read_only, published: state.config.publish };
+    if mode.read_only && request.method() != Method::GET && request.method() != Method::HEAD {
+        return read_only_response();
+    }
+    MODE.scope(mode, next.run(request)).await
+}
+
+/// Route layer for the GET routes that change state (logging in and out,
+/// creating a note, joining a shared editing session): refused in
+/// read-only mode.
+pub async fn mutating(request: Request, next: Next) -> Response {
+    if is_read_only() {
+        return read_only_response();
+    }
+    next.run(request).await
+}
+
 // ============================================================================
 // Authentication Check
 // ============================================================================
@@ -72,11 +139,16 @@ fn trust_proxy_auth() -> bool {
 
 /// Check if authentication is enabled
 pub fn is_auth_enabled() -> bool {
-    trust_proxy_auth() || env::var("NOTES_PASSWORD").is_ok()
+    !is_read_only() && (trust_proxy_auth() || env::var("NOTES_PASSWORD").is_ok())
 }
 
 /// Check if the user is logged in via cookie (server-side session lookup).
-pub fn is_logged_in(jar: &CookieJar, db: &sled::Db) -> bool {
+/// Always false in read-only mode, which hides all editing UI.
+pub fn is_logged_in(jar: &CookieJar, db: &crate::store::Db) -> bool {
+    if is_read_only() {
+        return false;
+    }
+
     if trust_proxy_auth() {
         return true;
     }
@@ -95,12 +167,12 @@ pub fn is_logged_in(jar: &CookieJar, db: &sled::Db) -> bool {
 // Server-Side Sessions (sled)
 // ============================================================================
 
-fn sessions_tree(db: &sled::Db) -> sled::Tree {
+fn sessions_tree(db: &crate::store::Db) -> crate::store::Tree {
     db.open_tree("sessions").expect("Failed to open sessions tree")
 }
 
 /// Create a new session, store it in sled, and return the session ID (hex string).
-pub fn create_session(db: &sled::Db) -> Option<String> {
+pub fn create_session(db: &crate::store::Db) -> Option<String> {
     let mut id_bytes = [0u8; 32];
     OsRng.fill(&mut id_bytes);
     let session_id = hex_encode(&id_bytes);
@@ -119,7 +191,7 @@ pub fn create_session(db: &sled::Db) -> Option<String> {
 }
 
 /// Verify a session ID exists and is not expired.
-pub fn verify_session(session_id: &str, db: &sled::Db) -> bool {
+pub fn verify_session(session_id: &str, db: &crate::store::Db) -> bool {
     let tree = sessions_tree(db);
     match tree.get(session_id.as_bytes()) {
         Ok(Some(data)) => {
@@ -137,40 +209,59 @@ pub fn verify_session(session_id: &str, db: &sled::Db) -> bool {
 }
 
 /// Delete a session (server-side revocation for logout).
-pub fn delete_session(session_id: &str, db: &sled::Db) {
+pub fn delete_session(session_id: &str, db: &crate::store::Db) {
     let tree = sessions_tree(db);
     let _ = tree.remove(session_id.as_bytes());
 }
 
+/// Copy the request's session from `from` into `to`, or drop it from `to`
+/// once `from` no longer has it. Logins live in the primary vault's
+/// database; this lets one login cover every vault. Writes only when the
+/// two differ, so most requests just read.
+pub fn mirror_session(jar: &CookieJar, from: &crate::store::Db, to: &crate::store::Db) {
+    let Some(cookie) = jar.get(SESSION_COOKIE) else {
+        return;
+    };
+    let id = cookie.value().as_bytes();
+    let source = sessions_tree(from).get(id).ok().flatten();
+    if source == sessions_tree(to).get(id).ok().flatten() {
+        return;
+    }
+    let _ = match source {
+        Some(data) => sessions_tree(to).insert(id, data),
+        None => sessions_tree(to).remove(id),
+    };
+}
+
 // ============================================================================
 // CSRF Tokens
 // ============================================================================
 
-fn csrf_tree(db: &sled::Db) -> sled::Tree {
+fn csrf_tree(db: &crate::store::Db) -> crate::store::Tree {
     db.open_tree("csrf_tokens")
         .expect("Failed to open csrf_tokens tree")
 }
 
 /// Create a one-time CSRF token stored in sled with a 10-minute TTL.
-pub fn create_csrf_token(db: &sled::Db) -> String {
+pub fn create_csrf_token(db: &crate::store::Db) -> String {
     let mut token_bytes = [0u8; 32];
     OsRng.fill(&mut token_bytes);
     let token = hex_encode(&token_bytes);
 
     let expires = Utc::now().timestamp() + CSRF_TTL_SECS;
     let tree = csrf_tree(db);
-    let _ = tree.insert(token.as_bytes(), &expires.to_be_bytes());
+    let _ = tree.insert(token.as_bytes(), expires.to_be_bytes());
 
     token
 }
 
 /// Verify and consume a CSRF token (one-time use).
-pub fn verify_and_consume_csrf_token(token: &str, db: &sled::Db) -> bool {
+pub fn verify_and_consume_csrf_token(token: &str, db: &crate::store::Db) -> bool {
     let tree = csrf_tree(db);
     match tree.remove(token.as_bytes()) {
         Ok(Some(data)) => {
             if data.len() == 8 {
-                let expires = i64::from_be_bytes(data.as_ref().try_into().unwrap());
+                let expires = i64::from_be_bytes(data.as_slice().try_into().unwrap());
                 return Utc::now().timestamp() < expires;
             }
             false
@@ -185,22 +276,20 @@ pub fn verify_and_consume_csrf_token(token: &str, db: &sled::Db) -> bool {
 
 /// Purge expired sessions and CSRF tokens from sled.
 /// Called at startup.
-pub fn purge_expired_sessions(db: &sled::Db) {
+pub fn purge_expired_sessions(db: &crate::store::Db) {
     let now = Utc::now().timestamp();
 
     // Purge expired sessions
     let tree = sessions_tree(db);
     let mut to_remove = Vec::new();
-    for entry in tree.iter() {
-        if let Ok((key, value)) = entry {
-            if let Ok(session) = serde_json::from_slice::<SessionData>(&value) {
-                if now >= session.expires {
-                    to_remove.push(key);
-                }
-            } else {
-                // Corrupt entry — remove it
+    for (key, value) in tree.iter().flatten() {
+        if let Ok(session) = serde_json::from_slice::<SessionData>(&value) {
+            if now >= session.expires {
                 to_remove.push(key);
             }
+        } else {
+            // Corrupt entry — remove it
+            to_remove.push(key);
         }
     }
     for key in to_remove {
@@ -210,16 +299,14 @@ pub fn purge_expired_sessions(db: &sled::Db) {
     // Purge expired CSRF tokens
     let csrf = csrf_tree(db);
     let mut to_remove = Vec::new();
-    for entry in csrf.iter() {
-        if let Ok((key, value)) = entry {
-            if value.len() == 8 {
-                let expires = i64::from_be_bytes(value.as_ref().try_into().unwrap());
-                if now >= expires {
-                    to_remove.push(key);
-                }
-            } else {
+    for (key, value) in csrf.iter().flatten() {
+        if value.len() == 8 {
+            let expires = i64::from_be_bytes(value.as_slice().try_into().unwrap());
+            if now >= expires {
                 to_remove.push(key);
             }
+        } else {
+            to_remove.push(key);
         }
     }
     for key in to_remove {
@@ -235,3 +322,42 @@ pub fn purge_expired_sessions(db: &sled::Db) {
 fn hex_encode(bytes: &[u8]) -> String {
     bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware::from_fn, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_read_only_guard() {
+        let dir = crate::test_util::TempDir::new("auth");
+        let state = Arc::new(crate::AppState::new(crate::config::Config {
+            notes_dir: dir.join("content"),
+            pdfs_dir: dir.join("pdfs"),
+            db_path: dir.join("db"),
+            read_only: true,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/page", get(|| async { is_read_only().to_string() }).post(|| async { "saved" }))
+            .route("/login", get(|| async { "login" }).route_layer(from_fn(mutating)))
+            .layer(axum::middleware::from_fn_with_state(state, read_only_guard));
+        let status = |method: Method, path: &'static str| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder().method(method).uri(path).body(Body::empty()).unwrap();
+                app.oneshot(request).await.unwrap().status()
+            }
+        };
+
+        assert_eq!(status(Method::GET, "/page").await, StatusCode::OK);
+        assert_eq!(status(Method::POST, "/page").await, StatusCode::FORBIDDEN);
+        assert_eq!(status(Method::GET, "/login").await, StatusCode::FORBIDDEN);
+        // Outside a request nothing is read-only
+        assert!(!is_read_only());
+        let request = Request::builder().uri("/page").body(Body::empty()).unwrap();
+        let body = app.oneshot(request).await.unwrap().into_body();
+        assert_eq!(axum::body::to_bytes(body, 16).await.unwrap(), "true");
+    }
+}
@@ -0,0 +1,324 @@
+//! Author pages.
+//!
+//! Paper authors come from the canonical BibTeX entry (`author`, or
+//! `editor` for edited volumes), else the legacy `authors:` field. Names are
+//! normalized (`Last, First` → `First Last`, TeX braces and accents
+//! dropped) and keyed by a slug with diacritics folded, so `Schölz, B.`
+//! and `Sch{\"o}lz, B.` are one author. `/authors` lists everyone;
+//! `/author/{slug}` shows their papers in the corpus, the time logged on
+//! them and their co-authors. Hidden papers and times are only shown to a
+//! logged-in user.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::{html_escape, parse_frontmatter};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// An author and their papers.
+#[derive(Debug)]
+pub struct Author<'a> {
+    pub slug: String,
+    /// The first spelling seen.
+    pub name: String,
+    pub papers: Vec<&'a Note>,
+    /// Minutes logged on their papers.
+    pub minutes: u32,
+    /// Co-author slug → number of shared papers.
+    pub coauthors: HashMap<String, usize>,
+}
+
+/// A display name from a BibTeX or frontmatter author: `Last, First` or
+/// `Last, Jr, First` reordered, TeX braces and accent commands dropped,
+/// whitespace collapsed. None for `others` and empty names.
+pub fn normalize_author(name: &str) -> Option<String> {
+    let mut plain = String::new();
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            // `\"o`, `\'e`, `\c{c}`: keep the letter
+            '\\' => {
+                while chars.peek().is_some_and(|c| !c.is_alphabetic() && *c != '{') {
+                    chars.next();
+                }
+                if chars.peek().is_some_and(|c| c.is_ascii_lowercase())
+                    && chars.clone().nth(1).is_some_and(|c| c == '{' || c == ' ')
+                {
+                    chars.next();
+                }
+            }
+            '~' => plain.push(' '),
+            c => plain.push(c),
+        }
+    }
+    let parts: Vec<&str> = plain.split(',').map(str::trim).collect();
+    let ordered = match parts.as_slice() {
+        [last, first] => format!("{} {}", first, last),
+        [last, jr, first] => format!("{} {} {}", first, last, jr),
+        _ => plain,
+    };
+    let name = ordered.split_whitespace().collect::<Vec<_>>().join(" ");
+    match name.to_lowercase().as_str() {
+        "" | "others" | "et al." | "et al" => None,
+        _ => Some(name),
+    }
+}
+
+/// URL slug of a normalized name: lowercase ASCII, diacritics folded,
+/// everything else a single `-`.
+pub fn author_slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.to_lowercase().chars() {
+        let folded = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+            'ç' | 'ć' | 'č' => "c",
+            'ď' | 'đ' => "d",
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+            'ğ' => "g",
+            'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+            'ł' => "l",
+            'ñ' | 'ń' | 'ň' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+            'ř' => "r",
+            'ś' | 'š' | 'ş' => "s",
+            'ß' => "ss",
+            'ť' | 'ţ' => "t",
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+            'ý' | 'ÿ' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            c if c.is_ascii_alphanumeric() => {
+                slug.push(c);
+                continue;
+            }
+            _ => "-",
+        };
+        slug.push_str(folded);
+    }
+    slug.split('-').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("-")
+}
+
+/// A paper's normalized authors, in order, without repeats.
+pub fn paper_authors(note: &Note) -> Vec<String> {
+    let NoteType::Paper(ref paper) = note.note_type else {
+        return Vec::new();
+    };
+    let names = match paper.effective_metadata(&note.title).authors {
+        Some(authors) => authors.split(" and ").map(str::to_string).collect(),
+        None => parse_frontmatter(&note.full_file_content).0.authors,
+    };
+    let mut seen = Vec::new();
+    let mut authors = Vec::new();
+    for name in names.iter().filter_map(|n| normalize_author(n)) {
+        let slug = author_slug(&name);
+        if !slug.is_empty() && !seen.contains(&slug) {
+            seen.push(slug);
+            authors.push(name);
+        }
+    }
+    authors
+}
+
+/// Every author of a paper in `notes`, by slug.
+pub fn author_index(notes: &[Note], include_hidden: bool) -> BTreeMap<String, Author<'_>> {
+    let mut index: BTreeMap<String, Author> = BTreeMap::new();
+    for note in notes.iter().filter(|n| include_hidden || !n.hidden) {
+        let authors = paper_authors(note);
+        let slugs: Vec<String> = authors.iter().map(|a| author_slug(a)).collect();
+        let minutes: u32 = note.time_entries.iter().map(|e| e.minutes).sum();
+        for (name, slug) in authors.iter().zip(&slugs) {
+            let author = index.entry(slug.clone()).or_insert_with(|| Author {
+                slug: slug.clone(),
+                name: name.clone(),
+                papers: Vec::new(),
+                minutes: 0,
+                coauthors: HashMap::new(),
+            });
+            author.papers.push(note);
+            author.minutes += minutes;
+            for other in slugs.iter().filter(|s| *s != slug) {
+                *author.coauthors.entry(other.clone()).or_default() += 1;
+            }
+        }
+    }
+    index
+}
+
+/// A paper's author names linked to their pages, for the meta block.
+pub fn author_links(note: &Note) -> String {
+    paper_authors(note)
+        .iter()
+        .map(|name| format!(r#"<a href="/author/{}">{}</a>"#, author_slug(name), html_escape(name)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn hours_minutes(minutes: u32) -> String {
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /authors`: everyone with a paper in the corpus, most papers first.
+pub async fn authors_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let notes = state.load_notes();
+    let index = author_index(&notes, logged_in);
+    let mut authors: Vec<&Author> = index.values().collect();
+    authors.sort_by(|a, b| b.papers.len().cmp(&a.papers.len()).then_with(|| a.name.cmp(&b.name)));
+
+    let mut html = String::from("<h1>Authors</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} authors of papers in the notes</p>"#,
+        authors.len()
+    ));
+    if authors.is_empty() {
+        html.push_str("<p>No paper has authors in its BibTeX yet.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>Author</th><th>Papers</th>"#);
+        if logged_in {
+            html.push_str("<th>Time</th>");
+        }
+        html.push_str("</tr>");
+        for author in authors {
+            html.push_str(&format!(
+                r#"<tr><td><a href="/author/{}">{}</a></td><td>{}</td>"#,
+                author.slug,
+                html_escape(&author.name),
+                author.papers.len()
+            ));
+            if logged_in {
+                html.push_str(&format!("<td>{}</td>", hours_minutes(author.minutes)));
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</table>");
+    }
+
+    Html(base_html("Authors", &html, None, logged_in)).into_response()
+}
+
+/// `GET /author/{slug}`: an author's papers and co-authors.
+pub async fn author_page(Path(slug): Path<String>, State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let notes = state.load_notes();
+    let index = author_index(&notes, logged_in);
+    let Some(author) = index.get(&slug) else {
+        return (StatusCode::NOT_FOUND, "Author not found").into_response();
+    };
+
+    let mut html = format!(
+        r#"<h1>{}</h1><p class="time-breadcrumb"><a href="/authors">Authors</a> &middot; {} {}"#,
+        html_escape(&author.name),
+        author.papers.len(),
+        if author.papers.len() == 1 { "paper" } else { "papers" }
+    );
+    if logged_in {
+        html.push_str(&format!(" &middot; {} logged", hours_minutes(author.minutes)));
+    }
+    html.push_str("</p>");
+
+    let mut papers = author.papers.clone();
+    papers.sort_by_key(|n| {
+        let NoteType::Paper(ref paper) = n.note_type else { unreachable!() };
+        (std::cmp::Reverse(paper.effective_metadata(&n.title).year), n.title.to_lowercase())
+    });
+    html.push_str("<h2>Papers</h2><ul>");
+    for note in papers {
+        let NoteType::Paper(ref paper) = note.note_type else { continue };
+        let meta = paper.effective_metadata(&note.title);
+        let details: Vec<String> = meta.venue.iter().cloned().chain(meta.year.map(|y| y.to_string())).collect();
+        html.push_str(&format!(r#"<li><a href="/note/{}">{}</a>"#, note.key, html_escape(&note.title)));
+        if !details.is_empty() {
+            html.push_str(&format!(" <small>{}</small>", html_escape(&details.join(", "))));
+        }
+        let minutes: u32 = note.time_entries.iter().map(|e| e.minutes).sum();
+        if logged_in && minutes > 0 {
+            html.push_str(&format!(" <small>&middot; {}</small>", hours_minutes(minutes)));
+        }
+        html.push_str("</li>");
+    }
+    html.push_str("</ul>");
+
+    let mut coauthors: Vec<(&Author, usize)> = author
+        .coauthors
+        .iter()
+        .filter_map(|(slug, &count)| Some((index.get(slug)?, count)))
+        .collect();
+    coauthors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.name.cmp(&b.0.name)));
+    if !coauthors.is_empty() {
+        html.push_str("<h2>Co-authors</h2><ul>");
+        for (coauthor, count) in coauthors {
+            html.push_str(&format!(
+                r#"<li><a href="/author/{}">{}</a> <small>{} shared</small></li>"#,
+                coauthor.slug,
+                html_escape(&coauthor.name),
+                count
+            ));
+        }
+        html.push_str("</ul>");
+    }
+
+    Html(base_html(&author.name, &html, None, logged_in)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::note_from_content;
+    use chrono::{NaiveDate, Utc};
+
+    #[test]
+    fn test_author_index() {
+        assert_eq!(normalize_author("Sch{\\\"o}lz, Bernhard").as_deref(), Some("Bernhard Scholz"));
+        assert_eq!(normalize_author("Steele, Jr., Guy L.").as_deref(), Some("Guy L. Steele Jr."));
+        assert_eq!(normalize_author(" others "), None);
+        assert_eq!(author_slug("Bernhard Schölz"), "bernhard-scholz");
+        assert_eq!(author_slug("Guy L. Steele Jr."), "guy-l-steele-jr");
+
+        let paper = |path: &str, frontmatter: &str| {
+            note_from_content(path.into(), format!("---\ntitle: {}\ntype: paper\n{}---\n", path, frontmatter), Utc::now())
+        };
+        let mut souffle = paper(
+            "souffle.md",
+            "bibtex: |\n  @inproceedings{jordan16,\n    author = {Jordan, Herbert and Sch{\\\"o}lz, Bernhard and Suboti{\\'c}, Pavle},\n    year = {2016}\n  }\n",
+        );
+        souffle.time_entries.push(crate::models::TimeEntry {
+            date: NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(),
+            minutes: 90,
+            category: crate::models::TimeCategory::Other("reading".into()),
+            description: None,
+        });
+        let notes = vec![
+            souffle,
+            paper("legacy.md", "authors:\n  - Bernhard Schölz\n  - Jane Doe\n"),
+            note_from_content("idea.md".into(), "---\ntitle: Idea\nauthors: [Jane Doe]\n---\n".into(), Utc::now()),
+            paper("secret.md", "hidden: true\nauthors: Jane Doe\n"),
+        ];
+
+        let index = author_index(&notes, false);
+        let slugs: Vec<&str> = index.keys().map(String::as_str).collect();
+        assert_eq!(slugs, ["bernhard-scholz", "herbert-jordan", "jane-doe", "pavle-subotic"]);
+        let scholz = &index["bernhard-scholz"];
+        assert_eq!(scholz.name, "Bernhard Schölz");
+        assert_eq!(scholz.papers.len(), 2);
+        assert_eq!(scholz.minutes, 90);
+        assert_eq!(scholz.coauthors.get("jane-doe"), Some(&1));
+        assert_eq!(index["jane-doe"].papers.len(), 1);
+        assert_eq!(author_index(&notes, true)["jane-doe"].papers.len(), 2);
+
+        assert!(author_links(&notes[0]).starts_with(r#"<a href="/author/herbert-jordan">Herbert Jordan</a>, "#));
+    }
+}
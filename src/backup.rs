@@ -0,0 +1,787 @@
+//! Full backup and restore.
+//!
+//! A backup is a gzipped tarball with a fixed layout:
+//!
+//! ```text
+//! manifest.json   format version, creation time, file counts
+//! db.jsonl        every database tree, one base64 `{tree, key, value}` per line
+//! content/...     the notes directory (including its git history)
+//! pdfs/...        the PDF directory
+//! ```
+//!
+//! `GET /api/backup` streams one while the server keeps running, and with
+//! `[backup] schedule` the server writes one on a cron schedule to a
+//! directory or the `[storage]` bucket, keeping the newest `keep`; the
+//! outcome shows on `/healthz`. `notes restore <archive>` validates and
+//! unpacks one with the server stopped (a sled database is locked while
+//! the server has it open).
+//!
+//! Scheduled archives are written as `<name>.partial` and renamed once
+//! complete, and older ones beyond `keep` are deleted.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::auth::is_logged_in;
+use crate::AppState;
+
+/// Bumped when the archive layout changes incompatibly.
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+const MANIFEST: &str = "manifest.json";
+const DB_EXPORT: &str = "db.jsonl";
+const CONTENT_DIR: &str = "content";
+const PDFS_DIR: &str = "pdfs";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    pub app_version: String,
+    pub content_files: usize,
+    pub pdf_files: usize,
+    pub db_records: usize,
+}
+
+/// One sled key/value pair, base64 encoded.
+#[derive(Serialize, Deserialize)]
+struct DbRecord {
+    tree: String,
+    key: String,
+    value: String,
+}
+
+/// Where a restore unpacks to.
+pub struct RestoreTarget<'a> {
+    pub notes_dir: &'a Path,
+    pub pdfs_dir: &'a Path,
+    pub db_path: &'a Path,
+    pub db_backend: crate::config::DbBackend,
+}
+
+// ============================================================================
+// Backup
+// ============================================================================
+
+fn count_files(dir: &Path) -> usize {
+    WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .count()
+}
+
+/// Serialize every sled tree as JSON lines.
+fn export_db(db: &crate::store::Db) -> Result<(Vec<u8>, usize), String> {
+    let mut out = Vec::new();
+    let mut records = 0;
+    for name in db.tree_names() {
+        let tree = db.open_tree(&name).map_err(|e| e.to_string())?;
+        let tree_name = BASE64.encode(&name);
+        for item in tree.iter() {
+            let (key, value) = item.map_err(|e| e.to_string())?;
+            let record = DbRecord {
+                tree: tree_name.clone(),
+                key: BASE64.encode(&key),
+                value: BASE64.encode(&value),
+            };
+            serde_json::to_writer(&mut out, &record).map_err(|e| e.to_string())?;
+            out.push(b'\n');
+            records += 1;
+        }
+    }
+    Ok((out, records))
+}
+
+fn append_bytes<W: Write>(tar: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    header.set_cksum();
+    tar.append_data(&mut header, name, data)
+        .map_err(|e| format!("Failed to write {}: {}", name, e))
+}
+
+/// Write a complete backup archive to `writer`.
+pub fn write_backup<W: Write>(
+    writer: W,
+    notes_dir: &Path,
+    pdfs_dir: &Path,
+    db: &crate::store::Db,
+) -> Result<BackupManifest, String> {
+    db.flush().map_err(|e| format!("Failed to flush database: {}", e))?;
+    let (db_export, db_records) = export_db(db)?;
+    let manifest = BackupManifest {
+        version: BACKUP_FORMAT_VERSION,
+        created: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        content_files: count_files(notes_dir),
+        pdf_files: count_files(pdfs_dir),
+        db_records,
+    };
+
+    let gz = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+    let mut tar = tar::Builder::new(gz);
+    tar.follow_symlinks(false);
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    append_bytes(&mut tar, MANIFEST, &manifest_json)?;
+    append_bytes(&mut tar, DB_EXPORT, &db_export)?;
+    for (name, dir) in [(CONTENT_DIR, notes_dir), (PDFS_DIR, pdfs_dir)] {
+        if dir.is_dir() {
+            tar.append_dir_all(name, dir)
+                .map_err(|e| format!("Failed to archive {}: {}", dir.display(), e))?;
+        }
+    }
+
+    tar.into_inner()
+        .and_then(|gz| gz.finish())
+        .and_then(|mut w| w.flush())
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+    Ok(manifest)
+}
+
+// ============================================================================
+// Restore
+// ============================================================================
+
+/// Validate an archive entry path, returning its top-level section and the
+/// path below it. Rejects absolute paths, `..` and unknown sections.
+fn entry_section(path: &Path) -> Result<(&'static str, PathBuf), String> {
+    let mut components = path.components().filter(|c| !matches!(c, Component::CurDir));
+    let first = match components.next() {
+        Some(Component::Normal(first)) => first.to_string_lossy().to_string(),
+        _ => return Err(format!("Invalid path in archive: {}", path.display())),
+    };
+    let rest: Vec<Component> = components.collect();
+    if !rest.iter().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("Invalid path in archive: {}", path.display()));
+    }
+    let rest: PathBuf = rest.into_iter().collect();
+    let section = match first.as_str() {
+        MANIFEST if rest.as_os_str().is_empty() => MANIFEST,
+        DB_EXPORT if rest.as_os_str().is_empty() => DB_EXPORT,
+        CONTENT_DIR => CONTENT_DIR,
+        PDFS_DIR => PDFS_DIR,
+        _ => return Err(format!("Unexpected entry in archive: {}", path.display())),
+    };
+    Ok((section, rest))
+}
+
+fn staging_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn is_non_empty(path: &Path) -> bool {
+    match fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => path.exists(),
+    }
+}
+
+/// Unpack the archive into staging directories and return the manifest and
+/// the raw db export.
+fn unpack(
+    archive: impl Read,
+    content_stage: &Path,
+    pdfs_stage: &Path,
+) -> Result<(BackupManifest, Vec<u8>), String> {
+    fs::create_dir_all(content_stage).map_err(|e| e.to_string())?;
+    fs::create_dir_all(pdfs_stage).map_err(|e| e.to_string())?;
+
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+    let mut manifest = None;
+    let mut db_export = None;
+    let entries = tar.entries().map_err(|e| format!("Not a backup archive: {}", e))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|e| format!("Corrupt archive: {}", e))?;
+        let path = entry.path().map_err(|e| e.to_string())?.to_path_buf();
+        let (section, rest) = entry_section(&path)?;
+        let kind = entry.header().entry_type();
+        if !(kind.is_file() || kind.is_dir()) {
+            return Err(format!("Unsupported entry type in archive: {}", path.display()));
+        }
+
+        match section {
+            MANIFEST | DB_EXPORT => {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).map_err(|e| e.to_string())?;
+                if section == MANIFEST {
+                    let parsed: BackupManifest = serde_json::from_slice(&data)
+                        .map_err(|e| format!("Invalid manifest: {}", e))?;
+                    manifest = Some(parsed);
+                } else {
+                    db_export = Some(data);
+                }
+            }
+            _ => {
+                let stage = if section == CONTENT_DIR { content_stage } else { pdfs_stage };
+                let dest = stage.join(&rest);
+                if kind.is_dir() {
+                    fs::create_dir_all(&dest).map_err(|e| e.to_string())?;
+                } else {
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                    entry
+                        .unpack(&dest)
+                        .map_err(|e| format!("Failed to unpack {}: {}", path.display(), e))?;
+                }
+            }
+        }
+    }
+
+    let manifest = manifest.ok_or("Archive has no manifest.json")?;
+    if manifest.version > BACKUP_FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than this build supports ({})",
+            manifest.version, BACKUP_FORMAT_VERSION
+        ));
+    }
+    let db_export = db_export.ok_or("Archive has no db.jsonl")?;
+    Ok((manifest, db_export))
+}
+
+fn import_db(backend: crate::config::DbBackend, db_path: &Path, export: &[u8]) -> Result<usize, String> {
+    let db = crate::store::open(backend, db_path).map_err(|e| format!("Failed to open {}: {}", db_path.display(), e))?;
+    let mut records = 0;
+    for (i, line) in BufReader::new(export).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: DbRecord = serde_json::from_str(&line)
+            .map_err(|e| format!("db.jsonl line {}: {}", i + 1, e))?;
+        let decode = |field: &str| {
+            BASE64
+                .decode(field)
+                .map_err(|e| format!("db.jsonl line {}: {}", i + 1, e))
+        };
+        let tree = db.open_tree(decode(&record.tree)?).map_err(|e| e.to_string())?;
+        tree.insert(decode(&record.key)?, decode(&record.value)?)
+            .map_err(|e| e.to_string())?;
+        records += 1;
+    }
+    db.flush().map_err(|e| e.to_string())?;
+    Ok(records)
+}
+
+/// Validate and unpack a backup into `target`. Existing data is refused
+/// unless `force`, in which case it is moved aside to
+/// `<path>.pre-restore-<timestamp>` rather than deleted.
+pub fn restore_backup(
+    archive: impl Read,
+    target: &RestoreTarget,
+    force: bool,
+) -> Result<BackupManifest, String> {
+    let existing: Vec<&Path> = [target.notes_dir, target.pdfs_dir, target.db_path]
+        .into_iter()
+        .filter(|p| is_non_empty(p))
+        .collect();
+    if !existing.is_empty() && !force {
+        let paths: Vec<String> = existing.iter().map(|p| p.display().to_string()).collect();
+        return Err(format!(
+            "Refusing to overwrite existing data ({}); pass --force to move it aside",
+            paths.join(", ")
+        ));
+    }
+    if target.db_path.exists() {
+        // Fails fast if a running server holds the lock.
+        crate::store::open(target.db_backend, target.db_path)
+            .map_err(|e| format!("Cannot open {} (is the server running?): {}", target.db_path.display(), e))?;
+    }
+
+    let content_stage = staging_path(target.notes_dir, ".restore-tmp");
+    let pdfs_stage = staging_path(target.pdfs_dir, ".restore-tmp");
+    let unpacked = unpack(archive, &content_stage, &pdfs_stage);
+    let (manifest, db_export) = match unpacked {
+        Ok(result) => result,
+        Err(e) => {
+            fs::remove_dir_all(&content_stage).ok();
+            fs::remove_dir_all(&pdfs_stage).ok();
+            return Err(e);
+        }
+    };
+
+    let suffix = format!(".pre-restore-{}", Utc::now().format("%Y%m%d%H%M%S"));
+    for path in &existing {
+        let aside = staging_path(path, &suffix);
+        fs::rename(path, &aside)
+            .map_err(|e| format!("Failed to move {} aside: {}", path.display(), e))?;
+        tracing::info!(from = %path.display(), to = %aside.display(), "Moved existing data aside");
+    }
+    for (stage, dest) in [(&content_stage, target.notes_dir), (&pdfs_stage, target.pdfs_dir)] {
+        if dest.exists() {
+            // An empty directory left in place.
+            fs::remove_dir(dest).map_err(|e| format!("Failed to replace {}: {}", dest.display(), e))?;
+        }
+        fs::rename(stage, dest).map_err(|e| format!("Failed to restore {}: {}", dest.display(), e))?;
+    }
+    if target.db_path.exists() {
+        fs::remove_dir_all(target.db_path).ok();
+    }
+    import_db(target.db_backend, target.db_path, &db_export)?;
+
+    Ok(manifest)
+}
+
+// ============================================================================
+// Scheduled Backups
+// ============================================================================
+
+const STATUS_TREE: &str = "backups";
+const ARCHIVE_PREFIX: &str = "notes-backup-";
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+/// Directory under the `[storage]` root that scheduled archives go in.
+const STORAGE_DIR: &str = "backups";
+
+/// A cron schedule: bit `n` of each field is set when value `n` matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day and weekday were both given; either one matching is enough.
+    either_day: bool,
+}
+
+fn parse_field(text: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|s| *s > 0);
+                (range, step.ok_or_else(|| format!("bad step in '{}'", part))?)
+            }
+            None => (part, 1),
+        };
+        let number = |n: &str| {
+            n.parse::<u32>()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .ok_or_else(|| format!("'{}' is not in {}-{}", n, min, max))
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((a, b)) => (number(a)?, number(b)?),
+            None if part.contains('/') => (number(range)?, max),
+            None => {
+                let n = number(range)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            return Err(format!("empty range '{}'", range));
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Ok(bits)
+}
+
+impl Schedule {
+    /// Parse `minute hour day month weekday` (weekday 0 or 7 is Sunday),
+    /// each `*`, `n`, `a-b`, `*/step` or `a-b/step`, or a comma list.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = match text.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("schedule '{}' needs five fields: minute hour day month weekday", text));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Schedule {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            either_day: day != "*" && weekday != "*",
+        })
+    }
+
+    /// Whether the schedule fires in the minute of `at`.
+    pub fn matches(&self, at: NaiveDateTime) -> bool {
+        let bit = |field: u64, n: u32| field & (1 << n) != 0;
+        let day = bit(self.days, at.day());
+        let weekday = bit(self.weekdays, at.weekday().num_days_from_sunday());
+        let day_matches = if self.either_day { day || weekday } else { day && weekday };
+        bit(self.minutes, at.minute()) && bit(self.hours, at.hour()) && bit(self.months, at.month()) && day_matches
+    }
+}
+
+/// Outcome of the scheduled backups so far, kept in the `backups` tree.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackupStatus {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_archive: Option<String>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+impl BackupStatus {
+    /// Whether the most recent attempt failed.
+    pub fn failing(&self) -> bool {
+        self.last_failure.is_some_and(|failed| self.last_success.is_none_or(|ok| failed > ok))
+    }
+
+    /// The `backup` object on `/healthz`: whether it's working and when it
+    /// last ran for everyone, the archive name and error only with `detailed`.
+    pub fn health(&self, detailed: bool) -> serde_json::Value {
+        let mut health = serde_json::json!({
+            "ok": !self.failing(),
+            "last_success": self.last_success,
+            "last_failure": self.last_failure,
+        });
+        if detailed {
+            health["last_archive"] = serde_json::json!(self.last_archive);
+            health["last_error"] = serde_json::json!(self.last_error);
+        }
+        health
+    }
+}
+
+pub fn load_status(db: &crate::store::Db) -> BackupStatus {
+    db.open_tree(STATUS_TREE)
+        .ok()
+        .and_then(|tree| tree.get("status").ok().flatten())
+        .and_then(|value| serde_json::from_slice(&value).ok())
+        .unwrap_or_default()
+}
+
+fn save_status(db: &crate::store::Db, status: &BackupStatus) {
+    let saved = db
+        .open_tree(STATUS_TREE)
+        .map_err(|e| e.to_string())
+        .and_then(|tree| {
+            let json = serde_json::to_vec(status).map_err(|e| e.to_string())?;
+            tree.insert("status", json).map_err(|e| e.to_string())
+        });
+    if let Err(e) = saved {
+        tracing::warn!(error = %e, "Failed to record backup status");
+    }
+}
+
+/// Archives among `names` (paths ending in the file name) beyond the
+/// newest `keep`, oldest first.
+fn expired(names: &[String], keep: usize) -> Vec<String> {
+    let mut archives: Vec<&String> = names
+        .iter()
+        .filter(|name| {
+            let file = name.rsplit('/').next().unwrap_or(name);
+            file.starts_with(ARCHIVE_PREFIX) && file.ends_with(ARCHIVE_SUFFIX)
+        })
+        .collect();
+    // The timestamp in the name sorts chronologically
+    archives.sort();
+    let excess = archives.len().saturating_sub(keep);
+    archives.into_iter().take(excess).cloned().collect()
+}
+
+/// Write one archive to `[backup] dir` or the bucket and delete the ones
+/// beyond `keep`. Returns where the archive went.
+pub async fn run_backup(state: &AppState) -> Result<String, String> {
+    let config = &state.config.backup;
+    let name = format!("{}{}{}", ARCHIVE_PREFIX, Utc::now().format("%Y%m%d-%H%M%S"), ARCHIVE_SUFFIX);
+    let partial = if config.s3 {
+        std::env::temp_dir().join(format!("{:08x}-{}", rand::random::<u32>(), name))
+    } else {
+        fs::create_dir_all(&config.dir).map_err(|e| format!("{}: {}", config.dir.display(), e))?;
+        config.dir.join(format!("{}.partial", name))
+    };
+
+    let (notes_dir, pdfs_dir, db, path) =
+        (state.notes_dir.clone(), state.pdfs_dir.clone(), state.db.clone(), partial.clone());
+    let written = tokio::task::spawn_blocking(move || {
+        let file = fs::File::create(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut writer = std::io::BufWriter::new(file);
+        write_backup(&mut writer, &notes_dir, &pdfs_dir, &db)?;
+        let file = writer.into_inner().map_err(|e| e.to_string())?;
+        file.sync_all().map_err(|e| format!("{}: {}", path.display(), e))
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+    if let Err(e) = written {
+        fs::remove_file(&partial).ok();
+        return Err(e);
+    }
+
+    if config.s3 {
+        let object = format!("{}/{}", STORAGE_DIR, name);
+        state.storage.put_file(&object, &partial).await?;
+        for old in expired(&state.storage.list(STORAGE_DIR).await?, config.keep) {
+            if let Err(e) = state.storage.delete(&old).await {
+                tracing::warn!(archive = %old, error = %e, "Failed to delete old backup");
+            }
+        }
+        Ok(object)
+    } else {
+        let path = config.dir.join(&name);
+        fs::rename(&partial, &path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let names: Vec<String> = fs::read_dir(&config.dir)
+            .map_err(|e| format!("{}: {}", config.dir.display(), e))?
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .collect();
+        for old in expired(&names, config.keep) {
+            if let Err(e) = fs::remove_file(config.dir.join(&old)) {
+                tracing::warn!(archive = %old, error = %e, "Failed to delete old backup");
+            }
+        }
+        Ok(path.display().to_string())
+    }
+}
+
+/// Check every minute whether `[backup] schedule` fires. Spawned per
+/// vault by the server when a schedule is set.
+pub async fn run_scheduled(state: Arc<AppState>) {
+    let Some(schedule) = state.config.backup.schedule.as_deref().and_then(|s| Schedule::parse(s).ok()) else {
+        return;
+    };
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+    let mut last_minute = None;
+    loop {
+        interval.tick().await;
+        let now = crate::timezone::now().naive_local();
+        let minute = now.with_second(0);
+        if !schedule.matches(now) || minute == last_minute {
+            continue;
+        }
+        last_minute = minute;
+
+        let mut status = load_status(&state.db);
+        match run_backup(&state).await {
+            Ok(archive) => {
+                tracing::info!(archive = %archive, "Scheduled backup written");
+                status.last_success = Some(Utc::now());
+                status.last_archive = Some(archive);
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Scheduled backup failed");
+                status.last_failure = Some(Utc::now());
+                status.last_error = Some(e);
+            }
+        }
+        save_status(&state.db, &status);
+    }
+}
+
+// ============================================================================
+// HTTP Handler
+// ============================================================================
+
+/// `Write` adapter that forwards chunks to a response body stream.
+struct ChannelWriter(tokio::sync::mpsc::Sender<Result<Bytes, std::io::Error>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub async fn backup(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(16);
+    let notes_dir = state.notes_dir.clone();
+    let pdfs_dir = state.pdfs_dir.clone();
+    let db = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let writer = std::io::BufWriter::with_capacity(64 * 1024, ChannelWriter(tx.clone()));
+        match write_backup(writer, &notes_dir, &pdfs_dir, &db) {
+            Ok(manifest) => tracing::info!(
+                content_files = manifest.content_files,
+                pdf_files = manifest.pdf_files,
+                db_records = manifest.db_records,
+                "Backup streamed"
+            ),
+            Err(e) => {
+                tracing::error!(error = %e, "Backup failed");
+                // Abort the response so the client doesn't keep a truncated
+                // archive that looks complete.
+                let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+            }
+        }
+    });
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+    let filename = format!("notes-backup-{}.tar.gz", Utc::now().format("%Y%m%d-%H%M%S"));
+    (
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn scratch_dir(name: &str) -> TempDir {
+        TempDir::new(&format!("backup-{}", name))
+    }
+
+    #[test]
+    fn test_schedule() {
+        let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let nightly = Schedule::parse("30 3 * * *").unwrap();
+        assert!(nightly.matches(at("2026-10-17 03:30")));
+        assert!(!nightly.matches(at("2026-10-17 04:30")));
+        assert_eq!(Schedule::parse("@daily").unwrap(), Schedule::parse("0 0 * * *").unwrap());
+
+        // 2026-10-18 is a Sunday; day and weekday both given match either
+        let sundays = Schedule::parse("0 */6 1,15 * 7").unwrap();
+        assert!(sundays.matches(at("2026-10-18 12:00")));
+        assert!(sundays.matches(at("2026-10-15 18:00")));
+        assert!(!sundays.matches(at("2026-10-17 12:00")));
+        assert!(!sundays.matches(at("2026-10-18 13:00")));
+        assert!(Schedule::parse("0 3 * *").is_err());
+        assert!(Schedule::parse("60 3 * * *").is_err());
+        assert!(Schedule::parse("0 5-3 * * *").is_err());
+
+        let names: Vec<String> = [
+            "notes-backup-20260103-000000.tar.gz",
+            "notes-backup-20260101-000000.tar.gz",
+            "other.txt",
+            "notes-backup-20260102-000000.tar.gz",
+        ]
+        .iter()
+        .map(|n| format!("backups/{}", n))
+        .collect();
+        assert_eq!(expired(&names, 2), vec!["backups/notes-backup-20260101-000000.tar.gz"]);
+        assert!(expired(&names, 5).is_empty());
+
+        let failed = BackupStatus {
+            last_failure: Some(Utc::now()),
+            last_error: Some("/srv/backups: Permission denied".to_string()),
+            ..Default::default()
+        };
+        assert!(failed.failing());
+        assert_eq!(failed.health(false)["ok"], false);
+        assert!(failed.health(false).get("last_error").is_none());
+        assert_eq!(failed.health(true)["last_error"], "/srv/backups: Permission denied");
+        assert!(!BackupStatus { last_success: Some(Utc::now()), ..failed }.failing());
+    }
+
+    #[test]
+    fn test_backup_roundtrip() {
+        let src = scratch_dir("src");
+        fs::create_dir_all(src.join("content/projects")).unwrap();
+        fs::create_dir_all(src.join("pdfs")).unwrap();
+        fs::write(src.join("content/projects/a.md"), "---\ntitle: A\n---\nhi").unwrap();
+        fs::write(src.join("pdfs/p.pdf"), b"%PDF-1.4").unwrap();
+        let db = crate::store::Db::temporary();
+        db.insert(b"session:x", b"1").unwrap();
+        db.open_tree("graph_nodes").unwrap().insert(b"k", b"v").unwrap();
+
+        let mut archive = Vec::new();
+        let manifest = write_backup(&mut archive, &src.join("content"), &src.join("pdfs"), &db).unwrap();
+        assert_eq!(manifest.content_files, 1);
+        assert_eq!(manifest.pdf_files, 1);
+        assert_eq!(manifest.db_records, 2);
+
+        let dst = scratch_dir("dst");
+        let target = RestoreTarget {
+            notes_dir: &dst.join("content"),
+            pdfs_dir: &dst.join("pdfs"),
+            db_path: &dst.join("db"),
+            db_backend: crate::config::DbBackend::Sled,
+        };
+        restore_backup(archive.as_slice(), &target, false).unwrap();
+        assert_eq!(
+            fs::read_to_string(dst.join("content/projects/a.md")).unwrap(),
+            "---\ntitle: A\n---\nhi"
+        );
+        assert!(dst.join("pdfs/p.pdf").exists());
+        {
+            let restored = crate::store::open(crate::config::DbBackend::Sled, &dst.join("db")).unwrap();
+            assert_eq!(restored.get(b"session:x").unwrap().unwrap(), b"1");
+            let tree = restored.open_tree("graph_nodes").unwrap();
+            assert_eq!(tree.get(b"k").unwrap().unwrap(), b"v");
+        }
+
+        // Existing data is refused without --force and moved aside with it.
+        let err = restore_backup(archive.as_slice(), &target, false).unwrap_err();
+        assert!(err.contains("--force"));
+        restore_backup(archive.as_slice(), &target, true).unwrap();
+        let moved_aside = fs::read_dir(&dst)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains(".pre-restore-"))
+            .count();
+        assert_eq!(moved_aside, 3);
+
+    }
+
+    #[test]
+    fn test_entry_section_rejects_escapes() {
+        assert_eq!(
+            entry_section(Path::new("content/a/b.md")).unwrap(),
+            (CONTENT_DIR, PathBuf::from("a/b.md"))
+        );
+        assert_eq!(entry_section(Path::new("./manifest.json")).unwrap().0, MANIFEST);
+        assert!(entry_section(Path::new("content/../../etc/passwd")).is_err());
+        assert!(entry_section(Path::new("/etc/passwd")).is_err());
+        assert!(entry_section(Path::new("other/file")).is_err());
+        assert!(entry_section(Path::new("manifest.json/x")).is_err());
+    }
+
+    #[test]
+    fn test_restore_rejects_garbage() {
+        let dst = scratch_dir("garbage");
+        let target = RestoreTarget {
+            notes_dir: &dst.join("content"),
+            pdfs_dir: &dst.join("pdfs"),
+            db_path: &dst.join("db"),
+            db_backend: crate::config::DbBackend::Sled,
+        };
+        assert!(restore_backup(&b"not a tarball"[..], &target, false).is_err());
+        assert!(!dst.join("content").exists());
+        assert!(!dst.join("content.restore-tmp").exists());
+    }
+}
@@ -0,0 +1,383 @@
+//! Kanban board.
+//!
+//! `/board?field=status` lays notes out in columns by the value of a
+//! frontmatter field, one card per note; `field=tag` gives a column per
+//! tag. Dragging a card to another column rewrites the field through
+//! `POST /api/board/move`. `items=tasks` shows open tasks instead, in the
+//! columns of the notes they are in; task cards don't move.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::handlers::rewrite_note_file;
+use crate::models::{Note, Task};
+use crate::notes::{frontmatter_field, html_escape, set_frontmatter_field};
+use crate::tasks::extract_tasks;
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Fields the app owns or that hold more than one line; a board can't be
+/// laid out on them.
+const RESERVED_FIELDS: &[&str] = &[
+    "id", "title", "type", "bibtex", "time", "sources", "created", "date", "due", "parent", "prev", "pdf",
+    "hidden", "rate",
+];
+
+/// The field a board is laid out on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BoardField {
+    /// `tags:`; a note is in the column of each of its tags.
+    Tags,
+    Field(String),
+}
+
+impl BoardField {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let name = name.trim().to_lowercase();
+        match name.as_str() {
+            "tag" | "tags" => Ok(BoardField::Tags),
+            _ if RESERVED_FIELDS.contains(&name.as_str()) => Err(format!("Can't make a board of '{}'", name)),
+            _ if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') => {
+                Ok(BoardField::Field(name))
+            }
+            _ => Err(format!("Invalid field '{}'", name)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            BoardField::Tags => "tags",
+            BoardField::Field(name) => name,
+        }
+    }
+
+    /// The note's values of the field; empty when it isn't set.
+    pub fn values(&self, note: &Note) -> Vec<String> {
+        match self {
+            BoardField::Tags => note.tags.clone(),
+            BoardField::Field(name) => frontmatter_field(&note.full_file_content, name).into_iter().collect(),
+        }
+    }
+}
+
+/// A board column: the notes with `value` (None for notes without one).
+#[derive(Debug)]
+pub struct Column<'a> {
+    pub value: Option<String>,
+    pub notes: Vec<&'a Note>,
+}
+
+/// Lay `notes` out by `field`. With `columns`, those columns in that
+/// order, and notes with other values left off; otherwise a column for
+/// every value in use, sorted, after one for notes without a value.
+pub fn board_columns<'a>(notes: &'a [Note], field: &BoardField, columns: Option<&[String]>) -> Vec<Column<'a>> {
+    let mut board: Vec<Column> = match columns {
+        Some(columns) => columns.iter().map(|c| Column { value: Some(c.clone()), notes: vec![] }).collect(),
+        None => {
+            let values: BTreeSet<String> = notes.iter().flat_map(|n| field.values(n)).collect();
+            std::iter::once(None)
+                .chain(values.into_iter().map(Some))
+                .map(|value| Column { value, notes: vec![] })
+                .collect()
+        }
+    };
+    for note in notes {
+        let values = field.values(note);
+        for column in board.iter_mut() {
+            let fits = match column.value {
+                Some(ref v) => values.iter().any(|nv| nv.eq_ignore_ascii_case(v)),
+                None => values.is_empty(),
+            };
+            if fits {
+                column.notes.push(note);
+            }
+        }
+    }
+    board
+}
+
+/// The note's file with its card moved from column `from` to `to` (None
+/// for the no-value column).
+pub fn move_card(
+    content: &str,
+    note: &Note,
+    field: &BoardField,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<String, String> {
+    if let Some(to) = to {
+        if to.contains(['\n', '\r']) || (*field == BoardField::Tags && to.contains([',', '[', ']'])) {
+            return Err(format!("Invalid value '{}'", to));
+        }
+    }
+    match field {
+        BoardField::Field(name) => set_frontmatter_field(content, name, to),
+        BoardField::Tags => {
+            let mut tags: Vec<String> = note
+                .tags
+                .iter()
+                .filter(|t| from.is_none_or(|f| !t.eq_ignore_ascii_case(f)))
+                .cloned()
+                .collect();
+            if let Some(to) = to {
+                if !tags.iter().any(|t| t.eq_ignore_ascii_case(to)) {
+                    tags.push(to.to_string());
+                }
+            }
+            let value = (!tags.is_empty()).then(|| format!("[{}]", tags.join(", ")));
+            set_frontmatter_field(content, "tags", value.as_deref())
+        }
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BoardQuery {
+    pub field: Option<String>,
+    /// Comma-separated column values, in order.
+    pub columns: Option<String>,
+    /// `tasks` for task cards.
+    pub items: Option<String>,
+}
+
+fn note_card(note: &Note) -> String {
+    format!(
+        r#"<div class="board-card" draggable="true" data-key="{key}"><a href="/note/{key}">{}</a></div>"#,
+        html_escape(&note.title),
+        key = note.key,
+    )
+}
+
+fn task_card(task: &Task) -> String {
+    format!(
+        r#"<div class="board-card">{}<br><small><a href="/note/{}">{}</a></small></div>"#,
+        html_escape(&task.text),
+        task.note_key,
+        html_escape(&task.note_title),
+    )
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /board?field=status`
+pub async fn board_page(
+    Query(query): Query<BoardQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let field = match BoardField::parse(query.field.as_deref().unwrap_or("status")) {
+        Ok(f) => f,
+        Err(e) => {
+            let html = format!(r#"<div class="message error">{}</div>"#, html_escape(&e));
+            return (StatusCode::BAD_REQUEST, Html(base_html("Board", &html, None, true))).into_response();
+        }
+    };
+    let columns: Option<Vec<String>> = non_empty(query.columns.as_deref())
+        .map(|c| c.split(',').map(str::trim).filter(|c| !c.is_empty()).map(str::to_string).collect());
+    let tasks = query.items.as_deref() == Some("tasks");
+
+    let notes = state.load_notes();
+    let board = board_columns(&notes, &field, columns.as_deref());
+
+    let mut html = format!("<h1>Board: {}</h1>", html_escape(field.name()));
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} &middot; <a href="/board?field={}{}{}">{}</a></p>"#,
+        if tasks {
+            "Open tasks by their note's field."
+        } else {
+            "Drag a card to another column to change the field."
+        },
+        urlencoding::encode(field.name()),
+        columns
+            .as_ref()
+            .map(|c| format!("&columns={}", urlencoding::encode(&c.join(","))))
+            .unwrap_or_default(),
+        if tasks { "" } else { "&items=tasks" },
+        if tasks { "Show notes" } else { "Show tasks" },
+    ));
+
+    html.push_str(r#"<div class="board">"#);
+    for column in &board {
+        let cards: Vec<String> = if tasks {
+            column
+                .notes
+                .iter()
+                .flat_map(|n| extract_tasks(n))
+                .filter(|t| !t.done)
+                .map(|t| task_card(&t))
+                .collect()
+        } else {
+            column.notes.iter().map(|n| note_card(n)).collect()
+        };
+        html.push_str(&format!(
+            r#"<div class="board-column" data-value="{}"><h3>{} <span class="key">{}</span></h3>{}</div>"#,
+            html_escape(column.value.as_deref().unwrap_or("")),
+            match column.value {
+                Some(ref v) => html_escape(v),
+                None => "<em>none</em>".to_string(),
+            },
+            cards.len(),
+            cards.join(""),
+        ));
+    }
+    html.push_str("</div>");
+    html.push_str(
+        r#"<style>
+            .board { display: flex; gap: 1rem; overflow-x: auto; align-items: flex-start; }
+            .board-column { flex: 0 0 240px; background: var(--accent); border-radius: 4px; padding: 0.5rem; min-height: 4rem; }
+            .board-column h3 { margin: 0 0 0.5rem; font-size: 0.95rem; }
+            .board-column.drop-target { outline: 2px dashed var(--link); }
+            .board-card { background: var(--bg); border: 1px solid var(--border); border-radius: 4px; padding: 0.5rem; margin-bottom: 0.5rem; font-size: 0.9rem; }
+            .board-card[draggable="true"] { cursor: grab; }
+        </style>"#,
+    );
+    if !tasks {
+        html.push_str(&format!(
+            r#"<script>
+            const boardField = '{}';
+            let dragged = null;
+            for (const card of document.querySelectorAll('.board-card[draggable]')) {{
+                card.addEventListener('dragstart', () => {{ dragged = card; }});
+            }}
+            for (const column of document.querySelectorAll('.board-column')) {{
+                column.addEventListener('dragover', e => {{ e.preventDefault(); column.classList.add('drop-target'); }});
+                column.addEventListener('dragleave', () => column.classList.remove('drop-target'));
+                column.addEventListener('drop', async e => {{
+                    e.preventDefault();
+                    column.classList.remove('drop-target');
+                    if (!dragged) return;
+                    const from = dragged.closest('.board-column');
+                    if (from === column) return;
+                    const resp = await fetch('/api/board/move', {{
+                        method: 'POST',
+                        headers: {{'Content-Type': 'application/json'}},
+                        body: JSON.stringify({{
+                            key: dragged.dataset.key,
+                            field: boardField,
+                            from: from.dataset.value || null,
+                            to: column.dataset.value || null
+                        }})
+                    }});
+                    if (!resp.ok) {{ alert('Failed to move: ' + await resp.text()); return; }}
+                    if (boardField === 'tags') {{ location.reload(); return; }}
+                    column.appendChild(dragged);
+                }});
+            }}
+            </script>"#,
+            field.name()
+        ));
+    }
+
+    Html(base_html("Board", &html, None, true)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveCardRequest {
+    pub key: String,
+    pub field: String,
+    /// Column the card left; None for the no-value column.
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Column the card was dropped on; None for the no-value column.
+    #[serde(default)]
+    pub to: Option<String>,
+}
+
+/// `POST /api/board/move`: set a note's field to the column it was dropped on.
+pub async fn move_card_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<MoveCardRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let field = match BoardField::parse(&req.field) {
+        Ok(f) => f,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let notes_map = state.notes_map();
+    let note = match notes_map.get(&req.key) {
+        Some(n) => n,
+        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+    };
+
+    let (from, to) = (non_empty(req.from.as_deref()), non_empty(req.to.as_deref()));
+    let now = crate::timezone::now();
+    let commit_msg = format!(
+        "set {} of '{}' to '{}': {}",
+        field.name(),
+        note.title,
+        to.unwrap_or(""),
+        now.format("%a %b %d, %-I:%M%p")
+    );
+    match rewrite_note_file(&state, note, commit_msg, |c| move_card(c, note, &field, from, to)) {
+        Ok(_) => axum::Json(serde_json::json!({ "field": field.name(), "value": to })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_board_columns_and_moves() {
+        let dir = TempDir::new("board");
+        fs::write(dir.join("a.md"), "---\ntitle: A\nstage: doing\ntags: [x, y]\n---\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: B\nstage: done\n---\n").unwrap();
+        fs::write(dir.join("c.md"), "---\ntitle: C\n---\n").unwrap();
+        let notes = load_all_notes(&dir);
+        let titles = |c: &Column| c.notes.iter().map(|n| n.title.clone()).collect::<Vec<_>>();
+
+        let stage = BoardField::parse("stage").unwrap();
+        let board = board_columns(&notes, &stage, None);
+        let values: Vec<Option<&str>> = board.iter().map(|c| c.value.as_deref()).collect();
+        assert_eq!(values, vec![None, Some("doing"), Some("done")]);
+        assert_eq!(titles(&board[0]), vec!["C"]);
+
+        let order = vec!["todo".to_string(), "doing".to_string()];
+        let board = board_columns(&notes, &stage, Some(&order));
+        assert_eq!(board.len(), 2);
+        assert!(board[0].notes.is_empty());
+        assert_eq!(titles(&board[1]), vec!["A"]);
+
+        let tags = BoardField::parse("tag").unwrap();
+        let board = board_columns(&notes, &tags, None);
+        assert_eq!(board.len(), 3);
+        assert_eq!(board[0].notes.len(), 2);
+
+        let a = notes.iter().find(|n| n.title == "A").unwrap();
+        let moved = move_card(&a.full_file_content, a, &stage, Some("doing"), Some("done")).unwrap();
+        assert_eq!(moved, "---\ntitle: A\nstage: done\ntags: [x, y]\n---\n");
+        let moved = move_card(&a.full_file_content, a, &tags, Some("x"), Some("z")).unwrap();
+        assert_eq!(moved, "---\ntitle: A\nstage: doing\ntags: [y, z]\n---\n");
+        let moved = move_card(&a.full_file_content, a, &stage, Some("doing"), None).unwrap();
+        assert_eq!(moved, "---\ntitle: A\ntags: [x, y]\n---\n");
+        assert!(move_card(&a.full_file_content, a, &tags, None, Some("a,b")).is_err());
+        assert!(BoardField::parse("title").is_err());
+        assert!(BoardField::parse("a b").is_err());
+    }
+}
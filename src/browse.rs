@@ -0,0 +1,267 @@
+//! `/browse`: the notes as a tree.
+//!
+//! By default the tree mirrors the directories under the notes directory;
+//! `?by=parent` nests notes under their `parent:` instead, a note with
+//! several `parents:` showing under each. Each section is a collapsible `<details>` with the number of notes beneath it. Hidden
+//! notes are only shown when logged in.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::html_escape;
+use crate::templates::base_html;
+use crate::AppState;
+
+/// A directory of notes.
+#[derive(Default)]
+pub struct Folder<'a> {
+    pub notes: Vec<&'a Note>,
+    pub folders: BTreeMap<String, Folder<'a>>,
+}
+
+impl Folder<'_> {
+    /// Notes in this folder and every folder below it.
+    pub fn count(&self) -> usize {
+        self.notes.len() + self.folders.values().map(Folder::count).sum::<usize>()
+    }
+}
+
+fn by_title(notes: &mut [&Note]) {
+    notes.sort_by(|a, b| a.title.to_lowercase().cmp(&b.title.to_lowercase()).then_with(|| a.key.cmp(&b.key)));
+}
+
+/// The notes' directories, from their paths relative to the notes dir.
+pub fn folder_tree<'a>(notes: &[&'a Note]) -> Folder<'a> {
+    let mut root = Folder::default();
+    for note in notes {
+        let mut folder = &mut root;
+        if let Some(dir) = note.path.parent() {
+            for part in dir.components() {
+                let name = part.as_os_str().to_string_lossy().into_owned();
+                folder = folder.folders.entry(name).or_default();
+            }
+        }
+        folder.notes.push(note);
+    }
+    fn sort(folder: &mut Folder) {
+        by_title(&mut folder.notes);
+        folder.folders.values_mut().for_each(sort);
+    }
+    sort(&mut root);
+    root
+}
+
+/// Children of each note by `parents:`, and the notes whose parents are
+/// all missing or not shown. A note in a parent cycle has no root, so the
+/// first of the cycle by title is taken as one.
+pub fn parent_tree<'a>(notes: &[&'a Note]) -> (Vec<&'a Note>, HashMap<&'a str, Vec<&'a Note>>) {
+    let shown: HashSet<&str> = notes.iter().map(|n| n.key.as_str()).collect();
+    let mut roots = Vec::new();
+    let mut children: HashMap<&str, Vec<&Note>> = HashMap::new();
+    for note in notes {
+        let parents: Vec<&str> =
+            note.parents.iter().map(String::as_str).filter(|p| shown.contains(p) && *p != note.key).collect();
+        if parents.is_empty() {
+            roots.push(*note);
+        }
+        for parent in parents {
+            children.entry(parent).or_default().push(note);
+        }
+    }
+    by_title(&mut roots);
+    children.values_mut().for_each(|c| by_title(c));
+
+    // Notes not reachable from a root sit on a cycle
+    let mut reached: HashSet<&str> = HashSet::new();
+    let mut stack: Vec<&Note> = roots.clone();
+    while let Some(note) = stack.pop() {
+        if reached.insert(note.key.as_str()) {
+            stack.extend(children.get(note.key.as_str()).into_iter().flatten());
+        }
+    }
+    let mut cyclic: Vec<&Note> = notes.iter().copied().filter(|n| !reached.contains(n.key.as_str())).collect();
+    by_title(&mut cyclic);
+    for note in cyclic {
+        if reached.contains(note.key.as_str()) {
+            continue;
+        }
+        roots.push(note);
+        let mut stack = vec![note];
+        while let Some(n) = stack.pop() {
+            if reached.insert(n.key.as_str()) {
+                stack.extend(children.get(n.key.as_str()).into_iter().flatten());
+            }
+        }
+    }
+    (roots, children)
+}
+
+fn note_item(note: &Note) -> String {
+    let badge = if matches!(note.note_type, NoteType::Paper(_)) {
+        r#"<span class="type-badge">paper</span> "#
+    } else {
+        ""
+    };
+    let class = if note.hidden { "note-item hidden-note" } else { "note-item" };
+    format!(
+        r#"<li class="{class}"><span>{badge}<a href="/note/{key}" class="title">{title}</a> <span class="key">[@{key}]</span></span></li>"#,
+        key = note.key,
+        title = html_escape(&note.title),
+    )
+}
+
+fn section(summary: &str, count: usize, open: bool, inner: &str) -> String {
+    format!(
+        r#"<details class="browse-section"{open}><summary>{summary} <span class="browse-count">{count}</span></summary>{inner}</details>"#,
+        open = if open { " open" } else { "" },
+    )
+}
+
+fn folder_html(folder: &Folder, depth: usize) -> String {
+    let mut html = String::new();
+    for (name, sub) in &folder.folders {
+        html.push_str(&section(&format!("{}/", html_escape(name)), sub.count(), depth == 0, &folder_html(sub, depth + 1)));
+    }
+    if !folder.notes.is_empty() {
+        html.push_str(r#"<ul class="note-list">"#);
+        for note in &folder.notes {
+            html.push_str(&note_item(note));
+        }
+        html.push_str("</ul>");
+    }
+    html
+}
+
+/// `path` holds the notes above this level, so a cycle stops where it
+/// comes back around.
+fn parent_html(notes: &[&Note], children: &HashMap<&str, Vec<&Note>>, depth: usize, path: &mut Vec<String>) -> String {
+    let mut html = String::new();
+    let mut leaves = String::new();
+    for note in notes {
+        if path.contains(&note.key) {
+            continue;
+        }
+        match children.get(note.key.as_str()) {
+            Some(kids) => {
+                path.push(note.key.clone());
+                let inner = parent_html(kids, children, depth + 1, path);
+                path.pop();
+                let count = descendants(note, children);
+                let summary = format!(r#"<a href="/note/{}">{}</a>"#, note.key, html_escape(&note.title));
+                html.push_str(&section(&summary, count, depth == 0, &inner));
+            }
+            None => leaves.push_str(&note_item(note)),
+        }
+    }
+    if !leaves.is_empty() {
+        html.push_str(&format!(r#"<ul class="note-list">{}</ul>"#, leaves));
+    }
+    html
+}
+
+/// Notes below `note` in the parent hierarchy.
+fn descendants(note: &Note, children: &HashMap<&str, Vec<&Note>>) -> usize {
+    let mut seen = HashSet::from([note.key.as_str()]);
+    let mut stack: Vec<&Note> = children.get(note.key.as_str()).cloned().unwrap_or_default();
+    while let Some(n) = stack.pop() {
+        if seen.insert(n.key.as_str()) {
+            stack.extend(children.get(n.key.as_str()).into_iter().flatten());
+        }
+    }
+    seen.len() - 1
+}
+
+#[derive(Deserialize)]
+pub struct BrowseQuery {
+    /// `parent` for the `parent:` hierarchy; directories otherwise.
+    pub by: Option<String>,
+}
+
+pub async fn browse(Query(query): Query<BrowseQuery>, State(state): State<Arc<AppState>>, jar: CookieJar) -> Html<String> {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let all = state.load_notes();
+    let notes: Vec<&Note> = all.iter().filter(|n| logged_in || !n.hidden).collect();
+    let by_parent = query.by.as_deref() == Some("parent");
+
+    let toggle = if by_parent {
+        r#"<a href="/browse">Folders</a> &middot; <strong>Parents</strong>"#
+    } else {
+        r#"<strong>Folders</strong> &middot; <a href="/browse?by=parent">Parents</a>"#
+    };
+    let tree = if by_parent {
+        let (roots, children) = parent_tree(&notes);
+        parent_html(&roots, &children, 0, &mut Vec::new())
+    } else {
+        folder_html(&folder_tree(&notes), 0)
+    };
+    let html = format!(
+        r#"<h1>Browse</h1>
+        <div class="hidden-toggle">{} &middot; {} notes</div>
+        {}"#,
+        toggle,
+        notes.len(),
+        tree
+    );
+    Html(base_html("Browse", &html, None, logged_in))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn note(key: &str, path: &str, parent: Option<&str>) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(path),
+            title: key.to_uppercase(),
+            parents: parent.into_iter().map(str::to_string).collect(),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_browse_trees() {
+        let mut all = [
+            note("a", "top.md", None),
+            note("b", "papers/datalog/souffle.md", Some("a")),
+            note("c", "papers/ifds.md", Some("b")),
+            note("d", "projects/d.md", Some("e")),
+            note("e", "projects/e.md", Some("d")),
+            note("f", "projects/f.md", Some("missing")),
+        ];
+        // c belongs under both b and f
+        all[2].parents.push("f".to_string());
+        let notes: Vec<&Note> = all.iter().collect();
+
+        let root = folder_tree(&notes);
+        assert_eq!(root.count(), 6);
+        assert_eq!(root.notes.len(), 1);
+        assert_eq!(root.folders["papers"].count(), 2);
+        assert_eq!(root.folders["papers"].folders["datalog"].notes[0].key, "b");
+        let html = folder_html(&root, 0);
+        assert!(html.contains(r#"<details class="browse-section" open><summary>papers/ <span class="browse-count">2</span>"#));
+        assert!(html.contains(r#"<details class="browse-section"><summary>datalog/ <span class="browse-count">1</span>"#));
+
+        let (roots, children) = parent_tree(&notes);
+        let keys: Vec<&str> = roots.iter().map(|n| n.key.as_str()).collect();
+        // d and e are each other's parent; D comes first by title
+        assert_eq!(keys, ["a", "f", "d"]);
+        assert_eq!(descendants(&all[0], &children), 2);
+        let html = parent_html(&roots, &children, 0, &mut Vec::new());
+        assert!(html.contains(r#"<summary><a href="/note/a">A</a> <span class="browse-count">2</span>"#));
+        assert_eq!(html.matches(r#"href="/note/e""#).count(), 1);
+        assert_eq!(html.matches(r#"href="/note/c""#).count(), 2);
+        assert!(html.contains(r#"<summary><a href="/note/f">F</a> <span class="browse-count">1</span>"#));
+    }
+}
@@ -0,0 +1,286 @@
+//! Bulk operations on many notes at once.
+//!
+//! `POST /api/notes/bulk` applies one operation to a list of notes. Every
+//! change is worked out before any file is touched, so a bad key or value
+//! changes nothing; if a write then fails, the files already written are
+//! put back. The whole batch is one git commit.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, response::Response};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::models::Note;
+use crate::notes::{ancestors, set_frontmatter_field};
+use crate::trash;
+use crate::AppState;
+
+/// The operation of a bulk request, named by its `op` field.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOp {
+    AddTag { tag: String },
+    /// A missing or empty status removes the field.
+    SetStatus { status: Option<String> },
+    /// A missing or empty parent removes the field.
+    SetParent { parent: Option<String> },
+    Delete,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkRequest {
+    pub keys: Vec<String>,
+    #[serde(flatten)]
+    pub op: BulkOp,
+    /// Required for `delete`, as for single deletes.
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// One note's part of a bulk operation: its new file contents, or None
+/// when it is to be deleted.
+#[derive(Debug, Clone)]
+pub struct BulkChange {
+    pub key: String,
+    pub path: PathBuf,
+    pub content: Option<String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Work out every change of `op` without writing anything. Fails on an
+/// unknown key or a value that doesn't fit one of the notes.
+pub fn plan_bulk(
+    notes_dir: &Path,
+    notes_map: &HashMap<String, Note>,
+    keys: &[String],
+    op: &BulkOp,
+) -> Result<Vec<BulkChange>, String> {
+    let mut seen = HashSet::new();
+    let notes: Vec<&Note> = keys
+        .iter()
+        .filter(|k| seen.insert(k.as_str()))
+        .map(|k| notes_map.get(k).ok_or_else(|| format!("Note '{}' not found", k)))
+        .collect::<Result<_, _>>()?;
+    if notes.is_empty() {
+        return Err("No notes given".to_string());
+    }
+
+    match op {
+        BulkOp::AddTag { tag } => {
+            let tag = tag.trim();
+            if tag.is_empty() || tag.contains([',', '[', ']', '\n']) {
+                return Err(format!("Invalid tag '{}'", tag));
+            }
+        }
+        BulkOp::SetParent { parent } => {
+            if let Some(parent) = non_empty(parent) {
+                if !notes_map.contains_key(parent) {
+                    return Err(format!("Parent '{}' not found", parent));
+                }
+                let mut above = ancestors(parent, notes_map);
+                above.push(parent.to_string());
+                if let Some(note) = notes.iter().find(|n| above.contains(&n.key)) {
+                    return Err(format!("'{}' can't be filed under its own descendant", note.title));
+                }
+            }
+        }
+        BulkOp::SetStatus { status } => {
+            if non_empty(status).is_some_and(|s| s.contains('\n')) {
+                return Err("Invalid status".to_string());
+            }
+        }
+        BulkOp::Delete => {}
+    }
+    if !matches!(op, BulkOp::Delete) {
+        notes.iter().try_for_each(|n| crate::formats::check_writable(&n.path))?;
+    }
+
+    notes
+        .into_iter()
+        .map(|note| {
+            let current = fs::read_to_string(notes_dir.join(&note.path))
+                .map_err(|e| format!("Failed to read {}: {}", note.path.display(), e))?;
+            let updated = match op {
+                BulkOp::AddTag { tag } => {
+                    let mut tags = note.tags.clone();
+                    if !tags.iter().any(|t| t == tag.trim()) {
+                        tags.push(tag.trim().to_string());
+                    }
+                    Some(set_frontmatter_field(&current, "tags", Some(&format!("[{}]", tags.join(", ")))))
+                }
+                BulkOp::SetStatus { status } => Some(set_frontmatter_field(&current, "status", non_empty(status))),
+                BulkOp::SetParent { parent } => Some(
+                    set_frontmatter_field(&current, "parents", None)
+                        .and_then(|c| set_frontmatter_field(&c, "parent", non_empty(parent))),
+                ),
+                BulkOp::Delete => None,
+            };
+            let content = updated
+                .transpose()
+                .map_err(|e| format!("{}: {}", note.path.display(), e))?;
+            Ok(BulkChange {
+                key: note.key.clone(),
+                path: note.path.clone(),
+                content,
+            })
+        })
+        .collect()
+}
+
+/// Apply planned changes. On failure, undo the ones already made. Returns
+/// the paths to commit.
+pub fn apply_bulk(
+    notes_dir: &Path,
+    notes_map: &HashMap<String, Note>,
+    changes: &[BulkChange],
+    now: NaiveDateTime,
+) -> Result<Vec<PathBuf>, String> {
+    let mut written: Vec<(PathBuf, String)> = Vec::new();
+    let mut trashed: Vec<trash::TrashedNote> = Vec::new();
+    let mut paths = Vec::new();
+
+    let result = changes.iter().try_for_each(|change| {
+        let full_path = notes_dir.join(&change.path);
+        match change.content {
+            Some(ref content) => {
+                let original = fs::read_to_string(&full_path)
+                    .map_err(|e| format!("Failed to read {}: {}", change.path.display(), e))?;
+                crate::encryption::write_file(&full_path, content)
+                    .map_err(|e| format!("{}: {}", change.path.display(), e))?;
+                written.push((full_path, original));
+                paths.push(change.path.clone());
+            }
+            None => {
+                let note = &notes_map[&change.key];
+                let entry = trash::trash_note(notes_dir, note, now)?;
+                paths.push(change.path.clone());
+                paths.push(entry.dir());
+                trashed.push(entry);
+            }
+        }
+        Ok::<(), String>(())
+    });
+
+    if let Err(e) = result {
+        for (path, original) in written {
+            let _ = fs::write(path, original);
+        }
+        for entry in trashed {
+            let _ = trash::restore(notes_dir, &entry.id);
+        }
+        return Err(e);
+    }
+    Ok(paths)
+}
+
+fn summary(op: &BulkOp, count: usize) -> String {
+    match op {
+        BulkOp::AddTag { tag } => format!("tagged {} notes '{}'", count, tag.trim()),
+        BulkOp::SetStatus { status } => match non_empty(status) {
+            Some(status) => format!("set status of {} notes to '{}'", count, status),
+            None => format!("cleared status of {} notes", count),
+        },
+        BulkOp::SetParent { parent } => match non_empty(parent) {
+            Some(parent) => format!("moved {} notes under '{}'", count, parent),
+            None => format!("cleared parent of {} notes", count),
+        },
+        BulkOp::Delete => format!("deleted {} notes", count),
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `POST /api/notes/bulk`
+pub async fn bulk_notes(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<BulkRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if matches!(req.op, BulkOp::Delete) && !req.confirm {
+        return (StatusCode::BAD_REQUEST, "Deletion not confirmed").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let changes = match plan_bulk(&state.notes_dir, &notes_map, &req.keys, &req.op) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let paths = match apply_bulk(&state.notes_dir, &notes_map, &changes, crate::timezone::now().naive_local()) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    state.invalidate_notes_cache();
+    for change in &changes {
+        match change.content {
+            Some(_) => state.reindex_graph_note(&change.key),
+            None => state.remove_graph_note(&change.key),
+        }
+    }
+
+    let notes_dir = state.notes_dir.clone();
+    let summary = summary(&req.op, changes.len());
+    state.spawn_background(move || trash::commit_paths(&notes_dir, &paths, &summary));
+
+    axum::Json(serde_json::json!({ "changed": changes.len() })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    #[test]
+    fn test_plan_and_apply_bulk() {
+        let dir = TempDir::new("bulk");
+        fs::write(dir.join("a.md"), "---\ntitle: A\nid: aaaaaaaaaaaa\ntags: [x]\n---\n\nA body\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: B\nid: bbbbbbbbbbbb\nparent: aaaaaaaaaaaa\n---\n").unwrap();
+        fs::write(dir.join("c.md"), "---\ntitle: C\nid: cccccccccccc\n---\n").unwrap();
+        let notes_map: HashMap<String, Note> =
+            load_all_notes(&dir).into_iter().map(|n| (n.key.clone(), n)).collect();
+        let keys = |ks: &[&str]| ks.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        let now = chrono::NaiveDate::from_ymd_opt(2026, 10, 17).unwrap().and_hms_opt(9, 0, 0).unwrap();
+
+        let op = BulkOp::AddTag { tag: "plt".to_string() };
+        let changes = plan_bulk(&dir, &notes_map, &keys(&["aaaaaaaaaaaa", "cccccccccccc", "aaaaaaaaaaaa"]), &op).unwrap();
+        assert_eq!(changes.len(), 2);
+        apply_bulk(&dir, &notes_map, &changes, now).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("a.md")).unwrap(),
+            "---\ntitle: A\nid: aaaaaaaaaaaa\ntags: [x, plt]\n---\n\nA body\n"
+        );
+        assert_eq!(fs::read_to_string(dir.join("c.md")).unwrap(), "---\ntitle: C\nid: cccccccccccc\ntags: [plt]\n---\n");
+
+        // Nothing is planned when any key is unknown or the parent would loop
+        assert!(plan_bulk(&dir, &notes_map, &keys(&["aaaaaaaaaaaa", "missing"]), &op).is_err());
+        let under_b = BulkOp::SetParent { parent: Some("bbbbbbbbbbbb".to_string()) };
+        assert!(plan_bulk(&dir, &notes_map, &keys(&["aaaaaaaaaaaa"]), &under_b).is_err());
+        let under_c = BulkOp::SetParent { parent: Some("cccccccccccc".to_string()) };
+        assert!(plan_bulk(&dir, &notes_map, &keys(&["aaaaaaaaaaaa", "bbbbbbbbbbbb"]), &under_c).is_ok());
+
+        let changes = plan_bulk(&dir, &notes_map, &keys(&["bbbbbbbbbbbb", "cccccccccccc"]), &BulkOp::Delete).unwrap();
+        let paths = apply_bulk(&dir, &notes_map, &changes, now).unwrap();
+        assert_eq!(paths.len(), 4);
+        assert!(!dir.join("b.md").exists());
+        assert_eq!(trash::list_trash(&dir).len(), 2);
+
+        let req: BulkRequest =
+            serde_json::from_str(r#"{"keys": ["a"], "op": "set_status", "status": "archived"}"#).unwrap();
+        assert!(matches!(req.op, BulkOp::SetStatus { status: Some(ref s) } if s == "archived"));
+    }
+}
@@ -29,6 +29,8 @@ use std::sync::Arc;
 mod citations_test;
 
 const CITATIONS_TREE: &str = "citations";
+/// PDFs fetched and scanned together by a bulk scan.
+const SCAN_CHUNK: usize = 16;
 const BEGIN_MARKER: &str = "<!-- BEGIN AUTO-CITATIONS -->";
 const END_MARKER: &str = "<!-- END AUTO-CITATIONS -->";
 
@@ -64,6 +66,11 @@ fn run_pdf_extract(path: &Path) -> Result<String, String> {
         .map_err(|e| format!("pdf-extract failed: {}", e))
 }
 
+/// Extract a PDF's text with `pdftotext`, or `pdf-extract` when that fails.
+pub fn extract_pdf_text(path: &Path) -> Result<String, String> {
+    run_pdftotext(path, false).or_else(|_| run_pdf_extract(path))
+}
+
 /// Extract PDF text, trying multiple methods and picking whichever yields
 /// the most reference entries:
 /// - `pdftotext` without `-layout`: good for multi-column heading detection
@@ -95,7 +102,7 @@ fn extract_pdf_text_best(path: &Path) -> Result<(String, Vec<String>), String> {
     }
 
     // Pick whichever yields the most references
-    candidates.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    candidates.sort_by_key(|c| std::cmp::Reverse(c.1.len()));
     Ok(candidates.into_iter().next().unwrap())
 }
 
@@ -708,8 +715,8 @@ fn edit_distance(a: &str, b: &str) -> usize {
     let mut prev = vec![0usize; n + 1];
     let mut curr = vec![0usize; n + 1];
 
-    for j in 0..=n {
-        prev[j] = j;
+    for (j, p) in prev.iter_mut().enumerate() {
+        *p = j;
     }
 
     for i in 1..=m {
@@ -744,7 +751,7 @@ impl NotePoolIndex {
                 let mut author_years = Vec::new();
 
                 if let NoteType::Paper(ref meta) = note.note_type {
-                    for source in &meta.sources {
+                    for source in meta.all_sources() {
                         match source.source_type.as_str() {
                             "doi" => dois.push((source.identifier.to_lowercase(), key.clone())),
                             "arxiv" => arxivs.push((source.identifier.clone(), key.clone())),
@@ -873,11 +880,10 @@ impl NotePoolIndex {
                         continue;
                     }
                     let dist = edit_distance(&norm, pool_title);
-                    if dist <= max_dist && dist > 0 {
-                        if best_match.is_none() || dist < best_match.unwrap().1 {
+                    if dist <= max_dist && dist > 0
+                        && (best_match.is_none() || dist < best_match.unwrap().1) {
                             best_match = Some((pool_key.as_str(), dist));
                         }
-                    }
                 }
 
                 if let Some((key, dist)) = best_match {
@@ -911,11 +917,11 @@ impl NotePoolIndex {
                 candidate_votes.iter().max_by_key(|(_, &v)| v)
             {
                 // Require either 2+ author matches, or 1 match on the first author
-                let is_first_author_match = reference.authors.first().map_or(false, |first| {
+                let is_first_author_match = reference.authors.first().is_some_and(|first| {
                     let lookup = format!("{}_{}", first, year);
                     self.author_year_to_keys
                         .get(&lookup)
-                        .map_or(false, |keys| keys.len() == 1 && keys[0] == best_key)
+                        .is_some_and(|keys| keys.len() == 1 && keys[0] == best_key)
                 });
 
                 if vote_count >= 2 || (vote_count == 1 && is_first_author_match) {
@@ -959,13 +965,13 @@ fn hash_pdf(path: &Path) -> Result<String, String> {
 // Sled Cache Operations
 // ============================================================================
 
-fn load_cached_result(db: &sled::Db, key: &str) -> Option<CitationScanResult> {
+fn load_cached_result(db: &crate::store::Db, key: &str) -> Option<CitationScanResult> {
     let tree = db.open_tree(CITATIONS_TREE).ok()?;
     let data = tree.get(key.as_bytes()).ok()??;
     serde_json::from_slice(&data).ok()
 }
 
-fn save_cached_result(db: &sled::Db, result: &CitationScanResult) -> Result<(), String> {
+fn save_cached_result(db: &crate::store::Db, result: &CitationScanResult) -> Result<(), String> {
     let tree = db
         .open_tree(CITATIONS_TREE)
         .map_err(|e| format!("Cannot open citations tree: {}", e))?;
@@ -983,21 +989,11 @@ fn save_cached_result(db: &sled::Db, result: &CitationScanResult) -> Result<(),
 fn scan_note_pdf_with_index(
     note: &Note,
     index: &NotePoolIndex,
-    pdfs_dir: &Path,
-    db: &sled::Db,
+    pdf_path: &Path,
+    db: &crate::store::Db,
     force: bool,
 ) -> Result<CitationScanResult, String> {
-    let pdf_filename = note
-        .pdf
-        .as_deref()
-        .ok_or_else(|| "Note has no attached PDF".to_string())?;
-
-    let pdf_path = pdfs_dir.join(pdf_filename);
-    if !pdf_path.exists() {
-        return Err(format!("PDF file not found: {}", pdf_filename));
-    }
-
-    let current_hash = hash_pdf(&pdf_path)?;
+    let current_hash = hash_pdf(pdf_path)?;
 
     // Check cache
     if !force {
@@ -1009,7 +1005,7 @@ fn scan_note_pdf_with_index(
     }
 
     // Extract text — tries both with and without -layout, picks whichever yields more refs
-    let (_text, raw_refs) = extract_pdf_text_best(&pdf_path)?;
+    let (_text, raw_refs) = extract_pdf_text_best(pdf_path)?;
     let parsed_refs: Vec<ExtractedReference> = raw_refs
         .iter()
         .enumerate()
@@ -1048,12 +1044,12 @@ fn scan_note_pdf_with_index(
 fn scan_note_pdf(
     note: &Note,
     notes: &[Note],
-    pdfs_dir: &Path,
-    db: &sled::Db,
+    pdf_path: &Path,
+    db: &crate::store::Db,
     force: bool,
 ) -> Result<CitationScanResult, String> {
     let index = NotePoolIndex::build(notes);
-    scan_note_pdf_with_index(note, &index, pdfs_dir, db, force)
+    scan_note_pdf_with_index(note, &index, pdf_path, db, force)
 }
 
 // ============================================================================
@@ -1068,7 +1064,7 @@ fn write_citations_to_markdown(
     notes_map: &HashMap<String, Note>,
     notes_dir: &Path,
 ) -> Result<(), String> {
-    let content = &note.full_file_content;
+    let content = &crate::encryption::open_file(&note.full_file_content)?;
 
     // Build the new auto-citations block
     let mut block = String::new();
@@ -1103,9 +1099,10 @@ fn write_citations_to_markdown(
         format!("{}\n\n{}\n", trimmed, block)
     };
 
+    crate::formats::check_writable(&note.path)?;
     let full_path = notes_dir.join(&note.path);
-    std::fs::write(&full_path, &new_content)
-        .map_err(|e| format!("Failed to write note to {}: {}", full_path.display(), e))?;
+    crate::encryption::write_file(&full_path, &new_content)
+        .map_err(|e| format!("{}: {}", full_path.display(), e))?;
 
     Ok(())
 }
@@ -1138,13 +1135,19 @@ pub async fn citation_scan(
         }
     };
 
-    let pdfs_dir = state.pdfs_dir.clone();
+    let Some(ref pdf) = note.pdf else {
+        return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, "Note has no attached PDF").into_response();
+    };
+    let pdf_file = match state.storage.local_file(pdf).await {
+        Ok(f) => f,
+        Err(e) => return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
     let db = state.db.clone();
     let force = req.force;
 
     // Run pdftotext on spawn_blocking to avoid blocking async runtime
     let result = tokio::task::spawn_blocking(move || {
-        scan_note_pdf(&note, &notes, &pdfs_dir, &db, force)
+        scan_note_pdf(&note, &notes, pdf_file.path(), &db, force)
     })
     .await;
 
@@ -1266,92 +1269,79 @@ pub async fn citation_scan_all(
     }
 
     let notes = state.load_notes();
-    let pdfs_dir = state.pdfs_dir.clone();
-    let db = state.db.clone();
-
-    let result = tokio::task::spawn_blocking(move || {
-        use rayon::prelude::*;
-
-        let notes_with_pdf: Vec<&Note> = notes.iter().filter(|n| n.pdf.is_some()).collect();
-        let skipped_no_pdf = notes.len() - notes_with_pdf.len();
+    let notes_with_pdf: Vec<Note> = notes.iter().filter(|n| n.pdf.is_some()).cloned().collect();
+    let mut totals = CitationScanAllResult {
+        scanned: 0,
+        skipped_cached: 0,
+        skipped_no_pdf: notes.len() - notes_with_pdf.len(),
+        total_matches: 0,
+        errors: Vec::new(),
+    };
+    // Build index once for all uncached scans
+    let index = match tokio::task::spawn_blocking(move || NotePoolIndex::build(&notes)).await {
+        Ok(index) => Arc::new(index),
+        Err(e) => {
+            return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Task join error: {}", e)).into_response()
+        }
+    };
 
-        // Parallel cache check + hash computation
-        let cache_status: Vec<(&Note, bool, usize)> = notes_with_pdf
-            .par_iter()
-            .map(|&note| {
-                if let Some(cached) = load_cached_result(&db, &note.key) {
-                    let pdf_path = pdfs_dir.join(note.pdf.as_deref().unwrap());
-                    if let Ok(hash) = hash_pdf(&pdf_path) {
-                        if cached.pdf_hash == hash {
-                            return (note, true, cached.matches.len());
+    // A chunk at a time, so object storage never holds more than a chunk
+    // of temporary copies on disk
+    for chunk in notes_with_pdf.chunks(SCAN_CHUNK) {
+        let mut files = Vec::new();
+        for note in chunk {
+            match state.storage.local_file(note.pdf.as_deref().unwrap_or_default()).await {
+                Ok(file) => files.push((note.clone(), file)),
+                Err(e) => totals.errors.push(format!("{}: {}", note.key, e)),
+            }
+        }
+        let index = index.clone();
+        let db = state.db.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            use rayon::prelude::*;
+
+            // Parallel cache check, then scan of uncached notes
+            files
+                .par_iter()
+                .map(|(note, file)| {
+                    let cached = load_cached_result(&db, &note.key)
+                        .filter(|c| hash_pdf(file.path()).is_ok_and(|hash| hash == c.pdf_hash));
+                    match cached {
+                        Some(c) => (note.key.clone(), Ok((true, c.matches.len()))),
+                        None => {
+                            let result = scan_note_pdf_with_index(note, &index, file.path(), &db, false);
+                            (note.key.clone(), result.map(|r| (false, r.matches.len())))
                         }
                     }
-                }
-                (note, false, 0)
-            })
-            .collect();
-
-        let mut skipped_cached = 0;
-        let mut cached_matches = 0;
-        let mut to_scan = Vec::new();
-
-        for (note, is_cached, match_count) in cache_status {
-            if is_cached {
-                skipped_cached += 1;
-                cached_matches += match_count;
-            } else {
-                to_scan.push(note);
+                })
+                .collect::<Vec<_>>()
+        })
+        .await;
+        let outcomes = match result {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                return (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("Task join error: {}", e))
+                    .into_response()
             }
-        }
-
-        // Build index once for all uncached scans
-        let index = NotePoolIndex::build(&notes);
-
-        // Parallel scan for uncached notes
-        let scan_results: Vec<(String, Result<CitationScanResult, String>)> = to_scan
-            .par_iter()
-            .map(|note| {
-                let result = scan_note_pdf_with_index(note, &index, &pdfs_dir, &db, false);
-                (note.key.clone(), result)
-            })
-            .collect();
-
-        let mut scanned = 0;
-        let mut total_matches = cached_matches;
-        let mut errors = Vec::new();
+        };
 
-        for (key, result) in scan_results {
-            match result {
-                Ok(r) => {
-                    total_matches += r.matches.len();
-                    scanned += 1;
-                }
-                Err(e) => {
-                    errors.push(format!("{}: {}", key, e));
+        for (key, outcome) in outcomes {
+            match outcome {
+                Ok((cached, matches)) => {
+                    totals.total_matches += matches;
+                    if cached {
+                        totals.skipped_cached += 1;
+                    } else {
+                        totals.scanned += 1;
+                    }
                 }
+                Err(e) => totals.errors.push(format!("{}: {}", key, e)),
             }
         }
-
-        CitationScanAllResult {
-            scanned,
-            skipped_cached,
-            skipped_no_pdf,
-            total_matches,
-            errors,
-        }
-    })
-    .await;
-
-    match result {
-        Ok(scan_all_result) => {
-            // Sync all citation edges into the graph index
-            let _ = crate::graph_index::sync_all_citations(&state.db);
-            axum::Json(scan_all_result).into_response()
-        }
-        Err(e) => (
-            axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Task join error: {}", e),
-        )
-            .into_response(),
     }
+
+    // Sync all citation edges into the graph index
+    let _ = crate::graph_index::sync_all_citations(&state.db);
+    axum::Json(totals).into_response()
 }
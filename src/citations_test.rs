@@ -33,23 +33,18 @@ fn mock_note(key: &str, title: &str, doi: Option<&str>, arxiv: Option<&str>, bib
         key: key.to_string(),
         path: PathBuf::from(format!("{}.md", key)),
         title: title.to_string(),
-        date: None,
         note_type: if bibtex.is_some() || doi.is_some() || arxiv.is_some() {
             NoteType::Paper(PaperMeta {
                 bibtex_entries,
                 canonical_key: None,
                 sources,
+                versions: vec![],
             })
         } else {
             NoteType::Note
         },
-        parent_key: None,
-        time_entries: Vec::new(),
-        raw_content: String::new(),
-        full_file_content: String::new(),
         modified: Utc::now(),
-        pdf: None,
-        hidden: false,
+        ..Default::default()
     }
 }
 
@@ -0,0 +1,1356 @@
+//! Command-line subcommands.
+//!
+//! These work directly on the configured content directory, without a
+//! running server, for use over ssh. Each command writes to the given
+//! `out` so it can be tested; `main` passes stdout.
+//!
+//! Commands that also touch the database (`mv`, `migrate-keys`,
+//! `dedupe --merge`) refuse to run while the server holds it. A running
+//! server only sees notes written here after a restart.
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::backup;
+use crate::config::{Config, DbBackend};
+use crate::dedupe;
+use crate::doctor;
+use crate::graph_index;
+use crate::graph_query::query_graph;
+use crate::key_migration;
+use crate::note_move;
+use crate::note_templates;
+use crate::models::{
+    BibFilter, GraphQuery, InputType, KnowledgeGraph, Note, NoteSort, NoteType, SmartAddCreateRequest, SortOrder, TimeEntry,
+};
+use crate::notes::{append_time_entry, filter_bibliography, generate_bibliography, load_all_notes, new_note_frontmatter, new_note_key, search_notes, set_frontmatter_field, sort_notes};
+use crate::smart_add::{
+    create_paper_note, detect_input_type, generate_suggested_filename, lookup_external, search_local_for_match,
+};
+use crate::validate_path_within;
+
+/// Result type for commands: errors are printed by `main`, which exits 1.
+pub type CliResult = Result<(), String>;
+
+fn io_err(e: std::io::Error) -> String {
+    format!("Write failed: {}", e)
+}
+
+// ============================================================================
+// Note Lookup
+// ============================================================================
+
+/// Find a note by key, path, exact title (case-insensitive), or a title
+/// substring that matches exactly one note.
+pub fn resolve_note<'a>(notes: &'a [Note], query: &str) -> Result<&'a Note, String> {
+    let query = query.trim();
+    if let Some(note) = notes.iter().find(|n| n.key == query) {
+        return Ok(note);
+    }
+    if let Some(note) = notes.iter().find(|n| n.path == Path::new(query)) {
+        return Ok(note);
+    }
+    let lower = query.to_lowercase();
+    if let Some(note) = notes.iter().find(|n| n.title.to_lowercase() == lower) {
+        return Ok(note);
+    }
+
+    let mut candidates: Vec<&Note> = notes
+        .iter()
+        .filter(|n| n.title.to_lowercase().contains(&lower))
+        .collect();
+    match candidates.len() {
+        0 => Err(format!("No note matches '{}'", query)),
+        1 => Ok(candidates[0]),
+        n => {
+            candidates.sort_by_key(|n| n.title.to_lowercase());
+            let listed: Vec<String> = candidates
+                .iter()
+                .take(10)
+                .map(|n| format!("  {}  {}", n.key, n.title))
+                .collect();
+            Err(format!(
+                "'{}' matches {} notes:\n{}",
+                query,
+                n,
+                listed.join("\n")
+            ))
+        }
+    }
+}
+
+/// "1 note", "2 notes".
+fn plural(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+fn note_line(note: &Note) -> String {
+    let date = note
+        .date
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "-".repeat(10));
+    let mut line = format!("{}  {}  {}", note.key, date, note.title);
+    if matches!(note.note_type, NoteType::Paper(_)) {
+        line.push_str("  [paper]");
+    }
+    if note.hidden {
+        line.push_str("  (hidden)");
+    }
+    line
+}
+
+// ============================================================================
+// Commands
+// ============================================================================
+
+/// Filters for `notes list`.
+#[derive(Debug, Default, Clone)]
+pub struct ListFilter {
+    pub papers: bool,
+    pub tag: Option<String>,
+    pub sort: NoteSort,
+    /// The sort's natural order when None.
+    pub order: Option<SortOrder>,
+}
+
+/// `notes list`: one line per note, sorted by title unless asked otherwise.
+pub fn list(notes: &[Note], filter: &ListFilter, out: &mut impl Write) -> CliResult {
+    let mut selected: Vec<&Note> = notes
+        .iter()
+        .filter(|n| !filter.papers || matches!(n.note_type, NoteType::Paper(_)))
+        .filter(|n| match filter.tag {
+            Some(ref tag) => n.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            None => true,
+        })
+        .collect();
+    sort_notes(&mut selected, filter.sort, filter.order.unwrap_or(filter.sort.default_order()));
+    for note in selected {
+        writeln!(out, "{}", note_line(note)).map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// `notes search`: matching notes with their matching lines.
+pub fn search(notes: &[Note], query: &str, out: &mut impl Write) -> CliResult {
+    let mut results = search_notes(notes, query);
+    if results.is_empty() {
+        return Err(format!("No notes match '{}'", query));
+    }
+    results.sort_by_key(|r| r.note.title.to_lowercase());
+    for result in results {
+        writeln!(out, "{}", note_line(&result.note)).map_err(io_err)?;
+        for m in result.matches.iter().filter(|m| m.line_number > 0) {
+            writeln!(out, "    {}: {}", m.line_number, m.line_content.trim()).map_err(io_err)?;
+        }
+    }
+    Ok(())
+}
+
+/// `notes show`: a short header followed by the body, or the raw file.
+pub fn show(notes: &[Note], query: &str, raw: bool, out: &mut impl Write) -> CliResult {
+    let note = resolve_note(notes, query)?;
+    if raw {
+        return write!(out, "{}", note.full_file_content).map_err(io_err);
+    }
+
+    let mut header = vec![
+        note.title.clone(),
+        format!("key: {}  path: {}", note.key, note.path.display()),
+    ];
+    if let Some(date) = note.date {
+        header.push(format!("date: {}", date.format("%Y-%m-%d")));
+    }
+    if let NoteType::Paper(ref paper) = note.note_type {
+        let meta = paper.effective_metadata(&note.title);
+        let mut line = format!("bib: {}", meta.bib_key);
+        if let Some(authors) = meta.authors {
+            line.push_str(&format!("  authors: {}", authors));
+        }
+        if let Some(year) = meta.year {
+            line.push_str(&format!("  year: {}", year));
+        }
+        header.push(line);
+    }
+    for parent in &note.parents {
+        let title = notes
+            .iter()
+            .find(|n| &n.key == parent)
+            .map(|n| n.title.as_str())
+            .unwrap_or("?");
+        header.push(format!("parent: {} ({})", title, parent));
+    }
+    if !note.tags.is_empty() {
+        header.push(format!("tags: {}", note.tags.join(", ")));
+    }
+    if !note.time_entries.is_empty() {
+        let minutes: u32 = note.time_entries.iter().map(|e| e.minutes).sum();
+        let count = note.time_entries.len();
+        header.push(format!(
+            "time: {}h{:02}m in {} {}",
+            minutes / 60,
+            minutes % 60,
+            count,
+            if count == 1 { "entry" } else { "entries" }
+        ));
+    }
+
+    writeln!(out, "{}", header.join("\n")).map_err(io_err)?;
+    writeln!(out).map_err(io_err)?;
+    writeln!(out, "{}", note.raw_content.trim()).map_err(io_err)
+}
+
+/// Options for `notes add`.
+#[derive(Debug, Default, Clone)]
+pub struct AddOptions {
+    /// Path under the notes directory; defaults to the suggested filename.
+    pub filename: Option<String>,
+    /// Add the paper even if it already matches a local note.
+    pub force: bool,
+    /// Note template for the body, by name.
+    pub template: Option<String>,
+}
+
+/// `notes add`: the smart-add pipeline from the terminal. Detects the input
+/// type, refuses inputs that already match a note, looks the paper up and
+/// writes the note, printing its key and path.
+pub async fn add(
+    notes: &[Note],
+    notes_dir: &Path,
+    templates_dir: &Path,
+    input: &str,
+    opts: &AddOptions,
+    out: &mut impl Write,
+) -> CliResult {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("Input is empty".to_string());
+    }
+    // Fail on a misspelled template before any lookups
+    note_templates::load_optional(templates_dir, opts.template.as_deref())?;
+    let input_type = detect_input_type(input);
+
+    if let Some(found) = search_local_for_match(notes, input, &input_type) {
+        if !opts.force {
+            return Err(format!(
+                "Already in notes ({} match): {}  {}\nUse --force to add it anyway",
+                found.match_type, found.key, found.title
+            ));
+        }
+    }
+
+    let external = lookup_external(&crate::http::shared_client(), None, &input_type)
+        .await
+        .ok_or_else(|| format!("No metadata found for '{}'", input))?;
+    let bibtex = external.bibtex.ok_or_else(|| {
+        format!(
+            "{} has no BibTeX for '{}'; add it from the web UI instead",
+            external.source, external.title
+        )
+    })?;
+
+    let (arxiv_id, doi) = match input_type {
+        InputType::ArxivUrl { arxiv_id } => (Some(arxiv_id), None),
+        InputType::DoiUrl { doi } => (None, Some(doi)),
+        _ => (None, None),
+    };
+    let filename = opts.filename.clone().unwrap_or(external.suggested_filename);
+    let template = opts.template.clone();
+    let request = SmartAddCreateRequest { bibtex, filename, arxiv_id, doi, template };
+    let key = create_paper_note(notes_dir, templates_dir, &request, |k| notes.iter().any(|n| n.key == k))?;
+    writeln!(out, "{}  {}", key, notes_dir.join(request.filename.trim()).display()).map_err(io_err)
+}
+
+/// Options for `notes capture`.
+#[derive(Debug, Clone)]
+pub struct CaptureOptions {
+    /// Defaults to the first line of the captured text.
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    /// Commit the new note to the notes git repository.
+    pub commit: bool,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            title: None,
+            tags: vec!["inbox".to_string()],
+            commit: true,
+        }
+    }
+}
+
+/// Directory (under the notes directory) that captured notes go into.
+pub const CAPTURE_DIR: &str = "inbox";
+
+/// `notes capture`: write `text` (usually stdin) to a new dated note under
+/// `inbox/` and print only its key, so it composes in scripts.
+pub fn capture(
+    notes: &[Note],
+    notes_dir: &Path,
+    text: &str,
+    opts: &CaptureOptions,
+    now: chrono::DateTime<chrono::FixedOffset>,
+    out: &mut impl Write,
+) -> CliResult {
+    let body = text.trim();
+    if body.is_empty() {
+        return Err("Nothing to capture (stdin was empty)".to_string());
+    }
+    let title = match opts.title {
+        Some(ref title) => title.split_whitespace().collect::<Vec<_>>().join(" "),
+        None => {
+            let first = body.lines().next().unwrap_or_default();
+            let first = first.trim_start_matches('#').trim();
+            match first.char_indices().nth(80) {
+                Some((cut, _)) => format!("{}…", first[..cut].trim_end()),
+                None => first.to_string(),
+            }
+        }
+    };
+    if title.is_empty() {
+        return Err("Title is empty".to_string());
+    }
+
+    let date = now.format("%Y-%m-%d").to_string();
+    let slug = generate_suggested_filename(&title);
+    let stem = match slug.trim_end_matches(".md") {
+        "" => date.clone(),
+        slug => format!("{}-{}", date, slug),
+    };
+    let dir = Path::new(CAPTURE_DIR);
+    let mut relative = dir.join(format!("{}.md", stem));
+    let mut n = 2;
+    while notes_dir.join(&relative).exists() {
+        relative = dir.join(format!("{}-{}.md", stem, n));
+        n += 1;
+    }
+
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let mut content = new_note_frontmatter(&title, &key, now);
+    content.push_str(&format!("date: {}\n", date));
+    let tags: Vec<&str> = opts.tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()).collect();
+    if !tags.is_empty() {
+        content.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    }
+    content.push_str(&format!("---\n\n{}\n", body));
+
+    let path = validate_path_within(&notes_dir.to_path_buf(), &notes_dir.join(&relative))?;
+    crate::encryption::write_file(&path, &content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    tracing::info!("Captured {}", relative.display());
+    if opts.commit {
+        commit_change(notes_dir, &relative, &[&relative], &format!("captured '{}'", title))?;
+    }
+    writeln!(out, "{}", key).map_err(io_err)
+}
+
+/// Parse a duration such as `45`, `45m`, `2h`, `1h30m`, `1.5h` or `1:30`
+/// into minutes.
+pub fn parse_duration(s: &str) -> Option<u32> {
+    let s = s.trim().to_lowercase();
+    if let Some((h, m)) = s.split_once(':') {
+        let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+        return (m < 60).then_some(h * 60 + m);
+    }
+    let minutes = match s.split_once('h') {
+        Some((h, rest)) => {
+            let hours: f64 = h.parse().ok()?;
+            let rest = rest.trim_end_matches(['m', 'i', 'n']);
+            let extra: u32 = if rest.is_empty() { 0 } else { rest.parse().ok()? };
+            (hours * 60.0).round() as u32 + extra
+        }
+        None => s.trim_end_matches("min").trim_end_matches('m').parse().ok()?,
+    };
+    Some(minutes)
+}
+
+/// `notes time`: append `entry` to a note's `time:` block (the same patch
+/// the web UI applies) and optionally commit it.
+pub fn log_time(
+    notes: &[Note],
+    notes_dir: &Path,
+    query: &str,
+    entry: &TimeEntry,
+    commit: bool,
+    out: &mut impl Write,
+) -> CliResult {
+    if entry.minutes == 0 {
+        return Err("Minutes must be positive".to_string());
+    }
+    let note = resolve_note(notes, query)?;
+    crate::formats::check_writable(&note.path)?;
+    let full_path = notes_dir.join(&note.path);
+    let content = std::fs::read_to_string(&full_path)
+        .map_err(|e| format!("Failed to read note: {}", e))?;
+    let new_content = append_time_entry(&content, entry)?;
+    crate::encryption::write_file(&full_path, &new_content)?;
+
+    let summary = format!("logged {} min on '{}'", entry.minutes, note.title);
+    if commit {
+        let now = crate::timezone::now();
+        let message = format!("{}: {}", summary, now.format("%a %b %d, %-I:%M%p"));
+        git(notes_dir, &["add", &note.path.to_string_lossy()])
+            .and_then(|_| git(notes_dir, &["commit", "-m", &message]))
+            .map_err(|e| format!("{}, but not committed: {}", summary, e))?;
+    }
+    writeln!(out, "{} ({})", summary, note.key).map_err(io_err)
+}
+
+fn git(dir: &Path, args: &[&str]) -> CliResult {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Output formats for `notes graph`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GraphFormat {
+    #[default]
+    Table,
+    Dot,
+}
+
+/// Open the graph index and reconcile it with `notes`, as the server does
+/// at startup. If a running server holds the database lock, index into a
+/// temporary database instead; manual edges are then missing.
+pub fn open_graph_index(config: &Config, notes: &[Note]) -> Result<crate::store::Db, String> {
+    let db = match crate::store::open(config.db_backend, &config.db_path) {
+        Ok(db) => db,
+        Err(e) => {
+            tracing::warn!(error = %e, "Database is in use; graph omits manual edges");
+            crate::store::Db::temporary()
+        }
+    };
+    graph_index::reconcile(&db, notes)?;
+    Ok(db)
+}
+
+/// `notes graph`: run a graph query (the `/graph` query language) and print
+/// the matching nodes and edges.
+pub fn graph(db: &crate::store::Db, query: &str, format: GraphFormat, out: &mut impl Write) -> CliResult {
+    let query = GraphQuery::parse(query);
+    let graph = query_graph(&query, db);
+    match format {
+        GraphFormat::Table => write_graph_table(&graph, &query.describe(), out),
+        GraphFormat::Dot => write_graph_dot(&graph, out),
+    }
+    .map_err(io_err)
+}
+
+fn write_graph_table(graph: &KnowledgeGraph, description: &str, out: &mut impl Write) -> std::io::Result<()> {
+    let mut nodes: Vec<_> = graph.nodes.iter().collect();
+    nodes.sort_by_key(|n| n.title.to_lowercase());
+    let titles: std::collections::HashMap<&str, &str> =
+        graph.nodes.iter().map(|n| (n.id.as_str(), n.title.as_str())).collect();
+
+    write!(out, "# {} nodes, {} edges", graph.nodes.len(), graph.edges.len())?;
+    if !description.is_empty() {
+        write!(out, " ({})", description)?;
+    }
+    writeln!(out)?;
+    for node in nodes {
+        writeln!(
+            out,
+            "{}  {:<5}  in {:>3}  out {:>3}  {}",
+            node.id, node.node_type, node.in_degree, node.out_degree, node.title
+        )?;
+    }
+
+    if !graph.edges.is_empty() {
+        writeln!(out)?;
+    }
+    let mut edges: Vec<_> = graph.edges.iter().collect();
+    edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+    for edge in edges {
+        let title = |key: &str| titles.get(key).copied().unwrap_or("?").to_string();
+        write!(
+            out,
+            "{} {} -> {} {}  [{}",
+            edge.source,
+            title(&edge.source),
+            edge.target,
+            title(&edge.target),
+            edge.edge_type
+        )?;
+        if edge.weight > 1 {
+            write!(out, " x{}", edge.weight)?;
+        }
+        write!(out, "]")?;
+        if let Some(ref annotation) = edge.annotation {
+            write!(out, "  \"{}\"", annotation)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn dot_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', " "))
+}
+
+fn write_graph_dot(graph: &KnowledgeGraph, out: &mut impl Write) -> std::io::Result<()> {
+    writeln!(out, "digraph notes {{")?;
+    writeln!(out, "  node [shape=box];")?;
+    for node in &graph.nodes {
+        let shape = if node.node_type == "paper" { ", shape=ellipse" } else { "" };
+        writeln!(out, "  {} [label={}{}];", dot_string(&node.id), dot_string(&node.title), shape)?;
+    }
+    for edge in &graph.edges {
+        let label = match edge.annotation {
+            Some(ref a) => format!("{}: {}", edge.edge_type, a),
+            None => edge.edge_type.clone(),
+        };
+        let style = if edge.edge_type == "parent" { ", style=dashed" } else { "" };
+        writeln!(
+            out,
+            "  {} -> {} [label={}{}];",
+            dot_string(&edge.source),
+            dot_string(&edge.target),
+            dot_string(&label),
+            style
+        )?;
+    }
+    writeln!(out, "}}")
+}
+
+/// `notes bib`: the filtered bibliography, sorted by bib key. With
+/// `output`, the file is only rewritten when its contents change, so Make
+/// doesn't rebuild papers needlessly.
+pub fn bib(notes: &[Note], filter: &BibFilter, output: Option<&Path>, out: &mut impl Write) -> CliResult {
+    let papers = filter_bibliography(notes, filter);
+    if papers.is_empty() {
+        tracing::warn!("No papers match the filter");
+    }
+    let bib = generate_bibliography(&papers);
+    let Some(path) = output else {
+        return write!(out, "{}", bib).map_err(io_err);
+    };
+    if std::fs::read_to_string(path).is_ok_and(|existing| existing == bib) {
+        tracing::info!(papers = papers.len(), "{} is up to date", path.display());
+        return Ok(());
+    }
+    std::fs::write(path, &bib).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    tracing::info!(papers = papers.len(), "Wrote {}", path.display());
+    Ok(())
+}
+
+/// `notes doctor`: lint every note file and print the report as text or
+/// JSON. Fails (exit 1) when there are errors, so it can gate scripts.
+pub fn doctor(config: &Config, json: bool, out: &mut impl Write) -> CliResult {
+    if !config.notes_dir.is_dir() {
+        return Err(format!("Notes directory {} does not exist", config.notes_dir.display()));
+    }
+    let categories = crate::time_categories::resolve_time_categories(
+        &config.time_categories,
+        &config.time_categories_path(),
+    );
+    let pdfs_dir = config.storage.backend.is_none().then_some(config.pdfs_dir.as_path());
+    let report = doctor::check_corpus(&config.notes_dir, pdfs_dir, &categories);
+    if json {
+        let text = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+        writeln!(out, "{}", text).map_err(io_err)?;
+    } else {
+        write!(out, "{}", report.to_text()).map_err(io_err)?;
+    }
+    match report.errors {
+        0 => Ok(()),
+        n => Err(format!("doctor found {} errors", n)),
+    }
+}
+
+/// Options for `notes mv`.
+#[derive(Debug, Clone, Default)]
+pub struct MoveOptions {
+    /// Print the plan without touching anything.
+    pub dry_run: bool,
+    /// Commit the move to the notes git repository.
+    pub commit: bool,
+}
+
+/// `notes mv`: move a note or a directory of notes, rewrite every `[@key]`
+/// and `parent:` reference to the moved notes' new keys, carry manual
+/// edges and shares over in the database, and commit it all at once. The
+/// server must be stopped, since it holds the database.
+pub fn mv(config: &Config, from: &Path, to: &Path, opts: &MoveOptions, out: &mut impl Write) -> CliResult {
+    let notes = load_notes(config)?;
+    let plan = note_move::plan_move(&config.notes_dir, &notes, from, to)?;
+    for m in &plan.moved {
+        let keys = match m.old_key == m.new_key {
+            true => m.old_key.clone(),
+            false => format!("{} -> {}", m.old_key, m.new_key),
+        };
+        writeln!(out, "{}  {} -> {}", keys, m.from.display(), m.to.display()).map_err(io_err)?;
+    }
+    writeln!(
+        out,
+        "{} rewritten in {}",
+        plural(plan.references, "reference"),
+        plural(plan.rewrites.len(), "note")
+    )
+    .map_err(io_err)?;
+    if opts.dry_run {
+        return Ok(());
+    }
+
+    let db = crate::store::open(config.db_backend, &config.db_path)
+        .map_err(|e| format!("Cannot open database ({}); stop the server before moving notes", e))?;
+    note_move::apply_move(&config.notes_dir, &plan)?;
+    let renames = plan.renames();
+    let records = graph_index::rename_keys(&db, &renames)?;
+    let shares = crate::shared::rename_source_keys(&db, &renames);
+    graph_index::reconcile(&db, &load_all_notes(&config.notes_dir))?;
+    db.flush().map_err(|e| e.to_string())?;
+    tracing::info!(records, shares, "Database updated");
+
+    if opts.commit {
+        let summary = format!("moved {} to {}", plan.from.display(), plan.to.display());
+        let mut changed = vec![plan.to.as_path()];
+        changed.extend(plan.rewrites.iter().map(|(path, _)| path.as_path()));
+        commit_change(&config.notes_dir, &plan.from, &changed, &summary)?;
+    }
+    Ok(())
+}
+
+/// Commit a structural change in one go: `removed` (a file or directory)
+/// leaves the index, `changed` paths are staged. Skipped with a warning
+/// when the notes directory isn't a git repository.
+fn commit_change(notes_dir: &Path, removed: &Path, changed: &[&Path], summary: &str) -> CliResult {
+    if git(notes_dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+        tracing::warn!("{} is not a git repository; not committing", notes_dir.display());
+        return Ok(());
+    }
+    let now = crate::timezone::now();
+    let message = format!("{}: {}", summary, now.format("%a %b %d, %-I:%M%p"));
+    let removed = removed.to_string_lossy();
+    let changed: Vec<String> = changed.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    let mut add = vec!["add", "-A", "--"];
+    add.extend(changed.iter().map(String::as_str));
+    git(notes_dir, &["rm", "-r", "-q", "--cached", "--ignore-unmatch", "--", &removed])
+        .and_then(|_| git(notes_dir, &add))
+        .and_then(|_| git(notes_dir, &["commit", "-m", &message]))
+        .map_err(|e| format!("{}, but not committed: {}", summary, e))
+}
+
+/// Options for `notes backfill-created`.
+#[derive(Debug, Clone, Default)]
+pub struct BackfillOptions {
+    /// Print the dates without touching anything.
+    pub dry_run: bool,
+    /// Commit the backfilled notes to the notes git repository.
+    pub commit: bool,
+}
+
+/// Date of the commit that added `path`, following renames.
+fn first_commit_date(notes_dir: &Path, path: &Path) -> Option<chrono::NaiveDate> {
+    let output = std::process::Command::new("git")
+        .args(["log", "--follow", "--diff-filter=A", "--format=%as", "--"])
+        .arg(path)
+        .current_dir(notes_dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    chrono::NaiveDate::parse_from_str(stdout.lines().last()?.trim(), "%Y-%m-%d").ok()
+}
+
+/// `notes backfill-created`: give every note without a `created:` date the
+/// date of the commit that added its file. Notes git doesn't know about
+/// are left alone.
+pub fn backfill_created(config: &Config, opts: &BackfillOptions, out: &mut impl Write) -> CliResult {
+    let notes_dir = &config.notes_dir;
+    if git(notes_dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+        return Err(format!("{} is not a git repository", notes_dir.display()));
+    }
+    let notes = load_notes(config)?;
+    let mut changed = Vec::new();
+    let mut untracked = 0;
+    for note in notes.iter().filter(|n| n.created.is_none()) {
+        let Some(date) = first_commit_date(notes_dir, &note.path) else {
+            untracked += 1;
+            continue;
+        };
+        writeln!(out, "{}  {}  {}", note.key, date.format("%Y-%m-%d"), note.title).map_err(io_err)?;
+        if !opts.dry_run {
+            let full_path = notes_dir.join(&note.path);
+            let content = std::fs::read_to_string(&full_path)
+                .map_err(|e| format!("Failed to read {}: {}", note.path.display(), e))?;
+            let updated = set_frontmatter_field(&content, "created", Some(&date.format("%Y-%m-%d").to_string()))
+                .map_err(|e| format!("{}: {}", note.path.display(), e))?;
+            crate::encryption::write_file(&full_path, &updated)
+                .map_err(|e| format!("{}: {}", note.path.display(), e))?;
+        }
+        changed.push(note.path.clone());
+    }
+    writeln!(
+        out,
+        "{} backfilled, {} not in git",
+        plural(changed.len(), "note"),
+        untracked
+    )
+    .map_err(io_err)?;
+
+    if opts.dry_run || !opts.commit || changed.is_empty() {
+        return Ok(());
+    }
+    let now = crate::timezone::now();
+    let summary = format!("backfilled created dates of {}", plural(changed.len(), "note"));
+    let message = format!("{}: {}", summary, now.format("%a %b %d, %-I:%M%p"));
+    let changed: Vec<String> = changed.iter().map(|p| p.to_string_lossy().to_string()).collect();
+    let mut add = vec!["add", "--"];
+    add.extend(changed.iter().map(String::as_str));
+    git(notes_dir, &add)
+        .and_then(|_| git(notes_dir, &["commit", "-m", &message]))
+        .map_err(|e| format!("{}, but not committed: {}", summary, e))
+}
+
+/// `notes migrate-keys`: give every note without an `id:` its long key,
+/// rewrite references and record redirects from the old keys. Opens the
+/// database, so the server must be stopped. Takes the same options as
+/// `backfill-created`.
+pub fn migrate_keys(config: &Config, opts: &BackfillOptions, out: &mut impl Write) -> CliResult {
+    let notes = load_notes(config)?;
+    let plan = key_migration::plan_migration(&notes);
+    for pinned in &plan.pinned {
+        writeln!(out, "{} -> {}  {}", pinned.old_key, pinned.new_key, pinned.path.display()).map_err(io_err)?;
+    }
+    for key in &plan.ambiguous {
+        writeln!(out, "{} is shared by several notes; references to it are left alone", key).map_err(io_err)?;
+    }
+    writeln!(
+        out,
+        "{} pinned, {} rewritten in {}",
+        plural(plan.pinned.len(), "note"),
+        plural(plan.references, "reference"),
+        plural(plan.rewrites.len(), "note")
+    )
+    .map_err(io_err)?;
+    if opts.dry_run || plan.rewrites.is_empty() {
+        return Ok(());
+    }
+
+    let db = crate::store::open(config.db_backend, &config.db_path)
+        .map_err(|e| format!("Cannot open database ({}); stop the server before migrating keys", e))?;
+    key_migration::apply_migration(&config.notes_dir, &plan)?;
+    let renames = plan.renames();
+    key_migration::save_redirects(&db, &renames)?;
+    let records = graph_index::rename_keys(&db, &renames)?;
+    let shares = crate::shared::rename_source_keys(&db, &renames);
+    graph_index::reconcile(&db, &load_all_notes(&config.notes_dir))?;
+    db.flush().map_err(|e| e.to_string())?;
+    tracing::info!(records, shares, "Database updated");
+
+    if !opts.commit || git(&config.notes_dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+        return Ok(());
+    }
+    let summary = format!("pinned long keys of {}", plural(plan.pinned.len(), "note"));
+    let message = format!("{}: {}", summary, crate::timezone::now().format("%a %b %d, %-I:%M%p"));
+    let changed: Vec<String> = plan.rewrites.iter().map(|(p, _)| p.to_string_lossy().to_string()).collect();
+    let mut add = vec!["add", "--"];
+    add.extend(changed.iter().map(String::as_str));
+    git(&config.notes_dir, &add)
+        .and_then(|_| git(&config.notes_dir, &["commit", "-m", &message]))
+        .map_err(|e| format!("{}, but not committed: {}", summary, e))
+}
+
+/// Options for `notes dedupe`.
+#[derive(Debug, Clone)]
+pub struct DedupeOptions {
+    /// Minimum Jaccard similarity for title and body matches.
+    pub threshold: f64,
+    /// Ask about each pair and merge the ones confirmed.
+    pub merge: bool,
+    /// Commit each merge to the notes git repository.
+    pub commit: bool,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        Self {
+            threshold: 0.8,
+            merge: false,
+            commit: true,
+        }
+    }
+}
+
+/// `notes dedupe`: report likely duplicate pairs. With `merge`, ask about
+/// each pair (answers are read from `input`); merging deletes one note and
+/// retargets its references at the other, like `notes mv`, so the server
+/// must be stopped.
+pub fn dedupe(config: &Config, opts: &DedupeOptions, input: &mut impl BufRead, out: &mut impl Write) -> CliResult {
+    if !(0.0..=1.0).contains(&opts.threshold) {
+        return Err("Threshold must be between 0 and 1".to_string());
+    }
+    let mut notes = load_notes(config)?;
+    let pairs = dedupe::find_duplicates(&notes, opts.threshold);
+    if pairs.is_empty() {
+        return writeln!(out, "No duplicates found").map_err(io_err);
+    }
+    if !opts.merge {
+        for pair in &pairs {
+            let line = |key: &str| notes.iter().find(|n| n.key == key).map(note_line).unwrap_or_default();
+            writeln!(out, "{:<14}{}\n{:<14}{}", pair.describe(), line(&pair.a), "", line(&pair.b))
+                .map_err(io_err)?;
+        }
+        return writeln!(out, "{}", plural(pairs.len(), "possible duplicate")).map_err(io_err);
+    }
+
+    let db = crate::store::open(config.db_backend, &config.db_path)
+        .map_err(|e| format!("Cannot open database ({}); stop the server before merging notes", e))?;
+    let mut merged = 0;
+    for (n, pair) in pairs.iter().enumerate() {
+        let find = |key: &str| notes.iter().find(|note| note.key == key).cloned();
+        // Skip pairs involving a note merged away earlier in the session
+        let (Some(a), Some(b)) = (find(&pair.a), find(&pair.b)) else {
+            continue;
+        };
+        writeln!(out, "[{}/{}] {}", n + 1, pairs.len(), pair.describe()).map_err(io_err)?;
+        writeln!(out, "  a  {}\n  b  {}", note_line(&a), note_line(&b)).map_err(io_err)?;
+        write!(out, "Keep [a], keep [b], [s]kip or [q]uit? ").map_err(io_err)?;
+        out.flush().map_err(io_err)?;
+        let mut answer = String::new();
+        if input.read_line(&mut answer).map_err(io_err)? == 0 {
+            break;
+        }
+        let (keep, drop) = match answer.trim() {
+            "a" => (a, b),
+            "b" => (b, a),
+            "q" => break,
+            _ => continue,
+        };
+        let plan = match dedupe::plan_merge(&notes, &keep, &drop) {
+            Ok(plan) => plan,
+            Err(e) => {
+                writeln!(out, "{}", e).map_err(io_err)?;
+                continue;
+            }
+        };
+        dedupe::apply_merge(&config.notes_dir, &plan)?;
+        graph_index::rename_keys(&db, &plan.renames)?;
+        crate::shared::rename_source_keys(&db, &plan.renames);
+        let summary = format!("merged '{}' into '{}'", drop.title, keep.title);
+        writeln!(out, "{} ({} retargeted)", summary, plural(plan.references, "reference")).map_err(io_err)?;
+        if opts.commit {
+            let changed: Vec<&Path> = plan.rewrites.iter().map(|(path, _)| path.as_path()).collect();
+            commit_change(&config.notes_dir, &plan.removed, &changed, &summary)?;
+        }
+        merged += 1;
+        notes = load_all_notes(&config.notes_dir);
+    }
+    if merged > 0 {
+        graph_index::reconcile(&db, &notes)?;
+        db.flush().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Load every note from the configured content directory.
+pub fn load_notes(config: &Config) -> Result<Vec<Note>, String> {
+    if !config.notes_dir.is_dir() {
+        return Err(format!(
+            "Notes directory {} does not exist",
+            config.notes_dir.display()
+        ));
+    }
+    Ok(load_all_notes(&config.notes_dir))
+}
+
+/// `notes upload-pdfs`: copy every file under `pdfs_dir` that the
+/// configured object storage doesn't have yet. Local files are kept.
+pub async fn upload_pdfs(config: &Config, dry_run: bool, out: &mut impl Write) -> CliResult {
+    let storage = crate::storage::Storage::from_config(&config.storage, &config.pdfs_dir)?;
+    if storage.local_dir().is_some() {
+        return Err("No [storage] backend is configured".to_string());
+    }
+    let mut files: Vec<(String, PathBuf)> = walkdir::WalkDir::new(&config.pdfs_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(&config.pdfs_dir).ok()?;
+            let name = rel.components().map(|c| c.as_os_str().to_str()).collect::<Option<Vec<_>>>()?.join("/");
+            Some((name, e.path().to_path_buf()))
+        })
+        .collect();
+    files.sort();
+
+    let (mut uploaded, mut present) = (0, 0);
+    for (name, path) in files {
+        if storage.exists(&name).await {
+            present += 1;
+            continue;
+        }
+        if !dry_run {
+            storage.copy_from(&name, &path).await.map_err(|e| format!("{}: {}", name, e))?;
+        }
+        uploaded += 1;
+        writeln!(out, "{}", name).map_err(io_err)?;
+    }
+    let verb = if dry_run { "would upload" } else { "uploaded" };
+    writeln!(out, "{} {} file(s); {} already stored", verb, uploaded, present).map_err(io_err)?;
+    Ok(())
+}
+
+/// `notes migrate-db`: copy the configured database into `to`, which is
+/// created next to it under `db_path` and must be empty.
+pub fn migrate_db(config: &Config, to: DbBackend, out: &mut impl Write) -> CliResult {
+    let name = |backend| match backend {
+        DbBackend::Sled => "sled",
+        DbBackend::Sqlite => "sqlite",
+    };
+    if to == config.db_backend {
+        return Err(format!("The database already uses {}", name(to)));
+    }
+    let from = crate::store::open(config.db_backend, &config.db_path)
+        .map_err(|e| format!("Cannot open database ({}); stop the server before migrating", e))?;
+    let target = crate::store::open(to, &config.db_path).map_err(|e| e.to_string())?;
+    let occupied = target.tree_names().iter().any(|tree| target.open_tree(tree).is_ok_and(|t| !t.is_empty()));
+    if occupied {
+        return Err(format!("The {} database in {} is not empty", name(to), config.db_path.display()));
+    }
+    let copied = crate::store::copy_all(&from, &target).map_err(|e| e.to_string())?;
+    writeln!(out, "Copied {} from {} to {}", plural(copied, "record"), name(config.db_backend), name(to)).map_err(io_err)?;
+    writeln!(out, "Set db_backend = \"{}\" (or NOTES_DB_BACKEND) to use it", name(to)).map_err(io_err)?;
+    Ok(())
+}
+
+/// `notes restore`: unpack a backup archive into the configured
+/// directories (the server must be stopped).
+pub fn restore(config: &Config, archive: &Path, force: bool) -> CliResult {
+    let file = std::fs::File::open(archive)
+        .map_err(|e| format!("Cannot open {}: {}", archive.display(), e))?;
+    let target = backup::RestoreTarget {
+        notes_dir: &config.notes_dir,
+        pdfs_dir: &config.pdfs_dir,
+        db_path: &config.db_path,
+        db_backend: config.db_backend,
+    };
+    let manifest = backup::restore_backup(std::io::BufReader::new(file), &target, force)?;
+    tracing::info!(
+        created = %manifest.created,
+        content_files = manifest.content_files,
+        pdf_files = manifest.pdf_files,
+        db_records = manifest.db_records,
+        "Restored {}",
+        archive.display()
+    );
+    Ok(())
+}
+
+/// URL of this server's `/healthz` as seen from the same host: the bound
+/// port, under `base_path`, over HTTPS when `[tls]` is on.
+pub fn healthcheck_url(config: &Config) -> String {
+    let (host, port) = config.bind.rsplit_once(':').unwrap_or(("", config.bind.as_str()));
+    let host = match host.trim_start_matches('[').trim_end_matches(']') {
+        "" | "0.0.0.0" => "127.0.0.1".to_string(),
+        "::" => "[::1]".to_string(),
+        h if h.contains(':') => format!("[{}]", h),
+        h => h.to_string(),
+    };
+    let tls = &config.tls;
+    let scheme = if tls.self_signed || (tls.cert.is_some() && tls.key.is_some()) { "https" } else { "http" };
+    format!("{}://{}:{}{}/healthz", scheme, host, port, config.base_path)
+}
+
+/// `notes healthcheck`: exit non-zero unless the running server answers
+/// `/healthz` with 200, e.g. for a container `HEALTHCHECK`.
+pub async fn healthcheck(config: &Config) -> CliResult {
+    let url = healthcheck_url(config);
+    // The server's own certificate may be self-signed or issued for
+    // another name than the loopback address.
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(std::time::Duration::from_secs(3))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(&url).send().await.map_err(|e| format!("{}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("{}: {}", url, response.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::path::PathBuf;
+
+    fn scratch_dir(name: &str) -> TempDir {
+        TempDir::new(&format!("cli-{}", name))
+    }
+
+    fn note(key: &str, title: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            raw_content: format!("\nAbout {}.\nSecond line.", title),
+            full_file_content: format!("---\ntitle: {}\n---\n\nAbout {}.\nSecond line.", title, title),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    fn corpus() -> Vec<Note> {
+        let mut datalog = note("aaa111", "Datalog");
+        datalog.tags = vec!["pl".to_string()];
+        datalog.date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1);
+        let mut child = note("bbb222", "Datalog engines");
+        child.parents = vec!["aaa111".to_string()];
+        child.hidden = true;
+        vec![child, datalog, note("ccc333", "Zettelkasten")]
+    }
+
+    fn output(f: impl FnOnce(&mut Vec<u8>) -> CliResult) -> Result<String, String> {
+        let mut out = Vec::new();
+        f(&mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_resolve_note() {
+        let notes = corpus();
+        assert_eq!(resolve_note(&notes, "ccc333").unwrap().title, "Zettelkasten");
+        assert_eq!(resolve_note(&notes, "ccc333.md").unwrap().title, "Zettelkasten");
+        assert_eq!(resolve_note(&notes, "datalog").unwrap().key, "aaa111");
+        assert_eq!(resolve_note(&notes, "engines").unwrap().key, "bbb222");
+        let err = resolve_note(&notes, "data").unwrap_err();
+        assert!(err.contains("matches 2 notes"));
+        assert!(resolve_note(&notes, "nothing").is_err());
+    }
+
+    #[test]
+    fn test_list_and_search() {
+        let notes = corpus();
+        let listed = output(|out| list(&notes, &ListFilter::default(), out)).unwrap();
+        assert_eq!(
+            listed,
+            "aaa111  2024-03-01  Datalog\n\
+             bbb222  ----------  Datalog engines  (hidden)\n\
+             ccc333  ----------  Zettelkasten\n"
+        );
+        let filter = ListFilter { tag: Some("PL".to_string()), ..Default::default() };
+        assert_eq!(output(|out| list(&notes, &filter, out)).unwrap().lines().count(), 1);
+
+        // Newest first; a note without created: counts from its mtime
+        let mut notes = notes;
+        notes[1].created = chrono::NaiveDate::from_ymd_opt(2024, 1, 1);
+        notes[2].created = chrono::NaiveDate::from_ymd_opt(2025, 1, 1);
+        let filter = ListFilter { sort: NoteSort::Created, ..Default::default() };
+        let keys: Vec<String> = output(|out| list(&notes, &filter, out))
+            .unwrap()
+            .lines()
+            .map(|l| l[..6].to_string())
+            .collect();
+        assert_eq!(keys, vec!["bbb222", "ccc333", "aaa111"]);
+
+        let found = output(|out| search(&notes, "zettel", out)).unwrap();
+        assert_eq!(found, "ccc333  ----------  Zettelkasten\n    2: title: Zettelkasten\n    5: About Zettelkasten.\n");
+        assert!(search(&notes, "nope", &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_show() {
+        let notes = corpus();
+        let shown = output(|out| show(&notes, "engines", false, out)).unwrap();
+        assert!(shown.starts_with("Datalog engines\nkey: bbb222  path: bbb222.md\nparent: Datalog (aaa111)\n\nAbout"));
+        let raw = output(|out| show(&notes, "bbb222", true, out)).unwrap();
+        assert!(raw.starts_with("---\ntitle: Datalog engines\n"));
+    }
+
+    #[tokio::test]
+    async fn test_add_refuses_local_match() {
+        let dir = scratch_dir("add");
+        let err = add(&corpus(), &dir, &dir, "Zettelkasten", &AddOptions::default(), &mut Vec::new())
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("Already in notes"), "{}", err);
+        assert!(err.contains("ccc333"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_create_paper_note() {
+        let dir = scratch_dir("create");
+        let request = SmartAddCreateRequest {
+            bibtex: "@article{knuth1984,\n  title = {Literate Programming},\n  year = {1984}\n}".to_string(),
+            filename: "papers/literate.md".to_string(),
+            arxiv_id: None,
+            doi: Some("10.1093/comjnl/27.2.97".to_string()),
+            template: None,
+        };
+        let templates = dir.join("templates");
+        let key = create_paper_note(&dir, &templates, &request, |k| k.is_empty()).unwrap();
+        let written = std::fs::read_to_string(dir.join("papers/literate.md")).unwrap();
+        assert!(written.starts_with(&format!("---\ntitle: Literate Programming\nid: {}\n", key)));
+        assert!(written.contains("type: paper\nbibtex: |\n  @article{knuth1984,\n"));
+        assert!(written.contains("doi: 10.1093/comjnl/27.2.97\n"));
+        assert!(written.ends_with("---\n\n## Summary\n\n## Key Contributions\n\n## Notes\n\n"));
+
+        let err = create_paper_note(&dir, &templates, &request, |_| false).unwrap_err();
+        assert!(err.contains("already exists"));
+        let escape = SmartAddCreateRequest { filename: "../x.md".to_string(), ..request.clone() };
+        assert_eq!(create_paper_note(&dir, &templates, &escape, |_| false).unwrap_err(), "Invalid filename");
+
+        std::fs::create_dir_all(&templates).unwrap();
+        std::fs::write(templates.join("reading.md"), "---\ntags: [toread]\n---\n## Why read [@{{bib_key}}]\n").unwrap();
+        let templated = SmartAddCreateRequest {
+            filename: "papers/literate-2.md".to_string(),
+            template: Some("reading".to_string()),
+            ..request
+        };
+        create_paper_note(&dir, &templates, &templated, |_| false).unwrap();
+        let written = std::fs::read_to_string(dir.join("papers/literate-2.md")).unwrap();
+        assert!(written.contains("doi: 10.1093/comjnl/27.2.97\ntags: [toread]\n---\n\n## Why read [@knuth1984]\n"));
+        let missing = SmartAddCreateRequest { template: Some("nope".to_string()), ..templated };
+        assert!(create_paper_note(&dir, &templates, &missing, |_| false).unwrap_err().contains("Unknown template"));
+    }
+
+    #[test]
+    fn test_capture() {
+        let dir = scratch_dir("capture");
+        let now = chrono::DateTime::parse_from_rfc3339("2024-05-02T21:15:00-07:00").unwrap();
+        let text = "# Idea: faster joins\n\nIndex the delta relation.\n\n";
+        let printed = output(|out| capture(&[], &dir, text, &CaptureOptions::default(), now, out)).unwrap();
+        let key = printed.trim_end();
+        let path = Path::new("inbox/2024-05-02-idea-faster-joins.md");
+        let written = std::fs::read_to_string(dir.join(path)).unwrap();
+        assert_eq!(
+            written,
+            format!("---\ntitle: Idea: faster joins\nid: {}\ncreated: 2024-05-02T21:15:00-07:00\ndate: 2024-05-02\ntags: [inbox]\n---\n\n# Idea: faster joins\n\nIndex the delta relation.\n", key)
+        );
+        assert_eq!(crate::notes::load_all_notes(&dir)[0].key, key);
+        let (frontmatter, _) = crate::notes::parse_frontmatter(&written);
+        assert_eq!(frontmatter.title.as_deref(), Some("Idea: faster joins"));
+        assert_eq!(frontmatter.tags, vec!["inbox"]);
+
+        capture(&[], &dir, text, &CaptureOptions::default(), now, &mut Vec::new()).unwrap();
+        assert!(dir.join("inbox/2024-05-02-idea-faster-joins-2.md").exists());
+
+        let opts = CaptureOptions {
+            title: Some("Abstract".to_string()),
+            tags: vec!["reading".to_string(), "pl".to_string()],
+            commit: false,
+        };
+        capture(&[], &dir, "Pasted abstract.", &opts, now, &mut Vec::new()).unwrap();
+        let written = std::fs::read_to_string(dir.join("inbox/2024-05-02-abstract.md")).unwrap();
+        assert!(written.contains("tags: [reading, pl]\n"));
+        assert!(capture(&[], &dir, " \n", &CaptureOptions::default(), now, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45"), Some(45));
+        assert_eq!(parse_duration("45m"), Some(45));
+        assert_eq!(parse_duration("45min"), Some(45));
+        assert_eq!(parse_duration("2h"), Some(120));
+        assert_eq!(parse_duration("1h30m"), Some(90));
+        assert_eq!(parse_duration("1.5h"), Some(90));
+        assert_eq!(parse_duration("1:05"), Some(65));
+        assert_eq!(parse_duration("1:75"), None);
+        assert_eq!(parse_duration("soon"), None);
+    }
+
+    #[test]
+    fn test_log_time() {
+        let dir = scratch_dir("time");
+        let mut notes = corpus();
+        std::fs::write(dir.join("ccc333.md"), &notes[2].full_file_content).unwrap();
+        notes[2].path = PathBuf::from("ccc333.md");
+        let entry = TimeEntry {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            minutes: 45,
+            category: crate::models::TimeCategory::Reading,
+            description: Some("skimmed intro".to_string()),
+        };
+        let printed = output(|out| log_time(&notes, &dir, "zettel", &entry, false, out)).unwrap();
+        assert_eq!(printed, "logged 45 min on 'Zettelkasten' (ccc333)\n");
+
+        let written = std::fs::read_to_string(dir.join("ccc333.md")).unwrap();
+        let (frontmatter, _) = crate::notes::parse_frontmatter(&written);
+        assert_eq!(frontmatter.time.len(), 1);
+        assert_eq!(frontmatter.time[0].minutes, 45);
+        assert_eq!(frontmatter.time[0].date, entry.date);
+        assert_eq!(frontmatter.time[0].description.as_deref(), Some("skimmed intro"));
+        assert!(written.ends_with("About Zettelkasten.\nSecond line."));
+
+        let zero = TimeEntry { minutes: 0, ..entry };
+        assert!(log_time(&notes, &dir, "ccc333", &zero, false, &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_graph_output() {
+        let mut notes = corpus();
+        notes[2].full_file_content.push_str("\nSee [@aaa111].");
+        let db = crate::store::Db::temporary();
+        graph_index::reconcile(&db, &notes).unwrap();
+
+        let table = output(|out| graph(&db, "from:ccc333 depth:1", GraphFormat::Table, out)).unwrap();
+        assert!(table.starts_with("# 2 nodes, 1 edges (centered on ccc333, 1 hops)\n"), "{}", table);
+        assert!(table.contains("ccc333 Zettelkasten -> aaa111 Datalog  [crosslink]\n"), "{}", table);
+        assert!(!table.contains("bbb222"));
+
+        let dot = output(|out| graph(&db, "", GraphFormat::Dot, out)).unwrap();
+        assert!(dot.starts_with("digraph notes {\n"));
+        assert!(dot.contains("  \"aaa111\" [label=\"Datalog\"];\n"), "{}", dot);
+        assert!(dot.contains("\"ccc333\" -> \"aaa111\" [label=\"crosslink\"];"), "{}", dot);
+        assert!(dot.contains("style=dashed"));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_bib_leaves_unchanged_file() {
+        let dir = scratch_dir("bib");
+        let mut notes = corpus();
+        notes[0].note_type = NoteType::Paper(crate::models::PaperMeta {
+            bibtex_entries: vec!["@article{a2020,\n  title={A},\n  year={2020}\n}".to_string()],
+            canonical_key: None,
+            sources: vec![],
+            versions: vec![],
+        });
+        let path = dir.join("refs.bib");
+        let printed = output(|out| bib(&notes, &BibFilter::default(), None, out)).unwrap();
+        assert_eq!(printed, "@article{a2020,\n  title={A},\n  year={2020}\n}\n\n");
+
+        bib(&notes, &BibFilter::default(), Some(&path), &mut Vec::new()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), printed);
+        let written = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        bib(&notes, &BibFilter::default(), Some(&path), &mut Vec::new()).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), written);
+
+        let filter = BibFilter { since: Some(2021), ..Default::default() };
+        bib(&notes, &filter, Some(&path), &mut Vec::new()).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn test_mv_rewrites_references() {
+        let dir = scratch_dir("mv");
+        let config = Config {
+            notes_dir: dir.join("content"),
+            db_path: dir.join("db"),
+            ..Config::default()
+        };
+        std::fs::create_dir_all(&config.notes_dir).unwrap();
+        let old_key = crate::notes::generate_key(Path::new("datalog.md"));
+        let new_key = crate::notes::generate_key(Path::new("papers/datalog.md"));
+        std::fs::write(config.notes_dir.join("datalog.md"), "---\ntitle: Datalog\n---\n").unwrap();
+        std::fs::write(
+            config.notes_dir.join("index.md"),
+            format!("---\ntitle: Index\n---\nSee [@{}].\n", old_key),
+        )
+        .unwrap();
+
+        let opts = MoveOptions { dry_run: true, commit: false };
+        let printed = output(|out| mv(&config, Path::new("datalog.md"), Path::new("papers/"), &opts, out)).unwrap();
+        assert_eq!(
+            printed,
+            format!("{} -> {}  datalog.md -> papers/datalog.md\n1 reference rewritten in 1 note\n", old_key, new_key)
+        );
+        assert!(config.notes_dir.join("datalog.md").exists());
+
+        let opts = MoveOptions { dry_run: false, commit: false };
+        mv(&config, Path::new("datalog.md"), Path::new("papers/"), &opts, &mut Vec::new()).unwrap();
+        assert!(config.notes_dir.join("papers/datalog.md").exists());
+        let index = std::fs::read_to_string(config.notes_dir.join("index.md")).unwrap();
+        assert!(index.contains(&format!("[@{}]", new_key)));
+    }
+
+    #[test]
+    fn test_backfill_created() {
+        let dir = scratch_dir("backfill");
+        let config = Config {
+            notes_dir: dir.join("content"),
+            db_path: dir.join("db"),
+            ..Config::default()
+        };
+        let notes_dir = &config.notes_dir;
+        std::fs::create_dir_all(notes_dir).unwrap();
+        std::fs::write(notes_dir.join("old.md"), "---\ntitle: Old\n---\n\nBody\n").unwrap();
+        std::fs::write(notes_dir.join("dated.md"), "---\ntitle: Dated\ncreated: 2020-05-01\n---\n").unwrap();
+        let run = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", "2023-04-05T12:00:00")
+                .env("GIT_COMMITTER_DATE", "2023-04-05T12:00:00")
+                .current_dir(notes_dir)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {:?}", args);
+        };
+        run(&["init", "-q"]);
+        run(&["add", "."]);
+        run(&["commit", "-q", "-m", "init"]);
+        std::fs::write(notes_dir.join("new.md"), "---\ntitle: New\n---\n").unwrap();
+
+        let opts = BackfillOptions { dry_run: true, commit: false };
+        let printed = output(|out| backfill_created(&config, &opts, out)).unwrap();
+        assert!(printed.ends_with("  2023-04-05  Old\n1 note backfilled, 1 not in git\n"));
+        assert!(!std::fs::read_to_string(notes_dir.join("old.md")).unwrap().contains("created"));
+
+        let opts = BackfillOptions { dry_run: false, commit: false };
+        backfill_created(&config, &opts, &mut Vec::new()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(notes_dir.join("old.md")).unwrap(),
+            "---\ntitle: Old\ncreated: 2023-04-05\n---\n\nBody\n"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_report_and_merge() {
+        let dir = scratch_dir("dedupe");
+        let config = Config {
+            notes_dir: dir.join("content"),
+            db_path: dir.join("db"),
+            ..Config::default()
+        };
+        std::fs::create_dir_all(&config.notes_dir).unwrap();
+        let body = "Semi naive evaluation only joins the facts derived in the previous round \
+            against the full relations, so each rule fires on new tuples and the fixpoint is \
+            reached without recomputing everything from scratch on every iteration";
+        std::fs::write(config.notes_dir.join("a.md"), format!("---\ntitle: Semi-naive\n---\n{}\n", body)).unwrap();
+        std::fs::write(config.notes_dir.join("b.md"), format!("---\ntitle: Seminaive eval\n---\n{}\n", body)).unwrap();
+        let b_key = crate::notes::generate_key(Path::new("b.md"));
+        let a_key = crate::notes::generate_key(Path::new("a.md"));
+        std::fs::write(config.notes_dir.join("c.md"), format!("---\ntitle: Index\n---\n[@{}]\n", b_key)).unwrap();
+
+        let report = output(|out| dedupe(&config, &DedupeOptions::default(), &mut "".as_bytes(), out)).unwrap();
+        assert!(report.starts_with("body 100%"), "{}", report);
+        assert!(report.ends_with("1 possible duplicate\n"));
+
+        let opts = DedupeOptions { merge: true, commit: false, ..Default::default() };
+        let printed = output(|out| dedupe(&config, &opts, &mut "a\n".as_bytes(), out)).unwrap();
+        assert!(printed.contains("Keep [a], keep [b], [s]kip or [q]uit?"));
+        assert!(printed.contains("merged 'Seminaive eval' into 'Semi-naive' (1 reference retargeted)"), "{}", printed);
+        assert!(!config.notes_dir.join("b.md").exists());
+        let index = std::fs::read_to_string(config.notes_dir.join("c.md")).unwrap();
+        assert!(index.contains(&format!("[@{}]", a_key)));
+    }
+
+    #[test]
+    fn test_healthcheck_url() {
+        let mut config = Config { bind: "0.0.0.0:3000".to_string(), ..Default::default() };
+        assert_eq!(healthcheck_url(&config), "http://127.0.0.1:3000/healthz");
+
+        config.bind = "[::]:8443".to_string();
+        config.base_path = "/notes".to_string();
+        config.tls.self_signed = true;
+        assert_eq!(healthcheck_url(&config), "https://[::1]:8443/notes/healthz");
+    }
+}
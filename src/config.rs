@@ -0,0 +1,1502 @@
+//! Runtime configuration.
+//!
+//! Settings are resolved in increasing order of precedence:
+//! built-in defaults, `notes.toml`, environment variables, command-line flags.
+//! The resolved `Config` is stored in `AppState` and used by `main` to bind
+//! the server and mount static directories.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::builder::BoolishValueParser;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{NoteSort, SortOrder, TimeCategoryDef};
+use crate::recurring::RecurringNote;
+
+pub const CONFIG_FILE: &str = "notes.toml";
+pub const DEFAULT_BIND: &str = "0.0.0.0:3000";
+pub const DEFAULT_TRASH_DAYS: u32 = 30;
+pub const DEFAULT_DAILY_TEMPLATE: &str = "daily";
+pub const DEFAULT_SENDMAIL: &str = "/usr/sbin/sendmail";
+pub const DEFAULT_VAULT_NAME: &str = "notes";
+
+/// Command-line (and environment) overrides for the config file.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct ConfigArgs {
+    /// Path to the TOML config file [default: notes.toml in the data dir or CWD]
+    #[arg(long, global = true, env = "NOTES_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Base directory for relative notes/pdfs/db paths
+    #[arg(long, global = true, env = "NOTES_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Directory containing markdown notes
+    #[arg(long, global = true, env = "NOTES_DIR")]
+    pub notes_dir: Option<PathBuf>,
+
+    /// Directory containing PDF attachments
+    #[arg(long, global = true, env = "NOTES_PDFS_DIR")]
+    pub pdfs_dir: Option<PathBuf>,
+
+    /// Path of the database directory
+    #[arg(long, global = true, env = "NOTES_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+
+    /// Database engine
+    #[arg(long, global = true, env = "NOTES_DB_BACKEND", value_enum)]
+    pub db_backend: Option<DbBackend>,
+
+    /// Directory of note templates
+    #[arg(long, global = true, env = "NOTES_TEMPLATES_DIR")]
+    pub templates_dir: Option<PathBuf>,
+
+    /// Template for new daily notes (skipped when it doesn't exist)
+    #[arg(long, global = true, env = "NOTES_DAILY_TEMPLATE")]
+    pub daily_template: Option<String>,
+
+    /// Listen address, e.g. 127.0.0.1:8080 or [::]:3000
+    #[arg(long, global = true, env = "NOTES_BIND")]
+    pub bind: Option<String>,
+
+    /// Listen port (replaces the port of the bind address)
+    #[arg(long, global = true, env = "NOTES_PORT")]
+    pub port: Option<u16>,
+
+    /// PEM certificate chain for HTTPS
+    #[arg(long, global = true, env = "NOTES_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for HTTPS
+    #[arg(long, global = true, env = "NOTES_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Serve HTTPS with a generated self-signed certificate
+    #[arg(long, global = true, env = "NOTES_TLS_SELF_SIGNED", value_parser = BoolishValueParser::new())]
+    pub tls_self_signed: bool,
+
+    /// Disable all editing (public mirror)
+    #[arg(long, global = true, env = "NOTES_READONLY", value_parser = BoolishValueParser::new())]
+    pub read_only: bool,
+
+    /// URL prefix when served behind a reverse proxy, e.g. /notes
+    #[arg(long, global = true, env = "NOTES_BASE_PATH")]
+    pub base_path: Option<String>,
+
+    /// Take client IP and scheme from X-Forwarded-For/-Proto headers
+    #[arg(long, global = true, env = "NOTES_TRUST_PROXY", value_parser = BoolishValueParser::new())]
+    pub trust_proxy: bool,
+
+    /// Serve the notes directory over WebDAV at /dav/
+    #[arg(long, global = true, env = "NOTES_WEBDAV", value_parser = BoolishValueParser::new())]
+    pub webdav: bool,
+
+    /// Log filter, e.g. `info` or `warn,notes=debug`
+    #[arg(long, global = true, env = "NOTES_LOG")]
+    pub log_level: Option<String>,
+
+    /// Log output format
+    #[arg(long, global = true, env = "NOTES_LOG_FORMAT", value_enum)]
+    pub log_format: Option<LogFormat>,
+
+    /// Days deleted notes stay in the trash (0 keeps them until emptied)
+    #[arg(long, global = true, env = "NOTES_TRASH_DAYS")]
+    pub trash_days: Option<u32>,
+
+    /// Flag notes untouched for this many months for archival (0 disables)
+    #[arg(long, global = true, env = "NOTES_ARCHIVE_AFTER_MONTHS")]
+    pub archive_after_months: Option<u32>,
+
+    /// IANA timezone pages show times in, e.g. Europe/Berlin [default: the server's]
+    #[arg(long, global = true, env = "NOTES_TIMEZONE")]
+    pub timezone: Option<String>,
+
+    /// Vault subcommands other than serve work on [default: the primary]
+    #[arg(long, global = true, env = "NOTES_VAULT")]
+    pub vault: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// When set, relative `notes_dir`, `pdfs_dir`, `db_path`,
+    /// `templates_dir` and the time categories file are resolved under it.
+    pub data_dir: Option<PathBuf>,
+    pub notes_dir: PathBuf,
+    pub pdfs_dir: PathBuf,
+    pub db_path: PathBuf,
+    /// Engine behind `db_path`: a sled database, or SQLite's
+    /// `notes.sqlite3` inside it.
+    pub db_backend: DbBackend,
+    /// Markdown skeletons offered when creating notes (`note_templates`).
+    pub templates_dir: PathBuf,
+    /// Template `/today` starts new daily notes from, when it exists.
+    pub daily_template: String,
+    /// Socket address the HTTP server listens on.
+    pub bind: String,
+    /// Reject all mutating requests and hide editing UI.
+    pub read_only: bool,
+    /// Serving as a public viewer (`notes publish`); implies `read_only`.
+    pub publish: bool,
+    /// URL prefix the app is mounted under (`""` or e.g. `/notes`).
+    pub base_path: String,
+    /// Trust `X-Forwarded-*` headers from a reverse proxy.
+    pub trust_proxy: bool,
+    /// Serve the notes directory over WebDAV at `/dav/`, for clients that
+    /// log in with the password.
+    pub webdav: bool,
+    /// Time categories; when empty, `time_categories.conf` or the built-in
+    /// set is used instead.
+    pub time_categories: Vec<TimeCategoryDef>,
+    /// Days a deleted note stays in the trash before it is purged; 0 keeps
+    /// it until deleted by hand.
+    pub trash_days: u32,
+    /// Months without changes after which a note is offered for archival on
+    /// `/archive`; 0 turns the policy off.
+    pub archive_after_months: u32,
+    /// IANA timezone times are shown in and "today" is taken from; the
+    /// server's local zone when unset.
+    pub timezone: Option<String>,
+    /// Extensions loaded as notes besides `.md`: `markdown`, `txt`, `org`
+    /// (read-only).
+    pub extensions: Vec<String>,
+    /// Recurring note series (`[[recurring]]` tables).
+    pub recurring: Vec<RecurringNote>,
+    /// Name of the vault held in `notes_dir`, shown in the vault switcher.
+    pub vault_name: String,
+    /// Require a login for every page of this vault, not only for edits.
+    pub require_login: bool,
+    /// Further vaults served alongside this one (`[[vaults]]` tables).
+    pub vaults: Vec<VaultConfig>,
+    pub notify: NotifyConfig,
+    pub habits: HabitsConfig,
+    pub embeddings: EmbeddingsConfig,
+    pub llm: LlmConfig,
+    pub transcription: TranscriptionConfig,
+    pub digest: DigestConfig,
+    pub url_policy: UrlPolicyConfig,
+    pub links: LinksConfig,
+    pub proxy: ProxyConfig,
+    pub rate_limit: RateLimitConfig,
+    pub stats: StatsConfig,
+    pub sort: SortConfig,
+    pub tls: TlsConfig,
+    pub log: LogConfig,
+    pub storage: StorageConfig,
+    pub sync: SyncConfig,
+    pub encryption: EncryptionConfig,
+    pub backup: BackupConfig,
+    pub mirror: MirrorConfig,
+}
+
+/// A `[[vaults]]` table: another content root with its own notes, PDFs
+/// and database under `dir`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VaultConfig {
+    pub name: String,
+    /// Holds the vault's `content/`, `pdfs/` and `.notes_db`; relative to
+    /// `data_dir` when set.
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub require_login: bool,
+}
+
+/// The `[storage]` table: where PDFs and audio memos are kept. Without a
+/// `backend`, they stay in `pdfs_dir`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: Option<StorageBackend>,
+    pub bucket: Option<String>,
+    /// Signing region (default `us-east-1`).
+    pub region: Option<String>,
+    /// Endpoint URL (default `https://s3.<region>.amazonaws.com`); point it
+    /// at any S3-compatible service (MinIO, R2, B2, Garage).
+    pub endpoint: Option<String>,
+    /// Prepended to every object key, e.g. `notes/`.
+    pub prefix: String,
+    /// Address the bucket as `<endpoint>/<bucket>` rather than
+    /// `<bucket>.<endpoint host>`; most self-hosted services need this.
+    pub path_style: bool,
+    /// Environment variables holding the credentials (default
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY`).
+    pub access_key_id_env: Option<String>,
+    pub secret_access_key_env: Option<String>,
+    /// How long the links PDFs are served through stay valid (default 3600).
+    pub presign_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// An S3-compatible object store.
+    S3,
+}
+
+impl StorageConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.backend.is_none() {
+            return Ok(());
+        }
+        if self.bucket.as_deref().is_none_or(|b| b.trim().is_empty()) {
+            return Err("storage: bucket is required".to_string());
+        }
+        if let Some(ref url) = self.endpoint {
+            if !url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some()) {
+                return Err(format!("storage: invalid endpoint '{}'", url));
+            }
+        }
+        if self.prefix.starts_with('/') {
+            return Err("storage: prefix must not start with '/'".to_string());
+        }
+        if self.presign_seconds.is_some_and(|s| s == 0 || s > 604_800) {
+            return Err("storage: presign_seconds must be between 1 and 604800".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[sync]` table: another instance `POST /api/sync` exchanges notes
+/// with. Both instances hold the same token; one with the token but no
+/// `peer` only answers syncs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SyncConfig {
+    /// The peer's URL, including its base path, e.g. `https://notes.example.org`.
+    pub peer: Option<String>,
+    /// Environment variable holding the shared token (default
+    /// `NOTES_SYNC_TOKEN`).
+    pub token_env: Option<String>,
+}
+
+impl SyncConfig {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(ref url) = self.peer {
+            if !url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.host_str().is_some()) {
+                return Err(format!("sync: invalid peer '{}'", url));
+            }
+        }
+        if self.token_env.as_deref().is_some_and(|e| e.trim().is_empty()) {
+            return Err("sync: token_env is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[encryption]` table: where the passphrase for `encrypted: true`
+/// notes comes from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+    /// Environment variable holding the passphrase (default
+    /// `NOTES_ENCRYPTION_PASSPHRASE`).
+    pub passphrase_env: Option<String>,
+}
+
+impl EncryptionConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.passphrase_env.as_deref().is_some_and(|e| e.trim().is_empty()) {
+            return Err("encryption: passphrase_env is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[backup]` table: archives like `GET /api/backup` written on a
+/// schedule. Off unless `schedule` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Cron expression (`minute hour day month weekday`, in the display
+    /// timezone), e.g. `"30 3 * * *"`, or `@daily` / `@hourly` / `@weekly`.
+    pub schedule: Option<String>,
+    /// Directory the archives go in; relative to the data directory.
+    pub dir: PathBuf,
+    /// Upload to the `[storage]` bucket under `backups/` instead of `dir`.
+    pub s3: bool,
+    /// Archives kept; older ones are deleted after each backup.
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { schedule: None, dir: PathBuf::from("backups"), s3: false, keep: 7 }
+    }
+}
+
+impl BackupConfig {
+    fn validate(&self, storage: &StorageConfig) -> Result<(), String> {
+        if let Some(ref schedule) = self.schedule {
+            crate::backup::Schedule::parse(schedule).map_err(|e| format!("backup: {}", e))?;
+        }
+        if self.keep == 0 {
+            return Err("backup: keep must be at least 1".to_string());
+        }
+        if self.s3 && storage.backend.is_none() {
+            return Err("backup: s3 = true needs a [storage] bucket".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[mirror]` table: serve a read-only copy of another instance's
+/// content repository, pulled every `interval_minutes`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MirrorConfig {
+    /// Git URL (or path) of the primary's content repository.
+    pub upstream: Option<String>,
+    /// Branch to follow (default: the upstream's default branch).
+    pub branch: Option<String>,
+    pub interval_minutes: u64,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self { upstream: None, branch: None, interval_minutes: 5 }
+    }
+}
+
+impl MirrorConfig {
+    fn validate(&self) -> Result<(), String> {
+        let flag_like = |v: &Option<String>| v.as_deref().is_some_and(|v| v.trim().is_empty() || v.starts_with('-'));
+        if flag_like(&self.upstream) {
+            return Err(format!("mirror: invalid upstream {:?}", self.upstream.as_deref().unwrap_or_default()));
+        }
+        if flag_like(&self.branch) {
+            return Err(format!("mirror: invalid branch {:?}", self.branch.as_deref().unwrap_or_default()));
+        }
+        if self.interval_minutes == 0 {
+            return Err("mirror: interval_minutes must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[tls]` table. HTTPS is enabled when `cert` and `key` are set, or
+/// when `self_signed` is true.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: Option<PathBuf>,
+    pub key: Option<PathBuf>,
+    pub self_signed: bool,
+    /// Extra DNS names/IPs for the self-signed certificate.
+    pub hostnames: Vec<String>,
+}
+
+/// The `[notify]` table: where `remind:` reminders are sent. Without a
+/// `channel`, reminders are only listed on `/reminders`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub channel: Option<NotifyChannel>,
+    /// URL a webhook is POSTed to, or the ntfy topic URL
+    /// (e.g. `https://ntfy.sh/my-topic`).
+    pub url: Option<String>,
+    /// Recipient of email reminders.
+    pub to: Option<String>,
+    /// Sender of email reminders; sendmail's default when unset.
+    pub from: Option<String>,
+    /// sendmail-compatible program email is handed to.
+    pub sendmail: PathBuf,
+    /// Public URL of the app, for links to the note in reminders.
+    pub base_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyChannel {
+    Webhook,
+    Ntfy,
+    Email,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            channel: None,
+            url: None,
+            to: None,
+            from: None,
+            sendmail: PathBuf::from(DEFAULT_SENDMAIL),
+            base_url: None,
+        }
+    }
+}
+
+impl NotifyConfig {
+    fn validate(&self) -> Result<(), String> {
+        match self.channel {
+            Some(NotifyChannel::Webhook | NotifyChannel::Ntfy) => match self.url {
+                Some(ref url) if url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) => {
+                    Ok(())
+                }
+                Some(ref url) => Err(format!("notify: invalid url '{}'", url)),
+                None => Err("notify: url is required for webhook and ntfy".to_string()),
+            },
+            Some(NotifyChannel::Email) if self.to.as_deref().is_none_or(|t| t.trim().is_empty()) => {
+                Err("notify: to is required for email".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `[habits]` table: daily goals shown on `/habits`; 0 sets none.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HabitsConfig {
+    /// Words to add to notes each day.
+    pub daily_words: u32,
+    /// Minutes of time to log each day.
+    pub daily_minutes: u32,
+}
+
+/// The `[embeddings]` table: the service that turns note bodies into
+/// vectors for `/search?mode=semantic`. Without a `backend`, semantic
+/// search is off.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmbeddingsConfig {
+    pub backend: Option<EmbeddingsBackend>,
+    /// Endpoint URL, e.g. `http://localhost:11434/api/embed` for Ollama or
+    /// `https://api.openai.com/v1/embeddings`.
+    pub url: Option<String>,
+    pub model: Option<String>,
+    /// Environment variable holding the API key, sent as a bearer token.
+    pub api_key_env: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingsBackend {
+    /// Ollama's `/api/embed`.
+    Ollama,
+    /// An OpenAI-compatible `/v1/embeddings`.
+    OpenAI,
+}
+
+impl EmbeddingsConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.backend.is_none() {
+            return Ok(());
+        }
+        match self.url {
+            Some(ref url) if url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) => {}
+            Some(ref url) => return Err(format!("embeddings: invalid url '{}'", url)),
+            None => return Err("embeddings: url is required".to_string()),
+        }
+        if self.model.as_deref().is_none_or(|m| m.trim().is_empty()) {
+            return Err("embeddings: model is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[llm]` table: the model behind summaries, tag suggestions, `/ask`
+/// and the metadata lookup fallbacks. By default the local `claude` CLI is
+/// run; `anthropic` and `openai` call an HTTP API instead, for servers
+/// without the CLI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LlmConfig {
+    pub provider: LlmProvider,
+    /// Model name; required for the API providers.
+    pub model: Option<String>,
+    /// Endpoint URL. Defaults to `https://api.anthropic.com/v1/messages` or
+    /// `https://api.openai.com/v1/chat/completions`; point it at any
+    /// compatible server (e.g. Ollama's `/v1/chat/completions`).
+    pub url: Option<String>,
+    /// Environment variable holding the API key.
+    pub api_key_env: Option<String>,
+    /// CLI to run for the `cli` provider (default `claude`).
+    pub command: Option<String>,
+    /// Most tokens a reply may use (API providers; default 4096).
+    pub max_tokens: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    /// `claude -p`, with the input on standard input.
+    #[default]
+    Cli,
+    /// Anthropic's Messages API.
+    Anthropic,
+    /// An OpenAI-compatible `/v1/chat/completions`.
+    OpenAI,
+}
+
+impl LlmConfig {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(ref url) = self.url {
+            if !url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+                return Err(format!("llm: invalid url '{}'", url));
+            }
+        }
+        if self.provider != LlmProvider::Cli && self.model.as_deref().is_none_or(|m| m.trim().is_empty()) {
+            return Err("llm: model is required".to_string());
+        }
+        if self.command.as_deref().is_some_and(|c| c.trim().is_empty()) {
+            return Err("llm: command is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[transcription]` table: how audio memos attached to notes are
+/// turned into text. Without a `backend`, memos can be attached and played
+/// but not transcribed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TranscriptionConfig {
+    pub backend: Option<TranscriptionBackend>,
+    /// whisper.cpp: path to the ggml model file. OpenAI: model name, e.g.
+    /// `whisper-1`.
+    pub model: Option<String>,
+    /// whisper.cpp binary (default `whisper-cli`).
+    pub command: Option<String>,
+    /// Endpoint URL (default `https://api.openai.com/v1/audio/transcriptions`).
+    pub url: Option<String>,
+    /// Environment variable holding the API key, sent as a bearer token.
+    pub api_key_env: Option<String>,
+    /// Spoken language, e.g. `en`; detected when unset.
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptionBackend {
+    /// A local whisper.cpp binary; audio is converted with `ffmpeg` first.
+    Whisper,
+    /// An OpenAI-compatible `/v1/audio/transcriptions`.
+    OpenAI,
+}
+
+impl TranscriptionConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.backend.is_none() {
+            return Ok(());
+        }
+        if let Some(ref url) = self.url {
+            if !url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) {
+                return Err(format!("transcription: invalid url '{}'", url));
+            }
+        }
+        if self.model.as_deref().is_none_or(|m| m.trim().is_empty()) {
+            return Err("transcription: model is required".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[digest]` table: a weekly summary of changed notes and added
+/// papers, written by the `[llm]` model. Off unless `enabled`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    /// Day of the week the digest of the seven days before it is written.
+    pub weekday: chrono::Weekday,
+    /// Local hour (0-23) on `weekday` from which it is written.
+    pub hour: u32,
+    pub deliver: DigestDelivery,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestDelivery {
+    /// Saved as a note under `digests/`.
+    #[default]
+    Note,
+    /// Sent through the `[notify]` channel.
+    Notify,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            weekday: chrono::Weekday::Mon,
+            hour: 8,
+            deliver: DigestDelivery::Note,
+        }
+    }
+}
+
+impl DigestConfig {
+    fn validate(&self, notify: &NotifyConfig) -> Result<(), String> {
+        if self.hour > 23 {
+            return Err(format!("digest: invalid hour {}", self.hour));
+        }
+        if self.enabled && self.deliver == DigestDelivery::Notify && notify.channel.is_none() {
+            return Err("digest: deliver = \"notify\" needs a [notify] channel".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `[url_policy]` table: which URLs Smart Add fetches and PDF
+/// downloads may reach. Internal addresses are always refused.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlPolicyConfig {
+    /// Schemes Smart Add may fetch pages over.
+    pub schemes: Vec<String>,
+    /// Smart Add fetches only from known academic domains and `allow`.
+    pub allowlist_only: bool,
+    /// Schemes PDFs may be downloaded over.
+    pub pdf_schemes: Vec<String>,
+    /// PDF downloads only from known academic domains and `allow`.
+    pub pdf_allowlist_only: bool,
+    /// Domains (and their subdomains) added to the allowlist.
+    pub allow: Vec<String>,
+    /// Hosts (and their subdomains) never fetched.
+    pub deny_hosts: Vec<String>,
+    /// Address ranges never fetched, e.g. `203.0.113.0/24`.
+    pub deny_cidrs: Vec<String>,
+}
+
+impl Default for UrlPolicyConfig {
+    fn default() -> Self {
+        Self {
+            schemes: vec!["https".to_string()],
+            allowlist_only: true,
+            pdf_schemes: vec!["https".to_string(), "http".to_string()],
+            pdf_allowlist_only: false,
+            allow: Vec::new(),
+            deny_hosts: Vec::new(),
+            deny_cidrs: Vec::new(),
+        }
+    }
+}
+
+impl UrlPolicyConfig {
+    fn validate(&self) -> Result<(), String> {
+        for scheme in self.schemes.iter().chain(&self.pdf_schemes) {
+            if !matches!(scheme.to_lowercase().as_str(), "http" | "https") {
+                return Err(format!("url_policy: unsupported scheme '{}'", scheme));
+            }
+        }
+        if self.schemes.is_empty() || self.pdf_schemes.is_empty() {
+            return Err("url_policy: schemes can't be empty".to_string());
+        }
+        for cidr in &self.deny_cidrs {
+            crate::url_validator::Cidr::parse(cidr).map_err(|e| format!("url_policy: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// The `[links]` table: the dead link checker behind `/links`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinksConfig {
+    /// Days after which the server checks a link again, once a day; 0
+    /// leaves checking to the button on `/links`.
+    pub check_days: u32,
+    /// Ask the Wayback Machine to archive `url:` sources as they are
+    /// attached, and record the snapshot as `wayback:`.
+    pub wayback: bool,
+}
+
+/// The `[sort]` table: how `/`, `/papers` and `/api/notes/list` are
+/// ordered without `?sort=`/`?order=`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SortConfig {
+    pub by: NoteSort,
+    /// The sort's natural order when unset (titles A-Z, the rest descending).
+    pub order: Option<SortOrder>,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            by: NoteSort::Modified,
+            order: None,
+        }
+    }
+}
+
+/// The `[stats]` table: view counting for `/stats`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    /// Count views made while logged in too, not only visitors'.
+    pub count_logged_in: bool,
+}
+
+/// The `[proxy]` table: a proxy for all outbound HTTP. Without `url`, the
+/// `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` and `NO_PROXY` environment
+/// variables are followed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// e.g. `http://proxy.example.com:3128`, with `user:pass@` if needed.
+    pub url: Option<String>,
+    /// Hosts, domains and CIDRs reached directly, like `NO_PROXY`.
+    pub no_proxy: Vec<String>,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            no_proxy: ["localhost", "127.0.0.1", "::1"].map(String::from).to_vec(),
+        }
+    }
+}
+
+impl ProxyConfig {
+    fn validate(&self) -> Result<(), String> {
+        match self.url {
+            Some(ref url) if !url::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https")) => {
+                Err(format!("proxy: invalid url '{}'", url))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `[rate_limit]` table: a token bucket per host for outbound fetches,
+/// so batch lookups and link checks stay polite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Sustained requests per second to one host.
+    pub per_second: f64,
+    /// Requests to one host that may go at once before `per_second` applies.
+    pub burst: u32,
+    /// Requests per second for these domains (subdomains included, one
+    /// bucket for all of them), without a burst.
+    pub hosts: BTreeMap<String, f64>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_second: 2.0,
+            burst: 5,
+            // arXiv's API terms ask for one request every three seconds
+            hosts: BTreeMap::from([("arxiv.org".to_string(), 1.0 / 3.0)]),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    fn validate(&self) -> Result<(), String> {
+        let positive = |rate: f64| rate.is_finite() && rate > 0.0;
+        if !positive(self.per_second) || self.burst == 0 {
+            return Err("rate_limit: per_second and burst must be positive".to_string());
+        }
+        match self.hosts.iter().find(|(_, &rate)| !positive(rate)) {
+            Some((host, _)) => Err(format!("rate_limit: rate for '{}' must be positive", host)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `[log]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `tracing_subscriber::EnvFilter` directive.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    #[default]
+    Sled,
+    Sqlite,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl TlsConfig {
+    pub fn enabled(&self) -> bool {
+        self.self_signed || self.cert.is_some() || self.key.is_some()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            notes_dir: PathBuf::from(crate::NOTES_DIR),
+            pdfs_dir: PathBuf::from(crate::PDFS_DIR),
+            db_path: PathBuf::from(crate::DB_PATH),
+            db_backend: DbBackend::default(),
+            templates_dir: PathBuf::from(crate::TEMPLATES_DIR),
+            daily_template: DEFAULT_DAILY_TEMPLATE.to_string(),
+            bind: DEFAULT_BIND.to_string(),
+            read_only: false,
+            publish: false,
+            base_path: String::new(),
+            trust_proxy: false,
+            webdav: false,
+            time_categories: Vec::new(),
+            trash_days: DEFAULT_TRASH_DAYS,
+            archive_after_months: 0,
+            timezone: None,
+            extensions: Vec::new(),
+            recurring: Vec::new(),
+            vault_name: DEFAULT_VAULT_NAME.to_string(),
+            require_login: false,
+            vaults: Vec::new(),
+            notify: NotifyConfig::default(),
+            habits: HabitsConfig::default(),
+            embeddings: EmbeddingsConfig::default(),
+            llm: LlmConfig::default(),
+            transcription: TranscriptionConfig::default(),
+            digest: DigestConfig::default(),
+            url_policy: UrlPolicyConfig::default(),
+            links: LinksConfig::default(),
+            proxy: ProxyConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            stats: StatsConfig::default(),
+            sort: SortConfig::default(),
+            tls: TlsConfig::default(),
+            log: LogConfig::default(),
+            storage: StorageConfig::default(),
+            sync: SyncConfig::default(),
+            encryption: EncryptionConfig::default(),
+            backup: BackupConfig::default(),
+            mirror: MirrorConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Parse a config file's contents. Missing keys take their defaults.
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| format!("Invalid config: {}", e))
+    }
+
+    /// Load `notes.toml` (or the file named by `--config`) and apply
+    /// environment/flag overrides. Without `--config`, the file is looked up
+    /// in `--data-dir` when given, else the working directory. A missing
+    /// default file is not an error; a missing file that was asked for
+    /// explicitly is.
+    pub fn load(args: &ConfigArgs) -> Result<Self, String> {
+        let (path, explicit) = match (&args.config, &args.data_dir) {
+            (Some(p), _) => (p.clone(), true),
+            (None, Some(dir)) => (dir.join(CONFIG_FILE), false),
+            (None, None) => (PathBuf::from(CONFIG_FILE), false),
+        };
+
+        let mut config = match fs::read_to_string(&path) {
+            Ok(text) => {
+                Self::from_toml(&text).map_err(|e| format!("{}: {}", path.display(), e))?
+            }
+            Err(e) if explicit => {
+                return Err(format!("Cannot read {}: {}", path.display(), e));
+            }
+            Err(_) => Self::default(),
+        };
+
+        config.apply_args(args);
+        config.resolve_data_dir();
+        validate_bind(&config.bind)?;
+        if let Some(ref tz) = config.timezone {
+            crate::timezone::parse(tz)?;
+        }
+        crate::formats::validate(&config.extensions)?;
+        crate::recurring::validate(&config.recurring)?;
+        config.validate_vaults()?;
+        config.notify.validate()?;
+        config.embeddings.validate()?;
+        config.llm.validate()?;
+        config.transcription.validate()?;
+        config.digest.validate(&config.notify)?;
+        config.url_policy.validate()?;
+        config.proxy.validate()?;
+        config.rate_limit.validate()?;
+        config.storage.validate()?;
+        config.sync.validate()?;
+        config.encryption.validate()?;
+        config.backup.validate(&config.storage)?;
+        config.mirror.validate()?;
+        config.base_path = crate::proxy::normalize_base_path(&config.base_path)?;
+        Ok(config)
+    }
+
+    /// Path of the `name: color` time categories file.
+    pub fn time_categories_path(&self) -> PathBuf {
+        match self.data_dir {
+            Some(ref dir) => dir.join(crate::TIME_CATEGORIES_PATH),
+            None => PathBuf::from(crate::TIME_CATEGORIES_PATH),
+        }
+    }
+
+    /// Directory the self-signed TLS certificate and key are kept in.
+    pub fn tls_dir(&self) -> PathBuf {
+        match self.data_dir {
+            Some(ref dir) => dir.join(crate::TLS_DIR),
+            None => PathBuf::from(crate::TLS_DIR),
+        }
+    }
+
+    /// The config of every vault, this one first. Other vaults share all
+    /// settings but their data paths, name and login requirement.
+    pub fn vault_configs(&self) -> Vec<Config> {
+        let mut configs = vec![self.clone()];
+        for vault in &self.vaults {
+            let dir = match self.data_dir {
+                Some(ref data) if vault.dir.is_relative() => data.join(&vault.dir),
+                _ => vault.dir.clone(),
+            };
+            let mut config = self.clone();
+            config.notes_dir = dir.join(crate::NOTES_DIR);
+            config.pdfs_dir = dir.join(crate::PDFS_DIR);
+            config.db_path = dir.join(crate::DB_PATH);
+            config.vault_name = vault.name.clone();
+            config.require_login = vault.require_login;
+            config.storage.prefix = format!("{}{}/", self.storage.prefix, vault.name);
+            config.sync = SyncConfig::default();
+            config.mirror = MirrorConfig::default();
+            config.backup.dir = self.backup.dir.join(&vault.name);
+            configs.push(config);
+        }
+        configs
+    }
+
+    /// The config of the vault called `name`.
+    pub fn vault(&self, name: &str) -> Result<Config, String> {
+        self.vault_configs()
+            .into_iter()
+            .find(|c| c.vault_name == name)
+            .ok_or_else(|| format!("Unknown vault: {}", name))
+    }
+
+    fn validate_vaults(&self) -> Result<(), String> {
+        let mut names = vec![self.vault_name.as_str()];
+        for vault in &self.vaults {
+            if names.contains(&vault.name.as_str()) {
+                return Err(format!("Duplicate vault name: {}", vault.name));
+            }
+            names.push(&vault.name);
+        }
+        for name in names {
+            let valid = !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                return Err(format!("Invalid vault name {:?}: use letters, digits, '-' and '_'", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebase relative data paths onto `data_dir`.
+    fn resolve_data_dir(&mut self) {
+        let Some(ref dir) = self.data_dir else {
+            return;
+        };
+        for path in [&mut self.notes_dir, &mut self.pdfs_dir, &mut self.db_path, &mut self.templates_dir, &mut self.backup.dir] {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+
+    fn apply_args(&mut self, args: &ConfigArgs) {
+        if let Some(ref dir) = args.data_dir {
+            self.data_dir = Some(dir.clone());
+        }
+        if let Some(ref dir) = args.notes_dir {
+            self.notes_dir = dir.clone();
+        }
+        if let Some(ref dir) = args.pdfs_dir {
+            self.pdfs_dir = dir.clone();
+        }
+        if let Some(ref path) = args.db_path {
+            self.db_path = path.clone();
+        }
+        if let Some(backend) = args.db_backend {
+            self.db_backend = backend;
+        }
+        if let Some(ref dir) = args.templates_dir {
+            self.templates_dir = dir.clone();
+        }
+        if let Some(ref name) = args.daily_template {
+            self.daily_template = name.clone();
+        }
+        if let Some(ref bind) = args.bind {
+            self.bind = bind.clone();
+        }
+        if let Some(port) = args.port {
+            self.bind = with_port(&self.bind, port);
+        }
+        if args.read_only {
+            self.read_only = true;
+        }
+        if let Some(ref base_path) = args.base_path {
+            self.base_path = base_path.clone();
+        }
+        if args.trust_proxy {
+            self.trust_proxy = true;
+        }
+        if args.webdav {
+            self.webdav = true;
+        }
+        if let Some(ref cert) = args.tls_cert {
+            self.tls.cert = Some(cert.clone());
+        }
+        if let Some(ref key) = args.tls_key {
+            self.tls.key = Some(key.clone());
+        }
+        if args.tls_self_signed {
+            self.tls.self_signed = true;
+        }
+        if let Some(ref level) = args.log_level {
+            self.log.level = level.clone();
+        }
+        if let Some(format) = args.log_format {
+            self.log.format = format;
+        }
+        if let Some(days) = args.trash_days {
+            self.trash_days = days;
+        }
+        if let Some(months) = args.archive_after_months {
+            self.archive_after_months = months;
+        }
+        if let Some(ref tz) = args.timezone {
+            self.timezone = Some(tz.clone());
+        }
+    }
+}
+
+/// Split `host:port`, accepting bracketed IPv6 hosts.
+fn split_bind(bind: &str) -> Option<(&str, &str)> {
+    let (host, port) = bind.rsplit_once(':')?;
+    if host.contains(':') && !(host.starts_with('[') && host.ends_with(']')) {
+        return None;
+    }
+    Some((host, port))
+}
+
+/// Replace the port of a bind address, keeping its host.
+fn with_port(bind: &str, port: u16) -> String {
+    let host = split_bind(bind).map(|(h, _)| h).unwrap_or(bind);
+    let host = if host.is_empty() { "0.0.0.0" } else { host };
+    format!("{}:{}", host, port)
+}
+
+fn validate_bind(bind: &str) -> Result<(), String> {
+    match split_bind(bind) {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => Ok(()),
+        _ => Err(format!(
+            "Invalid bind address '{}' (expected HOST:PORT, e.g. 127.0.0.1:3000)",
+            bind
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+        assert_eq!(config.notes_dir, PathBuf::from(crate::NOTES_DIR));
+        assert_eq!(config.bind, DEFAULT_BIND);
+        assert_eq!(config.trash_days, DEFAULT_TRASH_DAYS);
+        assert_eq!(config.daily_template, DEFAULT_DAILY_TEMPLATE);
+        assert_eq!(config.archive_after_months, 0);
+        assert!(config.time_categories.is_empty());
+    }
+
+    #[test]
+    fn test_parse_config_file() {
+        let config = Config::from_toml(
+            r##"
+notes_dir = "/srv/notes"
+bind = "127.0.0.1:7000"
+
+[[time_categories]]
+name = "research"
+color = "#268bd2"
+weekly_hours = 10
+"##,
+        )
+        .unwrap();
+        assert_eq!(config.notes_dir, PathBuf::from("/srv/notes"));
+        assert_eq!(config.pdfs_dir, PathBuf::from(crate::PDFS_DIR));
+        assert_eq!(config.bind, "127.0.0.1:7000");
+        assert_eq!(config.time_categories[0].name, "research");
+        assert_eq!(config.time_categories[0].weekly_hours, Some(10.0));
+    }
+
+    #[test]
+    fn test_tls_table() {
+        let config = Config::from_toml("[tls]\nself_signed = true\nhostnames = [\"notes.lan\"]").unwrap();
+        assert!(config.tls.enabled());
+        assert_eq!(config.tls.hostnames, vec!["notes.lan"]);
+        assert!(!Config::default().tls.enabled());
+    }
+
+    #[test]
+    fn test_log_table() {
+        let config = Config::from_toml("[log]\nlevel = \"debug\"\nformat = \"json\"").unwrap();
+        assert_eq!(config.log.level, "debug");
+        assert_eq!(config.log.format, LogFormat::Json);
+        assert!(Config::from_toml("[log]\nformat = \"xml\"").is_err());
+    }
+
+    #[test]
+    fn test_notify_table() {
+        let config = Config::from_toml("[notify]\nchannel = \"ntfy\"\nurl = \"https://ntfy.sh/notes\"").unwrap();
+        assert_eq!(config.notify.channel, Some(NotifyChannel::Ntfy));
+        assert!(config.notify.validate().is_ok());
+        assert_eq!(config.notify.sendmail, PathBuf::from(DEFAULT_SENDMAIL));
+
+        let email = Config::from_toml("[notify]\nchannel = \"email\"").unwrap();
+        assert!(email.notify.validate().is_err());
+        let webhook = Config::from_toml("[notify]\nchannel = \"webhook\"\nurl = \"ftp://x\"").unwrap();
+        assert!(webhook.notify.validate().is_err());
+        assert!(Config::default().notify.validate().is_ok());
+        assert!(Config::from_toml("[notify]\nchannel = \"pager\"").is_err());
+    }
+
+    #[test]
+    fn test_embeddings_table() {
+        let config = Config::from_toml(
+            "[embeddings]\nbackend = \"openai\"\nurl = \"https://api.openai.com/v1/embeddings\"\nmodel = \"text-embedding-3-small\"",
+        )
+        .unwrap();
+        assert_eq!(config.embeddings.backend, Some(EmbeddingsBackend::OpenAI));
+        assert!(config.embeddings.validate().is_ok());
+        let no_model = Config::from_toml("[embeddings]\nbackend = \"ollama\"\nurl = \"http://localhost:11434/api/embed\"").unwrap();
+        assert!(no_model.embeddings.validate().is_err());
+        assert!(Config::default().embeddings.validate().is_ok());
+    }
+
+    #[test]
+    fn test_llm_table() {
+        assert_eq!(Config::default().llm.provider, LlmProvider::Cli);
+        assert!(Config::default().llm.validate().is_ok());
+        let config = Config::from_toml(
+            "[llm]\nprovider = \"anthropic\"\nmodel = \"claude-sonnet-4-5\"\napi_key_env = \"ANTHROPIC_API_KEY\"",
+        )
+        .unwrap();
+        assert_eq!(config.llm.provider, LlmProvider::Anthropic);
+        assert!(config.llm.validate().is_ok());
+        let no_model = Config::from_toml("[llm]\nprovider = \"openai\"").unwrap();
+        assert!(no_model.llm.validate().is_err());
+        let bad_url = Config::from_toml("[llm]\nurl = \"localhost:8080\"").unwrap();
+        assert!(bad_url.llm.validate().is_err());
+    }
+
+    #[test]
+    fn test_transcription_table() {
+        assert!(Config::default().transcription.validate().is_ok());
+        let config = Config::from_toml(
+            "[transcription]\nbackend = \"whisper\"\nmodel = \"/opt/whisper/ggml-base.en.bin\"\nlanguage = \"en\"",
+        )
+        .unwrap();
+        assert_eq!(config.transcription.backend, Some(TranscriptionBackend::Whisper));
+        assert!(config.transcription.validate().is_ok());
+        let no_model = Config::from_toml("[transcription]\nbackend = \"openai\"").unwrap();
+        assert!(no_model.transcription.validate().is_err());
+    }
+
+    #[test]
+    fn test_digest_table() {
+        let default = Config::default();
+        assert!(!default.digest.enabled);
+        assert!(default.digest.validate(&default.notify).is_ok());
+        let config =
+            Config::from_toml("[digest]\nenabled = true\nweekday = \"fri\"\nhour = 17\ndeliver = \"notify\"").unwrap();
+        assert_eq!(config.digest.weekday, chrono::Weekday::Fri);
+        assert_eq!(config.digest.deliver, DigestDelivery::Notify);
+        assert!(config.digest.validate(&config.notify).is_err());
+        let with_channel = Config::from_toml(
+            "[digest]\nenabled = true\ndeliver = \"notify\"\n[notify]\nchannel = \"ntfy\"\nurl = \"https://ntfy.sh/notes\"",
+        )
+        .unwrap();
+        assert!(with_channel.digest.validate(&with_channel.notify).is_ok());
+        let bad_hour = Config::from_toml("[digest]\nhour = 24").unwrap();
+        assert!(bad_hour.digest.validate(&bad_hour.notify).is_err());
+    }
+
+    #[test]
+    fn test_url_policy_table() {
+        let default = Config::default().url_policy;
+        assert!(default.validate().is_ok());
+        assert!(default.allowlist_only && !default.pdf_allowlist_only);
+        let config = Config::from_toml(
+            "[url_policy]\nallowlist_only = false\nallow = [\"example.edu\"]\ndeny_cidrs = [\"203.0.113.0/24\"]",
+        )
+        .unwrap();
+        assert!(!config.url_policy.allowlist_only);
+        assert_eq!(config.url_policy.schemes, vec!["https"]);
+        assert!(config.url_policy.validate().is_ok());
+        let bad_cidr = Config::from_toml("[url_policy]\ndeny_cidrs = [\"10.0.0.0/40\"]").unwrap();
+        assert!(bad_cidr.url_policy.validate().is_err());
+        let bad_scheme = Config::from_toml("[url_policy]\npdf_schemes = [\"file\"]").unwrap();
+        assert!(bad_scheme.url_policy.validate().is_err());
+    }
+
+    #[test]
+    fn test_links_table() {
+        assert_eq!(Config::default().links.check_days, 0);
+        assert_eq!(Config::from_toml("[links]\ncheck_days = 7").unwrap().links.check_days, 7);
+        assert!(Config::from_toml("[links]\nevery = 7").is_err());
+    }
+
+    #[test]
+    fn test_stats_table() {
+        assert!(!Config::default().stats.count_logged_in);
+        assert!(Config::from_toml("[stats]\ncount_logged_in = true").unwrap().stats.count_logged_in);
+    }
+
+    #[test]
+    fn test_sort() {
+        assert_eq!(Config::default().sort.by, NoteSort::Modified);
+        let config = Config::from_toml("[sort]\nby = \"time_total\"\norder = \"asc\"").unwrap();
+        assert_eq!((config.sort.by, config.sort.order), (NoteSort::TimeTotal, Some(SortOrder::Asc)));
+        assert!(Config::from_toml("[sort]\nby = \"size\"").is_err());
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert!(Config::default().extensions.is_empty());
+        let config = Config::from_toml("extensions = [\"org\", \"txt\"]").unwrap();
+        assert_eq!(config.extensions, ["org", "txt"]);
+    }
+
+    #[test]
+    fn test_vaults() {
+        let config = Config::from_toml(
+            "data_dir = \"/data\"\nvault_name = \"research\"\n[[vaults]]\nname = \"teaching\"\ndir = \"teaching\"\nrequire_login = true",
+        )
+        .unwrap();
+        assert!(config.validate_vaults().is_ok());
+        let teaching = config.vault("teaching").unwrap();
+        assert_eq!(teaching.notes_dir, PathBuf::from("/data/teaching/content"));
+        assert_eq!(teaching.db_path, PathBuf::from("/data/teaching/.notes_db"));
+        assert!(teaching.require_login && !config.require_login);
+        assert_eq!(teaching.storage.prefix, "teaching/");
+        assert_eq!(config.vault_configs()[0].vault_name, "research");
+        assert!(config.vault("personal").is_err());
+
+        let dup = Config::from_toml("[[vaults]]\nname = \"notes\"\ndir = \"x\"").unwrap();
+        assert!(dup.validate_vaults().is_err());
+        let bad = Config::from_toml("[[vaults]]\nname = \"a b\"\ndir = \"x\"").unwrap();
+        assert!(bad.validate_vaults().is_err());
+    }
+
+    #[test]
+    fn test_sync_table() {
+        let config = Config::from_toml("[sync]\npeer = \"https://notes.example.org/notes\"").unwrap();
+        assert!(config.sync.validate().is_ok());
+        assert_eq!(config.sync.peer.as_deref(), Some("https://notes.example.org/notes"));
+        assert!(Config::from_toml("[sync]\npeer = \"notes.example.org\"").unwrap().sync.validate().is_err());
+        assert!(Config::from_toml("[sync]\ntoken_env = \"\"").unwrap().sync.validate().is_err());
+        let vaults = Config::from_toml("[sync]\npeer = \"http://peer\"\n[[vaults]]\nname = \"x\"\ndir = \"x\"").unwrap();
+        assert!(vaults.vault_configs()[1].sync.peer.is_none());
+    }
+
+    #[test]
+    fn test_backup_table() {
+        assert!(Config::default().backup.schedule.is_none());
+        let config = Config::from_toml("[backup]\nschedule = \"30 3 * * *\"\ndir = \"/mnt/backups\"\nkeep = 14").unwrap();
+        assert!(config.backup.validate(&config.storage).is_ok());
+        assert_eq!(config.backup.keep, 14);
+        let vaults = Config::from_toml("[backup]\ndir = \"/mnt/backups\"\n[[vaults]]\nname = \"x\"\ndir = \"x\"").unwrap();
+        assert_eq!(vaults.vault_configs()[1].backup.dir, PathBuf::from("/mnt/backups/x"));
+        let invalid = ["[backup]\nschedule = \"daily\"", "[backup]\nkeep = 0", "[backup]\ns3 = true"];
+        for toml in invalid {
+            let config = Config::from_toml(toml).unwrap();
+            assert!(config.backup.validate(&config.storage).is_err(), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn test_mirror_table() {
+        assert!(Config::default().mirror.upstream.is_none());
+        let config = Config::from_toml("[mirror]\nupstream = \"https://git.example.org/notes.git\"\nbranch = \"main\"").unwrap();
+        assert!(config.mirror.validate().is_ok());
+        assert_eq!(config.mirror.interval_minutes, 5);
+        assert!(Config::from_toml("[mirror]\nupstream = \"--upload-pack=x\"").unwrap().mirror.validate().is_err());
+        assert!(Config::from_toml("[mirror]\ninterval_minutes = 0").unwrap().mirror.validate().is_err());
+        let vaults = Config::from_toml("[mirror]\nupstream = \"/srv/notes\"\n[[vaults]]\nname = \"x\"\ndir = \"x\"").unwrap();
+        assert!(vaults.vault_configs()[1].mirror.upstream.is_none());
+    }
+
+    #[test]
+    fn test_encryption_table() {
+        assert!(Config::default().encryption.passphrase_env.is_none());
+        let config = Config::from_toml("[encryption]\npassphrase_env = \"VAULT_KEY\"").unwrap();
+        assert!(config.encryption.validate().is_ok());
+        assert_eq!(config.encryption.passphrase_env.as_deref(), Some("VAULT_KEY"));
+        assert!(Config::from_toml("[encryption]\npassphrase_env = \" \"").unwrap().encryption.validate().is_err());
+        assert!(Config::from_toml("[encryption]\npassphrase = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_storage_table() {
+        assert!(Config::default().storage.backend.is_none());
+        let config = Config::from_toml(
+            "[storage]\nbackend = \"s3\"\nbucket = \"papers\"\nendpoint = \"http://minio:9000\"\npath_style = true",
+        )
+        .unwrap();
+        assert!(config.storage.validate().is_ok());
+        assert_eq!(config.storage.backend, Some(StorageBackend::S3));
+        assert!(Config::from_toml("[storage]\nbackend = \"s3\"").unwrap().storage.validate().is_err());
+        let bad = Config::from_toml("[storage]\nbackend = \"s3\"\nbucket = \"b\"\nendpoint = \"minio\"").unwrap();
+        assert!(bad.storage.validate().is_err());
+    }
+
+    #[test]
+    fn test_timezone() {
+        assert_eq!(Config::default().timezone, None);
+        let config = Config::from_toml("timezone = \"Europe/Berlin\"").unwrap();
+        assert_eq!(config.timezone.as_deref(), Some("Europe/Berlin"));
+        let args = ConfigArgs {
+            timezone: Some("America/New_York".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(Config::load(&args).unwrap().timezone.as_deref(), Some("America/New_York"));
+        let args = ConfigArgs {
+            timezone: Some("Eastern".to_string()),
+            ..Default::default()
+        };
+        assert!(Config::load(&args).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_table() {
+        let config = Config::from_toml("[rate_limit]\nper_second = 1\nburst = 2\nhosts = { \"api.crossref.org\" = 5 }").unwrap();
+        assert!(config.rate_limit.validate().is_ok());
+        assert_eq!(config.rate_limit.hosts["api.crossref.org"], 5.0);
+        assert!(!config.rate_limit.hosts.contains_key("arxiv.org"));
+        let zero = Config::from_toml("[rate_limit]\nper_second = 0").unwrap();
+        assert!(zero.rate_limit.validate().is_err());
+    }
+
+    #[test]
+    fn test_proxy_table() {
+        assert!(Config::default().proxy.url.is_none());
+        let config = Config::from_toml("[proxy]\nurl = \"http://proxy.example.com:3128\"").unwrap();
+        assert!(config.proxy.validate().is_ok());
+        assert!(config.proxy.no_proxy.contains(&"localhost".to_string()));
+        let socks = Config::from_toml("[proxy]\nurl = \"socks5://proxy:1080\"").unwrap();
+        assert!(socks.proxy.validate().is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        assert!(Config::from_toml("notes_dri = \"x\"").is_err());
+    }
+
+    #[test]
+    fn test_db_backend() {
+        assert_eq!(Config::default().db_backend, DbBackend::Sled);
+        let mut config = Config::from_toml("db_backend = \"sqlite\"").unwrap();
+        assert_eq!(config.db_backend, DbBackend::Sqlite);
+        config.apply_args(&ConfigArgs { db_backend: Some(DbBackend::Sled), ..Default::default() });
+        assert_eq!(config.db_backend, DbBackend::Sled);
+        assert!(Config::from_toml("db_backend = \"rocksdb\"").is_err());
+    }
+
+    #[test]
+    fn test_args_override_file() {
+        let mut config = Config::from_toml("notes_dir = \"a\"\ndb_path = \"b\"").unwrap();
+        config.apply_args(&ConfigArgs {
+            notes_dir: Some(PathBuf::from("override")),
+            ..Default::default()
+        });
+        assert_eq!(config.notes_dir, PathBuf::from("override"));
+        assert_eq!(config.db_path, PathBuf::from("b"));
+    }
+
+    #[test]
+    fn test_bind_and_port_overrides() {
+        let mut config = Config::default();
+        config.apply_args(&ConfigArgs {
+            port: Some(8080),
+            ..Default::default()
+        });
+        assert_eq!(config.bind, "0.0.0.0:8080");
+
+        config.apply_args(&ConfigArgs {
+            bind: Some("[::1]:3000".to_string()),
+            port: Some(9000),
+            ..Default::default()
+        });
+        assert_eq!(config.bind, "[::1]:9000");
+    }
+
+    #[test]
+    fn test_validate_bind() {
+        assert!(validate_bind("127.0.0.1:3000").is_ok());
+        assert!(validate_bind("localhost:80").is_ok());
+        assert!(validate_bind("[::]:3000").is_ok());
+        assert!(validate_bind("3000").is_err());
+        assert!(validate_bind("127.0.0.1:99999").is_err());
+        assert!(validate_bind("::1:3000").is_err());
+    }
+
+    #[test]
+    fn test_data_dir_rebases_relative_paths() {
+        let mut config = Config::from_toml("data_dir = \"/data\"\npdfs_dir = \"/mnt/pdfs\"").unwrap();
+        config.resolve_data_dir();
+        assert_eq!(config.notes_dir, PathBuf::from("/data/content"));
+        assert_eq!(config.pdfs_dir, PathBuf::from("/mnt/pdfs"));
+        assert_eq!(config.db_path, PathBuf::from("/data/.notes_db"));
+        assert_eq!(config.templates_dir, PathBuf::from("/data/templates"));
+        assert_eq!(config.time_categories_path(), PathBuf::from("/data/time_categories.conf"));
+
+        let mut config = Config::default();
+        config.resolve_data_dir();
+        assert_eq!(config.notes_dir, PathBuf::from(crate::NOTES_DIR));
+    }
+
+    #[test]
+    fn test_base_path_is_normalized() {
+        let args = ConfigArgs {
+            base_path: Some("notes/".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(Config::load(&args).unwrap().base_path, "/notes");
+
+        let args = ConfigArgs {
+            base_path: Some("/no tes".to_string()),
+            ..Default::default()
+        };
+        assert!(Config::load(&args).is_err());
+    }
+
+    #[test]
+    fn test_missing_explicit_config_is_error() {
+        let args = ConfigArgs {
+            config: Some(PathBuf::from("/nonexistent/notes.toml")),
+            ..Default::default()
+        };
+        assert!(Config::load(&args).is_err());
+    }
+}
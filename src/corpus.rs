@@ -0,0 +1,467 @@
+//! Whole-corpus JSON dump and import.
+//!
+//! `GET /api/export.json` lists every note with its frontmatter as
+//! structured fields plus the markdown body, so external scripts never
+//! have to parse frontmatter themselves. `POST /api/import/json` takes the
+//! same shape back (optionally edited) and writes the notes out again.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+
+use crate::auth::is_logged_in;
+use crate::models::{
+    CorpusDump, CorpusImportRequest, CorpusImportResult, CorpusImportSkipped, CorpusNote,
+    CorpusTimeEntry, Note, NoteType, TimeCategory, TimeEntry,
+};
+use crate::notes::{format_time_entry, generate_key, is_valid_note_id, REMIND_FORMAT};
+use crate::{validate_path_within, AppState};
+
+/// Bumped when `CorpusNote` changes incompatibly.
+pub const CORPUS_DUMP_VERSION: u32 = 1;
+
+/// Frontmatter keys that carry a paper source, by `PaperSource::source_type`.
+const SOURCE_KEYS: &[&str] = &["arxiv", "doi", "url", "wayback"];
+
+// ============================================================================
+// Export
+// ============================================================================
+
+pub fn note_to_corpus(note: &Note) -> CorpusNote {
+    let (note_type, bibtex, canonical_key, sources, versions) = match note.note_type {
+        NoteType::Paper(ref paper) => (
+            "paper",
+            paper.bibtex_entries.clone(),
+            paper.canonical_key.clone(),
+            paper.sources.clone(),
+            paper.versions.clone(),
+        ),
+        NoteType::Note => ("note", Vec::new(), None, Vec::new(), Vec::new()),
+    };
+    CorpusNote {
+        key: note.key.clone(),
+        path: note.path.to_string_lossy().to_string(),
+        title: note.title.clone(),
+        created: note.created,
+        due: note.due,
+        remind: note.remind,
+        date: note.date,
+        note_type: note_type.to_string(),
+        parent: note.parents.first().cloned(),
+        parents: note.parents.clone(),
+        prev: note.prev_key.clone(),
+        status: note.status.clone(),
+        tags: note.tags.clone(),
+        hidden: note.hidden,
+        pdf: note.pdf.clone(),
+        rate: note.hourly_rate,
+        priority: note.priority,
+        est_minutes: note.est_minutes,
+        bibtex,
+        canonical_key,
+        sources,
+        versions,
+        time: note
+            .time_entries
+            .iter()
+            .map(|e| CorpusTimeEntry {
+                date: e.date,
+                minutes: e.minutes,
+                category: e.category.to_string(),
+                description: e.description.clone(),
+            })
+            .collect(),
+        body: note.raw_content.clone(),
+        modified: Some(note.modified),
+    }
+}
+
+/// Every note, sorted by path. Hidden notes only with `include_hidden`.
+pub fn corpus_dump(notes: &[Note], include_hidden: bool) -> CorpusDump {
+    let mut notes: Vec<CorpusNote> = notes
+        .iter()
+        .filter(|n| include_hidden || !n.hidden)
+        .map(note_to_corpus)
+        .collect();
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+    CorpusDump {
+        version: CORPUS_DUMP_VERSION,
+        exported: Utc::now(),
+        notes,
+    }
+}
+
+// ============================================================================
+// Import
+// ============================================================================
+
+/// A relative `.md` path with no `..`, absolute or dot-file components.
+fn validate_note_path(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path.trim());
+    if path.extension().and_then(|e| e.to_str()) != Some("md") {
+        return Err("path must end in .md".to_string());
+    }
+    let safe = path.components().all(|c| match c {
+        Component::Normal(part) => !part.to_string_lossy().starts_with('.'),
+        _ => false,
+    });
+    if !safe {
+        return Err("path must be relative to the notes directory".to_string());
+    }
+    Ok(path)
+}
+
+/// Collapse a frontmatter value onto one line.
+fn single_line(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Render a note file (frontmatter and body) from its corpus entry.
+pub fn render_note_file(note: &CorpusNote) -> Result<String, String> {
+    let title = single_line(&note.title);
+    if title.is_empty() {
+        return Err("title is empty".to_string());
+    }
+
+    let mut out = format!("---\ntitle: {}\n", title);
+    let key = note.key.trim();
+    if !key.is_empty() && key != generate_key(Path::new(note.path.trim())) {
+        if !is_valid_note_id(key) {
+            return Err(format!("invalid key '{}'", key));
+        }
+        out.push_str(&format!("id: {}\n", key));
+    }
+    if let Some(created) = note.created {
+        out.push_str(&format!("created: {}\n", created.format("%Y-%m-%d")));
+    }
+    if let Some(date) = note.date {
+        out.push_str(&format!("date: {}\n", date.format("%Y-%m-%d")));
+    }
+    if let Some(due) = note.due {
+        out.push_str(&format!("due: {}\n", due.format("%Y-%m-%d")));
+    }
+    if let Some(remind) = note.remind {
+        out.push_str(&format!("remind: {}\n", remind.format(REMIND_FORMAT)));
+    }
+    match note.note_type.as_str() {
+        "note" => {}
+        "paper" => out.push_str("type: paper\n"),
+        other => return Err(format!("unknown type '{}'", other)),
+    }
+    let mut parents: Vec<&str> = note.parent.iter().map(String::as_str).collect();
+    for parent in &note.parents {
+        if !parents.contains(&parent.as_str()) {
+            parents.push(parent);
+        }
+    }
+    match parents.as_slice() {
+        [] => {}
+        [parent] => out.push_str(&format!("parent: {}\n", single_line(parent))),
+        _ => {
+            let parents: Vec<String> = parents.iter().map(|p| single_line(p)).collect();
+            out.push_str(&format!("parents: [{}]\n", parents.join(", ")));
+        }
+    }
+    if let Some(ref prev) = note.prev {
+        out.push_str(&format!("prev: {}\n", single_line(prev)));
+    }
+    if let Some(ref status) = note.status {
+        out.push_str(&format!("status: {}\n", single_line(status)));
+    }
+    if !note.tags.is_empty() {
+        let tags: Vec<String> = note
+            .tags
+            .iter()
+            .map(|t| single_line(t).replace([',', '[', ']'], ""))
+            .filter(|t| !t.is_empty())
+            .collect();
+        out.push_str(&format!("tags: [{}]\n", tags.join(", ")));
+    }
+    if note.hidden {
+        out.push_str("hidden: true\n");
+    }
+    if let Some(ref pdf) = note.pdf {
+        out.push_str(&format!("pdf: {}\n", single_line(pdf)));
+    }
+    if let Some(rate) = note.rate {
+        out.push_str(&format!("rate: {}\n", rate));
+    }
+    if let Some(priority) = note.priority {
+        if !(1..=9).contains(&priority) {
+            return Err(format!("priority {} is not 1-9", priority));
+        }
+        out.push_str(&format!("priority: {}\n", priority));
+    }
+    if let Some(est) = note.est_minutes {
+        out.push_str(&format!("est_minutes: {}\n", est));
+    }
+    if let Some(ref canonical) = note.canonical_key {
+        out.push_str(&format!("canonical_key: {}\n", single_line(canonical)));
+    }
+    for source in &note.sources {
+        if !SOURCE_KEYS.contains(&source.source_type.as_str()) {
+            return Err(format!("unknown source type '{}'", source.source_type));
+        }
+        out.push_str(&format!("{}: {}\n", source.source_type, single_line(&source.identifier)));
+    }
+    if !note.versions.is_empty() {
+        out.push_str("versions:\n");
+        for version in &note.versions {
+            let source = &version.source;
+            if !SOURCE_KEYS.contains(&source.source_type.as_str()) {
+                return Err(format!("unknown source type '{}'", source.source_type));
+            }
+            out.push_str(&format!("  - {}: {}\n", source.source_type, single_line(&source.identifier)));
+            if let Some(ref label) = version.label {
+                out.push_str(&format!("    label: {}\n", single_line(label)));
+            }
+        }
+    }
+    for entry in &note.bibtex {
+        out.push_str("bibtex: |\n");
+        for line in entry.trim().lines() {
+            out.push_str(&format!("  {}\n", line.trim()));
+        }
+    }
+    if !note.time.is_empty() {
+        out.push_str("time:\n");
+        for entry in &note.time {
+            let entry = TimeEntry {
+                date: entry.date,
+                minutes: entry.minutes,
+                category: TimeCategory::from_name(&entry.category),
+                description: entry.description.clone(),
+            };
+            for line in format_time_entry(&entry, "  ") {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+    }
+
+    // Bodies read from disk keep the blank line after the frontmatter.
+    out.push_str("---\n");
+    if !note.body.starts_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(&note.body);
+    if !out.ends_with('\n') {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Decide what to write for each entry. `exists` reports whether a note
+/// file is already present at a relative path.
+pub fn plan_corpus_import(
+    request: &CorpusImportRequest,
+    exists: impl Fn(&Path) -> bool,
+) -> (CorpusImportResult, Vec<(PathBuf, String)>) {
+    let mut result = CorpusImportResult::default();
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+
+    for note in &request.notes {
+        let skip = |reason: String| CorpusImportSkipped {
+            path: note.path.clone(),
+            reason,
+        };
+        let path = match validate_note_path(&note.path) {
+            Ok(p) => p,
+            Err(e) => {
+                result.skipped.push(skip(e));
+                continue;
+            }
+        };
+        if !seen.insert(path.clone()) {
+            result.skipped.push(skip("duplicate path in request".to_string()));
+            continue;
+        }
+        let content = match render_note_file(note) {
+            Ok(c) => c,
+            Err(e) => {
+                result.skipped.push(skip(e));
+                continue;
+            }
+        };
+        let path_str = path.to_string_lossy().to_string();
+        if exists(&path) {
+            if !request.overwrite {
+                result.skipped.push(skip("note already exists".to_string()));
+                continue;
+            }
+            result.updated.push(path_str);
+        } else {
+            result.created.push(path_str);
+        }
+        files.push((path, content));
+    }
+
+    (result, files)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+pub async fn export_json(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let logged_in = is_logged_in(&jar, &state.db);
+    axum::Json(corpus_dump(&state.load_notes(), logged_in)).into_response()
+}
+
+pub async fn import_json(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<CorpusImportRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let (mut result, files) = plan_corpus_import(&body, |p| state.notes_dir.join(p).exists());
+    if body.dry_run || files.is_empty() {
+        return axum::Json(result).into_response();
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in &files {
+        let full_path = state.notes_dir.join(path);
+        let outcome = validate_path_within(&state.notes_dir, &full_path)
+            .and_then(|p| crate::encryption::write_file(&p, content).map(drop));
+        match outcome {
+            Ok(()) => written.push(path.clone()),
+            Err(e) => result
+                .errors
+                .push(format!("Failed to write {}: {}", path.display(), e)),
+        }
+    }
+    let was_written = |p: &String| written.iter().any(|w| w.to_string_lossy() == *p);
+    result.created.retain(was_written);
+    result.updated.retain(was_written);
+
+    if !written.is_empty() {
+        state.invalidate_notes_cache();
+        for note in state.load_notes().iter().filter(|n| written.contains(&n.path)) {
+            state.reindex_graph_note(&note.key);
+        }
+
+        let notes_dir = state.notes_dir.clone();
+        state.spawn_background(move || {
+            let now = crate::timezone::now();
+            let commit_msg = format!(
+                "imported {} notes from JSON: {}",
+                written.len(),
+                now.format("%a %b %d, %-I:%M%p")
+            );
+            for path in &written {
+                let _ = Command::new("git")
+                    .args(["add", &path.to_string_lossy()])
+                    .current_dir(&notes_dir)
+                    .output();
+            }
+            let _ = Command::new("git")
+                .args(["commit", "-m", &commit_msg])
+                .current_dir(&notes_dir)
+                .output();
+        });
+    }
+
+    axum::Json(result).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PaperSource;
+    use crate::notes::note_from_content;
+
+    fn note(path: &str, content: &str) -> Note {
+        note_from_content(PathBuf::from(path), content.to_string(), Utc::now())
+    }
+
+    const PAPER: &str = "---\ntitle: Souffle\ncreated: 2024-01-10\ndate: 2016-07-01\ndue: 2024-02-01\nremind: 2024-01-31 09:00\ntype: paper\nparent: abc123\nprev: def456\nstatus: archived\n\
+tags: [datalog, pl]\nhidden: true\npdf: souffle.pdf\nrate: 120\npriority: 2\nest_minutes: 90\narxiv: 1234.5678\n\
+versions:\n  - arxiv: 1234.5678v1\n    label: preprint\n  - doi: 10.1007/978-3-319-41540-6_23\n\
+bibtex: |\n  @inproceedings{jordan2016,\n  title={Souffle},\n  year={2016}\n  }\n\
+time:\n  - date: 2024-01-15\n    minutes: 45\n    category: reading\n    description: First pass\n\
+---\n\nFast Datalog.\n";
+
+    #[test]
+    fn test_render_roundtrips_frontmatter() {
+        let original = note("papers/souffle.md", PAPER);
+        let corpus = note_to_corpus(&original);
+        assert_eq!(corpus.note_type, "paper");
+        assert_eq!(corpus.time[0].category, "reading");
+
+        let rendered = render_note_file(&corpus).unwrap();
+        let reparsed = note("papers/souffle.md", &rendered);
+        let again = note_to_corpus(&reparsed);
+        assert_eq!(
+            serde_json::to_value(CorpusNote { modified: None, ..again }).unwrap(),
+            serde_json::to_value(CorpusNote { modified: None, ..corpus }).unwrap()
+        );
+        assert_eq!(rendered, PAPER);
+    }
+
+    #[test]
+    fn test_minimal_import_entry() {
+        let entry: CorpusNote =
+            serde_json::from_str(r#"{"path": "ideas/x.md", "title": "X", "body": "Hello"}"#).unwrap();
+        assert_eq!(render_note_file(&entry).unwrap(), "---\ntitle: X\n---\n\nHello\n");
+
+        let derived = CorpusNote { key: generate_key(Path::new("ideas/x.md")), ..entry.clone() };
+        assert_eq!(render_note_file(&derived).unwrap(), "---\ntitle: X\n---\n\nHello\n");
+        let pinned = CorpusNote { key: "3fa9c01b22d4".to_string(), ..entry.clone() };
+        assert!(render_note_file(&pinned).unwrap().starts_with("---\ntitle: X\nid: 3fa9c01b22d4\n"));
+        let invalid = CorpusNote { key: "x y".to_string(), ..entry.clone() };
+        assert!(render_note_file(&invalid).is_err());
+
+        let source = CorpusNote {
+            sources: vec![PaperSource {
+                source_type: "ftp".to_string(),
+                identifier: "x".to_string(),
+            }],
+            ..entry
+        };
+        assert!(render_note_file(&source).is_err());
+    }
+
+    #[test]
+    fn test_plan_corpus_import() {
+        let entry = |path: &str| CorpusNote {
+            path: path.to_string(),
+            ..serde_json::from_str(r#"{"path": "", "title": "T"}"#).unwrap()
+        };
+        let request = CorpusImportRequest {
+            notes: vec![
+                entry("new.md"),
+                entry("old.md"),
+                entry("new.md"),
+                entry("../escape.md"),
+                entry(".git/config.md"),
+                entry("notes.txt"),
+            ],
+            dry_run: true,
+            overwrite: false,
+        };
+        let exists = |p: &Path| p == Path::new("old.md");
+        let (result, files) = plan_corpus_import(&request, exists);
+        assert_eq!(result.created, vec!["new.md"]);
+        assert!(result.updated.is_empty());
+        assert_eq!(result.skipped.len(), 5);
+        assert_eq!(files.len(), 1);
+
+        let request = CorpusImportRequest { overwrite: true, ..request };
+        let (result, _) = plan_corpus_import(&request, exists);
+        assert_eq!(result.updated, vec!["old.md"]);
+    }
+}
@@ -0,0 +1,342 @@
+//! Courses and their lectures.
+//!
+//! A note with `type: course` is a course, described by `code:`,
+//! `semester:` and `schedule:`. Its children with `type: lecture` are its
+//! lectures, numbered by `lecture:` or else by their place in date order.
+//! `/courses` lists every course with its lectures and the Teaching time
+//! logged on it and everything below it; "New lecture" files the next
+//! numbered lecture under the course's directory (from the course's
+//! `lecture_template:` when it names one). Each course links to its
+//! reading list (see `reading_list`).
+//!
+//! Like `/today`, a new lecture is written without a commit.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, TimeCategory};
+use crate::note_templates::{self, TemplateVars};
+use crate::notes::{descendants, frontmatter_field, html_escape, new_note_frontmatter, new_note_key};
+use crate::templates::base_html;
+use crate::AppState;
+
+pub const COURSE_TYPE: &str = "course";
+pub const LECTURE_TYPE: &str = "lecture";
+
+fn note_type_is(note: &Note, kind: &str) -> bool {
+    frontmatter_field(&note.full_file_content, "type").is_some_and(|t| t.eq_ignore_ascii_case(kind))
+}
+
+pub fn is_course(note: &Note) -> bool {
+    note_type_is(note, COURSE_TYPE)
+}
+
+/// A lecture and its number in the course.
+#[derive(Debug)]
+pub struct Lecture<'a> {
+    pub number: u32,
+    pub note: &'a Note,
+}
+
+#[derive(Debug)]
+pub struct Course<'a> {
+    pub note: &'a Note,
+    pub code: Option<String>,
+    pub semester: Option<String>,
+    pub schedule: Option<String>,
+    pub lectures: Vec<Lecture<'a>>,
+    /// Teaching minutes on the course and every note below it.
+    pub teaching_minutes: u32,
+}
+
+impl Course<'_> {
+    /// `CODE: Title`, or the title alone.
+    pub fn name(&self) -> String {
+        match self.code {
+            Some(ref code) => format!("{}: {}", code, self.note.title),
+            None => self.note.title.clone(),
+        }
+    }
+
+    /// The number the next lecture gets.
+    pub fn next_lecture(&self) -> u32 {
+        self.lectures.iter().map(|l| l.number).max().unwrap_or(0) + 1
+    }
+}
+
+fn day(note: &Note) -> NaiveDate {
+    note.date
+        .or(note.created)
+        .unwrap_or_else(|| crate::timezone::local(note.modified).date_naive())
+}
+
+/// Minutes in Teaching entries on `keys`.
+fn teaching_minutes<'a>(keys: impl IntoIterator<Item = &'a str>, notes_map: &HashMap<String, Note>) -> u32 {
+    keys.into_iter()
+        .filter_map(|k| notes_map.get(k))
+        .flat_map(|n| &n.time_entries)
+        .filter(|e| e.category == TimeCategory::Teaching)
+        .map(|e| e.minutes)
+        .sum()
+}
+
+/// The lectures directly under `course`, in number order.
+pub fn lectures<'a>(course: &Note, notes: &'a [Note]) -> Vec<Lecture<'a>> {
+    let mut found: Vec<&Note> = notes
+        .iter()
+        .filter(|n| n.parents.contains(&course.key) && note_type_is(n, LECTURE_TYPE))
+        .collect();
+    found.sort_by_key(|n| (day(n), n.title.to_lowercase()));
+    let mut lectures: Vec<Lecture> = found
+        .into_iter()
+        .enumerate()
+        .map(|(i, note)| Lecture {
+            number: frontmatter_field(&note.full_file_content, "lecture")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(i as u32 + 1),
+            note,
+        })
+        .collect();
+    lectures.sort_by_key(|l| (l.number, day(l.note)));
+    lectures
+}
+
+/// Every course, the latest semester first.
+pub fn courses<'a>(notes: &'a [Note], notes_map: &HashMap<String, Note>) -> Vec<Course<'a>> {
+    let mut courses: Vec<Course> = notes
+        .iter()
+        .filter(|n| is_course(n))
+        .map(|note| {
+            let below = descendants(&note.key, notes_map);
+            let keys = std::iter::once(note.key.as_str()).chain(below.iter().map(String::as_str));
+            Course {
+                code: frontmatter_field(&note.full_file_content, "code"),
+                semester: frontmatter_field(&note.full_file_content, "semester"),
+                schedule: frontmatter_field(&note.full_file_content, "schedule"),
+                lectures: lectures(note, notes),
+                teaching_minutes: teaching_minutes(keys, notes_map),
+                note,
+            }
+        })
+        .collect();
+    // "Fall 2025" sorts by its year; a semester without one goes last
+    let year = |c: &Course| {
+        c.semester
+            .as_deref()
+            .and_then(|s| s.split(|ch: char| !ch.is_ascii_digit()).rfind(|w| w.len() == 4)?.parse::<u32>().ok())
+    };
+    courses.sort_by_key(|c| (Reverse(year(c)), c.name().to_lowercase()));
+    courses
+}
+
+/// Create lecture `course.next_lecture()` as `<course path without
+/// extension>/lecture-NN.md`. Returns its key and path.
+pub fn create_lecture(
+    notes_dir: &Path,
+    templates_dir: &Path,
+    course: &Course,
+    notes: &[Note],
+    now: DateTime<FixedOffset>,
+) -> Result<(String, PathBuf), String> {
+    let number = course.next_lecture();
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let dir = course.note.path.with_extension("");
+    let mut relative = dir.join(format!("lecture-{:02}.md", number));
+    if notes_dir.join(&relative).exists() {
+        relative = dir.join(format!("lecture-{:02}-{}.md", number, key));
+    }
+
+    let title = match course.code {
+        Some(ref code) => format!("{} Lecture {}", code, number),
+        None => format!("Lecture {}", number),
+    };
+    let date = now.format("%Y-%m-%d").to_string();
+    let mut frontmatter = new_note_frontmatter(&title, &key, now);
+    frontmatter.push_str(&format!(
+        "type: {}\nlecture: {}\nparent: {}\ndate: {}\n",
+        LECTURE_TYPE, number, course.note.key, date
+    ));
+    let template_name = frontmatter_field(&course.note.full_file_content, "lecture_template");
+    let content = match note_templates::load_optional(templates_dir, template_name.as_deref())? {
+        Some(template) => {
+            let vars = TemplateVars { title: &title, date: &date, bib_key: "" };
+            note_templates::instantiate(&frontmatter, &template, &vars)
+        }
+        None => frontmatter + "---\n\n",
+    };
+
+    let file_path = notes_dir.join(&relative);
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    crate::encryption::write_file(&file_path, &content)?;
+    Ok((key, relative))
+}
+
+fn hours(minutes: u32) -> String {
+    format!("{:.1}h", minutes as f64 / 60.0)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /courses`: every course with its lectures and teaching time.
+pub async fn courses_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let notes_map = state.notes_map();
+    let courses = courses(&notes, &notes_map);
+
+    let mut html = String::from("<h1>Courses</h1>");
+    if courses.is_empty() {
+        html.push_str(
+            "<p>No courses yet. Give a note <code>type: course</code>, with <code>code:</code>, \
+             <code>semester:</code> and <code>schedule:</code>, to list it here.</p>",
+        );
+    }
+    for course in &courses {
+        let details: Vec<String> = [course.semester.as_deref(), course.schedule.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(html_escape)
+            .collect();
+        html.push_str(&format!(
+            r#"<h2><a href="/note/{}">{}</a></h2>
+            <p class="time-breadcrumb">{}{} teaching &middot; {} lectures &middot; Reading list: <a href="/note/{}/reading-list.html">HTML</a>, <a href="/note/{key}/reading-list.md">Markdown</a>, <a href="/note/{key}/reading-list.bib">BibTeX</a>
+            <button onclick="newLecture('{}', this)">New lecture</button></p>"#,
+            course.note.key,
+            html_escape(&course.name()),
+            details.iter().map(|d| format!("{} &middot; ", d)).collect::<String>(),
+            hours(course.teaching_minutes),
+            course.lectures.len(),
+            course.note.key,
+            course.note.key,
+            key = course.note.key,
+        ));
+        if course.lectures.is_empty() {
+            continue;
+        }
+        html.push_str(r#"<table class="time-table"><tr><th>#</th><th>Lecture</th><th>Date</th><th>Teaching</th></tr>"#);
+        for lecture in &course.lectures {
+            let below = descendants(&lecture.note.key, &notes_map);
+            let keys = std::iter::once(lecture.note.key.as_str()).chain(below.iter().map(String::as_str));
+            html.push_str(&format!(
+                r#"<tr><td>{}</td><td><a href="/note/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+                lecture.number,
+                lecture.note.key,
+                html_escape(&lecture.note.title),
+                lecture.note.date.map(|d| d.format("%b %d, %Y").to_string()).unwrap_or_default(),
+                match teaching_minutes(keys, &notes_map) {
+                    0 => String::new(),
+                    m => hours(m),
+                },
+            ));
+        }
+        html.push_str("</table>");
+    }
+    html.push_str(
+        r#"<script>
+        async function newLecture(key, btn) {
+            btn.disabled = true;
+            const resp = await fetch('/api/courses/' + key + '/lecture', {method: 'POST'});
+            if (!resp.ok) { alert('Could not create the lecture: ' + await resp.text()); btn.disabled = false; return; }
+            const lecture = await resp.json();
+            window.location.href = '/note/' + lecture.key + '?edit=true';
+        }
+        </script>"#,
+    );
+
+    Html(base_html("Courses", &html, None, true)).into_response()
+}
+
+/// `POST /api/courses/{key}/lecture`: create the course's next lecture;
+/// returns `{key}`.
+pub async fn new_lecture(
+    AxumPath(key): AxumPath<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes = state.load_notes();
+    let notes_map = state.notes_map();
+    let courses = courses(&notes, &notes_map);
+    let Some(course) = courses.iter().find(|c| c.note.key == key) else {
+        return (StatusCode::NOT_FOUND, "No such course").into_response();
+    };
+    let now = crate::timezone::now();
+    match create_lecture(&state.notes_dir, &state.config.templates_dir, course, &notes, now) {
+        Ok((key, _)) => {
+            state.invalidate_notes_cache();
+            state.reindex_graph_note(&key);
+            Json(serde_json::json!({ "key": key })).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    #[test]
+    fn test_courses_and_lectures() {
+        let dir = TempDir::new("course");
+        fs::create_dir_all(dir.join("teaching")).unwrap();
+        let write = |name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+        write(
+            "teaching/cs101.md",
+            "---\ntitle: Intro to Programming\nid: cs101\ntype: course\ncode: CS 101\nsemester: Fall 2026\n\
+             schedule: MWF 10:00\ntime:\n  - date: 2026-09-01\n    minutes: 60\n    category: teaching\n---\n",
+        );
+        write("old.md", "---\ntitle: Old Course\nid: old\ntype: course\nsemester: Spring 2024\n---\n");
+        write(
+            "l1.md",
+            "---\ntitle: Welcome\nid: l1\ntype: lecture\nparent: cs101\ndate: 2026-09-02\ntime:\n  - date: 2026-09-02\n    minutes: 90\n    category: teaching\n  - date: 2026-09-02\n    minutes: 30\n    category: reading\n---\n",
+        );
+        write("l2.md", "---\ntitle: Recursion\nid: l2\ntype: lecture\nparent: cs101\ndate: 2026-09-04\n---\n");
+        write("l5.md", "---\ntitle: Guest\nid: l5\ntype: lecture\nlecture: 5\nparent: cs101\ndate: 2026-09-03\n---\n");
+        write("notes.md", "---\ntitle: Handout\nid: handout\nparent: cs101\n---\n");
+
+        let notes = load_all_notes(&dir);
+        let notes_map: HashMap<String, Note> = notes.iter().map(|n| (n.key.clone(), n.clone())).collect();
+        let courses = courses(&notes, &notes_map);
+        let names: Vec<String> = courses.iter().map(|c| c.name()).collect();
+        assert_eq!(names, vec!["CS 101: Intro to Programming", "Old Course"]);
+
+        let cs101 = &courses[0];
+        let numbered: Vec<(u32, &str)> = cs101.lectures.iter().map(|l| (l.number, l.note.title.as_str())).collect();
+        assert_eq!(numbered, vec![(1, "Welcome"), (3, "Recursion"), (5, "Guest")]);
+        assert_eq!(cs101.teaching_minutes, 150);
+        assert_eq!(cs101.next_lecture(), 6);
+
+        let now = DateTime::parse_from_rfc3339("2026-09-07T09:00:00-04:00").unwrap();
+        let (key, path) = create_lecture(&dir, &dir.join("templates"), cs101, &notes, now).unwrap();
+        assert_eq!(path, PathBuf::from("teaching/cs101/lecture-06.md"));
+        let created = load_all_notes(&dir).into_iter().find(|n| n.key == key).unwrap();
+        assert_eq!(created.title, "CS 101 Lecture 6");
+        assert_eq!(created.parents, vec!["cs101".to_string()]);
+        assert_eq!(created.date, NaiveDate::from_ymd_opt(2026, 9, 7));
+    }
+}
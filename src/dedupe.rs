@@ -0,0 +1,412 @@
+//! Near-duplicate detection and merging, behind `notes dedupe`.
+//!
+//! Pairs are found three ways: papers sharing a DOI or arXiv id (the usual
+//! result of importing a paper twice under different bib keys), titles whose
+//! word sets nearly coincide, and bodies whose 5-word shingles do. Short
+//! titles and bodies have to be identical after normalization. Candidate
+//! pairs come from an inverted index over rare words/shingles, so the scan
+//! stays well short of comparing every pair of notes.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::formats::check_writable;
+use crate::models::{source_match_id, Note, NoteType, TimeEntry};
+use crate::notes::{append_time_entry, normalize_title, rewrite_note_keys};
+use crate::validate_path_within;
+
+/// Words per body shingle.
+const SHINGLE_WORDS: usize = 5;
+/// Titles shorter than this many words only match when identical.
+const MIN_TITLE_WORDS: usize = 3;
+/// Bodies with fewer shingles than this only match when identical (and
+/// bodies shorter than one shingle never do).
+const MIN_BODY_SHINGLES: usize = 20;
+/// Words/shingles shared by more notes than this are boilerplate and don't
+/// generate candidates on their own.
+const MAX_POSTINGS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MatchKind {
+    Doi,
+    Arxiv,
+    Title,
+    Body,
+}
+
+/// Two notes that look like the same thing. `a` sorts before `b` by path.
+#[derive(Debug, Clone)]
+pub struct DuplicatePair {
+    pub a: String,
+    pub b: String,
+    pub kind: MatchKind,
+    /// Jaccard similarity (1.0 for identifier matches).
+    pub similarity: f64,
+}
+
+impl DuplicatePair {
+    pub fn describe(&self) -> String {
+        match self.kind {
+            MatchKind::Doi => "same DOI".to_string(),
+            MatchKind::Arxiv => "same arXiv id".to_string(),
+            MatchKind::Title => format!("title {:.0}%", self.similarity * 100.0),
+            MatchKind::Body => format!("body {:.0}%", self.similarity * 100.0),
+        }
+    }
+}
+
+fn hash_str(s: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn title_words(note: &Note) -> HashSet<u64> {
+    let title = match note.note_type {
+        NoteType::Paper(ref paper) => paper.effective_metadata(&note.title).title.unwrap_or_default(),
+        NoteType::Note => note.title.clone(),
+    };
+    let normalized = normalize_title(&title);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    match words.len() {
+        0 => HashSet::new(),
+        n if n < MIN_TITLE_WORDS => exact(&normalized),
+        _ => words.into_iter().map(hash_str).collect(),
+    }
+}
+
+/// A one-element set that only matches the identical text.
+fn exact(text: &str) -> HashSet<u64> {
+    HashSet::from([hash_str(&format!("\0{}", text))])
+}
+
+fn body_shingles(note: &Note) -> HashSet<u64> {
+    let normalized = normalize_title(&note.raw_content);
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let shingles: HashSet<u64> = words
+        .windows(SHINGLE_WORDS)
+        .map(|w| hash_str(&w.join(" ")))
+        .collect();
+    match shingles.len() {
+        0 => HashSet::new(),
+        n if n < MIN_BODY_SHINGLES => exact(&normalized),
+        _ => shingles,
+    }
+}
+
+/// DOIs and arXiv ids from a paper's sources, versions and BibTeX, as
+/// `PaperSource::match_id`s.
+fn identifiers(note: &Note) -> Vec<(MatchKind, String)> {
+    let NoteType::Paper(ref paper) = note.note_type else {
+        return vec![];
+    };
+    let mut ids: Vec<(MatchKind, String)> = paper
+        .all_sources()
+        .filter_map(|s| match s.source_type.as_str() {
+            "doi" => Some((MatchKind::Doi, s.match_id())),
+            "arxiv" => Some((MatchKind::Arxiv, s.match_id())),
+            _ => None,
+        })
+        .collect();
+    for parsed in paper.all_bibtex_parsed() {
+        ids.extend(parsed.doi.map(|d| (MatchKind::Doi, d.to_lowercase())));
+        ids.extend(parsed.eprint.map(|e| (MatchKind::Arxiv, source_match_id("arxiv", &e))));
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// Pairs of sets with Jaccard similarity at least `threshold`, among pairs
+/// sharing at least one element that appears in few sets.
+fn similar_sets(sets: &[HashSet<u64>], threshold: f64) -> Vec<(usize, usize, f64)> {
+    let mut postings: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, set) in sets.iter().enumerate() {
+        for &h in set {
+            postings.entry(h).or_default().push(i);
+        }
+    }
+    let mut candidates: HashSet<(usize, usize)> = HashSet::new();
+    for list in postings.values().filter(|l| l.len() > 1 && l.len() <= MAX_POSTINGS) {
+        for (n, &i) in list.iter().enumerate() {
+            for &j in &list[n + 1..] {
+                candidates.insert((i, j));
+            }
+        }
+    }
+    candidates
+        .into_iter()
+        .filter_map(|(i, j)| {
+            let shared = sets[i].intersection(&sets[j]).count();
+            let similarity = shared as f64 / (sets[i].len() + sets[j].len() - shared) as f64;
+            (similarity >= threshold).then_some((i, j, similarity))
+        })
+        .collect()
+}
+
+/// Find likely duplicate pairs, strongest evidence first. Each pair is
+/// reported once, under the strongest way it matched.
+pub fn find_duplicates(notes: &[Note], threshold: f64) -> Vec<DuplicatePair> {
+    let mut notes: Vec<&Note> = notes.iter().collect();
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut found: HashMap<(usize, usize), (MatchKind, f64)> = HashMap::new();
+    let mut record = |i: usize, j: usize, kind: MatchKind, similarity: f64| {
+        let pair = (i.min(j), i.max(j));
+        let entry = found.entry(pair).or_insert((kind, similarity));
+        if (kind, -similarity) < (entry.0, -entry.1) {
+            *entry = (kind, similarity);
+        }
+    };
+
+    let mut by_id: HashMap<(MatchKind, String), Vec<usize>> = HashMap::new();
+    for (i, note) in notes.iter().enumerate() {
+        for id in identifiers(note) {
+            by_id.entry(id).or_default().push(i);
+        }
+    }
+    for ((kind, _), list) in &by_id {
+        for (n, &i) in list.iter().enumerate() {
+            for &j in &list[n + 1..] {
+                record(i, j, *kind, 1.0);
+            }
+        }
+    }
+
+    let titles: Vec<HashSet<u64>> = notes.iter().map(|n| title_words(n)).collect();
+    for (i, j, similarity) in similar_sets(&titles, threshold) {
+        record(i, j, MatchKind::Title, similarity);
+    }
+    let bodies: Vec<HashSet<u64>> = notes.iter().map(|n| body_shingles(n)).collect();
+    for (i, j, similarity) in similar_sets(&bodies, threshold) {
+        record(i, j, MatchKind::Body, similarity);
+    }
+
+    let mut pairs: Vec<DuplicatePair> = found
+        .into_iter()
+        .map(|((i, j), (kind, similarity))| DuplicatePair {
+            a: notes[i].key.clone(),
+            b: notes[j].key.clone(),
+            kind,
+            similarity,
+        })
+        .collect();
+    pairs.sort_by(|x, y| {
+        x.kind
+            .cmp(&y.kind)
+            .then(y.similarity.total_cmp(&x.similarity))
+            .then_with(|| (&x.a, &x.b).cmp(&(&y.a, &y.b)))
+    });
+    pairs
+}
+
+// ============================================================================
+// Merging
+// ============================================================================
+
+/// Folding one note into another. Paths are relative to the notes directory.
+#[derive(Debug, Clone)]
+pub struct MergePlan {
+    pub kept: PathBuf,
+    pub removed: PathBuf,
+    /// Key (and bib key, for papers) of the removed note → the kept one's.
+    pub renames: HashMap<String, String>,
+    /// Notes whose content changes, including the kept note.
+    pub rewrites: Vec<(PathBuf, String)>,
+    /// Number of crosslinks and parent fields retargeted.
+    pub references: usize,
+}
+
+fn bib_key(note: &Note) -> Option<String> {
+    match note.note_type {
+        NoteType::Paper(ref paper) => {
+            Some(paper.effective_metadata(&note.title).bib_key).filter(|k| !k.is_empty())
+        }
+        NoteType::Note => None,
+    }
+}
+
+/// The kept note's file with the removed note's time entries added (except
+/// ones it already has, as with a copied file) and its body appended under
+/// a heading, unless the kept body already contains it.
+fn merged_content(keep: &Note, drop: &Note) -> Result<String, String> {
+    let mut content = keep.full_file_content.clone();
+    let same = |a: &TimeEntry, b: &TimeEntry| {
+        (a.date, a.minutes, &a.category, &a.description) == (b.date, b.minutes, &b.category, &b.description)
+    };
+    for entry in &drop.time_entries {
+        if !keep.time_entries.iter().any(|kept| same(kept, entry)) {
+            content = append_time_entry(&content, entry)?;
+        }
+    }
+    let body = drop.raw_content.trim();
+    if !body.is_empty() && !keep.raw_content.contains(body) {
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!("\n## Merged from {}\n\n{}\n", drop.title, body));
+    }
+    Ok(content)
+}
+
+/// Plan merging `drop` into `keep`: every reference to `drop` (by note key,
+/// or bib key for papers) is retargeted at `keep`, then `drop` is deleted.
+pub fn plan_merge(notes: &[Note], keep: &Note, drop: &Note) -> Result<MergePlan, String> {
+    if keep.key == drop.key {
+        return Err("Cannot merge a note into itself".to_string());
+    }
+    check_writable(&keep.path)?;
+    if keep.parents.contains(&drop.key) {
+        return Err(format!(
+            "'{}' is a child of '{}'; keep the parent instead",
+            keep.title, drop.title
+        ));
+    }
+    let mut renames = HashMap::from([(drop.key.clone(), keep.key.clone())]);
+    if let (Some(from), Some(to)) = (bib_key(drop), bib_key(keep)) {
+        if from != to {
+            renames.insert(from, to);
+        }
+    }
+
+    let kept_content = merged_content(keep, drop)?;
+    let mut rewrites = vec![];
+    let mut references = 0;
+    for note in notes.iter().filter(|n| n.key != drop.key && check_writable(&n.path).is_ok()) {
+        let is_kept = note.key == keep.key;
+        let content = if is_kept { &kept_content } else { &note.full_file_content };
+        let (content, count) = rewrite_note_keys(content, &renames);
+        references += count;
+        if count > 0 || is_kept {
+            rewrites.push((note.path.clone(), content));
+        }
+    }
+    rewrites.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(MergePlan {
+        kept: keep.path.clone(),
+        removed: drop.path.clone(),
+        renames,
+        rewrites,
+        references,
+    })
+}
+
+/// Write the rewritten notes, then delete the merged-away one.
+pub fn apply_merge(notes_dir: &Path, plan: &MergePlan) -> Result<(), String> {
+    let base = notes_dir.to_path_buf();
+    let removed = validate_path_within(&base, &notes_dir.join(&plan.removed))?;
+    for (path, content) in &plan.rewrites {
+        crate::encryption::write_file(&notes_dir.join(path), content)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    fs::remove_file(&removed).map_err(|e| format!("Failed to delete {}: {}", plan.removed.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PaperMeta, PaperSource, TimeCategory};
+
+    const ABSTRACT: &str = "We present a new approach to program analysis in which the analysis is \
+        specified declaratively as a set of Datalog rules and evaluated bottom up by a semi naive \
+        engine that scales to millions of facts while remaining easy to extend with new relations";
+
+    fn note(key: &str, title: &str, body: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            raw_content: format!("\n{}\n", body),
+            full_file_content: format!("---\ntitle: {}\n---\n\n{}\n", title, body),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    fn paper(key: &str, bib_key: &str, doi: &str) -> Note {
+        let mut n = note(key, "Declarative Points-to Analysis", "");
+        n.note_type = NoteType::Paper(PaperMeta {
+            bibtex_entries: vec![format!(
+                "@inproceedings{{{},\n  title={{Strictly Declarative Points-to Analysis}},\n  year={{2009}}\n}}",
+                bib_key
+            )],
+            canonical_key: None,
+            sources: vec![PaperSource {
+                source_type: "doi".to_string(),
+                identifier: doi.to_string(),
+            }],
+            versions: vec![],
+        });
+        n
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let notes = vec![
+            paper("aaa111", "bravenboer2009", "10.1145/1640089.1640108"),
+            paper("bbb222", "Bravenboer09", "10.1145/1640089.1640108"),
+            note("ccc333", "Datalog engines", ABSTRACT),
+            note("ddd444", "Notes on Datalog engines", &format!("{} today", ABSTRACT)),
+            note("eee555", "Zettelkasten method of note taking", "Index cards."),
+            note("fff666", "The Zettelkasten Method of Note-Taking", "Different text."),
+            note("ggg777", "Inbox", "Call the registrar about the room booking."),
+            note("hhh888", "inbox!", "Call the registrar about the room booking"),
+            note("iii999", "Todo", "Todo"),
+            note("jjj000", "Todo list", "Todo"),
+        ];
+        let pairs = find_duplicates(&notes, 0.8);
+        let summary: Vec<(&str, &str, MatchKind)> =
+            pairs.iter().map(|p| (p.a.as_str(), p.b.as_str(), p.kind)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("aaa111", "bbb222", MatchKind::Doi),
+                ("ggg777", "hhh888", MatchKind::Title),
+                ("eee555", "fff666", MatchKind::Title),
+                ("ccc333", "ddd444", MatchKind::Body),
+            ]
+        );
+        assert_eq!(pairs[0].describe(), "same DOI");
+        assert!(pairs[2].similarity < 1.0);
+    }
+
+    #[test]
+    fn test_plan_merge_retargets_references() {
+        let keep = paper("aaa111", "bravenboer2009", "10.1145/1640089.1640108");
+        let mut drop = paper("bbb222", "Bravenboer09", "10.1145/1640089.1640108");
+        drop.raw_content = "\nRead for the CFL chapter.\n".to_string();
+        drop.time_entries = vec![TimeEntry {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+            minutes: 30,
+            category: TimeCategory::Reading,
+            description: None,
+        }];
+        let mut citing = note("ccc333", "Survey", "See [@bbb222] and [@Bravenboer09].");
+        citing.full_file_content = "---\ntitle: Survey\nparent: bbb222\n---\n\nSee [@bbb222] and [@Bravenboer09].\n".to_string();
+        let notes = vec![keep.clone(), drop.clone(), citing];
+
+        let plan = plan_merge(&notes, &keep, &drop).unwrap();
+        assert_eq!(plan.removed, PathBuf::from("bbb222.md"));
+        assert_eq!(plan.references, 3);
+        assert_eq!(plan.rewrites.len(), 2);
+        let (_, kept) = &plan.rewrites[0];
+        assert!(kept.contains("minutes: 30"));
+        assert!(kept.ends_with("## Merged from Declarative Points-to Analysis\n\nRead for the CFL chapter.\n"));
+        let (_, survey) = &plan.rewrites[1];
+        assert!(survey.contains("parent: aaa111\n"));
+        assert!(survey.contains("See [@aaa111] and [@bravenboer2009]."));
+
+        let mut copy = drop.clone();
+        copy.key = "eee555".to_string();
+        let plan = plan_merge(&notes, &drop, &copy).unwrap();
+        let (_, kept) = &plan.rewrites[0];
+        assert_eq!(kept, &drop.full_file_content);
+
+        let mut child = note("ddd444", "Child", "");
+        child.parents = vec!["aaa111".to_string()];
+        assert!(plan_merge(&notes, &child, &notes[0]).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! Weekly digest.
+//!
+//! With `[digest] enabled`, the server writes a prose summary of the seven
+//! days before `weekday` once `hour` has passed: the `[llm]` model gets the
+//! week's created and edited notes and added papers (as on the weekly
+//! review) plus the notes directory's git log with diffs, and its reply is
+//! saved as `digests/YYYY-MM-DD.md` or sent through the `[notify]` channel.
+//! Written digests are recorded in sled so each week's goes out once; a
+//! week missed while the server was down is not backfilled.
+//! `POST /api/digest` writes one on demand from the weekly review page.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, response::Response};
+use axum_extra::extract::CookieJar;
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime, Timelike};
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::config::{DigestConfig, DigestDelivery};
+use crate::models::Note;
+use crate::notes::{new_note_frontmatter, new_note_key};
+use crate::review::{build_review, week_start, WeeklyReview, REVIEW_DIR};
+use crate::AppState;
+
+/// Directory under the notes directory that holds saved digests.
+pub const DIGEST_DIR: &str = "digests";
+
+const SENT_TREE: &str = "digests_sent";
+
+/// Characters of git diff the model is given; the rest is cut.
+const MAX_DIFF_CHARS: usize = 60_000;
+
+const PROMPT: &str = "Write a short weekly digest of a personal research notes collection. \
+The input lists the notes created and edited and the papers added this week, then the week's \
+git log with diffs. Summarize in a few paragraphs of markdown what was worked on, grouped by \
+theme, with the main ideas and decisions from the diffs and what the new papers are about. \
+Refer to notes as [@key] using the keys given. Don't list every note, don't describe the \
+formatting of the diffs, and don't add a title. Reply with the digest only.";
+
+/// Path of the saved digest for the week ending on `end`, relative to the
+/// notes directory.
+pub fn digest_path(end: NaiveDate) -> PathBuf {
+    Path::new(DIGEST_DIR).join(format!("{}.md", end.format("%Y-%m-%d")))
+}
+
+fn digest_title(end: NaiveDate) -> String {
+    format!(
+        "Weekly digest, {} to {}",
+        week_start(end).format("%Y-%m-%d"),
+        end.format("%Y-%m-%d")
+    )
+}
+
+/// End of the week whose digest is due at `now`, if any: the day before
+/// `weekday`, once `hour` has passed on it.
+pub fn due_week(config: &DigestConfig, now: NaiveDateTime) -> Option<NaiveDate> {
+    (now.weekday() == config.weekday && now.hour() >= config.hour).then(|| now.date() - Days::new(1))
+}
+
+/// The week's commits to note files with their diffs, oldest first, cut
+/// to `MAX_DIFF_CHARS`. Saved reviews and digests are left out. Empty when
+/// the directory isn't a git repository.
+pub fn git_changes(notes_dir: &Path, start: NaiveDate, end: NaiveDate) -> String {
+    let output = std::process::Command::new("git")
+        .args(["log", "-p", "-U2", "--reverse", "--no-color", "--relative", "--date=short"])
+        .arg("--format=commit %ad %s")
+        .arg(format!("--since={} 00:00", start.format("%Y-%m-%d")))
+        .arg(format!("--until={} 00:00", (end + Days::new(1)).format("%Y-%m-%d")))
+        .args(["--", "*.md"])
+        .arg(format!(":(exclude){}", REVIEW_DIR))
+        .arg(format!(":(exclude){}", DIGEST_DIR))
+        .current_dir(notes_dir)
+        .output();
+    match output {
+        Ok(o) if o.status.success() => truncate(String::from_utf8_lossy(&o.stdout).into_owned(), MAX_DIFF_CHARS),
+        _ => String::new(),
+    }
+}
+
+fn truncate(mut text: String, max: usize) -> String {
+    if text.len() > max {
+        let cut = (0..=max).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0);
+        text.truncate(cut);
+        text.push_str("\n[diff truncated]\n");
+    }
+    text
+}
+
+/// What the model is asked to summarize: the review's note lists, then
+/// `changes` from `git_changes`. None when nothing happened in the week.
+pub fn digest_input(review: &WeeklyReview, changes: &str) -> Option<String> {
+    if review.created.is_empty() && review.edited.is_empty() && changes.trim().is_empty() {
+        return None;
+    }
+    let list = |heading: &str, notes: &[&Note]| {
+        let mut text = format!("## {}\n\n", heading);
+        if notes.is_empty() {
+            text.push_str("None.\n");
+        }
+        for note in notes {
+            text.push_str(&format!("- {} [@{}]\n", note.title, note.key));
+        }
+        text + "\n"
+    };
+    let mut input = format!(
+        "Week of {} to {}\n\n",
+        review.start.format("%Y-%m-%d"),
+        review.end.format("%Y-%m-%d")
+    );
+    input.push_str(&list("Notes created", &review.created));
+    input.push_str(&list("Notes edited", &review.edited));
+    input.push_str(&list("Papers added", &review.papers));
+    input.push_str("## Git log\n\n");
+    input.push_str(if changes.trim().is_empty() { "No commits.\n" } else { changes });
+    Some(input)
+}
+
+/// Save `digest` as `digests/YYYY-MM-DD.md`. Returns the key of the new
+/// note, or of the one already saved for that week (and false).
+pub fn save_digest(notes_dir: &Path, notes: &[Note], end: NaiveDate, digest: &str) -> Result<(String, bool), String> {
+    let relative = digest_path(end);
+    if let Some(note) = notes.iter().find(|n| n.path == relative) {
+        return Ok((note.key.clone(), false));
+    }
+    let file_path = notes_dir.join(&relative);
+    if file_path.exists() {
+        return Err(format!("{} exists but isn't loaded as a note", relative.display()));
+    }
+
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let content = new_note_frontmatter(&digest_title(end), &key, crate::timezone::now())
+        + &format!("date: {}\n---\n\n", end.format("%Y-%m-%d"))
+        + digest.trim()
+        + &format!("\n\n*Written by AI. [Weekly review](/review/weekly?end={})*\n", end.format("%Y-%m-%d"));
+
+    fs::create_dir_all(notes_dir.join(DIGEST_DIR))
+        .map_err(|e| format!("Failed to create {}: {}", DIGEST_DIR, e))?;
+    crate::encryption::write_file(&file_path, &content)?;
+    Ok((key, true))
+}
+
+/// Ask the model for the digest of the week ending on `end`; None when
+/// the week had no changes.
+async fn write_digest(state: &AppState, end: NaiveDate) -> Result<Option<String>, String> {
+    let notes = state.load_notes();
+    let review = build_review(state, &notes, end);
+    let changes = git_changes(&state.notes_dir, review.start, end);
+    match digest_input(&review, &changes) {
+        Some(input) => crate::llm::complete(&state.http, PROMPT.to_string(), Some(input)).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Write the digest of the week ending on `end` and save it as a note.
+/// Returns the note's key and whether it is new, or None when the week had
+/// no changes.
+async fn digest_note(state: &AppState, end: NaiveDate) -> Result<Option<(String, bool)>, String> {
+    if let Some(note) = state.load_notes().iter().find(|n| n.path == digest_path(end)) {
+        return Ok(Some((note.key.clone(), false)));
+    }
+    let Some(digest) = write_digest(state, end).await? else {
+        return Ok(None);
+    };
+    let (key, created) = save_digest(&state.notes_dir, &state.load_notes(), end, &digest)?;
+    if created {
+        state.invalidate_notes_cache();
+        state.reindex_graph_note(&key);
+    }
+    Ok(Some((key, created)))
+}
+
+fn is_sent(db: &crate::store::Db, end: NaiveDate) -> bool {
+    db.open_tree(SENT_TREE)
+        .and_then(|t| t.contains_key(end.to_string().as_bytes()))
+        .unwrap_or(false)
+}
+
+fn mark_sent(db: &crate::store::Db, end: NaiveDate) -> Result<(), String> {
+    let tree = db.open_tree(SENT_TREE).map_err(|e| e.to_string())?;
+    tree.insert(end.to_string().as_bytes(), chrono::Utc::now().to_rfc3339().as_bytes())
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Write and deliver the week's digest if it is due at `now` and hasn't
+/// gone out.
+async fn deliver_due(state: &AppState, client: &reqwest::Client, now: NaiveDateTime) -> Result<(), String> {
+    let config = &state.config.digest;
+    let Some(end) = due_week(config, now).filter(|&end| !is_sent(&state.db, end)) else {
+        return Ok(());
+    };
+    match config.deliver {
+        DigestDelivery::Note => {
+            if let Some((key, _)) = digest_note(state, end).await? {
+                tracing::info!(key = %key, "Saved weekly digest");
+            }
+        }
+        DigestDelivery::Notify => {
+            if let Some(digest) = write_digest(state, end).await? {
+                crate::reminders::send_text(&state.config.notify, client, &digest_title(end), digest.trim())
+                    .await?;
+                tracing::info!(end = %end, "Sent weekly digest");
+            }
+        }
+    }
+    mark_sent(&state.db, end)
+}
+
+/// Check every hour whether the weekly digest is due. Spawned by the
+/// server when `[digest]` is enabled, unless it is read-only.
+pub async fn deliver_weekly(state: Arc<AppState>) {
+    let client = state.http.clone();
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        // Tried again next hour while still on the digest's day
+        if let Err(e) = deliver_due(&state, &client, crate::timezone::now().naive_local()).await {
+            tracing::warn!(error = %e, "Weekly digest failed");
+        }
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct DigestRequest {
+    pub end: Option<NaiveDate>,
+}
+
+/// `POST /api/digest`: write the digest of the week ending today or on
+/// `end` and save it as a note; returns `{key, created}`.
+pub async fn digest_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<DigestRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let end = body.end.unwrap_or_else(crate::timezone::today);
+    match digest_note(&state, end).await {
+        Ok(Some((key, created))) => {
+            axum::Json(serde_json::json!({ "key": key, "created": created })).into_response()
+        }
+        Ok(None) => (StatusCode::UNPROCESSABLE_ENTITY, "Nothing changed that week").into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_digest() {
+        let dir = TempDir::new("digest");
+        fs::create_dir_all(dir.join(DIGEST_DIR)).unwrap();
+        fs::write(dir.join("idea.md"), "---\ntitle: Idea\nid: aaaaaaaaaaaa\ncreated: 2026-10-14\n---\n").unwrap();
+        fs::write(
+            dir.join("digests/2026-10-10.md"),
+            "---\ntitle: Weekly digest\nid: cccccccccccc\ncreated: 2026-10-12\n---\n",
+        )
+        .unwrap();
+        let mut notes = load_all_notes(&dir.to_path_buf());
+        let long_ago = chrono::DateTime::parse_from_rfc3339("2026-01-01T12:00:00Z").unwrap().into();
+        for note in notes.iter_mut() {
+            note.modified = long_ago;
+        }
+        let end = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let review = crate::review::weekly_review(&notes, end, &[], &HashSet::new(), &[]);
+
+        let input = digest_input(&review, "").unwrap();
+        assert!(input.starts_with("Week of 2026-10-12 to 2026-10-18"));
+        assert!(input.contains("- Idea [@aaaaaaaaaaaa]"));
+        assert!(input.contains("No commits."));
+        assert!(!input.contains("cccccccccccc"));
+        let quiet = crate::review::weekly_review(&notes, end + Days::new(60), &[], &HashSet::new(), &[]);
+        assert!(digest_input(&quiet, "").is_none());
+
+        let config = DigestConfig { enabled: true, ..DigestConfig::default() };
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+        assert_eq!(due_week(&config, monday.and_hms_opt(9, 0, 0).unwrap()), Some(end));
+        assert_eq!(due_week(&config, monday.and_hms_opt(7, 0, 0).unwrap()), None);
+        assert_eq!(due_week(&config, end.and_hms_opt(9, 0, 0).unwrap()), None);
+
+        assert_eq!(truncate("héllo".to_string(), 2), "h\n[diff truncated]\n");
+
+        let (key, created) = save_digest(&dir, &notes, end, "A busy week on [@aaaaaaaaaaaa].\n").unwrap();
+        assert!(created);
+        let saved = fs::read_to_string(dir.join("digests/2026-10-18.md")).unwrap();
+        assert!(saved.contains(&format!("id: {}", key)));
+        assert!(saved.contains("title: Weekly digest, 2026-10-12 to 2026-10-18"));
+        assert!(saved.contains("A busy week on [@aaaaaaaaaaaa]."));
+    }
+}
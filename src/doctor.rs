@@ -0,0 +1,584 @@
+//! Corpus lint for `notes doctor`.
+//!
+//! The loader is forgiving: non-UTF-8 files are skipped and frontmatter
+//! values that don't parse are silently dropped. This module reads every
+//! note file itself and reports what the loader would lose or leave
+//! dangling: malformed frontmatter, unknown time categories, `[@key]`
+//! references to nothing, colliding note keys, duplicate bib keys and
+//! missing PDFs.
+//!
+//! BibTeX entries are checked for unbalanced braces and non-numeric years.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::formats::is_note_file;
+use crate::models::{Note, NoteType, TimeCategory, TimeCategoryDef};
+use crate::notes::{extract_references, is_valid_note_id, load_note, parse_bibtex, parse_created, parse_priority, parse_remind};
+use crate::time_categories::find_category;
+
+// ============================================================================
+// Report Types
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// One problem found in a note file.
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    /// Path relative to the notes directory.
+    pub path: PathBuf,
+    /// 1-based line number, when the problem is on a specific line.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub severity: Severity,
+    /// Short check name, e.g. `dangling-ref`.
+    pub check: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    pub files_checked: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl DoctorReport {
+    /// Human-readable report: one `path:line: severity: [check] message`
+    /// line per issue, then a summary.
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for issue in &self.issues {
+            out.push_str(&issue.path.display().to_string());
+            if let Some(line) = issue.line {
+                out.push_str(&format!(":{}", line));
+            }
+            out.push_str(&format!(": {}: [{}] {}\n", issue.severity, issue.check, issue.message));
+        }
+        out.push_str(&format!(
+            "{} errors, {} warnings in {} files\n",
+            self.errors, self.warnings, self.files_checked
+        ));
+        out
+    }
+}
+
+// ============================================================================
+// Checks
+// ============================================================================
+
+struct Checker<'a> {
+    issues: Vec<Issue>,
+    path: &'a Path,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, severity: Severity, check: &'static str, line: Option<usize>, message: String) {
+        self.issues.push(Issue {
+            path: self.path.to_path_buf(),
+            line,
+            severity,
+            check,
+            message,
+        });
+    }
+
+    fn error(&mut self, check: &'static str, line: Option<usize>, message: String) {
+        self.report(Severity::Error, check, line, message);
+    }
+
+    fn warning(&mut self, check: &'static str, line: Option<usize>, message: String) {
+        self.report(Severity::Warning, check, line, message);
+    }
+}
+
+/// Index of the closing `---`, if the file opens with frontmatter.
+fn frontmatter_end(lines: &[&str]) -> Option<Result<usize, ()>> {
+    if lines.first().map(|l| l.trim()) != Some("---") {
+        return None;
+    }
+    Some(
+        lines
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, l)| l.trim() == "---")
+            .map(|(i, _)| i)
+            .ok_or(()),
+    )
+}
+
+/// Frontmatter values the parser drops without complaint.
+fn check_frontmatter_lines(c: &mut Checker, lines: &[&str]) {
+    for (i, line) in lines.iter().enumerate() {
+        let line_no = Some(i + 2);
+        let trimmed = line.trim();
+        if let Some(date) = trimmed.strip_prefix("- date:") {
+            if NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").is_err() {
+                c.error(
+                    "time",
+                    line_no,
+                    format!("time entry date '{}' is not YYYY-MM-DD; the entry is dropped", date.trim()),
+                );
+            }
+        } else if let Some(minutes) = trimmed.strip_prefix("minutes:") {
+            match minutes.trim().parse::<u32>() {
+                Ok(0) => c.warning("time", line_no, "time entry has 0 minutes".to_string()),
+                Ok(_) => {}
+                Err(_) => c.error("time", line_no, format!("minutes '{}' is not a whole number", minutes.trim())),
+            }
+        } else if line.starts_with(' ') || line.starts_with('\t') || trimmed.starts_with('-') || trimmed.is_empty() {
+            continue;
+        } else if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                field @ ("date" | "due")
+                    if !value.is_empty() && NaiveDate::parse_from_str(value, "%Y-%m-%d").is_err() =>
+                {
+                    c.error("frontmatter", line_no, format!("{} '{}' is not YYYY-MM-DD", field, value));
+                }
+                "created" if !value.is_empty() && parse_created(value).is_none() => {
+                    c.error("frontmatter", line_no, format!("created '{}' is not YYYY-MM-DD or an RFC3339 timestamp", value));
+                }
+                "priority" if !value.is_empty() && parse_priority(value).is_none() => {
+                    c.error("frontmatter", line_no, format!("priority '{}' is not 1-9, high, medium or low", value));
+                }
+                "est_minutes" if !value.is_empty() && value.parse::<u32>().is_err() => {
+                    c.error("frontmatter", line_no, format!("est_minutes '{}' is not a whole number", value));
+                }
+                "remind" if !value.is_empty() && parse_remind(value).is_none() => {
+                    c.error("frontmatter", line_no, format!("remind '{}' is not YYYY-MM-DD HH:MM", value));
+                }
+                "type" if !matches!(value, "" | "note" | "paper") => {
+                    c.warning("frontmatter", line_no, format!("unknown type '{}' (treated as a note)", value));
+                }
+                "id" if !is_valid_note_id(value) => {
+                    c.error(
+                        "frontmatter",
+                        line_no,
+                        format!("id '{}' may only use letters, digits, - and _; the path-derived key is used", value),
+                    );
+                }
+                "rate" if !value.is_empty() && value.parse::<f64>().is_err() => {
+                    c.error("frontmatter", line_no, format!("rate '{}' is not a number", value));
+                }
+                _ => {}
+            }
+        } else {
+            c.error("frontmatter", line_no, format!("line is not `key: value`: {}", trimmed));
+        }
+    }
+}
+
+/// Checks that need only the file itself. `pdfs_dir` is None when PDFs are
+/// in object storage, which isn't checked.
+fn check_note_file(c: &mut Checker, note: &Note, pdfs_dir: Option<&Path>, categories: &[TimeCategoryDef]) {
+    let lines: Vec<&str> = note.full_file_content.lines().collect();
+    match frontmatter_end(&lines) {
+        None => {
+            c.warning("frontmatter", None, "no frontmatter; the title comes from the filename".to_string());
+        }
+        Some(Err(())) => {
+            c.error("frontmatter", Some(1), "frontmatter has no closing ---; it is read as body text".to_string());
+        }
+        Some(Ok(end)) => {
+            check_frontmatter_lines(c, &lines[1..end]);
+            if !lines[1..end].iter().any(|l| l.starts_with("title:")) {
+                c.warning("frontmatter", None, "no title; the title comes from the filename".to_string());
+            }
+        }
+    }
+
+    if let NoteType::Paper(ref paper) = note.note_type {
+        if paper.bibtex_entries.is_empty() {
+            c.warning("bibtex", None, "paper has no BibTeX".to_string());
+        }
+        for (i, entry) in paper.bibtex_entries.iter().enumerate() {
+            match brace_depth(entry) {
+                0 => {}
+                d if d > 0 => c.error("bibtex", None, format!("BibTeX entry {} has {} unclosed {{", i + 1, d)),
+                d => c.error("bibtex", None, format!("BibTeX entry {} has {} unmatched }}", i + 1, -d)),
+            }
+            match parse_bibtex(entry) {
+                None => c.error("bibtex", None, format!("BibTeX entry {} could not be parsed", i + 1)),
+                Some(parsed) => {
+                    if let (None, Some(year)) = (parsed.year, parsed.year_text) {
+                        c.warning("bibtex", None, format!("BibTeX entry {} has year '{}', which is not a number", i + 1, year));
+                    }
+                }
+            }
+        }
+    }
+
+    for entry in &note.time_entries {
+        if let TimeCategory::Other(ref name) = entry.category {
+            if name == "unset" {
+                c.error("time", None, format!("time entry on {} has no category", entry.date));
+                continue;
+            }
+        }
+        if find_category(categories, &entry.category).is_none() {
+            c.warning(
+                "time-category",
+                None,
+                format!("time entry on {} uses unknown category '{}'", entry.date, entry.category),
+            );
+        }
+    }
+
+    if let (Some(pdf), Some(pdfs_dir)) = (&note.pdf, pdfs_dir) {
+        if !pdfs_dir.join(pdf).is_file() {
+            c.error("missing-pdf", None, format!("PDF '{}' not found in {}", pdf, pdfs_dir.display()));
+        }
+    }
+}
+
+/// File-level checks for one note, without the rest of the corpus.
+pub fn check_file(note: &Note, pdfs_dir: Option<&Path>, categories: &[TimeCategoryDef]) -> Vec<Issue> {
+    let mut c = Checker { issues: Vec::new(), path: &note.path };
+    check_note_file(&mut c, note, pdfs_dir, categories);
+    c.issues
+}
+
+/// `{` minus `}`, ignoring escaped `\{` and `\}`.
+fn brace_depth(bibtex: &str) -> i64 {
+    let mut depth = 0;
+    let mut escaped = false;
+    for ch in bibtex.chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// Check every `.md` file under `notes_dir`.
+pub fn check_corpus(notes_dir: &Path, pdfs_dir: Option<&Path>, categories: &[TimeCategoryDef]) -> DoctorReport {
+    let notes_dir = notes_dir.to_path_buf();
+    let mut paths: Vec<PathBuf> = WalkDir::new(&notes_dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_entry(|e| e.depth() != 1 || e.file_name() != crate::trash::TRASH_DIR)
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && is_note_file(e.path()))
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    paths.sort();
+
+    let mut issues = Vec::new();
+    let mut notes = Vec::new();
+    for path in &paths {
+        let relative = path.strip_prefix(&notes_dir).unwrap_or(path).to_path_buf();
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => {
+                issues.push(Issue {
+                    path: relative,
+                    line: None,
+                    severity: Severity::Error,
+                    check: "read",
+                    message: format!("cannot read file: {}", e),
+                });
+                continue;
+            }
+        };
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            let line = bytes[..e.valid_up_to()].iter().filter(|&&b| b == b'\n').count() + 1;
+            issues.push(Issue {
+                path: relative,
+                line: Some(line),
+                severity: Severity::Error,
+                check: "utf8",
+                message: "file is not valid UTF-8; the note is skipped".to_string(),
+            });
+            continue;
+        }
+        if let Some(note) = load_note(path, &notes_dir) {
+            notes.push(note);
+        }
+    }
+
+    let mut by_key: HashMap<&str, Vec<&Note>> = HashMap::new();
+    for note in &notes {
+        by_key.entry(note.key.as_str()).or_default().push(note);
+    }
+    let keys: HashSet<&str> = by_key.keys().copied().collect();
+    let mut bib_keys: HashMap<String, Vec<&Note>> = HashMap::new();
+    for note in &notes {
+        if let NoteType::Paper(ref paper) = note.note_type {
+            let mut cite_keys: Vec<String> = paper
+                .bibtex_entries
+                .iter()
+                .filter_map(|e| parse_bibtex(e).map(|p| p.cite_key))
+                .collect();
+            if cite_keys.is_empty() {
+                cite_keys.push(paper.effective_metadata(&note.title).bib_key);
+            }
+            cite_keys.dedup();
+            for key in cite_keys {
+                bib_keys.entry(key).or_default().push(note);
+            }
+        }
+    }
+
+    for note in &notes {
+        let mut c = Checker { issues: Vec::new(), path: &note.path };
+        check_note_file(&mut c, note, pdfs_dir, categories);
+
+        let others: Vec<String> = by_key[note.key.as_str()]
+            .iter()
+            .filter(|n| n.path != note.path)
+            .map(|n| n.path.display().to_string())
+            .collect();
+        if !others.is_empty() {
+            c.error(
+                "duplicate-key",
+                None,
+                match note.key == crate::notes::generate_key(&note.path) {
+                    true => format!(
+                        "key '{}' is also used by {}; this note loads under a longer key until `notes migrate-keys`",
+                        note.key,
+                        others.join(", ")
+                    ),
+                    false => format!("key '{}' is also used by {}; only one of them is reachable", note.key, others.join(", ")),
+                },
+            );
+        }
+
+        for parent in &note.parents {
+            if !keys.contains(parent.as_str()) {
+                c.error("parent", None, format!("parent '{}' does not match any note", parent));
+            }
+        }
+        if let Some(ref prev) = note.prev_key {
+            if !keys.contains(prev.as_str()) {
+                c.error("prev", None, format!("prev '{}' does not match any note", prev));
+            }
+        }
+
+        check_references(&mut c, note, &keys, &bib_keys);
+
+        if let NoteType::Paper(ref paper) = note.note_type {
+            let own: HashSet<String> = paper
+                .bibtex_entries
+                .iter()
+                .filter_map(|e| parse_bibtex(e).map(|p| p.cite_key))
+                .chain(std::iter::once(paper.effective_metadata(&note.title).bib_key))
+                .collect();
+            let mut own: Vec<String> = own.into_iter().collect();
+            own.sort();
+            for key in own {
+                let Some(users) = bib_keys.get(&key) else { continue };
+                let others: Vec<String> = users
+                    .iter()
+                    .filter(|n| n.key != note.key)
+                    .map(|n| n.path.display().to_string())
+                    .collect();
+                if !others.is_empty() {
+                    c.error(
+                        "duplicate-bib-key",
+                        None,
+                        format!("bib key '{}' is also used by {}", key, others.join(", ")),
+                    );
+                }
+            }
+        }
+        issues.extend(c.issues);
+    }
+
+    issues.sort_by(|a, b| (&a.path, a.line).cmp(&(&b.path, b.line)));
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    DoctorReport {
+        files_checked: paths.len(),
+        errors,
+        warnings: issues.len() - errors,
+        issues,
+    }
+}
+
+/// Drop `` `code` `` spans, where `[@key]` is usually an example.
+fn without_inline_code(line: &str) -> String {
+    line.split('`').step_by(2).collect::<Vec<_>>().join(" ")
+}
+
+/// `[@key]` references in the body that no note answers to. References by
+/// bib key resolve in exports but not in the viewer, so they are warnings.
+fn check_references(
+    c: &mut Checker,
+    note: &Note,
+    keys: &HashSet<&str>,
+    bib_keys: &HashMap<String, Vec<&Note>>,
+) {
+    let lines: Vec<&str> = note.full_file_content.lines().collect();
+    let body_start = match frontmatter_end(&lines) {
+        Some(Ok(end)) => end + 1,
+        _ => 0,
+    };
+    let mut in_code = false;
+    for (i, line) in lines.iter().enumerate().skip(body_start) {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        for reference in extract_references(&without_inline_code(line)) {
+            if keys.contains(reference.as_str()) {
+                continue;
+            }
+            match bib_keys.get(&reference) {
+                Some(papers) if papers.len() == 1 => c.warning(
+                    "dangling-ref",
+                    Some(i + 1),
+                    format!(
+                        "[@{}] is a bib key; the viewer only links note keys (use [@{}])",
+                        reference, papers[0].key
+                    ),
+                ),
+                Some(papers) => c.error(
+                    "dangling-ref",
+                    Some(i + 1),
+                    format!("[@{}] is a bib key shared by {} papers", reference, papers.len()),
+                ),
+                None => c.error(
+                    "dangling-ref",
+                    Some(i + 1),
+                    format!("[@{}] does not match any note", reference),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn scratch_dir() -> TempDir {
+        let dir = TempDir::new("doctor");
+        fs::create_dir_all(dir.join("content/papers")).unwrap();
+        fs::create_dir_all(dir.join("pdfs")).unwrap();
+        dir
+    }
+
+    fn categories() -> Vec<TimeCategoryDef> {
+        vec![TimeCategoryDef { name: "reading".to_string(), color: String::new(), weekly_hours: None }]
+    }
+
+    #[test]
+    fn test_clean_corpus() {
+        let dir = scratch_dir();
+        let content = dir.join("content");
+        fs::write(
+            content.join("papers/a.md"),
+            "---\ntitle: A\ntype: paper\nbibtex: |\n  @article{smith2020a,\n    title = {A}\n  }\npdf: a.pdf\ntime:\n  - date: 2024-01-02\n    minutes: 30\n    category: reading\n---\n\nBody.\n",
+        )
+        .unwrap();
+        fs::write(dir.join("pdfs/a.pdf"), b"%PDF").unwrap();
+        let a_key = crate::notes::generate_key(Path::new("papers/a.md"));
+        fs::write(content.join("b.md"), format!("---\ntitle: B\nparent: {}\n---\n\nSee [@{}].\n", a_key, a_key)).unwrap();
+
+        let report = check_corpus(&content, Some(&dir.join("pdfs")), &categories());
+        assert_eq!(report.files_checked, 2);
+        assert!(report.issues.is_empty(), "{}", report.to_text());
+    }
+
+    #[test]
+    fn test_reports_problems() {
+        let dir = scratch_dir();
+        let content = dir.join("content");
+        fs::write(
+            content.join("papers/a.md"),
+            "---\ntitle: A\ntype: paper\nbibtex: |\n  @article{smith2020a,\n    title = {A}\n  }\npdf: gone.pdf\n---\n",
+        )
+        .unwrap();
+        fs::write(
+            content.join("papers/b.md"),
+            "---\ntitle: B\ntype: paper\nbibtex: |\n  @article{smith2020a,\n    title = {B}\n  }\n---\n",
+        )
+        .unwrap();
+        fs::write(
+            content.join("c.md"),
+            "---\ntitle: C\ndate: 2024-13-01\ntime:\n  - date: 2024-01-02\n    minutes: ten\n    category: napping\n---\n\nSee [@nothing] and [@smith2020a], not `[@code]`.\n\n```\n[@example]\n```\n",
+        )
+        .unwrap();
+        fs::write(content.join("d.md"), "---\ntitle: D\n\nnever closed\n").unwrap();
+        fs::write(content.join("e.md"), b"---\ntitle: E\n---\n\xff\xfe\n").unwrap();
+        fs::write(content.join("f.md"), "---\ntitle: F\nid: 3fa9c01b22d4\n---\n").unwrap();
+        fs::write(content.join("g.md"), "---\ntitle: G\nid: 3fa9c01b22d4\n---\n").unwrap();
+        fs::write(content.join("h.md"), "---\ntitle: H\nid: not a key\n---\n").unwrap();
+        fs::write(
+            content.join("papers/i.md"),
+            "---\ntitle: I\ntype: paper\nbibtex: |\n  @misc{jones,\n    title = {I}},\n    year = {n.d.}\n  }\n---\n",
+        )
+        .unwrap();
+
+        let report = check_corpus(&content, Some(&dir.join("pdfs")), &categories());
+        let found: Vec<(String, Option<usize>, &str)> = report
+            .issues
+            .iter()
+            .map(|i| (i.path.display().to_string(), i.line, i.check))
+            .collect();
+        let expect = |path: &str, line: Option<usize>, check: &str| {
+            assert!(
+                found.contains(&(path.to_string(), line, check)),
+                "missing {} {:?} {}\n{}",
+                path,
+                line,
+                check,
+                report.to_text()
+            );
+        };
+        expect("papers/a.md", None, "missing-pdf");
+        expect("papers/a.md", None, "duplicate-bib-key");
+        expect("papers/b.md", None, "duplicate-bib-key");
+        expect("c.md", Some(3), "frontmatter");
+        expect("c.md", Some(6), "time");
+        expect("c.md", None, "time-category");
+        expect("c.md", Some(10), "dangling-ref");
+        expect("d.md", Some(1), "frontmatter");
+        expect("e.md", Some(4), "utf8");
+        expect("f.md", None, "duplicate-key");
+        expect("g.md", None, "duplicate-key");
+        expect("h.md", Some(3), "frontmatter");
+        let bibtex: Vec<&str> = report
+            .issues
+            .iter()
+            .filter(|i| i.path == Path::new("papers/i.md"))
+            .map(|i| i.message.as_str())
+            .collect();
+        assert!(bibtex.iter().any(|m| m.contains("1 unmatched }")), "{:?}", bibtex);
+        // [@smith2020a] is ambiguous, so it is an error as well; the fenced
+        // example is ignored.
+        assert_eq!(found.iter().filter(|f| f.2 == "dangling-ref").count(), 2);
+        assert_eq!(report.files_checked, 9);
+        assert_eq!(report.errors + report.warnings, report.issues.len());
+    }
+}
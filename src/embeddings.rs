@@ -0,0 +1,269 @@
+//! Semantic search.
+//!
+//! With an `[embeddings]` backend configured, every note's title and body
+//! is turned into a vector by the embeddings service (Ollama or an
+//! OpenAI-compatible API) and stored in sled with a hash of the text, so a
+//! note is only embedded again after it changes. The index is brought up
+//! to date at startup and before each query. `/search?mode=semantic`
+//! embeds the query and ranks notes by cosine similarity, finding related
+//! notes that share no keywords with it.
+//!
+//! Semantic search is for logged-in users only, since each query calls the
+//! service.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use crate::config::{EmbeddingsBackend, EmbeddingsConfig};
+use crate::models::Note;
+use crate::AppState;
+
+/// Note key → sha256 of the embedded text, then the vector as f32 LE.
+const TREE: &str = "embeddings";
+
+/// Characters of a note sent to the service; longer notes are cut off.
+const MAX_TEXT_CHARS: usize = 8_000;
+
+/// Notes embedded per request.
+const BATCH_SIZE: usize = 32;
+
+/// A batch of notes can take a while on a local model.
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Results `/search?mode=semantic` shows.
+pub const MAX_RESULTS: usize = 20;
+
+pub fn is_enabled(config: &EmbeddingsConfig) -> bool {
+    config.backend.is_some()
+}
+
+/// What gets embedded for a note: its title and body.
+fn note_text(note: &Note) -> String {
+    let text = format!("{}\n\n{}", note.title, note.raw_content.trim());
+    match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((i, _)) => text[..i].to_string(),
+        None => text,
+    }
+}
+
+fn text_hash(text: &str) -> [u8; 32] {
+    Sha256::digest(text.as_bytes()).into()
+}
+
+fn encode(hash: &[u8; 32], vector: &[f32]) -> Vec<u8> {
+    let mut bytes = hash.to_vec();
+    bytes.extend(vector.iter().flat_map(|x| x.to_le_bytes()));
+    bytes
+}
+
+fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .get(32..)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect()
+}
+
+/// Notes whose text changed since they were embedded, or that never were,
+/// with that text.
+pub fn stale_notes<'a>(tree: &crate::store::Tree, notes: &'a [Note]) -> Vec<(&'a Note, String)> {
+    notes
+        .iter()
+        .map(|n| (n, note_text(n)))
+        .filter(|(n, text)| match tree.get(n.key.as_bytes()) {
+            Ok(Some(stored)) => stored.get(..32) != Some(&text_hash(text)[..]),
+            _ => true,
+        })
+        .collect()
+}
+
+/// Drop the vectors of notes that no longer exist.
+pub fn prune(tree: &crate::store::Tree, notes: &[Note]) -> Result<(), String> {
+    let keys: HashSet<&[u8]> = notes.iter().map(|n| n.key.as_bytes()).collect();
+    for item in tree.iter() {
+        let (key, _) = item.map_err(|e| e.to_string())?;
+        if !keys.contains(&key[..]) {
+            tree.remove(key).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn store(tree: &crate::store::Tree, key: &str, text: &str, vector: &[f32]) -> Result<(), String> {
+    tree.insert(key.as_bytes(), encode(&text_hash(text), vector))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 {
+        0.0
+    } else {
+        dot / denom
+    }
+}
+
+/// Keys of the stored notes most similar to `query`, best first, with
+/// their similarity.
+pub fn rank(tree: &crate::store::Tree, query: &[f32], limit: usize) -> Vec<(String, f32)> {
+    let mut scored: Vec<(String, f32)> = tree
+        .iter()
+        .filter_map(|item| {
+            let (key, value) = item.ok()?;
+            let key = String::from_utf8(key.to_vec()).ok()?;
+            Some((key, cosine(query, &decode_vector(&value))))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(limit);
+    scored
+}
+
+/// The vectors in a service's response, in input order.
+fn parse_response(backend: EmbeddingsBackend, body: &serde_json::Value) -> Result<Vec<Vec<f32>>, String> {
+    let to_vector = |v: &serde_json::Value| -> Option<Vec<f32>> {
+        v.as_array()?.iter().map(|x| x.as_f64().map(|x| x as f32)).collect()
+    };
+    let vectors: Option<Vec<Vec<f32>>> = match backend {
+        EmbeddingsBackend::Ollama => body["embeddings"].as_array().and_then(|a| a.iter().map(to_vector).collect()),
+        EmbeddingsBackend::OpenAI => body["data"]
+            .as_array()
+            .and_then(|a| a.iter().map(|d| to_vector(&d["embedding"])).collect()),
+    };
+    vectors.ok_or_else(|| "Unexpected response from the embeddings service".to_string())
+}
+
+/// Embed `texts` with the configured service.
+pub async fn embed(
+    config: &EmbeddingsConfig,
+    client: &reqwest::Client,
+    texts: &[String],
+) -> Result<Vec<Vec<f32>>, String> {
+    let Some(backend) = config.backend else {
+        return Err("Semantic search is not configured".to_string());
+    };
+    let url = config.url.as_deref().unwrap_or_default();
+    let mut request = client.post(url).timeout(TIMEOUT).json(&serde_json::json!({
+        "model": config.model,
+        "input": texts,
+    }));
+    if let Some(key) = config.api_key_env.as_deref().and_then(|var| std::env::var(var).ok()) {
+        request = request.bearer_auth(key);
+    }
+    let result = async {
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Embeddings request failed: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Embeddings service returned HTTP {}", response.status()));
+        }
+        let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+        let vectors = parse_response(backend, &body)?;
+        if vectors.len() != texts.len() {
+            return Err(format!("Asked for {} embeddings, got {}", texts.len(), vectors.len()));
+        }
+        Ok(vectors)
+    }
+    .await;
+    crate::telemetry::record_external_call("embeddings", result.is_ok());
+    result
+}
+
+/// Embed every note that changed since it was last embedded and drop
+/// deleted ones. Returns how many were embedded.
+pub async fn sync_index(state: &AppState, client: &reqwest::Client) -> Result<usize, String> {
+    let tree = state.db.open_tree(TREE).map_err(|e| e.to_string())?;
+    let notes = state.load_notes();
+    prune(&tree, &notes)?;
+    let stale = stale_notes(&tree, &notes);
+    for batch in stale.chunks(BATCH_SIZE) {
+        let texts: Vec<String> = batch.iter().map(|(_, text)| text.clone()).collect();
+        let vectors = embed(&state.config.embeddings, client, &texts).await?;
+        for ((note, text), vector) in batch.iter().zip(&vectors) {
+            store(&tree, &note.key, text, vector)?;
+        }
+    }
+    Ok(stale.len())
+}
+
+/// Build the index in the background at startup.
+pub async fn index_at_startup(state: Arc<AppState>) {
+    match sync_index(&state, &state.http).await {
+        Ok(0) => {}
+        Ok(n) => tracing::info!(notes = n, "Embedded notes for semantic search"),
+        Err(e) => tracing::warn!(error = %e, "Failed to build the semantic search index"),
+    }
+}
+
+/// Keys of the notes closest in meaning to `query`, best first, with their
+/// similarity. Notes changed since the last query are embedded first.
+pub async fn semantic_search(state: &AppState, query: &str) -> Result<Vec<(String, f32)>, String> {
+    sync_index(state, &state.http).await?;
+    let query_vector = embed(&state.config.embeddings, &state.http, &[query.to_string()])
+        .await?
+        .pop()
+        .unwrap_or_default();
+    let tree = state.db.open_tree(TREE).map_err(|e| e.to_string())?;
+    Ok(rank(&tree, &query_vector, MAX_RESULTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_index_and_rank() {
+        let dir = TempDir::new("embeddings");
+        fs::write(dir.join("a.md"), "---\ntitle: Types\nid: aaaaaaaaaaaa\n---\n\nType systems.\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: Cooking\nid: bbbbbbbbbbbb\n---\n\nBread.\n").unwrap();
+        let notes = load_all_notes(&dir);
+        let db = crate::store::Db::temporary();
+        let tree = db.open_tree(TREE).unwrap();
+
+        let stale = stale_notes(&tree, &notes);
+        assert_eq!(stale.len(), 2);
+        for (note, text) in &stale {
+            let vector = if note.title == "Types" { [1.0, 0.1] } else { [0.0, 1.0] };
+            store(&tree, &note.key, text, &vector).unwrap();
+        }
+        assert!(stale_notes(&tree, &notes).is_empty());
+
+        let ranked = rank(&tree, &[0.9, 0.0], 10);
+        assert_eq!(ranked[0].0, "aaaaaaaaaaaa");
+        assert!(ranked[0].1 > 0.99 && ranked[1].1 < 0.01);
+
+        // An edited note is stale again; a deleted one is pruned
+        fs::write(dir.join("a.md"), "---\ntitle: Types\nid: aaaaaaaaaaaa\n---\n\nGradual typing.\n").unwrap();
+        fs::remove_file(dir.join("b.md")).unwrap();
+        let notes = load_all_notes(&dir);
+        assert_eq!(stale_notes(&tree, &notes).len(), 1);
+        prune(&tree, &notes).unwrap();
+        assert_eq!(rank(&tree, &[1.0, 0.0], 10).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let ollama = serde_json::json!({"model": "m", "embeddings": [[0.5, 1.0], [0.0, -1.0]]});
+        assert_eq!(
+            parse_response(EmbeddingsBackend::Ollama, &ollama).unwrap(),
+            vec![vec![0.5, 1.0], vec![0.0, -1.0]]
+        );
+        let openai = serde_json::json!({"data": [{"index": 0, "embedding": [0.25]}]});
+        assert_eq!(parse_response(EmbeddingsBackend::OpenAI, &openai).unwrap(), vec![vec![0.25]]);
+        assert!(parse_response(EmbeddingsBackend::OpenAI, &ollama).is_err());
+    }
+}
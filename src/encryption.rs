@@ -0,0 +1,272 @@
+//! Encryption at rest for notes marked `encrypted: true`.
+//!
+//! Saving such a note replaces its body with an armored block; the
+//! frontmatter stays readable so titles, tags and links keep working. The
+//! block starts with a `Version:` line, then holds a salt, a random nonce
+//! and the body sealed with XChaCha20-Poly1305 under a key derived from
+//! the passphrase by Argon2id; the version line is authenticated too.
+//! Bodies are decrypted only for logged-in readers and the editor; search,
+//! exports and everyone else see the block or a notice. Writes go through
+//! `write_file`, which seals; rewrites of an existing note decrypt with
+//! `open_file` first. The passphrase is read from the environment once at
+//! startup by `configure`.
+//!
+//! `Note` keeps the sealed body, so the index and caches never hold
+//! plaintext, and sealed notes can't be shared.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::RngCore;
+
+use crate::config::EncryptionConfig;
+use crate::models::Note;
+use crate::notes::{body_line_offset, parse_frontmatter};
+
+const BEGIN: &str = "-----BEGIN ENCRYPTED NOTE-----";
+const END: &str = "-----END ENCRYPTED NOTE-----";
+const DEFAULT_PASSPHRASE_ENV: &str = "NOTES_ENCRYPTION_PASSPHRASE";
+/// Armor header naming the format; bumped when the format changes.
+const VERSION_LINE: &str = "Version: 2";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const LINE_WIDTH: usize = 76;
+
+/// Shown in place of the body when it can't be shown.
+const LOCKED_NOTICE: &str = "*This note is encrypted. Log in to read it.*";
+
+static KEYRING: RwLock<Option<Keyring>> = RwLock::new(None);
+
+/// Read the passphrase named by `[encryption]` (set at startup).
+pub fn configure(config: &EncryptionConfig) {
+    let var = config.passphrase_env.as_deref().unwrap_or(DEFAULT_PASSPHRASE_ENV);
+    let keyring = std::env::var(var).ok().filter(|p| !p.is_empty()).map(|p| Keyring::new(&p));
+    *KEYRING.write().unwrap_or_else(|e| e.into_inner()) = keyring;
+}
+
+/// The passphrase and the keys derived from it so far. Notes sealed by
+/// this process share one salt, so Argon2 runs once per salt rather than
+/// once per save.
+struct Keyring {
+    passphrase: String,
+    write_salt: [u8; SALT_LEN],
+    derived: Mutex<HashMap<[u8; SALT_LEN], [u8; 32]>>,
+}
+
+impl Keyring {
+    fn new(passphrase: &str) -> Self {
+        let mut write_salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut write_salt);
+        Keyring { passphrase: passphrase.to_string(), write_salt, derived: Mutex::new(HashMap::new()) }
+    }
+
+    fn cipher(&self, salt: &[u8; SALT_LEN]) -> Result<XChaCha20Poly1305, String> {
+        let cached = self.derived.lock().unwrap_or_else(|e| e.into_inner()).get(salt).copied();
+        let key = match cached {
+            Some(key) => key,
+            None => {
+                let mut key = [0u8; 32];
+                argon2::Argon2::default()
+                    .hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+                    .map_err(|e| format!("Key derivation failed: {}", e))?;
+                self.derived.lock().unwrap_or_else(|e| e.into_inner()).insert(*salt, key);
+                key
+            }
+        };
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    /// `body` as an armored block.
+    fn seal(&self, body: &str) -> Result<String, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let payload = Payload { msg: body.as_bytes(), aad: VERSION_LINE.as_bytes() };
+        let sealed = self
+            .cipher(&self.write_salt)?
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| "Encryption failed".to_string())?;
+        let mut blob = self.write_salt.to_vec();
+        blob.extend_from_slice(&nonce);
+        blob.extend_from_slice(&sealed);
+
+        let encoded = BASE64.encode(blob);
+        let lines: Vec<&str> = encoded
+            .as_bytes()
+            .chunks(LINE_WIDTH)
+            .map(|c| std::str::from_utf8(c).unwrap_or_default())
+            .collect();
+        Ok(format!("{}\n{}\n{}\n{}\n", BEGIN, VERSION_LINE, lines.join("\n"), END))
+    }
+
+    /// The body inside an armored block.
+    fn open(&self, armored: &str) -> Result<String, String> {
+        let inner = armored
+            .trim()
+            .strip_prefix(BEGIN)
+            .and_then(|rest| rest.trim_end().strip_suffix(END))
+            .ok_or("Not an encrypted note body")?
+            .trim_start();
+        let (version, encoded) = inner.split_once('\n').unwrap_or((inner, ""));
+        if version.trim_end() != VERSION_LINE {
+            return Err("Encrypted body is in an unsupported format".to_string());
+        }
+        let encoded: String = encoded.split_whitespace().collect();
+        let blob = BASE64.decode(encoded).map_err(|_| "Encrypted body is corrupt")?;
+        if blob.len() < SALT_LEN + NONCE_LEN + TAG_LEN {
+            return Err("Encrypted body is corrupt".to_string());
+        }
+        let (salt, rest) = blob.split_at(SALT_LEN);
+        let (nonce, sealed) = rest.split_at(NONCE_LEN);
+        let mut salt_bytes = [0u8; SALT_LEN];
+        salt_bytes.copy_from_slice(salt);
+        let payload = Payload { msg: sealed, aad: VERSION_LINE.as_bytes() };
+        let body = self
+            .cipher(&salt_bytes)?
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| "Wrong passphrase, or the encrypted body was altered".to_string())?;
+        String::from_utf8(body).map_err(|_| "Decrypted body is not UTF-8".to_string())
+    }
+}
+
+/// Frontmatter (through its closing `---` line) and body of a note file.
+fn split(content: &str) -> (&str, &str) {
+    let offset = body_line_offset(content);
+    let at = content.split_inclusive('\n').take(offset).map(str::len).sum();
+    content.split_at(at)
+}
+
+/// Whether a note body is an armored block.
+pub fn is_sealed(body: &str) -> bool {
+    body.trim_start().starts_with(BEGIN)
+}
+
+/// Whether a whole note file (frontmatter and body) has a sealed body.
+pub fn is_sealed_file(content: &str) -> bool {
+    is_sealed(split(content).1)
+}
+
+fn with_keyring<T>(f: impl FnOnce(&Keyring) -> Result<T, String>) -> Result<T, String> {
+    match *KEYRING.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref keyring) => f(keyring),
+        None => Err("Encrypted notes need a passphrase (NOTES_ENCRYPTION_PASSPHRASE, or [encryption] passphrase_env)".to_string()),
+    }
+}
+
+fn seal_file_with(keyring: &Keyring, content: &str) -> Result<String, String> {
+    let (head, body) = split(content);
+    if !parse_frontmatter(content).0.encrypted || is_sealed(body) {
+        return Ok(content.to_string());
+    }
+    Ok(format!("{}\n{}", head, keyring.seal(body.trim_start_matches('\n'))?))
+}
+
+fn open_file_with(keyring: &Keyring, content: &str) -> Result<String, String> {
+    let (head, body) = split(content);
+    if !is_sealed(body) {
+        return Ok(content.to_string());
+    }
+    Ok(format!("{}\n{}", head, keyring.open(body)?))
+}
+
+/// File contents as they should be written: the body sealed when the
+/// frontmatter says `encrypted: true`, else unchanged. Fails rather than
+/// write an encrypted note in the clear.
+pub fn seal_file(content: &str) -> Result<String, String> {
+    if !parse_frontmatter(content).0.encrypted || is_sealed(split(content).1) {
+        return Ok(content.to_string());
+    }
+    with_keyring(|keyring| seal_file_with(keyring, content))
+}
+
+/// File contents with the body decrypted if sealed, for rewrites that
+/// edit a note in place before writing it back with `write_file`.
+pub fn open_file(content: &str) -> Result<String, String> {
+    if !is_sealed(split(content).1) {
+        return Ok(content.to_string());
+    }
+    with_keyring(|keyring| open_file_with(keyring, content))
+}
+
+/// Write a note file through `seal_file`. Returns what was written.
+pub fn write_file(path: &Path, content: &str) -> Result<String, String> {
+    let sealed = seal_file(content)?;
+    std::fs::write(path, &sealed).map_err(|e| format!("Failed to write note: {}", e))?;
+    Ok(sealed)
+}
+
+/// Write any file under the notes directory; Markdown goes through
+/// `write_file`, anything else (or not UTF-8) is written as is.
+pub fn write_bytes(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if path.extension().is_some_and(|e| e == "md") => write_file(path, text).map(drop),
+        _ => std::fs::write(path, bytes).map_err(|e| format!("Failed to write: {}", e)),
+    }
+}
+
+/// The note with its body decrypted, for the editor and logged-in readers.
+pub fn open_note(note: &Note) -> Result<Cow<'_, Note>, String> {
+    if !is_sealed(&note.raw_content) {
+        return Ok(Cow::Borrowed(note));
+    }
+    let content = with_keyring(|keyring| open_file_with(keyring, &note.full_file_content))?;
+    let mut opened = note.clone();
+    opened.raw_content = split(&content).1.trim_start_matches('\n').to_string();
+    opened.full_file_content = content;
+    Ok(Cow::Owned(opened))
+}
+
+/// The note as a reader sees it: decrypted when logged in, else (or when
+/// it can't be decrypted) with a notice for a body.
+pub fn for_reader(note: &Note, logged_in: bool) -> Cow<'_, Note> {
+    if !is_sealed(&note.raw_content) {
+        return Cow::Borrowed(note);
+    }
+    let notice = if logged_in {
+        match open_note(note) {
+            Ok(opened) => return opened,
+            Err(e) => format!("*This note is encrypted and could not be decrypted: {}.*", e),
+        }
+    } else {
+        LOCKED_NOTICE.to_string()
+    };
+    let mut locked = note.clone();
+    locked.raw_content = notice;
+    Cow::Owned(locked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let keyring = Keyring::new("correct horse");
+        let content = "---\ntitle: Medical\nencrypted: true\n---\n\nBlood type: O-\n";
+        let sealed = seal_file_with(&keyring, content).unwrap();
+        assert!(sealed.starts_with("---\ntitle: Medical\nencrypted: true\n---\n\n-----BEGIN"));
+        assert!(!sealed.contains("Blood type"));
+        assert_eq!(seal_file_with(&keyring, &sealed).unwrap(), sealed);
+        assert_eq!(open_file_with(&keyring, &sealed).unwrap(), content);
+
+        let plain = "---\ntitle: Open\n---\n\nHello\n";
+        assert_eq!(seal_file_with(&keyring, plain).unwrap(), plain);
+
+        let other = Keyring::new("wrong");
+        assert!(open_file_with(&other, &sealed).is_err());
+        let (head, body) = split(&sealed);
+        let mut lines: Vec<String> = body.lines().map(String::from).collect();
+        assert_eq!(lines[2], VERSION_LINE);
+        let last = lines.len() - 2;
+        let flipped = if lines[last].starts_with('A') { "B" } else { "A" };
+        lines[last].replace_range(0..1, flipped);
+        assert!(open_file_with(&keyring, &format!("{}{}", head, lines.join("\n"))).is_err());
+        let downgraded = sealed.replace(VERSION_LINE, "Version: 1");
+        assert!(open_file_with(&keyring, &downgraded).is_err());
+    }
+}
@@ -0,0 +1,1481 @@
+//! Note export to other formats.
+//!
+//! Notes are converted from their markdown body via pulldown-cmark events.
+//! Crosslinks (`[@key]`) are first rewritten to ordinary markdown links with
+//! a `note:` scheme so that every exporter sees them as links and can
+//! decide how to render them.
+//!
+//! Org nests headings under the note with a PROPERTIES drawer from the
+//! frontmatter; standalone HTML inlines the CSS and local images; LaTeX
+//! turns paper crosslinks and `[@bibkey]` into `\cite{}`; DOCX and EPUB go
+//! through pandoc when it is installed.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::{generate_bibliography, html_escape};
+use crate::proxy::client_info;
+use crate::templates::STYLE;
+use crate::AppState;
+
+/// URL scheme used for crosslinks after `crosslinks_to_links`.
+pub const NOTE_LINK_SCHEME: &str = "note:";
+
+// ============================================================================
+// Shared Helpers
+// ============================================================================
+
+/// Markdown extensions enabled for all exporters.
+pub fn markdown_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+}
+
+/// Rewrite `[@key]` crosslinks to `[Title](note:key)`. Unknown keys are
+/// left untouched.
+pub fn crosslinks_to_links(content: &str, notes: &HashMap<String, Note>) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("[@") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find(']') {
+            Some(end) if notes.contains_key(&after[..end]) => {
+                let key = &after[..end];
+                let title = notes[key].title.replace('[', "(").replace(']', ")");
+                out.push_str(&format!("[{}]({}{})", title, NOTE_LINK_SCHEME, key));
+                rest = &after[end + 1..];
+            }
+            _ => {
+                out.push_str("[@");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A filesystem-friendly name for downloads, from the note's file stem.
+pub fn export_filename(note: &Note, extension: &str) -> String {
+    let stem = note
+        .path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| note.key.clone());
+    let stem: String = stem
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    format!("{}.{}", stem, extension)
+}
+
+fn download(content_type: &str, filename: &str, body: impl IntoResponse) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+// ============================================================================
+// Org-mode
+// ============================================================================
+
+/// Org tags may only contain letters, digits, `_` and `@`.
+fn org_tag(tag: &str) -> String {
+    tag.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '@' { c } else { '_' })
+        .collect()
+}
+
+fn org_property(out: &mut String, name: &str, value: &str) {
+    let value = value.replace('\n', " ");
+    if !value.trim().is_empty() {
+        out.push_str(&format!(":{}: {}\n", name, value.trim()));
+    }
+}
+
+/// Convert a markdown body to Org. Markdown headings are nested below
+/// `level` (the level of the note's own heading).
+pub fn markdown_to_org(markdown: &str, level: usize) -> String {
+    let mut out = String::new();
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut links: Vec<(String, usize)> = Vec::new();
+    let mut in_image = false;
+    let mut in_code = false;
+
+    let newline = |out: &mut String| {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+    };
+    let list_indent = |lists: &Vec<Option<u64>>| "  ".repeat(lists.len().saturating_sub(1));
+
+    for event in Parser::new_ext(markdown, markdown_options()) {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { level: h, .. } => {
+                    newline(&mut out);
+                    out.push_str(&"*".repeat(level + h as usize));
+                    out.push(' ');
+                }
+                Tag::Paragraph if lists.is_empty() => newline(&mut out),
+                Tag::BlockQuote => {
+                    newline(&mut out);
+                    out.push_str("#+BEGIN_QUOTE\n");
+                }
+                Tag::CodeBlock(kind) => {
+                    newline(&mut out);
+                    let lang = match kind {
+                        CodeBlockKind::Fenced(lang) => lang.split_whitespace().next().unwrap_or("").to_string(),
+                        CodeBlockKind::Indented => String::new(),
+                    };
+                    if lang.is_empty() {
+                        out.push_str("#+BEGIN_EXAMPLE\n");
+                    } else {
+                        out.push_str(&format!("#+BEGIN_SRC {}\n", lang));
+                    }
+                    in_code = true;
+                }
+                Tag::List(start) => {
+                    newline(&mut out);
+                    lists.push(start);
+                }
+                Tag::Item => {
+                    newline(&mut out);
+                    let indent = list_indent(&lists);
+                    let bullet = match lists.last_mut() {
+                        Some(Some(n)) => {
+                            let bullet = format!("{}. ", n);
+                            *n += 1;
+                            bullet
+                        }
+                        _ => "- ".to_string(),
+                    };
+                    out.push_str(&indent);
+                    out.push_str(&bullet);
+                }
+                Tag::Emphasis => out.push('/'),
+                Tag::Strong => out.push('*'),
+                Tag::Strikethrough => out.push('+'),
+                Tag::Link { dest_url, .. } => links.push((dest_url.to_string(), out.len())),
+                Tag::Image { dest_url, .. } => {
+                    out.push_str(&format!("[[{}]]", dest_url));
+                    in_image = true;
+                }
+                Tag::Table(_) => newline(&mut out),
+                Tag::TableRow | Tag::TableHead => out.push('|'),
+                Tag::TableCell => out.push(' '),
+                Tag::FootnoteDefinition(label) => {
+                    newline(&mut out);
+                    out.push_str(&format!("[fn:{}] ", label));
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Heading(_) => out.push('\n'),
+                TagEnd::Paragraph => {
+                    out.push('\n');
+                    if lists.is_empty() {
+                        out.push('\n');
+                    }
+                }
+                TagEnd::BlockQuote => {
+                    while out.ends_with("\n\n") {
+                        out.pop();
+                    }
+                    newline(&mut out);
+                    out.push_str("#+END_QUOTE\n\n");
+                }
+                TagEnd::CodeBlock => {
+                    newline(&mut out);
+                    let end = if out.rfind("#+BEGIN_SRC") > out.rfind("#+BEGIN_EXAMPLE") {
+                        "#+END_SRC"
+                    } else {
+                        "#+END_EXAMPLE"
+                    };
+                    out.push_str(end);
+                    out.push_str("\n\n");
+                    in_code = false;
+                }
+                TagEnd::List(_) => {
+                    lists.pop();
+                    if lists.is_empty() {
+                        newline(&mut out);
+                        out.push('\n');
+                    }
+                }
+                TagEnd::Item => newline(&mut out),
+                TagEnd::Emphasis => out.push('/'),
+                TagEnd::Strong => out.push('*'),
+                TagEnd::Strikethrough => out.push('+'),
+                TagEnd::Link => {
+                    if let Some((dest, start)) = links.pop() {
+                        let text = out.split_off(start);
+                        let target = match dest.strip_prefix(NOTE_LINK_SCHEME) {
+                            Some(key) => format!("id:{}", key),
+                            None => dest,
+                        };
+                        if text.is_empty() || text == target {
+                            out.push_str(&format!("[[{}]]", target));
+                        } else {
+                            out.push_str(&format!("[[{}][{}]]", target, text));
+                        }
+                    }
+                }
+                TagEnd::Image => in_image = false,
+                TagEnd::TableHead => out.push_str("\n|-\n"),
+                TagEnd::TableRow => out.push('\n'),
+                TagEnd::TableCell => out.push_str(" |"),
+                TagEnd::Table => out.push('\n'),
+                TagEnd::FootnoteDefinition => newline(&mut out),
+                _ => {}
+            },
+            Event::Text(text) => {
+                if in_image {
+                    continue;
+                }
+                if in_code {
+                    out.push_str(&text);
+                } else if !lists.is_empty() {
+                    let indent = format!("{}  ", list_indent(&lists));
+                    out.push_str(&text.replace('\n', &format!("\n{}", indent)));
+                } else {
+                    out.push_str(&text);
+                }
+            }
+            Event::Code(code) => out.push_str(&format!("~{}~", code)),
+            Event::Html(html) => {
+                newline(&mut out);
+                out.push_str("#+BEGIN_EXPORT html\n");
+                out.push_str(&html);
+                newline(&mut out);
+                out.push_str("#+END_EXPORT\n");
+            }
+            Event::InlineHtml(html) => out.push_str(&format!("@@html:{}@@", html)),
+            Event::FootnoteReference(label) => out.push_str(&format!("[fn:{}]", label)),
+            Event::SoftBreak => {
+                out.push('\n');
+                if !lists.is_empty() {
+                    out.push_str(&format!("{}  ", list_indent(&lists)));
+                }
+            }
+            Event::HardBreak => out.push_str("\\\\\n"),
+            Event::Rule => {
+                newline(&mut out);
+                out.push_str("-----\n\n");
+            }
+            Event::TaskListMarker(done) => out.push_str(if done { "[X] " } else { "[ ] " }),
+        }
+    }
+
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
+
+/// Render one note as an Org heading at `level`, with a PROPERTIES drawer
+/// for its frontmatter.
+pub fn note_to_org(note: &Note, notes: &HashMap<String, Note>, level: usize) -> String {
+    let mut out = "*".repeat(level);
+    out.push(' ');
+    out.push_str(&note.title.replace('\n', " "));
+    let tags: Vec<String> = note.tags.iter().map(|t| org_tag(t)).filter(|t| !t.is_empty()).collect();
+    if !tags.is_empty() {
+        out.push_str(&format!(" :{}:", tags.join(":")));
+    }
+    out.push('\n');
+
+    out.push_str(":PROPERTIES:\n");
+    org_property(&mut out, "ID", &note.key);
+    org_property(&mut out, "FILE", &note.path.to_string_lossy());
+    if let Some(date) = note.date {
+        org_property(&mut out, "DATE", &date.format("[%Y-%m-%d %a]").to_string());
+    }
+    if !note.parents.is_empty() {
+        org_property(&mut out, "PARENT", &note.parents.join(" "));
+    }
+    if let Some(ref pdf) = note.pdf {
+        org_property(&mut out, "PDF", pdf);
+    }
+    if let Some(rate) = note.hourly_rate {
+        org_property(&mut out, "RATE", &rate.to_string());
+    }
+    if let NoteType::Paper(ref paper) = note.note_type {
+        let meta = paper.effective_metadata(&note.title);
+        org_property(&mut out, "TYPE", "paper");
+        org_property(&mut out, "BIB_KEY", &meta.bib_key);
+        org_property(&mut out, "AUTHORS", meta.authors.as_deref().unwrap_or(""));
+        if let Some(year) = meta.year {
+            org_property(&mut out, "YEAR", &year.to_string());
+        }
+        org_property(&mut out, "VENUE", meta.venue.as_deref().unwrap_or(""));
+        for source in &paper.sources {
+            org_property(
+                &mut out,
+                &format!("SOURCE_{}", source.source_type.to_uppercase()),
+                &source.identifier,
+            );
+        }
+    }
+    out.push_str(":END:\n");
+
+    let body = markdown_to_org(&crosslinks_to_links(&note.raw_content, notes), level);
+    if !body.trim().is_empty() {
+        out.push('\n');
+        out.push_str(body.trim_start_matches('\n'));
+        out.push('\n');
+    }
+
+    if let NoteType::Paper(ref paper) = note.note_type {
+        for entry in &paper.bibtex_entries {
+            out.push_str(&format!("\n#+BEGIN_SRC bibtex\n{}\n#+END_SRC\n", entry.trim()));
+        }
+    }
+
+    if !note.time_entries.is_empty() {
+        out.push_str(&format!("\n{} Time Log\n", "*".repeat(level + 1)));
+        out.push_str("| Date | Minutes | Category | Description |\n|-\n");
+        for entry in &note.time_entries {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                entry.date.format("%Y-%m-%d"),
+                entry.minutes,
+                entry.category,
+                entry.description.as_deref().unwrap_or("").replace('|', "\\vert{}")
+            ));
+        }
+    }
+
+    out
+}
+
+/// All notes as one Org document, children nested under their first shown
+/// parent and siblings sorted by title. Hidden notes are skipped unless `include_hidden`.
+pub fn corpus_to_org(notes: &HashMap<String, Note>, include_hidden: bool) -> String {
+    let visible = |n: &Note| include_hidden || !n.hidden;
+    let mut children: HashMap<Option<&str>, Vec<&Note>> = HashMap::new();
+    for note in notes.values().filter(|n| visible(n)) {
+        let parent = note
+            .parents
+            .iter()
+            .map(String::as_str)
+            .find(|p| notes.get(*p).is_some_and(visible));
+        children.entry(parent).or_default().push(note);
+    }
+    for list in children.values_mut() {
+        list.sort_by_key(|n| n.title.to_lowercase());
+    }
+
+    let mut out = String::from("#+TITLE: Notes\n\n");
+    let mut stack: Vec<(&Note, usize)> = children
+        .get(&None)
+        .map(|roots| roots.iter().rev().map(|n| (*n, 1)).collect())
+        .unwrap_or_default();
+    let mut seen = std::collections::HashSet::new();
+    while let Some((note, level)) = stack.pop() {
+        if !seen.insert(note.key.as_str()) {
+            continue;
+        }
+        out.push_str(&note_to_org(note, notes, level));
+        out.push('\n');
+        if let Some(kids) = children.get(&Some(note.key.as_str())) {
+            stack.extend(kids.iter().rev().map(|n| (*n, level + 1)));
+        }
+    }
+    out
+}
+
+// ============================================================================
+// Standalone HTML
+// ============================================================================
+
+/// Largest local image inlined into an HTML export.
+const MAX_EMBEDDED_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+fn image_mime(path: &std::path::Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    })
+}
+
+/// Resolve an image `src` to a file under the notes or PDF directory:
+/// `/pdfs/...` maps into `pdfs_dir`, other relative paths are taken
+/// relative to the note's own directory. Remote URLs and paths that
+/// escape those directories yield `None`.
+pub fn local_image_path(
+    src: &str,
+    note: &Note,
+    notes_dir: &std::path::Path,
+    pdfs_dir: &std::path::Path,
+) -> Option<PathBuf> {
+    let src = src.split(['?', '#']).next()?;
+    let src = urlencoding::decode(src).ok()?;
+    if src.contains("://") || src.starts_with("data:") || src.starts_with("//") {
+        return None;
+    }
+    let (base, rel) = match src.strip_prefix("/pdfs/") {
+        Some(rel) => (pdfs_dir.to_path_buf(), rel.to_string()),
+        None if !src.starts_with('/') => (
+            notes_dir.join(note.path.parent().unwrap_or(std::path::Path::new(""))),
+            src.to_string(),
+        ),
+        None => return None,
+    };
+    let rel = std::path::Path::new(&rel);
+    let safe = rel
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)));
+    safe.then(|| base.join(rel))
+}
+
+/// Read a local image as a `data:` URI.
+pub fn image_data_uri(path: &std::path::Path) -> Option<String> {
+    use base64::Engine;
+    let mime = image_mime(path)?;
+    let size = std::fs::metadata(path).ok()?.len();
+    if size > MAX_EMBEDDED_IMAGE_BYTES {
+        tracing::warn!(path = %path.display(), size, "Image too large to embed");
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    Some(format!(
+        "data:{};base64,{}",
+        mime,
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Sanitize exported HTML, additionally allowing `data:` URIs on images.
+pub fn clean_export_html(html: &str) -> String {
+    ammonia::Builder::default()
+        .add_url_schemes(&["data"])
+        .attribute_filter(|element, attribute, value| {
+            let is_data = value.trim_start().to_ascii_lowercase().starts_with("data:");
+            if is_data && !(element == "img" && attribute == "src") {
+                None
+            } else {
+                Some(value.into())
+            }
+        })
+        .clean(html)
+        .to_string()
+}
+
+/// Render a note's body as sanitized HTML. Crosslinks become absolute
+/// links under `link_base` (scheme, host and base path), or plain text
+/// when `link_base` is `None` or the target is hidden. `embed_image` maps
+/// an image `src` to a replacement (normally a `data:` URI).
+pub fn note_body_html(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    link_base: Option<&str>,
+    embed_image: impl Fn(&str) -> Option<String>,
+) -> String {
+    let markdown = crosslinks_to_links(&note.raw_content, notes);
+    let mut dropped_links = 0usize;
+    let events: Vec<Event> = Parser::new_ext(&markdown, markdown_options())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { link_type, dest_url, title, id })
+                if dest_url.starts_with(NOTE_LINK_SCHEME) =>
+            {
+                let key = &dest_url[NOTE_LINK_SCHEME.len()..];
+                let visible = notes.get(key).is_some_and(|n| !n.hidden);
+                match link_base {
+                    Some(base) if visible => Some(Event::Start(Tag::Link {
+                        link_type,
+                        dest_url: format!("{}/note/{}", base, key).into(),
+                        title,
+                        id,
+                    })),
+                    _ => {
+                        dropped_links += 1;
+                        None
+                    }
+                }
+            }
+            Event::End(TagEnd::Link) if dropped_links > 0 => {
+                dropped_links -= 1;
+                None
+            }
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+                let dest_url = embed_image(&dest_url).map(Into::into).unwrap_or(dest_url);
+                Some(Event::Start(Tag::Image { link_type, dest_url, title, id }))
+            }
+            other => Some(other),
+        })
+        .collect();
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, events.into_iter());
+    clean_export_html(&body)
+}
+
+/// Render a note as a self-contained HTML document, its body as in
+/// `note_body_html`.
+pub fn note_to_html_document(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    link_base: Option<&str>,
+    embed_image: impl Fn(&str) -> Option<String>,
+) -> String {
+    let body = note_body_html(note, notes, link_base, embed_image);
+
+    let mut meta = Vec::new();
+    if let Some(date) = note.date {
+        meta.push(date.format("%Y-%m-%d").to_string());
+    }
+    if let NoteType::Paper(ref paper) = note.note_type {
+        let effective = paper.effective_metadata(&note.title);
+        meta.extend(effective.authors);
+        meta.extend(effective.venue);
+        meta.extend(effective.year.map(|y| y.to_string()));
+    }
+    if !note.tags.is_empty() {
+        meta.push(note.tags.join(", "));
+    }
+    let meta_html = if meta.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<div class=\"meta-block\">{}</div>",
+            meta.iter().map(|m| html_escape(m)).collect::<Vec<_>>().join(" · ")
+        )
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <style>{style}</style>
+</head>
+<body>
+    <div class="container">
+        <div class="note-header"><h1>{title}</h1></div>
+        {meta}
+        <div class="note-content">{body}</div>
+    </div>
+</body>
+</html>
+"#,
+        title = html_escape(&note.title),
+        style = STYLE,
+        meta = meta_html,
+        body = body,
+    )
+}
+
+// ============================================================================
+// LaTeX
+// ============================================================================
+
+const LATEX_SECTIONS: &[&str] = &["section", "subsection", "subsubsection", "paragraph", "subparagraph"];
+
+/// Sectioning command for a 0-based depth, clamped to `\subparagraph`.
+fn latex_section(depth: usize) -> &'static str {
+    LATEX_SECTIONS[depth.min(LATEX_SECTIONS.len() - 1)]
+}
+
+/// Escape LaTeX special characters. Math spans (see `math_span`) are kept.
+pub fn escape_latex(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some((start, end)) = math_span(rest) {
+        out.push_str(&escape_latex_plain(&rest[..start]));
+        out.push_str(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    out.push_str(&escape_latex_plain(rest));
+    out
+}
+
+/// Byte range of the first math span in `text`, by pandoc's rule: `$$...$$`,
+/// or `$...$` with no whitespace just inside either `$` and no digit right
+/// after the closing one, so `$5 and $10` stays text.
+fn math_span(text: &str) -> Option<(usize, usize)> {
+    let mut from = 0;
+    while let Some(i) = text[from..].find('$').map(|i| from + i) {
+        let after = &text[i + 1..];
+        if let Some(inner) = after.strip_prefix('$') {
+            if let Some(len) = inner.find("$$") {
+                return Some((i, i + 2 + len + 2));
+            }
+        } else if after.starts_with(|c: char| !c.is_whitespace()) {
+            let closing = after.match_indices('$').map(|(j, _)| j).find(|&j| {
+                !after[..j].ends_with(char::is_whitespace)
+                    && !after[j + 1..].starts_with(|c: char| c.is_ascii_digit())
+            });
+            if let Some(j) = closing {
+                return Some((i, i + j + 2));
+            }
+        }
+        from = i + 1;
+    }
+    None
+}
+
+fn escape_latex_plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '^' => out.push_str("\\textasciicircum{}"),
+            '~' => out.push_str("\\textasciitilde{}"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// URLs go inside `\href`/`\includegraphics` arguments, where only `%`,
+/// `#` and braces need care.
+fn escape_latex_url(url: &str) -> String {
+    url.replace('%', "\\%")
+        .replace('#', "\\#")
+        .replace(['{', '}', '\\'], "")
+}
+
+/// Rewrite `[@bibkey]` references to papers into `[@notekey]` crosslinks so
+/// citations may use either form.
+fn bib_keys_to_crosslinks(content: &str, notes: &HashMap<String, Note>) -> String {
+    let mut by_bib_key = HashMap::new();
+    for note in notes.values() {
+        if let NoteType::Paper(ref paper) = note.note_type {
+            by_bib_key.insert(paper.effective_metadata(&note.title).bib_key, note.key.as_str());
+        }
+    }
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("[@") {
+        out.push_str(&rest[..start + 2]);
+        rest = &rest[start + 2..];
+        if let Some(end) = rest.find(']') {
+            if !notes.contains_key(&rest[..end]) {
+                if let Some(key) = by_bib_key.get(&rest[..end]) {
+                    out.push_str(key);
+                    rest = &rest[end..];
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Convert a markdown body to LaTeX. Headings start at sectioning `depth`.
+/// Crosslinks to papers become `\cite{}` and their bib keys are appended to
+/// `cited`; other crosslinks become plain text.
+pub fn markdown_to_latex(
+    markdown: &str,
+    notes: &HashMap<String, Note>,
+    depth: usize,
+    cited: &mut Vec<String>,
+) -> String {
+    let markdown = crosslinks_to_links(&bib_keys_to_crosslinks(markdown, notes), notes);
+    let mut out = String::new();
+    let mut lists: Vec<&str> = Vec::new();
+    // Closing text for each open link or image; `None` while its inner
+    // text is suppressed (citations, image alt text).
+    let mut links: Vec<Option<&str>> = Vec::new();
+    let mut in_code = false;
+    let mut table_cell = 0usize;
+
+    for event in Parser::new_ext(&markdown, markdown_options()) {
+        let suppressed = links.iter().any(Option::is_none);
+        if suppressed && !matches!(event, Event::End(TagEnd::Link | TagEnd::Image)) {
+            continue;
+        }
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Heading { level, .. } => {
+                    out.push_str(&format!("\n\\{}{{", latex_section(depth + level as usize - 1)));
+                }
+                Tag::Paragraph => out.push('\n'),
+                Tag::BlockQuote => out.push_str("\n\\begin{quote}\n"),
+                Tag::CodeBlock(_) => {
+                    out.push_str("\n\\begin{verbatim}\n");
+                    in_code = true;
+                }
+                Tag::List(start) => {
+                    let env = if start.is_some() { "enumerate" } else { "itemize" };
+                    out.push_str(&format!("\n\\begin{{{}}}\n", env));
+                    lists.push(env);
+                }
+                Tag::Item => out.push_str("\\item "),
+                Tag::Emphasis => out.push_str("\\emph{"),
+                Tag::Strong => out.push_str("\\textbf{"),
+                Tag::Strikethrough => out.push_str("\\sout{"),
+                Tag::Link { dest_url, .. } => match dest_url.strip_prefix(NOTE_LINK_SCHEME) {
+                    Some(key) => match notes.get(key) {
+                        Some(Note { note_type: NoteType::Paper(paper), title, .. }) => {
+                            let bib_key = paper.effective_metadata(title).bib_key;
+                            out.push_str(&format!("\\cite{{{}}}", bib_key));
+                            if !cited.contains(&bib_key) {
+                                cited.push(bib_key);
+                            }
+                            links.push(None);
+                        }
+                        _ => links.push(Some("")),
+                    },
+                    None => {
+                        out.push_str(&format!("\\href{{{}}}{{", escape_latex_url(&dest_url)));
+                        links.push(Some("}"));
+                    }
+                },
+                Tag::Image { dest_url, .. } => {
+                    out.push_str(&format!(
+                        "\\includegraphics[width=\\linewidth]{{{}}}",
+                        escape_latex_url(&dest_url)
+                    ));
+                    links.push(None);
+                }
+                Tag::Table(alignments) => {
+                    let cols: String = alignments
+                        .iter()
+                        .map(|a| match a {
+                            pulldown_cmark::Alignment::Center => 'c',
+                            pulldown_cmark::Alignment::Right => 'r',
+                            _ => 'l',
+                        })
+                        .collect();
+                    out.push_str(&format!("\n\\begin{{tabular}}{{{}}}\n\\hline\n", cols));
+                }
+                Tag::TableHead | Tag::TableRow => table_cell = 0,
+                Tag::TableCell => {
+                    if table_cell > 0 {
+                        out.push_str(" & ");
+                    }
+                    table_cell += 1;
+                }
+                Tag::FootnoteDefinition(label) => {
+                    out.push_str(&format!("\n\\noindent\\textsuperscript{{{}}} ", escape_latex(&label)));
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Heading(_) => out.push_str("}\n"),
+                TagEnd::Paragraph => out.push('\n'),
+                TagEnd::BlockQuote => out.push_str("\\end{quote}\n"),
+                TagEnd::CodeBlock => {
+                    if !out.ends_with('\n') {
+                        out.push('\n');
+                    }
+                    out.push_str("\\end{verbatim}\n");
+                    in_code = false;
+                }
+                TagEnd::List(_) => {
+                    out.push_str(&format!("\\end{{{}}}\n", lists.pop().unwrap_or("itemize")));
+                }
+                TagEnd::Item if !out.ends_with('\n') => out.push('\n'),
+                TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => out.push('}'),
+                TagEnd::Link | TagEnd::Image => {
+                    if let Some(Some(close)) = links.pop() {
+                        out.push_str(close);
+                    }
+                }
+                TagEnd::TableHead => out.push_str(" \\\\\n\\hline\n"),
+                TagEnd::TableRow => out.push_str(" \\\\\n"),
+                TagEnd::Table => out.push_str("\\hline\n\\end{tabular}\n"),
+                _ => {}
+            },
+            Event::Text(text) => {
+                if in_code {
+                    out.push_str(&text);
+                } else {
+                    out.push_str(&escape_latex(&text));
+                }
+            }
+            Event::Code(code) => out.push_str(&format!("\\texttt{{{}}}", escape_latex_plain(&code))),
+            Event::FootnoteReference(label) => {
+                out.push_str(&format!("\\textsuperscript{{{}}}", escape_latex(&label)));
+            }
+            Event::SoftBreak => out.push('\n'),
+            Event::HardBreak => out.push_str("\\\\\n"),
+            Event::Rule => out.push_str("\n\\noindent\\rule{\\linewidth}{0.4pt}\n"),
+            Event::TaskListMarker(done) => {
+                out.push_str(if done { "$\\boxtimes$ " } else { "$\\square$ " });
+            }
+            Event::Html(_) | Event::InlineHtml(_) => {}
+        }
+    }
+    out
+}
+
+/// `note` followed by its descendants in pre-order (siblings by title),
+/// each paired with its depth below `note`.
+fn with_descendants<'a>(
+    note: &'a Note,
+    notes: &'a HashMap<String, Note>,
+    include_hidden: bool,
+) -> Vec<(&'a Note, usize)> {
+    let mut out = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut stack = vec![(note, 0)];
+    while let Some((current, depth)) = stack.pop() {
+        if !seen.insert(current.key.as_str()) {
+            continue;
+        }
+        out.push((current, depth));
+        let mut kids: Vec<&Note> = notes
+            .values()
+            .filter(|n| n.parents.contains(&current.key))
+            .filter(|n| include_hidden || !n.hidden)
+            .collect();
+        kids.sort_by_key(|n| std::cmp::Reverse(n.title.to_lowercase()));
+        stack.extend(kids.into_iter().map(|n| (n, depth + 1)));
+    }
+    out
+}
+
+/// A LaTeX export: the document and the BibTeX for the papers it cites.
+pub struct LatexExport {
+    pub tex: String,
+    pub bib: String,
+}
+
+/// Render `note` (and, with `children`, all of its descendants as
+/// sections) as a LaTeX article. The document's `\bibliography` refers to
+/// `bib_name`, which should be the stem of the accompanying `.bib` file.
+pub fn note_to_latex(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    children: bool,
+    include_hidden: bool,
+    bib_name: &str,
+) -> LatexExport {
+    let parts = if children {
+        with_descendants(note, notes, include_hidden)
+    } else {
+        vec![(note, 0)]
+    };
+
+    let mut cited = Vec::new();
+    let mut body = String::new();
+    for (part, depth) in parts {
+        if depth > 0 {
+            body.push_str(&format!(
+                "\n\\{}{{{}}}\n",
+                latex_section(depth - 1),
+                escape_latex_plain(&part.title)
+            ));
+        }
+        body.push_str(&markdown_to_latex(&part.raw_content, notes, depth, &mut cited));
+    }
+
+    let bib = cited_bibliography(notes, &cited);
+
+    let date = note
+        .date
+        .map(|d| d.format("%B %-d, %Y").to_string())
+        .unwrap_or_default();
+    let bibliography = if cited.is_empty() {
+        String::new()
+    } else {
+        format!("\n\\bibliographystyle{{plain}}\n\\bibliography{{{}}}\n", bib_name)
+    };
+    let tex = format!(
+        "\\documentclass{{article}}\n\
+         \\usepackage[utf8]{{inputenc}}\n\
+         \\usepackage{{amssymb}}\n\
+         \\usepackage{{graphicx}}\n\
+         \\usepackage[normalem]{{ulem}}\n\
+         \\usepackage{{hyperref}}\n\
+         \n\
+         \\title{{{}}}\n\
+         \\date{{{}}}\n\
+         \n\
+         \\begin{{document}}\n\
+         \\maketitle\n\
+         {}{}\
+         \\end{{document}}\n",
+        escape_latex_plain(&note.title),
+        date,
+        body,
+        bibliography
+    );
+    LatexExport { tex, bib }
+}
+
+// ============================================================================
+// Pandoc (DOCX / EPUB)
+// ============================================================================
+
+/// How long a single pandoc conversion may run.
+const PANDOC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+static PANDOC: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+
+/// Look for `pandoc` on the PATH and remember its version line. Called once
+/// at startup; later calls return the first result.
+pub fn detect_pandoc() -> Option<&'static str> {
+    PANDOC
+        .get_or_init(|| {
+            let output = std::process::Command::new("pandoc").arg("--version").output().ok()?;
+            if !output.status.success() {
+                return None;
+            }
+            let version = String::from_utf8_lossy(&output.stdout);
+            Some(version.lines().next().unwrap_or("pandoc").trim().to_string())
+        })
+        .as_deref()
+}
+
+/// Whether DOCX/EPUB export is available (pandoc was found at startup).
+pub fn pandoc_available() -> bool {
+    PANDOC.get().is_some_and(Option::is_some)
+}
+
+/// Output formats produced through pandoc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PandocFormat {
+    Docx,
+    Epub,
+}
+
+impl PandocFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            PandocFormat::Docx => "docx",
+            PandocFormat::Epub => "epub",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            PandocFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            PandocFormat::Epub => "application/epub+zip",
+        }
+    }
+}
+
+/// BibTeX for the papers whose effective bib keys are in `cited`.
+fn cited_bibliography(notes: &HashMap<String, Note>, cited: &[String]) -> String {
+    let papers: Vec<Note> = notes
+        .values()
+        .filter(|n| match n.note_type {
+            NoteType::Paper(ref paper) => cited.contains(&paper.effective_metadata(&n.title).bib_key),
+            _ => false,
+        })
+        .cloned()
+        .collect();
+    generate_bibliography(&papers)
+}
+
+/// Prepare a note body for pandoc. Crosslinks to papers become pandoc
+/// citations (`[@bibkey]`, resolved by `--citeproc`), other crosslinks
+/// become the target's title, and `/pdfs/` image paths point into
+/// `pdfs_dir`. Returns the markdown and the BibTeX for the cited papers.
+pub fn pandoc_markdown(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    pdfs_dir: &std::path::Path,
+) -> (String, String) {
+    let content = bib_keys_to_crosslinks(&note.raw_content, notes);
+    let mut cited = Vec::new();
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content.as_str();
+    while let Some(start) = rest.find("[@") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let target = after.find(']').and_then(|end| Some((notes.get(&after[..end])?, end)));
+        match target {
+            Some((target, end)) => {
+                match target.note_type {
+                    NoteType::Paper(ref paper) => {
+                        let bib_key = paper.effective_metadata(&target.title).bib_key;
+                        out.push_str(&format!("[@{}]", bib_key));
+                        if !cited.contains(&bib_key) {
+                            cited.push(bib_key);
+                        }
+                    }
+                    _ => out.push_str(&target.title),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                out.push_str("\\[@");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+
+    let out = out.replace("](/pdfs/", &format!("]({}/", pdfs_dir.display()));
+    (out, cited_bibliography(notes, &cited))
+}
+
+/// Convert a note with pandoc, returning the output file's bytes.
+pub async fn run_pandoc(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    notes_dir: &std::path::Path,
+    pdfs_dir: &std::path::Path,
+    format: PandocFormat,
+) -> Result<Vec<u8>, String> {
+    let (markdown, bib) = pandoc_markdown(note, notes, pdfs_dir);
+    let work_dir = std::env::temp_dir().join(format!(
+        "notes-pandoc-{}-{:016x}",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    tokio::fs::create_dir_all(&work_dir)
+        .await
+        .map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    let result = async {
+        let input = work_dir.join("note.md");
+        let output = work_dir.join(format!("note.{}", format.extension()));
+        tokio::fs::write(&input, &markdown)
+            .await
+            .map_err(|e| format!("Failed to write pandoc input: {}", e))?;
+
+        let resource_dir = notes_dir.join(note.path.parent().unwrap_or(std::path::Path::new("")));
+        let mut cmd = tokio::process::Command::new("pandoc");
+        cmd.arg("--from=markdown")
+            .arg("--standalone")
+            .arg(format!("--metadata=title:{}", note.title))
+            .arg(format!("--resource-path={}", resource_dir.display()))
+            .arg("--output")
+            .arg(&output)
+            .kill_on_drop(true);
+        if let Some(date) = note.date {
+            cmd.arg(format!("--metadata=date:{}", date.format("%Y-%m-%d")));
+        }
+        if !bib.is_empty() {
+            let bib_path = work_dir.join("references.bib");
+            tokio::fs::write(&bib_path, &bib)
+                .await
+                .map_err(|e| format!("Failed to write bibliography: {}", e))?;
+            cmd.arg("--citeproc").arg("--bibliography").arg(&bib_path);
+        }
+        cmd.arg(&input);
+
+        let run = tokio::time::timeout(PANDOC_TIMEOUT, cmd.output())
+            .await
+            .map_err(|_| "pandoc timed out".to_string())?
+            .map_err(|e| format!("Failed to run pandoc: {}", e))?;
+        if !run.status.success() {
+            return Err(format!("pandoc failed: {}", String::from_utf8_lossy(&run.stderr).trim()));
+        }
+        tokio::fs::read(&output)
+            .await
+            .map_err(|e| format!("Failed to read pandoc output: {}", e))
+    }
+    .await;
+
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        tracing::warn!(error = %e, dir = %work_dir.display(), "Failed to remove pandoc temp dir");
+    }
+    result
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+pub async fn export_note_org(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    let notes = state.notes_map();
+    let Some(note) = notes.get(&key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    let org = format!(
+        "#+TITLE: {}\n\n{}",
+        note.title.replace('\n', " "),
+        note_to_org(note, &notes, 1)
+    );
+    download("text/org; charset=utf-8", &export_filename(note, "org"), org)
+}
+
+#[derive(serde::Deserialize)]
+pub struct HtmlExportQuery {
+    /// `text` renders crosslinks as plain text instead of absolute links.
+    pub links: Option<String>,
+}
+
+/// Scheme, host and base path the request came in on, for absolute links
+/// to notes; None without a `Host` header.
+pub fn link_base(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok())?;
+    let (_, scheme) = client_info(headers, None, state.config.tls.enabled(), state.config.trust_proxy);
+    let host = if state.config.trust_proxy {
+        headers
+            .get("x-forwarded-host")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or(host)
+    } else {
+        host
+    };
+    Some(format!("{}://{}{}", scheme, host, state.config.base_path))
+}
+
+pub async fn export_note_html(
+    Path(key): Path<String>,
+    Query(query): Query<HtmlExportQuery>,
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Response {
+    let notes = state.notes_map();
+    let Some(note) = notes.get(&key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+
+    let link_base = match query.links.as_deref() {
+        Some("text") => None,
+        _ => link_base(&state, &headers),
+    };
+
+    let html = note_to_html_document(note, &notes, link_base.as_deref(), |src| {
+        local_image_path(src, note, &state.notes_dir, &state.pdfs_dir)
+            .and_then(|path| image_data_uri(&path))
+    });
+    download("text/html; charset=utf-8", &export_filename(note, "html"), html)
+}
+
+#[derive(serde::Deserialize)]
+pub struct LatexExportQuery {
+    /// Include all descendant notes as sections.
+    pub children: Option<bool>,
+}
+
+fn latex_export(
+    key: &str,
+    query: &LatexExportQuery,
+    state: &AppState,
+    jar: &CookieJar,
+) -> Result<(LatexExport, String), (StatusCode, String)> {
+    let notes = state.notes_map();
+    let note = notes
+        .get(key)
+        .ok_or((StatusCode::NOT_FOUND, "Note not found".to_string()))?;
+    let bib_file = export_filename(note, "bib");
+    let export = note_to_latex(
+        note,
+        &notes,
+        query.children.unwrap_or(false),
+        is_logged_in(jar, &state.db),
+        bib_file.trim_end_matches(".bib"),
+    );
+    Ok((export, bib_file))
+}
+
+pub async fn export_note_tex(
+    Path(key): Path<String>,
+    Query(query): Query<LatexExportQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    match latex_export(&key, &query, &state, &jar) {
+        Ok((export, bib_file)) => download(
+            "application/x-tex; charset=utf-8",
+            &format!("{}.tex", bib_file.trim_end_matches(".bib")),
+            export.tex,
+        ),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// The `.bib` accompanying `export_note_tex`, restricted to cited papers.
+pub async fn export_note_bib(
+    Path(key): Path<String>,
+    Query(query): Query<LatexExportQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    match latex_export(&key, &query, &state, &jar) {
+        Ok((export, bib_file)) => download("text/plain; charset=utf-8", &bib_file, export.bib),
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn export_note_pandoc(key: &str, state: &AppState, format: PandocFormat) -> Response {
+    if !pandoc_available() {
+        return (StatusCode::NOT_IMPLEMENTED, "pandoc is not installed on this server").into_response();
+    }
+    let notes = state.notes_map();
+    let Some(note) = notes.get(key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    match run_pandoc(note, &notes, &state.notes_dir, &state.pdfs_dir, format).await {
+        Ok(bytes) => download(
+            format.content_type(),
+            &export_filename(note, format.extension()),
+            bytes,
+        ),
+        Err(e) => {
+            tracing::error!(error = %e, key = %key, "Pandoc export failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+pub async fn export_note_docx(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    export_note_pandoc(&key, &state, PandocFormat::Docx).await
+}
+
+pub async fn export_note_epub(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    export_note_pandoc(&key, &state, PandocFormat::Epub).await
+}
+
+pub async fn export_corpus_org(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let org = corpus_to_org(&state.notes_map(), logged_in);
+    download("text/org; charset=utf-8", "notes.org", org)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PaperMeta, TimeCategory, TimeEntry};
+    use std::path::PathBuf;
+
+    fn note(key: &str, title: &str, body: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", title.to_lowercase().replace(' ', "-"))),
+            title: title.to_string(),
+            raw_content: body.to_string(),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    fn notes_map(notes: Vec<Note>) -> HashMap<String, Note> {
+        notes.into_iter().map(|n| (n.key.clone(), n)).collect()
+    }
+
+    #[test]
+    fn test_crosslinks_to_links() {
+        let notes = notes_map(vec![note("abc123", "Datalog [draft]", "")]);
+        assert_eq!(
+            crosslinks_to_links("see [@abc123] and [@missing]", &notes),
+            "see [Datalog (draft)](note:abc123) and [@missing]"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_org() {
+        let md = "# Intro\n\nSome *emph*, **bold**, `code` and [a link](https://x.org).\n\n\
+                  - one\n- [x] done\n  1. nested\n\n```rust\nfn main() {}\n```\n\n\
+                  > quoted\n\n| a | b |\n|---|---|\n| 1 | 2 |\n";
+        let org = markdown_to_org(md, 1);
+        assert!(org.starts_with("** Intro\n"));
+        assert!(org.contains("Some /emph/, *bold*, ~code~ and [[https://x.org][a link]]."));
+        assert!(org.contains("- one\n- [X] done\n  1. nested\n"));
+        assert!(org.contains("#+BEGIN_SRC rust\nfn main() {}\n#+END_SRC"));
+        assert!(org.contains("#+BEGIN_QUOTE\nquoted\n#+END_QUOTE"));
+        assert!(org.contains("| a | b |\n|-\n| 1 | 2 |\n"));
+    }
+
+    #[test]
+    fn test_note_to_org_properties_and_crosslinks() {
+        let mut paper = note("p1", "Soufflé", "Fast Datalog.");
+        paper.note_type = NoteType::Paper(PaperMeta {
+            bibtex_entries: vec!["@inproceedings{jordan2016souffle,\n  title={Souffl{\\'e}},\n  author={Jordan, Herbert},\n  year={2016}\n}".to_string()],
+            canonical_key: None,
+            sources: vec![],
+            versions: vec![],
+        });
+        let mut n = note("n1", "Reading list", "Read [@p1] next.");
+        n.tags = vec!["phd".to_string(), "to read".to_string()];
+        n.date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1);
+        n.time_entries = vec![TimeEntry {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+            minutes: 30,
+            category: TimeCategory::Reading,
+            description: None,
+        }];
+        let notes = notes_map(vec![paper, n]);
+
+        let org = note_to_org(&notes["n1"], &notes, 1);
+        assert!(org.starts_with("* Reading list :phd:to_read:\n:PROPERTIES:\n:ID: n1\n"));
+        assert!(org.contains(":DATE: [2024-03-01 Fri]\n"));
+        assert!(org.contains("Read [[id:p1][Soufflé]] next."));
+        assert!(org.contains("** Time Log\n| Date | Minutes | Category | Description |\n|-\n| 2024-03-01 | 30 | reading |  |\n"));
+
+        let org = note_to_org(&notes["p1"], &notes, 2);
+        assert!(org.contains(":TYPE: paper\n:BIB_KEY: jordan2016souffle\n"));
+        assert!(org.contains("#+BEGIN_SRC bibtex\n@inproceedings{jordan2016souffle"));
+    }
+
+    #[test]
+    fn test_corpus_nests_children() {
+        let parent = note("a", "Project", "");
+        let mut child = note("b", "Sub task", "");
+        child.parents = vec!["a".to_string()];
+        let mut hidden = note("c", "Secret", "");
+        hidden.hidden = true;
+        let notes = notes_map(vec![child, parent, hidden]);
+        assert!(corpus_to_org(&notes, true).contains("* Secret"));
+        let org = corpus_to_org(&notes, false);
+        let project = org.find("* Project").unwrap();
+        let sub = org.find("** Sub task").unwrap();
+        assert!(project < sub);
+        assert!(!org.contains("Secret"));
+    }
+
+    #[test]
+    fn test_html_document_links_and_images() {
+        let mut secret = note("s1", "Secret", "");
+        secret.hidden = true;
+        let n = note(
+            "n1",
+            "Summary",
+            "See [@p1] and [@s1].\n\n![fig](fig.png) <script>alert(1)</script>",
+        );
+        let notes = notes_map(vec![note("p1", "Paper", ""), secret, n]);
+        let embed = |src: &str| (src == "fig.png").then(|| "data:image/png;base64,AAAA".to_string());
+
+        let html = note_to_html_document(&notes["n1"], &notes, Some("https://x.org/notes"), embed);
+        assert!(html.contains("<style>"));
+        assert!(html.contains(r#"<a href="https://x.org/notes/note/p1""#));
+        assert!(html.contains("and Secret."));
+        assert!(html.contains(r#"src="data:image/png;base64,AAAA""#));
+        assert!(!html.contains("<script>alert"));
+
+        let html = note_to_html_document(&notes["n1"], &notes, None, |_| None);
+        assert!(html.contains("See Paper and Secret."));
+        assert!(html.contains(r#"src="fig.png""#));
+    }
+
+    #[test]
+    fn test_local_image_path() {
+        let mut n = note("n1", "x", "");
+        n.path = PathBuf::from("projects/x.md");
+        let (notes_dir, pdfs_dir) = (std::path::Path::new("/c"), std::path::Path::new("/p"));
+        assert_eq!(
+            local_image_path("img/a%20b.png", &n, notes_dir, pdfs_dir),
+            Some(PathBuf::from("/c/projects/img/a b.png"))
+        );
+        assert_eq!(
+            local_image_path("/pdfs/fig.png", &n, notes_dir, pdfs_dir),
+            Some(PathBuf::from("/p/fig.png"))
+        );
+        assert_eq!(local_image_path("../../etc/passwd", &n, notes_dir, pdfs_dir), None);
+        assert_eq!(local_image_path("https://x.org/a.png", &n, notes_dir, pdfs_dir), None);
+        assert_eq!(local_image_path("/etc/a.png", &n, notes_dir, pdfs_dir), None);
+    }
+
+    #[test]
+    fn test_clean_export_html_limits_data_uris() {
+        let html = clean_export_html(r#"<a href="data:text/html,x">a</a><img src="data:image/png;base64,AA">"#);
+        assert!(!html.contains("data:text/html"));
+        assert!(html.contains("data:image/png;base64,AA"));
+    }
+
+    fn paper(key: &str, title: &str, bib_key: &str) -> Note {
+        let mut n = note(key, title, "");
+        n.note_type = NoteType::Paper(PaperMeta {
+            bibtex_entries: vec![format!("@article{{{},\n  title={{{}}}\n}}", bib_key, title)],
+            canonical_key: None,
+            sources: vec![],
+            versions: vec![],
+        });
+        n
+    }
+
+    #[test]
+    fn test_escape_latex_keeps_math() {
+        assert_eq!(escape_latex("50% of $x_1$ & a_b"), "50\\% of $x_1$ \\& a\\_b");
+        assert_eq!(escape_latex("costs $5"), "costs \\$5");
+        assert_eq!(escape_latex("costs $5 and $10"), "costs \\$5 and \\$10");
+        assert_eq!(escape_latex("$ x $ and $x$2"), "\\$ x \\$ and \\$x\\$2");
+        assert_eq!(escape_latex("$$a + b$$ or $a$"), "$$a + b$$ or $a$");
+    }
+
+    #[test]
+    fn test_note_to_latex_cites_papers() {
+        let mut parent = note("a", "Survey", "# Background\n\nBuilds on [@p1] and [@smith2020].\n\nSee [@c].");
+        parent.date = chrono::NaiveDate::from_ymd_opt(2024, 3, 1);
+        let mut child = note("c", "Details", "Uses [@p1], *really*.");
+        child.parents = vec!["a".to_string()];
+        let notes = notes_map(vec![
+            parent,
+            child,
+            paper("p1", "Souffle", "jordan2016"),
+            paper("p2", "Other", "smith2020"),
+            paper("p3", "Uncited", "nobody2000"),
+        ]);
+
+        let export = note_to_latex(&notes["a"], &notes, false, false, "survey");
+        assert!(export.tex.contains("\\title{Survey}\n\\date{March 1, 2024}"));
+        assert!(export.tex.contains("\\section{Background}"));
+        assert!(export.tex.contains("Builds on \\cite{jordan2016} and \\cite{smith2020}."));
+        assert!(export.tex.contains("See Details."));
+        assert!(!export.tex.contains("\\section{Details}"));
+        assert!(export.tex.contains("\\bibliography{survey}"));
+        assert!(export.bib.contains("@article{jordan2016"));
+        assert!(export.bib.contains("@article{smith2020"));
+        assert!(!export.bib.contains("nobody2000"));
+
+        let export = note_to_latex(&notes["a"], &notes, true, false, "survey");
+        assert!(export.tex.contains("\\section{Details}\n\nUses \\cite{jordan2016}, \\emph{really}."));
+        assert_eq!(export.tex.matches("\\cite{jordan2016}").count(), 2);
+
+        let export = note_to_latex(&notes["p3"], &notes, false, false, "uncited");
+        assert!(export.bib.is_empty());
+        assert!(!export.tex.contains("\\bibliography"));
+    }
+
+    #[test]
+    fn test_pandoc_markdown_citations() {
+        let mut n = note("n1", "Draft", "Per [@p1] and [@smith2020], see [@c1]. Not [@nope].\n\n![f](/pdfs/fig.png)");
+        n.path = PathBuf::from("draft.md");
+        let notes = notes_map(vec![
+            n,
+            note("c1", "Background", ""),
+            paper("p1", "Souffle", "jordan2016"),
+            paper("p2", "Other", "smith2020"),
+            paper("p3", "Uncited", "nobody2000"),
+        ]);
+        let (markdown, bib) = pandoc_markdown(&notes["n1"], &notes, std::path::Path::new("/data/pdfs"));
+        assert!(markdown.starts_with("Per [@jordan2016] and [@smith2020], see Background. Not \\[@nope]."));
+        assert!(markdown.contains("![f](/data/pdfs/fig.png)"));
+        assert!(bib.contains("@article{jordan2016") && bib.contains("@article{smith2020"));
+        assert!(!bib.contains("nobody2000"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Atom feed of recent notes.
+//!
+//! `/feed.xml` lists the most recently modified notes that aren't hidden,
+//! newest first, with their rendered bodies, so a feed reader picks up new
+//! paper summaries. Crosslinks to hidden notes are plain text, as in the
+//! standalone HTML export.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+use crate::export::{link_base, note_body_html};
+use crate::models::Note;
+use crate::notes::html_escape;
+use crate::AppState;
+
+/// Entries in the feed.
+const FEED_LENGTH: usize = 30;
+
+/// The `FEED_LENGTH` most recently modified notes that aren't hidden.
+pub fn recent_notes(notes: &HashMap<String, Note>) -> Vec<&Note> {
+    let mut recent: Vec<&Note> = notes.values().filter(|n| !n.hidden).collect();
+    recent.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.key.cmp(&b.key)));
+    recent.truncate(FEED_LENGTH);
+    recent
+}
+
+fn entry_xml(note: &Note, notes: &HashMap<String, Note>, base: &str) -> String {
+    let url = format!("{}/note/{}", base, note.key);
+    let published = note
+        .created
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| format!("\n    <published>{}</published>", d.and_utc().to_rfc3339()))
+        .unwrap_or_default();
+    let categories: String = note
+        .tags
+        .iter()
+        .map(|t| format!("\n    <category term=\"{}\"/>", html_escape(t)))
+        .collect();
+    let body = note_body_html(note, notes, Some(base), |_| None);
+    format!(
+        r#"  <entry>
+    <title>{title}</title>
+    <id>{url}</id>
+    <link href="{url}"/>{published}
+    <updated>{updated}</updated>{categories}
+    <content type="html">{content}</content>
+  </entry>
+"#,
+        title = html_escape(&note.title),
+        url = html_escape(&url),
+        updated = note.modified.to_rfc3339(),
+        content = html_escape(&body),
+    )
+}
+
+/// The Atom document for the notes' feed, with links under `base`.
+pub fn feed_xml(notes: &HashMap<String, Note>, base: &str, now: DateTime<Utc>) -> String {
+    let recent = recent_notes(notes);
+    let updated = recent.first().map(|n| n.modified).unwrap_or(now);
+    let entries: String = recent.iter().map(|n| entry_xml(n, notes, base)).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Notes</title>
+  <id>{base}/</id>
+  <link rel="self" href="{base}/feed.xml"/>
+  <link href="{base}/"/>
+  <updated>{updated}</updated>
+{entries}</feed>
+"#,
+        base = html_escape(base),
+        updated = updated.to_rfc3339(),
+    )
+}
+
+pub async fn feed(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let base = link_base(&state, &headers).unwrap_or_default();
+    let xml = feed_xml(&state.notes_map(), &base, Utc::now());
+    ([(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")], xml).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn note(key: &str, title: &str, body: &str, modified: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            raw_content: body.to_string(),
+            modified: DateTime::parse_from_rfc3339(modified).unwrap().with_timezone(&Utc),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_feed_xml() {
+        let mut older = note("a1", "Old & gold", "Plain.", "2026-09-01T10:00:00Z");
+        older.created = chrono::NaiveDate::from_ymd_opt(2026, 8, 30);
+        older.tags = vec!["datalog".to_string()];
+        let newer = note("b2", "Soufflé summary", "See [@a1] and [@s1].", "2026-10-01T10:00:00Z");
+        let mut secret = note("s1", "Secret", "", "2026-10-02T10:00:00Z");
+        secret.hidden = true;
+        let notes: HashMap<String, Note> = [older, newer, secret].into_iter().map(|n| (n.key.clone(), n)).collect();
+
+        let keys: Vec<&str> = recent_notes(&notes).iter().map(|n| n.key.as_str()).collect();
+        assert_eq!(keys, ["b2", "a1"]);
+
+        let xml = feed_xml(&notes, "https://notes.example", Utc::now());
+        assert!(xml.contains("<updated>2026-10-01T10:00:00+00:00</updated>\n  <entry>"));
+        assert!(xml.contains("<title>Old &amp; gold</title>"));
+        assert!(xml.contains("<published>2026-08-30T00:00:00+00:00</published>"));
+        assert!(xml.contains(r#"<category term="datalog"/>"#));
+        assert!(xml.contains("&lt;a href=&quot;https://notes.example/note/a1&quot;"));
+        assert!(xml.find("/note/b2").unwrap() < xml.find("/note/a1</id>").unwrap());
+        assert!(!xml.contains("/note/s1"));
+    }
+}
@@ -0,0 +1,193 @@
+//! Note file formats.
+//!
+//! `.md` files are always notes. The top-level `extensions` config list
+//! adds `markdown` (rendered like `.md`), `txt` (shown preformatted) and
+//! `org` (converted to Markdown for display; read-only, since the app
+//! writes Markdown frontmatter). Set once at startup by `configure`.
+//!
+//! Everything that writes a note's file calls `check_writable` first, and
+//! moves and merges leave read-only notes unrewritten.
+
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Extensions `extensions` may list, besides `md`.
+pub const SUPPORTED: &[&str] = &["markdown", "txt", "org"];
+
+static EXTENSIONS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteFormat {
+    Markdown,
+    Text,
+    Org,
+}
+
+impl NoteFormat {
+    pub fn is_read_only(self) -> bool {
+        self == NoteFormat::Org
+    }
+}
+
+/// Check an `extensions` list: every entry must be in `SUPPORTED` (or `md`).
+pub fn validate(extensions: &[String]) -> Result<(), String> {
+    match extensions.iter().find(|e| e.as_str() != "md" && !SUPPORTED.contains(&e.as_str())) {
+        Some(ext) => Err(format!("Unsupported note extension '{}' (expected {})", ext, SUPPORTED.join(", "))),
+        None => Ok(()),
+    }
+}
+
+/// Also load notes with these extensions (set at startup).
+pub fn configure(extensions: &[String]) {
+    *EXTENSIONS.write().unwrap_or_else(|e| e.into_inner()) = extensions.to_vec();
+}
+
+/// The format of a file by its extension, whether or not it is configured.
+pub fn format_of(path: &Path) -> NoteFormat {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("txt") => NoteFormat::Text,
+        Some("org") => NoteFormat::Org,
+        _ => NoteFormat::Markdown,
+    }
+}
+
+/// Whether `path` is loaded as a note: `.md`, or a configured extension.
+pub fn is_note_file(path: &Path) -> bool {
+    let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
+        return false;
+    };
+    ext == "md" || EXTENSIONS.read().unwrap_or_else(|e| e.into_inner()).iter().any(|e| e == ext)
+}
+
+/// Err for notes the app must not rewrite.
+pub fn check_writable(path: &Path) -> Result<(), String> {
+    if format_of(path).is_read_only() {
+        return Err(format!("{} is read-only", path.display()));
+    }
+    Ok(())
+}
+
+/// A note body as Markdown for rendering, search and export.
+pub fn body_markdown(format: NoteFormat, body: &str) -> String {
+    match format {
+        NoteFormat::Markdown => body.to_string(),
+        NoteFormat::Text => {
+            let fence = "`".repeat(body.lines().map(backtick_run).max().unwrap_or(0).max(2) + 1);
+            format!("{}text\n{}\n{}\n", fence, body.trim_end(), fence)
+        }
+        NoteFormat::Org => org_to_markdown(body),
+    }
+}
+
+fn backtick_run(line: &str) -> usize {
+    line.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+/// `#+TITLE:` of an Org file.
+pub fn org_title(org: &str) -> Option<String> {
+    org.lines().find_map(|l| {
+        let (key, value) = l.trim().split_once(':')?;
+        key.eq_ignore_ascii_case("#+title").then(|| value.trim().to_string()).filter(|t| !t.is_empty())
+    })
+}
+
+/// Org to Markdown, line for line: headings, links, source/example/quote
+/// blocks; `#+` keywords and property drawers become blank lines.
+pub fn org_to_markdown(org: &str) -> String {
+    let mut out = Vec::new();
+    let mut in_drawer = false;
+    let mut in_block: Option<String> = None;
+    for line in org.lines() {
+        let trimmed = line.trim();
+        let upper = trimmed.to_ascii_uppercase();
+        if let Some(ref block) = in_block {
+            if upper.starts_with("#+END_") {
+                out.push(if block == "QUOTE" { String::new() } else { "```".to_string() });
+                in_block = None;
+            } else if block == "QUOTE" {
+                out.push(format!("> {}", convert_links(line)));
+            } else {
+                out.push(line.to_string());
+            }
+            continue;
+        }
+        if in_drawer {
+            in_drawer = upper != ":END:";
+            out.push(String::new());
+            continue;
+        }
+        if upper == ":PROPERTIES:" || upper == ":LOGBOOK:" {
+            in_drawer = true;
+            out.push(String::new());
+        } else if let Some(rest) = upper.strip_prefix("#+BEGIN_") {
+            let kind = rest.split_whitespace().next().unwrap_or("").to_string();
+            out.push(match kind.as_str() {
+                "QUOTE" => String::new(),
+                "SRC" => format!("```{}", trimmed.split_whitespace().nth(1).unwrap_or("")),
+                _ => "```".to_string(),
+            });
+            in_block = Some(kind);
+        } else if trimmed.starts_with("#+") || trimmed == "#" || trimmed.starts_with("# ") {
+            out.push(String::new());
+        } else if let Some(level) = heading_level(line) {
+            out.push(format!("{} {}", "#".repeat(level.min(6)), convert_links(line[level..].trim())));
+        } else {
+            out.push(convert_links(line));
+        }
+    }
+    let mut markdown = out.join("\n");
+    markdown.push('\n');
+    markdown
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == '*').count();
+    (level > 0 && line[level..].starts_with(' ')).then_some(level)
+}
+
+/// `[[target][desc]]` → `[desc](target)`, `[[target]]` → `<target>`.
+fn convert_links(line: &str) -> String {
+    let mut out = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("[[") {
+        let Some(end) = rest[start..].find("]]") else { break };
+        let inner = &rest[start + 2..start + end];
+        out.push_str(&rest[..start]);
+        match inner.split_once("][") {
+            Some((target, desc)) => out.push_str(&format!("[{}]({})", desc, target)),
+            None => out.push_str(&format!("<{}>", inner)),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        assert!(validate(&["org".to_string(), "md".to_string()]).is_ok());
+        assert!(validate(&["docx".to_string()]).is_err());
+        assert_eq!(format_of(Path::new("a/b.markdown")), NoteFormat::Markdown);
+        assert!(check_writable(Path::new("old.org")).is_err());
+        assert!(check_writable(Path::new("old.txt")).is_ok());
+
+        let org = "#+TITLE: Old notes\n* Datalog\n:PROPERTIES:\n:ID: x\n:END:\nSee [[https://souffle-lang.github.io][Soufflé]].\n** Code\n#+BEGIN_SRC rust\nfn main() {}\n#+END_SRC\n";
+        assert_eq!(org_title(org).as_deref(), Some("Old notes"));
+        let markdown = org_to_markdown(org);
+        assert_eq!(markdown.lines().count(), org.lines().count());
+        assert_eq!(
+            markdown,
+            "\n# Datalog\n\n\n\nSee [Soufflé](https://souffle-lang.github.io).\n## Code\n```rust\nfn main() {}\n```\n"
+        );
+
+        assert_eq!(body_markdown(NoteFormat::Text, "a ``` b\n"), "````text\na ``` b\n````\n");
+
+        let note = crate::notes::note_from_content("old.org".into(), org.to_string(), chrono::Utc::now());
+        assert_eq!(note.title, "Old notes");
+        assert!(note.raw_content.contains("# Datalog"));
+    }
+}
@@ -5,7 +5,7 @@
 
 use crate::auth::is_logged_in;
 use crate::graph_index;
-use crate::models::{GraphEdge, GraphNode, GraphQuery, GraphStats, KnowledgeGraph};
+use crate::models::{GraphEdge, GraphNode, GraphQuery, GraphStats, KnowledgeGraph, TimeCategory};
 use crate::notes::html_escape;
 use crate::templates::{base_html, render_graph_js, graph_css, GraphRendererConfig, GraphDataSource};
 use axum::{
@@ -24,7 +24,7 @@ use crate::AppState;
 // Graph Building
 // ============================================================================
 
-pub fn build_knowledge_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGraph {
+pub fn build_knowledge_graph(query: &GraphQuery, db: &crate::store::Db) -> KnowledgeGraph {
     let indexed_nodes = graph_index::load_all_nodes(db).unwrap_or_default();
     let indexed_edges = graph_index::load_all_edges(db).unwrap_or_default();
 
@@ -41,7 +41,7 @@ pub fn build_knowledge_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGrap
     // Calculate degrees
     let mut in_degree: HashMap<String, usize> = HashMap::new();
     let mut out_degree: HashMap<String, usize> = HashMap::new();
-    for ((src, tgt), _) in &edge_counts {
+    for (src, tgt) in edge_counts.keys() {
         *out_degree.entry(src.clone()).or_insert(0) += 1;
         *in_degree.entry(tgt.clone()).or_insert(0) += 1;
     }
@@ -102,7 +102,8 @@ pub fn build_knowledge_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGrap
         }
 
         if let Some(ref cat_filter) = query.category_filter {
-            if node.primary_category.as_deref() != Some(cat_filter) {
+            let cat_filter = TimeCategory::from_name(cat_filter).to_string();
+            if node.primary_category.as_deref() != Some(cat_filter.as_str()) {
                 continue;
             }
         }
@@ -126,7 +127,7 @@ pub fn build_knowledge_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGrap
             primary_category: node.primary_category.clone(),
             in_degree: indeg,
             out_degree: outdeg,
-            parent: node.parent_key.clone(),
+            parents: node.parents.clone(),
             authors: node.authors.clone(),
             year: node.year,
             venue: node.venue.clone(),
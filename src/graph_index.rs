@@ -8,9 +8,8 @@
 //! with instant sled reads, while keeping the index in sync via incremental updates.
 
 use crate::models::{CitationScanResult, Note, NoteType};
-use crate::notes::extract_references;
+use crate::notes::{content_hash, extract_references};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
 const EDGES_TREE: &str = "kg:edges";
@@ -31,7 +30,7 @@ pub struct IndexedNode {
     pub date: Option<String>,
     pub time_total: u32,
     pub primary_category: Option<String>,
-    pub parent_key: Option<String>,
+    pub parents: Vec<String>,
     pub hidden: bool,
     pub modified: String,
     pub content_hash: String,
@@ -51,6 +50,9 @@ pub struct IndexedEdge {
     pub weight: u32,
 }
 
+/// (source, target, edge_type, weight) as extracted from a single note.
+type EdgeTuple = (String, String, String, u32);
+
 pub struct ReconcileStats {
     pub reindexed: usize,
     pub removed: usize,
@@ -87,12 +89,6 @@ fn decode_edge_key(key: &[u8]) -> Option<(String, String, String)> {
 // Helpers
 // ============================================================================
 
-fn content_hash(content: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    format!("{:x}", hasher.finalize())
-}
-
 /// Public wrapper for short label computation.
 pub fn compute_short_label_pub(note: &Note) -> String {
     compute_short_label(note)
@@ -112,8 +108,7 @@ fn compute_short_label(note: &Note) -> String {
                 .trim();
             let last_name = first_author
                 .split_whitespace()
-                .filter(|w| w.chars().next().map(|c| c.is_uppercase()).unwrap_or(false))
-                .last()
+                .rfind(|w| w.chars().next().map(|c| c.is_uppercase()).unwrap_or(false))
                 .unwrap_or(first_author);
             if authors.contains(" and ") {
                 return format!("{} et al.", last_name);
@@ -131,7 +126,7 @@ fn compute_short_label(note: &Note) -> String {
 }
 
 /// Extract all edges for a single note: crosslinks from [@key] references and parent edge.
-fn extract_edges_for_note(note: &Note, all_keys: &std::collections::HashSet<String>) -> Vec<(String, String, String, u32)> {
+fn extract_edges_for_note(note: &Note, all_keys: &std::collections::HashSet<String>) -> Vec<EdgeTuple> {
     let mut edges: HashMap<(String, String, String), u32> = HashMap::new();
 
     // Crosslinks from [@key] references
@@ -143,8 +138,8 @@ fn extract_edges_for_note(note: &Note, all_keys: &std::collections::HashSet<Stri
         }
     }
 
-    // Parent relationship
-    if let Some(ref parent) = note.parent_key {
+    // Parent relationships
+    for parent in &note.parents {
         if all_keys.contains(parent) {
             let key = (note.key.clone(), parent.clone(), "parent".to_string());
             *edges.entry(key).or_insert(0) += 1;
@@ -183,7 +178,7 @@ fn build_indexed_node(note: &Note) -> IndexedNode {
         date: note.date.map(|d| d.to_string()),
         time_total,
         primary_category,
-        parent_key: note.parent_key.clone(),
+        parents: note.parents.clone(),
         hidden: note.hidden,
         modified: note.modified.to_rfc3339(),
         content_hash: content_hash(&note.full_file_content),
@@ -197,10 +192,10 @@ fn build_indexed_node(note: &Note) -> IndexedNode {
 // Edge CRUD
 // ============================================================================
 
-fn delete_edges_by_source(edges_tree: &sled::Tree, source: &str) -> sled::Result<()> {
+fn delete_edges_by_source(edges_tree: &crate::store::Tree, source: &str) -> crate::store::Result<()> {
     let mut prefix = source.as_bytes().to_vec();
     prefix.push(0);
-    let to_remove: Vec<sled::IVec> = edges_tree
+    let to_remove: Vec<Vec<u8>> = edges_tree
         .scan_prefix(&prefix)
         .filter_map(|r| r.ok())
         .map(|(k, _)| k)
@@ -211,10 +206,10 @@ fn delete_edges_by_source(edges_tree: &sled::Tree, source: &str) -> sled::Result
     Ok(())
 }
 
-fn delete_edges_by_target(edges_tree: &sled::Tree, target: &str) -> sled::Result<()> {
+fn delete_edges_by_target(edges_tree: &crate::store::Tree, target: &str) -> crate::store::Result<()> {
     // Full scan — fine for ~2000 edges
     let target_bytes = target.as_bytes();
-    let to_remove: Vec<sled::IVec> = edges_tree
+    let to_remove: Vec<Vec<u8>> = edges_tree
         .iter()
         .filter_map(|r| r.ok())
         .filter(|(k, _)| {
@@ -231,10 +226,10 @@ fn delete_edges_by_target(edges_tree: &sled::Tree, target: &str) -> sled::Result
 }
 
 /// Remove all edges between source and target from the kg:edges tree (all types).
-pub fn remove_indexed_edge(db: &sled::Db, source: &str, target: &str) -> Result<(), String> {
+pub fn remove_indexed_edge(db: &crate::store::Db, source: &str, target: &str) -> Result<(), String> {
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
     let prefix = format!("{}\0{}\0", source, target);
-    let to_remove: Vec<sled::IVec> = edges_tree
+    let to_remove: Vec<Vec<u8>> = edges_tree
         .scan_prefix(prefix.as_bytes())
         .filter_map(|r| r.ok())
         .map(|(k, _)| k)
@@ -245,9 +240,9 @@ pub fn remove_indexed_edge(db: &sled::Db, source: &str, target: &str) -> Result<
     Ok(())
 }
 
-fn insert_edge(edges_tree: &sled::Tree, source: &str, target: &str, edge_type: &str, weight: u32) -> sled::Result<()> {
+fn insert_edge(edges_tree: &crate::store::Tree, source: &str, target: &str, edge_type: &str, weight: u32) -> crate::store::Result<()> {
     let key = encode_edge_key(source, target, edge_type);
-    edges_tree.insert(key, &weight.to_le_bytes())?;
+    edges_tree.insert(key, weight.to_le_bytes())?;
     Ok(())
 }
 
@@ -256,7 +251,7 @@ fn insert_edge(edges_tree: &sled::Tree, source: &str, target: &str, edge_type: &
 // ============================================================================
 
 /// Full reconciliation at startup. Compares sled index with notes on disk.
-pub fn reconcile(db: &sled::Db, notes: &[Note]) -> Result<ReconcileStats, String> {
+pub fn reconcile(db: &crate::store::Db, notes: &[Note]) -> Result<ReconcileStats, String> {
     use rayon::prelude::*;
 
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
@@ -266,7 +261,7 @@ pub fn reconcile(db: &sled::Db, notes: &[Note]) -> Result<ReconcileStats, String
     let notes_map: HashMap<String, &Note> = notes.iter().map(|n| (n.key.clone(), n)).collect();
 
     // Parallel: compute hashes, check staleness, build nodes + extract edges for changed notes
-    let note_updates: Vec<(String, IndexedNode, Vec<(String, String, String, u32)>)> = notes
+    let note_updates: Vec<(String, IndexedNode, Vec<EdgeTuple>)> = notes
         .par_iter()
         .filter_map(|note| {
             let hash = content_hash(&note.full_file_content);
@@ -338,7 +333,7 @@ pub fn reconcile(db: &sled::Db, notes: &[Note]) -> Result<ReconcileStats, String
 }
 
 /// Reindex a single note. Returns true if the note was actually updated.
-pub fn reindex_note(db: &sled::Db, note: &Note, all_keys: &std::collections::HashSet<String>) -> Result<bool, String> {
+pub fn reindex_note(db: &crate::store::Db, note: &Note, all_keys: &std::collections::HashSet<String>) -> Result<bool, String> {
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
     let nodes_tree = db.open_tree(NODES_TREE).map_err(|e| e.to_string())?;
 
@@ -369,7 +364,7 @@ pub fn reindex_note(db: &sled::Db, note: &Note, all_keys: &std::collections::Has
 }
 
 /// Remove a note from the graph index.
-pub fn remove_note(db: &sled::Db, key: &str) -> Result<(), String> {
+pub fn remove_note(db: &crate::store::Db, key: &str) -> Result<(), String> {
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
     let nodes_tree = db.open_tree(NODES_TREE).map_err(|e| e.to_string())?;
 
@@ -380,12 +375,65 @@ pub fn remove_note(db: &sled::Db, key: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Carry state that is keyed by note key but not derived from note content
+/// (manual edges, edge annotations, cached citation scans) over to new keys
+/// after notes are moved. Call `reconcile` afterwards to rebuild nodes and
+/// edges. Returns the number of records rewritten.
+pub fn rename_keys(db: &crate::store::Db, renames: &HashMap<String, String>) -> Result<usize, String> {
+    let rename = |key: &str| renames.get(key).cloned().unwrap_or_else(|| key.to_string());
+    let mut count = 0;
+
+    for tree_name in [MANUAL_EDGES_TREE, EDGE_ANNOTATIONS_TREE] {
+        let tree = db.open_tree(tree_name).map_err(|e| e.to_string())?;
+        let entries: Vec<_> = tree.iter().collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        for (k, v) in entries {
+            let key_str = String::from_utf8_lossy(&k).to_string();
+            if let Some((source, target)) = key_str.split_once('\0') {
+                let new_key = format!("{}\0{}", rename(source), rename(target));
+                if new_key != key_str {
+                    tree.remove(&k).map_err(|e| e.to_string())?;
+                    tree.insert(new_key.as_bytes(), v).map_err(|e| e.to_string())?;
+                    count += 1;
+                }
+            }
+        }
+    }
+
+    let citations_tree = db.open_tree(CITATIONS_TREE).map_err(|e| e.to_string())?;
+    let entries: Vec<_> = citations_tree
+        .iter()
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    for (k, v) in entries {
+        let Ok(mut result) = serde_json::from_slice::<CitationScanResult>(&v) else {
+            continue;
+        };
+        let touched = renames.contains_key(&result.source_key)
+            || result.matches.iter().any(|m| renames.contains_key(&m.target_key));
+        if !touched {
+            continue;
+        }
+        result.source_key = rename(&result.source_key);
+        for m in &mut result.matches {
+            m.target_key = rename(&m.target_key);
+        }
+        let json = serde_json::to_vec(&result).map_err(|e| e.to_string())?;
+        citations_tree.remove(&k).map_err(|e| e.to_string())?;
+        citations_tree
+            .insert(result.source_key.as_bytes(), json)
+            .map_err(|e| e.to_string())?;
+        count += 1;
+    }
+
+    Ok(count)
+}
+
 // ============================================================================
 // Citation Sync
 // ============================================================================
 
 /// Sync citation edges for a single note from the citations cache tree into kg:edges.
-pub fn sync_citations(db: &sled::Db, source_key: &str) -> Result<(), String> {
+pub fn sync_citations(db: &crate::store::Db, source_key: &str) -> Result<(), String> {
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
     let nodes_tree = db.open_tree(NODES_TREE).map_err(|e| e.to_string())?;
     let citations_tree = db.open_tree(CITATIONS_TREE).map_err(|e| e.to_string())?;
@@ -397,7 +445,7 @@ pub fn sync_citations(db: &sled::Db, source_key: &str) -> Result<(), String> {
         p
     };
     let citation_suffix = b"citation";
-    let to_remove: Vec<sled::IVec> = edges_tree
+    let to_remove: Vec<Vec<u8>> = edges_tree
         .scan_prefix(&prefix)
         .filter_map(|r| r.ok())
         .filter(|(k, _)| {
@@ -436,7 +484,7 @@ pub fn sync_citations(db: &sled::Db, source_key: &str) -> Result<(), String> {
 }
 
 /// Sync all citation edges from the citations tree.
-pub fn sync_all_citations(db: &sled::Db) -> Result<usize, String> {
+pub fn sync_all_citations(db: &crate::store::Db) -> Result<usize, String> {
     let citations_tree = db.open_tree(CITATIONS_TREE).map_err(|e| e.to_string())?;
 
     let source_keys: Vec<String> = citations_tree
@@ -463,7 +511,7 @@ pub struct ManualEdgeValue {
 }
 
 /// Add a manual edge between two notes.
-pub fn add_manual_edge(db: &sled::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
+pub fn add_manual_edge(db: &crate::store::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let key = format!("{}\0{}", source, target);
     let value = ManualEdgeValue {
@@ -476,7 +524,7 @@ pub fn add_manual_edge(db: &sled::Db, source: &str, target: &str, annotation: Op
 }
 
 /// Remove a manual edge.
-pub fn remove_manual_edge(db: &sled::Db, source: &str, target: &str) -> Result<(), String> {
+pub fn remove_manual_edge(db: &crate::store::Db, source: &str, target: &str) -> Result<(), String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let key = format!("{}\0{}", source, target);
     tree.remove(key.as_bytes()).map_err(|e| e.to_string())?;
@@ -484,7 +532,7 @@ pub fn remove_manual_edge(db: &sled::Db, source: &str, target: &str) -> Result<(
 }
 
 /// Load all manual edges.
-pub fn load_manual_edges(db: &sled::Db) -> Result<Vec<IndexedEdge>, String> {
+pub fn load_manual_edges(db: &crate::store::Db) -> Result<Vec<IndexedEdge>, String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let mut edges = Vec::new();
 
@@ -505,7 +553,7 @@ pub fn load_manual_edges(db: &sled::Db) -> Result<Vec<IndexedEdge>, String> {
 }
 
 /// Update the annotation on an existing manual edge, preserving the created timestamp.
-pub fn update_manual_edge_annotation(db: &sled::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
+pub fn update_manual_edge_annotation(db: &crate::store::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let key = format!("{}\0{}", source, target);
     match tree.get(key.as_bytes()).map_err(|e| e.to_string())? {
@@ -521,7 +569,7 @@ pub fn update_manual_edge_annotation(db: &sled::Db, source: &str, target: &str,
 }
 
 /// Load annotation for a manual edge, if it exists.
-pub fn get_manual_edge_annotation(db: &sled::Db, source: &str, target: &str) -> Result<Option<String>, String> {
+pub fn get_manual_edge_annotation(db: &crate::store::Db, source: &str, target: &str) -> Result<Option<String>, String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let key = format!("{}\0{}", source, target);
     match tree.get(key.as_bytes()).map_err(|e| e.to_string())? {
@@ -534,7 +582,7 @@ pub fn get_manual_edge_annotation(db: &sled::Db, source: &str, target: &str) ->
 }
 
 /// Load all manual edge annotations as a map.
-pub fn load_manual_edge_annotations(db: &sled::Db) -> Result<HashMap<(String, String), String>, String> {
+pub fn load_manual_edge_annotations(db: &crate::store::Db) -> Result<HashMap<(String, String), String>, String> {
     let tree = db.open_tree(MANUAL_EDGES_TREE).map_err(|e| e.to_string())?;
     let mut annotations = HashMap::new();
 
@@ -558,7 +606,7 @@ pub fn load_manual_edge_annotations(db: &sled::Db) -> Result<HashMap<(String, St
 // ============================================================================
 
 /// Set or clear an annotation on any edge (crosslink, citation, parent, or manual).
-pub fn set_edge_annotation(db: &sled::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
+pub fn set_edge_annotation(db: &crate::store::Db, source: &str, target: &str, annotation: Option<String>) -> Result<(), String> {
     let tree = db.open_tree(EDGE_ANNOTATIONS_TREE).map_err(|e| e.to_string())?;
     let key = format!("{}\0{}", source, target);
     match annotation {
@@ -573,7 +621,7 @@ pub fn set_edge_annotation(db: &sled::Db, source: &str, target: &str, annotation
 }
 
 /// Load all edge annotations (from both manual edges and the general annotations tree).
-pub fn load_all_edge_annotations(db: &sled::Db) -> Result<HashMap<(String, String), String>, String> {
+pub fn load_all_edge_annotations(db: &crate::store::Db) -> Result<HashMap<(String, String), String>, String> {
     let mut annotations = load_manual_edge_annotations(db)?;
 
     let tree = db.open_tree(EDGE_ANNOTATIONS_TREE).map_err(|e| e.to_string())?;
@@ -595,7 +643,7 @@ pub fn load_all_edge_annotations(db: &sled::Db) -> Result<HashMap<(String, Strin
 // ============================================================================
 
 /// Load all edges from the kg:edges tree, including manual edges.
-pub fn load_all_edges(db: &sled::Db) -> Result<Vec<IndexedEdge>, String> {
+pub fn load_all_edges(db: &crate::store::Db) -> Result<Vec<IndexedEdge>, String> {
     let edges_tree = db.open_tree(EDGES_TREE).map_err(|e| e.to_string())?;
     let mut edges = Vec::new();
 
@@ -625,7 +673,7 @@ pub fn load_all_edges(db: &sled::Db) -> Result<Vec<IndexedEdge>, String> {
 }
 
 /// Load all nodes from the kg:nodes tree.
-pub fn load_all_nodes(db: &sled::Db) -> Result<HashMap<String, IndexedNode>, String> {
+pub fn load_all_nodes(db: &crate::store::Db) -> Result<HashMap<String, IndexedNode>, String> {
     let nodes_tree = db.open_tree(NODES_TREE).map_err(|e| e.to_string())?;
     let mut nodes = HashMap::new();
 
@@ -639,3 +687,48 @@ pub fn load_all_nodes(db: &sled::Db) -> Result<HashMap<String, IndexedNode>, Str
 
     Ok(nodes)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CitationMatch;
+
+    #[test]
+    fn test_rename_keys() {
+        let db = crate::store::Db::temporary();
+        add_manual_edge(&db, "aaa111", "bbb222", Some("extends".to_string())).unwrap();
+        set_edge_annotation(&db, "ccc333", "aaa111", Some("cites".to_string())).unwrap();
+        let scan = CitationScanResult {
+            source_key: "aaa111".to_string(),
+            matches: vec![CitationMatch {
+                target_key: "ccc333".to_string(),
+                match_type: "doi".to_string(),
+                confidence: 1.0,
+                raw_text: String::new(),
+            }],
+            unmatched_count: 0,
+            timestamp: String::new(),
+            pdf_hash: String::new(),
+        };
+        db.open_tree(CITATIONS_TREE)
+            .unwrap()
+            .insert("aaa111", serde_json::to_vec(&scan).unwrap())
+            .unwrap();
+
+        let renames = HashMap::from([("aaa111".to_string(), "fff999".to_string())]);
+        assert_eq!(rename_keys(&db, &renames).unwrap(), 3);
+        assert_eq!(
+            get_manual_edge_annotation(&db, "fff999", "bbb222").unwrap().as_deref(),
+            Some("extends")
+        );
+        let annotations = load_all_edge_annotations(&db).unwrap();
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[&("ccc333".to_string(), "fff999".to_string())], "cites");
+        let citations = db.open_tree(CITATIONS_TREE).unwrap();
+        assert!(citations.get("aaa111").unwrap().is_none());
+        let moved: CitationScanResult =
+            serde_json::from_slice(&citations.get("fff999").unwrap().unwrap()).unwrap();
+        assert_eq!(moved.source_key, "fff999");
+        assert_eq!(rename_keys(&db, &renames).unwrap(), 0);
+    }
+}
@@ -6,7 +6,7 @@
 //! is untouched.
 
 use crate::graph_index::{self, IndexedEdge};
-use crate::models::{GraphEdge, GraphNode, GraphQuery, GraphStats, KnowledgeGraph};
+use crate::models::{GraphEdge, GraphNode, GraphQuery, GraphStats, KnowledgeGraph, TimeCategory};
 use ascent::{ascent_run, Dual};
 use chrono::{DateTime, Utc};
 use std::collections::{HashMap, HashSet};
@@ -147,7 +147,7 @@ fn compute_shortest_path(
 /// Build a KnowledgeGraph by querying sled data through Ascent.
 ///
 /// This replaces `build_knowledge_graph()` in graph.rs with the same output type.
-pub fn query_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGraph {
+pub fn query_graph(query: &GraphQuery, db: &crate::store::Db) -> KnowledgeGraph {
     let indexed_nodes = graph_index::load_all_nodes(db).unwrap_or_default();
     let indexed_edges = graph_index::load_all_edges(db).unwrap_or_default();
 
@@ -164,7 +164,7 @@ pub fn query_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGraph {
     // Calculate degrees
     let mut in_degree: HashMap<String, usize> = HashMap::new();
     let mut out_degree: HashMap<String, usize> = HashMap::new();
-    for ((src, tgt), _) in &edge_counts {
+    for (src, tgt) in edge_counts.keys() {
         *out_degree.entry(src.clone()).or_insert(0) += 1;
         *in_degree.entry(tgt.clone()).or_insert(0) += 1;
     }
@@ -227,7 +227,8 @@ pub fn query_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGraph {
         }
 
         if let Some(ref cat_filter) = query.category_filter {
-            if node.primary_category.as_deref() != Some(cat_filter) {
+            let cat_filter = TimeCategory::from_name(cat_filter).to_string();
+            if node.primary_category.as_deref() != Some(cat_filter.as_str()) {
                 continue;
             }
         }
@@ -288,7 +289,7 @@ pub fn query_graph(query: &GraphQuery, db: &sled::Db) -> KnowledgeGraph {
             primary_category: node.primary_category.clone(),
             in_degree: indeg,
             out_degree: outdeg,
-            parent: node.parent_key.clone(),
+            parents: node.parents.clone(),
             authors: node.authors.clone(),
             year: node.year,
             venue: node.venue.clone(),
@@ -0,0 +1,314 @@
+//! Writing habits.
+//!
+//! Every edit made through the app is recorded per day in sled: the words
+//! it added to the note's body and the note's key. Time logged comes from
+//! the notes' dated `time:` entries. `/habits` shows the current and
+//! longest streaks of active days and, with `[habits]` goals configured,
+//! how today measures up and how many days in a row met them.
+//!
+//! Word counts are reset from the notes at startup, so edits made outside
+//! the app aren't credited.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::is_logged_in;
+use crate::config::HabitsConfig;
+use crate::models::{Note, TimeCategory};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Day (`YYYY-MM-DD`) → `DayActivity` as JSON.
+const DAYS_TREE: &str = "habit_days";
+
+/// Note key → body word count at its last recorded edit.
+const WORDS_TREE: &str = "habit_words";
+
+/// Days `/habits` lists.
+const SHOWN_DAYS: u64 = 30;
+
+/// Edits recorded on one day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DayActivity {
+    /// Words added, net per save; deletions don't count against it.
+    pub words: u32,
+    /// Keys of the notes edited.
+    pub notes: Vec<String>,
+}
+
+pub fn word_count(note: &Note) -> u32 {
+    note.raw_content.split_whitespace().count() as u32
+}
+
+fn decode_count(bytes: &[u8]) -> u32 {
+    bytes.try_into().map(u32::from_be_bytes).unwrap_or(0)
+}
+
+/// Store every note's current word count, so words written outside the
+/// app aren't credited to its next edit. Run at startup.
+pub fn seed_word_counts(db: &crate::store::Db, notes: &[Note]) -> Result<(), String> {
+    let tree = db.open_tree(WORDS_TREE).map_err(|e| e.to_string())?;
+    for note in notes {
+        tree.insert(note.key.as_bytes(), word_count(note).to_be_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Record an edit of `note` on `date`.
+pub fn record_edit(db: &crate::store::Db, note: &Note, date: NaiveDate) -> Result<(), String> {
+    let words_tree = db.open_tree(WORDS_TREE).map_err(|e| e.to_string())?;
+    let words = word_count(note);
+    // A note with no count yet is new, so all its words are added
+    let before = words_tree
+        .fetch_and_update(note.key.as_bytes(), |_| Some(words.to_be_bytes().to_vec()))
+        .map_err(|e| e.to_string())?
+        .map_or(0, |old| decode_count(&old));
+
+    let days_tree = db.open_tree(DAYS_TREE).map_err(|e| e.to_string())?;
+    days_tree
+        .update_and_fetch(date.format("%Y-%m-%d").to_string().as_bytes(), |old| {
+            let mut day: DayActivity = old.and_then(|b| serde_json::from_slice(b).ok()).unwrap_or_default();
+            day.words += words.saturating_sub(before);
+            if !day.notes.contains(&note.key) {
+                day.notes.push(note.key.clone());
+            }
+            serde_json::to_vec(&day).ok()
+        })
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Every recorded day.
+pub fn load_days(db: &crate::store::Db) -> BTreeMap<NaiveDate, DayActivity> {
+    let Ok(tree) = db.open_tree(DAYS_TREE) else {
+        return BTreeMap::new();
+    };
+    tree.iter()
+        .filter_map(|item| {
+            let (key, value) = item.ok()?;
+            let date = NaiveDate::parse_from_str(std::str::from_utf8(&key).ok()?, "%Y-%m-%d").ok()?;
+            Some((date, serde_json::from_slice(&value).ok()?))
+        })
+        .collect()
+}
+
+/// One day's activity: recorded edits plus time logged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Day {
+    pub words: u32,
+    pub notes: usize,
+    pub minutes: u32,
+    /// Of `minutes`, those in the `reading` category.
+    pub reading: u32,
+}
+
+impl Day {
+    pub fn active(&self) -> bool {
+        self.words > 0 || self.notes > 0 || self.minutes > 0
+    }
+
+    /// Whether the day met every goal set. False when none are set.
+    pub fn meets(&self, goals: &HabitsConfig) -> bool {
+        (goals.daily_words > 0 || goals.daily_minutes > 0)
+            && self.words >= goals.daily_words
+            && self.minutes >= goals.daily_minutes
+    }
+}
+
+/// Recorded edits and logged time, by day.
+pub fn activity(days: &BTreeMap<NaiveDate, DayActivity>, notes: &[Note]) -> BTreeMap<NaiveDate, Day> {
+    let mut by_day: BTreeMap<NaiveDate, Day> = days
+        .iter()
+        .map(|(date, a)| {
+            let day = Day {
+                words: a.words,
+                notes: a.notes.len(),
+                ..Day::default()
+            };
+            (*date, day)
+        })
+        .collect();
+    let reading = TimeCategory::from_name("reading");
+    for entry in notes.iter().flat_map(|n| &n.time_entries) {
+        let day = by_day.entry(entry.date).or_default();
+        day.minutes += entry.minutes;
+        if entry.category == reading {
+            day.reading += entry.minutes;
+        }
+    }
+    by_day
+}
+
+/// Days in a row up to `today` for which `counts` holds. A streak that ran
+/// through yesterday still stands while today is young.
+pub fn current_streak(today: NaiveDate, counts: impl Fn(NaiveDate) -> bool) -> u32 {
+    let mut day = if counts(today) { today } else { today - Days::new(1) };
+    let mut streak = 0;
+    while counts(day) {
+        streak += 1;
+        day = day - Days::new(1);
+    }
+    streak
+}
+
+/// The most days in a row for which `counts` holds.
+pub fn longest_streak(days: &BTreeMap<NaiveDate, Day>, counts: impl Fn(&Day) -> bool) -> u32 {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut last: Option<NaiveDate> = None;
+    for date in days.iter().filter(|(_, d)| counts(d)).map(|(date, _)| date) {
+        run = match last {
+            Some(prev) if prev + Days::new(1) == *date => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        last = Some(*date);
+    }
+    longest
+}
+
+fn plural_days(n: u32) -> String {
+    format!("{} day{}", n, if n == 1 { "" } else { "s" })
+}
+
+fn hours_minutes(minutes: u32) -> String {
+    if minutes == 0 {
+        return String::new();
+    }
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /habits`: streaks, goals and the last 30 days.
+pub async fn habits_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let days = activity(&load_days(&state.db), &notes);
+    let goals = &state.config.habits;
+    let today = crate::timezone::today();
+    let none = Day::default();
+    let day = |date: NaiveDate| days.get(&date).unwrap_or(&none);
+
+    let mut html = String::from("<h1>Habits</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">Streak: <strong>{}</strong> · longest {}. A day counts when you edit a note or log time.</p>"#,
+        plural_days(current_streak(today, |d| day(d).active())),
+        plural_days(longest_streak(&days, Day::active)),
+    ));
+
+    let has_goals = goals.daily_words > 0 || goals.daily_minutes > 0;
+    if has_goals {
+        let mut progress = Vec::new();
+        if goals.daily_words > 0 {
+            progress.push(format!("{} of {} words", day(today).words, goals.daily_words));
+        }
+        if goals.daily_minutes > 0 {
+            progress.push(format!("{} of {} minutes logged", day(today).minutes, goals.daily_minutes));
+        }
+        html.push_str(&format!(
+            r#"<p>Today: {}. Goals met <strong>{}</strong> in a row (longest {}).</p>"#,
+            progress.join(", "),
+            plural_days(current_streak(today, |d| day(d).meets(goals))),
+            plural_days(longest_streak(&days, |d| d.meets(goals))),
+        ));
+    } else {
+        html.push_str(
+            r#"<p class="time-breadcrumb">Set <code>daily_words</code> or <code>daily_minutes</code> under <code>[habits]</code> in notes.toml for daily goals.</p>"#,
+        );
+    }
+
+    html.push_str(r#"<table class="time-table"><tr><th>Date</th><th>Words</th><th>Notes</th><th>Time</th><th>Reading</th>"#);
+    if has_goals {
+        html.push_str("<th>Goal</th>");
+    }
+    html.push_str("</tr>");
+    for offset in 0..SHOWN_DAYS {
+        let date = today - Days::new(offset);
+        let d = day(date);
+        let style = if d.active() { "" } else { r#" style="opacity: 0.5""# };
+        html.push_str(&format!(
+            r#"<tr{}><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>"#,
+            style,
+            date.format("%a %Y-%m-%d"),
+            d.words,
+            d.notes,
+            hours_minutes(d.minutes),
+            hours_minutes(d.reading),
+        ));
+        if has_goals {
+            html.push_str(&format!("<td>{}</td>", if d.meets(goals) { "✓" } else { "" }));
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+
+    Html(base_html("Habits", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    fn date(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 10, d).unwrap()
+    }
+
+    #[test]
+    fn test_record_edits_and_streaks() {
+        let dir = TempDir::new("habits");
+        fs::write(dir.join("a.md"), "---\ntitle: A\n---\n\none two three\n").unwrap();
+        let db = crate::store::Db::temporary();
+        seed_word_counts(&db, &load_all_notes(&dir)).unwrap();
+
+        fs::write(dir.join("a.md"), "---\ntitle: A\n---\n\none two three four five\n").unwrap();
+        fs::write(
+            dir.join("b.md"),
+            "---\ntitle: B\ntime:\n  - date: 2026-10-15\n    minutes: 40\n    category: reading\n---\n\nnew note\n",
+        )
+        .unwrap();
+        let notes = load_all_notes(&dir);
+        for note in &notes {
+            record_edit(&db, note, date(16)).unwrap();
+        }
+        // Shrinking a note adds nothing
+        fs::write(dir.join("a.md"), "---\ntitle: A\n---\n\none\n").unwrap();
+        let shrunk = load_all_notes(&dir).into_iter().find(|n| n.title == "A").unwrap();
+        record_edit(&db, &shrunk, date(17)).unwrap();
+
+        let recorded = load_days(&db);
+        assert_eq!(recorded[&date(16)].words, 4);
+        assert_eq!(recorded[&date(16)].notes.len(), 2);
+        assert_eq!(recorded[&date(17)].words, 0);
+        assert_eq!(recorded[&date(17)].notes, vec![shrunk.key.clone()]);
+
+        let days = activity(&recorded, &notes);
+        assert_eq!(days[&date(15)], Day { words: 0, notes: 0, minutes: 40, reading: 40 });
+        assert_eq!(current_streak(date(17), |d| days.get(&d).is_some_and(Day::active)), 3);
+        assert_eq!(current_streak(date(18), |d| days.get(&d).is_some_and(Day::active)), 3);
+        assert_eq!(current_streak(date(19), |d| days.get(&d).is_some_and(Day::active)), 0);
+        assert_eq!(longest_streak(&days, Day::active), 3);
+
+        let goals = HabitsConfig { daily_words: 3, daily_minutes: 0 };
+        assert!(days[&date(16)].meets(&goals));
+        assert!(!days[&date(17)].meets(&goals));
+        assert!(!days[&date(16)].meets(&HabitsConfig::default()));
+    }
+}
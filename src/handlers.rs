@@ -2,17 +2,24 @@
 //!
 //! This module contains all the route handlers for the notes application,
 //! including index, search, note viewing/editing, authentication, and more.
+//!
+//! Listings (`/`, `/papers`, `/api/notes/list`) take `?sort=` and `?order=`,
+//! defaulting to the `[sort]` config; links leave out the default.
 
 use crate::auth::{
-    create_csrf_token, create_session, delete_session, is_logged_in,
+    create_csrf_token, create_session, delete_session, is_logged_in, is_published,
     verify_and_consume_csrf_token, verify_password, SESSION_COOKIE, SESSION_TTL_HOURS,
 };
-use crate::models::{AddEdgeRequest, Note, NoteType, TimeCategory};
+use crate::formats::check_writable;
+use crate::config::SortConfig;
+use crate::models::{AddEdgeRequest, BibFilter, Note, NoteSort, NoteType, SortOrder, TimeCategory};
 use crate::notes::{
-    generate_bibliography, generate_key, get_file_at_commit, get_git_history, html_escape,
-    parse_frontmatter, process_crosslinks, render_markdown, search_notes,
+    ancestor_chain, ancestors, bibtex_type_label, body_line_offset, content_hash, filter_bibliography, generate_bibliography, get_file_at_commit,
+    get_git_history, html_escape, new_note_frontmatter, new_note_key,
+    parse_frontmatter, process_crosslinks, render_markdown, render_markdown_with_tasks, search_notes, sort_notes,
 };
 use crate::templates::{base_html, render_editor, render_viewer};
+use crate::{embeddings, note_templates, trash};
 use crate::AppState;
 use axum::{
     extract::{Multipart, Path, Query, State},
@@ -21,13 +28,13 @@ use axum::{
 };
 use axum_extra::extract::CookieJar;
 use chrono::Utc;
+use rand::seq::SliceRandom;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 use std::sync::Arc;
-use reqwest;
 use crate::validate_path_within;
 
 // ============================================================================
@@ -37,6 +44,58 @@ use crate::validate_path_within;
 #[derive(Deserialize)]
 pub struct IndexQuery {
     pub hidden: Option<String>,
+    pub sort: Option<NoteSort>,
+    pub order: Option<SortOrder>,
+}
+
+/// The order a listing asked for, else the `[sort]` default.
+fn listing_sort(config: &SortConfig, sort: Option<NoteSort>, order: Option<SortOrder>) -> (NoteSort, SortOrder) {
+    let by = sort.unwrap_or(config.by);
+    let order = order.or(if sort.is_none() { config.order } else { None });
+    (by, order.unwrap_or(by.default_order()))
+}
+
+/// `sort=`/`order=` for a listing URL; empty for the default order.
+fn sort_param(config: &SortConfig, by: NoteSort, order: SortOrder) -> String {
+    if (by, order) == listing_sort(config, None, None) {
+        String::new()
+    } else if order == by.default_order() {
+        format!("sort={}", by.as_str())
+    } else {
+        format!("sort={}&order={}", by.as_str(), order.as_str())
+    }
+}
+
+fn listing_url(path: &str, params: &[&str]) -> String {
+    let params: Vec<&str> = params.iter().copied().filter(|p| !p.is_empty()).collect();
+    if params.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, params.join("&"))
+    }
+}
+
+/// "Sort: modified · created · title · time" plus a link reversing the
+/// current order; `extra` is kept in every link.
+fn sort_links(path: &str, extra: &str, config: &SortConfig, by: NoteSort, order: SortOrder) -> String {
+    let links: Vec<String> = NoteSort::ALL
+        .iter()
+        .map(|&s| {
+            let label = if s == NoteSort::TimeTotal { "time" } else { s.as_str() };
+            if s == by {
+                format!("<strong>{}</strong>", label)
+            } else {
+                format!(r#"<a href="{}">{}</a>"#, listing_url(path, &[extra, &sort_param(config, s, s.default_order())]), label)
+            }
+        })
+        .collect();
+    let reverse = listing_url(path, &[extra, &sort_param(config, by, order.reversed())]);
+    format!(
+        r#"<div class="hidden-toggle">Sort: {} &middot; <a href="{}" title="Reverse the order">{}</a></div>"#,
+        links.join(" &middot; "),
+        reverse,
+        if order == SortOrder::Asc { "&uarr;" } else { "&darr;" }
+    )
 }
 
 pub async fn index(
@@ -47,29 +106,55 @@ pub async fn index(
     let logged_in = is_logged_in(&jar, &state.db);
     let notes = state.load_notes();
     let show_hidden = query.hidden.as_deref() == Some("true");
+    let (sort, order) = listing_sort(&state.config.sort, query.sort, query.order);
+    let mut sorted: Vec<&Note> = notes.iter().collect();
+    sort_notes(&mut sorted, sort, order);
 
     let hidden_count = notes.iter().filter(|n| n.hidden).count();
+    let sort_param = sort_param(&state.config.sort, sort, order);
+    let sort_param = sort_param.as_str();
+    let index_url = |params: &[&str]| listing_url("/", params);
+    let hidden_param = if show_hidden { "hidden=true" } else { "" };
 
     let mut list_html = String::new();
 
+    list_html.push_str(&sort_links("/", hidden_param, &state.config.sort, sort, order));
+
     // Hidden toggle badge
     if hidden_count > 0 {
         if show_hidden {
             list_html.push_str(&format!(
-                r#"<div class="hidden-toggle"><a href="/">&larr; Hide {count} hidden</a></div>"#,
+                r#"<div class="hidden-toggle"><a href="{url}">&larr; Hide {count} hidden</a></div>"#,
+                url = index_url(&[sort_param]),
                 count = hidden_count
             ));
         } else {
             list_html.push_str(&format!(
-                r#"<div class="hidden-toggle"><a href="/?hidden=true">{count} hidden</a></div>"#,
+                r#"<div class="hidden-toggle"><a href="{url}">{count} hidden</a></div>"#,
+                url = index_url(&["hidden=true", sort_param]),
                 count = hidden_count
             ));
         }
     }
 
+    // Archival policy: point at the notes it flags
+    if logged_in {
+        let stale = crate::archive::stale_notes(&notes, state.config.archive_after_months, Utc::now()).len();
+        if stale > 0 {
+            list_html.push_str(&format!(
+                r#"<div class="hidden-toggle"><a href="/archive">{} stale</a></div>"#,
+                stale
+            ));
+        }
+    }
+
+    if logged_in {
+        list_html.push_str(&crate::history::index_section(&state.db, &state.notes_map()));
+    }
+
     list_html.push_str("<ul class=\"note-list\">");
 
-    for note in &notes {
+    for note in sorted {
         if note.hidden && !show_hidden {
             continue;
         }
@@ -86,6 +171,8 @@ pub async fn index(
 
         let type_badge = if is_paper {
             "<span class=\"type-badge\">paper</span>"
+        } else if crate::archive::is_archived(note) {
+            "<span class=\"type-badge\">archived</span>"
         } else {
             ""
         };
@@ -116,7 +203,14 @@ pub async fn index(
             key = note.key,
             title = html_escape(&note.title),
             hide_btn = hide_btn,
-            modified = note.modified.format("%Y-%m-%d %H:%M"),
+            modified = match (sort, note.created) {
+                (NoteSort::Created, Some(created)) => format!("created {}", created.format("%Y-%m-%d")),
+                (NoteSort::TimeTotal, _) => {
+                    let minutes: u32 = note.time_entries.iter().map(|e| e.minutes).sum();
+                    format!("{}h {:02}m logged", minutes / 60, minutes % 60)
+                }
+                _ => crate::timezone::format(note.modified, "%Y-%m-%d %H:%M"),
+            },
         ));
     }
 
@@ -156,6 +250,8 @@ pub async fn index(
 #[derive(Deserialize)]
 pub struct SearchQuery {
     pub q: Option<String>,
+    /// `semantic` ranks notes by meaning instead of matching words.
+    pub mode: Option<String>,
 }
 
 pub async fn search(
@@ -175,13 +271,26 @@ pub async fn search(
         ));
     }
 
+    let semantic_enabled = logged_in && embeddings::is_enabled(&state.config.embeddings);
+    if semantic_enabled && query.mode.as_deref() == Some("semantic") {
+        return semantic_search(&state, &q).await;
+    }
+
     let notes = state.load_notes();
     let results = search_notes(&notes, &q);
 
     let mut html = format!(
-        "<h1>Search: \"{}\"</h1><p>{} results</p><div class=\"search-results\">",
+        "<h1>Search: \"{}\"</h1><p>{} results{}</p><div class=\"search-results\">",
         html_escape(&q),
-        results.len()
+        results.len(),
+        match (semantic_enabled, logged_in) {
+            (true, _) => format!(
+                r#" · <a href="/search?q={}&mode=semantic">search by meaning</a> · <a href="/ask">ask your notes</a>"#,
+                urlencoding::encode(&q)
+            ),
+            (false, true) => r#" · <a href="/ask">ask your notes</a>"#.to_string(),
+            (false, false) => String::new(),
+        }
     );
 
     for result in results {
@@ -227,6 +336,40 @@ pub async fn search(
     ))
 }
 
+async fn semantic_search(state: &AppState, q: &str) -> Html<String> {
+    let mut html = format!(
+        r#"<h1>Search: "{}"</h1><p class="time-breadcrumb">Notes closest in meaning · <a href="/search?q={}">match words instead</a></p>"#,
+        html_escape(q),
+        urlencoding::encode(q)
+    );
+    match embeddings::semantic_search(state, q).await {
+        Ok(ranked) => {
+            let notes_map = state.notes_map();
+            html.push_str(r#"<div class="search-results">"#);
+            for (key, score) in ranked {
+                let Some(note) = notes_map.get(&key) else { continue };
+                html.push_str(&format!(
+                    r#"<div class="result-group">
+                <a href="/note/{}">{}</a>
+                <span class="key">[@{}]</span>
+                <div class="match">{:.2}</div>
+            </div>"#,
+                    note.key,
+                    html_escape(&note.title),
+                    note.key,
+                    score
+                ));
+            }
+            html.push_str("</div>");
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Semantic search failed");
+            html.push_str(&format!("<p>Semantic search failed: {}</p>", html_escape(&e)));
+        }
+    }
+    Html(base_html(&format!("Search: {}", q), &html, Some(q), true))
+}
+
 // ============================================================================
 // Note View Handler
 // ============================================================================
@@ -247,16 +390,81 @@ pub async fn view_note(
 
     let note = match notes_map.get(&key) {
         Some(n) => n,
-        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+        None => return crate::key_migration::old_key_response(&state.db, &notes_map, &key, logged_in),
     };
 
-    let edit_mode = query.edit.unwrap_or(false) && logged_in;
+    let edit_mode = query.edit.unwrap_or(false) && logged_in && check_writable(&note.path).is_ok();
+
+    if logged_in {
+        if let Err(e) = crate::history::record_view(&state.db, &key, Utc::now()) {
+            tracing::warn!(key = %key, error = %e, "Failed to record note view");
+        }
+    }
+    if !edit_mode && (!logged_in || state.config.stats.count_logged_in) {
+        if let Err(e) = crate::stats::record_view(&state.db, &key) {
+            tracing::warn!(key = %key, error = %e, "Failed to count note view");
+        }
+    }
 
     if edit_mode {
-        return Html(render_editor(note, &notes_map, logged_in)).into_response();
+        return match crate::encryption::open_note(note) {
+            Ok(opened) => crate::pwa::no_store_if_sealed(note, Html(render_editor(&opened, &notes_map, logged_in)).into_response()),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        };
     }
+    let stored = note;
+    let note = &*crate::encryption::for_reader(note, logged_in);
 
-    render_view(note, &notes_map, &state.notes_dir, logged_in).into_response()
+    let related = state.related_index().related(&key, crate::related::RELATED_COUNT);
+    let audio = if logged_in { crate::transcribe::audio_memos(&state.storage, &key).await } else { Vec::new() };
+    let can_transcribe = crate::transcribe::is_enabled(&state.config.transcription);
+    let previews = crate::link_preview::note_previews(&state.db, note, logged_in);
+    let page = render_view(note, &notes_map, &related, &previews, &audio, can_transcribe, &state.notes_dir, logged_in);
+    crate::pwa::no_store_if_sealed(stored, page.into_response())
+}
+
+#[derive(Deserialize)]
+pub struct RandomQuery {
+    /// `paper` or `note`.
+    #[serde(rename = "type")]
+    pub note_type: Option<String>,
+    pub tag: Option<String>,
+}
+
+/// The notes `/random` picks from: those matching the query's type and
+/// tag, leaving out hidden notes unless `include_hidden`.
+fn random_candidates<'a>(notes: &'a HashMap<String, Note>, query: &RandomQuery, include_hidden: bool) -> Vec<&'a Note> {
+    let mut candidates: Vec<&Note> = notes
+        .values()
+        .filter(|n| include_hidden || !n.hidden)
+        .filter(|n| match query.note_type.as_deref() {
+            Some("paper") => matches!(n.note_type, NoteType::Paper(_)),
+            Some("note") => matches!(n.note_type, NoteType::Note),
+            _ => true,
+        })
+        .filter(|n| {
+            query
+                .tag
+                .as_deref()
+                .is_none_or(|tag| n.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.key.cmp(&b.key));
+    candidates
+}
+
+/// `GET /random[?type=paper&tag=plt]`: redirect to a random matching note.
+pub async fn random_note(
+    Query(query): Query<RandomQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    let notes_map = state.notes_map();
+    let candidates = random_candidates(&notes_map, &query, is_logged_in(&jar, &state.db));
+    match candidates.choose(&mut rand::thread_rng()) {
+        Some(note) => Redirect::to(&format!("/note/{}", note.key)).into_response(),
+        None => (StatusCode::NOT_FOUND, "No matching notes").into_response(),
+    }
 }
 
 /// Build the meta HTML block (key, date, paper metadata, bibtex) for a note.
@@ -277,6 +485,12 @@ pub fn build_note_meta_html(note: &Note, notes_map: &HashMap<String, Note>) -> S
         meta_html.push_str(&meta_row("Date", &date.format("%Y-%m-%d").to_string()));
     }
 
+    if let Some(due) = note.due {
+        meta_html.push_str(&meta_row("Due", &due.format("%Y-%m-%d").to_string()));
+    }
+
+    meta_html.push_str(&meta_row("Length", &crate::stats::length_label(note)));
+
     if let NoteType::Paper(ref paper) = note.note_type {
         let effective = paper.effective_metadata(&note.title);
 
@@ -284,14 +498,34 @@ pub fn build_note_meta_html(note: &Note, notes_map: &HashMap<String, Note>) -> S
             "Cite",
             &format!("<code>{}</code>", html_escape(&effective.bib_key)),
         ));
-        if let Some(ref authors) = effective.authors {
-            meta_html.push_str(&meta_row("Authors", &html_escape(authors)));
+        if !effective.entry_type.is_empty() {
+            meta_html.push_str(&meta_row("Type", &html_escape(bibtex_type_label(&effective.entry_type))));
+        }
+        let authors = crate::authors::author_links(note);
+        if !authors.is_empty() {
+            meta_html.push_str(&meta_row("Authors", &authors));
+        }
+        if let Some(editors) = effective.editors.as_ref().filter(|e| effective.authors.as_ref() != Some(*e)) {
+            meta_html.push_str(&meta_row("Editors", &html_escape(editors)));
         }
         if let Some(year) = effective.year {
             meta_html.push_str(&meta_row("Year", &year.to_string()));
         }
         if let Some(ref venue) = effective.venue {
-            meta_html.push_str(&meta_row("Venue", &html_escape(venue)));
+            let mut venue = html_escape(venue);
+            if let Some(ref volume) = effective.volume {
+                venue.push_str(&format!(" {}", html_escape(volume)));
+                if let Some(ref number) = effective.number {
+                    venue.push_str(&format!("({})", html_escape(number)));
+                }
+            }
+            meta_html.push_str(&meta_row("Venue", &venue));
+        }
+        if let Some(ref pages) = effective.pages {
+            meta_html.push_str(&meta_row("Pages", &html_escape(pages)));
+        }
+        if let Some(publisher) = effective.publisher.as_ref().filter(|p| effective.venue.as_ref() != Some(*p)) {
+            meta_html.push_str(&meta_row("Publisher", &html_escape(publisher)));
         }
         if !paper.sources.is_empty() {
             let mut sources_html = String::new();
@@ -308,6 +542,10 @@ pub fn build_note_meta_html(note: &Note, notes_map: &HashMap<String, Note>) -> S
                         "<a href=\"https://doi.org/{}\" target=\"_blank\">DOI</a>",
                         html_escape(&source.identifier)
                     ),
+                    "wayback" => format!(
+                        "<a href=\"{}\" target=\"_blank\">Archived</a>",
+                        html_escape(&source.identifier)
+                    ),
                     _ => format!(
                         "<a href=\"{}\" target=\"_blank\">Link</a>",
                         html_escape(&source.identifier)
@@ -317,16 +555,79 @@ pub fn build_note_meta_html(note: &Note, notes_map: &HashMap<String, Note>) -> S
             }
             meta_html.push_str(&meta_row("Sources", &sources_html));
         }
+        if !paper.versions.is_empty() {
+            let versions: Vec<String> = paper
+                .versions
+                .iter()
+                .map(|version| {
+                    let id = &version.source.identifier;
+                    let (href, text) = match version.source.source_type.as_str() {
+                        "arxiv" => (format!("https://arxiv.org/abs/{}", id), format!("arXiv:{}", id)),
+                        "doi" => (format!("https://doi.org/{}", id), format!("DOI {}", id)),
+                        _ => (id.clone(), id.clone()),
+                    };
+                    let link = format!("<a href=\"{}\" target=\"_blank\">{}</a>", html_escape(&href), html_escape(&text));
+                    match version.label {
+                        Some(ref label) => format!("{}: {}", html_escape(label), link),
+                        None => link,
+                    }
+                })
+                .collect();
+            meta_html.push_str(&meta_row("Versions", &versions.join(" · ")));
+        }
     }
 
-    if let Some(ref parent_key) = note.parent_key {
-        if let Some(parent) = notes_map.get(parent_key) {
+    let parents: Vec<String> = note
+        .parents
+        .iter()
+        .filter_map(|k| notes_map.get(k))
+        .map(|parent| format!("<a href=\"/note/{}\">{}</a>", parent.key, html_escape(&parent.title)))
+        .collect();
+    if !parents.is_empty() {
+        let label = if parents.len() == 1 { "Parent" } else { "Parents" };
+        meta_html.push_str(&meta_row(label, &parents.join(", ")));
+    }
+
+    // Recurring series: link the instances before and after this one
+    if let Some(prev) = note.prev_key.as_ref().and_then(|k| notes_map.get(k)) {
+        meta_html.push_str(&meta_row(
+            "Previous",
+            &format!("<a href=\"/note/{}\">{}</a>", prev.key, html_escape(&prev.title)),
+        ));
+    }
+    if let Some(next) = notes_map.values().find(|n| n.prev_key.as_ref() == Some(&note.key)) {
+        meta_html.push_str(&meta_row(
+            "Next",
+            &format!("<a href=\"/note/{}\">{}</a>", next.key, html_escape(&next.title)),
+        ));
+    }
+
+    // Export routes aren't served by `notes publish`
+    if !is_published() {
+        let mut export_links = format!(
+            "<a href=\"/note/{0}/export.html\">HTML</a> · <a href=\"/note/{0}/export.org\">Org</a> · \
+             <a href=\"/note/{0}/export.tex\">LaTeX</a> (<a href=\"/note/{0}/export.bib\">.bib</a>) · \
+             <a href=\"/note/{0}/print\">Print</a>",
+            note.key
+        );
+        if crate::export::pandoc_available() {
+            export_links.push_str(&format!(
+                " · <a href=\"/note/{0}/export.docx\">DOCX</a> · <a href=\"/note/{0}/export.epub\">EPUB</a>",
+                note.key
+            ));
+        }
+        meta_html.push_str(&meta_row("Export", &export_links));
+
+        let has_papers = crate::notes::descendants(&note.key, notes_map)
+            .iter()
+            .any(|k| notes_map.get(k).is_some_and(|n| matches!(n.note_type, NoteType::Paper(_))));
+        if has_papers {
             meta_html.push_str(&meta_row(
-                "Parent",
+                "Reading list",
                 &format!(
-                    "<a href=\"/note/{}\">{}</a>",
-                    parent_key,
-                    html_escape(&parent.title)
+                    "<a href=\"/note/{0}/reading-list.html\">HTML</a> · <a href=\"/note/{0}/reading-list.md\">Markdown</a> · \
+                     <a href=\"/note/{0}/reading-list.bib\">BibTeX</a>",
+                    note.key
                 ),
             ));
         }
@@ -354,19 +655,35 @@ pub fn build_note_meta_html(note: &Note, notes_map: &HashMap<String, Note>) -> S
     meta_html
 }
 
+#[allow(clippy::too_many_arguments)]
 fn render_view(
     note: &Note,
     notes_map: &HashMap<String, Note>,
+    related: &[(String, f32)],
+    previews: &HashMap<String, crate::link_preview::Preview>,
+    audio: &[String],
+    can_transcribe: bool,
     notes_dir: &PathBuf,
     logged_in: bool,
 ) -> Html<String> {
     let meta_html = build_note_meta_html(note, notes_map);
 
     let content_with_links = process_crosslinks(&note.raw_content, notes_map);
-    let rendered_content = render_markdown(&content_with_links);
+    let rendered_content = if logged_in {
+        let offset = body_line_offset(&note.full_file_content);
+        crate::link_preview::add_cards(&render_markdown_with_tasks(&content_with_links, Some(offset)), previews)
+            + &crate::tasks::toggle_script(&note.key, &content_hash(&note.full_file_content))
+            + &crate::summarize::summarize_script(&note.key)
+            + &crate::transcribe::audio_script(&note.key)
+    } else {
+        crate::link_preview::add_cards(&render_markdown(&content_with_links), previews)
+    };
 
+    // A published copy leaves out time logs and git history, and the
+    // routes (exports, history, /time) those sections link to
+    let published = is_published();
     let mut time_html = String::new();
-    if !note.time_entries.is_empty() {
+    if !note.time_entries.is_empty() && !published {
         time_html.push_str("<h2>Time Log</h2><table class=\"time-table\">");
         time_html
             .push_str("<tr><th>Date</th><th>Minutes</th><th>Category</th><th>Description</th></tr>");
@@ -385,7 +702,7 @@ fn render_view(
 
     let sub_notes: Vec<_> = notes_map
         .values()
-        .filter(|n| n.parent_key.as_ref() == Some(&note.key))
+        .filter(|n| n.parents.contains(&note.key))
         .collect();
 
     let mut sub_notes_html = String::new();
@@ -400,8 +717,10 @@ fn render_view(
         }
         sub_notes_html.push_str("</ul></div>");
     }
+    sub_notes_html.push_str(&crate::transcribe::audio_html(audio, can_transcribe));
+    sub_notes_html.push_str(&crate::related::related_html(related, notes_map));
 
-    let history = get_git_history(&note.path, notes_dir);
+    let history = if published { vec![] } else { get_git_history(&note.path, notes_dir) };
     let mut history_html = String::new();
     if !history.is_empty() {
         history_html.push_str("<h2>Edit History</h2><div class=\"history-list\">");
@@ -417,18 +736,31 @@ fn render_view(
                 html_escape(&commit.message),
                 note.key,
                 &commit.hash[..7],
-                commit.date.format("%Y-%m-%d %H:%M"),
+                crate::timezone::format(commit.date, "%Y-%m-%d %H:%M"),
                 html_escape(&commit.author)
             ));
         }
         history_html.push_str("</div>");
     }
 
-    let mode_toggle = if logged_in {
+    let mode_toggle = if logged_in && check_writable(&note.path).is_err() {
+        format!(
+            r#"<div class="mode-toggle">
+                <button class="active">View</button>
+                <button disabled title="{} files are read-only">Edit</button>
+                <button class="delete-btn" onclick="confirmDelete('{}', '{}')">Delete</button>
+            </div>"#,
+            note.path.extension().map(|e| e.to_string_lossy()).unwrap_or_default(),
+            note.key,
+            html_escape(&note.title).replace('\'', "\\'")
+        )
+    } else if logged_in {
         format!(
             r#"<div class="mode-toggle">
                 <button class="active">View</button>
                 <button onclick="window.location.href='/note/{}?edit=true'">Edit</button>
+                <button onclick="summarizeNote('note', this)" title="Add an AI summary of this note">Summarize</button>
+                <button onclick="attachAudio()" title="Attach an audio memo">Audio</button>
                 <button class="delete-btn" onclick="confirmDelete('{}', '{}')">Delete</button>
             </div>"#,
             note.key,
@@ -503,15 +835,13 @@ pub async fn save_note(
         None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
     };
 
+    if let Err(e) = check_writable(&note.path) {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
     let full_path = state.notes_dir.join(&note.path);
     let note_path = note.path.clone();
-
-    if let Err(e) = fs::write(&full_path, &body.content) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to save: {}", e),
-        )
-            .into_response();
+    if let Err(e) = crate::encryption::write_file(&full_path, &body.content) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
     }
 
     state.invalidate_notes_cache();
@@ -520,9 +850,9 @@ pub async fn save_note(
     // Make git commit if auto_commit is true
     if body.auto_commit {
         let notes_dir = state.notes_dir.clone();
-        tokio::task::spawn_blocking(move || {
+        state.spawn_background(move || {
             // Format: "automatic save from notes: Sat Jan 24, 3:35PM"
-            let now = chrono::Local::now();
+            let now = crate::timezone::now();
             let commit_msg = format!(
                 "automatic save from notes: {}",
                 now.format("%a %b %d, %-I:%M%p")
@@ -575,52 +905,22 @@ pub async fn delete_note(
         None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
     };
 
-    let full_path = state.notes_dir.join(&note.path);
-    let note_path = note.path.clone();
-    let note_title = note.title.clone();
-
-    // Delete the file
-    if let Err(e) = fs::remove_file(&full_path) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to delete: {}", e),
-        )
-            .into_response();
-    }
+    // Move the file to the trash
+    let trashed = match trash::trash_note(&state.notes_dir, note, crate::timezone::now().naive_local()) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
 
     state.invalidate_notes_cache();
     state.remove_graph_note(&key);
 
-    // Git commit the deletion
+    // Git commit the move (recorded as a rename into .trash/)
     let notes_dir = state.notes_dir.clone();
-    tokio::task::spawn_blocking(move || {
-        let now = chrono::Local::now();
-        let commit_msg = format!(
-            "deleted note '{}': {}",
-            note_title,
-            now.format("%a %b %d, %-I:%M%p")
-        );
+    let paths = vec![trashed.path.clone(), trashed.dir()];
+    let summary = format!("deleted note '{}'", trashed.title);
+    state.spawn_background(move || trash::commit_paths(&notes_dir, &paths, &summary));
 
-        // Stage the deletion
-        let _ = Command::new("git")
-            .args(["rm", "--cached", &note_path.to_string_lossy()])
-            .current_dir(&notes_dir)
-            .output();
-
-        // Also stage the actual file removal
-        let _ = Command::new("git")
-            .args(["add", "-A"])
-            .current_dir(&notes_dir)
-            .output();
-
-        // Commit
-        let _ = Command::new("git")
-            .args(["commit", "-m", &commit_msg])
-            .current_dir(&notes_dir)
-            .output();
-    });
-
-    (StatusCode::OK, "Deleted").into_response()
+    (StatusCode::OK, "Moved to trash").into_response()
 }
 
 // ============================================================================
@@ -808,11 +1108,16 @@ pub async fn new_note_page(
         return Redirect::to("/login").into_response();
     }
 
-    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let today = crate::timezone::today().format("%Y-%m-%d").to_string();
+    let template_options: String = note_templates::list_templates(&state.config.templates_dir)
+        .iter()
+        .map(|name| format!(r#"<option value="{0}">{0}</option>"#, html_escape(name)))
+        .collect();
 
     let html = format!(
         r##"
         <h1>New Note</h1>
+        <p class="time-breadcrumb">Or <a href="/import/outline">import a Roam or Logseq graph</a>.</p>
         <form method="POST" action="/new" class="new-note-form">
             <div class="form-group">
                 <label for="title">Title</label>
@@ -840,6 +1145,15 @@ pub async fn new_note_page(
                 <input type="date" id="date" name="date" value="{}">
             </div>
 
+            <div class="form-group">
+                <label for="template">Template</label>
+                <select id="template" name="template">
+                    <option value="">None</option>
+                    {}
+                </select>
+                <small>Markdown files in <code>templates/</code>; <code>{{{{title}}}}</code>, <code>{{{{date}}}}</code> and <code>{{{{bib_key}}}}</code> are filled in</small>
+            </div>
+
             <div class="form-group" id="paper-fields" style="display: none;">
                 <label for="bib_key">Bib Key</label>
                 <input type="text" id="bib_key" name="bib_key" placeholder="smith2024attention">
@@ -906,7 +1220,8 @@ pub async fn new_note_page(
             }});
         </script>
         "##,
-        today
+        today,
+        template_options
     );
 
     Html(base_html("New Note", &html, None, true)).into_response()
@@ -922,6 +1237,7 @@ pub struct NewNoteForm {
     pub authors: Option<String>,
     pub year: Option<String>,
     pub venue: Option<String>,
+    pub template: Option<String>,
 }
 
 pub async fn create_note(
@@ -949,7 +1265,7 @@ pub async fn create_note(
     let file_path = state.notes_dir.join(filename);
 
     // Validate the path stays within notes_dir
-    if let Err(_) = validate_path_within(&state.notes_dir, &file_path) {
+    if validate_path_within(&state.notes_dir, &file_path).is_err() {
         let html = r#"<div class="message error">Invalid filename.</div>"#;
         return Html(base_html("Error", html, None, true)).into_response();
     }
@@ -975,8 +1291,18 @@ pub async fn create_note(
         }
     }
 
+    let template = match note_templates::load_optional(&state.config.templates_dir, form.template.as_deref()) {
+        Ok(t) => t,
+        Err(e) => {
+            let html = format!(r#"<div class="message error">{}</div>"#, html_escape(&e));
+            return Html(base_html("Error", &html, None, true)).into_response();
+        }
+    };
+
     // Build frontmatter
-    let mut frontmatter = format!("---\ntitle: {}\n", form.title);
+    let notes_map = state.notes_map();
+    let key = new_note_key(|k| notes_map.contains_key(k));
+    let mut frontmatter = new_note_frontmatter(&form.title, &key, crate::timezone::now());
 
     if let Some(ref date) = form.date {
         if !date.is_empty() {
@@ -1008,23 +1334,26 @@ pub async fn create_note(
         }
     }
 
-    frontmatter.push_str("---\n\n");
+    let content = match template {
+        Some(ref template) => {
+            let today = crate::timezone::today().format("%Y-%m-%d").to_string();
+            let vars = note_templates::TemplateVars {
+                title: &form.title,
+                date: form.date.as_deref().filter(|d| !d.is_empty()).unwrap_or(&today),
+                bib_key: form.bib_key.as_deref().unwrap_or_default(),
+            };
+            note_templates::instantiate(&frontmatter, template, &vars)
+        }
+        None => frontmatter + "---\n\n",
+    };
 
     // Write the file
-    if let Err(e) = fs::write(&file_path, &frontmatter) {
-        let html = format!(
-            r#"<div class="message error">Failed to create note: {}</div>"#,
-            e
-        );
+    if let Err(e) = crate::encryption::write_file(&file_path, &content) {
+        let html = format!(r#"<div class="message error">{}</div>"#, e);
         return Html(base_html("Error", &html, None, true)).into_response();
     }
 
     state.invalidate_notes_cache();
-
-    // Get the key of the new note
-    let relative_path = PathBuf::from(filename);
-    let key = generate_key(&relative_path);
-
     state.reindex_graph_note(&key);
 
     // Redirect to edit the new note
@@ -1110,12 +1439,8 @@ pub async fn toggle_hidden(
     }
 
     let new_content = new_lines.join("\n");
-    if let Err(e) = fs::write(&full_path, &new_content) {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to write note: {}", e),
-        )
-            .into_response();
+    if let Err(e) = crate::encryption::write_file(&full_path, &new_content) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
     }
 
     state.invalidate_notes_cache();
@@ -1124,6 +1449,250 @@ pub async fn toggle_hidden(
     axum::Json(serde_json::json!({ "hidden": new_hidden })).into_response()
 }
 
+// ============================================================================
+// Time Entry API
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct AddTimeEntryBody {
+    /// `YYYY-MM-DD`; defaults to today (local time).
+    pub date: Option<String>,
+    pub minutes: u32,
+    pub category: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteTimeEntryBody {
+    /// Position of the entry in the note's time list.
+    pub index: usize,
+    /// The entry the client expects at `index`; a mismatch is a conflict.
+    pub date: chrono::NaiveDate,
+    pub minutes: u32,
+    pub category: String,
+}
+
+/// Apply a rewrite to a note's file, then refresh caches and commit.
+pub(crate) fn rewrite_note_file(
+    state: &AppState,
+    note: &Note,
+    commit_msg: String,
+    rewrite: impl FnOnce(&str) -> Result<String, String>,
+) -> Result<Vec<crate::models::TimeEntry>, (StatusCode, String)> {
+    rewrite_note_file_with(state, note, commit_msg, |c| {
+        rewrite(c).map_err(|e| (StatusCode::BAD_REQUEST, e))
+    })
+}
+
+/// [`rewrite_note_file`] for rewrites that pick their own error status.
+pub(crate) fn rewrite_note_file_with(
+    state: &AppState,
+    note: &Note,
+    commit_msg: String,
+    rewrite: impl FnOnce(&str) -> Result<String, (StatusCode, String)>,
+) -> Result<Vec<crate::models::TimeEntry>, (StatusCode, String)> {
+    check_writable(&note.path).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+    let full_path = state.notes_dir.join(&note.path);
+    let content = fs::read_to_string(&full_path).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read note: {}", e),
+        )
+    })?;
+    // Rewrites see and produce the decrypted file; it is sealed again on write
+    let content = crate::encryption::open_file(&content)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    let new_content = rewrite(&content)?;
+
+    crate::encryption::write_file(&full_path, &new_content)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    state.invalidate_notes_cache();
+    state.reindex_graph_note(&note.key);
+
+    let notes_dir = state.notes_dir.clone();
+    let note_path = note.path.clone();
+    state.spawn_background(move || {
+        let _ = Command::new("git")
+            .args(["add", &note_path.to_string_lossy()])
+            .current_dir(&notes_dir)
+            .output();
+        let _ = Command::new("git")
+            .args(["commit", "-m", &commit_msg])
+            .current_dir(&notes_dir)
+            .output();
+    });
+
+    Ok(parse_frontmatter(&new_content).0.time)
+}
+
+pub async fn add_time_entry(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<AddTimeEntryBody>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let date = match body.date.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        Some(d) => match chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, "Invalid date (expected YYYY-MM-DD)")
+                    .into_response()
+            }
+        },
+        None => crate::timezone::today(),
+    };
+    if body.minutes == 0 {
+        return (StatusCode::BAD_REQUEST, "Minutes must be positive").into_response();
+    }
+    if body.category.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, "Category is required").into_response();
+    }
+
+    let entry = crate::models::TimeEntry {
+        date,
+        minutes: body.minutes,
+        category: TimeCategory::from_name(&body.category),
+        description: body.description.filter(|d| !d.trim().is_empty()),
+    };
+
+    let notes_map = state.notes_map();
+    let note = match notes_map.get(&key) {
+        Some(n) => n,
+        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+    };
+
+    let now = crate::timezone::now();
+    let commit_msg = format!(
+        "logged {} min on '{}': {}",
+        entry.minutes,
+        note.title,
+        now.format("%a %b %d, %-I:%M%p")
+    );
+
+    match rewrite_note_file(&state, note, commit_msg, |c| {
+        crate::notes::append_time_entry(c, &entry)
+    }) {
+        Ok(entries) => axum::Json(serde_json::json!({ "entries": entries })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+pub async fn delete_time_entry(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<DeleteTimeEntryBody>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let note = match notes_map.get(&key) {
+        Some(n) => n,
+        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+    };
+
+    let now = crate::timezone::now();
+    let commit_msg = format!(
+        "removed time entry from '{}': {}",
+        note.title,
+        now.format("%a %b %d, %-I:%M%p")
+    );
+
+    let category = TimeCategory::from_name(&body.category);
+    match rewrite_note_file_with(&state, note, commit_msg, |c| {
+        let entries = parse_frontmatter(c).0.time;
+        let matches = entries.get(body.index).is_some_and(|e| {
+            e.date == body.date && e.minutes == body.minutes && e.category == category
+        });
+        if !matches {
+            return Err((
+                StatusCode::CONFLICT,
+                "Time entry changed since the page was loaded".to_string(),
+            ));
+        }
+        crate::notes::remove_time_entry(c, body.index).map_err(|e| (StatusCode::BAD_REQUEST, e))
+    }) {
+        Ok(entries) => axum::Json(serde_json::json!({ "entries": entries })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// ============================================================================
+// Health Handlers
+// ============================================================================
+
+/// Liveness probe: the process is up and serving requests. With
+/// `[backup] schedule`, also how the scheduled backups have gone (archive
+/// names and errors only when logged in), and on a mirror how the last
+/// pull went (the upstream and error likewise).
+pub async fn healthz(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let mut body = serde_json::json!({ "status": "ok" });
+    if state.config.backup.schedule.is_some() {
+        body["backup"] = crate::backup::load_status(&state.db).health(logged_in);
+    }
+    if let Some(mirror) = crate::mirror::status() {
+        body["mirror"] = mirror.health(logged_in);
+    }
+    axum::Json(body).into_response()
+}
+
+/// One readiness check; the error (which can name paths) only when logged in.
+fn readiness_check(result: Result<(), String>, logged_in: bool) -> serde_json::Value {
+    match result {
+        Ok(()) => serde_json::json!({ "ok": true }),
+        Err(e) if logged_in => serde_json::json!({ "ok": false, "error": e }),
+        Err(_) => serde_json::json!({ "ok": false }),
+    }
+}
+
+/// Readiness probe: the database answers, the content directory is readable, and
+/// git is on the PATH. Returns 503 if any check fails.
+pub async fn readyz(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let db_check = state
+        .db
+        .get(b"__readyz")
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+
+    let content_check = fs::read_dir(&state.notes_dir)
+        .map(|_| ())
+        .map_err(|e| format!("{}: {}", state.notes_dir.display(), e));
+
+    let notes_dir = state.notes_dir.clone();
+    let git_check = tokio::task::spawn_blocking(move || {
+        match Command::new("git").arg("--version").current_dir(&notes_dir).output() {
+            Ok(out) if out.status.success() => Ok(()),
+            Ok(out) => Err(String::from_utf8_lossy(&out.stderr).trim().to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    })
+    .await
+    .unwrap_or_else(|e| Err(e.to_string()));
+
+    let ready = db_check.is_ok() && content_check.is_ok() && git_check.is_ok();
+    let logged_in = is_logged_in(&jar, &state.db);
+    let body = serde_json::json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "checks": {
+            "db": readiness_check(db_check, logged_in),
+            "content_dir": readiness_check(content_check, logged_in),
+            "git": readiness_check(git_check, logged_in),
+        }
+    });
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, axum::Json(body)).into_response()
+}
+
 // ============================================================================
 // Papers Handler
 // ============================================================================
@@ -1136,11 +1705,14 @@ pub async fn papers(
     let logged_in = is_logged_in(&jar, &state.db);
     let notes = state.load_notes();
     let show_hidden = query.hidden.as_deref() == Some("true");
+    let (sort, order) = listing_sort(&state.config.sort, query.sort, query.order);
 
-    let papers: Vec<_> = notes
+    let mut papers: Vec<_> = notes
         .iter()
         .filter(|n| matches!(n.note_type, NoteType::Paper(_)))
         .collect();
+    sort_notes(&mut papers, sort, order);
+    let sort_param = sort_param(&state.config.sort, sort, order);
 
     let hidden_count = papers.iter().filter(|n| n.hidden).count();
 
@@ -1150,6 +1722,8 @@ pub async fn papers(
         html.push_str(r#"<div style="margin-bottom:1rem;display:flex;align-items:center;gap:0.75rem;flex-wrap:wrap;">
             <button class="btn" id="scan-all-btn" onclick="scanAllPdfs()">Scan All PDFs for Citations</button>
             <a href="/papers/find-pdfs" class="btn" style="text-decoration:none;">Find Missing PDFs</a>
+            <a href="/queue" class="btn" style="text-decoration:none;">Reading Queue</a>
+            <a href="/papers/metadata" class="btn" style="text-decoration:none;">Fix Metadata</a>
             <span id="scan-all-status" style="margin-left:0.75rem;font-size:0.85rem;color:var(--muted);"></span>
         </div>
         <script>
@@ -1180,15 +1754,25 @@ pub async fn papers(
         </script>"#);
     }
 
+    html.push_str(r#"<p class="time-breadcrumb"><a href="/authors">Browse by author</a></p>"#);
+    html.push_str(&sort_links(
+        "/papers",
+        if show_hidden { "hidden=true" } else { "" },
+        &state.config.sort,
+        sort,
+        order,
+    ));
     if hidden_count > 0 {
         if show_hidden {
             html.push_str(&format!(
-                r#"<div class="hidden-toggle"><a href="/papers">&larr; Hide {count} hidden</a></div>"#,
+                r#"<div class="hidden-toggle"><a href="{url}">&larr; Hide {count} hidden</a></div>"#,
+                url = listing_url("/papers", &[&sort_param]),
                 count = hidden_count
             ));
         } else {
             html.push_str(&format!(
-                r#"<div class="hidden-toggle"><a href="/papers?hidden=true">{count} hidden</a></div>"#,
+                r#"<div class="hidden-toggle"><a href="{url}">{count} hidden</a></div>"#,
+                url = listing_url("/papers", &["hidden=true", &sort_param]),
                 count = hidden_count
             ));
         }
@@ -1247,15 +1831,65 @@ pub async fn papers(
 // Time Tracking Handler
 // ============================================================================
 
-pub async fn time_tracking(State(state): State<Arc<AppState>>, jar: CookieJar) -> Html<String> {
+#[derive(Deserialize)]
+pub struct TimeQuery {
+    pub project: Option<String>,
+}
+
+/// Which keys a note's time rolls up to. Without a scope these are the
+/// top-level projects above it, so a note filed under two projects counts
+/// toward both; within a scope, the scope's direct children on the paths
+/// to the note (or the scope itself). Empty means the note lies outside
+/// the scope.
+fn rollup_keys(key: &str, scope: Option<&str>, notes_map: &HashMap<String, Note>) -> Vec<String> {
+    let lineage = std::iter::once(key.to_string()).chain(ancestors(key, notes_map));
+    match scope {
+        None => {
+            let is_root = |k: &String| notes_map.get(k).is_some_and(|n| !n.parents.iter().any(|p| notes_map.contains_key(p)));
+            let roots: Vec<String> = lineage.filter(is_root).collect();
+            if roots.is_empty() {
+                // Only cycles above the note
+                ancestor_chain(key, notes_map).last().cloned().into_iter().collect()
+            } else {
+                roots
+            }
+        }
+        Some(scope) if scope == key => vec![key.to_string()],
+        Some(scope) => lineage
+            .filter(|k| notes_map.get(k).is_some_and(|n| n.parents.iter().any(|p| p == scope)))
+            .collect(),
+    }
+}
+
+pub async fn time_tracking(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TimeQuery>,
+    jar: CookieJar,
+) -> Html<String> {
     let logged_in = is_logged_in(&jar, &state.db);
     let notes = state.load_notes();
+    let notes_map = state.notes_map();
+    let scope = query
+        .project
+        .as_deref()
+        .filter(|k| notes_map.contains_key(*k));
 
     let mut totals: HashMap<TimeCategory, u32> = HashMap::new();
+    let mut project_totals: HashMap<String, u32> = HashMap::new();
     let mut entries_by_date: HashMap<chrono::NaiveDate, Vec<(&Note, &crate::models::TimeEntry)>> =
         HashMap::new();
 
     for note in &notes {
+        if note.time_entries.is_empty() {
+            continue;
+        }
+        let projects = rollup_keys(&note.key, scope, &notes_map);
+        if projects.is_empty() {
+            continue;
+        }
+        for project in projects {
+            *project_totals.entry(project).or_insert(0) += note.time_entries.iter().map(|e| e.minutes).sum::<u32>();
+        }
         for entry in &note.time_entries {
             *totals.entry(entry.category.clone()).or_insert(0) += entry.minutes;
             entries_by_date
@@ -1271,24 +1905,39 @@ pub async fn time_tracking(State(state): State<Arc<AppState>>, jar: CookieJar) -
     let mut legend_html = String::from("<div class=\"time-legend\">");
 
     if total_minutes > 0 {
-        let categories = [
-            (TimeCategory::Programming, "programming"),
-            (TimeCategory::Teaching, "teaching"),
-            (TimeCategory::Reading, "reading"),
-            (TimeCategory::Writing, "writing"),
-            (TimeCategory::Service, "service"),
-        ];
-
-        for (cat, class) in &categories {
+        // Configured categories first, in config order; then anything else
+        // that appears in the notes, alphabetically.
+        let mut categories: Vec<TimeCategory> = state
+            .time_categories
+            .iter()
+            .map(|d| TimeCategory::from_name(&d.name))
+            .collect();
+        let mut extra: Vec<TimeCategory> = totals
+            .keys()
+            .filter(|c| !categories.contains(c))
+            .cloned()
+            .collect();
+        extra.sort_by_key(|c| c.to_string());
+        categories.extend(extra);
+
+        for cat in &categories {
             if let Some(&mins) = totals.get(cat) {
                 let pct = (mins as f64 / total_minutes as f64) * 100.0;
+                let class = if crate::time_categories::find_category(&state.time_categories, cat)
+                    .is_some()
+                {
+                    cat.css_slug()
+                } else {
+                    "other".to_string()
+                };
+                let name = html_escape(&cat.to_string());
                 bar_html.push_str(&format!(
                     "<div class=\"time-segment cat-{}\" style=\"width: {:.1}%\" title=\"{}: {} mins\"></div>",
-                    class, pct, class, mins
+                    class, pct, name, mins
                 ));
                 legend_html.push_str(&format!(
                     "<span class=\"time-legend-item\"><span class=\"time-legend-color cat-{}\"></span>{}: {}h {}m</span>",
-                    class, class, mins / 60, mins % 60
+                    class, name, mins / 60, mins % 60
                 ));
             }
         }
@@ -1313,39 +1962,309 @@ pub async fn time_tracking(State(state): State<Arc<AppState>>, jar: CookieJar) -
                     entry.date.format("%Y-%m-%d"),
                     note.key,
                     html_escape(&note.title),
-                    entry.category,
+                    html_escape(&entry.category.to_string()),
                     entry.minutes,
-                    entry.description.as_deref().unwrap_or("-")
+                    html_escape(entry.description.as_deref().unwrap_or("-"))
                 ));
             }
         }
     }
     entries_html.push_str("</table>");
 
+    let mut projects: Vec<(String, u32)> = project_totals.into_iter().collect();
+    projects.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut projects_html = String::from("<h2>By Project</h2><table class=\"time-table\">");
+    projects_html.push_str("<tr><th>Project</th><th>Total</th><th></th></tr>");
+    for (key, mins) in &projects {
+        let title = notes_map
+            .get(key)
+            .map(|n| html_escape(&n.title))
+            .unwrap_or_else(|| html_escape(key));
+        let has_children = notes.iter().any(|n| n.parents.contains(key));
+        let drill = if has_children && Some(key.as_str()) != scope {
+            format!("<a href=\"/time?project={}\">breakdown</a>", key)
+        } else {
+            String::new()
+        };
+        let label = if Some(key.as_str()) == scope {
+            format!("{} <span class=\"time-breadcrumb\">(this note)</span>", title)
+        } else {
+            title
+        };
+        projects_html.push_str(&format!(
+            "<tr><td><a href=\"/note/{}\">{}</a></td><td>{}h {}m</td><td>{}</td></tr>",
+            key,
+            label,
+            mins / 60,
+            mins % 60,
+            drill
+        ));
+    }
+    projects_html.push_str("</table>");
+
+    let heading = match scope.and_then(|k| notes_map.get(k)) {
+        Some(project) => {
+            let mut crumbs: Vec<String> = ancestor_chain(&project.key, &notes_map)
+                .iter()
+                .skip(1)
+                .rev()
+                .filter_map(|k| notes_map.get(k))
+                .map(|n| format!("<a href=\"/time?project={}\">{}</a>", n.key, html_escape(&n.title)))
+                .collect();
+            crumbs.insert(0, "<a href=\"/time\">All projects</a>".to_string());
+            format!(
+                "<h1>Time Tracking: {}</h1><p class=\"time-breadcrumb\">{}</p>",
+                html_escape(&project.title),
+                crumbs.join(" &rsaquo; ")
+            )
+        }
+        None => "<h1>Time Tracking</h1><p class=\"time-breadcrumb\"><a href=\"/time/invoice\">Invoices</a> &middot; <a href=\"/time/import\">Import CSV</a></p>".to_string(),
+    };
+
     let html = format!(
-        "<h1>Time Tracking</h1>
+        "<style>{}</style>
+        {}
         <div class=\"time-summary\">
             <p>Total tracked: <strong>{}h {}m</strong></p>
             {}{}
         </div>
+        {}
         {}",
+        crate::time_categories::category_css(&state.time_categories),
+        heading,
         total_minutes / 60,
         total_minutes % 60,
         bar_html,
         legend_html,
+        projects_html,
         entries_html
     );
 
     Html(base_html("Time Tracking", &html, None, logged_in))
 }
 
+// ============================================================================
+// Invoice Handler
+// ============================================================================
+
+#[derive(Deserialize)]
+pub struct InvoiceQuery {
+    pub tag: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub format: Option<String>,
+}
+
+/// One billable line on an invoice.
+struct InvoiceItem<'a> {
+    note: &'a Note,
+    entry: &'a crate::models::TimeEntry,
+    rate: Option<f64>,
+}
+
+impl InvoiceItem<'_> {
+    fn hours(&self) -> f64 {
+        self.entry.minutes as f64 / 60.0
+    }
+
+    fn amount(&self) -> Option<f64> {
+        self.rate.map(|r| r * self.hours())
+    }
+}
+
+/// Collect time entries for notes carrying `tag` (directly or through an
+/// ancestor) within the date range. Each entry is billed at the nearest
+/// `rate:` found walking up the parent chain. Without a tag, every note
+/// with a rate is included.
+fn invoice_items<'a>(
+    notes: &'a [Note],
+    notes_map: &HashMap<String, Note>,
+    tag: Option<&str>,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+) -> Vec<InvoiceItem<'a>> {
+    let mut items = Vec::new();
+
+    for note in notes {
+        if note.time_entries.is_empty() {
+            continue;
+        }
+        let chain: Vec<&Note> = ancestor_chain(&note.key, notes_map)
+            .iter()
+            .filter_map(|k| notes_map.get(k))
+            .collect();
+        let rate = chain.iter().find_map(|n| n.hourly_rate);
+        let included = match tag {
+            Some(tag) => chain
+                .iter()
+                .any(|n| n.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))),
+            None => rate.is_some(),
+        };
+        if !included {
+            continue;
+        }
+
+        for entry in &note.time_entries {
+            if from.is_some_and(|f| entry.date < f) || to.is_some_and(|t| entry.date > t) {
+                continue;
+            }
+            items.push(InvoiceItem { note, entry, rate });
+        }
+    }
+
+    items.sort_by(|a, b| {
+        a.entry
+            .date
+            .cmp(&b.entry.date)
+            .then_with(|| a.note.title.cmp(&b.note.title))
+    });
+    items
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn invoice_csv(items: &[InvoiceItem]) -> String {
+    let mut csv = String::from("date,note,category,description,hours,rate,amount\n");
+    for item in items {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.2},{},{}\n",
+            item.entry.date.format("%Y-%m-%d"),
+            csv_field(&item.note.title),
+            csv_field(&item.entry.category.to_string()),
+            csv_field(item.entry.description.as_deref().unwrap_or("")),
+            item.hours(),
+            item.rate.map(|r| format!("{:.2}", r)).unwrap_or_default(),
+            item.amount().map(|a| format!("{:.2}", a)).unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+pub async fn time_invoice(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<InvoiceQuery>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let parse_date = |s: &Option<String>| {
+        s.as_deref()
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d").ok())
+    };
+    let from = parse_date(&query.from);
+    let to = parse_date(&query.to);
+    let tag = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty());
+
+    let notes = state.load_notes();
+    let notes_map = state.notes_map();
+    let items = invoice_items(&notes, &notes_map, tag, from, to);
+
+    if query.format.as_deref() == Some("csv") {
+        let filename = format!(
+            "invoice-{}-{}-{}.csv",
+            crate::models::category_slug(tag.unwrap_or("all")),
+            from.map(|d| d.to_string()).unwrap_or_else(|| "start".to_string()),
+            to.map(|d| d.to_string()).unwrap_or_else(|| "end".to_string()),
+        );
+        return (
+            [
+                ("content-type", "text/csv; charset=utf-8".to_string()),
+                (
+                    "content-disposition",
+                    format!("attachment; filename=\"{}\"", filename),
+                ),
+            ],
+            invoice_csv(&items),
+        )
+            .into_response();
+    }
+
+    let total_minutes: u32 = items.iter().map(|i| i.entry.minutes).sum();
+    let total_amount: f64 = items.iter().filter_map(|i| i.amount()).sum();
+    let unrated = items.iter().filter(|i| i.rate.is_none()).count();
+
+    let mut rows = String::new();
+    for item in &items {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td><a href=\"/note/{}\">{}</a></td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td><td>{}</td></tr>",
+            item.entry.date.format("%Y-%m-%d"),
+            item.note.key,
+            html_escape(&item.note.title),
+            html_escape(&item.entry.category.to_string()),
+            html_escape(item.entry.description.as_deref().unwrap_or("-")),
+            item.hours(),
+            item.rate.map(|r| format!("{:.2}", r)).unwrap_or_else(|| "-".to_string()),
+            item.amount().map(|a| format!("{:.2}", a)).unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+
+    let csv_url = format!(
+        "/time/invoice?tag={}&from={}&to={}&format=csv",
+        urlencoding::encode(tag.unwrap_or("")),
+        from.map(|d| d.to_string()).unwrap_or_default(),
+        to.map(|d| d.to_string()).unwrap_or_default(),
+    );
+
+    let warning = if unrated > 0 {
+        format!(
+            "<p class=\"time-breadcrumb\">{} entr{} have no <code>rate:</code> and are not billed.</p>",
+            unrated,
+            if unrated == 1 { "y" } else { "ies" }
+        )
+    } else {
+        String::new()
+    };
+
+    let html = format!(
+        r#"<h1>Invoice{title_suffix}</h1>
+        <form method="get" action="/time/invoice" class="invoice-filter">
+            <input type="text" name="tag" placeholder="tag" value="{tag}">
+            <input type="date" name="from" value="{from}">
+            <input type="date" name="to" value="{to}">
+            <button type="submit">Update</button>
+            <a href="{csv_url}">Download CSV</a>
+        </form>
+        <table class="time-table">
+            <tr><th>Date</th><th>Note</th><th>Category</th><th>Description</th><th>Hours</th><th>Rate</th><th>Amount</th></tr>
+            {rows}
+            <tr><th colspan="4">Total</th><th>{hours:.2}</th><th></th><th>{amount:.2}</th></tr>
+        </table>
+        {warning}"#,
+        title_suffix = tag
+            .map(|t| format!(": {}", html_escape(t)))
+            .unwrap_or_default(),
+        tag = html_escape(tag.unwrap_or("")),
+        from = from.map(|d| d.to_string()).unwrap_or_default(),
+        to = to.map(|d| d.to_string()).unwrap_or_default(),
+        csv_url = html_escape(&csv_url),
+        rows = rows,
+        hours = total_minutes as f64 / 60.0,
+        amount = total_amount,
+        warning = warning,
+    );
+
+    Html(base_html("Invoice", &html, None, true)).into_response()
+}
+
 // ============================================================================
 // Bibliography Handler
 // ============================================================================
 
-pub async fn bibliography(State(state): State<Arc<AppState>>) -> Response {
+pub async fn bibliography(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<BibFilter>,
+) -> Response {
     let notes = state.load_notes();
-    let bib = generate_bibliography(&notes);
+    let bib = generate_bibliography(&filter_bibliography(&notes, &filter));
 
     ([("content-type", "text/plain; charset=utf-8")], bib).into_response()
 }
@@ -1354,10 +2273,17 @@ pub async fn bibliography(State(state): State<Arc<AppState>>) -> Response {
 // Notes List API (for graph autocomplete)
 // ============================================================================
 
+/// `GET /api/notes/list[?sort=…&order=…]`: every note, in the `[sort]`
+/// order by default.
 pub async fn notes_list_api(
+    Query(query): Query<IndexQuery>,
     State(state): State<Arc<AppState>>,
 ) -> Response {
-    let notes_list: Vec<serde_json::Value> = state.notes_map().values().map(|n| {
+    let (sort, order) = listing_sort(&state.config.sort, query.sort, query.order);
+    let notes = state.load_notes();
+    let mut sorted: Vec<&Note> = notes.iter().collect();
+    sort_notes(&mut sorted, sort, order);
+    let notes_list: Vec<serde_json::Value> = sorted.into_iter().map(|n| {
         let (nt, authors, year, venue, short_label) = match &n.note_type {
             crate::models::NoteType::Paper(meta) => {
                 let eff = meta.effective_metadata(&n.title);
@@ -1431,7 +2357,7 @@ pub async fn delete_graph_edge(
     // If this is a citation edge, also remove it from the note's auto-citations block
     if req.edge_type.as_deref() == Some("citation") {
         if let Err(e) = remove_citation_from_note(&state, &req.source, &req.target).await {
-            eprintln!("Warning: failed to remove citation from note: {}", e);
+            tracing::warn!(error = %e, "Failed to remove citation from note");
         }
         // Remove from the citations cache in sled
         if let Ok(tree) = state.db.open_tree("citations") {
@@ -1460,7 +2386,7 @@ async fn remove_citation_from_note(state: &AppState, source_key: &str, target_ke
     let notes_map = state.notes_map();
     let note = notes_map.get(source_key).ok_or_else(|| format!("Note not found: {}", source_key))?;
 
-    let content = &note.full_file_content;
+    let content = &crate::encryption::open_file(&note.full_file_content)?;
     let begin_marker = "<!-- BEGIN AUTO-CITATIONS -->";
     let end_marker = "<!-- END AUTO-CITATIONS -->";
 
@@ -1488,13 +2414,13 @@ async fn remove_citation_from_note(state: &AppState, source_key: &str, target_ke
     let new_content = format!("{}{}{}", before, new_block, after);
 
     // Write the file
-    let path = std::path::PathBuf::from(crate::NOTES_DIR).join(format!("{}.md", source_key));
-    std::fs::write(&path, &new_content).map_err(|e| format!("Failed to write note: {}", e))?;
+    check_writable(&note.path)?;
+    let path = state.notes_dir.join(&note.path);
+    crate::encryption::write_file(&path, &new_content)?;
 
     // Reload into cache
     drop(notes_map);
-    let notes_dir = std::path::PathBuf::from(crate::NOTES_DIR);
-    if let Some(updated_note) = crate::notes::load_note(&path, &notes_dir) {
+    if let Some(updated_note) = crate::notes::load_note(&path, &state.notes_dir) {
         let all_keys: std::collections::HashSet<String> = state.notes_map().keys().cloned().collect();
         let _ = crate::graph_index::reindex_note(&state.db, &updated_note, &all_keys);
     }
@@ -1555,40 +2481,46 @@ pub async fn upload_pdf(
         None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
     };
 
-    // Get the file from multipart
-    let mut filename = String::new();
-    let mut file_data = Vec::new();
+    // Stream the file from multipart into a temporary file
+    let upload_path = std::env::temp_dir().join(format!("notes-upload-{:08x}.pdf", rand::random::<u32>()));
+    let mut filename = None;
+    let mut size = 0;
 
-    while let Ok(Some(field)) = multipart.next_field().await {
+    while let Ok(Some(mut field)) = multipart.next_field().await {
         if field.name() == Some("file") {
-            filename = field
-                .file_name()
-                .unwrap_or("document.pdf")
-                .to_string();
-
-            match field.bytes().await {
-                Ok(bytes) => file_data = bytes.to_vec(),
-                Err(e) => return (StatusCode::BAD_REQUEST, format!("Failed to read file: {}", e)).into_response(),
+            filename = Some(field.file_name().unwrap_or("document.pdf").to_string());
+            let mut file = match tokio::fs::File::create(&upload_path).await {
+                Ok(f) => f,
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save PDF: {}", e)).into_response(),
+            };
+            loop {
+                match field.chunk().await {
+                    Ok(Some(chunk)) => {
+                        size += chunk.len();
+                        if let Err(e) = tokio::io::AsyncWriteExt::write_all(&mut file, &chunk).await {
+                            let _ = fs::remove_file(&upload_path);
+                            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save PDF: {}", e)).into_response();
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = fs::remove_file(&upload_path);
+                        return (StatusCode::BAD_REQUEST, format!("Failed to read file: {}", e)).into_response();
+                    }
+                }
             }
             break;
         }
     }
 
-    if file_data.is_empty() {
+    let Some(filename) = filename.filter(|_| size > 0) else {
+        let _ = fs::remove_file(&upload_path);
         return (StatusCode::BAD_REQUEST, "No file uploaded").into_response();
-    }
+    };
 
-    // Sanitize filename
+    // Sanitize filename and save
     let safe_filename = sanitize_pdf_filename(&filename);
-    let pdf_path = state.pdfs_dir.join(&safe_filename);
-
-    // Validate path stays within pdfs_dir
-    if let Err(_) = validate_path_within(&state.pdfs_dir, &pdf_path) {
-        return (StatusCode::BAD_REQUEST, "Invalid filename").into_response();
-    }
-
-    // Save file
-    if let Err(e) = fs::write(&pdf_path, &file_data) {
+    if let Err(e) = state.storage.put_file(&safe_filename, &upload_path).await {
         return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save PDF: {}", e)).into_response();
     }
 
@@ -1620,11 +2552,11 @@ pub async fn download_pdf_from_url(
         return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
     }
 
-    // Validate URL: must be absolute HTTP(S) and not targeting internal IPs.
-    // We skip the domain allowlist here because PDF URLs from smart-find
-    // can point to any academic publisher/CDN (github.io, usenix.org CDN, etc.)
-    // and the user explicitly clicks "Download & Attach".
-    if let Err(e) = validate_pdf_download_url(&body.url) {
+    // Validate URL against `[url_policy]`. By default the domain allowlist
+    // is skipped here because PDF URLs from smart-find can point to any
+    // academic publisher/CDN (github.io, usenix.org CDN, etc.) and the user
+    // explicitly clicks "Download & Attach".
+    if let Err(e) = crate::url_validator::validate_pdf_url(&body.url) {
         return (StatusCode::BAD_REQUEST, format!("Invalid URL: {}", e)).into_response();
     }
 
@@ -1635,24 +2567,20 @@ pub async fn download_pdf_from_url(
     };
 
     // Download the PDF with browser-like headers (many academic servers block bare requests)
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .redirect(reqwest::redirect::Policy::limited(10))
-        .build()
-        .unwrap_or_else(|_| reqwest::Client::new());
     // Derive Referer from the URL's origin — many academic publishers (ACM, IEEE, Springer) 403 without it
     let referer = url::Url::parse(&body.url)
         .ok()
         .map(|u| format!("{}://{}/", u.scheme(), u.host_str().unwrap_or("")))
         .unwrap_or_default();
-    let response = match client
-        .get(&body.url)
-        .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .header("Accept", "application/pdf,*/*")
-        .header("Referer", &referer)
-        .send()
-        .await
-    {
+    // Redirects are followed by the policy, which validates each hop
+    let response = crate::url_validator::UrlPolicy::pdf_download()
+        .get("pdf_download", &body.url, std::time::Duration::from_secs(30), |r| {
+            r.header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+                .header("Accept", "application/pdf,*/*")
+                .header("Referer", &referer)
+        })
+        .await;
+    let response = match response {
         Ok(r) => r,
         Err(e) => return (StatusCode::BAD_REQUEST, format!("Failed to download: {}", e)).into_response(),
     };
@@ -1671,7 +2599,7 @@ pub async fn download_pdf_from_url(
         let meta = paper.effective_metadata(&note.title);
         format!("{}.pdf", meta.bib_key)
     } else {
-        let url_path = body.url.split('/').last().unwrap_or("document");
+        let url_path = body.url.split('/').next_back().unwrap_or("document");
         if url_path.ends_with(".pdf") {
             url_path.to_string()
         } else {
@@ -1680,15 +2608,9 @@ pub async fn download_pdf_from_url(
     };
 
     let safe_filename = sanitize_pdf_filename(&filename);
-    let pdf_path = state.pdfs_dir.join(&safe_filename);
-
-    // Validate path stays within pdfs_dir
-    if let Err(_) = validate_path_within(&state.pdfs_dir, &pdf_path) {
-        return (StatusCode::BAD_REQUEST, "Invalid filename").into_response();
-    }
 
     // Save file
-    if let Err(e) = fs::write(&pdf_path, &bytes) {
+    if let Err(e) = state.storage.put(&safe_filename, bytes.to_vec()).await {
         return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save PDF: {}", e)).into_response();
     }
 
@@ -1734,22 +2656,11 @@ pub async fn rename_pdf(
     // Sanitize both old (from frontmatter, could be tampered) and new filenames
     let old_filename_safe = sanitize_pdf_filename(&old_filename);
     let new_filename = sanitize_pdf_filename(&body.new_name);
-    let old_path = state.pdfs_dir.join(&old_filename_safe);
-    let new_path = state.pdfs_dir.join(&new_filename);
-
-    // Validate both paths stay within pdfs_dir
-    if let Err(_) = validate_path_within(&state.pdfs_dir, &old_path) {
-        return (StatusCode::BAD_REQUEST, "Invalid source filename").into_response();
-    }
-    if let Err(_) = validate_path_within(&state.pdfs_dir, &new_path) {
-        return (StatusCode::BAD_REQUEST, "Invalid target filename").into_response();
-    }
-
-    if !old_path.exists() {
+    if !state.storage.exists(&old_filename_safe).await {
         return (StatusCode::NOT_FOUND, "PDF file not found").into_response();
     }
 
-    if let Err(e) = fs::rename(&old_path, &new_path) {
+    if let Err(e) = state.storage.rename(&old_filename_safe, &new_filename).await {
         return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to rename PDF: {}", e)).into_response();
     }
 
@@ -1878,11 +2789,9 @@ pub async fn smart_pdf_find(
         url.starts_with("https://") || url.starts_with("http://")
     }
 
-    // Build a short-timeout client for the fast API lookups
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(6))
-        .build()
-        .unwrap_or_else(|_| reqwest::Client::new());
+    // Short timeouts for the fast API lookups
+    let client = state.http.clone();
+    const FAST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6);
 
     // --- Phase 1: Run all fast API lookups in parallel ---
 
@@ -1893,7 +2802,10 @@ pub async fn smart_pdf_find(
         async move {
             let aid = aid?;
             let pdf_url = format!("https://arxiv.org/pdf/{}.pdf", aid);
-            let resp = client.head(&pdf_url).send().await.ok()?;
+            crate::http::throttle(&pdf_url).await;
+            let resp = client.head(&pdf_url).timeout(FAST_TIMEOUT).send().await;
+            crate::telemetry::record_external_call("arxiv", resp.is_ok());
+            let resp = resp.ok()?;
             if resp.status().is_success() || resp.status().is_redirection() {
                 Some(("arxiv".to_string(), pdf_url))
             } else {
@@ -1912,7 +2824,10 @@ pub async fn smart_pdf_find(
                 "https://api.semanticscholar.org/graph/v1/paper/DOI:{}?fields=openAccessPdf",
                 d
             );
-            let resp = client.get(&url).send().await.ok()?;
+            crate::http::throttle(&url).await;
+            let resp = client.get(&url).timeout(FAST_TIMEOUT).send().await;
+            crate::telemetry::record_http_call("semanticscholar", &resp);
+            let resp = resp.ok()?;
             if !resp.status().is_success() { return None; }
             let json: serde_json::Value = resp.json().await.ok()?;
             let pdf_url = json.get("openAccessPdf")?.get("url")?.as_str()?;
@@ -1933,7 +2848,10 @@ pub async fn smart_pdf_find(
                 "https://api.semanticscholar.org/graph/v1/paper/search?query={}&limit=1&fields=openAccessPdf",
                 encoded
             );
-            let resp = client.get(&url).send().await.ok()?;
+            crate::http::throttle(&url).await;
+            let resp = client.get(&url).timeout(FAST_TIMEOUT).send().await;
+            crate::telemetry::record_http_call("semanticscholar", &resp);
+            let resp = resp.ok()?;
             if !resp.status().is_success() { return None; }
             let json: serde_json::Value = resp.json().await.ok()?;
             let pdf_url = json.get("data")?
@@ -1955,7 +2873,10 @@ pub async fn smart_pdf_find(
                 "https://api.unpaywall.org/v2/{}?email=notes@example.com",
                 d
             );
-            let resp = client.get(&url).send().await.ok()?;
+            crate::http::throttle(&url).await;
+            let resp = client.get(&url).timeout(FAST_TIMEOUT).send().await;
+            crate::telemetry::record_http_call("unpaywall", &resp);
+            let resp = resp.ok()?;
             if !resp.status().is_success() { return None; }
             let json: serde_json::Value = resp.json().await.ok()?;
             let pdf_url = json.get("best_oa_location")?
@@ -1984,35 +2905,25 @@ pub async fn smart_pdf_find(
         })).into_response();
     }
 
-    // --- Phase 2: Claude CLI fallback (slow — only if fast sources all missed) ---
-    let search_title = title.clone();
-    let search_authors = authors.unwrap_or_default();
-    let claude_result = tokio::task::spawn_blocking(move || {
-        let prompt = format!(
-            "Find a direct PDF download URL for the paper: \"{}\" by {}. \
-             Return ONLY a JSON object: {{\"url\":\"...\"}} or {{\"error\":\"not found\"}}. \
-             No other text.",
-            search_title, search_authors
-        );
-        Command::new("claude")
-            .args(["-p", &prompt])
-            .output()
-    }).await;
-
-    if let Ok(Ok(output)) = claude_result {
-        if output.status.success() {
-            let response = String::from_utf8_lossy(&output.stdout);
-            if let Some(json_start) = response.find('{') {
-                if let Some(json_end) = response.rfind('}') {
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&response[json_start..=json_end]) {
-                        if let Some(url) = json.get("url").and_then(|u| u.as_str()) {
-                            if !url.is_empty() && url.starts_with("http") {
-                                return axum::Json(serde_json::json!({
-                                    "status": "found",
-                                    "url": url,
-                                    "source": "claude"
-                                })).into_response();
-                            }
+    // --- Phase 2: LLM fallback (slow — only if fast sources all missed) ---
+    let prompt = format!(
+        "Find a direct PDF download URL for the paper: \"{}\" by {}. \
+         Return ONLY a JSON object: {{\"url\":\"...\"}} or {{\"error\":\"not found\"}}. \
+         No other text.",
+        title,
+        authors.unwrap_or_default()
+    );
+    if let Ok(response) = crate::llm::complete(&client, prompt, None).await {
+        if let Some(json_start) = response.find('{') {
+            if let Some(json_end) = response.rfind('}') {
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&response[json_start..=json_end]) {
+                    if let Some(url) = json.get("url").and_then(|u| u.as_str()) {
+                        if !url.is_empty() && url.starts_with("http") {
+                            return axum::Json(serde_json::json!({
+                                "status": "found",
+                                "url": url,
+                                "source": "claude"
+                            })).into_response();
                         }
                     }
                 }
@@ -2026,39 +2937,6 @@ pub async fn smart_pdf_find(
     })).into_response()
 }
 
-/// Permissive URL validation for PDF downloads: requires absolute HTTP(S) URL
-/// and blocks internal/private IPs, but does NOT enforce the domain allowlist.
-/// Used for user-initiated PDF downloads where the URL may come from any academic source.
-fn validate_pdf_download_url(url_str: &str) -> Result<(), String> {
-    let url = url::Url::parse(url_str)
-        .map_err(|e| format!("{}", e))?;
-
-    if url.scheme() != "https" && url.scheme() != "http" {
-        return Err("Only HTTP(S) URLs are allowed".to_string());
-    }
-
-    let host = url.host_str()
-        .ok_or_else(|| "No host in URL".to_string())?;
-
-    // Block internal IPs via DNS resolution
-    let port = url.port().unwrap_or(if url.scheme() == "https" { 443 } else { 80 });
-    let socket_addr = format!("{}:{}", host, port);
-    if let Ok(addrs) = std::net::ToSocketAddrs::to_socket_addrs(&socket_addr.as_str()) {
-        for addr in addrs {
-            let ip = addr.ip();
-            let is_internal = match ip {
-                std::net::IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local() || v4.is_unspecified(),
-                std::net::IpAddr::V6(v6) => v6.is_loopback() || v6.is_unspecified(),
-            };
-            if is_internal {
-                return Err(format!("Internal IP address not allowed: {}", ip));
-            }
-        }
-    }
-
-    Ok(())
-}
-
 pub async fn unlink_pdf(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
@@ -2089,7 +2967,8 @@ pub async fn unlink_pdf(
     })).into_response()
 }
 
-fn remove_note_pdf_frontmatter(notes_dir: &PathBuf, note_path: &PathBuf) -> Result<(), String> {
+fn remove_note_pdf_frontmatter(notes_dir: &std::path::Path, note_path: &std::path::Path) -> Result<(), String> {
+    check_writable(note_path)?;
     let full_path = notes_dir.join(note_path);
     let content = fs::read_to_string(&full_path)
         .map_err(|e| format!("Failed to read note: {}", e))?;
@@ -2119,13 +2998,13 @@ fn remove_note_pdf_frontmatter(notes_dir: &PathBuf, note_path: &PathBuf) -> Resu
     if !new_content.ends_with('\n') {
         new_content.push('\n');
     }
-    fs::write(&full_path, new_content)
-        .map_err(|e| format!("Failed to write note: {}", e))?;
+    crate::encryption::write_file(&full_path, &new_content)?;
 
     Ok(())
 }
 
 fn update_note_pdf_frontmatter(notes_dir: &PathBuf, note_path: &PathBuf, pdf_filename: &str) -> Result<(), String> {
+    check_writable(note_path)?;
     let full_path = notes_dir.join(note_path);
 
     // Defensive: if cached path doesn't exist, try to find the note by filename on disk
@@ -2198,8 +3077,7 @@ fn update_note_pdf_frontmatter(notes_dir: &PathBuf, note_path: &PathBuf, pdf_fil
     if !new_content.ends_with('\n') {
         new_content.push('\n');
     }
-    fs::write(&full_path, new_content)
-        .map_err(|e| format!("Failed to write note: {}", e))?;
+    crate::encryption::write_file(&full_path, &new_content)?;
 
     Ok(())
 }
@@ -2576,18 +3454,28 @@ mod tests {
             key: key.to_string(),
             path: PathBuf::from(format!("{}.md", key)),
             title: title.to_string(),
-            date: None,
-            note_type: NoteType::Note,
-            parent_key: None,
-            time_entries: vec![],
             raw_content: "Some content.".to_string(),
             full_file_content: "---\ntitle: Test\n---\n\nSome content.\n".to_string(),
             modified: Utc::now(),
-            pdf: None,
-            hidden: false,
+            ..Default::default()
         }
     }
 
+    fn time_tree() -> HashMap<String, Note> {
+        let mut root = make_note("datalog", "Datalog");
+        root.parents = vec![];
+        let mut child = make_note("semantics", "Semantics");
+        child.parents = vec!["datalog".to_string()];
+        let mut leaf = make_note("proofs", "Proofs");
+        leaf.parents = vec!["semantics".to_string()];
+        let mut orphan = make_note("orphan", "Orphan");
+        orphan.parents = vec!["missing".to_string()];
+        [root, child, leaf, orphan]
+            .into_iter()
+            .map(|n| (n.key.clone(), n))
+            .collect()
+    }
+
     fn make_paper_note(key: &str, title: &str, bibtex: &str) -> Note {
         Note {
             key: key.to_string(),
@@ -2607,14 +3495,13 @@ mod tests {
                         identifier: "10.1234/test".to_string(),
                     },
                 ],
+                versions: vec![],
             }),
-            parent_key: None,
-            time_entries: vec![],
             raw_content: "Paper content.".to_string(),
             full_file_content: "---\ntitle: Test Paper\n---\n\nPaper content.\n".to_string(),
             modified: Utc::now(),
             pdf: Some("test.pdf".to_string()),
-            hidden: false,
+            ..Default::default()
         }
     }
 
@@ -2665,7 +3552,7 @@ mod tests {
         let note = make_paper_note("test", "Test Paper", bibtex);
         let html = build_note_meta_html(&note, &HashMap::new());
         assert!(html.contains("Authors"));
-        assert!(html.contains("Smith"));
+        assert!(html.contains(r#"<a href="/author/john-smith">John Smith</a>, <a href="/author/jane-doe">Jane Doe</a>"#));
     }
 
     #[test]
@@ -2688,11 +3575,21 @@ mod tests {
   author={Smith},
   year={2024}
 }"#;
-        let note = make_paper_note("test", "Test", bibtex);
+        let mut note = make_paper_note("test", "Test", bibtex);
         let html = build_note_meta_html(&note, &HashMap::new());
         assert!(html.contains("Sources"));
         assert!(html.contains("arxiv.org"));
         assert!(html.contains("doi.org"));
+        assert!(!html.contains("Versions"));
+
+        if let NoteType::Paper(ref mut paper) = note.note_type {
+            paper.versions.push(crate::models::PaperVersion {
+                label: Some("camera-ready".to_string()),
+                source: PaperSource { source_type: "doi".to_string(), identifier: "10.1145/99".to_string() },
+            });
+        }
+        let html = build_note_meta_html(&note, &HashMap::new());
+        assert!(html.contains(r#"camera-ready: <a href="https://doi.org/10.1145/99" target="_blank">DOI 10.1145/99</a>"#), "{}", html);
     }
 
     #[test]
@@ -2722,7 +3619,7 @@ mod tests {
     fn test_meta_html_parent_link() {
         let parent = make_note("parent-note", "Parent Note");
         let mut child = make_note("child-note", "Child Note");
-        child.parent_key = Some("parent-note".to_string());
+        child.parents = vec!["parent-note".to_string()];
 
         let mut notes_map = HashMap::new();
         notes_map.insert("parent-note".to_string(), parent);
@@ -2737,7 +3634,7 @@ mod tests {
     #[test]
     fn test_meta_html_parent_missing() {
         let mut note = make_note("child", "Child");
-        note.parent_key = Some("nonexistent".to_string());
+        note.parents = vec!["nonexistent".to_string()];
         let html = build_note_meta_html(&note, &HashMap::new());
         // Should not crash, just skip the parent row
         assert!(!html.contains("Parent"));
@@ -2766,4 +3663,174 @@ mod tests {
         // Should work fine with empty notes map
         assert!(html.contains("[@test]"));
     }
+
+    // ========================================================================
+    // Time roll-up
+    // ========================================================================
+
+    #[test]
+    fn test_rollup_to_top_level_project() {
+        let map = time_tree();
+        assert_eq!(rollup_keys("proofs", None, &map), ["datalog"]);
+        assert_eq!(rollup_keys("datalog", None, &map), ["datalog"]);
+        // A dangling parent makes the note its own project
+        assert_eq!(rollup_keys("orphan", None, &map), ["orphan"]);
+    }
+
+    #[test]
+    fn test_rollup_within_scope() {
+        let map = time_tree();
+        assert_eq!(rollup_keys("proofs", Some("datalog"), &map), ["semantics"]);
+        assert_eq!(rollup_keys("datalog", Some("datalog"), &map), ["datalog"]);
+        assert!(rollup_keys("orphan", Some("datalog"), &map).is_empty());
+    }
+
+    #[test]
+    fn test_rollup_with_two_parents() {
+        let mut map = time_tree();
+        map.insert("verification".to_string(), make_note("verification", "Verification"));
+        map.get_mut("proofs").unwrap().parents = vec!["semantics".to_string(), "verification".to_string()];
+        assert_eq!(rollup_keys("proofs", None, &map), ["verification", "datalog"]);
+        assert_eq!(rollup_keys("proofs", Some("datalog"), &map), ["semantics"]);
+        assert_eq!(rollup_keys("proofs", Some("verification"), &map), ["proofs"]);
+        assert_eq!(ancestor_chain("proofs", &map), ["proofs", "semantics", "datalog"]);
+    }
+
+    #[test]
+    fn test_ancestor_chain_stops_on_cycle() {
+        let mut map = time_tree();
+        map.get_mut("datalog").unwrap().parents = vec!["proofs".to_string()];
+        let chain = ancestor_chain("proofs", &map);
+        assert_eq!(chain, vec!["proofs", "semantics", "datalog"]);
+    }
+
+    // ========================================================================
+    // Invoices
+    // ========================================================================
+
+    fn with_time(mut note: Note, minutes: u32, day: u32) -> Note {
+        note.time_entries.push(crate::models::TimeEntry {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 3, day).unwrap(),
+            minutes,
+            category: TimeCategory::Programming,
+            description: Some("work, \"quoted\"".to_string()),
+        });
+        note
+    }
+
+    #[test]
+    fn test_invoice_items_inherit_tag_and_rate() {
+        let mut client = with_time(make_note("acme", "Acme"), 60, 1);
+        client.tags = vec!["ClientX".to_string()];
+        client.hourly_rate = Some(100.0);
+        let mut task = with_time(make_note("acme-task", "Acme Task"), 90, 5);
+        task.parents = vec!["acme".to_string()];
+        let other = with_time(make_note("other", "Other"), 30, 2);
+        let notes = vec![client, task, other];
+        let map: HashMap<String, Note> = notes.iter().map(|n| (n.key.clone(), n.clone())).collect();
+
+        let items = invoice_items(&notes, &map, Some("clientx"), None, None);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].note.key, "acme-task");
+        assert_eq!(items[1].amount(), Some(150.0));
+
+        let from = chrono::NaiveDate::from_ymd_opt(2024, 3, 2);
+        let items = invoice_items(&notes, &map, Some("clientx"), from, None);
+        assert_eq!(items.len(), 1);
+
+        // Without a tag only rated notes are billed
+        let items = invoice_items(&notes, &map, None, None, None);
+        assert_eq!(items.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_bibliography_by_tag_and_year() {
+        let mut project = make_note("plt", "PLT");
+        project.tags = vec!["PLT".to_string()];
+        let mut old = make_paper_note("old", "Old", "@article{zeta1999,\n  title={Old},\n  year={1999}\n}");
+        old.parents = vec!["plt".to_string()];
+        let mut new = make_paper_note("new", "New", "@article{alpha2024,\n  title={New},\n  year={2024}\n}");
+        new.tags = vec!["plt".to_string()];
+        let other = make_paper_note("other", "Other", "@article{beta2025,\n  title={Other},\n  year={2025}\n}");
+        let notes = vec![project, old, new, other];
+        let keys = |filter: BibFilter| -> Vec<String> {
+            filter_bibliography(&notes, &filter).into_iter().map(|n| n.key).collect()
+        };
+
+        assert_eq!(keys(BibFilter::default()), ["new", "other", "old"]);
+        let tag = || BibFilter { tag: Some("plt".to_string()), ..Default::default() };
+        assert_eq!(keys(tag()), ["new", "old"]);
+        assert_eq!(keys(BibFilter { since: Some(2024), ..tag() }), ["new"]);
+        assert_eq!(keys(BibFilter { until: Some(2024), ..Default::default() }), ["new", "old"]);
+    }
+
+    #[test]
+    fn test_invoice_csv_escapes_fields() {
+        let mut note = with_time(make_note("acme", "Acme, Inc."), 45, 1);
+        note.hourly_rate = Some(80.0);
+        let notes = vec![note];
+        let map: HashMap<String, Note> = notes.iter().map(|n| (n.key.clone(), n.clone())).collect();
+        let csv = invoice_csv(&invoice_items(&notes, &map, None, None, None));
+        assert_eq!(
+            csv.lines().nth(1),
+            Some("2024-03-01,\"Acme, Inc.\",programming,\"work, \"\"quoted\"\"\",0.75,80.00,60.00")
+        );
+    }
+
+    #[test]
+    fn test_random_candidates_filter() {
+        let mut paper = make_note("p1", "Paper");
+        paper.note_type = NoteType::Paper(PaperMeta { bibtex_entries: vec![], canonical_key: None, sources: vec![], versions: vec![] });
+        paper.tags = vec!["PLT".to_string()];
+        let mut hidden = make_note("h1", "Hidden");
+        hidden.hidden = true;
+        let notes: HashMap<String, Note> =
+            [paper, hidden, make_note("n1", "Note")].into_iter().map(|n| (n.key.clone(), n)).collect();
+        let keys = |query: RandomQuery, include_hidden| -> Vec<String> {
+            random_candidates(&notes, &query, include_hidden).iter().map(|n| n.key.clone()).collect()
+        };
+        assert_eq!(keys(RandomQuery { note_type: None, tag: None }, false), ["n1", "p1"]);
+        assert_eq!(keys(RandomQuery { note_type: None, tag: None }, true), ["h1", "n1", "p1"]);
+        assert_eq!(keys(RandomQuery { note_type: Some("paper".into()), tag: None }, false), ["p1"]);
+        assert_eq!(keys(RandomQuery { note_type: Some("note".into()), tag: Some("plt".into()) }, false), Vec::<String>::new());
+        assert_eq!(keys(RandomQuery { note_type: None, tag: Some("plt".into()) }, false), ["p1"]);
+    }
+
+    #[test]
+    fn test_listing_sort() {
+        let config = SortConfig::default();
+        assert_eq!(listing_sort(&config, None, None), (NoteSort::Modified, SortOrder::Desc));
+        assert_eq!(listing_sort(&config, Some(NoteSort::Title), None), (NoteSort::Title, SortOrder::Asc));
+        assert_eq!(sort_param(&config, NoteSort::Modified, SortOrder::Desc), "");
+        assert_eq!(sort_param(&config, NoteSort::Title, SortOrder::Desc), "sort=title&order=desc");
+
+        let config = SortConfig { by: NoteSort::TimeTotal, order: Some(SortOrder::Asc) };
+        assert_eq!(listing_sort(&config, None, None), (NoteSort::TimeTotal, SortOrder::Asc));
+        assert_eq!(sort_param(&config, NoteSort::TimeTotal, SortOrder::Desc), "sort=time_total");
+
+        let entry = |minutes| crate::models::TimeEntry {
+            date: chrono::NaiveDate::from_ymd_opt(2026, 1, 1).unwrap(),
+            minutes,
+            category: TimeCategory::Reading,
+            description: None,
+        };
+        let mut a = make_note("a", "Alpha");
+        a.time_entries = vec![entry(30), entry(30)];
+        let b = make_note("b", "Beta");
+        let mut c = make_note("c", "Gamma");
+        c.time_entries = vec![entry(90)];
+        let mut notes = vec![&b, &a, &c];
+        sort_notes(&mut notes, NoteSort::TimeTotal, SortOrder::Desc);
+        assert_eq!(notes.iter().map(|n| n.key.as_str()).collect::<Vec<_>>(), ["c", "a", "b"]);
+        sort_notes(&mut notes, NoteSort::Title, SortOrder::Desc);
+        assert_eq!(notes.iter().map(|n| n.key.as_str()).collect::<Vec<_>>(), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_readiness_check_hides_errors() {
+        let failed = || Err("/srv/notes: Permission denied".to_string());
+        assert_eq!(readiness_check(Ok(()), false), serde_json::json!({ "ok": true }));
+        assert_eq!(readiness_check(failed(), false), serde_json::json!({ "ok": false }));
+        assert_eq!(readiness_check(failed(), true)["error"], "/srv/notes: Permission denied");
+    }
 }
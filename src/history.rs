@@ -0,0 +1,171 @@
+//! Recently viewed notes.
+//!
+//! Each logged-in view of a note is recorded in the `recently_viewed` sled
+//! tree (note key → last view time), so "that note I had open yesterday"
+//! can be found without its title. Visitors who aren't logged in aren't
+//! recorded, and the history outlives sessions, which end after a day.
+//! The index shows the last few; `/history` lists them all by day.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::auth::is_logged_in;
+use crate::models::Note;
+use crate::notes::html_escape;
+use crate::templates::base_html;
+use crate::timezone;
+use crate::AppState;
+
+const HISTORY_TREE: &str = "recently_viewed";
+
+/// Views kept; the oldest are dropped beyond this.
+const MAX_HISTORY: usize = 200;
+
+/// Notes shown under "Recently viewed" on the index.
+pub const INDEX_RECENT: usize = 5;
+
+/// Record a view of `key` now.
+pub fn record_view(db: &crate::store::Db, key: &str, now: DateTime<Utc>) -> Result<(), String> {
+    let tree = db.open_tree(HISTORY_TREE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_vec(&now).map_err(|e| e.to_string())?;
+    tree.insert(key.as_bytes(), value).map_err(|e| e.to_string())?;
+    if tree.len() > MAX_HISTORY {
+        for (key, _) in load_views(db).into_iter().skip(MAX_HISTORY) {
+            tree.remove(key.as_bytes()).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Every recorded view, most recent first.
+fn load_views(db: &crate::store::Db) -> Vec<(String, DateTime<Utc>)> {
+    let Ok(tree) = db.open_tree(HISTORY_TREE) else {
+        return Vec::new();
+    };
+    let mut views: Vec<(String, DateTime<Utc>)> = tree
+        .iter()
+        .filter_map(|item| {
+            let (key, value) = item.ok()?;
+            Some((String::from_utf8(key.to_vec()).ok()?, serde_json::from_slice(&value).ok()?))
+        })
+        .collect();
+    views.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    views
+}
+
+/// The viewed notes that still exist, most recent first, at most `limit`.
+pub fn recently_viewed<'a>(
+    db: &crate::store::Db,
+    notes: &'a HashMap<String, Note>,
+    limit: usize,
+) -> Vec<(&'a Note, DateTime<Utc>)> {
+    load_views(db)
+        .into_iter()
+        .filter_map(|(key, viewed)| Some((notes.get(&key)?, viewed)))
+        .take(limit)
+        .collect()
+}
+
+fn note_item(note: &Note, meta: &str) -> String {
+    format!(
+        r#"<li class="note-item"><span><a href="/note/{key}" class="title">{title}</a> <span class="key">[@{key}]</span></span><span class="meta">{meta}</span></li>"#,
+        key = note.key,
+        title = html_escape(&note.title),
+    )
+}
+
+/// The index's "Recently viewed" section; empty without any views.
+pub fn index_section(db: &crate::store::Db, notes: &HashMap<String, Note>) -> String {
+    let recent = recently_viewed(db, notes, INDEX_RECENT);
+    if recent.is_empty() {
+        return String::new();
+    }
+    let items: String = recent
+        .iter()
+        .map(|(note, viewed)| note_item(note, &timezone::format(*viewed, "%Y-%m-%d %H:%M")))
+        .collect();
+    format!(
+        r#"<div class="recently-viewed"><div class="hidden-toggle">Recently viewed &middot; <a href="/history">all</a></div><ul class="note-list">{}</ul></div>"#,
+        items
+    )
+}
+
+pub async fn history_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+    let notes = state.notes_map();
+    let views = recently_viewed(&state.db, &notes, MAX_HISTORY);
+    let mut html = String::from("<h1>Recently viewed</h1>");
+    if views.is_empty() {
+        html.push_str("<p>No notes viewed yet.</p>");
+    }
+    let mut day: Option<NaiveDate> = None;
+    for (note, viewed) in &views {
+        let viewed = timezone::local(*viewed);
+        if day != Some(viewed.date_naive()) {
+            if day.is_some() {
+                html.push_str("</ul>");
+            }
+            day = Some(viewed.date_naive());
+            html.push_str(&format!(
+                r#"<h3>{}</h3><ul class="note-list">"#,
+                viewed.format("%A, %Y-%m-%d")
+            ));
+        }
+        html.push_str(&note_item(note, &viewed.format("%H:%M").to_string()));
+    }
+    if day.is_some() {
+        html.push_str("</ul>");
+    }
+    Html(base_html("Recently viewed", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::path::PathBuf;
+
+    fn note(key: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: format!("Note {}", key),
+            modified: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recently_viewed() {
+        let db = crate::store::Db::temporary();
+        let notes: HashMap<String, Note> = ["a", "b"].into_iter().map(|k| (k.to_string(), note(k))).collect();
+        let now = Utc::now();
+        record_view(&db, "a", now - TimeDelta::hours(30)).unwrap();
+        record_view(&db, "gone", now - TimeDelta::hours(2)).unwrap();
+        record_view(&db, "b", now - TimeDelta::hours(1)).unwrap();
+        let keys = |limit| -> Vec<String> {
+            recently_viewed(&db, &notes, limit).iter().map(|(n, _)| n.key.clone()).collect()
+        };
+        assert_eq!(keys(10), ["b", "a"]);
+        // Viewing again moves a note to the front
+        record_view(&db, "a", now).unwrap();
+        assert_eq!(keys(10), ["a", "b"]);
+        assert_eq!(keys(1), ["a"]);
+        assert!(index_section(&db, &notes).contains(r#"<a href="/note/a" class="title">Note a</a>"#));
+
+        for i in 0..MAX_HISTORY + 5 {
+            record_view(&db, &format!("n{i}"), now + TimeDelta::seconds(i as i64)).unwrap();
+        }
+        assert_eq!(load_views(&db).len(), MAX_HISTORY);
+        assert_eq!(load_views(&db)[0].0, format!("n{}", MAX_HISTORY + 4));
+    }
+}
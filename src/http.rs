@@ -0,0 +1,205 @@
+//! Outbound HTTP clients.
+//!
+//! The server makes one pooled client at startup (`shared_client`, kept in
+//! `AppState::http`) and lookups set their own shorter timeouts per request.
+//! Every `reqwest` client is built from `client_builder`, which routes it
+//! through the `[proxy]` table's proxy, if set. Without one, reqwest's own
+//! handling of `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` and `NO_PROXY`
+//! applies. Behind a proxy the local network may not resolve outside names
+//! at all, so `proxy_for` tells URL validation when to let the proxy do it.
+//!
+//! Requests for metadata, pages and PDFs first wait their turn in a token
+//! bucket for the host (`throttle`), as `[rate_limit]` sets. The config is
+//! set once at startup, like `[llm]`.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use url::Url;
+
+use crate::config::{ProxyConfig, RateLimitConfig};
+use crate::url_validator::Cidr;
+
+static PROXY: RwLock<Option<ProxyConfig>> = RwLock::new(None);
+
+static RATE_LIMIT: RwLock<Option<RateLimitConfig>> = RwLock::new(None);
+
+/// Token buckets by host, or by the `[rate_limit] hosts` domain covering it.
+static BUCKETS: LazyLock<Mutex<HashMap<String, Bucket>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Use `proxy` and `rate_limit` from now on (set from config at startup).
+pub fn configure(proxy: &ProxyConfig, rate_limit: &RateLimitConfig) {
+    *PROXY.write().unwrap_or_else(|e| e.into_inner()) = Some(proxy.clone());
+    *RATE_LIMIT.write().unwrap_or_else(|e| e.into_inner()) = Some(rate_limit.clone());
+}
+
+fn config() -> ProxyConfig {
+    PROXY.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+/// A client builder with the configured proxy.
+pub fn client_builder() -> reqwest::ClientBuilder {
+    let config = config();
+    let builder = reqwest::Client::builder();
+    let Some(ref url) = config.url else {
+        return builder;
+    };
+    match reqwest::Proxy::all(url) {
+        Ok(proxy) => {
+            let no_proxy = reqwest::NoProxy::from_string(&config.no_proxy.join(","));
+            builder.proxy(proxy.no_proxy(no_proxy))
+        }
+        // Checked by `ProxyConfig::validate` at startup
+        Err(_) => builder,
+    }
+}
+
+/// Timeout for requests that don't set their own.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const USER_AGENT: &str = "NotesApp/1.0";
+
+/// The client shared by lookups: pooled connections, the configured proxy,
+/// `TIMEOUT` and a default user agent. Call after `configure`.
+pub fn shared_client() -> reqwest::Client {
+    client_builder()
+        .timeout(TIMEOUT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// A token bucket. Tokens go negative for requests queued behind the ones
+/// that took the last token, so each waits for its own turn.
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    /// Take a token at `now`, refilling at `rate` per second up to `burst`;
+    /// returns how long to wait before sending.
+    fn reserve(&mut self, now: Instant, rate: f64, burst: f64) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst) - 1.0;
+        self.last = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// The bucket key, rate and burst for `host`.
+fn host_limit(config: &RateLimitConfig, host: &str) -> (String, f64, f64) {
+    let host = host.to_lowercase();
+    let rule = config
+        .hosts
+        .iter()
+        .find(|(domain, _)| host == domain.as_str() || host.ends_with(&format!(".{}", domain)));
+    match rule {
+        Some((domain, &rate)) => (domain.clone(), rate, 1.0),
+        None => (host, config.per_second, config.burst as f64),
+    }
+}
+
+/// Wait until a request to `url`'s host is within `[rate_limit]`.
+pub async fn throttle(url: &str) {
+    let Some(host) = Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) else {
+        return;
+    };
+    let config = RATE_LIMIT.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default();
+    let (key, rate, burst) = host_limit(&config, &host);
+    let now = Instant::now();
+    let wait = BUCKETS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(key)
+        .or_insert(Bucket { tokens: burst, last: now })
+        .reserve(now, rate, burst);
+    if !wait.is_zero() {
+        tracing::debug!(host = %host, wait_ms = wait.as_millis() as u64, "Rate limited");
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Whether `host` is in a `NO_PROXY`-style list: `*`, a domain (matching
+/// its subdomains too, with or without a leading dot), or an IP or CIDR.
+fn no_proxy_matches(list: &[String], host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+    let ip = host.parse().ok();
+    list.iter().map(|e| e.trim().to_lowercase()).filter(|e| !e.is_empty()).any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+        if let (Some(ip), Ok(cidr)) = (ip, Cidr::parse(&entry)) {
+            return cidr.contains(&ip);
+        }
+        let domain = entry.trim_start_matches('.');
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names.iter().find_map(|n| std::env::var(n).ok()).filter(|v| !v.trim().is_empty())
+}
+
+/// The proxy a request to `url` goes through: the `[proxy]` one, else the
+/// one the environment names for its scheme, unless the host is exempt.
+pub fn proxy_for(url: &Url) -> Option<String> {
+    let host = url.host_str()?;
+    let config = config();
+    if let Some(proxy) = config.url {
+        return (!no_proxy_matches(&config.no_proxy, host)).then_some(proxy);
+    }
+    let proxy = match url.scheme() {
+        "https" => env_var(&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]),
+        _ => env_var(&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]),
+    }?;
+    let no_proxy: Vec<String> = env_var(&["NO_PROXY", "no_proxy"])
+        .map(|v| v.split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    (!no_proxy_matches(&no_proxy, host)).then_some(proxy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let config = RateLimitConfig::default();
+        assert_eq!(host_limit(&config, "export.arxiv.org"), ("arxiv.org".to_string(), 1.0 / 3.0, 1.0));
+        assert_eq!(host_limit(&config, "API.crossref.org"), ("api.crossref.org".to_string(), 2.0, 5.0));
+
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 2.0, last: start };
+        assert_eq!(bucket.reserve(start, 1.0, 2.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(start, 1.0, 2.0), Duration::ZERO);
+        // Queued: one second for the next token, two for the one after
+        assert_eq!(bucket.reserve(start, 1.0, 2.0), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(start, 1.0, 2.0), Duration::from_secs(2));
+        // Ten idle seconds refill only up to the burst
+        let later = start + Duration::from_secs(12);
+        assert_eq!(bucket.reserve(later, 1.0, 2.0), Duration::ZERO);
+        assert_eq!(bucket.reserve(later, 1.0, 2.0), Duration::ZERO);
+        assert!(!bucket.reserve(later, 1.0, 2.0).is_zero());
+    }
+
+    #[test]
+    fn test_no_proxy_matches() {
+        let list: Vec<String> = ["localhost", ".corp.example", "10.0.0.0/8", "::1"].map(String::from).to_vec();
+        assert!(no_proxy_matches(&list, "localhost"));
+        assert!(no_proxy_matches(&list, "wiki.corp.example"));
+        assert!(no_proxy_matches(&list, "corp.example"));
+        assert!(!no_proxy_matches(&list, "notcorp.example"));
+        assert!(no_proxy_matches(&list, "10.1.2.3"));
+        assert!(no_proxy_matches(&list, "[::1]"));
+        assert!(!no_proxy_matches(&list, "arxiv.org"));
+        assert!(no_proxy_matches(&["*".to_string()], "arxiv.org"));
+        assert!(!no_proxy_matches(&[], "arxiv.org"));
+    }
+}
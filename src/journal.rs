@@ -0,0 +1,248 @@
+//! Daily notes.
+//!
+//! `/today` opens `journal/YYYY-MM-DD.md`, creating it on first visit from
+//! the `daily_template` template when one exists. `/journal` lists the
+//! daily notes, newest first. A daily note is an ordinary note; only its
+//! path marks it as one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::models::Note;
+use crate::note_templates::{self, TemplateVars};
+use crate::notes::{html_escape, load_note, new_note_frontmatter, new_note_key};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Directory under the notes directory that holds the daily notes.
+pub const JOURNAL_DIR: &str = "journal";
+
+/// Entries `/journal` shows unless asked for all of them.
+const RECENT_ENTRIES: usize = 30;
+
+/// Path of the daily note for `date`, relative to the notes directory.
+pub fn daily_path(date: NaiveDate) -> PathBuf {
+    Path::new(JOURNAL_DIR).join(format!("{}.md", date.format("%Y-%m-%d")))
+}
+
+/// The date in the name of a note at `dir/YYYY-MM-DD.md`.
+pub fn dated_note(note: &Note, dir: &Path) -> Option<NaiveDate> {
+    if note.path.parent() != Some(dir) || note.path.extension()? != "md" {
+        return None;
+    }
+    NaiveDate::parse_from_str(&note.path.file_stem()?.to_string_lossy(), "%Y-%m-%d").ok()
+}
+
+/// The day a note is the daily note for, if it is one.
+pub fn daily_date(note: &Note) -> Option<NaiveDate> {
+    dated_note(note, Path::new(JOURNAL_DIR))
+}
+
+/// Daily notes, newest first.
+pub fn daily_notes(notes: &[Note]) -> Vec<(NaiveDate, &Note)> {
+    let mut days: Vec<(NaiveDate, &Note)> = notes.iter().filter_map(|n| Some((daily_date(n)?, n))).collect();
+    days.sort_by_key(|(date, _)| std::cmp::Reverse(*date));
+    days
+}
+
+/// Key of the daily note for `date`, creating the note (created `now`) if
+/// there is none. The flag is true when the note was created. `template` names the
+/// template to start from; it is skipped when no such template exists.
+pub fn open_daily(
+    notes_dir: &Path,
+    templates_dir: &Path,
+    template: &str,
+    notes: &[Note],
+    date: NaiveDate,
+    now: DateTime<FixedOffset>,
+) -> Result<(String, bool), String> {
+    let relative = daily_path(date);
+    if let Some(note) = notes.iter().find(|n| n.path == relative) {
+        return Ok((note.key.clone(), false));
+    }
+
+    let file_path = notes_dir.join(&relative);
+    if file_path.exists() {
+        let note = load_note(&file_path, &notes_dir.to_path_buf())
+            .ok_or_else(|| format!("Cannot read {}", relative.display()))?;
+        return Ok((note.key, false));
+    }
+
+    let template = match template.trim() {
+        "" => None,
+        name if note_templates::list_templates(templates_dir).iter().any(|t| t == name) => {
+            Some(note_templates::load_template(templates_dir, name)?)
+        }
+        _ => None,
+    };
+
+    let day = date.format("%Y-%m-%d").to_string();
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let frontmatter = new_note_frontmatter(&day, &key, now) + &format!("date: {}\n", day);
+    let content = match template {
+        Some(ref template) => {
+            let vars = TemplateVars {
+                title: &day,
+                date: &day,
+                bib_key: "",
+            };
+            note_templates::instantiate(&frontmatter, template, &vars)
+        }
+        None => frontmatter + "---\n\n",
+    };
+
+    fs::create_dir_all(notes_dir.join(JOURNAL_DIR))
+        .map_err(|e| format!("Failed to create {}: {}", JOURNAL_DIR, e))?;
+    crate::encryption::write_file(&file_path, &content)?;
+    Ok((key, true))
+}
+
+/// First line of a note's body worth showing in a list.
+fn preview(note: &Note) -> String {
+    let line = note
+        .raw_content
+        .lines()
+        .map(|l| l.trim().trim_start_matches('#').trim())
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    match line.char_indices().nth(80) {
+        Some((i, _)) => format!("{}…", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /today`: open today's daily note, creating it when logged in.
+pub async fn today(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    let notes = state.load_notes();
+    let now = crate::timezone::now();
+    let date = now.date_naive();
+
+    if !is_logged_in(&jar, &state.db) || state.config.read_only {
+        let relative = daily_path(date);
+        return match notes.iter().find(|n| n.path == relative && !n.hidden) {
+            Some(note) => Redirect::to(&format!("/note/{}", note.key)).into_response(),
+            None => Redirect::to("/journal").into_response(),
+        };
+    }
+
+    let config = &state.config;
+    match open_daily(&state.notes_dir, &config.templates_dir, &config.daily_template, &notes, date, now) {
+        Ok((key, true)) => {
+            state.invalidate_notes_cache();
+            state.reindex_graph_note(&key);
+            Redirect::to(&format!("/note/{}?edit=true", key)).into_response()
+        }
+        Ok((key, false)) => Redirect::to(&format!("/note/{}", key)).into_response(),
+        Err(e) => {
+            let html = format!(r#"<div class="message error">{}</div>"#, html_escape(&e));
+            Html(base_html("Error", &html, None, true)).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalQuery {
+    pub all: Option<String>,
+}
+
+/// `GET /journal`: the daily notes, newest first.
+pub async fn journal_page(
+    Query(query): Query<JournalQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Html<String> {
+    let logged_in = is_logged_in(&jar, &state.db);
+    let notes = state.load_notes();
+    let days: Vec<(NaiveDate, &Note)> = daily_notes(&notes)
+        .into_iter()
+        .filter(|(_, n)| logged_in || !n.hidden)
+        .collect();
+    let show_all = query.all.as_deref() == Some("true");
+    let shown = if show_all { days.len() } else { days.len().min(RECENT_ENTRIES) };
+
+    let mut html = String::from("<h1>Journal</h1>");
+    if logged_in && !state.config.read_only {
+        html.push_str(r#"<p class="time-breadcrumb"><a href="/today">Open today's note</a></p>"#);
+    }
+    if days.is_empty() {
+        html.push_str("<p>No daily notes yet.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>Date</th><th>Day</th><th></th></tr>"#);
+        for (date, note) in &days[..shown] {
+            html.push_str(&format!(
+                r#"<tr><td><a href="/note/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+                note.key,
+                date.format("%Y-%m-%d"),
+                date.format("%A"),
+                html_escape(&preview(note)),
+            ));
+        }
+        html.push_str("</table>");
+        if shown < days.len() {
+            html.push_str(&format!(
+                r#"<p><a href="/journal?all=true">Show all {} entries</a></p>"#,
+                days.len()
+            ));
+        }
+    }
+
+    Html(base_html("Journal", &html, None, logged_in))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    #[test]
+    fn test_open_daily_creates_once() {
+        let dir = TempDir::new("journal");
+        let notes_dir = dir.join("content");
+        let templates_dir = dir.join("templates");
+        fs::create_dir_all(notes_dir.join(JOURNAL_DIR)).unwrap();
+        fs::create_dir_all(&templates_dir).unwrap();
+        fs::write(templates_dir.join("daily.md"), "---\ntags: [journal]\n---\n\n# {{date}}\n\n## Done\n").unwrap();
+        fs::write(notes_dir.join("journal/2026-10-15.md"), "---\ntitle: Thursday\n---\n\nShipped it.\n").unwrap();
+        fs::write(notes_dir.join("journal/ideas.md"), "---\ntitle: Ideas\n---\n").unwrap();
+
+        let date = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let now = DateTime::parse_from_rfc3339("2026-10-17T07:45:00+01:00").unwrap();
+        let notes = load_all_notes(&notes_dir);
+        let (key, created) = open_daily(&notes_dir, &templates_dir, "daily", &notes, date, now).unwrap();
+        assert!(created);
+        let text = fs::read_to_string(notes_dir.join("journal/2026-10-17.md")).unwrap();
+        assert_eq!(
+            text,
+            format!("---\ntitle: 2026-10-17\nid: {}\ncreated: 2026-10-17T07:45:00+01:00\ndate: 2026-10-17\ntags: [journal]\n---\n\n# 2026-10-17\n\n## Done\n", key)
+        );
+
+        let notes = load_all_notes(&notes_dir);
+        assert_eq!(open_daily(&notes_dir, &templates_dir, "daily", &notes, date, now).unwrap(), (key.clone(), false));
+
+        let days: Vec<NaiveDate> = daily_notes(&notes).into_iter().map(|(d, _)| d).collect();
+        assert_eq!(days, vec![date, NaiveDate::from_ymd_opt(2026, 10, 15).unwrap()]);
+        let thursday = notes.iter().find(|n| n.title == "Thursday").unwrap();
+        assert_eq!(preview(thursday), "Shipped it.");
+
+        // No such template: a bare note
+        let next = date.succ_opt().unwrap();
+        open_daily(&notes_dir, &templates_dir, "missing", &notes, next, now).unwrap();
+        let text = fs::read_to_string(notes_dir.join("journal/2026-10-18.md")).unwrap();
+        assert!(text.ends_with("date: 2026-10-18\n---\n\n"));
+    }
+}
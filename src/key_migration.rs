@@ -0,0 +1,196 @@
+//! Longer note keys and redirects from the old ones.
+//!
+//! Path-derived keys are three bytes of a SHA-256, so a few thousand notes
+//! make a collision plausible. `load_all_notes` gives colliding notes their
+//! `long_key`; `notes migrate-keys` pins every path-derived note to its long
+//! key as an `id:` field, rewrites references, and records old key → new key
+//! in the `key_redirects` sled tree. `/note/{old}` then redirects, and a
+//! short key that is a prefix of several long keys lists them.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use axum::{
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+
+use crate::formats::check_writable;
+use crate::models::Note;
+use crate::notes::{generate_key, html_escape, long_key, parse_frontmatter, rewrite_note_keys, set_frontmatter_field};
+use crate::templates::base_html;
+
+const REDIRECTS_TREE: &str = "key_redirects";
+
+/// A note given a pinned long key.
+#[derive(Debug, Clone)]
+pub struct PinnedNote {
+    pub path: PathBuf,
+    pub old_key: String,
+    pub new_key: String,
+}
+
+/// Everything `notes migrate-keys` changes. Paths are relative to the notes
+/// directory.
+#[derive(Debug, Clone, Default)]
+pub struct KeyMigration {
+    pub pinned: Vec<PinnedNote>,
+    /// Notes whose content changes, as (path, new content).
+    pub rewrites: Vec<(PathBuf, String)>,
+    /// Number of crosslinks and parent fields rewritten.
+    pub references: usize,
+    /// Short keys shared by several notes; references to them are left alone.
+    pub ambiguous: Vec<String>,
+}
+
+impl KeyMigration {
+    /// Short key → long key for every note whose short key was its own.
+    pub fn renames(&self) -> HashMap<String, String> {
+        self.pinned
+            .iter()
+            .filter(|p| !self.ambiguous.contains(&p.old_key))
+            .map(|p| (p.old_key.clone(), p.new_key.clone()))
+            .collect()
+    }
+}
+
+/// Plan pinning every writable note without an `id:` to its long key.
+pub fn plan_migration(notes: &[Note]) -> KeyMigration {
+    let mut short_keys: HashMap<String, usize> = HashMap::new();
+    for note in notes {
+        let short = match parse_frontmatter(&note.full_file_content).0.id {
+            Some(id) => id,
+            None => generate_key(&note.path),
+        };
+        *short_keys.entry(short).or_default() += 1;
+    }
+
+    let mut plan = KeyMigration::default();
+    let mut pinned_contents: HashMap<&Path, String> = HashMap::new();
+    for note in notes {
+        if check_writable(&note.path).is_err() || parse_frontmatter(&note.full_file_content).0.id.is_some() {
+            continue;
+        }
+        let new_key = long_key(&note.path);
+        match set_frontmatter_field(&note.full_file_content, "id", Some(&new_key)) {
+            Ok(content) => pinned_contents.insert(note.path.as_path(), content),
+            Err(e) => {
+                tracing::warn!(path = %note.path.display(), error = %e, "Cannot pin note key");
+                continue;
+            }
+        };
+        let old_key = generate_key(&note.path);
+        if short_keys[&old_key] > 1 && !plan.ambiguous.contains(&old_key) {
+            plan.ambiguous.push(old_key.clone());
+        }
+        plan.pinned.push(PinnedNote { path: note.path.clone(), old_key, new_key });
+    }
+    plan.ambiguous.sort();
+
+    let renames = plan.renames();
+    for note in notes.iter().filter(|n| check_writable(&n.path).is_ok()) {
+        let content = pinned_contents.get(note.path.as_path()).unwrap_or(&note.full_file_content);
+        let (content, count) = rewrite_note_keys(content, &renames);
+        if content != note.full_file_content {
+            plan.references += count;
+            plan.rewrites.push((note.path.clone(), content));
+        }
+    }
+    plan
+}
+
+/// Write the planned contents.
+pub fn apply_migration(notes_dir: &Path, plan: &KeyMigration) -> Result<(), String> {
+    for (path, content) in &plan.rewrites {
+        crate::encryption::write_file(&notes_dir.join(path), content).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Remember where old keys went.
+pub fn save_redirects(db: &crate::store::Db, renames: &HashMap<String, String>) -> Result<(), String> {
+    let tree = db.open_tree(REDIRECTS_TREE).map_err(|e| e.to_string())?;
+    for (old, new) in renames {
+        tree.insert(old.as_bytes(), new.as_bytes()).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn load_redirect(db: &crate::store::Db, key: &str) -> Option<String> {
+    let tree = db.open_tree(REDIRECTS_TREE).ok()?;
+    let value = tree.get(key.as_bytes()).ok()??;
+    Some(String::from_utf8_lossy(&value).to_string())
+}
+
+/// Notes an unknown key may mean: its redirect target, else the notes whose
+/// path-derived long key it is a short form of.
+pub fn resolve_old_key<'a>(db: &crate::store::Db, notes: &'a HashMap<String, Note>, key: &str) -> Vec<&'a Note> {
+    if let Some(note) = load_redirect(db, key).and_then(|new| notes.get(&new)) {
+        return vec![note];
+    }
+    let mut matches: Vec<&Note> = notes
+        .values()
+        .filter(|n| generate_key(&n.path) == key && n.key == long_key(&n.path))
+        .collect();
+    matches.sort_by(|a, b| a.path.cmp(&b.path));
+    matches
+}
+
+/// Response for `/note/{key}` when no note has `key`.
+pub fn old_key_response(db: &crate::store::Db, notes: &HashMap<String, Note>, key: &str, logged_in: bool) -> Response {
+    match resolve_old_key(db, notes, key).as_slice() {
+        [] => (StatusCode::NOT_FOUND, "Note not found").into_response(),
+        [note] => Redirect::permanent(&format!("/note/{}", note.key)).into_response(),
+        several => {
+            let mut html = format!(
+                "<h1>Ambiguous key</h1><p>Several notes had the key <code>{}</code>:</p><ul>",
+                html_escape(key)
+            );
+            for note in several {
+                html.push_str(&format!(
+                    r#"<li><a href="/note/{}">{}</a> <small>{}</small></li>"#,
+                    note.key,
+                    html_escape(&note.title),
+                    html_escape(&note.path.display().to_string())
+                ));
+            }
+            html.push_str("</ul>");
+            (StatusCode::MULTIPLE_CHOICES, Html(base_html("Ambiguous key", &html, None, logged_in))).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::note_from_content;
+    use chrono::Utc;
+
+    #[test]
+    fn test_key_migration() {
+        let a_key = generate_key(Path::new("a.md"));
+        let notes = vec![
+            note_from_content("a.md".into(), "---\ntitle: A\n---\n".to_string(), Utc::now()),
+            note_from_content("b.md".into(), format!("---\ntitle: B\nparent: {}\n---\nSee [@{}].\n", a_key, a_key), Utc::now()),
+            note_from_content("c.md".into(), "---\ntitle: C\nid: c\n---\n".to_string(), Utc::now()),
+            note_from_content("d.org".into(), "#+TITLE: D\n".to_string(), Utc::now()),
+        ];
+        let plan = plan_migration(&notes);
+        assert_eq!(plan.pinned.len(), 2);
+        assert!(plan.ambiguous.is_empty());
+        assert_eq!(plan.references, 2);
+        let long = long_key(Path::new("a.md"));
+        let b = &plan.rewrites.iter().find(|(p, _)| p == Path::new("b.md")).unwrap().1;
+        assert!(b.contains(&format!("parent: {}", long)) && b.contains(&format!("[@{}]", long)));
+        let a = &plan.rewrites.iter().find(|(p, _)| p == Path::new("a.md")).unwrap().1;
+        assert_eq!(parse_frontmatter(a).0.id.as_deref(), Some(long.as_str()));
+
+        let db = crate::store::Db::temporary();
+        save_redirects(&db, &plan.renames()).unwrap();
+        let mut migrated = note_from_content("a.md".into(), a.clone(), Utc::now());
+        migrated.key = long.clone();
+        let map: HashMap<String, Note> = [(long.clone(), migrated)].into_iter().collect();
+        assert_eq!(resolve_old_key(&db, &map, &a_key)[0].key, long);
+        assert!(resolve_old_key(&db, &map, "ffffff").is_empty());
+    }
+}
@@ -3,27 +3,95 @@
 //! This module provides public access to all the application's modules
 //! for testing purposes and potential library use.
 
-use sled::Db;
+use store::Db;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use tokio::sync::RwLock as TokioRwLock;
+use tokio::sync::{Mutex as TokioMutex, RwLock as TokioRwLock};
+use tokio_util::task::TaskTracker;
 
+pub mod admin;
+pub mod agenda;
+pub mod archive;
+pub mod ask;
 pub mod auth;
+pub mod authors;
+pub mod backup;
+pub mod board;
+pub mod browse;
+pub mod bulk;
 pub mod citations;
+pub mod cli;
+pub mod config;
+pub mod corpus;
+pub mod course;
+pub mod dedupe;
+pub mod digest;
+pub mod doctor;
+pub mod embeddings;
+pub mod encryption;
+pub mod export;
+pub mod feed;
+pub mod formats;
 pub mod graph;
 pub mod graph_index;
 pub mod graph_query;
+pub mod habits;
+pub mod history;
 pub mod handlers;
+pub mod http;
+pub mod journal;
+pub mod key_migration;
+pub mod link_preview;
+pub mod links;
+pub mod llm;
+pub mod metadata;
+pub mod mirror;
 pub mod models;
+pub mod note_move;
+pub mod note_templates;
 pub mod notes;
+pub mod outline_import;
+pub mod page_cache;
+pub mod print;
+pub mod proxy;
+pub mod pwa;
+pub mod queue;
+pub mod reading_list;
+pub mod recurring;
+pub mod related;
+pub mod reminders;
+pub mod review;
 pub mod shared;
+pub mod sitemap;
 pub mod smart_add;
+pub mod stats;
+pub mod store;
+pub mod storage;
+pub mod summarize;
+pub mod sync;
+pub mod tag_suggest;
+pub mod tasks;
+pub mod telemetry;
 pub mod templates;
+#[cfg(test)]
+mod test_util;
+pub mod theme;
+pub mod time_categories;
+pub mod time_import;
+pub mod timezone;
+pub mod tls;
+pub mod transcribe;
+pub mod trash;
 pub mod url_validator;
+pub mod validate;
+pub mod vaults;
+pub mod wayback;
+pub mod webdav;
 
 // ============================================================================
 // Configuration
@@ -32,6 +100,9 @@ pub mod url_validator;
 pub const NOTES_DIR: &str = "content";
 pub const PDFS_DIR: &str = "pdfs";
 pub const DB_PATH: &str = ".notes_db";
+pub const TEMPLATES_DIR: &str = "templates";
+pub const TIME_CATEGORIES_PATH: &str = "time_categories.conf";
+pub const TLS_DIR: &str = ".notes_tls";
 
 // ============================================================================
 // Rate Limiting
@@ -43,6 +114,12 @@ pub struct LoginRateLimit {
     pub locked_until: Option<DateTime<Utc>>,
 }
 
+impl Default for LoginRateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LoginRateLimit {
     pub fn new() -> Self {
         Self {
@@ -85,22 +162,44 @@ impl LoginRateLimit {
 pub struct AppState {
     pub notes_dir: PathBuf,
     pub pdfs_dir: PathBuf,
+    /// Where PDFs and audio memos are kept: `pdfs_dir` or object storage.
+    pub storage: storage::Storage,
     pub db: Db,
     pub password_hash: Option<String>,
     pub login_rate_limit: Arc<Mutex<LoginRateLimit>>,
     pub notes_cache: Arc<RwLock<Option<Vec<models::Note>>>>,
+    /// TF-IDF index for related notes, built on first use after the notes
+    /// cache is invalidated.
+    pub related_cache: Arc<RwLock<Option<Arc<related::RelatedIndex>>>>,
     pub shared_rooms: Arc<TokioRwLock<HashMap<String, shared::SharedRoom>>>,
+    pub time_categories: Arc<Vec<models::TimeCategoryDef>>,
+    pub config: Arc<config::Config>,
+    /// Outbound HTTP client for lookups (`http::shared_client`); cloning it
+    /// shares the connection pool.
+    pub http: reqwest::Client,
+    /// Detached blocking work (git commits) that shutdown waits for.
+    pub background: TaskTracker,
+    /// Held while this vault syncs with its peer (`sync::sync`) or applies
+    /// the peer's changes, so one sync runs at a time.
+    pub syncing: Arc<TokioMutex<()>>,
 }
 
 impl AppState {
-    pub fn new() -> Self {
-        let notes_dir = PathBuf::from(NOTES_DIR);
+    pub fn new(config: config::Config) -> Self {
+        if let Some(ref dir) = config.data_dir {
+            fs::create_dir_all(dir).ok();
+        }
+
+        let notes_dir = config.notes_dir.clone();
         fs::create_dir_all(&notes_dir).ok();
 
-        let pdfs_dir = PathBuf::from(PDFS_DIR);
-        fs::create_dir_all(&pdfs_dir).ok();
+        let pdfs_dir = config.pdfs_dir.clone();
+        let storage = storage::Storage::from_config(&config.storage, &pdfs_dir).expect("Failed to set up storage");
+        if storage.local_dir().is_some() {
+            fs::create_dir_all(&pdfs_dir).ok();
+        }
 
-        let db = sled::open(DB_PATH).expect("Failed to open database");
+        let db = store::open(config.db_backend, &config.db_path).expect("Failed to open database");
 
         // Purge expired sessions/CSRF tokens from previous runs
         auth::purge_expired_sessions(&db);
@@ -111,26 +210,41 @@ impl AppState {
         let state = Self {
             notes_dir,
             pdfs_dir,
+            storage,
             db,
             password_hash,
             login_rate_limit: Arc::new(Mutex::new(LoginRateLimit::new())),
             notes_cache: Arc::new(RwLock::new(None)),
+            related_cache: Arc::new(RwLock::new(None)),
             shared_rooms: Arc::new(TokioRwLock::new(HashMap::new())),
+            time_categories: Arc::new(time_categories::resolve_time_categories(
+                &config.time_categories,
+                &config.time_categories_path(),
+            )),
+            config: Arc::new(config),
+            http: http::shared_client(),
+            background: TaskTracker::new(),
+            syncing: Arc::new(TokioMutex::new(())),
         };
 
         // Reconcile knowledge graph index with notes on disk
         let notes = state.load_notes();
         match graph_index::reconcile(&state.db, &notes) {
             Ok(stats) => {
-                eprintln!(
-                    "Graph index: reindexed {}, removed {}, unchanged {}",
-                    stats.reindexed, stats.removed, stats.unchanged
+                tracing::info!(
+                    reindexed = stats.reindexed,
+                    removed = stats.removed,
+                    unchanged = stats.unchanged,
+                    "Graph index reconciled"
                 );
             }
             Err(e) => {
-                eprintln!("Graph index reconciliation error: {}", e);
+                tracing::error!(error = %e, "Graph index reconciliation failed");
             }
         }
+        if let Err(e) = habits::seed_word_counts(&state.db, &notes) {
+            tracing::warn!(error = %e, "Seeding word counts failed");
+        }
 
         state
     }
@@ -144,7 +258,9 @@ impl AppState {
             }
         }
         // Slow path: load from disk and populate cache
+        let started = std::time::Instant::now();
         let notes = notes::load_all_notes(&self.notes_dir);
+        telemetry::record_corpus_load(notes.len(), started.elapsed());
         {
             let mut cache = self.notes_cache.write().unwrap();
             *cache = Some(notes.clone());
@@ -155,6 +271,16 @@ impl AppState {
     pub fn invalidate_notes_cache(&self) {
         let mut cache = self.notes_cache.write().unwrap();
         *cache = None;
+        *self.related_cache.write().unwrap() = None;
+    }
+
+    pub fn related_index(&self) -> Arc<related::RelatedIndex> {
+        if let Some(ref index) = *self.related_cache.read().unwrap() {
+            return index.clone();
+        }
+        let index = Arc::new(related::RelatedIndex::build(&self.load_notes()));
+        *self.related_cache.write().unwrap() = Some(index.clone());
+        index
     }
 
     pub fn notes_map(&self) -> HashMap<String, models::Note> {
@@ -164,14 +290,18 @@ impl AppState {
             .collect()
     }
 
-    /// Reindex a single note in the knowledge graph after mutation.
+    /// Reindex a single note in the knowledge graph after mutation, and
+    /// record the edit for `/habits`.
     pub fn reindex_graph_note(&self, key: &str) {
         let notes = self.load_notes();
         let all_keys: std::collections::HashSet<String> =
             notes.iter().map(|n| n.key.clone()).collect();
         if let Some(note) = notes.iter().find(|n| n.key == key) {
             if let Err(e) = graph_index::reindex_note(&self.db, note, &all_keys) {
-                eprintln!("Graph reindex error for {}: {}", key, e);
+                tracing::error!(key, error = %e, "Graph reindex failed");
+            }
+            if let Err(e) = habits::record_edit(&self.db, note, crate::timezone::today()) {
+                tracing::warn!(key, error = %e, "Recording edit failed");
             }
         }
     }
@@ -179,14 +309,43 @@ impl AppState {
     /// Remove a note from the knowledge graph index.
     pub fn remove_graph_note(&self, key: &str) {
         if let Err(e) = graph_index::remove_note(&self.db, key) {
-            eprintln!("Graph remove error for {}: {}", key, e);
+            tracing::error!(key, error = %e, "Graph remove failed");
+        }
+    }
+
+    /// Run blocking work (typically a git commit) after the response is
+    /// sent, tracked so that shutdown can wait for it.
+    pub fn spawn_background<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.background.spawn_blocking(f);
+    }
+
+    /// Wait up to `timeout` for background work to finish, then flush the database.
+    /// Called once the server has stopped accepting requests.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.background.close();
+        if !self.background.is_empty() {
+            tracing::info!(pending = self.background.len(), "Waiting for background tasks");
+        }
+        if tokio::time::timeout(timeout, self.background.wait()).await.is_err() {
+            tracing::warn!(
+                pending = self.background.len(),
+                "Timed out waiting for background tasks"
+            );
+        }
+        let db = self.db.clone();
+        match tokio::task::spawn_blocking(move || db.flush()).await.unwrap_or_else(|e| Err(store::Error::from(e))) {
+            Ok(()) => tracing::info!("Database flushed"),
+            Err(e) => tracing::error!(error = %e, "Database flush failed"),
         }
     }
 }
 
 impl Default for AppState {
     fn default() -> Self {
-        Self::new()
+        Self::new(config::Config::default())
     }
 }
 
@@ -222,17 +381,20 @@ pub fn validate_path_within(base: &PathBuf, target: &PathBuf) -> Result<PathBuf,
 
 // Re-export commonly used types
 pub use models::{
-    AttachSourceRequest, CitationMatch, CitationScanAllResult, CitationScanRequest,
-    CitationScanResult, CitationWriteRequest, ExternalResult, ExtractedReference, GitCommit,
+    AttachSourceRequest, BibFilter, CitationMatch, CitationScanAllResult, CitationScanRequest,
+    CitationScanResult, CitationWriteRequest, CorpusDump, CorpusImportRequest, CorpusImportResult,
+    CorpusImportSkipped, CorpusNote, CorpusTimeEntry, ExternalResult, ExtractedReference, GitCommit,
     GraphEdge, GraphNode, GraphQuery, GraphStats, InputType, KnowledgeGraph, LocalMatch, Note,
-    NoteType, PaperMeta, PaperSource, SearchMatch, SearchResult, SmartAddCreateRequest,
-    SmartAddRequest, SmartAddResult, TimeCategory, TimeEntry,
+    NoteSort, NoteType, PaperMeta, PaperSource, SearchMatch, SearchResult, SmartAddCreateRequest,
+    SmartAddRequest, SmartAddResult, SortOrder, TimeCategory, TimeCategoryDef, TimeEntry, TimeImportMatch,
+    TimeImportRequest, TimeImportResult, TimeImportUnmatched, OutlineImportPage,
+    OutlineImportRequest, OutlineImportResult, OutlineImportSkipped, Task,
 };
 
 pub use notes::{
-    extract_references, generate_bibliography, generate_key, get_file_at_commit, get_git_history,
-    html_escape, load_all_notes, load_note, normalize_bibtex, normalize_title, parse_bibtex,
-    parse_frontmatter, process_crosslinks, render_markdown, search_notes, split_bib_file,
+    ancestor_chain, extract_references, filter_bibliography, generate_bibliography, generate_key, get_file_at_commit, get_git_history, rewrite_note_keys,
+    html_escape, load_all_notes, load_note, new_note_key, normalize_bibtex, normalize_title, parse_bibtex,
+    parse_frontmatter, process_crosslinks, render_markdown, search_notes, sort_notes, split_bib_file,
     Frontmatter, ParsedBibtex,
 };
 
@@ -249,11 +411,18 @@ pub use graph_query::query_graph;
 pub use graph_index::{reconcile, reindex_note, remove_note, load_all_edges, load_all_nodes};
 
 pub use smart_add::{
-    bib_import_analyze, bib_import_execute, detect_input_type, extract_arxiv_id, extract_doi,
-    fetch_and_extract_metadata, generate_bib_key, generate_suggested_filename, query_arxiv_api,
-    query_claude_for_url, query_crossref_api, query_crossref_by_title, search_local_for_match,
+    bib_import_analyze, bib_import_execute, create_paper_note, detect_input_type, extract_arxiv_id,
+    extract_doi, fetch_and_extract_metadata, generate_bib_key, generate_suggested_filename,
+    lookup_external, query_arxiv_api, query_claude_for_url, query_crossref_api, query_crossref_by_title, search_local_for_match,
 };
 
 pub use templates::{base_html, nav_bar, render_editor, render_viewer, smart_add_html, STYLE};
 
+pub use config::{Config, ConfigArgs, DbBackend, LogConfig, LogFormat, TlsConfig};
+
+pub use time_categories::{
+    category_css, default_time_categories, load_time_categories, parse_time_categories,
+    resolve_time_categories,
+};
+
 pub use url_validator::{validate_api_url, validate_url, UrlValidationError};
@@ -0,0 +1,263 @@
+//! Link preview cards.
+//!
+//! A URL standing alone in a paragraph of a note body (bare, or as an
+//! `<autolink>`) is rendered as a small card with the page's title, site
+//! name and favicon, read from its meta tags with `extract_meta_content`.
+//! Previews are kept in sled; rendering only ever uses what is there, and a
+//! logged-in view fetches missing or stale ones in the background, so the
+//! first view shows the plain link. `previews: false` in a note's
+//! frontmatter turns cards off for that note.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use futures_util::StreamExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::models::Note;
+use crate::notes::{frontmatter_field, html_escape};
+use crate::page_cache;
+use crate::smart_add::{extract_html_title, extract_meta_content};
+use crate::tasks::body_lines;
+use crate::url_validator::UrlPolicy;
+
+const PREVIEW_TREE: &str = "link_previews";
+
+/// Previews older than this are fetched again.
+const REFRESH_DAYS: i64 = 30;
+
+/// Pages that had no usable title are tried again after this long.
+const RETRY_DAYS: i64 = 3;
+
+const FETCH_CONCURRENCY: usize = 2;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// URLs being fetched, so views of the same note don't fetch twice.
+static FETCHING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// A paragraph of rendered markdown that is one link and nothing else.
+static BARE_LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<p>(?:<a [^>]*>)?(https?://[^<\s"]+)(?:</a>)?</p>"#).unwrap());
+
+/// What a card shows. `title` is None for a page that couldn't be fetched
+/// or had no title; no card is shown for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preview {
+    pub title: Option<String>,
+    pub site: String,
+    pub favicon: Option<String>,
+    pub fetched: DateTime<Utc>,
+}
+
+impl Preview {
+    fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        let days = if self.title.is_some() { REFRESH_DAYS } else { RETRY_DAYS };
+        self.fetched < now - TimeDelta::days(days)
+    }
+}
+
+/// The host of `url` without `www.`.
+fn site_name(url: &url::Url) -> String {
+    url.host_str().unwrap_or_default().trim_start_matches("www.").to_string()
+}
+
+/// The `<link rel="icon">` of a page, resolved against `base`; else the
+/// site's `/favicon.ico`.
+fn favicon(html: &str, base: &url::Url) -> Option<String> {
+    static ICON_RE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(?i)<link[^>]*rel=["'](?:shortcut )?icon["'][^>]*>"#).unwrap()
+    });
+    static HREF_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?i)href=["']([^"']+)["']"#).unwrap());
+    let declared = ICON_RE
+        .find(html)
+        .and_then(|tag| HREF_RE.captures(tag.as_str()))
+        .and_then(|caps| base.join(&caps[1]).ok());
+    let icon = declared.or_else(|| base.join("/favicon.ico").ok())?;
+    matches!(icon.scheme(), "http" | "https").then(|| icon.to_string())
+}
+
+/// Build a preview from a fetched page. `final_url` is where redirects
+/// ended up; `html` is None when there was no text body.
+pub fn preview_from_html(final_url: &str, html: Option<&str>, fetched: DateTime<Utc>) -> Preview {
+    let base = url::Url::parse(final_url).ok();
+    let title = html.and_then(|h| extract_meta_content(h, &["og:title", "twitter:title"]).or_else(|| extract_html_title(h)));
+    let site = html
+        .and_then(|h| extract_meta_content(h, &["og:site_name"]))
+        .or_else(|| base.as_ref().map(site_name))
+        .unwrap_or_default();
+    let favicon = base.as_ref().and_then(|b| favicon(html.unwrap_or_default(), b));
+    Preview { title, site, favicon, fetched }
+}
+
+pub fn load_preview(db: &crate::store::Db, url: &str) -> Option<Preview> {
+    let value = db.open_tree(PREVIEW_TREE).ok()?.get(url.as_bytes()).ok()??;
+    serde_json::from_slice(&value).ok()
+}
+
+fn save_preview(db: &crate::store::Db, url: &str, preview: &Preview) -> Result<(), String> {
+    let tree = db.open_tree(PREVIEW_TREE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_vec(preview).map_err(|e| e.to_string())?;
+    tree.insert(url.as_bytes(), value).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Whether the note shows cards (`previews: false` turns them off).
+pub fn enabled(note: &Note) -> bool {
+    frontmatter_field(&note.full_file_content, "previews").is_none_or(|v| !v.eq_ignore_ascii_case("false"))
+}
+
+/// URLs on a line of their own in the note body, outside fenced code: the
+/// candidates for a card.
+pub fn bare_urls(note: &Note) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for (_, line) in body_lines(&note.full_file_content) {
+        let line = line.trim();
+        let url = line.strip_prefix('<').and_then(|l| l.strip_suffix('>')).unwrap_or(line);
+        let is_url = (url.starts_with("http://") || url.starts_with("https://"))
+            && !url.contains(char::is_whitespace)
+            && url::Url::parse(url).is_ok_and(|u| u.host_str().is_some());
+        if is_url && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+    }
+    urls
+}
+
+async fn fetch_preview(db: &crate::store::Db, policy: &UrlPolicy, url: &str) -> Preview {
+    let page = page_cache::fetch(Some(db), policy, "link_preview", url, FETCH_TIMEOUT, |r| {
+        r.header(reqwest::header::USER_AGENT, "Mozilla/5.0 (compatible; NotesApp/1.0)")
+    })
+    .await;
+    match page {
+        Ok(page) => preview_from_html(&page.final_url, page.body.as_deref(), Utc::now()),
+        Err(e) => {
+            tracing::debug!(url = %url, error = %e, "Link preview fetch failed");
+            preview_from_html(url, None, Utc::now())
+        }
+    }
+}
+
+/// The cached previews of the note's bare URLs. With `fetch`, missing and
+/// stale ones are fetched in the background for a later view.
+pub fn note_previews(db: &crate::store::Db, note: &Note, fetch: bool) -> HashMap<String, Preview> {
+    if !enabled(note) {
+        return HashMap::new();
+    }
+    let now = Utc::now();
+    let mut previews = HashMap::new();
+    let mut missing = Vec::new();
+    for url in bare_urls(note) {
+        let preview = load_preview(db, &url);
+        if preview.as_ref().is_none_or(|p| p.is_stale(now)) {
+            missing.push(url.clone());
+        }
+        if let Some(preview) = preview {
+            previews.insert(url, preview);
+        }
+    }
+    if fetch {
+        let mut fetching = FETCHING.lock().unwrap_or_else(|e| e.into_inner());
+        missing.retain(|url| fetching.insert(url.clone()));
+    } else {
+        missing.clear();
+    }
+    if !missing.is_empty() {
+        let db = db.clone();
+        tokio::spawn(async move {
+            let policy = UrlPolicy::link_check();
+            futures_util::stream::iter(missing)
+                .for_each_concurrent(FETCH_CONCURRENCY, |url| {
+                    let (db, policy) = (db.clone(), policy.clone());
+                    async move {
+                        let preview = fetch_preview(&db, &policy, &url).await;
+                        if let Err(e) = save_preview(&db, &url, &preview) {
+                            tracing::warn!(url = %url, error = %e, "Failed to save link preview");
+                        }
+                        FETCHING.lock().unwrap_or_else(|e| e.into_inner()).remove(&url);
+                    }
+                })
+                .await;
+        });
+    }
+    previews
+}
+
+fn card_html(url: &str, preview: &Preview, title: &str) -> String {
+    let icon = preview
+        .favicon
+        .as_deref()
+        .map(|f| {
+            format!(
+                r#"<img class="link-card-icon" src="{}" alt="" width="16" height="16" loading="lazy" referrerpolicy="no-referrer">"#,
+                html_escape(f)
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        r#"<p><a class="link-card" href="{}" rel="noopener noreferrer" target="_blank">{}<span class="link-card-text"><span class="link-card-title">{}</span><span class="link-card-site">{}</span></span></a></p>"#,
+        html_escape(url),
+        icon,
+        html_escape(title),
+        html_escape(&preview.site),
+    )
+}
+
+/// Replace each paragraph of rendered HTML that is just a link with a card,
+/// when there is a titled preview for it.
+pub fn add_cards(html: &str, previews: &HashMap<String, Preview>) -> String {
+    if previews.is_empty() {
+        return html.to_string();
+    }
+    BARE_LINK_RE
+        .replace_all(html, |caps: &regex::Captures| {
+            let url = caps[1].replace("&amp;", "&");
+            match previews.get(&url).and_then(|p| Some((p, p.title.as_deref()?))) {
+                Some((preview, title)) => card_html(&url, preview, title),
+                None => caps[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notes::render_markdown;
+
+    #[test]
+    fn test_preview_cards() {
+        let html = r#"<html><head><title>Fallback - Site</title>
+<meta property="og:title" content="Datalog &amp; Friends">
+<meta property="og:site_name" content="Example Blog">
+<link rel="icon" href="/static/icon.png"></head></html>"#;
+        let now = Utc::now();
+        let preview = preview_from_html("https://www.example.org/post?a=1", Some(html), now);
+        assert_eq!(preview.title.as_deref(), Some("Datalog & Friends"));
+        assert_eq!(preview.site, "Example Blog");
+        assert_eq!(preview.favicon.as_deref(), Some("https://www.example.org/static/icon.png"));
+        let bare = preview_from_html("https://www.example.org/x", None, now);
+        assert_eq!(bare.title, None);
+        assert_eq!(bare.site, "example.org");
+        assert_eq!(bare.favicon.as_deref(), Some("https://www.example.org/favicon.ico"));
+        assert!(!preview.is_stale(now));
+        assert!(Preview { fetched: now - TimeDelta::days(5), ..bare.clone() }.is_stale(now));
+
+        let body = "Read this:\n\nhttps://www.example.org/post?a=1\n\n<https://other.example/y>\n\nInline https://www.example.org/post?a=1 link.\n";
+        let rendered = render_markdown(body);
+        let previews: HashMap<String, Preview> = [
+            ("https://www.example.org/post?a=1".to_string(), preview),
+            ("https://other.example/y".to_string(), bare),
+        ]
+        .into();
+        let carded = add_cards(&rendered, &previews);
+        assert_eq!(carded.matches("link-card-title").count(), 1);
+        assert!(carded.contains(r#"href="https://www.example.org/post?a=1""#));
+        assert!(carded.contains("Datalog &amp; Friends"));
+        assert!(carded.contains("Inline https://www.example.org/post?a=1 link."));
+        assert!(carded.contains("other.example/y</a></p>"));
+    }
+}
@@ -0,0 +1,372 @@
+//! Dead link checker.
+//!
+//! Every external URL in a note body (outside fenced code) and every `url`
+//! source of a paper is checked with a GET through the link-check URL
+//! policy, conditional on the page cache (`page_cache`); the status, where redirects ended up and when it was checked are
+//! kept in sled. With `[links] check_days` set, the server rechecks links
+//! older than that once a day; `/links` lists broken links (4xx/5xx or no
+//! response) and links that now redirect to another domain, and can start
+//! a check by hand.
+//!
+//! Only one check runs at a time.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, TimeDelta, Utc};
+use futures_util::StreamExt;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::html_escape;
+use crate::page_cache;
+use crate::tasks::body_lines;
+use crate::templates::base_html;
+use crate::url_validator::UrlPolicy;
+use crate::AppState;
+
+const STATUS_TREE: &str = "link_status";
+
+/// Links checked at once.
+const CHECK_CONCURRENCY: usize = 4;
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Set while a check runs, so `/links` doesn't start a second one.
+static CHECKING: AtomicBool = AtomicBool::new(false);
+
+static URL_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"https?://[^\s<>"'`\[\]{}|\\^]+"#).unwrap());
+
+/// The outcome of the last check of a link.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkStatus {
+    /// HTTP status of the final response; None when there wasn't one.
+    pub status: Option<u16>,
+    /// Why there was no response (DNS failure, refused by the URL policy...).
+    pub error: Option<String>,
+    /// Where redirects ended up, when that isn't the link itself.
+    pub final_url: Option<String>,
+    pub checked: DateTime<Utc>,
+}
+
+impl LinkStatus {
+    pub fn is_broken(&self) -> bool {
+        self.status.is_none_or(|s| s >= 400)
+    }
+
+    /// Whether the link now redirects to another domain (`www.` aside).
+    pub fn moved_domain(&self, url: &str) -> bool {
+        let host = |u: &str| {
+            url::Url::parse(u)
+                .ok()
+                .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_lowercase()))
+        };
+        self.final_url.as_deref().is_some_and(|f| host(f) != host(url))
+    }
+}
+
+/// Trim what a URL in prose picks up from its surroundings: trailing
+/// punctuation and a closing parenthesis it didn't open (markdown links).
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '_']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(rest) if trimmed.matches('(').count() < trimmed.matches(')').count() => rest,
+            _ => trimmed,
+        };
+        if trimmed == url {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+/// The external URLs of a note: its body outside fenced code, then the
+/// `url` sources of a paper. Each appears once.
+pub fn note_urls(note: &Note) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for (_, line) in body_lines(&note.full_file_content) {
+        for m in URL_RE.find_iter(line) {
+            let url = trim_url(m.as_str());
+            if url::Url::parse(url).is_ok_and(|u| u.host_str().is_some()) && !urls.iter().any(|u| u == url) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+    if let NoteType::Paper(ref paper) = note.note_type {
+        for source in paper.sources.iter().filter(|s| s.source_type == "url") {
+            if !urls.contains(&source.identifier) {
+                urls.push(source.identifier.clone());
+            }
+        }
+    }
+    urls
+}
+
+/// Every external URL in the notes, with the notes it appears in.
+pub fn collect_links(notes: &[Note]) -> BTreeMap<String, Vec<&Note>> {
+    let mut links: BTreeMap<String, Vec<&Note>> = BTreeMap::new();
+    for note in notes {
+        for url in note_urls(note) {
+            links.entry(url).or_default().push(note);
+        }
+    }
+    links
+}
+
+pub fn load_status(db: &crate::store::Db, url: &str) -> Option<LinkStatus> {
+    let value = db.open_tree(STATUS_TREE).ok()?.get(url.as_bytes()).ok()??;
+    serde_json::from_slice(&value).ok()
+}
+
+fn save_status(db: &crate::store::Db, url: &str, status: &LinkStatus) -> Result<(), String> {
+    let tree = db.open_tree(STATUS_TREE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_vec(status).map_err(|e| e.to_string())?;
+    tree.insert(url.as_bytes(), value).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fetch `url` once and record what happened. An unchanged cached page
+/// counts with its cached status.
+async fn check_link(db: &crate::store::Db, policy: &UrlPolicy, url: &str) -> LinkStatus {
+    let page = page_cache::fetch(Some(db), policy, "link_check", url, CHECK_TIMEOUT, |r| {
+        r.header(reqwest::header::USER_AGENT, "Mozilla/5.0 (compatible; NotesApp/1.0)")
+    })
+    .await;
+    let checked = Utc::now();
+    match page {
+        Ok(page) => LinkStatus {
+            status: Some(page.status),
+            error: None,
+            final_url: (page.final_url != url).then_some(page.final_url),
+            checked,
+        },
+        Err(e) => LinkStatus { status: None, error: Some(e), final_url: None, checked },
+    }
+}
+
+/// Check every link last checked before `before` (or never). Returns how
+/// many were checked; 0 when another check is running.
+pub async fn check_links(state: &AppState, before: DateTime<Utc>) -> usize {
+    if CHECKING.swap(true, Ordering::SeqCst) {
+        return 0;
+    }
+    let urls: Vec<String> = collect_links(&state.load_notes())
+        .into_keys()
+        .filter(|url| load_status(&state.db, url).is_none_or(|s| s.checked < before))
+        .collect();
+    let policy = UrlPolicy::link_check();
+    // Saved as each finishes, so the report fills in during a long check
+    let checked = futures_util::stream::iter(urls)
+        .map(|url| {
+            let policy = policy.clone();
+            let db = state.db.clone();
+            async move {
+                let status = check_link(&db, &policy, &url).await;
+                save_status(&db, &url, &status)
+                    .map_err(|e| tracing::warn!(url = %url, error = %e, "Failed to record link status"))
+                    .is_ok()
+            }
+        })
+        .buffer_unordered(CHECK_CONCURRENCY)
+        .filter(|saved| std::future::ready(*saved))
+        .count()
+        .await;
+    CHECKING.store(false, Ordering::SeqCst);
+    tracing::info!(checked, "Checked links");
+    checked
+}
+
+/// Once a day, recheck links older than `[links] check_days`. Spawned by
+/// the server when it is set, unless it is read-only.
+pub async fn check_daily(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+    loop {
+        interval.tick().await;
+        let days = TimeDelta::days(state.config.links.check_days as i64);
+        check_links(&state, Utc::now() - days).await;
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+fn note_links(notes: &[&Note]) -> String {
+    notes
+        .iter()
+        .map(|n| format!(r#"<a href="/note/{}">{}</a>"#, n.key, html_escape(&n.title)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn url_link(url: &str) -> String {
+    format!(r#"<a href="{0}" rel="noopener noreferrer" target="_blank">{0}</a>"#, html_escape(url))
+}
+
+/// `GET /links`: broken and moved links, from the last check of each.
+pub async fn links_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let links = collect_links(&notes);
+    let statuses: Vec<(&String, &Vec<&Note>, Option<LinkStatus>)> =
+        links.iter().map(|(url, notes)| (url, notes, load_status(&state.db, url))).collect();
+    let unchecked = statuses.iter().filter(|(_, _, s)| s.is_none()).count();
+    let last_checked = statuses.iter().filter_map(|(_, _, s)| s.as_ref().map(|s| s.checked)).max();
+
+    let mut html = String::from("<h1>Links</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} external links in notes and paper sources · {} not checked yet{}</p>"#,
+        links.len(),
+        unchecked,
+        last_checked.map_or(String::new(), |t| format!(
+            " · last checked {}",
+            crate::timezone::format(t, "%Y-%m-%d %H:%M")
+        )),
+    ));
+    if !state.config.read_only {
+        html.push_str(
+            r#"<p><button id="check-links">Check all links now</button></p>
+<script>
+document.getElementById('check-links').addEventListener('click', async (e) => {
+    e.target.disabled = true;
+    const resp = await fetch('/api/links/check', {method: 'POST'});
+    if (resp.ok) {
+        const data = await resp.json();
+        e.target.textContent = 'Checking ' + data.links + ' links; reload in a few minutes';
+    } else {
+        alert('Check failed: ' + await resp.text());
+        e.target.disabled = false;
+    }
+});
+</script>"#,
+        );
+    }
+
+    let broken: Vec<_> = statuses
+        .iter()
+        .filter_map(|(url, notes, s)| Some((url, notes, s.as_ref().filter(|s| s.is_broken())?)))
+        .collect();
+    html.push_str(&format!("<h2>Broken ({})</h2>", broken.len()));
+    if broken.is_empty() {
+        html.push_str("<p>None.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>Link</th><th>Status</th><th>Notes</th><th>Checked</th></tr>"#);
+        for (url, notes, status) in &broken {
+            let result = match (status.status, &status.error) {
+                (Some(code), _) => code.to_string(),
+                (None, Some(e)) => html_escape(e),
+                (None, None) => "No response".to_string(),
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                url_link(url),
+                result,
+                note_links(notes),
+                crate::timezone::format(status.checked, "%Y-%m-%d"),
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    let moved: Vec<_> = statuses
+        .iter()
+        .filter_map(|(url, notes, s)| Some((url, notes, s.as_ref().filter(|s| !s.is_broken() && s.moved_domain(url))?)))
+        .collect();
+    html.push_str(&format!("<h2>Moved to another domain ({})</h2>", moved.len()));
+    if moved.is_empty() {
+        html.push_str("<p>None.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>Link</th><th>Now at</th><th>Notes</th><th>Checked</th></tr>"#);
+        for (url, notes, status) in &moved {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                url_link(url),
+                url_link(status.final_url.as_deref().unwrap_or_default()),
+                note_links(notes),
+                crate::timezone::format(status.checked, "%Y-%m-%d"),
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    Html(base_html("Links", &html, None, true)).into_response()
+}
+
+/// `POST /api/links/check`: check every link again in the background;
+/// returns `{links}`, the number there are.
+pub async fn check_links_handler(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if CHECKING.load(Ordering::SeqCst) {
+        return (StatusCode::CONFLICT, "A check is already running").into_response();
+    }
+
+    let links = collect_links(&state.load_notes()).len();
+    tokio::spawn(async move {
+        check_links(&state, Utc::now()).await;
+    });
+    axum::Json(serde_json::json!({ "links": links })).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_collect_links() {
+        let dir = TempDir::new("links");
+        fs::write(
+            dir.join("a.md"),
+            "---\ntitle: A\nid: aaaaaaaaaaaa\n---\n\nSee [the docs](https://example.com/docs_(v2)), and https://example.org/x.\n\
+             Twice: <https://example.com/docs_(v2)>\n\n```\ncurl https://in.code.example/\n```\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.md"),
+            "---\ntitle: B\nid: bbbbbbbbbbbb\ntype: paper\nurl: https://example.org/x\nbibtex: |\n  @misc{b, title = {B}}\n---\n\nNo links.\n",
+        )
+        .unwrap();
+        let notes = load_all_notes(&dir.to_path_buf());
+
+        let a = notes.iter().find(|n| n.title == "A").unwrap();
+        assert_eq!(note_urls(a), vec!["https://example.com/docs_(v2)", "https://example.org/x"]);
+        let links = collect_links(&notes);
+        assert_eq!(links.len(), 2);
+        let mut titles: Vec<&str> = links["https://example.org/x"].iter().map(|n| n.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, vec!["A", "B"]);
+
+        let checked = Utc::now();
+        let moved = LinkStatus {
+            status: Some(200),
+            error: None,
+            final_url: Some("https://new.example.net/x".to_string()),
+            checked,
+        };
+        assert!(!moved.is_broken());
+        assert!(moved.moved_domain("https://example.org/x"));
+        let www = LinkStatus { final_url: Some("https://www.example.org/x/".to_string()), ..moved.clone() };
+        assert!(!www.moved_domain("http://example.org/x"));
+        assert!(LinkStatus { status: Some(404), ..moved.clone() }.is_broken());
+        assert!(LinkStatus { status: None, error: Some("DNS".to_string()), final_url: None, checked }.is_broken());
+    }
+}
@@ -0,0 +1,176 @@
+//! The language model behind the AI features.
+//!
+//! Summaries, tag suggestions, `/ask` and the metadata lookup fallbacks
+//! all go through `complete`, which sends a prompt and an optional input
+//! document to the provider chosen in `[llm]`: the `claude` CLI (the
+//! default), Anthropic's Messages API, or an OpenAI-compatible chat
+//! completions endpoint. The config is set once at startup, like read-only
+//! mode, so callers without an `AppState` (the `notes add` command) use it
+//! too.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::config::{LlmConfig, LlmProvider};
+
+const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1/messages";
+const OPENAI_URL: &str = "https://api.openai.com/v1/chat/completions";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_COMMAND: &str = "claude";
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Replies to long documents can take minutes.
+const API_TIMEOUT: Duration = Duration::from_secs(300);
+
+static CONFIG: RwLock<Option<LlmConfig>> = RwLock::new(None);
+
+/// Use `config` for every later call (set from config at startup).
+pub fn configure(config: &LlmConfig) {
+    *CONFIG.write().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+}
+
+fn config() -> LlmConfig {
+    CONFIG.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+/// Ask the configured model `prompt` about `input` (e.g. a document to
+/// work on) and return its reply. API calls go through `client`.
+pub async fn complete(client: &reqwest::Client, prompt: String, input: Option<String>) -> Result<String, String> {
+    let config = config();
+    match config.provider {
+        LlmProvider::Cli => run_cli(&config, prompt, input).await,
+        LlmProvider::Anthropic | LlmProvider::OpenAI => {
+            let result = call_api(client, &config, &message(&prompt, input.as_deref())).await;
+            let api = if config.provider == LlmProvider::Anthropic { "anthropic_api" } else { "openai_api" };
+            crate::telemetry::record_external_call(api, result.is_ok());
+            result
+        }
+    }
+}
+
+/// Run the CLI on `prompt`, with `input` piped to it, and return what it
+/// prints.
+async fn run_cli(config: &LlmConfig, prompt: String, input: Option<String>) -> Result<String, String> {
+    let command = config.command.clone().unwrap_or_else(|| DEFAULT_COMMAND.to_string());
+    let name = command.clone();
+    let output = tokio::task::spawn_blocking(move || {
+        let mut child = Command::new(&command)
+            .args(["-p", &prompt])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.unwrap_or_default().as_bytes())?;
+        }
+        child.wait_with_output()
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    crate::telemetry::record_external_call(
+        "claude_cli",
+        matches!(output, Ok(ref o) if o.status.success()),
+    );
+    let output = output.map_err(|e| format!("Failed to run {}: {}", name, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", name, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The single user message an API provider gets: the prompt, then the
+/// input the CLI would read from standard input.
+fn message(prompt: &str, input: Option<&str>) -> String {
+    match input {
+        Some(input) if !input.trim().is_empty() => format!("{}\n\n<input>\n{}\n</input>", prompt, input),
+        _ => prompt.to_string(),
+    }
+}
+
+fn request_body(config: &LlmConfig, message: &str) -> serde_json::Value {
+    serde_json::json!({
+        "model": config.model,
+        "max_tokens": config.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+        "messages": [{"role": "user", "content": message}],
+    })
+}
+
+/// The text of a provider's reply.
+fn parse_reply(provider: LlmProvider, body: &serde_json::Value) -> Result<String, String> {
+    let text = match provider {
+        LlmProvider::Anthropic => body["content"].as_array().map(|blocks| {
+            blocks
+                .iter()
+                .filter(|b| b["type"] == "text")
+                .filter_map(|b| b["text"].as_str())
+                .collect::<String>()
+        }),
+        LlmProvider::OpenAI => body["choices"][0]["message"]["content"].as_str().map(str::to_string),
+        LlmProvider::Cli => None,
+    };
+    text.ok_or_else(|| "Unexpected response from the language model".to_string())
+}
+
+async fn call_api(client: &reqwest::Client, config: &LlmConfig, message: &str) -> Result<String, String> {
+    let default_url = if config.provider == LlmProvider::Anthropic { ANTHROPIC_URL } else { OPENAI_URL };
+    let key = config.api_key_env.as_deref().and_then(|var| std::env::var(var).ok());
+    let mut request = client
+        .post(config.url.as_deref().unwrap_or(default_url))
+        .timeout(API_TIMEOUT)
+        .json(&request_body(config, message));
+    request = match (config.provider, key) {
+        (LlmProvider::Anthropic, key) => {
+            let request = request.header("anthropic-version", ANTHROPIC_VERSION);
+            match key {
+                Some(key) => request.header("x-api-key", key),
+                None => request,
+            }
+        }
+        (_, Some(key)) => request.bearer_auth(key),
+        (_, None) => request,
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Language model request failed: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let detail = response.text().await.unwrap_or_default();
+        return Err(format!("Language model returned HTTP {}: {}", status, detail.trim()));
+    }
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    parse_reply(config.provider, &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_and_reply() {
+        assert_eq!(message("Summarize.", None), "Summarize.");
+        assert_eq!(message("Summarize.", Some("Text")), "Summarize.\n\n<input>\nText\n</input>");
+
+        let config = LlmConfig {
+            provider: LlmProvider::OpenAI,
+            model: Some("gpt-4o-mini".to_string()),
+            ..LlmConfig::default()
+        };
+        let body = request_body(&config, "Hi");
+        assert_eq!(body["model"], "gpt-4o-mini");
+        assert_eq!(body["max_tokens"], DEFAULT_MAX_TOKENS);
+        assert_eq!(body["messages"][0]["content"], "Hi");
+
+        let anthropic = serde_json::json!({"content": [
+            {"type": "text", "text": "Hello, "},
+            {"type": "tool_use", "id": "x"},
+            {"type": "text", "text": "world"}
+        ]});
+        assert_eq!(parse_reply(LlmProvider::Anthropic, &anthropic).unwrap(), "Hello, world");
+        let openai = serde_json::json!({"choices": [{"message": {"role": "assistant", "content": "Hi"}}]});
+        assert_eq!(parse_reply(LlmProvider::OpenAI, &openai).unwrap(), "Hi");
+        assert!(parse_reply(LlmProvider::OpenAI, &anthropic).is_err());
+    }
+}
@@ -9,13 +9,269 @@
 //! - `templates`: HTML/CSS/JS templates and rendering
 //! - `graph`: Knowledge graph building and visualization
 //! - `smart_add`: Smart paper/reference addition feature
+//! - `authors`: Author index and pages built from paper BibTeX
 //! - `handlers`: HTTP route handlers
+//! - `time_categories`: User-configurable time tracking categories
+//! - `telemetry`: Logging and Prometheus metrics
+//! - `outline_import`: Roam / Logseq graph import
+//! - `export`: Org, HTML, LaTeX and pandoc export
+//! - `backup`: Backup archives and `notes restore`
+//! - `corpus`: JSON dump and import of all notes
+//! - `course`: Courses, numbered lectures and teaching time at `/courses`
+//! - `reading_list`: Syllabus reading lists of the papers below a note
+//! - `doctor`: Corpus lint behind `notes doctor`
+//! - `formats`: Extra note file extensions and their rendering
+//! - `validate`: Editor diagnostics and the `/problems` page, from the doctor checks
+//! - `note_move`: Moving notes and rewriting references, behind `notes mv`
+//! - `key_migration`: Long note keys and old-key redirects, behind `notes migrate-keys`
+//! - `admin`: Database inspection and cache purges at `/admin/db`
+//! - `store`: The key-value store behind server state, on sled or SQLite
+//! - `storage`: PDFs and audio memos on disk or in an S3-compatible bucket
+//! - `encryption`: Encrypted bodies for notes marked `encrypted: true`
+//! - `sync`: Syncing notes with another instance over `/api/sync`
+//! - `webdav`: WebDAV access to the notes directory at `/dav/`
+//! - `mirror`: Read-only mirror that pulls the content repo from a primary
+//! - `pwa`: Installable web app with a change feed and offline batched edits
+//! - `vaults`: Several content roots in one server, with the nav bar switcher
+//! - `dedupe`: Duplicate detection and merging, behind `notes dedupe`
+//! - `cli`: `notes list`, `search`, `show`, `add`, `capture`, `bib`, `doctor`, `graph`, `time`, `mv`, `migrate-keys`, `dedupe`, `upload-pdfs`, `migrate-db` and `restore` subcommands
 
-use axum::{extract::DefaultBodyLimit, routing::get, Router};
+use axum::{extract::DefaultBodyLimit, middleware::from_fn, response::Redirect, routing::get, Router};
+use axum_prometheus::metrics_exporter_prometheus::PrometheusHandle;
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::services::ServeDir;
 
-use notes::{auth, citations, graph, handlers, shared, smart_add, AppState, NOTES_DIR};
+use notes::{
+    admin, agenda, archive, ask, auth, authors, backup, board, browse, bulk, citations, cli, corpus, course, digest, embeddings, encryption, export, feed, formats, graph, habits, handlers, history, http, journal, links, llm, metadata, mirror, note_templates, outline_import, print, proxy, pwa, queue, reading_list, recurring, related, reminders, review, shared, sitemap, smart_add, stats, storage, summarize, sync, tag_suggest, tasks, telemetry, templates, theme, time_import, timezone, tls, transcribe, trash, url_validator, validate, vaults, webdav, AppState,
+    BibFilter, Config, ConfigArgs, DbBackend, NoteSort, SortOrder, TimeCategory, TimeEntry,
+};
+use notes::time_categories::{find_category, resolve_time_categories};
+
+// ============================================================================
+// Command Line
+// ============================================================================
+
+#[derive(Parser)]
+#[command(name = "notes", version, about = "Personal knowledge management server and command-line tools")]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the web server (the default)
+    Serve,
+    /// Serve a public, read-only copy: index, notes, search, papers, graph and bibliography only
+    Publish,
+    /// List notes: key, date and title, sorted by title
+    List {
+        /// Only list papers
+        #[arg(long)]
+        papers: bool,
+        /// Only list notes with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Sort order
+        #[arg(long, value_enum, default_value_t = NoteSort::Title)]
+        sort: NoteSort,
+        /// Ascending or descending [default: A-Z for titles, else descending]
+        #[arg(long, value_enum)]
+        order: Option<SortOrder>,
+    },
+    /// Full-text search over titles and contents
+    Search {
+        /// Search terms
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Print a note, found by key, path or title
+    Show {
+        /// Key, path or (part of a) title
+        note: String,
+        /// Print the file as stored, including frontmatter
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Add a paper from an arXiv/DOI URL, web page or title, like Smart Add
+    Add {
+        /// URL, DOI or title
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Path of the new note under the notes directory (default: from the title)
+        #[arg(long)]
+        filename: Option<String>,
+        /// Add even if the input matches an existing note
+        #[arg(long)]
+        force: bool,
+        /// Start the note from this template in the templates directory
+        #[arg(long)]
+        template: Option<String>,
+    },
+    /// Save stdin as a dated inbox note and print its key, e.g. `pbpaste | notes capture`
+    Capture {
+        /// Note title (default: the first line of the text)
+        #[arg(long)]
+        title: Option<String>,
+        /// Tag for the note; repeat for several
+        #[arg(long = "tag", default_value = "inbox")]
+        tags: Vec<String>,
+        /// Don't commit the note to git
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Write a BibTeX bibliography, optionally filtered, to stdout or a file
+    Bib {
+        /// Only papers tagged with this (directly or through a parent)
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only papers published in or after this year
+        #[arg(long)]
+        since: Option<i32>,
+        /// Only papers published in or before this year
+        #[arg(long)]
+        until: Option<i32>,
+        /// Write to this file (left untouched if unchanged) instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check every note for malformed frontmatter, dangling references,
+    /// duplicate bib keys, missing PDFs and more
+    Doctor {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Query the knowledge graph, e.g. `notes graph "from:KEY depth:2 type:paper"`
+    Graph {
+        /// Query in the /graph query language (empty for the whole graph)
+        query: Vec<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t)]
+        format: cli::GraphFormat,
+    },
+    /// Log time on a note, e.g. `notes time datalog 45m reading "skimmed intro"`
+    Time {
+        /// Key, path or (part of a) title
+        note: String,
+        /// Duration: 45, 45m, 1h30m, 1.5h or 1:30
+        duration: String,
+        /// Time category
+        category: String,
+        /// Optional description
+        description: Vec<String>,
+        /// Date of the entry (YYYY-MM-DD, default today)
+        #[arg(long)]
+        date: Option<String>,
+        /// Commit the change to git
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Move a note or directory, rewriting links to it (stop the server first)
+    Mv {
+        /// Note or directory to move, relative to the notes directory
+        from: PathBuf,
+        /// New path, or an existing directory to move into
+        to: PathBuf,
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't commit the move to git
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Set missing created: dates from the commit that added each note
+    BackfillCreated {
+        /// Show the dates without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't commit the changes to git
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Pin path-derived note keys to longer ones, redirecting the old keys
+    MigrateKeys {
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Don't commit the changes to git
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Find likely duplicate notes and papers, and optionally merge them
+    Dedupe {
+        /// Minimum title/body similarity, 0 to 1
+        #[arg(long, default_value_t = 0.8)]
+        threshold: f64,
+        /// Ask about each pair and merge the confirmed ones (stop the server first)
+        #[arg(long)]
+        merge: bool,
+        /// Don't commit merges to git
+        #[arg(long)]
+        no_commit: bool,
+    },
+    /// Copy the local PDFs and audio memos into the [storage] bucket
+    UploadPdfs {
+        /// List what would be uploaded without uploading
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Copy the database into another backend (stop the server first)
+    MigrateDb {
+        /// Backend to copy into; the configured db_backend is read
+        #[arg(long, value_enum)]
+        to: DbBackend,
+    },
+    /// Restore a backup archive from /api/backup (stop the server first)
+    Restore {
+        /// The .tar.gz archive to restore
+        archive: PathBuf,
+        /// Move existing notes, PDFs and database aside instead of refusing
+        #[arg(long)]
+        force: bool,
+    },
+    /// Exit non-zero unless the running server's /healthz answers (for HEALTHCHECK)
+    Healthcheck,
+}
+
+// ============================================================================
+// Shutdown
+// ============================================================================
+
+/// How long in-flight requests and then background git commits get to
+/// finish after SIGTERM/SIGINT.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
 
 // ============================================================================
 // Main
@@ -23,20 +279,312 @@ use notes::{auth, citations, graph, handlers, shared, smart_add, AppState, NOTES
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    let config = match Config::load(&cli.config) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    llm::configure(&config.llm);
+    url_validator::configure(&config.url_policy);
+    http::configure(&config.proxy, &config.rate_limit);
+    formats::configure(&config.extensions);
+    timezone::configure(config.timezone.as_deref().and_then(|tz| timezone::parse(tz).ok()));
+    templates::custom::configure(templates::custom::load(config.data_dir.as_deref().unwrap_or(Path::new("."))));
+    vaults::configure(&config);
+    encryption::configure(&config.encryption);
+    match cli.command {
+        None | Some(Command::Serve) => serve(config, false).await,
+        Some(Command::Publish) => serve(config, true).await,
+        Some(command) => {
+            if let Err(e) = telemetry::init_cli_logging(&config.log) {
+                eprintln!("Configuration error: {}", e);
+                std::process::exit(1);
+            }
+            let config = match cli.config.vault {
+                Some(ref name) => config.vault(name).unwrap_or_else(|e| {
+                    eprintln!("Configuration error: {}", e);
+                    std::process::exit(1);
+                }),
+                None => config,
+            };
+            if let Err(e) = run_command(&config, command).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Run a subcommand other than `serve`, writing its output to stdout.
+async fn run_command(config: &Config, command: Command) -> cli::CliResult {
+    let mut out = std::io::stdout().lock();
+    match command {
+        Command::Serve | Command::Publish => unreachable!("serve and publish are handled in main"),
+        Command::List { papers, tag, sort, order } => {
+            let notes = cli::load_notes(config)?;
+            cli::list(&notes, &cli::ListFilter { papers, tag, sort, order }, &mut out)
+        }
+        Command::Search { query } => {
+            let notes = cli::load_notes(config)?;
+            cli::search(&notes, &query.join(" "), &mut out)
+        }
+        Command::Show { note, raw } => {
+            let notes = cli::load_notes(config)?;
+            cli::show(&notes, &note, raw, &mut out)
+        }
+        Command::Add { input, filename, force, template } => {
+            let notes = cli::load_notes(config)?;
+            let opts = cli::AddOptions { filename, force, template };
+            let input = input.join(" ");
+            cli::add(&notes, &config.notes_dir, &config.templates_dir, &input, &opts, &mut out).await
+        }
+        Command::Capture { title, tags, no_commit } => {
+            let mut text = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            let opts = cli::CaptureOptions { title, tags, commit: !no_commit };
+            let notes = cli::load_notes(config)?;
+            cli::capture(&notes, &config.notes_dir, &text, &opts, timezone::now(), &mut out)
+        }
+        Command::Bib { tag, since, until, output } => {
+            let notes = cli::load_notes(config)?;
+            let filter = BibFilter { tag, since, until };
+            cli::bib(&notes, &filter, output.as_deref(), &mut out)
+        }
+        Command::Doctor { json } => cli::doctor(config, json, &mut out),
+        Command::Graph { query, format } => {
+            let notes = cli::load_notes(config)?;
+            let db = cli::open_graph_index(config, &notes)?;
+            cli::graph(&db, &query.join(" "), format, &mut out)
+        }
+        Command::Time { note, duration, category, description, date, commit } => {
+            let minutes = cli::parse_duration(&duration)
+                .ok_or_else(|| format!("Invalid duration '{}' (e.g. 45m, 1h30m, 1:30)", duration))?;
+            let date = match date {
+                Some(d) => chrono::NaiveDate::parse_from_str(d.trim(), "%Y-%m-%d")
+                    .map_err(|_| "Invalid date (expected YYYY-MM-DD)".to_string())?,
+                None => timezone::today(),
+            };
+            let category = TimeCategory::from_name(&category);
+            let known = resolve_time_categories(&config.time_categories, &config.time_categories_path());
+            if find_category(&known, &category).is_none() {
+                tracing::warn!(%category, "Category is not configured; logging it anyway");
+            }
+            let description = description.join(" ");
+            let entry = TimeEntry {
+                date,
+                minutes,
+                category,
+                description: Some(description).filter(|d| !d.trim().is_empty()),
+            };
+            let notes = cli::load_notes(config)?;
+            cli::log_time(&notes, &config.notes_dir, &note, &entry, commit, &mut out)
+        }
+        Command::Mv { from, to, dry_run, no_commit } => {
+            let opts = cli::MoveOptions { dry_run, commit: !no_commit };
+            cli::mv(config, &from, &to, &opts, &mut out)
+        }
+        Command::BackfillCreated { dry_run, no_commit } => {
+            let opts = cli::BackfillOptions { dry_run, commit: !no_commit };
+            cli::backfill_created(config, &opts, &mut out)
+        }
+        Command::MigrateKeys { dry_run, no_commit } => {
+            let opts = cli::BackfillOptions { dry_run, commit: !no_commit };
+            cli::migrate_keys(config, &opts, &mut out)
+        }
+        Command::Dedupe { threshold, merge, no_commit } => {
+            let opts = cli::DedupeOptions { threshold, merge, commit: !no_commit };
+            cli::dedupe(config, &opts, &mut std::io::stdin().lock(), &mut out)
+        }
+        Command::UploadPdfs { dry_run } => cli::upload_pdfs(config, dry_run, &mut out).await,
+        Command::MigrateDb { to } => cli::migrate_db(config, to, &mut out),
+        Command::Restore { archive, force } => cli::restore(config, &archive, force),
+        Command::Healthcheck => cli::healthcheck(config).await,
+    }
+}
+
+/// Run the web server until SIGTERM/SIGINT. With `publish`, run it as a
+/// public viewer: read-only, with only `viewer_routes` mounted.
+async fn serve(mut config: Config, publish: bool) {
+    if let Err(e) = telemetry::init_logging(&config.log) {
+        eprintln!("Configuration error: {}", e);
+        std::process::exit(1);
+    }
+    if publish {
+        config.publish = true;
+        config.read_only = true;
+    }
+    if config.mirror.upstream.is_some() {
+        config.read_only = true;
+        if let Err(e) = mirror::prepare(&config.mirror, &config.notes_dir) {
+            tracing::error!(error = %e, "Mirror setup failed");
+            std::process::exit(1);
+        }
+    }
+    let bind = config.bind.clone();
+    let notes_dir = config.notes_dir.clone();
+
     rayon::ThreadPoolBuilder::new()
         .num_threads(16)
         .build_global()
         .unwrap();
 
-    let state = Arc::new(AppState::new());
+    let (metric_layer, metric_handle) = telemetry::metrics_layer();
+    let mut vault_apps = Vec::new();
+    for vault_config in config.vault_configs() {
+        let state = Arc::new(AppState::new(vault_config));
+        let routes = if publish {
+            viewer_routes()
+        } else {
+            app_routes(state.storage.local_dir(), metric_handle.clone(), state.db.clone())
+        };
+        let router = routes
+            .layer(axum::middleware::from_fn_with_state(state.clone(), auth::read_only_guard))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), vaults::vault_guard))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), theme::select_theme))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), proxy::rewrite_urls))
+            .layer(metric_layer.clone())
+            .layer(telemetry::trace_layer())
+            .with_state(state.clone());
+        vault_apps.push((state, router));
+    }
+    let state = vault_apps[0].0.clone();
+    let app = if vault_apps.len() == 1 {
+        vault_apps[0].1.clone()
+    } else {
+        let vaults = Arc::new(vaults::Vaults { vaults: vault_apps.clone() });
+        Router::new().fallback(vaults::dispatch).with_state(vaults)
+    };
+
+    // Behind a reverse proxy, serve everything under the configured prefix.
+    let base_path = state.config.base_path.clone();
+    let app = if base_path.is_empty() {
+        app
+    } else {
+        let index = base_path.clone();
+        let to_index = move || {
+            let index = index.clone();
+            async move { Redirect::to(&index) }
+        };
+        Router::new()
+            .nest(&base_path, app)
+            .route("/", get(to_index.clone()))
+            .route(&format!("{}/", base_path), get(to_index))
+    };
+
+    let listener = tokio::net::TcpListener::bind(&bind)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", bind, e));
+
+    let tls_config = match tls::rustls_config(&state.config, &state.db).await {
+        Ok(c) => c,
+        Err(e) => {
+            tracing::error!(error = %e, "TLS setup failed");
+            std::process::exit(1);
+        }
+    };
+
+    let scheme = if tls_config.is_some() { "https" } else { "http" };
+    tracing::info!("Notes server running at {}://{}{}", scheme, bind, base_path);
+    tracing::info!("Notes directory: {}", notes_dir.display());
+
+    if !publish {
+        match export::detect_pandoc() {
+            Some(version) => tracing::info!("DOCX/EPUB export: enabled ({})", version),
+            None => tracing::info!("DOCX/EPUB export: disabled (pandoc not found)"),
+        }
+    }
 
-    let app = Router::new()
+    if state.config.publish {
+        tracing::info!("Publishing: viewer routes only, editing disabled");
+    } else if let Some(ref upstream) = state.config.mirror.upstream {
+        let minutes = state.config.mirror.interval_minutes;
+        tracing::info!(upstream = %upstream, minutes, "Mirror: read-only, pulling from upstream");
+    } else if state.config.read_only {
+        tracing::info!("Read-only mode: editing disabled");
+    } else if auth::is_auth_enabled() {
+        tracing::info!("Authentication: ENABLED (NOTES_PASSWORD set)");
+    } else {
+        tracing::warn!("Authentication: DISABLED (set NOTES_PASSWORD env var to enable editing)");
+    }
+
+    for (vault, _) in &vault_apps {
+        if vault.config.vault_name != state.config.vault_name {
+            tracing::info!(vault = %vault.config.vault_name, "Vault directory: {}", vault.config.notes_dir.display());
+        }
+        if vault.config.require_login && (vault.config.read_only || !auth::is_auth_enabled()) {
+            tracing::warn!(vault = %vault.config.vault_name, "Vault requires a login, but authentication is disabled");
+        }
+        if !vault.config.read_only && vault.config.trash_days > 0 {
+            tokio::spawn(trash::purge_daily(vault.clone()));
+        }
+        if !vault.config.publish && vault.config.backup.schedule.is_some() {
+            tokio::spawn(backup::run_scheduled(vault.clone()));
+        }
+    }
+    if state.config.mirror.upstream.is_some() {
+        tokio::spawn(mirror::poll(state.clone()));
+    }
+    if !state.config.read_only && !state.config.recurring.is_empty() {
+        tokio::spawn(recurring::create_hourly(state.clone()));
+    }
+    if !state.config.read_only && state.config.notify.channel.is_some() {
+        tokio::spawn(reminders::check_minutely(state.clone()));
+    }
+    if !state.config.read_only && state.config.links.check_days > 0 {
+        tokio::spawn(links::check_daily(state.clone()));
+    }
+    if !state.config.read_only && state.config.digest.enabled {
+        tokio::spawn(digest::deliver_weekly(state.clone()));
+    }
+    if !state.config.publish && embeddings::is_enabled(&state.config.embeddings) {
+        tokio::spawn(embeddings::index_at_startup(state.clone()));
+    }
+
+    // Stop accepting connections on SIGTERM/SIGINT and let in-flight
+    // requests (including note saves) finish.
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown_signal().await;
+            tracing::info!("Shutdown requested, draining connections");
+            handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+        }
+    });
+
+    let listener = listener.into_std().expect("Failed to convert listener");
+    match tls_config {
+        Some(rustls) => axum_server::from_tcp_rustls(listener, rustls)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .expect("Server error"),
+        None => axum_server::from_tcp(listener)
+            .handle(handle)
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+            .await
+            .expect("Server error"),
+    }
+
+    for (vault, _) in &vault_apps {
+        vault.shutdown(SHUTDOWN_GRACE).await;
+    }
+    tracing::info!("Shutdown complete");
+}
+
+/// Every route of the full server.
+fn app_routes(pdfs_dir: Option<&Path>, metric_handle: PrometheusHandle, metrics_db: notes::store::Db) -> Router<Arc<AppState>> {
+    let routes = Router::new()
         // Core routes
         .route("/", get(handlers::index))
         .route("/search", get(handlers::search))
-        .route("/new", get(handlers::new_note_page).post(handlers::create_note))
-        .route("/login", get(handlers::login_page).post(handlers::login_submit))
-        .route("/logout", get(handlers::logout))
+        .route("/new", get(handlers::new_note_page).post(handlers::create_note).route_layer(from_fn(auth::mutating)))
+        .route("/login", get(handlers::login_page).post(handlers::login_submit).route_layer(from_fn(auth::mutating)))
+        .route("/logout", get(handlers::logout).route_layer(from_fn(auth::mutating)))
         // Note routes
         .route("/note/{key}", get(handlers::view_note))
         .route(
@@ -44,22 +592,89 @@ async fn main() {
             axum::routing::post(handlers::save_note).delete(handlers::delete_note),
         )
         .route("/api/note/{key}/toggle-hidden", axum::routing::post(handlers::toggle_hidden))
+        .route(
+            "/api/note/{key}/time",
+            axum::routing::post(handlers::add_time_entry).delete(handlers::delete_time_entry),
+        )
         .route("/note/{key}/history/{commit}", get(handlers::view_note_history))
+        .route("/note/{key}/print", get(print::print_note))
+        .route("/random", get(handlers::random_note))
+        .route("/browse", get(browse::browse))
+        .route("/history", get(history::history_page))
+        .route("/stats", get(stats::stats_page))
         // List routes
         .route("/papers", get(handlers::papers))
+        .route("/authors", get(authors::authors_page))
+        .route("/author/{slug}", get(authors::author_page))
         .route("/papers/find-pdfs", get(handlers::find_pdfs_page))
+        .route("/papers/metadata", get(metadata::metadata_page))
+        .route("/api/metadata/propose", axum::routing::post(metadata::propose))
+        .route("/api/metadata/apply", axum::routing::post(metadata::apply))
         .route("/time", get(handlers::time_tracking))
+        .route("/time/invoice", get(handlers::time_invoice))
+        .route("/time/import", get(time_import::time_import_page))
+        .route("/api/time/import", axum::routing::post(time_import::time_import))
         // Graph routes
         .route("/graph", get(graph::graph_page))
         .route("/api/graph", get(graph::graph_api))
         .route("/api/graph/edge", axum::routing::post(handlers::add_graph_edge).delete(handlers::delete_graph_edge))
         .route("/api/graph/edge/annotation", axum::routing::post(handlers::update_edge_annotation))
         .route("/api/notes/list", get(handlers::notes_list_api))
+        .route("/api/notes/bulk", axum::routing::post(bulk::bulk_notes))
         // Smart Add routes
         .route("/api/smart-add/lookup", axum::routing::post(smart_add::smart_add_lookup))
+        .route("/api/smart-add/extract", axum::routing::post(smart_add::smart_add_extract))
         .route("/api/smart-add/create", axum::routing::post(smart_add::smart_add_create))
         .route("/api/smart-add/attach", axum::routing::post(smart_add::smart_add_attach))
         .route("/api/smart-add/quick-note", axum::routing::post(smart_add::quick_note_create))
+        .route("/api/templates", get(note_templates::list_templates_api))
+        // Trash routes
+        .route("/archive", get(archive::archive_page))
+        .route("/api/note/{key}/archive", axum::routing::post(archive::archive_note))
+        .route("/today", get(journal::today))
+        .route("/journal", get(journal::journal_page))
+        .route("/tasks", get(tasks::tasks_page))
+        .route("/api/tasks", get(tasks::tasks_api))
+        .route("/api/note/{key}/task/{line}", axum::routing::post(tasks::toggle_task))
+        .route("/api/note/{key}/summarize", axum::routing::post(summarize::summarize_note))
+        .route("/api/note/{key}/related", get(related::related_notes))
+        .route("/api/note/{key}/suggest-tags", get(tag_suggest::suggest_tags_api))
+        .route("/api/note/{key}/validate", axum::routing::post(validate::validate_note))
+        .route("/problems", get(validate::problems_page))
+        .route("/agenda", get(agenda::agenda_page))
+        .route("/board", get(board::board_page))
+        .route("/reminders", get(reminders::reminders_page))
+        .route("/links", get(links::links_page))
+        .route("/api/links/check", axum::routing::post(links::check_links_handler))
+        .route("/api/theme", axum::routing::post(theme::set_default_theme))
+        .route("/api/board/move", axum::routing::post(board::move_card_handler))
+        .route("/review/weekly", get(review::weekly_review_page))
+        .route("/queue", get(queue::queue_page))
+        .route("/courses", get(course::courses_page))
+        .route("/api/courses/{key}/lecture", axum::routing::post(course::new_lecture))
+        .route("/habits", get(habits::habits_page))
+        .route("/ask", get(ask::ask_page))
+        .route("/api/ask", axum::routing::post(ask::ask))
+        .route("/api/review/weekly", axum::routing::post(review::save_review_handler))
+        .route("/api/digest", axum::routing::post(digest::digest_handler))
+        .route("/admin/db", get(admin::db_page))
+        .route("/api/admin/db/purge/{id}", axum::routing::post(admin::purge_handler))
+        // Offline web app routes
+        .route("/manifest.webmanifest", get(pwa::manifest))
+        .route("/sw.js", get(pwa::service_worker))
+        .route("/icon.svg", get(pwa::icon))
+        .route("/api/changes", get(pwa::changes))
+        .route("/api/batch", axum::routing::post(pwa::batch)
+            .layer(DefaultBodyLimit::max(50 * 1024 * 1024)))
+        .route("/trash", get(trash::trash_page))
+        .route("/api/trash/{id}/restore", axum::routing::post(trash::restore_note))
+        .route("/api/trash/{id}", axum::routing::delete(trash::delete_forever))
+        // Outliner import routes
+        .route("/import/outline", get(outline_import::outline_import_page))
+        .route("/api/import/outline", axum::routing::post(outline_import::outline_import)
+            .layer(DefaultBodyLimit::max(50 * 1024 * 1024)))
+        .route("/api/import/json", axum::routing::post(corpus::import_json)
+            .layer(DefaultBodyLimit::max(50 * 1024 * 1024)))
         // BibTeX Import routes
         .route("/api/bib-import/analyze", axum::routing::post(smart_add::bib_import_analyze)
             .layer(DefaultBodyLimit::max(10 * 1024 * 1024)))
@@ -68,38 +683,85 @@ async fn main() {
         .route("/api/citations/scan", axum::routing::post(citations::citation_scan))
         .route("/api/citations/write", axum::routing::post(citations::citation_write))
         .route("/api/citations/scan-all", axum::routing::post(citations::citation_scan_all))
+        // Health routes
+        .route("/healthz", get(handlers::healthz))
+        .route("/readyz", get(handlers::readyz))
+        .route(
+            "/metrics",
+            get(move || async move { telemetry::render_metrics(&metric_handle, &metrics_db) }),
+        )
         // Export routes
         .route("/bibliography.bib", get(handlers::bibliography))
+        .route("/feed.xml", get(feed::feed))
+        .route("/robots.txt", get(sitemap::robots))
+        .route("/note/{key}/export.org", get(export::export_note_org))
+        .route("/note/{key}/export.html", get(export::export_note_html))
+        .route("/note/{key}/export.tex", get(export::export_note_tex))
+        .route("/note/{key}/export.bib", get(export::export_note_bib))
+        .route("/note/{key}/export.docx", get(export::export_note_docx))
+        .route("/note/{key}/export.epub", get(export::export_note_epub))
+        .route("/note/{key}/reading-list.md", get(reading_list::reading_list_md))
+        .route("/note/{key}/reading-list.html", get(reading_list::reading_list_html))
+        .route("/note/{key}/reading-list.bib", get(reading_list::reading_list_bib))
+        .route("/export/corpus.org", get(export::export_corpus_org))
+        .route("/api/backup", get(backup::backup))
+        .route("/api/export.json", get(corpus::export_json))
         // Shared notes routes
         .route("/api/shared/create", axum::routing::post(shared::create_shared_note))
         .route("/api/shared/list/{note_key}", get(shared::list_shared_notes))
         .route("/api/shared/{token}/deactivate", axum::routing::post(shared::deactivate_shared_note))
         .route("/api/shared/{token}/contributors", axum::routing::post(shared::manage_contributors))
         .route("/shared/{token}", get(shared::shared_editor_page))
-        .route("/shared/{token}/ws", get(shared::ws_handler))
+        .route("/shared/{token}/ws", get(shared::ws_handler).route_layer(from_fn(auth::mutating)))
         .route("/api/shared/{token}/attribution", get(shared::get_attribution))
         // PDF routes
-        .nest_service("/pdfs", ServeDir::new("pdfs"))
+        .route("/pdfs/audio/{file}", get(transcribe::serve_audio))
+        .route("/api/note/{key}/audio", axum::routing::post(transcribe::upload_audio)
+            .layer(DefaultBodyLimit::max(200 * 1024 * 1024)))
+        .route("/api/note/{key}/transcribe", axum::routing::post(transcribe::transcribe_memo))
         .route("/api/pdf/upload", axum::routing::post(handlers::upload_pdf)
             .layer(DefaultBodyLimit::max(50 * 1024 * 1024)))
         .route("/api/pdf/download-url", axum::routing::post(handlers::download_pdf_from_url))
         .route("/api/pdf/rename", axum::routing::post(handlers::rename_pdf))
         .route("/api/pdf/unlink", axum::routing::post(handlers::unlink_pdf))
         .route("/api/pdf/smart-find", axum::routing::post(handlers::smart_pdf_find))
-        .with_state(state);
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
-        .await
-        .expect("Failed to bind to port 3000");
-
-    println!("Notes server running at http://0.0.0.0:3000");
-    println!("Notes directory: {}", NOTES_DIR);
-
-    if auth::is_auth_enabled() {
-        println!("Authentication: ENABLED (NOTES_PASSWORD set)");
-    } else {
-        println!("Authentication: DISABLED (set NOTES_PASSWORD env var to enable editing)");
+        // Sync with another instance
+        .route("/api/sync", axum::routing::post(sync::sync))
+        .route("/api/sync/manifest", get(sync::peer_manifest))
+        .route("/api/sync/fetch", axum::routing::post(sync::peer_fetch))
+        .route("/api/sync/apply", axum::routing::post(sync::peer_apply)
+            .layer(DefaultBodyLimit::max(sync::MAX_BODY)))
+        // WebDAV (when `webdav` is set)
+        .route("/dav", axum::routing::any(webdav::handle))
+        .route("/dav/", axum::routing::any(webdav::handle))
+        .route("/dav/{*path}", axum::routing::any(webdav::handle));
+    // PDFs from disk, or redirects to object storage
+    match pdfs_dir {
+        Some(dir) => routes.nest_service("/pdfs", ServeDir::new(dir)),
+        None => routes.route("/pdfs/{*name}", get(storage::serve_object)),
     }
+}
 
-    axum::serve(listener, app).await.expect("Server error");
+/// The routes `notes publish` serves: pages that only read notes, plus the
+/// graph data the graph page loads and health checks. Editing, Smart Add,
+/// login, time tracking, exports, PDFs and shared notes aren't routed.
+fn viewer_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(handlers::index))
+        .route("/search", get(handlers::search))
+        .route("/note/{key}", get(handlers::view_note))
+        .route("/note/{key}/print", get(print::print_note))
+        .route("/random", get(handlers::random_note))
+        .route("/browse", get(browse::browse))
+        .route("/papers", get(handlers::papers))
+        .route("/authors", get(authors::authors_page))
+        .route("/author/{slug}", get(authors::author_page))
+        .route("/graph", get(graph::graph_page))
+        .route("/api/graph", get(graph::graph_api))
+        .route("/bibliography.bib", get(handlers::bibliography))
+        .route("/feed.xml", get(feed::feed))
+        .route("/sitemap.xml", get(sitemap::sitemap))
+        .route("/robots.txt", get(sitemap::robots))
+        .route("/healthz", get(handlers::healthz))
+        .route("/readyz", get(handlers::readyz))
 }
@@ -0,0 +1,607 @@
+//! Paper metadata cleanup.
+//!
+//! `/papers/metadata` lists paper notes whose BibTeX is missing a year,
+//! authors or venue, has authors that don't look like a BibTeX author
+//! list, or disagrees with leftover `year:`/`venue:`/`authors:`
+//! frontmatter (which the loader ignores). For the papers picked, the stored DOI or arXiv ID
+//! is looked up again on CrossRef or arXiv and the differences are offered
+//! as fixes to the BibTeX entry; the fixes approved are applied as one git
+//! commit. The cite key is never changed, so existing citations keep
+//! working.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::is_logged_in;
+use crate::bulk::{apply_bulk, BulkChange};
+use crate::models::{ExternalResult, Note, NoteType};
+use crate::notes::{frontmatter_end, frontmatter_field, html_escape, parse_bibtex, set_frontmatter_field, ParsedBibtex};
+use crate::smart_add::{query_arxiv_api, query_crossref_api};
+use crate::templates::base_html;
+use crate::{trash, AppState};
+
+/// Papers looked up per request, to go easy on CrossRef and arXiv.
+pub const MAX_LOOKUPS: usize = 20;
+
+/// Frontmatter fields older notes carry that BibTeX now stands in for.
+const LEGACY_FIELDS: &[&str] = &["year", "venue", "authors"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    /// Short check name, e.g. `missing-year`.
+    pub check: &'static str,
+    pub message: String,
+}
+
+/// A paper with metadata problems.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaperProblems {
+    pub key: String,
+    pub title: String,
+    pub problems: Vec<Problem>,
+    /// `doi:…` or `arxiv:…` to look the paper up by, if it has one.
+    pub identifier: Option<String>,
+}
+
+/// A legacy field's value; a YAML list of `authors:` is joined BibTeX-style.
+fn legacy_value(note: &Note, field: &str) -> Option<String> {
+    if field == "authors" {
+        let authors = crate::notes::parse_frontmatter(&note.full_file_content).0.authors;
+        return (!authors.is_empty()).then(|| authors.join(" and "));
+    }
+    frontmatter_field(&note.full_file_content, field)
+}
+
+fn problem(check: &'static str, message: impl Into<String>) -> Problem {
+    Problem {
+        check,
+        message: message.into(),
+    }
+}
+
+/// Whether `author` reads as a BibTeX author list: names joined by ` and `,
+/// each `First Last` or `Last, First`.
+pub fn authors_look_malformed(author: &str) -> bool {
+    let lower = author.to_lowercase();
+    let names: Vec<&str> = author.split(" and ").map(str::trim).collect();
+    author.contains(';')
+        || lower.contains("et al")
+        || author.chars().any(|c| c.is_ascii_digit())
+        || names.iter().any(|n| n.is_empty() || n.matches(',').count() > 1)
+}
+
+fn is_preprint(parsed: &ParsedBibtex) -> bool {
+    parsed.eprint.is_some() || matches!(parsed.entry_type.as_str(), "misc" | "unpublished" | "techreport")
+}
+
+fn normalized(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// The identifier a paper can be looked up by, DOI first.
+fn identifier(note: &Note, parsed: Option<&ParsedBibtex>) -> Option<String> {
+    let NoteType::Paper(ref paper) = note.note_type else {
+        return None;
+    };
+    let source = |kind: &str| paper.sources.iter().find(|s| s.source_type == kind).map(|s| s.identifier.clone());
+    source("doi")
+        .or_else(|| parsed.and_then(|p| p.doi.clone()))
+        .map(|doi| format!("doi:{}", doi))
+        .or_else(|| {
+            source("arxiv")
+                .or_else(|| parsed.and_then(|p| p.eprint.clone()))
+                .map(|id| format!("arxiv:{}", id))
+        })
+}
+
+fn canonical_parsed(note: &Note) -> Option<ParsedBibtex> {
+    match note.note_type {
+        NoteType::Paper(ref paper) => paper.canonical_bibtex().and_then(|b| parse_bibtex(b)),
+        NoteType::Note => None,
+    }
+}
+
+/// Problems with one paper's metadata; empty for other notes.
+pub fn check_paper(note: &Note) -> Vec<Problem> {
+    if !matches!(note.note_type, NoteType::Paper(_)) {
+        return Vec::new();
+    }
+    let Some(parsed) = canonical_parsed(note) else {
+        return vec![problem("no-bibtex", "No BibTeX entry that parses")];
+    };
+
+    let mut problems = Vec::new();
+    let this_year = chrono::Datelike::year(&crate::timezone::today());
+    match parsed.year {
+        None | Some(0) => problems.push(problem("missing-year", "No year")),
+        Some(y) if !(1800..=this_year + 1).contains(&y) => {
+            problems.push(problem("bad-year", format!("Year {} is out of range", y)))
+        }
+        Some(_) => {}
+    }
+    match parsed.author.as_deref().map(str::trim) {
+        None | Some("") => problems.push(problem("missing-authors", "No authors")),
+        Some(a) if authors_look_malformed(a) => {
+            problems.push(problem("malformed-authors", format!("Authors don't look like a BibTeX list: {}", a)))
+        }
+        Some(_) => {}
+    }
+    if parsed.venue.as_deref().is_none_or(|v| v.trim().is_empty()) && !is_preprint(&parsed) {
+        problems.push(problem("missing-venue", "No journal or booktitle"));
+    }
+
+    for field in LEGACY_FIELDS {
+        let Some(value) = legacy_value(note, field) else { continue };
+        let bibtex = match *field {
+            "year" => parsed.year.map(|y| y.to_string()),
+            "venue" => parsed.venue.clone(),
+            _ => parsed.author.clone(),
+        };
+        if bibtex.as_deref().is_none_or(|b| normalized(b) != normalized(&value)) {
+            problems.push(problem(
+                "frontmatter-mismatch",
+                format!("Frontmatter {}: {} disagrees with the BibTeX", field, value),
+            ));
+        }
+    }
+    problems
+}
+
+/// Every paper with problems, by title.
+pub fn find_problems(notes: &[Note]) -> Vec<PaperProblems> {
+    let mut found: Vec<PaperProblems> = notes
+        .iter()
+        .filter_map(|note| {
+            let problems = check_paper(note);
+            if problems.is_empty() {
+                return None;
+            }
+            Some(PaperProblems {
+                key: note.key.clone(),
+                title: note.title.clone(),
+                problems,
+                identifier: identifier(note, canonical_parsed(note).as_ref()),
+            })
+        })
+        .collect();
+    found.sort_by_key(|p| p.title.to_lowercase());
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixPlace {
+    Bibtex,
+    Frontmatter,
+}
+
+/// A proposed change to one field of a paper.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fix {
+    pub key: String,
+    pub place: FixPlace,
+    pub field: String,
+    #[serde(default)]
+    pub old: Option<String>,
+    /// None removes the field (frontmatter only).
+    pub new: Option<String>,
+}
+
+/// BibTeX field a paper's venue goes in.
+fn venue_field(parsed: &ParsedBibtex, bibtex: &str) -> &'static str {
+    let lower = bibtex.to_lowercase();
+    if lower.contains("booktitle") {
+        "booktitle"
+    } else if lower.contains("journal") {
+        "journal"
+    } else if matches!(parsed.entry_type.as_str(), "inproceedings" | "incollection" | "conference") {
+        "booktitle"
+    } else {
+        "journal"
+    }
+}
+
+/// Fixes that bring `note` in line with what `found` says about it.
+pub fn propose_fixes(note: &Note, found: &ExternalResult) -> Vec<Fix> {
+    let (Some(parsed), NoteType::Paper(ref paper)) = (canonical_parsed(note), &note.note_type) else {
+        return Vec::new();
+    };
+    let bibtex = paper.canonical_bibtex().cloned().unwrap_or_default();
+    let fix = |field: &str, old: Option<String>, new: String| Fix {
+        key: note.key.clone(),
+        place: FixPlace::Bibtex,
+        field: field.to_string(),
+        old,
+        new: Some(new),
+    };
+
+    let mut fixes = Vec::new();
+    if let Some(year) = found.year.filter(|y| *y > 0) {
+        if parsed.year != Some(year) {
+            fixes.push(fix("year", parsed.year.map(|y| y.to_string()), year.to_string()));
+        }
+    }
+    if let Some(authors) = found.authors.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+        let current = parsed.author.as_deref().map(str::trim).unwrap_or_default();
+        if current.is_empty() || authors_look_malformed(current) {
+            fixes.push(fix("author", parsed.author.clone(), authors.to_string()));
+        }
+    }
+    // arXiv calls everything "arXiv"; that isn't a venue worth writing
+    if let Some(venue) = found
+        .venue
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && !v.eq_ignore_ascii_case("arxiv"))
+    {
+        if parsed.venue.as_deref().is_none_or(|v| normalized(v) != normalized(venue)) {
+            fixes.push(fix(venue_field(&parsed, &bibtex), parsed.venue.clone(), venue.to_string()));
+        }
+    }
+
+    // Leftover frontmatter the loader ignores goes once the BibTeX is right
+    for field in LEGACY_FIELDS {
+        if let Some(value) = legacy_value(note, field) {
+            fixes.push(Fix {
+                key: note.key.clone(),
+                place: FixPlace::Frontmatter,
+                field: field.to_string(),
+                old: Some(value),
+                new: None,
+            });
+        }
+    }
+    fixes
+}
+
+/// Look `identifier` up on CrossRef or arXiv.
+async fn lookup(client: &reqwest::Client, identifier: &str) -> Option<ExternalResult> {
+    match identifier.split_once(':')? {
+        ("doi", doi) => query_crossref_api(client, doi).await,
+        ("arxiv", id) => query_arxiv_api(client, id).await,
+        _ => None,
+    }
+}
+
+/// Set `field` of the BibTeX entry `cite_key` in a note file, replacing its
+/// value or adding it at the end of the entry. The entry must span several
+/// lines, as Smart Add writes them.
+pub fn set_bibtex_field(content: &str, cite_key: &str, field: &str, value: &str) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let fm_end = frontmatter_end(&lines).ok_or("No frontmatter")?;
+    let start = (1..fm_end)
+        .find(|&i| {
+            let t = lines[i].trim();
+            t.starts_with('@') && t.split_once('{').is_some_and(|(_, rest)| rest.trim_start().starts_with(&format!("{},", cite_key)))
+        })
+        .ok_or_else(|| format!("BibTeX entry '{}' not found", cite_key))?;
+
+    let depth_change = |line: &str| line.matches('{').count() as i32 - line.matches('}').count() as i32;
+    let mut depth = 0;
+    let mut end = None;
+    for (i, line) in lines.iter().enumerate().take(fm_end).skip(start) {
+        depth += depth_change(line);
+        if depth <= 0 {
+            end = Some(i);
+            break;
+        }
+    }
+    let end = end.ok_or("BibTeX entry isn't closed")?;
+    if end == start {
+        return Err("BibTeX entry is on one line".to_string());
+    }
+
+    let value = value.replace(['{', '}'], "");
+    let is_field = |line: &str| {
+        let t = line.trim_start().to_lowercase();
+        t.strip_prefix(field).is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let indent_of = |line: &str| line[..line.len() - line.trim_start().len()].to_string();
+
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    if let Some(at) = (start + 1..end).find(|&i| is_field(lines[i])) {
+        // The value may run over several lines
+        let mut last = at;
+        let mut depth = depth_change(lines[at]);
+        while depth > 0 && last + 1 < end {
+            last += 1;
+            depth += depth_change(lines[last]);
+        }
+        let comma = if lines[last].trim_end().ends_with(',') { "," } else { "" };
+        let line = format!("{}{} = {{{}}}{}", indent_of(lines[at]), field, value, comma);
+        out.splice(at..=last, [line]);
+    } else {
+        if lines[end].trim() != "}" {
+            return Err("Can't add a field: the entry's closing brace shares a line".to_string());
+        }
+        let indent = if end > start + 1 { indent_of(lines[start + 1]) } else { format!("{}  ", indent_of(lines[start])) };
+        if end > start + 1 && !lines[end - 1].trim_end().ends_with(',') {
+            out[end - 1].push(',');
+        }
+        out.insert(end, format!("{}{} = {{{}}},", indent, field, value));
+    }
+
+    let mut result = out.join("\n");
+    if content.ends_with('\n') {
+        result.push('\n');
+    }
+    Ok(result)
+}
+
+/// Work out the new contents of every note the fixes touch, without
+/// writing anything.
+pub fn plan_fixes(
+    notes_dir: &std::path::Path,
+    notes_map: &HashMap<String, Note>,
+    fixes: &[Fix],
+) -> Result<Vec<BulkChange>, String> {
+    let mut by_note: Vec<(&Note, Vec<&Fix>)> = Vec::new();
+    for fix in fixes {
+        let note = notes_map.get(&fix.key).ok_or_else(|| format!("Note '{}' not found", fix.key))?;
+        match by_note.iter_mut().find(|(n, _)| n.key == note.key) {
+            Some((_, list)) => list.push(fix),
+            None => by_note.push((note, vec![fix])),
+        }
+    }
+
+    by_note
+        .into_iter()
+        .map(|(note, fixes)| {
+            let mut content = std::fs::read_to_string(notes_dir.join(&note.path))
+                .map_err(|e| format!("Failed to read {}: {}", note.path.display(), e))?;
+            let cite_key = canonical_parsed(note).map(|p| p.cite_key).unwrap_or_default();
+            for fix in fixes {
+                content = match (fix.place, fix.new.as_deref()) {
+                    (FixPlace::Bibtex, Some(value)) if !fix.field.contains(['=', '{', '}', '\n']) => {
+                        set_bibtex_field(&content, &cite_key, &fix.field, value)
+                    }
+                    (FixPlace::Frontmatter, None) if LEGACY_FIELDS.contains(&fix.field.as_str()) => {
+                        set_frontmatter_field(&content, &fix.field, None)
+                    }
+                    _ => Err(format!("Invalid fix of '{}'", fix.field)),
+                }
+                .map_err(|e| format!("{}: {}", note.title, e))?;
+            }
+            Ok(BulkChange {
+                key: note.key.clone(),
+                path: note.path.clone(),
+                content: Some(content),
+            })
+        })
+        .collect()
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ProposeRequest {
+    pub keys: Vec<String>,
+}
+
+/// `POST /api/metadata/propose`: `{keys}` → `{fixes, not_found}`. At most
+/// `MAX_LOOKUPS` papers are looked up.
+pub async fn propose(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<ProposeRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if req.keys.len() > MAX_LOOKUPS {
+        return (StatusCode::BAD_REQUEST, format!("At most {} papers at a time", MAX_LOOKUPS)).into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let mut fixes = Vec::new();
+    let mut not_found = Vec::new();
+    for key in &req.keys {
+        let Some(note) = notes_map.get(key) else {
+            return (StatusCode::NOT_FOUND, format!("Note '{}' not found", key)).into_response();
+        };
+        let found = match identifier(note, canonical_parsed(note).as_ref()) {
+            Some(id) => lookup(&state.http, &id).await,
+            None => None,
+        };
+        match found {
+            Some(found) => fixes.extend(propose_fixes(note, &found)),
+            None => not_found.push(key.clone()),
+        }
+    }
+    axum::Json(serde_json::json!({ "fixes": fixes, "not_found": not_found })).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplyRequest {
+    pub fixes: Vec<Fix>,
+}
+
+/// `POST /api/metadata/apply`: `{fixes}` → `{changed}`, one git commit.
+pub async fn apply(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<ApplyRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if req.fixes.is_empty() {
+        return (StatusCode::BAD_REQUEST, "No fixes given").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let changes = match plan_fixes(&state.notes_dir, &notes_map, &req.fixes) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let paths = match apply_bulk(&state.notes_dir, &notes_map, &changes, crate::timezone::now().naive_local()) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    state.invalidate_notes_cache();
+    for change in &changes {
+        state.reindex_graph_note(&change.key);
+    }
+    let notes_dir = state.notes_dir.clone();
+    let summary = format!("fixed metadata of {} papers", changes.len());
+    state.spawn_background(move || trash::commit_paths(&notes_dir, &paths, &summary));
+
+    axum::Json(serde_json::json!({ "changed": changes.len() })).into_response()
+}
+
+/// `GET /papers/metadata`: papers with metadata problems, and a way to fix them.
+pub async fn metadata_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let papers = find_problems(&notes);
+    let mut html = String::from("<h1>Paper metadata</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} papers with problems. Pick some (up to {} at a time) to look up their DOI or arXiv ID and review the fixes.</p>"#,
+        papers.len(),
+        MAX_LOOKUPS
+    ));
+    if papers.is_empty() {
+        html.push_str("<p>Every paper's metadata looks complete.</p>");
+        return Html(base_html("Paper metadata", &html, None, true)).into_response();
+    }
+
+    html.push_str(r#"<table class="time-table"><tr><th></th><th>Paper</th><th>Problems</th><th>Look up by</th></tr>"#);
+    for paper in &papers {
+        let problems: Vec<String> = paper.problems.iter().map(|p| html_escape(&p.message)).collect();
+        html.push_str(&format!(
+            r#"<tr><td><input type="checkbox" class="meta-pick" value="{}"{}></td><td><a href="/note/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+            paper.key,
+            if paper.identifier.is_some() { "" } else { " disabled" },
+            paper.key,
+            html_escape(&paper.title),
+            problems.join("<br>"),
+            paper.identifier.as_deref().map(html_escape).unwrap_or_else(|| "—".to_string()),
+        ));
+    }
+    html.push_str(
+        r#"</table>
+<p><button onclick="proposeFixes(this)">Look up fixes</button></p>
+<div id="meta-fixes"></div>
+<script>
+async function proposeFixes(btn) {
+    const keys = [...document.querySelectorAll('.meta-pick:checked')].map(c => c.value);
+    if (keys.length === 0) return;
+    btn.disabled = true;
+    const out = document.getElementById('meta-fixes');
+    out.textContent = 'Looking up ' + keys.length + ' papers...';
+    const resp = await fetch('/api/metadata/propose', {
+        method: 'POST',
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify({keys}),
+    });
+    btn.disabled = false;
+    if (!resp.ok) { out.textContent = 'Failed: ' + await resp.text(); return; }
+    const data = await resp.json();
+    window.metaFixes = data.fixes;
+    out.innerHTML = '';
+    if (data.not_found.length > 0) {
+        const p = document.createElement('p');
+        p.textContent = data.not_found.length + ' not found online.';
+        out.append(p);
+    }
+    if (data.fixes.length === 0) { out.append('No fixes to propose.'); return; }
+    const table = document.createElement('table');
+    table.className = 'time-table';
+    table.innerHTML = '<tr><th></th><th>Note</th><th>Field</th><th>Now</th><th>Proposed</th></tr>';
+    data.fixes.forEach((f, i) => {
+        const row = table.insertRow();
+        const cells = ['', f.key, (f.place === 'frontmatter' ? 'frontmatter ' : '') + f.field, f.old ?? '', f.new ?? '(remove)'];
+        cells.forEach((text, j) => {
+            const cell = row.insertCell();
+            if (j === 0) cell.innerHTML = '<input type="checkbox" class="meta-fix" checked value="' + i + '">';
+            else cell.textContent = text;
+        });
+    });
+    const apply = document.createElement('button');
+    apply.textContent = 'Apply selected';
+    apply.onclick = applyFixes;
+    out.append(table, apply);
+}
+
+async function applyFixes() {
+    const fixes = [...document.querySelectorAll('.meta-fix:checked')].map(c => window.metaFixes[c.value]);
+    if (fixes.length === 0) return;
+    const resp = await fetch('/api/metadata/apply', {
+        method: 'POST',
+        headers: {'Content-Type': 'application/json'},
+        body: JSON.stringify({fixes}),
+    });
+    if (resp.ok) window.location.reload();
+    else alert('Failed: ' + await resp.text());
+}
+</script>"#,
+    );
+
+    Html(base_html("Paper metadata", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_check_and_fix_metadata() {
+        let dir = TempDir::new("metadata");
+        let sloppy = "---\ntitle: Sloppy\nid: aaaaaaaaaaaa\ndoi: 10.1/x\nvenue: POPL\nbibtex: |\n  @inproceedings{smith2020,\n    title = {Sloppy},\n    author = {Smith, J.; Doe, A.}\n  }\n---\n\nBody.\n";
+        fs::write(dir.join("a.md"), sloppy).unwrap();
+        fs::write(
+            dir.join("b.md"),
+            "---\ntitle: Fine\nbibtex: |\n  @article{lee2019,\n    title = {Fine},\n    author = {Lee, K. and Park, S.},\n    year = {2019},\n    journal = {JFP}\n  }\n---\n",
+        )
+        .unwrap();
+        let notes = load_all_notes(&dir);
+
+        let found = find_problems(&notes);
+        assert_eq!(found.len(), 1);
+        let checks: Vec<&str> = found[0].problems.iter().map(|p| p.check).collect();
+        assert_eq!(checks, vec!["missing-year", "malformed-authors", "missing-venue", "frontmatter-mismatch"]);
+        assert_eq!(found[0].identifier.as_deref(), Some("doi:10.1/x"));
+
+        let note = notes.iter().find(|n| n.title == "Sloppy").unwrap();
+        let remote = ExternalResult {
+            title: "Sloppy".to_string(),
+            authors: Some("J. Smith and A. Doe".to_string()),
+            year: Some(2020),
+            venue: Some("POPL".to_string()),
+            bib_key: "smith2020sloppy".to_string(),
+            bibtex: None,
+            suggested_filename: String::new(),
+            source: "crossref".to_string(),
+        };
+        let fixes = propose_fixes(note, &remote);
+        let fields: Vec<&str> = fixes.iter().map(|f| f.field.as_str()).collect();
+        assert_eq!(fields, vec!["year", "author", "booktitle", "venue"]);
+        assert_eq!(fixes[3].place, FixPlace::Frontmatter);
+
+        let notes_map: HashMap<String, Note> = notes.iter().map(|n| (n.key.clone(), n.clone())).collect();
+        let changes = plan_fixes(&dir, &notes_map, &fixes).unwrap();
+        assert_eq!(
+            changes[0].content.as_deref().unwrap(),
+            "---\ntitle: Sloppy\nid: aaaaaaaaaaaa\ndoi: 10.1/x\nbibtex: |\n  @inproceedings{smith2020,\n    title = {Sloppy},\n    \
+             author = {J. Smith and A. Doe},\n    year = {2020},\n    booktitle = {POPL},\n  }\n---\n\nBody.\n"
+        );
+        assert!(set_bibtex_field("---\nbibtex: @misc{k, year={1}}\n---\n", "k", "year", "2").is_err());
+    }
+}
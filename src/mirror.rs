@@ -0,0 +1,195 @@
+//! Read-only mirror of another instance.
+//!
+//! With `[mirror] upstream` set, the server runs read-only and keeps the
+//! notes directory a copy of the upstream content repository: `prepare`
+//! clones it at startup when the directory isn't a repository yet, and
+//! `poll` fetches every `interval_minutes` and resets to what it fetched,
+//! so rewritten upstream history is followed too. When the commit changes,
+//! the notes cache is dropped and the graph index reconciled. PDFs are not
+//! mirrored.
+//!
+//! `/healthz` reports the mirror's last pull, with the upstream and any
+//! error shown only when logged in.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::config::MirrorConfig;
+use crate::graph_index;
+use crate::AppState;
+
+/// How the last pull went, shown on `/healthz`.
+#[derive(Debug, Clone, Default)]
+pub struct MirrorStatus {
+    pub upstream: String,
+    pub last_pull: Option<DateTime<Utc>>,
+    pub commit: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl MirrorStatus {
+    /// The `mirror` object on `/healthz`; the upstream URL and the error
+    /// only with `detailed`.
+    pub fn health(&self, detailed: bool) -> serde_json::Value {
+        let mut health = serde_json::json!({
+            "ok": self.last_error.is_none(),
+            "last_pull": self.last_pull,
+            "commit": self.commit,
+        });
+        if detailed {
+            health["upstream"] = serde_json::json!(self.upstream);
+            health["last_error"] = serde_json::json!(self.last_error);
+        }
+        health
+    }
+}
+
+static STATUS: RwLock<Option<MirrorStatus>> = RwLock::new(None);
+
+/// The mirror's status, when this server is one.
+pub fn status() -> Option<MirrorStatus> {
+    STATUS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("git {}: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Clone the upstream into `notes_dir` unless it is already a repository.
+pub fn prepare(config: &MirrorConfig, notes_dir: &Path) -> Result<(), String> {
+    let Some(ref upstream) = config.upstream else {
+        return Ok(());
+    };
+    if notes_dir.join(".git").exists() {
+        return Ok(());
+    }
+    if std::fs::read_dir(notes_dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(format!("{} is not empty and not a git repository; cannot mirror into it", notes_dir.display()));
+    }
+    let dir = notes_dir.to_string_lossy();
+    let mut args = vec!["clone", "--quiet"];
+    if let Some(ref branch) = config.branch {
+        args.extend(["--branch", branch]);
+    }
+    args.extend(["--", upstream, &dir]);
+    git(Path::new("."), &args)?;
+    tracing::info!(upstream = %upstream, "Cloned mirror");
+    Ok(())
+}
+
+/// Fetch the upstream and reset to it. Returns the new commit when it
+/// changed.
+pub fn pull(config: &MirrorConfig, notes_dir: &Path) -> Result<Option<String>, String> {
+    let Some(ref upstream) = config.upstream else {
+        return Ok(None);
+    };
+    let branch = config.branch.as_deref().unwrap_or("HEAD");
+    git(notes_dir, &["fetch", "--quiet", upstream, branch])?;
+    let fetched = git(notes_dir, &["rev-parse", "FETCH_HEAD"])?;
+    if git(notes_dir, &["rev-parse", "HEAD"]).ok().as_ref() == Some(&fetched) {
+        return Ok(None);
+    }
+    git(notes_dir, &["reset", "--quiet", "--hard", &fetched])?;
+    Ok(Some(fetched))
+}
+
+/// Pull every `interval_minutes`. Spawned by the server when mirroring.
+pub async fn poll(state: Arc<AppState>) {
+    let config = state.config.mirror.clone();
+    let upstream = config.upstream.clone().unwrap_or_default();
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_minutes * 60));
+    loop {
+        interval.tick().await;
+        let (mirror, notes_dir) = (config.clone(), state.notes_dir.clone());
+        let result = tokio::task::spawn_blocking(move || pull(&mirror, &notes_dir))
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()));
+
+        let mut status = status().unwrap_or_else(|| MirrorStatus { upstream: upstream.clone(), ..Default::default() });
+        match result {
+            Ok(changed) => {
+                status.last_pull = Some(Utc::now());
+                status.last_error = None;
+                if let Some(commit) = changed {
+                    tracing::info!(commit = %commit, "Mirror updated");
+                    status.commit = Some(commit);
+                    state.invalidate_notes_cache();
+                    if let Err(e) = graph_index::reconcile(&state.db, &state.load_notes()) {
+                        tracing::error!(error = %e, "Graph index reconciliation failed");
+                    }
+                } else if status.commit.is_none() {
+                    status.commit = git(&state.notes_dir, &["rev-parse", "HEAD"]).ok();
+                }
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Mirror pull failed");
+                status.last_error = Some(e);
+            }
+        }
+        *STATUS.write().unwrap_or_else(|e| e.into_inner()) = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::fs;
+
+    fn commit(dir: &Path, file: &str, text: &str) {
+        fs::write(dir.join(file), text).unwrap();
+        git(dir, &["add", "-A"]).unwrap();
+        git(dir, &["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-qm", file]).unwrap();
+    }
+
+    #[test]
+    fn test_clone_and_pull() {
+        let root = TempDir::new("mirror");
+        let upstream = root.join("upstream");
+        fs::create_dir_all(&upstream).unwrap();
+        git(&upstream, &["init", "-q"]).unwrap();
+        commit(&upstream, "a.md", "---\ntitle: A\n---\none\n");
+
+        let config = MirrorConfig { upstream: Some(upstream.to_string_lossy().into_owned()), ..Default::default() };
+        let mirror = root.join("mirror/content");
+        prepare(&config, &mirror).unwrap();
+        assert!(mirror.join("a.md").exists());
+        assert_eq!(pull(&config, &mirror).unwrap(), None);
+
+        commit(&upstream, "b.md", "---\ntitle: B\n---\ntwo\n");
+        let head = git(&upstream, &["rev-parse", "HEAD"]).unwrap();
+        assert_eq!(pull(&config, &mirror).unwrap(), Some(head));
+        assert!(mirror.join("b.md").exists());
+
+        // Rewritten upstream history replaces the mirror's
+        git(&upstream, &["reset", "-q", "--hard", "HEAD~1"]).unwrap();
+        assert!(pull(&config, &mirror).unwrap().is_some());
+        assert!(!mirror.join("b.md").exists());
+
+        let occupied = root.join("occupied");
+        fs::create_dir_all(&occupied).unwrap();
+        fs::write(occupied.join("x.md"), "x").unwrap();
+        assert!(prepare(&config, &occupied).is_err());
+
+        let failing = MirrorStatus {
+            upstream: "https://token@git.example.com/notes.git".to_string(),
+            last_error: Some("git fetch: could not resolve host".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(failing.health(false)["ok"], false);
+        assert!(!failing.health(false).to_string().contains("token"));
+        assert_eq!(failing.health(true)["upstream"], failing.upstream);
+    }
+}
@@ -3,7 +3,7 @@
 //! This module contains all the core data structures used throughout the application,
 //! including notes, papers, time tracking, graph visualization, and smart add features.
 
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -11,25 +11,104 @@ use std::path::PathBuf;
 // Core Note Types
 // ============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Note {
     pub key: String,
     pub path: PathBuf,
     pub title: String,
     pub date: Option<NaiveDate>,
+    /// Creation date from `created:`; unlike `modified`, not reset by syncs.
+    pub created: Option<NaiveDate>,
+    /// Deadline from `due:`.
+    pub due: Option<NaiveDate>,
+    /// Local time of a reminder, from `remind:`.
+    pub remind: Option<NaiveDateTime>,
     pub note_type: NoteType,
-    pub parent_key: Option<String>,
+    /// Keys from `parent:`/`parents:`; a note may sit under several.
+    pub parents: Vec<String>,
+    /// Previous note in a recurring series (`prev:`).
+    pub prev_key: Option<String>,
     pub time_entries: Vec<TimeEntry>,
     pub raw_content: String,
     pub full_file_content: String,
     pub modified: DateTime<Utc>,
     pub pdf: Option<String>,
     pub hidden: bool,
+    pub tags: Vec<String>,
+    /// Lifecycle status from `status:`, e.g. `archived`.
+    pub status: Option<String>,
+    /// Hourly billing rate for consulting notes (inherited by children).
+    pub hourly_rate: Option<f64>,
+    /// Reading priority from `priority:`; 1 comes first.
+    pub priority: Option<u8>,
+    /// Estimated reading time from `est_minutes:`.
+    pub est_minutes: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Orders for note lists: by default titles A-Z, dates newest first and
+/// the most time logged first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteSort {
+    #[default]
+    Title,
+    /// `created:`, falling back to the file's mtime.
+    Created,
+    /// File mtime.
+    Modified,
+    /// Minutes in `time:` entries.
+    #[value(name = "time_total")]
+    TimeTotal,
+}
+
+impl NoteSort {
+    pub const ALL: [NoteSort; 4] = [NoteSort::Modified, NoteSort::Created, NoteSort::Title, NoteSort::TimeTotal];
+
+    /// The `sort=` value.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NoteSort::Title => "title",
+            NoteSort::Created => "created",
+            NoteSort::Modified => "modified",
+            NoteSort::TimeTotal => "time_total",
+        }
+    }
+
+    pub fn default_order(self) -> SortOrder {
+        match self {
+            NoteSort::Title => SortOrder::Asc,
+            _ => SortOrder::Desc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "asc",
+            SortOrder::Desc => "desc",
+        }
+    }
+
+    pub fn reversed(self) -> SortOrder {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum NoteType {
+    #[default]
     Note,
     Paper(PaperMeta),
 }
@@ -44,14 +123,50 @@ pub struct PaperMeta {
     pub canonical_key: Option<String>,
     /// External sources (arxiv, doi, url) for the paper
     pub sources: Vec<PaperSource>,
+    /// Other releases of the same work, from `versions:`
+    #[serde(default)]
+    pub versions: Vec<PaperVersion>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PaperSource {
-    pub source_type: String, // "arxiv", "doi", "url"
+    pub source_type: String, // "arxiv", "doi", "url", "wayback"
     pub identifier: String,  // The arxiv ID, DOI, or URL
 }
 
+impl PaperSource {
+    /// The identifier for comparison: lowercased, and for arXiv without an
+    /// `arXiv:` prefix or `vN` revision, so every version of a preprint
+    /// matches.
+    pub fn match_id(&self) -> String {
+        source_match_id(&self.source_type, &self.identifier)
+    }
+}
+
+/// `PaperSource::match_id` for a bare type and identifier.
+pub fn source_match_id(source_type: &str, identifier: &str) -> String {
+    let id = identifier.trim().to_lowercase();
+    if source_type != "arxiv" {
+        return id;
+    }
+    let id = id.strip_prefix("arxiv:").unwrap_or(&id);
+    match id.rsplit_once('v') {
+        Some((base, rev)) if !base.is_empty() && !rev.is_empty() && rev.chars().all(|c| c.is_ascii_digit()) => {
+            base.to_string()
+        }
+        _ => id.to_string(),
+    }
+}
+
+/// A `versions:` item: one release of the paper (an arXiv revision, the
+/// conference or journal DOI), optionally labelled, e.g. `camera-ready`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PaperVersion {
+    #[serde(default)]
+    pub label: Option<String>,
+    pub source: PaperSource,
+}
+
 // ============================================================================
 // Effective Metadata (derived from BibTeX)
 // ============================================================================
@@ -59,10 +174,20 @@ pub struct PaperSource {
 #[derive(Debug, Clone, Default)]
 pub struct EffectivePaperMeta {
     pub bib_key: String,
+    /// Lowercase BibTeX entry type, e.g. `inproceedings`
+    pub entry_type: String,
     pub title: Option<String>,
+    /// `author`, or `editor` for edited volumes
     pub authors: Option<String>,
+    pub editors: Option<String>,
+    /// `year`, or the year of a BibLaTeX `date`
     pub year: Option<i32>,
+    /// `journal`/`booktitle`/`howpublished`, else the institution or publisher
     pub venue: Option<String>,
+    pub publisher: Option<String>,
+    pub volume: Option<String>,
+    pub number: Option<String>,
+    pub pages: Option<String>,
 }
 
 impl PaperMeta {
@@ -72,11 +197,8 @@ impl PaperMeta {
         use crate::notes::parse_bibtex;
 
         let mut effective = EffectivePaperMeta {
-            bib_key: String::new(),
             title: Some(note_title.to_string()),
-            authors: None,
-            year: None,
-            venue: None,
+            ..Default::default()
         };
 
         if self.bibtex_entries.is_empty() {
@@ -99,12 +221,18 @@ impl PaperMeta {
 
         if let Some(parsed) = parse_bibtex(canonical_bibtex) {
             effective.bib_key = parsed.cite_key;
+            effective.entry_type = parsed.entry_type;
             if parsed.title.is_some() {
                 effective.title = parsed.title;
             }
-            effective.authors = parsed.author;
+            effective.authors = parsed.author.or_else(|| parsed.editor.clone());
+            effective.editors = parsed.editor;
             effective.year = parsed.year;
-            effective.venue = parsed.venue;
+            effective.venue = parsed.venue.or(parsed.institution).or_else(|| parsed.publisher.clone());
+            effective.publisher = parsed.publisher;
+            effective.volume = parsed.volume;
+            effective.number = parsed.number;
+            effective.pages = parsed.pages;
         }
 
         effective
@@ -131,6 +259,11 @@ impl PaperMeta {
         }
     }
 
+    /// `sources`, then the source of every version.
+    pub fn all_sources(&self) -> impl Iterator<Item = &PaperSource> {
+        self.sources.iter().chain(self.versions.iter().map(|v| &v.source))
+    }
+
     /// Returns all parsed BibTeX entries
     pub fn all_bibtex_parsed(&self) -> Vec<crate::notes::ParsedBibtex> {
         use crate::notes::parse_bibtex;
@@ -140,6 +273,19 @@ impl PaperMeta {
     }
 }
 
+// ============================================================================
+// Bibliography Export
+// ============================================================================
+
+/// Filters for `/bibliography.bib` and `notes bib`. Years are publication
+/// years from the paper's BibTeX.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BibFilter {
+    pub tag: Option<String>,
+    pub since: Option<i32>,
+    pub until: Option<i32>,
+}
+
 // ============================================================================
 // Time Tracking
 // ============================================================================
@@ -176,6 +322,66 @@ impl std::fmt::Display for TimeCategory {
     }
 }
 
+impl TimeCategory {
+    /// Parse a category name as written in frontmatter. Names are
+    /// case-insensitive; anything that isn't a built-in becomes `Other`.
+    pub fn from_name(name: &str) -> Self {
+        let name = name.trim().to_lowercase();
+        match name.as_str() {
+            "programming" => TimeCategory::Programming,
+            "teaching" => TimeCategory::Teaching,
+            "reading" => TimeCategory::Reading,
+            "writing" => TimeCategory::Writing,
+            "service" => TimeCategory::Service,
+            _ => TimeCategory::Other(name),
+        }
+    }
+
+    /// CSS class suffix used for this category (`cat-{slug}`).
+    pub fn css_slug(&self) -> String {
+        category_slug(&self.to_string())
+    }
+}
+
+/// Reduce a category name to characters safe for a CSS class name.
+pub fn category_slug(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+/// A user-configured time category and the color used to display it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeCategoryDef {
+    pub name: String,
+    pub color: String,
+    /// Hours a week to aim for, shown by the weekly review.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly_hours: Option<f64>,
+}
+
+// ============================================================================
+// Tasks
+// ============================================================================
+
+/// A markdown checkbox (`- [ ]` or `- [x]`) in a note's body.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Task {
+    pub note_key: String,
+    pub note_title: String,
+    /// Line of the checkbox in the note's file, from 1.
+    pub line: usize,
+    /// The text without its `@due(...)` annotation.
+    pub text: String,
+    pub done: bool,
+    /// From an inline `@due(YYYY-MM-DD)`.
+    pub due: Option<NaiveDate>,
+    /// Tags of the note the task is in.
+    pub tags: Vec<String>,
+}
+
 // ============================================================================
 // Git and Search
 // ============================================================================
@@ -215,7 +421,7 @@ pub struct GraphNode {
     pub primary_category: Option<String>,
     pub in_degree: usize,            // Incoming links
     pub out_degree: usize,           // Outgoing links
-    pub parent: Option<String>,
+    pub parents: Vec<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub authors: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -383,12 +589,8 @@ impl GraphQuery {
         if self.hubs_only {
             parts.push("hubs only".to_string());
         }
-        if self.path_start.is_some() && self.path_end.is_some() {
-            parts.push(format!(
-                "path {}->{}",
-                self.path_start.as_ref().unwrap(),
-                self.path_end.as_ref().unwrap()
-            ));
+        if let (Some(start), Some(end)) = (&self.path_start, &self.path_end) {
+            parts.push(format!("path {}->{}", start, end));
         }
 
         if let Some(ref a) = self.author_filter {
@@ -466,6 +668,10 @@ pub struct SmartAddCreateRequest {
     pub filename: String,
     pub arxiv_id: Option<String>,
     pub doi: Option<String>,
+    /// Name of a note template for the body; the default paper skeleton
+    /// when absent.
+    #[serde(default)]
+    pub template: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -473,6 +679,8 @@ pub struct QuickNoteRequest {
     pub title: String,
     pub date: Option<String>,
     pub subdirectory: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -482,6 +690,224 @@ pub struct AttachSourceRequest {
     pub identifier: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmartExtractRequest {
+    /// Pasted text citing one or more papers.
+    pub text: String,
+}
+
+/// One identifier found in pasted text, with what it resolved to.
+#[derive(Debug, Clone, Serialize)]
+pub struct PastedReference {
+    /// "arxiv" or "doi"
+    pub input_type: String,
+    pub identifier: String,
+    /// A note that has the identifier, or whose title matches the paper's.
+    pub local_match: Option<LocalMatch>,
+    pub external_result: Option<ExternalResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SmartExtractResult {
+    pub references: Vec<PastedReference>,
+    /// Identifiers found beyond the lookup limit, which were left out.
+    pub skipped: usize,
+    pub error: Option<String>,
+}
+
+// ============================================================================
+// Time Import Data Structures
+// ============================================================================
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeImportRequest {
+    /// Raw CSV export from Toggl Track or Clockify.
+    pub csv: String,
+    /// Report what would be imported without writing any notes.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct TimeImportResult {
+    pub imported: Vec<TimeImportMatch>,
+    /// Rows skipped because the note already has an identical entry.
+    pub duplicates: usize,
+    pub unmatched: Vec<TimeImportUnmatched>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeImportMatch {
+    /// 1-based CSV line number (header is line 1).
+    pub row: usize,
+    pub note_key: String,
+    pub note_title: String,
+    pub entry: TimeEntry,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimeImportUnmatched {
+    pub row: usize,
+    pub project: String,
+    pub reason: String,
+}
+
+// ============================================================================
+// Outliner Import Data Structures
+// ============================================================================
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OutlineImportRequest {
+    /// Roam Research JSON export, or Logseq JSON/EDN export.
+    pub data: String,
+    /// Report what would be created without writing any notes.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct OutlineImportResult {
+    /// Detected export format: `roam` or `logseq`.
+    pub format: String,
+    pub created: Vec<OutlineImportPage>,
+    pub skipped: Vec<OutlineImportSkipped>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlineImportPage {
+    pub title: String,
+    pub key: String,
+    pub path: String,
+    /// Set for daily/journal pages.
+    pub date: Option<NaiveDate>,
+    pub blocks: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlineImportSkipped {
+    pub title: String,
+    pub reason: String,
+}
+
+// ============================================================================
+// Corpus Dump Data Structures
+// ============================================================================
+
+/// One note in `GET /api/export.json`. Field names follow the frontmatter
+/// keys; everything but `path` and `title` is optional on import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusNote {
+    /// On import, kept as the note's `id:` unless empty or equal to the
+    /// key derived from `path`.
+    #[serde(default)]
+    pub key: String,
+    /// Path relative to the notes directory, e.g. `projects/datalog.md`.
+    pub path: String,
+    pub title: String,
+    #[serde(default)]
+    pub created: Option<NaiveDate>,
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    /// Local time, e.g. `2024-01-31T09:00:00`.
+    #[serde(default)]
+    pub remind: Option<NaiveDateTime>,
+    /// `note` or `paper`.
+    #[serde(rename = "type", default = "default_corpus_note_type")]
+    pub note_type: String,
+    /// Key of the (first) parent note.
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Keys of every parent note, `parent` first.
+    #[serde(default)]
+    pub parents: Vec<String>,
+    /// Key of the previous note in a recurring series.
+    #[serde(default)]
+    pub prev: Option<String>,
+    /// e.g. `archived`.
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub hidden: bool,
+    #[serde(default)]
+    pub pdf: Option<String>,
+    #[serde(default)]
+    pub rate: Option<f64>,
+    /// 1 (first) to 9.
+    #[serde(default)]
+    pub priority: Option<u8>,
+    #[serde(default)]
+    pub est_minutes: Option<u32>,
+    #[serde(default)]
+    pub bibtex: Vec<String>,
+    #[serde(default)]
+    pub canonical_key: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<PaperSource>,
+    #[serde(default)]
+    pub versions: Vec<PaperVersion>,
+    #[serde(default)]
+    pub time: Vec<CorpusTimeEntry>,
+    /// Markdown after the frontmatter.
+    #[serde(default)]
+    pub body: String,
+    /// File modification time; ignored on import.
+    #[serde(default)]
+    pub modified: Option<DateTime<Utc>>,
+}
+
+fn default_corpus_note_type() -> String {
+    "note".to_string()
+}
+
+/// A time entry with its category as written in frontmatter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusTimeEntry {
+    pub date: NaiveDate,
+    pub minutes: u32,
+    pub category: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorpusDump {
+    pub version: u32,
+    pub exported: DateTime<Utc>,
+    pub notes: Vec<CorpusNote>,
+}
+
+/// Body of `POST /api/import/json`. A `CorpusDump` is accepted as-is.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CorpusImportRequest {
+    pub notes: Vec<CorpusNote>,
+    /// Report what would be written without touching any files.
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Replace notes that already exist at the same path.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct CorpusImportResult {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<CorpusImportSkipped>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorpusImportSkipped {
+    pub path: String,
+    pub reason: String,
+}
+
 // ============================================================================
 // BibTeX Import Data Structures
 // ============================================================================
@@ -0,0 +1,230 @@
+//! Moving notes within the content directory.
+//!
+//! A note without an `id:` field is keyed by a hash of its path, so moving
+//! the file changes its key and breaks every `[@key]` crosslink and
+//! `parent:` field that pointed at it. `plan_move` works out the new paths
+//! and keys plus the rewrites the rest of the corpus needs; `apply_move`
+//! performs them on disk. Notes with an `id:` keep their key.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::formats::{check_writable, is_note_file};
+use crate::models::Note;
+use crate::notes::{generate_key, parse_frontmatter, rewrite_note_keys};
+use crate::validate_path_within;
+
+/// A single note affected by a move.
+#[derive(Debug, Clone)]
+pub struct MovedNote {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub old_key: String,
+    pub new_key: String,
+}
+
+/// Everything a move changes. Paths are relative to the notes directory.
+#[derive(Debug, Clone)]
+pub struct MovePlan {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub moved: Vec<MovedNote>,
+    /// Notes whose content changes, as (path after the move, new content).
+    pub rewrites: Vec<(PathBuf, String)>,
+    /// Number of crosslinks and parent fields rewritten.
+    pub references: usize,
+}
+
+impl MovePlan {
+    /// Old key → new key for every moved note whose key changes.
+    pub fn renames(&self) -> HashMap<String, String> {
+        self.moved
+            .iter()
+            .filter(|m| m.old_key != m.new_key)
+            .map(|m| (m.old_key.clone(), m.new_key.clone()))
+            .collect()
+    }
+}
+
+/// Interpret `path` relative to the notes directory. A leading notes
+/// directory prefix (e.g. `content/`) is accepted; `..` is not.
+pub fn relative_path(notes_dir: &Path, path: &Path) -> Result<PathBuf, String> {
+    let stripped = path.strip_prefix(notes_dir).unwrap_or(path);
+    let mut rel = PathBuf::new();
+    for component in stripped.components() {
+        match component {
+            Component::Normal(part) => rel.push(part),
+            Component::CurDir => {}
+            _ => return Err(format!("{} is outside the notes directory", path.display())),
+        }
+    }
+    Ok(rel)
+}
+
+/// Whether `path` was written with a trailing slash (`papers/`).
+fn names_directory(path: &Path) -> bool {
+    path.as_os_str().to_string_lossy().ends_with(std::path::MAIN_SEPARATOR)
+}
+
+/// Plan moving the note or directory `from` to `to`. Like `mv`, moving into
+/// an existing directory (or one named with a trailing slash) keeps the
+/// source's file name. Refuses to overwrite.
+pub fn plan_move(notes_dir: &Path, notes: &[Note], from: &Path, to_arg: &Path) -> Result<MovePlan, String> {
+    let from = relative_path(notes_dir, from)?;
+    let mut to = relative_path(notes_dir, to_arg)?;
+    let Some(file_name) = from.file_name() else {
+        return Err("Cannot move the notes directory itself".to_string());
+    };
+    let from_full = notes_dir.join(&from);
+    let is_dir = fs::symlink_metadata(&from_full)
+        .map_err(|_| format!("{} does not exist", from.display()))?
+        .is_dir();
+    if names_directory(to_arg) || notes_dir.join(&to).is_dir() {
+        to.push(file_name);
+    }
+
+    if is_dir {
+        if to.starts_with(&from) {
+            return Err(format!("Cannot move {} into itself", from.display()));
+        }
+    } else {
+        if !is_note_file(&from) {
+            return Err(format!("{} is not a note (.md file)", from.display()));
+        }
+        if from.extension() != to.extension() {
+            return Err(format!("Destination {} must keep the extension of {}", to.display(), from.display()));
+        }
+    }
+    if notes_dir.join(&to).exists() {
+        return Err(format!("{} already exists", to.display()));
+    }
+
+    let moved: Vec<MovedNote> = notes
+        .iter()
+        .filter_map(|note| {
+            let new_path = if is_dir {
+                to.join(note.path.strip_prefix(&from).ok()?)
+            } else if note.path == from {
+                to.clone()
+            } else {
+                return None;
+            };
+            let pinned = parse_frontmatter(&note.full_file_content).0.id.is_some();
+            Some(MovedNote {
+                from: note.path.clone(),
+                old_key: note.key.clone(),
+                new_key: if pinned { note.key.clone() } else { generate_key(&new_path) },
+                to: new_path,
+            })
+        })
+        .collect();
+    if moved.is_empty() {
+        return Err(format!("No notes found at {}", from.display()));
+    }
+
+    let plan = MovePlan {
+        from,
+        to,
+        moved,
+        rewrites: vec![],
+        references: 0,
+    };
+    let renames = plan.renames();
+    let new_paths: HashMap<&Path, &Path> = plan
+        .moved
+        .iter()
+        .map(|m| (m.from.as_path(), m.to.as_path()))
+        .collect();
+
+    let mut rewrites = vec![];
+    let mut references = 0;
+    for note in notes.iter().filter(|n| check_writable(&n.path).is_ok()) {
+        let (content, count) = rewrite_note_keys(&note.full_file_content, &renames);
+        if count > 0 {
+            let path = new_paths.get(note.path.as_path()).copied().unwrap_or(&note.path);
+            rewrites.push((path.to_path_buf(), content));
+            references += count;
+        }
+    }
+    rewrites.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(MovePlan {
+        rewrites,
+        references,
+        ..plan
+    })
+}
+
+/// Rename `plan.from` to `plan.to` (one rename, so a directory moves with
+/// any attachments inside it) and write the rewritten notes.
+pub fn apply_move(notes_dir: &Path, plan: &MovePlan) -> Result<(), String> {
+    let base = notes_dir.to_path_buf();
+    let from = validate_path_within(&base, &notes_dir.join(&plan.from))?;
+    let to = validate_path_within(&base, &notes_dir.join(&plan.to))?;
+    fs::rename(&from, &to).map_err(|e| {
+        format!("Failed to move {} to {}: {}", plan.from.display(), plan.to.display(), e)
+    })?;
+    for (path, content) in &plan.rewrites {
+        crate::encryption::write_file(&notes_dir.join(path), content).map_err(|e| {
+            format!("Moved {} but failed to update {}: {}", plan.from.display(), path.display(), e)
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    fn scratch_dir() -> TempDir {
+        let dir = TempDir::new("move");
+        fs::create_dir_all(dir.join("papers")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_move_directory() {
+        let dir = scratch_dir();
+        let paper_key = generate_key(Path::new("papers/datalog.md"));
+        fs::write(dir.join("papers/datalog.md"), "---\ntitle: Datalog\n---\nBody\n").unwrap();
+        fs::write(dir.join("papers/figure.png"), "png").unwrap();
+        fs::write(dir.join("papers/pinned.md"), "---\ntitle: Pinned\nid: 3fa9c01b22d4\n---\n").unwrap();
+        fs::write(
+            dir.join("papers/child.md"),
+            format!("---\ntitle: Child\nparent: {}\n---\n", paper_key),
+        )
+        .unwrap();
+        fs::write(dir.join("index.md"), format!("---\ntitle: Index\n---\n[@{}]\n", paper_key)).unwrap();
+        let notes = load_all_notes(&dir);
+
+        let plan = plan_move(&dir, &notes, Path::new("papers/"), Path::new("reading")).unwrap();
+        let new_key = generate_key(Path::new("reading/datalog.md"));
+        assert_eq!(plan.moved.len(), 3);
+        assert_eq!(plan.renames().len(), 2);
+        assert_eq!(plan.renames()[&paper_key], new_key);
+        assert!(plan.moved.iter().any(|m| m.new_key == "3fa9c01b22d4"));
+        assert_eq!(plan.references, 2);
+        assert_eq!(plan.rewrites[0].0, PathBuf::from("index.md"));
+        assert_eq!(plan.rewrites[1].0, PathBuf::from("reading/child.md"));
+
+        apply_move(&dir, &plan).unwrap();
+        assert!(!dir.join("papers").exists());
+        assert!(dir.join("reading/figure.png").exists());
+        let child = fs::read_to_string(dir.join("reading/child.md")).unwrap();
+        assert!(child.contains(&format!("parent: {}", new_key)));
+        let index = fs::read_to_string(dir.join("index.md")).unwrap();
+        assert!(index.contains(&format!("[@{}]", new_key)));
+
+        let notes = load_all_notes(&dir);
+        assert!(plan_move(&dir, &notes, Path::new("index.md"), Path::new("reading/child.md")).is_err());
+        assert!(plan_move(&dir, &notes, Path::new("index.md"), Path::new("../index.md")).is_err());
+        assert!(plan_move(&dir, &notes, Path::new("reading"), Path::new("reading/sub")).is_err());
+        let into_dir = plan_move(&dir, &notes, Path::new("index.md"), Path::new("reading")).unwrap();
+        assert_eq!(into_dir.to, PathBuf::from("reading/index.md"));
+        let into_new_dir = plan_move(&dir, &notes, Path::new("index.md"), Path::new("archive/")).unwrap();
+        assert_eq!(into_new_dir.to, PathBuf::from("archive/index.md"));
+    }
+}
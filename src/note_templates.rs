@@ -0,0 +1,194 @@
+//! User-defined note templates.
+//!
+//! A template is a markdown file in the templates directory (`templates/`
+//! by default), named by its file stem: `meeting.md` is the `meeting`
+//! template. `{{title}}`, `{{date}}` and `{{bib_key}}` are replaced when a
+//! note is created from it. A template may carry frontmatter of its own
+//! (tags, say); its fields are kept unless the app sets the same field.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::auth::is_logged_in;
+use crate::notes::is_valid_note_id;
+use crate::AppState;
+
+/// Values substituted for a template's placeholders.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVars<'a> {
+    pub title: &'a str,
+    pub date: &'a str,
+    pub bib_key: &'a str,
+}
+
+/// Names of the templates in `dir`, sorted.
+pub fn list_templates(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .filter(|name| is_valid_note_id(name))
+        .collect();
+    names.sort();
+    names
+}
+
+/// The text of the template called `name`.
+pub fn load_template(dir: &Path, name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if !is_valid_note_id(name) {
+        return Err(format!("Unknown template '{}'", name));
+    }
+    fs::read_to_string(dir.join(format!("{}.md", name))).map_err(|_| format!("Unknown template '{}'", name))
+}
+
+/// Load the template named in a request, if any. An empty name means none.
+pub fn load_optional(dir: &Path, name: Option<&str>) -> Result<Option<String>, String> {
+    match name.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(name) => load_template(dir, name).map(Some),
+    }
+}
+
+/// Replace `{{title}}`, `{{date}}` and `{{bib_key}}` (spaces inside the
+/// braces allowed). Other `{{...}}` text is left alone.
+pub fn render_template(template: &str, vars: &TemplateVars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            out.push_str(&rest[start..]);
+            return out;
+        };
+        match after[..end].trim() {
+            "title" => out.push_str(vars.title),
+            "date" => out.push_str(vars.date),
+            "bib_key" => out.push_str(vars.bib_key),
+            _ => out.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Top-level frontmatter entries: each field line with the indented lines
+/// that continue it.
+fn frontmatter_entries(lines: &[&str]) -> Vec<(String, Vec<String>)> {
+    let mut entries: Vec<(String, Vec<String>)> = Vec::new();
+    for line in lines {
+        let continues = line.starts_with(' ') || line.starts_with('\t');
+        match entries.last_mut() {
+            Some((_, entry)) if continues => entry.push(line.to_string()),
+            _ => {
+                let key = line.split_once(':').map(|(k, _)| k).unwrap_or(line);
+                entries.push((key.trim().to_lowercase(), vec![line.to_string()]));
+            }
+        }
+    }
+    entries
+}
+
+/// Build a new note's file from the frontmatter the app generated and a
+/// template. `frontmatter` is the opening `---` line and the generated
+/// fields, without the closing `---`.
+pub fn instantiate(frontmatter: &str, template: &str, vars: &TemplateVars) -> String {
+    let rendered = render_template(template, vars);
+    let lines: Vec<&str> = rendered.lines().collect();
+    let (fields, body) = match lines.first().map(|l| l.trim()) {
+        Some("---") => match lines.iter().skip(1).position(|l| l.trim() == "---") {
+            Some(end) => (&lines[1..end + 1], lines[end + 2..].join("\n")),
+            None => (&lines[..0], rendered.clone()),
+        },
+        _ => (&lines[..0], rendered.clone()),
+    };
+
+    let generated: Vec<&str> = frontmatter.lines().skip(1).collect();
+    let taken: HashSet<String> = frontmatter_entries(&generated).into_iter().map(|(k, _)| k).collect();
+    let mut out = frontmatter.to_string();
+    for (key, entry) in frontmatter_entries(fields) {
+        if !taken.contains(&key) {
+            for line in entry {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+    }
+    out.push_str("---\n\n");
+    let body = body.trim_start_matches('\n');
+    out.push_str(body);
+    if !body.is_empty() && !body.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /api/templates`: template names for the pickers.
+pub async fn list_templates_api(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    axum::Json(list_templates(&state.config.templates_dir)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const MEETING: &str = "---\ntitle: ignored\ntags: [meeting]\nattendees:\n  - me\n---\n\n# {{ title }} ({{date}})\n\n## Agenda\n\nSee {{bib_key}}; {{unknown}} stays.\n";
+
+    #[test]
+    fn test_instantiate_template() {
+        let vars = TemplateVars {
+            title: "Weekly sync",
+            date: "2026-10-17",
+            bib_key: "smith2024",
+        };
+        let out = instantiate("---\ntitle: Weekly sync\nid: 3fa9c01b22d4\n", MEETING, &vars);
+        assert_eq!(
+            out,
+            "---\ntitle: Weekly sync\nid: 3fa9c01b22d4\ntags: [meeting]\nattendees:\n  - me\n---\n\n\
+             # Weekly sync (2026-10-17)\n\n## Agenda\n\nSee smith2024; {{unknown}} stays.\n"
+        );
+        let (fm, _) = crate::notes::parse_frontmatter(&out);
+        assert_eq!(fm.tags, vec!["meeting"]);
+
+        let plain = instantiate("---\ntitle: T\n", "Just a body with {{title", &vars);
+        assert_eq!(plain, "---\ntitle: T\n---\n\nJust a body with {{title\n");
+    }
+
+    #[test]
+    fn test_list_and_load_templates() {
+        let dir = TempDir::new("templates");
+        fs::write(dir.join("meeting.md"), MEETING).unwrap();
+        fs::write(dir.join("lecture.md"), "## Outline\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a template").unwrap();
+
+        assert_eq!(list_templates(&dir), vec!["lecture", "meeting"]);
+        assert_eq!(load_template(&dir, "lecture").unwrap(), "## Outline\n");
+        assert!(load_template(&dir, "../meeting").is_err());
+        assert!(load_template(&dir, "missing").is_err());
+        assert_eq!(load_optional(&dir, Some("")).unwrap(), None);
+        assert!(list_templates(&dir.join("nope")).is_empty());
+    }
+}
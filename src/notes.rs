@@ -8,16 +8,22 @@
 //! - Markdown rendering
 //! - Git integration for version history
 //! - Bibliography generation
+//!
+//! A note's key is its `id:` field, else a short hash of its path;
+//! `load_all_notes` gives path keys that collide their `long_key`.
+//! `parse_bibtex` reads the fields the viewer's meta block shows, including
+//! BibLaTeX `date` for the year.
 
+use crate::formats::{body_markdown, is_note_file, org_title, NoteFormat};
 use crate::models::{
-    GitCommit, Note, NoteType, PaperMeta, PaperSource, SearchMatch, SearchResult, TimeCategory,
+    BibFilter, GitCommit, Note, NoteSort, SortOrder, NoteType, PaperMeta, PaperSource, PaperVersion, SearchMatch, SearchResult, TimeCategory,
     TimeEntry,
 };
-use chrono::{DateTime, NaiveDate, Utc};
-use pulldown_cmark::Parser;
-use std::collections::HashMap;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use pulldown_cmark::{Event, Options, Parser};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use walkdir::WalkDir;
 
@@ -27,43 +33,185 @@ use walkdir::WalkDir;
 
 #[derive(Debug, Default)]
 pub struct Frontmatter {
+    /// Stable key that overrides the path-derived one
+    pub id: Option<String>,
     pub title: Option<String>,
     pub date: Option<NaiveDate>,
+    /// When the note was created, as opposed to its file's mtime
+    pub created: Option<NaiveDate>,
+    /// Deadline shown on `/agenda`
+    pub due: Option<NaiveDate>,
+    /// When to send a reminder about the note, in local time
+    pub remind: Option<NaiveDateTime>,
     pub note_type: Option<String>,
-    pub parent: Option<String>,
+    /// `parent:` and `parents:` keys, in order
+    pub parents: Vec<String>,
+    /// Previous note in a recurring series
+    pub prev: Option<String>,
     /// One or more BibTeX entries (sole source of truth for paper metadata)
     pub bibtex_entries: Vec<String>,
     /// When multiple bibtex entries exist, this specifies which cite key is canonical
     pub canonical_key: Option<String>,
     pub time: Vec<TimeEntry>,
     pub sources: Vec<PaperSource>,
+    pub versions: Vec<PaperVersion>,
     pub pdf: Option<String>,
     pub hidden: bool,
+    /// Keep the body encrypted on disk (see `encryption`)
+    pub encrypted: bool,
+    pub tags: Vec<String>,
+    /// Legacy paper authors; BibTeX is the source of truth
+    pub authors: Vec<String>,
+    /// Other names the note goes by
+    pub aliases: Vec<String>,
+    pub status: Option<String>,
+    pub rate: Option<f64>,
+    /// Place in the reading queue, 1 first
+    pub priority: Option<u8>,
+    /// Estimated reading time
+    pub est_minutes: Option<u32>,
 }
 
-pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
-    let mut fm = Frontmatter::default();
-    let lines: Vec<&str> = content.lines().collect();
+/// Format of `remind:` values.
+pub const REMIND_FORMAT: &str = "%Y-%m-%d %H:%M";
 
-    if lines.is_empty() || lines[0].trim() != "---" {
-        return (fm, content.to_string());
+/// Parse a `remind:` value, `YYYY-MM-DD HH:MM` (a `T` may stand in for the
+/// space).
+pub fn parse_remind(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(&value.trim().replacen('T', " ", 1), REMIND_FORMAT).ok()
+}
+
+/// Parse a `priority:` value: 1 (first) to 9, or `high`, `medium` or `low`
+/// for 1, 2 and 3.
+pub fn parse_priority(value: &str) -> Option<u8> {
+    match value.trim().to_lowercase().as_str() {
+        "high" => Some(1),
+        "medium" => Some(2),
+        "low" => Some(3),
+        n => n.parse().ok().filter(|p| (1..=9).contains(p)),
     }
+}
 
-    let mut end_idx = None;
-    for (i, line) in lines.iter().enumerate().skip(1) {
-        if line.trim() == "---" {
-            end_idx = Some(i);
-            break;
+/// Items of a one-line list value: `[a, "b"]` or `a, b`.
+fn inline_list(value: &str) -> Vec<String> {
+    value
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|t| t.trim().trim_matches('"').to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Authors from a one-line value: `A and B` (BibTeX style, so `Doe, Jane
+/// and Roe, Rick` keeps its commas) or `A, B`.
+fn split_authors(value: &str) -> Vec<String> {
+    let value = value.trim_start_matches('[').trim_end_matches(']');
+    if value.contains(" and ") {
+        value.split(" and ").map(|a| a.trim().trim_matches('"').to_string()).filter(|a| !a.is_empty()).collect()
+    } else {
+        inline_list(value)
+    }
+}
+
+/// The item of a YAML block list line (`- item` or `  - item`).
+fn list_item(line: &str) -> Option<&str> {
+    let item = line.trim().strip_prefix("- ")?.trim().trim_matches('"').trim_matches('\'');
+    (!item.is_empty()).then_some(item)
+}
+
+/// A `sources:` list item: `arxiv: ID`, `doi: 10.x/y`, `url: https://...`,
+/// `wayback: ...`, or a bare URL or DOI.
+fn source_item(item: &str) -> Option<PaperSource> {
+    let source = |source_type: &str, identifier: &str| {
+        (!identifier.is_empty()).then(|| PaperSource {
+            source_type: source_type.to_string(),
+            identifier: identifier.to_string(),
+        })
+    };
+    if item.starts_with("http://") || item.starts_with("https://") {
+        return source("url", item);
+    }
+    if let Some((kind, id)) = item.split_once(':') {
+        let kind = kind.trim().to_lowercase();
+        if matches!(kind.as_str(), "arxiv" | "doi" | "url" | "wayback") {
+            return source(&kind, id.trim().trim_matches('"'));
+        }
+    }
+    if item.starts_with("10.") {
+        return source("doi", item);
+    }
+    None
+}
+
+/// A `versions:` item being parsed: label and source.
+type PendingVersion = (Option<String>, Option<PaperSource>);
+
+/// Apply one `versions:` item field: `label: ...` or a `source_item`.
+fn version_field(version: &mut PendingVersion, field: &str) {
+    match field.split_once(':') {
+        Some((key, value)) if key.trim().eq_ignore_ascii_case("label") => {
+            version.0 = Some(value.trim().trim_matches('"').to_string()).filter(|l| !l.is_empty());
+        }
+        _ => {
+            if let Some(source) = source_item(field) {
+                version.1 = Some(source);
+            }
         }
     }
+}
+
+/// Add a block list item under `key`; false if `key` isn't a list field.
+fn push_list_item(fm: &mut Frontmatter, key: &str, item: &str) -> bool {
+    match key {
+        "tags" => fm.tags.push(item.to_string()),
+        "authors" => fm.authors.push(item.to_string()),
+        "aliases" => fm.aliases.push(item.to_string()),
+        "parent" | "parents" => fm.parents.push(item.to_string()),
+        "sources" => fm.sources.extend(source_item(item)),
+        "arxiv" | "doi" | "wayback" => fm.sources.push(PaperSource {
+            source_type: key.to_string(),
+            identifier: item.to_string(),
+        }),
+        "url" | "source_url" => fm.sources.push(PaperSource {
+            source_type: "url".to_string(),
+            identifier: item.to_string(),
+        }),
+        _ => return false,
+    }
+    true
+}
 
-    let end_idx = match end_idx {
+/// Index of the line closing the frontmatter, if the file has any.
+pub(crate) fn frontmatter_end(lines: &[&str]) -> Option<usize> {
+    if lines.first()?.trim() != "---" {
+        return None;
+    }
+    lines.iter().skip(1).position(|l| l.trim() == "---").map(|i| i + 1)
+}
+
+/// Lines of a note file before its body, i.e. the frontmatter's length.
+/// Line `n` of the body returned by `parse_frontmatter` is line
+/// `body_line_offset + n` of the file.
+pub fn body_line_offset(content: &str) -> usize {
+    let lines: Vec<&str> = content.lines().collect();
+    frontmatter_end(&lines).map_or(0, |end| end + 1)
+}
+
+pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
+    let mut fm = Frontmatter::default();
+    let lines: Vec<&str> = content.lines().collect();
+
+    let end_idx = match frontmatter_end(&lines) {
         Some(i) => i,
         None => return (fm, content.to_string()),
     };
 
     let mut current_key: Option<String> = None;
     let mut multiline_value = String::new();
+    // `- type: arxiv` under `sources:`, waiting for its `identifier:`
+    let mut source_type: Option<String> = None;
+    let mut versions: Vec<PendingVersion> = Vec::new();
     let mut in_time_block = false;
     let mut time_entries: Vec<TimeEntry> = Vec::new();
     let mut current_time: Option<(NaiveDate, u32, TimeCategory, Option<String>)> = None;
@@ -97,14 +245,7 @@ pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
             } else if trimmed.starts_with("category:") {
                 if let Some(ref mut t) = current_time {
                     let cat_str = trimmed.strip_prefix("category:").unwrap().trim();
-                    t.2 = match cat_str {
-                        "programming" => TimeCategory::Programming,
-                        "teaching" => TimeCategory::Teaching,
-                        "reading" => TimeCategory::Reading,
-                        "writing" => TimeCategory::Writing,
-                        "service" => TimeCategory::Service,
-                        other => TimeCategory::Other(other.to_string()),
-                    };
+                    t.2 = TimeCategory::from_name(cat_str);
                 }
                 continue;
             } else if trimmed.starts_with("description:") {
@@ -135,6 +276,43 @@ pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
             }
         }
 
+        // YAML block lists under `tags:`, `authors:`, `aliases:`, `sources:`
+        // and the source fields, indented or not
+        if current_key.as_deref() == Some("versions") {
+            if let Some(item) = list_item(line) {
+                let mut version = (None, None);
+                version_field(&mut version, item);
+                versions.push(version);
+                continue;
+            }
+            if line.starts_with([' ', '\t']) {
+                if let Some(version) = versions.last_mut() {
+                    version_field(version, trimmed);
+                }
+                continue;
+            }
+        }
+        if let Some(item) = list_item(line) {
+            if current_key.as_deref() == Some("sources") {
+                if let Some(("type", kind)) = item.split_once(':').map(|(k, v)| (k.trim(), v.trim())) {
+                    source_type = Some(kind.to_lowercase());
+                    continue;
+                }
+            }
+            let pushed = current_key.as_deref().is_some_and(|key| push_list_item(&mut fm, key, item));
+            if pushed || !line.starts_with([' ', '\t']) {
+                continue;
+            }
+        }
+        if current_key.as_deref() == Some("sources") {
+            if let Some(id) = trimmed.strip_prefix("identifier:") {
+                if let Some(kind) = source_type.take() {
+                    fm.sources.extend(source_item(&format!("{}: {}", kind, id.trim())));
+                }
+                continue;
+            }
+        }
+
         if line.starts_with("  ") || line.starts_with("\t") {
             if current_key.is_some() {
                 multiline_value.push_str(trimmed);
@@ -158,58 +336,71 @@ pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
             current_key = Some(key.clone());
 
             match key.as_str() {
+                "id" if is_valid_note_id(value) => fm.id = Some(value.to_string()),
                 "title" => fm.title = Some(value.to_string()),
                 "date" => {
                     if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
                         fm.date = Some(date);
                     }
                 }
+                "created" => fm.created = parse_created(value),
+                "due" => fm.due = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok(),
+                "remind" => fm.remind = parse_remind(value),
                 "type" => fm.note_type = Some(value.to_string()),
-                "parent" => fm.parent = Some(value.to_string()),
+                "parent" | "parents" => fm.parents.extend(inline_list(value)),
+                "prev" => fm.prev = Some(value.to_string()),
+                "status" if !value.is_empty() => fm.status = Some(value.to_lowercase()),
                 "canonical_key" | "canonical" => fm.canonical_key = Some(value.to_string()),
-                "bibtex" => {
-                    // Single-line bibtex (rare but supported)
-                    if !value.starts_with('|') && !value.is_empty() {
-                        fm.bibtex_entries.push(value.to_string());
-                    }
+                // Single-line bibtex (rare but supported)
+                "bibtex" if !value.starts_with('|') && !value.is_empty() => {
+                    fm.bibtex_entries.push(value.to_string());
                 }
-                "arxiv" => {
-                    if !value.is_empty() {
-                        fm.sources.push(PaperSource {
-                            source_type: "arxiv".to_string(),
-                            identifier: value.to_string(),
-                        });
-                    }
+                "arxiv" if !value.is_empty() => {
+                    fm.sources.push(PaperSource {
+                        source_type: "arxiv".to_string(),
+                        identifier: value.to_string(),
+                    });
                 }
-                "doi" => {
-                    if !value.is_empty() {
-                        fm.sources.push(PaperSource {
-                            source_type: "doi".to_string(),
-                            identifier: value.to_string(),
-                        });
-                    }
+                "doi" if !value.is_empty() => {
+                    fm.sources.push(PaperSource {
+                        source_type: "doi".to_string(),
+                        identifier: value.to_string(),
+                    });
                 }
-                "url" | "source_url" => {
-                    if !value.is_empty() {
-                        fm.sources.push(PaperSource {
-                            source_type: "url".to_string(),
-                            identifier: value.to_string(),
-                        });
-                    }
+                "url" | "source_url" if !value.is_empty() => {
+                    fm.sources.push(PaperSource {
+                        source_type: "url".to_string(),
+                        identifier: value.to_string(),
+                    });
+                }
+                "wayback" if !value.is_empty() => {
+                    fm.sources.push(PaperSource {
+                        source_type: "wayback".to_string(),
+                        identifier: value.to_string(),
+                    });
                 }
                 "time" => {
                     in_time_block = true;
                 }
-                "pdf" => {
-                    if !value.is_empty() {
-                        fm.pdf = Some(value.to_string());
-                    }
+                "pdf" if !value.is_empty() => {
+                    fm.pdf = Some(value.to_string());
                 }
                 "hidden" => {
                     fm.hidden = value.eq_ignore_ascii_case("true");
                 }
+                "encrypted" => {
+                    fm.encrypted = value.eq_ignore_ascii_case("true");
+                }
+                "tags" => fm.tags = inline_list(value),
+                "authors" => fm.authors = split_authors(value),
+                "aliases" => fm.aliases = inline_list(value),
+                "rate" | "hourly_rate" => {
+                    fm.rate = value.trim_start_matches('$').parse().ok();
+                }
+                "priority" => fm.priority = parse_priority(value),
+                "est_minutes" => fm.est_minutes = value.parse().ok(),
                 // Legacy fields - ignore (bibtex is now the source of truth)
-                "bib_key" | "bibkey" | "authors" | "venue" | "year" => {}
+                "bib_key" | "bibkey" | "venue" | "year" => {}
                 _ => {}
             }
         }
@@ -224,6 +415,12 @@ pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
         });
     }
     fm.time = time_entries;
+    fm.versions = versions
+        .into_iter()
+        .filter_map(|(label, source)| Some(PaperVersion { label, source: source? }))
+        .collect();
+    let mut seen = HashSet::new();
+    fm.parents.retain(|p| seen.insert(p.clone()));
 
     if let Some(ref key) = current_key {
         if !multiline_value.is_empty() && key.as_str() == "bibtex" {
@@ -235,16 +432,382 @@ pub fn parse_frontmatter(content: &str) -> (Frontmatter, String) {
     (fm, body)
 }
 
+// ============================================================================
+// Time Block Editing
+// ============================================================================
+
+/// Render a time entry as frontmatter lines using the given list indent.
+pub fn format_time_entry(entry: &TimeEntry, indent: &str) -> Vec<String> {
+    let mut lines = vec![
+        format!("{}- date: {}", indent, entry.date.format("%Y-%m-%d")),
+        format!("{}  minutes: {}", indent, entry.minutes),
+        format!("{}  category: {}", indent, entry.category),
+    ];
+    if let Some(ref desc) = entry.description {
+        let desc = desc.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !desc.is_empty() {
+            lines.push(format!("{}  description: {}", indent, desc));
+        }
+    }
+    lines
+}
+
+/// Location of the `time:` block inside a note's frontmatter.
+struct TimeBlock {
+    /// Index of the closing `---` line.
+    fm_end: usize,
+    /// Index of the `time:` line, if present.
+    header: Option<usize>,
+    /// Line ranges (start, end exclusive) of entries with a parseable date,
+    /// in the same order as `Frontmatter::time`.
+    entries: Vec<(usize, usize)>,
+    /// One past the last line belonging to the block.
+    end: usize,
+    indent: String,
+}
+
+fn find_time_block(lines: &[&str]) -> Result<TimeBlock, String> {
+    if lines.is_empty() || lines[0].trim() != "---" {
+        return Err("Note has no frontmatter".to_string());
+    }
+    let fm_end = lines
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(_, l)| l.trim() == "---")
+        .map(|(i, _)| i)
+        .ok_or_else(|| "Invalid frontmatter".to_string())?;
+
+    let header = (1..fm_end).find(|&i| {
+        let line = lines[i];
+        !line.starts_with(' ') && !line.starts_with('\t') && line.trim_end() == "time:"
+    });
+
+    let mut block = TimeBlock {
+        fm_end,
+        header,
+        entries: Vec::new(),
+        end: fm_end,
+        indent: "  ".to_string(),
+    };
+    let Some(header) = header else {
+        return Ok(block);
+    };
+
+    // The block runs until the next unindented key (same rule as the parser)
+    let mut end = header + 1;
+    while end < fm_end {
+        let line = lines[end];
+        let trimmed = line.trim();
+        if !trimmed.is_empty()
+            && !trimmed.starts_with('-')
+            && !line.starts_with(' ')
+            && !line.starts_with('\t')
+        {
+            break;
+        }
+        end += 1;
+    }
+    while end > header + 1 && lines[end - 1].trim().is_empty() {
+        end -= 1;
+    }
+    block.end = end;
+
+    let starts: Vec<usize> = (header + 1..end)
+        .filter(|&i| lines[i].trim().starts_with("- date:"))
+        .collect();
+    if let Some(&first) = starts.first() {
+        let line = lines[first];
+        block.indent = line[..line.len() - line.trim_start().len()].to_string();
+    }
+    for (n, &start) in starts.iter().enumerate() {
+        let stop = starts.get(n + 1).copied().unwrap_or(end);
+        let date_str = lines[start].trim().trim_start_matches("- date:").trim();
+        if NaiveDate::parse_from_str(date_str, "%Y-%m-%d").is_ok() {
+            block.entries.push((start, stop));
+        }
+    }
+
+    Ok(block)
+}
+
+fn join_lines(lines: Vec<String>, original: &str) -> String {
+    let mut out = lines.join("\n");
+    if original.ends_with('\n') {
+        out.push('\n');
+    }
+    out
+}
+
+/// Rewrite note-key references after notes are renamed: every `[@old]`
+/// crosslink and every frontmatter `parent: old`, `prev: old` or `old` in
+/// a `parents:` list whose key is in `renames`.
+/// Returns the new content and the number of references changed.
+pub fn rewrite_note_keys(content: &str, renames: &HashMap<String, String>) -> (String, usize) {
+    let mut count = 0;
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_frontmatter = false;
+    let mut in_parents = false;
+    for (i, line) in content.lines().enumerate() {
+        if line.trim() == "---" && (i == 0 || in_frontmatter) {
+            in_frontmatter = i == 0;
+            lines.push(line.to_string());
+            continue;
+        }
+        if in_frontmatter {
+            if let Some(item) = list_item(line).filter(|_| in_parents) {
+                if let Some(new) = renames.get(item) {
+                    count += 1;
+                    lines.push(line.replacen(item, new, 1));
+                    continue;
+                }
+            } else if let Some((key, value)) = line.split_once(':') {
+                let field = key.trim().to_lowercase();
+                in_parents = matches!(field.as_str(), "parent" | "parents");
+                if in_parents && (value.trim().starts_with('[') || value.contains(',')) {
+                    let items = inline_list(value.trim());
+                    if items.iter().any(|k| renames.contains_key(k)) {
+                        let items: Vec<&str> = items
+                            .iter()
+                            .map(|k| match renames.get(k) {
+                                Some(new) => {
+                                    count += 1;
+                                    new.as_str()
+                                }
+                                None => k.as_str(),
+                            })
+                            .collect();
+                        lines.push(format!("{}: [{}]", key, items.join(", ")));
+                        continue;
+                    }
+                } else if matches!(field.as_str(), "parent" | "parents" | "prev") {
+                    if let Some(new) = renames.get(value.trim()) {
+                        count += 1;
+                        lines.push(format!("{}: {}", key, new));
+                        continue;
+                    }
+                }
+            }
+            lines.push(line.to_string());
+            continue;
+        }
+        let mut out = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find("[@") {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            if let Some(end) = rest.find(']') {
+                if let Some(new) = renames.get(&rest[..end]) {
+                    count += 1;
+                    out.push_str(new);
+                    rest = &rest[end..];
+                }
+            }
+        }
+        out.push_str(rest);
+        lines.push(out);
+    }
+    if count == 0 {
+        return (content.to_string(), 0);
+    }
+    (join_lines(lines, content), count)
+}
+
+/// Append a time entry to a note's `time:` block, creating the block if
+/// needed. Only the block itself is touched; all other lines are preserved.
+pub fn append_time_entry(content: &str, entry: &TimeEntry) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let block = find_time_block(&lines)?;
+
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    let new_lines = format_time_entry(entry, &block.indent);
+    let insert_at = match block.header {
+        Some(_) => block.end,
+        None => {
+            out.insert(block.fm_end, "time:".to_string());
+            block.fm_end + 1
+        }
+    };
+    for (i, line) in new_lines.into_iter().enumerate() {
+        out.insert(insert_at + i, line);
+    }
+
+    Ok(join_lines(out, content))
+}
+
+/// Remove the `index`-th time entry (as ordered by `Frontmatter::time`).
+/// Drops the `time:` key entirely when no entries remain.
+pub fn remove_time_entry(content: &str, index: usize) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let block = find_time_block(&lines)?;
+
+    let (start, stop) = *block
+        .entries
+        .get(index)
+        .ok_or_else(|| format!("No time entry at index {}", index))?;
+
+    let remove_header = block.entries.len() == 1
+        && block.header.is_some_and(|h| {
+            (h + 1..block.end).all(|i| (start..stop).contains(&i) || lines[i].trim().is_empty())
+        });
+
+    let out: Vec<String> = lines
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            if (start..stop).contains(i) {
+                return false;
+            }
+            !(remove_header && block.header.is_some_and(|h| (h..block.end).contains(i)))
+        })
+        .map(|(_, l)| l.to_string())
+        .collect();
+
+    Ok(join_lines(out, content))
+}
+
+/// Value of a single-line frontmatter field, if set and not empty.
+pub fn frontmatter_field(content: &str, field: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let end = frontmatter_end(&lines)?;
+    lines[1..end]
+        .iter()
+        .filter(|l| !l.starts_with(' ') && !l.starts_with('\t'))
+        .filter_map(|l| l.split_once(':'))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case(field))
+        .map(|(_, v)| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Set a single-line frontmatter field, replacing the field (and any
+/// indented lines or `- ` list items under it) if present, or remove it when `value` is None.
+/// All other lines are preserved.
+pub fn set_frontmatter_field(content: &str, field: &str, value: Option<&str>) -> Result<String, String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let fm_end = find_time_block(&lines)?.fm_end;
+
+    let is_field = |line: &str| {
+        !line.starts_with(' ')
+            && !line.starts_with('\t')
+            && line.split_once(':').is_some_and(|(k, _)| k.trim().eq_ignore_ascii_case(field))
+    };
+    let continues = |line: &str| line.starts_with(' ') || line.starts_with('\t') || line.starts_with("- ");
+
+    let mut out: Vec<String> = Vec::new();
+    let mut insert_at = None;
+    let mut i = 0;
+    while i < lines.len() {
+        if i > 0 && i < fm_end && is_field(lines[i]) {
+            insert_at.get_or_insert(out.len());
+            i += 1;
+            while i < fm_end && continues(lines[i]) {
+                i += 1;
+            }
+            continue;
+        }
+        if i == fm_end && insert_at.is_none() {
+            insert_at = Some(out.len());
+        }
+        out.push(lines[i].to_string());
+        i += 1;
+    }
+    if let (Some(value), Some(at)) = (value, insert_at) {
+        out.insert(at, format!("{}: {}", field, value));
+    }
+
+    Ok(join_lines(out, content))
+}
+
 // ============================================================================
 // Key Generation
 // ============================================================================
 
-pub fn generate_key(path: &PathBuf) -> String {
+/// Bytes in the random keys given to new notes. Notes without an `id:`
+/// field keep the three-byte key hashed from their path.
+pub const NOTE_ID_BYTES: usize = 6;
+
+/// Key of a note with no `id:` field: a hash of its relative path, so it
+/// changes when the file moves.
+pub fn generate_key(path: &Path) -> String {
+    path_hash_key(path, 3)
+}
+
+/// The `NOTE_ID_BYTES` form of `generate_key`, which it extends: used by
+/// notes whose short keys collide, and pinned as `id:` by
+/// `notes migrate-keys`.
+pub fn long_key(path: &Path) -> String {
+    path_hash_key(path, NOTE_ID_BYTES)
+}
+
+fn path_hash_key(path: &Path, bytes: usize) -> String {
     use sha2::Digest;
     let mut hasher = sha2::Sha256::new();
     hasher.update(path.to_string_lossy().as_bytes());
     let result = hasher.finalize();
-    result[..3].iter().map(|b| format!("{:02x}", b)).collect()
+    result[..bytes].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Give notes whose path-derived keys collide their `long_key` instead, so
+/// neither shadows the other; notes with an `id:` keep it. Returns each
+/// colliding key with the paths that shared it.
+pub fn lengthen_colliding_keys(notes: &mut [Note]) -> Vec<(String, Vec<PathBuf>)> {
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, note) in notes.iter().enumerate() {
+        by_key.entry(note.key.clone()).or_default().push(i);
+    }
+    let mut collisions: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    for (key, indices) in by_key.into_iter().filter(|(_, indices)| indices.len() > 1) {
+        let paths = indices.iter().map(|&i| notes[i].path.clone()).collect();
+        for i in indices {
+            if notes[i].key == generate_key(&notes[i].path) {
+                notes[i].key = long_key(&notes[i].path);
+            }
+        }
+        collisions.push((key, paths));
+    }
+    collisions.sort();
+    collisions
+}
+
+/// A fresh key for a new note, to be written as its `id:` field so it
+/// survives renames. `taken` reports keys already in use.
+pub fn new_note_key(taken: impl Fn(&str) -> bool) -> String {
+    loop {
+        let bytes: [u8; NOTE_ID_BYTES] = rand::random();
+        let key: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        if !taken(&key) {
+            return key;
+        }
+    }
+}
+
+/// SHA-256 of a note file's contents, in hex; guards edits against changes
+/// made since the editor read the file.
+pub fn content_hash(content: &str) -> String {
+    use sha2::Digest;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(content.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Opening frontmatter of a new note: the `---` line, `title:`, `id:` and
+/// `created:`. Callers add any other fields and the closing `---`.
+pub fn new_note_frontmatter(title: &str, key: &str, created: DateTime<FixedOffset>) -> String {
+    format!("---\ntitle: {}\nid: {}\ncreated: {}\n", title, key, crate::timezone::timestamp(created))
+}
+
+/// The date of a `created:` value: `YYYY-MM-DD`, or an RFC3339 timestamp
+/// taken in its own offset.
+pub fn parse_created(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .or_else(|| DateTime::parse_from_rfc3339(value).ok().map(|t| t.date_naive()))
+}
+
+/// Whether `id` can be used as a note key: it appears in URLs and
+/// `[@key]` links, so only ASCII letters, digits, `-` and `_`.
+pub fn is_valid_note_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
 }
 
 // ============================================================================
@@ -254,44 +817,61 @@ pub fn generate_key(path: &PathBuf) -> String {
 pub fn load_note(path: &PathBuf, notes_dir: &PathBuf) -> Option<Note> {
     let content = fs::read_to_string(path).ok()?;
     let relative_path = path.strip_prefix(notes_dir).ok()?.to_path_buf();
-    let key = generate_key(&relative_path);
-
-    let (fm, body) = parse_frontmatter(&content);
-
-    let title = fm.title.unwrap_or_else(|| {
-        relative_path
-            .file_stem()
-            .map(|s| s.to_string_lossy().to_string())
-            .unwrap_or_else(|| "Untitled".to_string())
-    });
-
     let metadata = fs::metadata(path).ok()?;
     let modified: DateTime<Utc> = metadata.modified().ok()?.into();
+    Some(note_from_content(relative_path, content, modified))
+}
+
+/// Build a note from file contents without touching the disk.
+pub fn note_from_content(relative_path: PathBuf, content: String, modified: DateTime<Utc>) -> Note {
+    let (fm, body) = parse_frontmatter(&content);
+    let key = fm.id.unwrap_or_else(|| generate_key(&relative_path));
+    let format = crate::formats::format_of(&relative_path);
+
+    let title = fm
+        .title
+        .or_else(|| if format == NoteFormat::Org { org_title(&body) } else { None })
+        .unwrap_or_else(|| {
+            relative_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "Untitled".to_string())
+        });
 
     let note_type = if fm.note_type.as_deref() == Some("paper") || !fm.bibtex_entries.is_empty() {
         NoteType::Paper(PaperMeta {
             bibtex_entries: fm.bibtex_entries,
             canonical_key: fm.canonical_key,
             sources: fm.sources,
+            versions: fm.versions,
         })
     } else {
         NoteType::Note
     };
 
-    Some(Note {
+    Note {
         key,
         path: relative_path,
         title,
         date: fm.date,
         note_type,
-        parent_key: fm.parent,
+        parents: fm.parents,
+        prev_key: fm.prev,
+        created: fm.created,
+        due: fm.due,
+        remind: fm.remind,
+        status: fm.status,
         time_entries: fm.time,
-        raw_content: body,
+        raw_content: body_markdown(format, &body),
         full_file_content: content,
         modified,
         pdf: fm.pdf,
         hidden: fm.hidden,
-    })
+        tags: fm.tags,
+        hourly_rate: fm.rate,
+        priority: fm.priority,
+        est_minutes: fm.est_minutes,
+    }
 }
 
 pub fn load_all_notes(notes_dir: &PathBuf) -> Vec<Note> {
@@ -300,8 +880,9 @@ pub fn load_all_notes(notes_dir: &PathBuf) -> Vec<Note> {
     let paths: Vec<PathBuf> = WalkDir::new(notes_dir)
         .follow_links(false)
         .into_iter()
+        .filter_entry(|e| e.depth() != 1 || e.file_name() != crate::trash::TRASH_DIR)
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map(|ext| ext == "md").unwrap_or(false))
+        .filter(|e| is_note_file(e.path()))
         .map(|e| e.path().to_path_buf())
         .collect();
 
@@ -310,10 +891,34 @@ pub fn load_all_notes(notes_dir: &PathBuf) -> Vec<Note> {
         .filter_map(|path| load_note(path, notes_dir))
         .collect();
 
-    notes.sort_by(|a, b| b.modified.cmp(&a.modified));
+    for (key, paths) in lengthen_colliding_keys(&mut notes) {
+        let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+        tracing::warn!(key = %key, paths = %paths.join(", "), "Note key collision; run `notes migrate-keys`");
+    }
+
+    notes.sort_by_key(|n| std::cmp::Reverse(n.modified));
     notes
 }
 
+/// Sort a list of notes; see `NoteSort`.
+pub fn sort_notes<N: std::borrow::Borrow<Note>>(notes: &mut [N], by: NoteSort, order: SortOrder) {
+    match by {
+        NoteSort::Title => notes.sort_by_key(|n| n.borrow().title.to_lowercase()),
+        NoteSort::Created => notes.sort_by_key(|n| {
+            let n = n.borrow();
+            (n.created.unwrap_or_else(|| crate::timezone::local(n.modified).date_naive()), n.modified)
+        }),
+        NoteSort::Modified => notes.sort_by_key(|n| n.borrow().modified),
+        NoteSort::TimeTotal => notes.sort_by_key(|n| {
+            let n = n.borrow();
+            (n.time_entries.iter().map(|e| e.minutes).sum::<u32>(), std::cmp::Reverse(n.title.to_lowercase()))
+        }),
+    }
+    if order == SortOrder::Desc {
+        notes.reverse();
+    }
+}
+
 // ============================================================================
 // Full-Text Search
 // ============================================================================
@@ -414,11 +1019,39 @@ pub fn html_escape(s: &str) -> String {
 // ============================================================================
 
 pub fn render_markdown(content: &str) -> String {
-    let parser = Parser::new(content);
+    render_markdown_with_tasks(content, None)
+}
+
+/// Render markdown with task list checkboxes. With `line_offset` (see
+/// `body_line_offset`), each checkbox carries the file line it is on as
+/// `data-line`, for toggling it from the viewer. Checkboxes are rendered
+/// disabled either way.
+pub fn render_markdown_with_tasks(content: &str, line_offset: Option<usize>) -> String {
+    let parser = Parser::new_ext(content, Options::ENABLE_TASKLISTS)
+        .into_offset_iter()
+        .map(|(event, range)| match event {
+            Event::TaskListMarker(checked) => {
+                let line = line_offset
+                    .map(|offset| {
+                        let line = offset + content[..range.start].matches('\n').count() + 1;
+                        format!(r#" data-line="{}""#, line)
+                    })
+                    .unwrap_or_default();
+                let checked = if checked { " checked" } else { "" };
+                Event::Html(format!(r#"<input type="checkbox" disabled{}{}> "#, checked, line).into())
+            }
+            other => other,
+        });
     let mut html_output = String::new();
     pulldown_cmark::html::push_html(&mut html_output, parser);
-    // Sanitize HTML to prevent XSS from raw HTML in markdown
-    ammonia::clean(&html_output)
+    // Sanitize HTML to prevent XSS from raw HTML in markdown; checkboxes
+    // are the only inputs let through
+    ammonia::Builder::default()
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["checked", "disabled", "data-line"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .clean(&html_output)
+        .to_string()
 }
 
 // ============================================================================
@@ -462,7 +1095,7 @@ pub fn get_git_history(file_path: &PathBuf, notes_dir: &PathBuf) -> Vec<GitCommi
 }
 
 pub fn get_file_at_commit(
-    file_path: &PathBuf,
+    file_path: &Path,
     commit_hash: &str,
     notes_dir: &PathBuf,
 ) -> Option<String> {
@@ -499,9 +1132,18 @@ pub struct ParsedBibtex {
     pub title: Option<String>,
     pub author: Option<String>,
     pub year: Option<i32>,
+    /// The `year` field as written; `year` is None when it isn't a number.
+    pub year_text: Option<String>,
     pub venue: Option<String>,
     pub doi: Option<String>,
     pub eprint: Option<String>,
+    pub editor: Option<String>,
+    pub pages: Option<String>,
+    pub publisher: Option<String>,
+    /// `institution`, or `school` for theses
+    pub institution: Option<String>,
+    pub volume: Option<String>,
+    pub number: Option<String>,
 }
 
 /// Parse a BibTeX entry string and extract structured fields.
@@ -576,12 +1218,12 @@ pub fn parse_bibtex(bibtex: &str) -> Option<ParsedBibtex> {
                     }
                 }
                 if end > 1 { Some(&rest[1..end]) } else { None }
-            } else if rest.starts_with('"') {
-                let end = rest[1..].find('"').map(|i| i + 1)?;
-                Some(&rest[1..end])
+            } else if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"')?;
+                Some(&quoted[..end])
             } else {
                 // Bare value (number)
-                let end = rest.find(|c: char| c == ',' || c == '}' || c == '\n').unwrap_or(rest.len());
+                let end = rest.find([',', '}', '\n']).unwrap_or(rest.len());
                 Some(rest[..end].trim())
             };
 
@@ -680,6 +1322,10 @@ pub fn parse_bibtex(bibtex: &str) -> Option<ParsedBibtex> {
     // Parse year
     if let Some(year_str) = extract_field(bibtex, "year") {
         result.year = year_str.parse().ok();
+        result.year_text = Some(year_str);
+    } else if let Some(date) = extract_field(bibtex, "date") {
+        // BibLaTeX: date = {2020-05-01}
+        result.year = date.get(..4).and_then(|y| y.parse().ok());
     }
 
     // Derive venue from journal, booktitle, or howpublished
@@ -688,10 +1334,33 @@ pub fn parse_bibtex(bibtex: &str) -> Option<ParsedBibtex> {
         .or_else(|| extract_field(bibtex, "howpublished"));
 
     result.eprint = extract_field(bibtex, "eprint");
+    result.editor = extract_field(bibtex, "editor");
+    result.pages = extract_field(bibtex, "pages");
+    result.publisher = extract_field(bibtex, "publisher");
+    result.institution = extract_field(bibtex, "institution").or_else(|| extract_field(bibtex, "school"));
+    result.volume = extract_field(bibtex, "volume");
+    result.number = extract_field(bibtex, "number");
 
     Some(result)
 }
 
+/// Readable name of a BibTeX entry type, e.g. `inproceedings`.
+pub fn bibtex_type_label(entry_type: &str) -> &str {
+    match entry_type {
+        "article" => "Journal article",
+        "inproceedings" | "conference" => "Conference paper",
+        "proceedings" => "Proceedings",
+        "book" => "Book",
+        "incollection" | "inbook" => "Book chapter",
+        "phdthesis" => "PhD thesis",
+        "mastersthesis" => "Master's thesis",
+        "techreport" => "Technical report",
+        "unpublished" => "Unpublished",
+        "misc" => "Misc",
+        other => other,
+    }
+}
+
 // ============================================================================
 // BibTeX File Splitting
 // ============================================================================
@@ -751,7 +1420,7 @@ pub fn split_bib_file(content: &str) -> Vec<String> {
             if chars.peek() == Some(&'{') {
                 entry.push(chars.next().unwrap()); // '{'
                 let mut depth = 1;
-                while let Some(c) = chars.next() {
+                for c in chars.by_ref() {
                     entry.push(c);
                     if c == '{' {
                         depth += 1;
@@ -812,6 +1481,94 @@ pub fn generate_bibliography(notes: &[Note]) -> String {
     bib
 }
 
+/// The note's key followed by each ancestor's key, walking first parents
+/// until a missing parent or a cycle is reached.
+pub fn ancestor_chain(key: &str, notes_map: &HashMap<String, Note>) -> Vec<String> {
+    let first_parent = |k: &str| notes_map.get(k).and_then(|n| n.parents.first().cloned());
+    let mut chain = vec![key.to_string()];
+    let mut current = first_parent(key);
+    while let Some(parent) = current {
+        if chain.contains(&parent) || !notes_map.contains_key(&parent) {
+            break;
+        }
+        current = first_parent(&parent);
+        chain.push(parent);
+    }
+    chain
+}
+
+/// Every ancestor of the note through any of its parents, nearest first.
+/// Missing parents and cycles end a branch.
+pub fn ancestors(key: &str, notes_map: &HashMap<String, Note>) -> Vec<String> {
+    let mut seen = HashSet::from([key.to_string()]);
+    let mut found = Vec::new();
+    let mut queue = std::collections::VecDeque::from([key.to_string()]);
+    while let Some(current) = queue.pop_front() {
+        for parent in notes_map.get(&current).map(|n| n.parents.as_slice()).unwrap_or_default() {
+            if notes_map.contains_key(parent) && seen.insert(parent.clone()) {
+                found.push(parent.clone());
+                queue.push_back(parent.clone());
+            }
+        }
+    }
+    found
+}
+
+/// Every note below `key` through any of its parents, nearest first.
+/// Cycles end a branch.
+pub fn descendants(key: &str, notes_map: &HashMap<String, Note>) -> Vec<String> {
+    let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+    for note in notes_map.values() {
+        for parent in &note.parents {
+            children.entry(parent.as_str()).or_default().push(note.key.as_str());
+        }
+    }
+    let mut seen = HashSet::from([key]);
+    let mut found = Vec::new();
+    let mut queue = std::collections::VecDeque::from([key]);
+    while let Some(current) = queue.pop_front() {
+        let mut kids = children.get(current).cloned().unwrap_or_default();
+        kids.sort_unstable();
+        for child in kids {
+            if seen.insert(child) {
+                found.push(child.to_string());
+                queue.push_back(child);
+            }
+        }
+    }
+    found
+}
+
+/// Papers for a bibliography export, sorted by bib key. With a tag, only
+/// papers carrying it directly or through an ancestor (as invoices do);
+/// with `since`/`until`, only papers whose publication year is in range.
+pub fn filter_bibliography(notes: &[Note], filter: &BibFilter) -> Vec<Note> {
+    let notes_map: HashMap<String, Note> = notes.iter().map(|n| (n.key.clone(), n.clone())).collect();
+    let mut papers: Vec<(String, &Note)> = notes
+        .iter()
+        .filter_map(|note| match note.note_type {
+            NoteType::Paper(ref paper) => Some((paper.effective_metadata(&note.title), note)),
+            NoteType::Note => None,
+        })
+        .filter(|(meta, _)| match (filter.since, filter.until) {
+            (None, None) => true,
+            (since, until) => meta.year.is_some_and(|y| {
+                since.is_none_or(|s| y >= s) && until.is_none_or(|u| y <= u)
+            }),
+        })
+        .filter(|(_, note)| match filter.tag.as_deref().map(str::trim).filter(|t| !t.is_empty()) {
+            None => true,
+            Some(tag) => std::iter::once(note.key.clone())
+                .chain(ancestors(&note.key, &notes_map))
+                .filter_map(|k| notes_map.get(&k))
+                .any(|n| n.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))),
+        })
+        .map(|(meta, note)| (meta.bib_key, note))
+        .collect();
+    papers.sort_by(|a, b| (&a.0, &a.1.path).cmp(&(&b.0, &b.1.path)));
+    papers.into_iter().map(|(_, note)| note.clone()).collect()
+}
+
 // ============================================================================
 // Reference Extraction (for graph building)
 // ============================================================================
@@ -842,3 +1599,205 @@ pub fn extract_references(content: &str) -> Vec<String> {
 
     refs
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "---\ntitle: Datalog\ntime:\n  - date: 2024-01-15\n    minutes: 45\n    category: reading\n    description: First pass\n  - date: 2024-01-16\n    minutes: 30\n    category: writing\nhidden: true\n---\n\nBody text.\n";
+
+    fn entry(minutes: u32) -> TimeEntry {
+        TimeEntry {
+            date: NaiveDate::from_ymd_opt(2024, 2, 1).unwrap(),
+            minutes,
+            category: TimeCategory::Programming,
+            description: Some("Fixed\nparser".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_yaml_list_frontmatter() {
+        let content = "---\ntitle: Soufflé\ntags:\n  - datalog\n  - \"static analysis\"\nauthors:\n- Herbert Jordan\n- Bernhard Scholz\naliases: [souffle, Souffle paper]\nsources:\n  - arxiv: 1234.5678\n  - https://souffle-lang.github.io\n  - 10.1007/978-3-319-41540-6_23\n  - type: url\n    identifier: https://example.org/souffle\ndoi:\n  - 10.1145/1.2\nhidden: true\n---\n\nBody.\n";
+        let (fm, body) = parse_frontmatter(content);
+        assert_eq!(fm.tags, ["datalog", "static analysis"]);
+        assert_eq!(fm.authors, ["Herbert Jordan", "Bernhard Scholz"]);
+        assert_eq!(fm.aliases, ["souffle", "Souffle paper"]);
+        let sources: Vec<(&str, &str)> =
+            fm.sources.iter().map(|s| (s.source_type.as_str(), s.identifier.as_str())).collect();
+        assert_eq!(
+            sources,
+            [
+                ("arxiv", "1234.5678"),
+                ("url", "https://souffle-lang.github.io"),
+                ("doi", "10.1007/978-3-319-41540-6_23"),
+                ("url", "https://example.org/souffle"),
+                ("doi", "10.1145/1.2"),
+            ]
+        );
+        assert!(fm.hidden);
+        assert_eq!(body.trim(), "Body.");
+
+        // One-line forms still work
+        let (fm, _) = parse_frontmatter("---\ntags: [a, b]\nauthors: Doe, Jane and Roe, Rick\n---\n");
+        assert_eq!(fm.tags, ["a", "b"]);
+        assert_eq!(fm.authors, ["Doe, Jane", "Roe, Rick"]);
+        let (fm, _) = parse_frontmatter("---\nauthors: Jane Doe, Rick Roe\n---\n");
+        assert_eq!(fm.authors, ["Jane Doe", "Rick Roe"]);
+
+        // Rewriting a list field drops its items
+        let set = set_frontmatter_field(content, "tags", Some("[plt]")).unwrap();
+        assert_eq!(parse_frontmatter(&set).0.tags, ["plt"]);
+        let set = set_frontmatter_field(&set, "authors", None).unwrap();
+        let (fm, _) = parse_frontmatter(&set);
+        assert!(fm.authors.is_empty());
+        assert_eq!(fm.aliases.len(), 2);
+    }
+
+    #[test]
+    fn test_append_time_entry_to_existing_block() {
+        let out = append_time_entry(NOTE, &entry(20)).unwrap();
+        let (fm, body) = parse_frontmatter(&out);
+        assert_eq!(fm.time.len(), 3);
+        assert_eq!(fm.time[2].minutes, 20);
+        assert_eq!(fm.time[2].description.as_deref(), Some("Fixed parser"));
+        assert!(fm.hidden);
+        assert_eq!(fm.title.as_deref(), Some("Datalog"));
+        assert!(body.contains("Body text."));
+        assert!(out.ends_with("Body text.\n"));
+    }
+
+    #[test]
+    fn test_append_time_entry_creates_block() {
+        let out = append_time_entry("---\ntitle: New\n---\nBody\n", &entry(10)).unwrap();
+        assert!(out.starts_with("---\ntitle: New\ntime:\n  - date: 2024-02-01\n"));
+        let (fm, _) = parse_frontmatter(&out);
+        assert_eq!(fm.time.len(), 1);
+        assert!(append_time_entry("no frontmatter", &entry(10)).is_err());
+    }
+
+    #[test]
+    fn test_remove_time_entry() {
+        let out = remove_time_entry(NOTE, 0).unwrap();
+        let (fm, _) = parse_frontmatter(&out);
+        assert_eq!(fm.time.len(), 1);
+        assert_eq!(fm.time[0].minutes, 30);
+        assert!(fm.hidden);
+
+        let out = remove_time_entry(&out, 0).unwrap();
+        assert!(!out.contains("time:"));
+        assert!(out.contains("hidden: true"));
+        assert!(remove_time_entry(&out, 0).is_err());
+    }
+
+    #[test]
+    fn test_created_timestamp() {
+        let created = DateTime::parse_from_rfc3339("2026-10-16T23:30:00-04:00").unwrap();
+        let content = new_note_frontmatter("T", "abc123", created) + "---\n";
+        assert_eq!(content, "---\ntitle: T\nid: abc123\ncreated: 2026-10-16T23:30:00-04:00\n---\n");
+        // The date is the one in the timestamp's own offset, not UTC's
+        assert_eq!(parse_frontmatter(&content).0.created, NaiveDate::from_ymd_opt(2026, 10, 16));
+        assert_eq!(parse_created("2024-01-10"), NaiveDate::from_ymd_opt(2024, 1, 10));
+        assert_eq!(parse_created("yesterday"), None);
+    }
+
+    #[test]
+    fn test_set_frontmatter_field() {
+        let content = "---\ntitle: T\nstatus: draft\ntags:\n  - a\n---\nstatus: body\n";
+        let set = set_frontmatter_field(content, "status", Some("archived")).unwrap();
+        assert_eq!(set, "---\ntitle: T\nstatus: archived\ntags:\n  - a\n---\nstatus: body\n");
+        let tags = set_frontmatter_field(&set, "tags", Some("[a, b]")).unwrap();
+        assert_eq!(tags, "---\ntitle: T\nstatus: archived\ntags: [a, b]\n---\nstatus: body\n");
+        let removed = set_frontmatter_field(&tags, "status", None).unwrap();
+        assert_eq!(removed, "---\ntitle: T\ntags: [a, b]\n---\nstatus: body\n");
+        let added = set_frontmatter_field(&removed, "parent", Some("abc123")).unwrap();
+        assert_eq!(added, "---\ntitle: T\ntags: [a, b]\nparent: abc123\n---\nstatus: body\n");
+        assert!(set_frontmatter_field("no frontmatter", "status", None).is_err());
+    }
+
+    #[test]
+    fn test_rewrite_note_keys() {
+        let renames: HashMap<String, String> =
+            [("aaa111".to_string(), "fff999".to_string())].into_iter().collect();
+        let content = "---\ntitle: Child\nparent: aaa111\nprev: aaa111\n---\nSee [@aaa111] and [@bbb222].\nparent: aaa111\n";
+        let (out, count) = rewrite_note_keys(content, &renames);
+        assert_eq!(count, 3);
+        assert_eq!(
+            out,
+            "---\ntitle: Child\nparent: fff999\nprev: fff999\n---\nSee [@fff999] and [@bbb222].\nparent: aaa111\n"
+        );
+        assert_eq!(rewrite_note_keys("[@bbb222] [@", &renames), ("[@bbb222] [@".to_string(), 0));
+
+        let inline = "---\nparents: [bbb222, aaa111]\n---\n";
+        assert_eq!(rewrite_note_keys(inline, &renames), ("---\nparents: [bbb222, fff999]\n---\n".to_string(), 1));
+        let block = "---\nparents:\n  - aaa111\n  - bbb222\ntags:\n  - aaa111\n---\n";
+        assert_eq!(
+            rewrite_note_keys(block, &renames),
+            ("---\nparents:\n  - fff999\n  - bbb222\ntags:\n  - aaa111\n---\n".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn test_multiple_parents() {
+        let (fm, _) = parse_frontmatter("---\nparent: aaa111\nparents: [bbb222, aaa111]\n---\n");
+        assert_eq!(fm.parents, ["aaa111", "bbb222"]);
+        let (fm, _) = parse_frontmatter("---\nparents:\n  - aaa111\n  - ccc333\n---\n");
+        assert_eq!(fm.parents, ["aaa111", "ccc333"]);
+    }
+
+    #[test]
+    fn test_structured_bibtex() {
+        let paper = PaperMeta {
+            bibtex_entries: vec![
+                "@proceedings{pldi20,\n  editor = {Jane Doe and Rick Roe},\n  title = {PLDI 2020},\n  publisher = {ACM},\n  date = {2020-06-15}\n}".to_string(),
+                "@article{smith21,\n  author = {Ann Smith},\n  journal = {JFP},\n  volume = {31},\n  number = {2},\n  pages = {1--20},\n  year = 2021\n}".to_string(),
+            ],
+            canonical_key: None,
+            sources: Vec::new(),
+            versions: Vec::new(),
+        };
+        let meta = paper.effective_metadata("Note");
+        assert_eq!(meta.entry_type, "proceedings");
+        assert_eq!(meta.authors.as_deref(), Some("Jane Doe and Rick Roe"));
+        assert_eq!(meta.year, Some(2020));
+        assert_eq!(meta.venue.as_deref(), Some("ACM"));
+
+        let paper = PaperMeta { canonical_key: Some("smith21".to_string()), ..paper };
+        let meta = paper.effective_metadata("Note");
+        assert_eq!(bibtex_type_label(&meta.entry_type), "Journal article");
+        assert_eq!(meta.editors, None);
+        assert_eq!((meta.volume.as_deref(), meta.number.as_deref()), (Some("31"), Some("2")));
+        assert_eq!(meta.pages.as_deref(), Some("1\u{2013}20"));
+        assert_eq!(meta.venue.as_deref(), Some("JFP"));
+    }
+
+    #[test]
+    fn test_note_id_overrides_path_key() {
+        let dir = crate::test_util::TempDir::new("id");
+        fs::write(dir.join("pinned.md"), "---\nid: 3fa9c01b22d4\ntitle: Pinned\n---\n").unwrap();
+        fs::write(dir.join("legacy.md"), "---\nid: not/valid\ntitle: Legacy\n---\n").unwrap();
+        let pinned = load_note(&dir.join("pinned.md"), &dir).unwrap();
+        let legacy = load_note(&dir.join("legacy.md"), &dir).unwrap();
+        assert_eq!(pinned.key, "3fa9c01b22d4");
+        assert_eq!(legacy.key, generate_key(Path::new("legacy.md")));
+
+        let key = new_note_key(|k| k == pinned.key);
+        assert_eq!(key.len(), NOTE_ID_BYTES * 2);
+        assert!(is_valid_note_id(&key));
+    }
+
+    #[test]
+    fn test_key_collisions() {
+        let path_key = generate_key(Path::new("a.md"));
+        assert!(long_key(Path::new("a.md")).starts_with(&path_key));
+        let mut notes = vec![
+            note_from_content("a.md".into(), "---\ntitle: A\n---\n".to_string(), Utc::now()),
+            note_from_content("b.md".into(), format!("---\ntitle: B\nid: {}\n---\n", path_key), Utc::now()),
+            note_from_content("c.md".into(), "---\ntitle: C\n---\n".to_string(), Utc::now()),
+        ];
+        let collisions = lengthen_colliding_keys(&mut notes);
+        assert_eq!(collisions, [(path_key.clone(), vec![PathBuf::from("a.md"), PathBuf::from("b.md")])]);
+        assert_eq!(notes[0].key, long_key(Path::new("a.md")));
+        assert_eq!(notes[1].key, path_key);
+        assert_eq!(notes[2].key, generate_key(Path::new("c.md")));
+    }
+}
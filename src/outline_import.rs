@@ -0,0 +1,873 @@
+//! Import from block-based outliners: Roam Research JSON exports and
+//! Logseq JSON/EDN exports.
+//!
+//! Each page becomes a note whose body is the page's block tree as nested
+//! markdown bullets. Daily/journal pages become dated notes under `daily/`.
+//! `[[Page]]`, `#[[Page]]` and `#Page` references become `[@key]`
+//! crosslinks when the page is imported (or already exists as a note), and
+//! plain text otherwise; `((uid))` block references are replaced with the
+//! referenced block's text.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use serde_json::Value;
+
+use crate::auth::is_logged_in;
+use crate::models::{
+    Note, OutlineImportPage, OutlineImportRequest, OutlineImportResult, OutlineImportSkipped,
+};
+use crate::notes::{new_note_frontmatter, new_note_key, normalize_title};
+use crate::templates::base_html;
+use crate::{validate_path_within, AppState};
+
+/// Maximum nesting depth accepted from an EDN export.
+const MAX_EDN_DEPTH: usize = 512;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlinePage {
+    pub title: String,
+    pub date: Option<NaiveDate>,
+    pub blocks: Vec<OutlineBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineBlock {
+    pub uid: Option<String>,
+    pub text: String,
+    pub children: Vec<OutlineBlock>,
+}
+
+// ============================================================================
+// EDN Reading
+// ============================================================================
+
+/// Read EDN into a JSON value: keywords and symbols become strings (without
+/// the leading `:`), lists/vectors/sets become arrays, and tagged literals
+/// such as `#uuid "..."` become their inner value.
+pub fn parse_edn(text: &str) -> Result<Value, String> {
+    let mut reader = EdnReader { text, pos: 0 };
+    let value = reader.value(0)?;
+    reader.skip_ws(0)?;
+    if reader.pos < text.len() {
+        return Err(format!("Unexpected trailing EDN at byte {}", reader.pos));
+    }
+    Ok(value)
+}
+
+struct EdnReader<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl EdnReader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_ws(&mut self, depth: usize) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() || c == ',' => {
+                    self.bump();
+                }
+                Some(';') => {
+                    while !matches!(self.bump(), Some('\n') | None) {}
+                }
+                Some('#') if self.text[self.pos..].starts_with("#_") => {
+                    self.pos += 2;
+                    self.value(depth + 1)?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn token(&mut self) -> &str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ',' || "()[]{}\";".contains(c) {
+                break;
+            }
+            self.bump();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn seq(&mut self, close: char, depth: usize) -> Result<Vec<Value>, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_ws(depth)?;
+            if self.peek() == Some(close) {
+                self.bump();
+                return Ok(items);
+            }
+            items.push(self.value(depth + 1)?);
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                None => return Err("Unterminated EDN string".to_string()),
+                Some('"') => return Ok(out),
+                Some('\\') => match self.bump() {
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.bump()).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid EDN escape \\u{}", hex))?;
+                        out.push(c);
+                    }
+                    Some(c) => out.push(c),
+                    None => return Err("Unterminated EDN string".to_string()),
+                },
+                Some(c) => out.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        if depth > MAX_EDN_DEPTH {
+            return Err("EDN nested too deeply".to_string());
+        }
+        self.skip_ws(depth)?;
+        match self.peek() {
+            None => Err("Unexpected end of EDN".to_string()),
+            Some('{') => {
+                self.bump();
+                let items = self.seq('}', depth)?;
+                if items.len() % 2 != 0 {
+                    return Err("EDN map with odd number of forms".to_string());
+                }
+                let mut map = serde_json::Map::new();
+                let mut items = items.into_iter();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    let key = match k {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    map.insert(key, v);
+                }
+                Ok(Value::Object(map))
+            }
+            Some('[') => {
+                self.bump();
+                Ok(Value::Array(self.seq(']', depth)?))
+            }
+            Some('(') => {
+                self.bump();
+                Ok(Value::Array(self.seq(')', depth)?))
+            }
+            Some('"') => {
+                self.bump();
+                Ok(Value::String(self.string()?))
+            }
+            Some('#') => {
+                self.bump();
+                if self.peek() == Some('{') {
+                    self.bump();
+                    return Ok(Value::Array(self.seq('}', depth)?));
+                }
+                // Tagged literal (#uuid, #inst, ...): keep the tagged value.
+                self.token();
+                self.value(depth + 1)
+            }
+            Some('\\') => {
+                self.bump();
+                let ch = match self.token() {
+                    "newline" => "\n".to_string(),
+                    "space" => " ".to_string(),
+                    "tab" => "\t".to_string(),
+                    "" => self.bump().map(String::from).unwrap_or_default(),
+                    other => other.to_string(),
+                };
+                Ok(Value::String(ch))
+            }
+            Some(c) if ")]}".contains(c) => Err(format!("Unexpected '{}' in EDN", c)),
+            Some(_) => {
+                let token = self.token();
+                if let Some(keyword) = token.strip_prefix(':') {
+                    return Ok(Value::String(keyword.to_string()));
+                }
+                Ok(match token {
+                    "nil" => Value::Null,
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    _ => {
+                        let number = token.trim_end_matches(['N', 'M']);
+                        if let Ok(n) = number.parse::<i64>() {
+                            Value::from(n)
+                        } else if let Ok(f) = number.parse::<f64>() {
+                            Value::from(f)
+                        } else {
+                            Value::String(token.to_string())
+                        }
+                    }
+                })
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Export Parsing
+// ============================================================================
+
+/// Look up `name`, also accepting Logseq's namespaced `block/name` form.
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    let obj = value.as_object()?;
+    obj.get(name).or_else(|| obj.get(&format!("block/{}", name)))
+}
+
+fn str_field(value: &Value, names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .find_map(|n| field(value, n).and_then(Value::as_str))
+        .map(str::to_string)
+}
+
+fn children(value: &Value) -> &[Value] {
+    field(value, "children")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+/// Parse daily page titles: Roam's "October 17th, 2026", Logseq's
+/// "Oct 17th, 2026", and ISO or underscore dates.
+pub fn parse_daily_title(title: &str) -> Option<NaiveDate> {
+    let title = title.trim();
+    if let Ok(d) = NaiveDate::parse_from_str(title, "%Y-%m-%d") {
+        return Some(d);
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(title, "%Y_%m_%d") {
+        return Some(d);
+    }
+    // Drop the ordinal suffix from the day: "17th," -> "17,"
+    let (month, rest) = title.split_once(' ')?;
+    let (day, year) = rest.split_once(',')?;
+    let day = day
+        .trim()
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let cleaned = format!("{} {} {}", month, day, year.trim());
+    NaiveDate::parse_from_str(&cleaned, "%B %d %Y")
+        .or_else(|_| NaiveDate::parse_from_str(&cleaned, "%b %d %Y"))
+        .ok()
+}
+
+/// Strip Logseq `key:: value` property lines from block content.
+fn strip_properties(content: &str) -> String {
+    content
+        .lines()
+        .filter(|line| {
+            let line = line.trim();
+            match line.split_once(":: ").or_else(|| line.strip_suffix("::").map(|k| (k, ""))) {
+                Some((key, _)) => {
+                    key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                }
+                None => true,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse a block and its children. Logseq blocks (`content`) carry their
+/// properties inline; Roam attributes (`string`) are kept as text.
+fn parse_block(value: &Value, text_field: &str) -> Option<OutlineBlock> {
+    let raw = str_field(value, &[text_field]).unwrap_or_default();
+    let text = if text_field == "content" {
+        strip_properties(&raw)
+    } else {
+        raw
+    };
+    let text = text.trim().to_string();
+    let children: Vec<OutlineBlock> = children(value)
+        .iter()
+        .filter_map(|c| parse_block(c, text_field))
+        .collect();
+    if text.is_empty() && children.is_empty() {
+        return None;
+    }
+    Some(OutlineBlock {
+        uid: str_field(value, &["uid", "id", "uuid"]),
+        text,
+        children,
+    })
+}
+
+fn roam_page(value: &Value) -> Option<OutlinePage> {
+    let title = str_field(value, &["title"])?;
+    Some(OutlinePage {
+        date: parse_daily_title(&title),
+        blocks: children(value)
+            .iter()
+            .filter_map(|b| parse_block(b, "string"))
+            .collect(),
+        title,
+    })
+}
+
+fn logseq_page(value: &Value) -> Option<OutlinePage> {
+    let title = str_field(value, &["original-name", "page-name", "name", "title"])?;
+    let date = field(value, "journal-day")
+        .and_then(Value::as_i64)
+        .and_then(|d| NaiveDate::parse_from_str(&d.to_string(), "%Y%m%d").ok())
+        .or_else(|| parse_daily_title(&title));
+    Some(OutlinePage {
+        title,
+        date,
+        blocks: children(value)
+            .iter()
+            .filter_map(|b| parse_block(b, "content"))
+            .collect(),
+    })
+}
+
+/// Detect the export format and parse its pages. Returns the format name
+/// (`roam` or `logseq`) with the pages.
+pub fn parse_outline(data: &str) -> Result<(&'static str, Vec<OutlinePage>), String> {
+    let data = data.trim_start_matches('\u{feff}').trim();
+    if data.is_empty() {
+        return Err("Empty export".to_string());
+    }
+    let is_json = data.starts_with('[')
+        || data
+            .strip_prefix('{')
+            .is_some_and(|rest| rest.trim_start().starts_with('"'));
+    let value = if is_json {
+        serde_json::from_str(data).map_err(|e| format!("Invalid JSON: {}", e))?
+    } else {
+        parse_edn(data)?
+    };
+
+    match value {
+        Value::Array(pages) => Ok(("roam", pages.iter().filter_map(roam_page).collect())),
+        Value::Object(_) => {
+            let blocks = field(&value, "blocks")
+                .and_then(Value::as_array)
+                .ok_or("Expected a Logseq export with a blocks list")?;
+            Ok(("logseq", blocks.iter().filter_map(logseq_page).collect()))
+        }
+        _ => Err("Unrecognized export format".to_string()),
+    }
+}
+
+// ============================================================================
+// Markdown Conversion
+// ============================================================================
+
+/// Find the `]]` closing a `[[` whose contents start at `start`, allowing
+/// nested references.
+fn closing_brackets(text: &str, start: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut i = start;
+    while i < text.len() {
+        if text[i..].starts_with("[[") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("]]") {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+            i += 2;
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    None
+}
+
+fn page_link(title: &str, links: &HashMap<String, String>) -> String {
+    let plain = title.replace("[[", "").replace("]]", "");
+    match links.get(&normalize_title(&plain)) {
+        Some(key) => format!("[@{}]", key),
+        None => plain,
+    }
+}
+
+/// Rewrite outliner markup in one block's text.
+fn convert_refs(
+    text: &str,
+    links: &HashMap<String, String>,
+    block_text: &HashMap<String, String>,
+) -> String {
+    let text = text
+        .replace("{{[[TODO]]}}", "[ ]")
+        .replace("{{[[DONE]]}}", "[x]")
+        .replace("{{TODO}}", "[ ]")
+        .replace("{{DONE}}", "[x]");
+    let text = if let Some(rest) = text.strip_prefix("TODO ") {
+        format!("[ ] {}", rest)
+    } else if let Some(rest) = text.strip_prefix("DONE ") {
+        format!("[x] {}", rest)
+    } else {
+        text
+    };
+
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    let mut prev: Option<char> = None;
+    while i < text.len() {
+        let rest = &text[i..];
+        let reference = if let Some(inner) = rest.strip_prefix("#[[") {
+            closing_brackets(&text, i + 3).map(|end| (inner, end - (i + 3), end + 2))
+        } else if let Some(inner) = rest.strip_prefix("[[") {
+            closing_brackets(&text, i + 2).map(|end| (inner, end - (i + 2), end + 2))
+        } else {
+            None
+        };
+        if let Some((inner, len, next)) = reference {
+            out.push_str(&page_link(&inner[..len], links));
+            prev = Some(']');
+            i = next;
+            continue;
+        }
+
+        if let Some(inner) = rest.strip_prefix("((") {
+            if let Some(end) = inner.find("))") {
+                let uid = &inner[..end];
+                if !uid.is_empty() && !uid.contains(char::is_whitespace) {
+                    if let Some(target) = block_text.get(uid) {
+                        out.push_str(target.lines().next().unwrap_or(""));
+                    }
+                    prev = Some(')');
+                    i += 2 + end + 2;
+                    continue;
+                }
+            }
+        }
+
+        if rest.starts_with('#') && prev.is_none_or(char::is_whitespace) {
+            let tag: String = rest[1..]
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || "-_/".contains(*c))
+                .collect();
+            if !tag.is_empty() {
+                if let Some(key) = links.get(&normalize_title(&tag)) {
+                    out.push_str(&format!("[@{}]", key));
+                    prev = tag.chars().last();
+                    i += 1 + tag.len();
+                    continue;
+                }
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        prev = Some(c);
+        i += c.len_utf8();
+    }
+    out
+}
+
+fn collect_block_text(blocks: &[OutlineBlock], into: &mut HashMap<String, String>) {
+    for block in blocks {
+        if let Some(ref uid) = block.uid {
+            into.insert(uid.clone(), block.text.clone());
+        }
+        collect_block_text(&block.children, into);
+    }
+}
+
+fn count_blocks(blocks: &[OutlineBlock]) -> usize {
+    blocks.iter().map(|b| 1 + count_blocks(&b.children)).sum()
+}
+
+/// Render a block tree as nested markdown bullets.
+fn render_blocks(
+    blocks: &[OutlineBlock],
+    depth: usize,
+    links: &HashMap<String, String>,
+    block_text: &HashMap<String, String>,
+    out: &mut String,
+) {
+    let indent = "  ".repeat(depth);
+    for block in blocks {
+        let text = convert_refs(&block.text, links, block_text);
+        let mut lines = text.lines();
+        out.push_str(&format!("{}- {}\n", indent, lines.next().unwrap_or("")));
+        for line in lines {
+            out.push_str(&format!("{}  {}\n", indent, line));
+        }
+        render_blocks(&block.children, depth + 1, links, block_text, out);
+    }
+}
+
+fn slugify(title: &str) -> String {
+    let slug = title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "page".to_string()
+    } else {
+        slug.chars().take(80).collect()
+    }
+}
+
+/// Decide which pages to create and render their files. Pages whose title
+/// matches an existing note are skipped (and linked to that note instead);
+/// pages with no blocks are skipped. New notes are marked created `now`.
+pub fn plan_outline_import(
+    format: &str,
+    pages: &[OutlinePage],
+    notes: &[Note],
+    now: DateTime<FixedOffset>,
+) -> (OutlineImportResult, Vec<(PathBuf, String)>) {
+    let mut result = OutlineImportResult {
+        format: format.to_string(),
+        ..Default::default()
+    };
+
+    let mut links: HashMap<String, String> = notes
+        .iter()
+        .map(|n| (normalize_title(&n.title), n.key.clone()))
+        .collect();
+    let mut used_paths: HashSet<PathBuf> = notes.iter().map(|n| n.path.clone()).collect();
+    let mut used_keys: HashSet<String> = notes.iter().map(|n| n.key.clone()).collect();
+    let mut planned: Vec<(&OutlinePage, PathBuf, String, String)> = Vec::new();
+
+    for page in pages {
+        let title = page.title.replace(['\n', '\r'], " ").trim().to_string();
+        let normalized = normalize_title(&title);
+        if page.blocks.is_empty() {
+            result.skipped.push(OutlineImportSkipped {
+                title,
+                reason: "empty page".to_string(),
+            });
+            continue;
+        }
+        if links.contains_key(&normalized) {
+            result.skipped.push(OutlineImportSkipped {
+                title,
+                reason: "a note with this title already exists".to_string(),
+            });
+            continue;
+        }
+
+        let stem = match page.date {
+            Some(date) => format!("daily/{}", date.format("%Y-%m-%d")),
+            None => slugify(&title),
+        };
+        let mut path = PathBuf::from(format!("{}.md", stem));
+        let mut n = 2;
+        while used_paths.contains(&path) {
+            path = PathBuf::from(format!("{}-{}.md", stem, n));
+            n += 1;
+        }
+        used_paths.insert(path.clone());
+
+        let key = new_note_key(|k| used_keys.contains(k));
+        used_keys.insert(key.clone());
+        links.insert(normalized, key.clone());
+        planned.push((page, path, title, key));
+    }
+
+    let mut block_text = HashMap::new();
+    for (page, _, _, _) in &planned {
+        collect_block_text(&page.blocks, &mut block_text);
+    }
+
+    let mut files = Vec::new();
+    for (page, path, title, key) in planned {
+        let mut content = new_note_frontmatter(&title, &key, now);
+        if let Some(date) = page.date {
+            content.push_str(&format!("date: {}\n", date.format("%Y-%m-%d")));
+        }
+        content.push_str("---\n\n");
+        render_blocks(&page.blocks, 0, &links, &block_text, &mut content);
+
+        result.created.push(OutlineImportPage {
+            title,
+            key,
+            path: path.to_string_lossy().to_string(),
+            date: page.date,
+            blocks: count_blocks(&page.blocks),
+        });
+        files.push((path, content));
+    }
+
+    (result, files)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+pub async fn outline_import(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<OutlineImportRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let (format, pages) = match parse_outline(&body.data) {
+        Ok(p) => p,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let notes = state.load_notes();
+    let (mut result, files) = plan_outline_import(format, &pages, &notes, crate::timezone::now());
+
+    if body.dry_run || files.is_empty() {
+        return axum::Json(result).into_response();
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in &files {
+        let full_path = state.notes_dir.join(path);
+        let outcome = validate_path_within(&state.notes_dir, &full_path).and_then(|p| {
+            if p.exists() {
+                return Err("file already exists".to_string());
+            }
+            crate::encryption::write_file(&p, content).map(drop)
+        });
+        match outcome {
+            Ok(()) => written.push(path.clone()),
+            Err(e) => result
+                .errors
+                .push(format!("Failed to write {}: {}", path.display(), e)),
+        }
+    }
+    result
+        .created
+        .retain(|p| written.iter().any(|w| w.to_string_lossy() == p.path));
+
+    if !written.is_empty() {
+        state.invalidate_notes_cache();
+        for page in &result.created {
+            state.reindex_graph_note(&page.key);
+        }
+
+        let notes_dir = state.notes_dir.clone();
+        let source = if format == "roam" { "Roam" } else { "Logseq" };
+        state.spawn_background(move || {
+            let now = crate::timezone::now();
+            let commit_msg = format!(
+                "imported {} pages from {}: {}",
+                written.len(),
+                source,
+                now.format("%a %b %d, %-I:%M%p")
+            );
+            for path in &written {
+                let _ = Command::new("git")
+                    .args(["add", &path.to_string_lossy()])
+                    .current_dir(&notes_dir)
+                    .output();
+            }
+            let _ = Command::new("git")
+                .args(["commit", "-m", &commit_msg])
+                .current_dir(&notes_dir)
+                .output();
+        });
+    }
+
+    axum::Json(result).into_response()
+}
+
+pub async fn outline_import_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let html = r##"<h1>Import from Roam or Logseq</h1>
+        <p class="time-breadcrumb">Upload a Roam Research JSON export, or a Logseq JSON or EDN
+        export. Each page becomes a note; daily pages become dated notes under <code>daily/</code>.</p>
+        <input type="file" id="outline-file" accept=".json,.edn,application/json">
+        <div class="invoice-filter">
+            <button onclick="runImport(true)">Preview</button>
+            <button onclick="runImport(false)">Import</button>
+        </div>
+        <div id="import-result"></div>
+        <script>
+        let exportText = '';
+        document.getElementById('outline-file').addEventListener('change', async (e) => {
+            const file = e.target.files[0];
+            exportText = file ? await file.text() : '';
+        });
+        function esc(s) {
+            const d = document.createElement('div');
+            d.textContent = s == null ? '' : String(s);
+            return d.innerHTML;
+        }
+        async function runImport(dryRun) {
+            const out = document.getElementById('import-result');
+            if (!exportText) { out.innerHTML = '<p>Choose an export file first.</p>'; return; }
+            const resp = await fetch('/api/import/outline', {
+                method: 'POST',
+                headers: {'Content-Type': 'application/json'},
+                body: JSON.stringify({data: exportText, dry_run: dryRun})
+            });
+            if (!resp.ok) { out.innerHTML = '<p>' + esc(await resp.text()) + '</p>'; return; }
+            const r = await resp.json();
+            let html = '<h2>' + (dryRun ? 'Would create ' : 'Created ') + r.created.length + ' notes (' + esc(r.format) + ')</h2>';
+            html += '<table class="time-table"><tr><th>Title</th><th>File</th><th>Date</th><th>Blocks</th></tr>';
+            for (const p of r.created) {
+                const title = dryRun ? esc(p.title) : '<a href="/note/' + esc(p.key) + '">' + esc(p.title) + '</a>';
+                html += '<tr><td>' + title + '</td><td>' + esc(p.path) + '</td><td>' + esc(p.date || '-') + '</td><td>' + p.blocks + '</td></tr>';
+            }
+            html += '</table>';
+            if (r.skipped.length) {
+                html += '<h2>Skipped pages</h2><table class="time-table"><tr><th>Title</th><th>Reason</th></tr>';
+                for (const s of r.skipped) html += '<tr><td>' + esc(s.title) + '</td><td>' + esc(s.reason) + '</td></tr>';
+                html += '</table>';
+            }
+            for (const e of r.errors) html += '<p>' + esc(e) + '</p>';
+            out.innerHTML = html;
+        }
+        </script>"##;
+
+    Html(base_html("Import Outline", html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(key: &str, title: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    const ROAM: &str = r#"[
+        {"title": "Datalog", "children": [
+            {"string": "Semi-naive evaluation", "uid": "b1", "children": [
+                {"string": "see [[Soufflé]] and #Engines", "uid": "b2"}
+            ]},
+            {"string": "{{[[TODO]]}} read ((b1))", "uid": "b3"}
+        ]},
+        {"title": "October 17th, 2026", "children": [
+            {"string": "Worked on [[Datalog]]\nsecond line", "uid": "b4"}
+        ]},
+        {"title": "Engines"},
+        {"title": "Existing Note", "children": [{"string": "dup", "uid": "b5"}]}
+    ]"#;
+
+    const LOGSEQ_EDN: &str = r#"{:version 1,
+        :blocks
+        ({:block/id #uuid "6530f0a0-0000-0000-0000-000000000001",
+          :block/page-name "rust",
+          :block/original-name "Rust",
+          :block/properties {},
+          :block/format :markdown,
+          :block/children
+          [{:block/id #uuid "6530f0a0-0000-0000-0000-000000000002",
+            :block/content "TODO learn lifetimes\nid:: 6530f0a0-0000-0000-0000-000000000002",
+            :block/children []}]}
+         {:block/page-name "oct 17th, 2026",
+          :block/journal? true,
+          :block/journal-day 20261017,
+          :block/children [{:block/content "tried #[[Rust]] ; not a comment", :block/children ()}]})}"#;
+
+    #[test]
+    fn test_parse_edn_values() {
+        let v = parse_edn(r#"{:a [1 2.5 "s\n" nil true] :b #{:x} :c #inst "2024-01-01" #_ :skip :d \c}"#)
+            .unwrap();
+        assert_eq!(v["a"], serde_json::json!([1, 2.5, "s\n", null, true]));
+        assert_eq!(v["b"], serde_json::json!(["x"]));
+        assert_eq!(v["c"], "2024-01-01");
+        assert_eq!(v["d"], "c");
+        assert!(parse_edn("{:a").is_err());
+        assert!(parse_edn(&"[".repeat(1000)).is_err());
+    }
+
+    #[test]
+    fn test_parse_daily_title() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 17);
+        assert_eq!(parse_daily_title("October 17th, 2026"), date);
+        assert_eq!(parse_daily_title("Oct 17th, 2026"), date);
+        assert_eq!(parse_daily_title("2026_10_17"), date);
+        assert_eq!(parse_daily_title("Datalog"), None);
+    }
+
+    #[test]
+    fn test_roam_import() {
+        let (format, pages) = parse_outline(ROAM).unwrap();
+        assert_eq!(format, "roam");
+        assert_eq!(pages.len(), 4);
+
+        let notes = vec![note("aaa111", "Existing note")];
+        let now = DateTime::parse_from_rfc3339("2026-10-18T08:00:00+02:00").unwrap();
+        let (result, files) = plan_outline_import(format, &pages, &notes, now);
+        assert_eq!(result.created.len(), 2);
+        assert_eq!(result.skipped.len(), 2);
+
+        let datalog_key = &result.created[0].key;
+        let (path, datalog) = &files[0];
+        assert_eq!(path, &PathBuf::from("datalog.md"));
+        assert!(datalog.starts_with(&format!("---\ntitle: Datalog\nid: {}\ncreated: 2026-10-18T08:00:00+02:00\n---\n\n", datalog_key)));
+        assert!(datalog.contains("- Semi-naive evaluation\n  - see Soufflé and #Engines\n"));
+        assert!(datalog.contains("- [ ] read Semi-naive evaluation\n"));
+
+        let (path, daily) = &files[1];
+        assert_eq!(path, &PathBuf::from("daily/2026-10-17.md"));
+        assert!(daily.contains("date: 2026-10-17\n"));
+        assert!(daily.contains(&format!("- Worked on [@{}]\n  second line\n", datalog_key)));
+        assert_eq!(result.created[0].blocks, 3);
+    }
+
+    #[test]
+    fn test_logseq_edn_import() {
+        let (format, pages) = parse_outline(LOGSEQ_EDN).unwrap();
+        assert_eq!(format, "logseq");
+        assert_eq!(pages[0].title, "Rust");
+        assert_eq!(pages[0].blocks[0].text, "TODO learn lifetimes");
+        assert_eq!(pages[1].date, NaiveDate::from_ymd_opt(2026, 10, 17));
+
+        let (result, files) = plan_outline_import(format, &pages, &[], DateTime::default());
+        let rust_key = &result.created[0].key;
+        assert!(files[0].1.contains("- [ ] learn lifetimes\n"));
+        assert!(files[1].1.contains(&format!("- tried [@{}] ; not a comment\n", rust_key)));
+    }
+
+    #[test]
+    fn test_logseq_json_and_path_collisions() {
+        let json = r#"{"version": 1, "blocks": [
+            {"page-name": "datalog", "original-name": "Datalog", "children": [
+                {"content": "x\nstatus:: draft", "children": []}
+            ]}
+        ]}"#;
+        let (format, pages) = parse_outline(json).unwrap();
+        assert_eq!(format, "logseq");
+        assert_eq!(pages[0].blocks[0].text, "x");
+
+        let notes = vec![note("datalog", "Something else")];
+        let (result, _) = plan_outline_import(format, &pages, &notes, DateTime::default());
+        assert_eq!(result.created[0].path, "datalog-2.md");
+    }
+}
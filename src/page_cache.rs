@@ -0,0 +1,174 @@
+//! Conditional fetching cache for external pages.
+//!
+//! Smart Add's page fetches and the link checker GET pages through `fetch`,
+//! which keeps a successful text response in sled with its `ETag` and
+//! `Last-Modified`. The next fetch of the same URL sends them back as
+//! `If-None-Match`/`If-Modified-Since`, and a `304 Not Modified` answer is
+//! served from the cache instead of downloading the page again. Pages
+//! without either validator, and bodies over `MAX_BODY`, aren't kept.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+
+use crate::url_validator::UrlPolicy;
+
+const CACHE_TREE: &str = "page_cache";
+
+/// Largest body read and cached.
+const MAX_BODY: usize = 1024 * 1024;
+
+/// A fetched page, as returned by `fetch` and kept in the cache.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    /// HTTP status of the final response (the cached one's for a 304).
+    pub status: u16,
+    /// Where redirects ended up.
+    pub final_url: String,
+    /// The text of a successful HTML or other text response up to
+    /// `MAX_BODY`; None otherwise.
+    pub body: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched: DateTime<Utc>,
+}
+
+impl Page {
+    fn is_cacheable(&self) -> bool {
+        (200..300).contains(&self.status) && self.body.is_some() && (self.etag.is_some() || self.last_modified.is_some())
+    }
+}
+
+pub fn load(db: &crate::store::Db, url: &str) -> Option<Page> {
+    let value = db.open_tree(CACHE_TREE).ok()?.get(url.as_bytes()).ok()??;
+    serde_json::from_slice(&value).ok()
+}
+
+fn save(db: &crate::store::Db, url: &str, page: &Page) -> Result<(), String> {
+    let tree = db.open_tree(CACHE_TREE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_vec(page).map_err(|e| e.to_string())?;
+    tree.insert(url.as_bytes(), value).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn remove(db: &crate::store::Db, url: &str) {
+    if let Ok(tree) = db.open_tree(CACHE_TREE) {
+        tree.remove(url.as_bytes()).ok();
+    }
+}
+
+/// Ask for `cached` to be revalidated rather than sent again.
+fn with_validators(request: reqwest::RequestBuilder, cached: Option<&Page>) -> reqwest::RequestBuilder {
+    let Some(cached) = cached else {
+        return request;
+    };
+    let mut request = request;
+    if let Some(ref etag) = cached.etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    if let Some(ref modified) = cached.last_modified {
+        request = request.header(IF_MODIFIED_SINCE, modified);
+    }
+    request
+}
+
+fn is_text(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|t| {
+        let t = t.to_ascii_lowercase();
+        t.starts_with("text/") || t.contains("html") || t.contains("xml")
+    })
+}
+
+/// GET `url` through `policy` (see `UrlPolicy::get`), revalidating the
+/// cached copy in `db` if there is one. Without `db` nothing is cached.
+pub async fn fetch(
+    db: Option<&crate::store::Db>,
+    policy: &UrlPolicy,
+    api: &'static str,
+    url: &str,
+    timeout: Duration,
+    request: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+) -> Result<Page, String> {
+    let cached = db.and_then(|db| load(db, url));
+    let response = policy.get(api, url, timeout, |r| with_validators(request(r), cached.as_ref())).await?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(mut page) = cached {
+            page.fetched = Utc::now();
+            if let Some(db) = db {
+                save(db, url, &page).ok();
+            }
+            return Ok(page);
+        }
+    }
+
+    let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+    let etag = header(ETAG);
+    let last_modified = header(LAST_MODIFIED);
+    let fits = response.content_length().is_none_or(|len| len <= MAX_BODY as u64);
+    let final_url = response.url().to_string();
+    let body = if status.is_success() && fits && is_text(header(CONTENT_TYPE).as_deref()) {
+        let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+        (bytes.len() <= MAX_BODY).then(|| String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        None
+    };
+    let page = Page { status: status.as_u16(), final_url, body, etag, last_modified, fetched: Utc::now() };
+
+    if let Some(db) = db {
+        if page.is_cacheable() {
+            if let Err(e) = save(db, url, &page) {
+                tracing::warn!(url = %url, error = %e, "Failed to cache page");
+            }
+        } else {
+            // A page that changed into something uncacheable mustn't be
+            // revalidated against its old copy
+            remove(db, url);
+        }
+    }
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_and_validators() {
+        let db = crate::store::Db::temporary();
+        let page = Page {
+            status: 200,
+            final_url: "https://example.org/paper".to_string(),
+            body: Some("<title>Paper</title>".to_string()),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            fetched: Utc::now(),
+        };
+        assert!(page.is_cacheable());
+        assert!(!Page { etag: None, ..page.clone() }.is_cacheable());
+        assert!(!Page { status: 404, ..page.clone() }.is_cacheable());
+
+        assert!(load(&db, "https://example.org/paper").is_none());
+        save(&db, "https://example.org/paper", &page).unwrap();
+        let cached = load(&db, "https://example.org/paper").unwrap();
+        assert_eq!(cached, page);
+
+        let client = reqwest::Client::new();
+        let request = with_validators(client.get("https://example.org/paper"), Some(&cached)).build().unwrap();
+        assert_eq!(request.headers()[IF_NONE_MATCH], "\"abc\"");
+        assert!(request.headers().get(IF_MODIFIED_SINCE).is_none());
+        let plain = with_validators(client.get("https://example.org/paper"), None).build().unwrap();
+        assert!(plain.headers().get(IF_NONE_MATCH).is_none());
+
+        remove(&db, "https://example.org/paper");
+        assert!(load(&db, "https://example.org/paper").is_none());
+
+        assert!(is_text(Some("text/html; charset=utf-8")));
+        assert!(is_text(Some("application/xhtml+xml")));
+        assert!(!is_text(Some("application/pdf")));
+        assert!(!is_text(None));
+    }
+}
@@ -0,0 +1,240 @@
+//! Printable note page.
+//!
+//! `GET /note/{key}/print` renders a note on its own, for printing a paper
+//! summary to mark up on paper: no nav bar or Smart Add button, the light
+//! theme with print CSS, the paper's formatted citation at the top, and
+//! crosslinks turned into numbered references listed with their full URLs
+//! at the end, since a link can't be followed on paper.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+use crate::auth::is_logged_in;
+use crate::export::{clean_export_html, crosslinks_to_links, link_base, markdown_options, NOTE_LINK_SCHEME};
+use crate::models::{Note, NoteType};
+use crate::notes::html_escape;
+use crate::templates::{SOLARIZED_LIGHT, STYLE};
+use crate::AppState;
+
+const PRINT_STYLE: &str = r#"
+body { background: #fff; color: #000; font-family: Georgia, 'Times New Roman', serif; font-size: 11pt; line-height: 1.5; }
+.container { max-width: 42rem; }
+.print-actions { display: flex; gap: 1rem; align-items: center; margin-bottom: 1.5rem; font-family: sans-serif; font-size: 0.85rem; }
+.print-citation { border-left: 3px solid var(--base1); padding-left: 0.75rem; margin: 0.5rem 0 1.5rem; }
+.note-content sup { font-size: 0.75em; }
+.print-refs { margin-top: 2rem; border-top: 1px solid var(--base1); padding-top: 0.5rem; font-size: 9pt; }
+.print-refs li { overflow-wrap: anywhere; }
+@page { margin: 2cm; }
+@media print {
+    .print-actions { display: none; }
+    a { color: inherit; text-decoration: none; }
+    pre, blockquote, img, table { break-inside: avoid; }
+    h1, h2, h3 { break-after: avoid; }
+}
+"#;
+
+/// BibTeX `Last, First and Other, Name` as `First Last and Name Other`.
+fn format_authors(authors: &str) -> String {
+    let names: Vec<String> = authors
+        .split(" and ")
+        .map(|name| match name.split_once(',') {
+            Some((last, first)) => format!("{} {}", first.trim(), last.trim()),
+            None => name.trim().to_string(),
+        })
+        .collect();
+    match names.as_slice() {
+        [] => String::new(),
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+/// A paper's reference as `Authors. Year. Title. Venue. DOI`, from its
+/// canonical BibTeX entry; None for other notes.
+pub fn citation(note: &Note) -> Option<String> {
+    let NoteType::Paper(ref paper) = note.note_type else {
+        return None;
+    };
+    let effective = paper.effective_metadata(&note.title);
+    let mut parts = Vec::new();
+    parts.extend(effective.authors.as_deref().map(format_authors));
+    parts.extend(effective.year.map(|y| y.to_string()));
+    parts.push(effective.title.unwrap_or_else(|| note.title.clone()));
+    parts.extend(effective.venue);
+    let mut citation: String = parts.iter().map(|p| format!("{}. ", p.trim_end_matches('.'))).collect();
+    let link = paper.sources.iter().find_map(|s| match s.source_type.as_str() {
+        "doi" => Some(format!("https://doi.org/{}", s.identifier)),
+        "arxiv" => Some(format!("https://arxiv.org/abs/{}", s.identifier)),
+        _ => None,
+    });
+    if let Some(link) = link {
+        citation.push_str(&link);
+    }
+    Some(citation.trim_end().to_string())
+}
+
+/// The note body as HTML with crosslinks replaced by `[n]` markers, and
+/// the `(title, url)` of each reference. A crosslink to a hidden note is
+/// plain text unless `include_hidden`.
+fn body_with_references(
+    note: &Note,
+    notes: &HashMap<String, Note>,
+    link_base: &str,
+    include_hidden: bool,
+) -> (String, Vec<(String, String)>) {
+    let markdown = crosslinks_to_links(&note.raw_content, notes);
+    let mut references: Vec<(String, String)> = Vec::new();
+    // For each open link, the reference it ends with: None for ordinary
+    // links, Some(0) for a crosslink dropped to plain text
+    let mut open: Vec<Option<usize>> = Vec::new();
+    let events: Vec<Event> = Parser::new_ext(&markdown, markdown_options())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { ref dest_url, .. }) if dest_url.starts_with(NOTE_LINK_SCHEME) => {
+                let key = &dest_url[NOTE_LINK_SCHEME.len()..];
+                let target = notes.get(key).filter(|n| include_hidden || !n.hidden);
+                let number = target.map(|target| {
+                    let url = format!("{}/note/{}", link_base, key);
+                    match references.iter().position(|(_, u)| *u == url) {
+                        Some(i) => i + 1,
+                        None => {
+                            references.push((target.title.clone(), url));
+                            references.len()
+                        }
+                    }
+                });
+                open.push(Some(number.unwrap_or(0)));
+                None
+            }
+            Event::Start(Tag::Link { .. }) => {
+                open.push(None);
+                Some(event)
+            }
+            Event::End(TagEnd::Link) => match open.pop().flatten() {
+                None => Some(event),
+                Some(0) => None,
+                Some(n) => Some(Event::Html(format!("<sup>[{}]</sup>", n).into())),
+            },
+            other => Some(other),
+        })
+        .collect();
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, events.into_iter());
+    (clean_export_html(&body), references)
+}
+
+/// The print page for a note. `link_base` prefixes the reference URLs.
+pub fn render_print(note: &Note, notes: &HashMap<String, Note>, link_base: &str, include_hidden: bool) -> String {
+    let (body, references) = body_with_references(note, notes, link_base, include_hidden);
+    let citation_html = citation(note)
+        .map(|c| format!(r#"<p class="print-citation">{}</p>"#, html_escape(&c)))
+        .unwrap_or_default();
+    let references_html = if references.is_empty() {
+        String::new()
+    } else {
+        let items: String = references
+            .iter()
+            .map(|(title, url)| format!("<li>{} — {}</li>", html_escape(title), html_escape(url)))
+            .collect();
+        format!(r#"<ol class="print-refs">{}</ol>"#, items)
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>{title}</title>
+    <style>{theme_css}{STYLE}{PRINT_STYLE}</style>
+</head>
+<body>
+    <div class="container">
+        <div class="print-actions">
+            <button class="btn" onclick="window.print()">Print</button>
+            <a href="/note/{key}">Back to note</a>
+        </div>
+        <h1>{title}</h1>
+        {citation_html}
+        <div class="note-content">{body}</div>
+        {references_html}
+    </div>
+</body>
+</html>
+"#,
+        title = html_escape(&note.title),
+        key = note.key,
+        theme_css = SOLARIZED_LIGHT.css(),
+    )
+}
+
+pub async fn print_note(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    headers: HeaderMap,
+) -> Response {
+    let notes = state.notes_map();
+    let Some(note) = notes.get(&key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    let base = link_base(&state, &headers).unwrap_or_default();
+    let logged_in = is_logged_in(&jar, &state.db);
+    let page = Html(render_print(&crate::encryption::for_reader(note, logged_in), &notes, &base, logged_in));
+    crate::pwa::no_store_if_sealed(note, page.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{PaperMeta, PaperSource};
+    use std::path::PathBuf;
+
+    fn note(key: &str, title: &str, body: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            raw_content: body.to_string(),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_print_page() {
+        let mut paper = note("p1", "Soufflé", "Builds on [@d1], see [@s1], [@d1] and [the site](https://souffle-lang.github.io).");
+        paper.note_type = NoteType::Paper(PaperMeta {
+            bibtex_entries: vec![
+                "@inproceedings{jordan2016souffle,\n  title={Soufflé: On Synthesis of Program Analyzers},\n  author={Jordan, Herbert and Scholz, Bernhard and Suboti{\\'c}, Pavle},\n  booktitle={CAV},\n  year={2016}\n}".to_string(),
+            ],
+            canonical_key: None,
+            sources: vec![PaperSource { source_type: "doi".to_string(), identifier: "10.1007/978-3-319-41540-6_23".to_string() }],
+            versions: vec![],
+        });
+        let mut secret = note("s1", "Secret", "");
+        secret.hidden = true;
+        let notes: HashMap<String, Note> =
+            [paper, note("d1", "Datalog", ""), secret].into_iter().map(|n| (n.key.clone(), n)).collect();
+
+        let cite = citation(&notes["p1"]).unwrap();
+        assert!(cite.starts_with("Herbert Jordan, Bernhard Scholz and "), "{cite}");
+        assert!(cite.contains(". 2016. Soufflé: On Synthesis of Program Analyzers. CAV. https://doi.org/10.1007/"));
+        assert_eq!(citation(&notes["d1"]), None);
+
+        let html = render_print(&notes["p1"], &notes, "https://notes.example", false);
+        assert!(html.contains(r#"Builds on Datalog<sup>[1]</sup>, see Secret, Datalog<sup>[1]</sup>"#));
+        assert!(html.contains(r#"<a href="https://souffle-lang.github.io" rel="noopener noreferrer">the site</a>"#));
+        assert!(html.contains("<li>Datalog — https://notes.example/note/d1</li>"));
+        assert!(!html.contains("/note/s1"));
+        assert!(!html.contains("smart-add"));
+        let html = render_print(&notes["p1"], &notes, "https://notes.example", true);
+        assert!(html.contains("<li>Secret — https://notes.example/note/s1</li>"));
+    }
+}
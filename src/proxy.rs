@@ -0,0 +1,421 @@
+//! Reverse-proxy support.
+//!
+//! When `base_path` is set (e.g. `/notes`), `main` nests the whole router
+//! under it and `rewrite_urls` prefixes the root-relative URLs the app
+//! generates: links, form actions and `fetch` paths in HTML pages,
+//! `Location` headers, and cookie paths. Templates keep writing plain
+//! `/note/...` URLs. Pages over `MAX_REWRITE` are sent unmodified, with a
+//! warning, rather than buffered whole.
+//!
+//! With `trust_proxy`, the client IP and scheme are taken from the
+//! `X-Forwarded-For` / `X-Forwarded-Proto` headers instead of the socket.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures_util::StreamExt;
+
+use crate::AppState;
+
+/// Largest HTML page `rewrite_urls` buffers to prefix its URLs; bigger ones
+/// go out as they are.
+const MAX_REWRITE: usize = 16 * 1024 * 1024;
+
+/// First path segments of the app's routes. Only URLs starting with one of
+/// these (or exactly `/`) are prefixed, so unrelated strings such as
+/// regex literals in inline scripts are left alone. `test_route_roots`
+/// checks that every route in `main.rs` is covered.
+const ROUTE_ROOTS: &[&str] = &[
+    "api",
+    "note",
+    "random",
+    "new",
+    "search",
+    "papers",
+    "authors",
+    "author",
+    "time",
+    "graph",
+    "browse",
+    "shared",
+    "pdfs",
+    "login",
+    "logout",
+    "import",
+    "export",
+    "trash",
+    "problems",
+    "today",
+    "journal",
+    "tasks",
+    "agenda",
+    "board",
+    "reminders",
+    "review",
+    "queue",
+    "history",
+    "courses",
+    "habits",
+    "ask",
+    "archive",
+    "links",
+    "bibliography.bib",
+    "feed.xml",
+    "sitemap.xml",
+    "robots.txt",
+    "healthz",
+    "readyz",
+    "metrics",
+    "admin",
+    "stats",
+    "dav",
+    "manifest.webmanifest",
+    "sw.js",
+    "icon.svg",
+];
+
+/// Normalize a configured base path to `""` or `/segment[/segment...]`.
+pub fn normalize_base_path(path: &str) -> Result<String, String> {
+    let trimmed = path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        return Ok(String::new());
+    }
+    let valid = trimmed
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./".contains(c))
+        && !trimmed.split('/').any(|s| s.is_empty() || s == "." || s == "..");
+    if !valid {
+        return Err(format!("Invalid base path '{}'", path));
+    }
+    Ok(format!("/{}", trimmed))
+}
+
+/// Whether `rest` (the text after a leading `/`) is an app URL.
+fn is_app_url(rest: &str) -> bool {
+    let is_boundary = |c: char| matches!(c, '/' | '?' | '#' | '"' | '\'' | '`' | '$');
+    match rest.chars().next() {
+        None => false,
+        Some(c) if is_boundary(c) && c != '/' => true,
+        _ => ROUTE_ROOTS.iter().any(|root| {
+            rest.strip_prefix(root)
+                .and_then(|after| after.chars().next())
+                .is_some_and(is_boundary)
+        }),
+    }
+}
+
+/// Prefix quoted root-relative app URLs (`"/note/x"`, `'/api/...'`,
+/// `` `/pdfs/${f}` ``) with `base`. The root URL `"/"` becomes `"{base}"`,
+/// which is where the nested index route lives.
+pub fn prefix_urls(text: &str, base: &str) -> String {
+    if base.is_empty() {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len() + 256);
+    let mut prev: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        out.push(c);
+        if c == '/'
+            && matches!(prev, Some('"' | '\'' | '`' | '('))
+            && is_app_url(&text[i + 1..])
+        {
+            // Replace the slash just written with the base, keeping the
+            // slash unless this is the root URL.
+            out.pop();
+            out.push_str(base);
+            if chars.peek().is_some_and(|&(_, next)| next != '/' && !is_root_suffix(next)) {
+                out.push('/');
+            }
+        }
+        prev = Some(c);
+    }
+    out
+}
+
+/// Characters that, right after a leading `/`, mark the root URL.
+fn is_root_suffix(c: char) -> bool {
+    matches!(c, '?' | '#' | '"' | '\'' | '`' | '$' | ')' | ';')
+}
+
+/// Prefix a `Location` value or other single URL.
+fn prefix_location(location: &str, base: &str) -> String {
+    match location.strip_prefix('/') {
+        Some(rest) if rest.is_empty() || rest.starts_with(is_root_suffix) => {
+            format!("{}{}", base, rest)
+        }
+        Some(rest) if !rest.starts_with('/') => format!("{}/{}", base, rest),
+        _ => location.to_string(),
+    }
+}
+
+/// Scope `Path=/` cookies to the base path.
+fn prefix_cookie_path(cookie: &str, base: &str) -> String {
+    cookie
+        .split("; ")
+        .map(|part| match part.strip_prefix("Path=") {
+            Some(path) => format!("Path={}", prefix_location(path, base)),
+            None => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Client address and scheme, honoring proxy headers when trusted.
+pub fn client_info(
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+    tls: bool,
+    trust_proxy: bool,
+) -> (Option<String>, &'static str) {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let peer_ip = peer.map(|p| p.ip().to_string());
+    let direct_scheme = if tls { "https" } else { "http" };
+    if !trust_proxy {
+        return (peer_ip, direct_scheme);
+    }
+
+    let ip = header("x-forwarded-for")
+        .and_then(|v| v.split(',').next())
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .or(peer_ip);
+    let scheme = match header("x-forwarded-proto").map(|v| v.trim().to_ascii_lowercase()) {
+        Some(p) if p == "https" => "https",
+        Some(p) if p == "http" => "http",
+        _ => direct_scheme,
+    };
+    (ip, scheme)
+}
+
+/// Middleware: record client info on the request span and apply the base
+/// path to the response.
+pub async fn rewrite_urls(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|c| c.0);
+    let (ip, scheme) = client_info(
+        request.headers(),
+        peer,
+        state.config.tls.enabled(),
+        state.config.trust_proxy,
+    );
+    let span = tracing::Span::current();
+    if let Some(ref ip) = ip {
+        span.record("client_ip", ip.as_str());
+    }
+    span.record("scheme", scheme);
+
+    let response = next.run(request).await;
+    let base = state.config.base_path.as_str();
+    if base.is_empty() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+
+    if let Some(location) = parts.headers.get(LOCATION).and_then(|v| v.to_str().ok()) {
+        if let Ok(value) = HeaderValue::from_str(&prefix_location(location, base)) {
+            parts.headers.insert(LOCATION, value);
+        }
+    }
+
+    let cookies: Vec<HeaderValue> = parts
+        .headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| HeaderValue::from_str(&prefix_cookie_path(v, base)).ok())
+        .collect();
+    if !cookies.is_empty() {
+        parts.headers.remove(SET_COOKIE);
+        for cookie in cookies {
+            parts.headers.append(SET_COOKIE, cookie);
+        }
+    }
+
+    let is_html = parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if !is_html {
+        return Response::from_parts(parts, body);
+    }
+
+    let declared = parts
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > MAX_REWRITE) {
+        tracing::warn!(bytes = declared, "HTML response too large to rewrite; sent unmodified");
+        return Response::from_parts(parts, body);
+    }
+
+    let mut stream = body.into_data_stream();
+    let mut buffered = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => buffered.extend_from_slice(&chunk),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to read response body");
+                return Response::from_parts(parts, Body::empty());
+            }
+        }
+        if buffered.len() > MAX_REWRITE {
+            // Hand on what was read so far, then the rest as it comes
+            tracing::warn!(bytes = buffered.len(), "HTML response too large to rewrite; sent unmodified");
+            let head = futures_util::stream::once(async move { Ok(Bytes::from(buffered)) });
+            return Response::from_parts(parts, Body::from_stream(head.chain(stream)));
+        }
+    }
+    let html = prefix_urls(&String::from_utf8_lossy(&buffered), base);
+    parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_base_path() {
+        assert_eq!(normalize_base_path("").unwrap(), "");
+        assert_eq!(normalize_base_path("/").unwrap(), "");
+        assert_eq!(normalize_base_path("notes/").unwrap(), "/notes");
+        assert_eq!(normalize_base_path("/apps/notes").unwrap(), "/apps/notes");
+        assert!(normalize_base_path("/no tes").is_err());
+        assert!(normalize_base_path("/a/../b").is_err());
+        assert!(normalize_base_path("/a\"b").is_err());
+    }
+
+    #[test]
+    fn test_prefix_urls() {
+        let html = r#"<a href="/">All</a><a href="/note/x?edit=true">x</a>
+<form action="/search"></form><img src="/pdfs/a.pdf">
+<script>fetch('/api/note/' + key); const u = `/pdfs/${f}`; location.href = '/';
+const re = /"/g; const ext = "https://example.com/note/y"; const p = "/static/x";</script>"#;
+        let out = prefix_urls(html, "/notes");
+        assert!(out.contains(r#"href="/notes""#));
+        assert!(out.contains(r#"href="/notes/note/x?edit=true""#));
+        assert!(out.contains(r#"action="/notes/search""#));
+        assert!(out.contains(r#"src="/notes/pdfs/a.pdf""#));
+        assert!(out.contains("fetch('/notes/api/note/'"));
+        assert!(out.contains("`/notes/pdfs/${f}`"));
+        assert!(out.contains("location.href = '/notes'"));
+        assert!(out.contains(r#"const re = /"/g;"#));
+        assert!(out.contains("https://example.com/note/y"));
+        assert!(out.contains(r#""/static/x""#));
+        assert_eq!(prefix_urls(html, ""), html);
+    }
+
+    #[test]
+    fn test_prefix_nav_bar() {
+        let nav = prefix_urls(&crate::templates::nav_bar(None, true), "/notes");
+        let mut links = nav.split(r#"href=""#).skip(1).chain(nav.split(r#"action=""#).skip(1)).peekable();
+        assert!(links.peek().is_some());
+        for link in links {
+            assert!(link.starts_with("/notes"), "unprefixed nav link: {}", &link[..link.find('"').unwrap_or(link.len())]);
+        }
+    }
+
+    #[test]
+    fn test_route_roots() {
+        let main = include_str!("main.rs");
+        let mut roots: Vec<&str> = main
+            .split(".route(")
+            .chain(main.split("nest_service("))
+            .skip(1)
+            .filter_map(|call| call.trim_start().strip_prefix("\"/"))
+            .map(|path| &path[..path.find(['/', '"', '{']).unwrap_or(path.len())])
+            .filter(|root| !root.is_empty())
+            .collect();
+        roots.sort();
+        roots.dedup();
+        assert!(roots.len() > 20);
+        for root in roots {
+            assert!(ROUTE_ROOTS.contains(&root), "/{} is missing from ROUTE_ROOTS", root);
+        }
+    }
+
+    #[test]
+    fn test_prefix_location_and_cookie() {
+        assert_eq!(prefix_location("/login", "/notes"), "/notes/login");
+        assert_eq!(prefix_location("/", "/notes"), "/notes");
+        assert_eq!(prefix_location("/?hidden=true", "/notes"), "/notes?hidden=true");
+        assert_eq!(prefix_location("//evil.com", "/notes"), "//evil.com");
+        assert_eq!(
+            prefix_cookie_path("s=1; Path=/; HttpOnly", "/notes"),
+            "s=1; Path=/notes; HttpOnly"
+        );
+    }
+
+    #[test]
+    fn test_client_info() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.5, 10.0.0.1".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        let peer: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+
+        let (ip, scheme) = client_info(&headers, Some(peer), false, true);
+        assert_eq!(ip.as_deref(), Some("203.0.113.5"));
+        assert_eq!(scheme, "https");
+
+        let (ip, scheme) = client_info(&headers, Some(peer), false, false);
+        assert_eq!(ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(scheme, "http");
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_urls_size_cap() {
+        use axum::{middleware, response::Html, routing::get, Router};
+        use tower::ServiceExt;
+
+        let dir = crate::test_util::TempDir::new("proxy");
+        let state = Arc::new(AppState::new(crate::config::Config {
+            notes_dir: dir.join("content"),
+            pdfs_dir: dir.join("pdfs"),
+            db_path: dir.join("db"),
+            base_path: "/notes".to_string(),
+            ..Default::default()
+        }));
+        let large = format!(r#"<a href="/note/x">x</a>{}"#, " ".repeat(MAX_REWRITE));
+        let (page, chunks) = (large.clone(), large.clone());
+        let app = Router::new()
+            .route("/small", get(|| async { Html(r#"<a href="/note/x">x</a>"#) }))
+            .route("/large", get(move || async move { Html(page) }))
+            .route(
+                "/streamed",
+                get(move || async move {
+                    // No Content-Length: found to be too large while reading
+                    let (head, tail) = chunks.split_at(MAX_REWRITE / 2);
+                    let parts = [head.to_string(), tail.to_string()].map(Ok::<_, std::io::Error>);
+                    ([(CONTENT_TYPE, "text/html")], Body::from_stream(futures_util::stream::iter(parts)))
+                }),
+            )
+            .layer(middleware::from_fn_with_state(state, rewrite_urls));
+        let body = |path: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(Request::get(path).body(Body::empty()).unwrap()).await.unwrap();
+                axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap()
+            }
+        };
+
+        assert_eq!(body("/small").await, r#"<a href="/notes/note/x">x</a>"#);
+        assert_eq!(body("/large").await, large.as_bytes());
+        assert_eq!(body("/streamed").await, large.as_bytes());
+    }
+}
@@ -0,0 +1,726 @@
+//! Installable web app with offline edits.
+//!
+//! `/manifest.webmanifest` and `/sw.js` make the site installable on a
+//! phone. The service worker answers page loads from its cache when the
+//! network is gone, and queues `POST /api/batch` requests it couldn't send
+//! in IndexedDB, replaying them once the browser is back online. Every
+//! batched op carries a client-generated `id` whose result is remembered
+//! for `OP_DAYS`, so a replayed op gets its first answer instead of being
+//! applied twice. Saves and deletes of existing notes must carry the
+//! `base_hash` they were made against; one that no longer matches the note
+//! on disk comes back as a conflict rather than overwriting it. Pages of
+//! encrypted notes are sent `no-store` and never enter the worker's cache.
+//!
+//! `GET /api/changes?since=<cursor>` lists the notes changed or deleted
+//! since `cursor`. The change log lives in the `changes` tree and is brought
+//! up to date from the notes on disk on each call, so edits made through
+//! git, WebDAV or sync show up as well as those made here.
+//!
+//! The worker is registered on every page except under `notes publish`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::auth::is_logged_in;
+use crate::encryption::{open_note, write_file};
+use crate::formats::check_writable;
+use crate::models::Note;
+use crate::store::Db;
+use crate::{trash, validate_path_within, AppState};
+
+/// Sequence number (big-endian) → note key, one entry per key.
+const LOG_TREE: &str = "changes";
+/// Note key → `Tracked`.
+const TRACKED_TREE: &str = "changes_tracked";
+/// Op id → `StoredResult`.
+const OPS_TREE: &str = "pwa_ops";
+/// How long op results are kept for replays.
+const OP_DAYS: i64 = 30;
+/// Changes per `/api/changes` page, and ops per batch.
+const PAGE_SIZE: usize = 200;
+const MAX_OPS: usize = 100;
+
+/// One refresh of the change log at a time.
+static RECORDING: Mutex<()> = Mutex::new(());
+/// One batch at a time, so two replays of the same op can't both apply it.
+static APPLYING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// What the change log last saw of a note.
+#[derive(Debug, Serialize, Deserialize)]
+struct Tracked {
+    seq: u64,
+    hash: String,
+    deleted: bool,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn note_hash(note: &Note) -> String {
+    sha256_hex(note.full_file_content.as_bytes())
+}
+
+/// Bring the change log up to date with `notes`; returns the latest
+/// sequence number.
+pub fn record(db: &Db, notes: &[Note]) -> Result<u64, String> {
+    let _guard = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let log = db.open_tree(LOG_TREE).map_err(|e| e.to_string())?;
+    let tracked = db.open_tree(TRACKED_TREE).map_err(|e| e.to_string())?;
+    let mut last = log
+        .iter()
+        .flatten()
+        .last()
+        .and_then(|(k, _)| Some(u64::from_be_bytes(k.as_slice().try_into().ok()?)))
+        .unwrap_or(0);
+
+    let mut bump = |key: &str, hash: String, deleted: bool, prev: Option<u64>| -> Result<(), String> {
+        last += 1;
+        if let Some(prev) = prev {
+            log.remove(prev.to_be_bytes()).map_err(|e| e.to_string())?;
+        }
+        log.insert(last.to_be_bytes(), key.as_bytes()).map_err(|e| e.to_string())?;
+        let entry = serde_json::to_vec(&Tracked { seq: last, hash, deleted }).map_err(|e| e.to_string())?;
+        tracked.insert(key.as_bytes(), entry).map_err(|e| e.to_string())?;
+        Ok(())
+    };
+
+    let mut seen: HashMap<Vec<u8>, Tracked> = tracked
+        .iter()
+        .flatten()
+        .filter_map(|(k, v)| Some((k, serde_json::from_slice(&v).ok()?)))
+        .collect();
+    for note in notes {
+        let hash = note_hash(note);
+        match seen.remove(note.key.as_bytes()) {
+            Some(t) if t.hash == hash && !t.deleted => {}
+            prev => bump(&note.key, hash, false, prev.map(|t| t.seq))?,
+        }
+    }
+    for (key, t) in seen {
+        if !t.deleted {
+            bump(&String::from_utf8_lossy(&key), t.hash, true, Some(t.seq))?;
+        }
+    }
+    Ok(last)
+}
+
+/// One entry of `/api/changes`. Deleted notes only carry their key.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub key: String,
+    pub deleted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// SHA-256 of the file as stored; the `base_hash` for later edits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The note's text, decrypted when this server holds the key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangesResponse {
+    /// Pass back as `since` for the next page or the next poll.
+    pub cursor: String,
+    pub changes: Vec<Change>,
+    /// More changes are waiting past `cursor`.
+    pub more: bool,
+}
+
+/// Changes after `since`, at most `PAGE_SIZE` of them.
+pub fn changes_since(db: &Db, notes: &[Note], since: u64) -> Result<ChangesResponse, String> {
+    let head = record(db, notes)?;
+    let log = db.open_tree(LOG_TREE).map_err(|e| e.to_string())?;
+    let by_key: HashMap<&str, &Note> = notes.iter().map(|n| (n.key.as_str(), n)).collect();
+
+    let mut entries = Vec::new();
+    for (k, v) in log.iter().flatten() {
+        let Some(seq) = k.as_slice().try_into().ok().map(u64::from_be_bytes) else {
+            continue;
+        };
+        if seq > since {
+            entries.push((seq, String::from_utf8_lossy(&v).into_owned()));
+        }
+    }
+    let more = entries.len() > PAGE_SIZE;
+    entries.truncate(PAGE_SIZE);
+    let cursor = if more { entries.last().map_or(since, |(seq, _)| *seq) } else { head.max(since) };
+
+    let changes = entries
+        .into_iter()
+        .map(|(_, key)| match by_key.get(key.as_str()) {
+            Some(note) => Change {
+                path: Some(note.path.to_string_lossy().replace('\\', "/")),
+                title: Some(note.title.clone()),
+                hash: Some(note_hash(note)),
+                content: open_note(note).ok().map(|n| n.full_file_content.clone()),
+                key,
+                deleted: false,
+            },
+            None => Change { key, deleted: true, path: None, title: None, hash: None, content: None },
+        })
+        .collect();
+    Ok(ChangesResponse { cursor: cursor.to_string(), changes, more })
+}
+
+// ============================================================================
+// Batched writes
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub ops: Vec<Op>,
+}
+
+/// One queued edit. `id` is generated by the client and names the op across
+/// retries.
+#[derive(Debug, Deserialize)]
+pub struct Op {
+    pub id: String,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Action {
+    Save { key: String, content: String, base_hash: Option<String> },
+    Create { path: String, content: String },
+    Delete { key: String, base_hash: Option<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpStatus {
+    Applied,
+    /// The note changed since `base_hash`; `current` holds what it is now.
+    Conflict,
+    /// Not applied and not remembered, so a retry tries again.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpResult {
+    pub id: String,
+    pub status: OpStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl OpResult {
+    fn new(id: &str, status: OpStatus) -> Self {
+        OpResult { id: id.to_string(), status, key: None, hash: None, current: None, error: None }
+    }
+
+    fn applied(id: &str, key: &str, hash: Option<String>) -> Self {
+        OpResult { key: Some(key.to_string()), hash, ..Self::new(id, OpStatus::Applied) }
+    }
+
+    fn conflict(id: &str, note: &Note) -> Self {
+        OpResult {
+            key: Some(note.key.clone()),
+            hash: Some(note_hash(note)),
+            current: open_note(note).ok().map(|n| n.full_file_content.clone()),
+            ..Self::new(id, OpStatus::Conflict)
+        }
+    }
+
+    fn failed(id: &str, error: impl Into<String>) -> Self {
+        OpResult { error: Some(error.into()), ..Self::new(id, OpStatus::Failed) }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredResult {
+    at: DateTime<Utc>,
+    result: OpResult,
+}
+
+fn load_result(db: &Db, id: &str) -> Option<OpResult> {
+    let ops = db.open_tree(OPS_TREE).ok()?;
+    let stored: StoredResult = serde_json::from_slice(&ops.get(id.as_bytes()).ok()??).ok()?;
+    Some(stored.result)
+}
+
+fn save_result(db: &Db, result: &OpResult) -> Result<(), String> {
+    let ops = db.open_tree(OPS_TREE).map_err(|e| e.to_string())?;
+    let stored = StoredResult { at: Utc::now(), result: result.clone() };
+    let bytes = serde_json::to_vec(&stored).map_err(|e| e.to_string())?;
+    ops.insert(result.id.as_bytes(), bytes).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Forget op results older than `OP_DAYS`.
+fn prune_results(db: &Db, now: DateTime<Utc>) {
+    let Ok(ops) = db.open_tree(OPS_TREE) else {
+        return;
+    };
+    for (k, v) in ops.iter().flatten() {
+        let expired = serde_json::from_slice::<StoredResult>(&v)
+            .map_or(true, |s| now - s.at > Duration::days(OP_DAYS));
+        if expired {
+            let _ = ops.remove(k);
+        }
+    }
+}
+
+/// A new note's path: relative, `.md`, no dotfiles, inside the notes dir.
+fn new_note_path(notes_dir: &PathBuf, path: &str) -> Result<PathBuf, String> {
+    let valid = path.ends_with(".md")
+        && path
+            .split('/')
+            .all(|s| !s.is_empty() && !s.starts_with('.') && !s.contains(['\\', '\0']));
+    if !valid {
+        return Err(format!("Invalid path '{}'", path));
+    }
+    let full = notes_dir.join(path);
+    validate_path_within(notes_dir, &full)?;
+    Ok(full)
+}
+
+/// Apply one op. Returns the result and the paths to commit.
+fn apply(state: &AppState, op: &Op) -> (OpResult, Vec<PathBuf>) {
+    let id = op.id.as_str();
+    let notes_map = state.notes_map();
+    // Changing an existing note needs the hash it was based on, so an edit
+    // queued before someone else's can't silently replace it
+    let base_mismatch = |note: &Note, base: &Option<String>| match base {
+        None => Some(OpResult::failed(id, "base_hash is required to change an existing note")),
+        Some(b) if *b != note_hash(note) => Some(OpResult::conflict(id, note)),
+        Some(_) => None,
+    };
+
+    match &op.action {
+        Action::Save { key, content, base_hash } => {
+            let Some(note) = notes_map.get(key) else {
+                return (OpResult::failed(id, "Note not found"), vec![]);
+            };
+            if let Some(result) = base_mismatch(note, base_hash) {
+                return (result, vec![]);
+            }
+            if let Err(e) = check_writable(&note.path) {
+                return (OpResult::failed(id, e), vec![]);
+            }
+            let sealed = match write_file(&state.notes_dir.join(&note.path), content) {
+                Ok(sealed) => sealed,
+                Err(e) => return (OpResult::failed(id, e), vec![]),
+            };
+            state.invalidate_notes_cache();
+            state.reindex_graph_note(key);
+            (OpResult::applied(id, key, Some(sha256_hex(sealed.as_bytes()))), vec![note.path.clone()])
+        }
+        Action::Create { path, content } => {
+            let full = match new_note_path(&state.notes_dir, path) {
+                Ok(full) => full,
+                Err(e) => return (OpResult::failed(id, e), vec![]),
+            };
+            let rel = PathBuf::from(path);
+            let existing = notes_map.values().find(|n| n.path == rel);
+            if full.exists() {
+                // The same text already there means an earlier try got through
+                return match existing {
+                    Some(note) if open_note(note).is_ok_and(|n| n.full_file_content == *content) => {
+                        (OpResult::applied(id, &note.key, Some(note_hash(note))), vec![])
+                    }
+                    Some(note) => (OpResult::conflict(id, note), vec![]),
+                    None => (OpResult::failed(id, format!("'{}' already exists", path)), vec![]),
+                };
+            }
+            let sealed = match write_file(&full, content) {
+                Ok(sealed) => sealed,
+                Err(e) => return (OpResult::failed(id, e), vec![]),
+            };
+            state.invalidate_notes_cache();
+            let Some(key) = state.load_notes().into_iter().find(|n| n.path == rel).map(|n| n.key) else {
+                return (OpResult::failed(id, "Created file is not a note"), vec![rel]);
+            };
+            state.reindex_graph_note(&key);
+            (OpResult::applied(id, &key, Some(sha256_hex(sealed.as_bytes()))), vec![rel])
+        }
+        Action::Delete { key, base_hash } => {
+            let Some(note) = notes_map.get(key) else {
+                // Already gone: deleting is idempotent
+                return (OpResult::applied(id, key, None), vec![]);
+            };
+            if let Some(result) = base_mismatch(note, base_hash) {
+                return (result, vec![]);
+            }
+            let trashed = match trash::trash_note(&state.notes_dir, note, crate::timezone::now().naive_local()) {
+                Ok(t) => t,
+                Err(e) => return (OpResult::failed(id, e), vec![]),
+            };
+            state.invalidate_notes_cache();
+            state.remove_graph_note(key);
+            (OpResult::applied(id, key, None), vec![trashed.path.clone(), trashed.dir()])
+        }
+    }
+}
+
+/// Apply `ops` in order, answering replayed ids from their stored result.
+/// Returns the results and the paths to commit.
+pub fn apply_batch(state: &AppState, ops: &[Op]) -> (Vec<OpResult>, Vec<PathBuf>) {
+    prune_results(&state.db, Utc::now());
+    let mut results = Vec::with_capacity(ops.len());
+    let mut paths = Vec::new();
+    for op in ops {
+        if let Some(result) = load_result(&state.db, &op.id) {
+            results.push(result);
+            continue;
+        }
+        let (result, changed) = apply(state, op);
+        if result.status != OpStatus::Failed {
+            if let Err(e) = save_result(&state.db, &result) {
+                tracing::warn!(id = %op.id, error = %e, "Failed to record op result");
+            }
+        }
+        paths.extend(changed);
+        results.push(result);
+    }
+    (results, paths)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ChangesQuery {
+    pub since: Option<String>,
+}
+
+/// `GET /api/changes?since=<cursor>`: notes changed since `cursor`, or all
+/// of them without one.
+pub async fn changes(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Query(query): Query<ChangesQuery>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let since = match query.since.as_deref().filter(|s| !s.is_empty()).map(str::parse::<u64>) {
+        None => 0,
+        Some(Ok(since)) => since,
+        Some(Err(_)) => return (StatusCode::BAD_REQUEST, "Invalid cursor").into_response(),
+    };
+
+    let state = state.clone();
+    let result = tokio::task::spawn_blocking(move || changes_since(&state.db, &state.load_notes(), since))
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+    match result {
+        Ok(response) => Json(response).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+/// `POST /api/batch`: apply queued edits; answers `{results}` in op order.
+pub async fn batch(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Json(body): Json<BatchRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if body.ops.len() > MAX_OPS {
+        return (StatusCode::BAD_REQUEST, format!("At most {} ops per batch", MAX_OPS)).into_response();
+    }
+    if body.ops.iter().any(|op| op.id.is_empty() || op.id.len() > 128) {
+        return (StatusCode::BAD_REQUEST, "Every op needs an id of at most 128 bytes").into_response();
+    }
+
+    let _guard = APPLYING.lock().await;
+    let worker = state.clone();
+    let (results, paths) = match tokio::task::spawn_blocking(move || apply_batch(&worker, &body.ops)).await {
+        Ok(applied) => applied,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    if !paths.is_empty() {
+        let applied = results.iter().filter(|r| r.status == OpStatus::Applied).count();
+        let notes_dir = state.notes_dir.clone();
+        let summary = format!("offline edits from notes ({} ops)", applied);
+        state.spawn_background(move || trash::commit_paths(&notes_dir, &paths, &summary));
+    }
+    Json(serde_json::json!({ "results": results })).into_response()
+}
+
+/// Mark a page that shows an encrypted note `no-store`, so neither the
+/// browser nor the service worker keeps a copy of the plaintext.
+pub fn no_store_if_sealed(note: &Note, mut response: Response) -> Response {
+    if crate::encryption::is_sealed(&note.raw_content) {
+        response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+    }
+    response
+}
+
+/// `GET /manifest.webmanifest`.
+pub async fn manifest(State(state): State<Arc<AppState>>) -> Response {
+    let base = &state.config.base_path;
+    let manifest = serde_json::json!({
+        "name": "Notes",
+        "short_name": "Notes",
+        "start_url": format!("{}/", base),
+        "scope": format!("{}/", base),
+        "display": "standalone",
+        "background_color": "#ffffff",
+        "theme_color": "#1f2937",
+        "icons": [{ "src": format!("{}/icon.svg", base), "sizes": "any", "type": "image/svg+xml" }],
+    });
+    ([(header::CONTENT_TYPE, "application/manifest+json")], manifest.to_string()).into_response()
+}
+
+/// `GET /icon.svg`: the app icon.
+pub async fn icon() -> Response {
+    ([(header::CONTENT_TYPE, "image/svg+xml"), (header::CACHE_CONTROL, "max-age=86400")], ICON).into_response()
+}
+
+/// `GET /sw.js`: the service worker, scoped to the base path.
+pub async fn service_worker(State(state): State<Arc<AppState>>) -> Response {
+    let base = serde_json::to_string(&state.config.base_path).unwrap_or_else(|_| "\"\"".to_string());
+    (
+        [(header::CONTENT_TYPE, "application/javascript"), (header::CACHE_CONTROL, "no-cache")],
+        SERVICE_WORKER.replace("__BASE__", &base),
+    )
+        .into_response()
+}
+
+const ICON: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 64 64"><rect width="64" height="64" rx="12" fill="#1f2937"/><path d="M18 14h22l8 8v28H18z" fill="#f9fafb"/><path d="M24 30h16M24 37h16M24 44h10" stroke="#1f2937" stroke-width="3"/></svg>"##;
+
+const SERVICE_WORKER: &str = r#"const BASE = __BASE__;
+const PAGES = 'notes-pages-v1';
+const DB_NAME = 'notes-offline';
+
+self.addEventListener('install', () => self.skipWaiting());
+self.addEventListener('activate', event => event.waitUntil(self.clients.claim()));
+
+function openQueue() {
+    return new Promise((resolve, reject) => {
+        const req = indexedDB.open(DB_NAME, 1);
+        req.onupgradeneeded = () => req.result.createObjectStore('queue', {autoIncrement: true});
+        req.onsuccess = () => resolve(req.result);
+        req.onerror = () => reject(req.error);
+    });
+}
+
+async function enqueue(body) {
+    const db = await openQueue();
+    await new Promise((resolve, reject) => {
+        const tx = db.transaction('queue', 'readwrite');
+        tx.objectStore('queue').add(body);
+        tx.oncomplete = resolve;
+        tx.onerror = () => reject(tx.error);
+    });
+}
+
+async function queued() {
+    const db = await openQueue();
+    return new Promise((resolve, reject) => {
+        const tx = db.transaction('queue');
+        const req = tx.objectStore('queue').openCursor();
+        const items = [];
+        req.onsuccess = () => {
+            const cursor = req.result;
+            if (!cursor) return resolve({db, items});
+            items.push({key: cursor.key, body: cursor.value});
+            cursor.continue();
+        };
+        req.onerror = () => reject(req.error);
+    });
+}
+
+// Replay queued batches in order. A batch is dropped once the server has
+// answered it, unless it was turned away for a reason a retry could fix.
+let flushing = null;
+function flush() {
+    if (!flushing) {
+        flushing = (async () => {
+            const {db, items} = await queued();
+            for (const item of items) {
+                let resp;
+                try {
+                    resp = await fetch(BASE + '/api/batch', {
+                        method: 'POST',
+                        headers: {'Content-Type': 'application/json'},
+                        body: item.body,
+                        credentials: 'same-origin',
+                    });
+                } catch (e) {
+                    break;
+                }
+                if (!resp.ok && (resp.status >= 500 || [401, 403, 429].includes(resp.status))) break;
+                await new Promise(resolve => {
+                    const tx = db.transaction('queue', 'readwrite');
+                    tx.objectStore('queue').delete(item.key);
+                    tx.oncomplete = resolve;
+                    tx.onerror = resolve;
+                });
+                const clients = await self.clients.matchAll();
+                const results = resp.ok ? (await resp.json()).results : [];
+                clients.forEach(c => c.postMessage({type: 'synced', results}));
+            }
+        })().finally(() => { flushing = null; });
+    }
+    return flushing;
+}
+
+self.addEventListener('sync', event => event.waitUntil(flush()));
+self.addEventListener('message', event => {
+    if (event.data === 'flush') event.waitUntil(flush());
+});
+
+self.addEventListener('fetch', event => {
+    const req = event.request;
+    const url = new URL(req.url);
+    if (url.origin !== self.location.origin || !url.pathname.startsWith(BASE + '/')) return;
+
+    if (req.method === 'POST' && url.pathname === BASE + '/api/batch') {
+        event.respondWith(req.clone().text().then(body =>
+            fetch(req).catch(async () => {
+                await enqueue(body);
+                if (self.registration.sync) self.registration.sync.register('batch').catch(() => {});
+                return new Response(JSON.stringify({queued: true}), {
+                    status: 202,
+                    headers: {'Content-Type': 'application/json'},
+                });
+            })
+        ));
+        return;
+    }
+    if (req.method !== 'GET' || url.pathname.startsWith(BASE + '/api/')) return;
+
+    if (url.pathname === BASE + '/logout') {
+        event.waitUntil(caches.delete(PAGES));
+        return;
+    }
+    // Network first; the last copy of a page when offline
+    event.respondWith(
+        fetch(req).then(resp => {
+            if (resp.ok && req.mode === 'navigate') {
+                // Pages of encrypted notes are sent no-store: never kept
+                const noStore = /no-store/.test(resp.headers.get('Cache-Control') || '');
+                const copy = resp.clone();
+                caches.open(PAGES).then(cache => noStore ? cache.delete(req) : cache.put(req, copy));
+            }
+            return resp;
+        }).catch(async () => {
+            const cached = await caches.match(req);
+            return cached || new Response('<h1>Offline</h1><p>This page has not been opened on this device yet.</p>', {
+                status: 503,
+                headers: {'Content-Type': 'text/html; charset=utf-8'},
+            });
+        })
+    );
+});
+"#;
+
+/// Registers the service worker; included in every page.
+pub const REGISTER_SCRIPT: &str = r#"<script>
+    if ('serviceWorker' in navigator) {
+        navigator.serviceWorker.register('/sw.js').then(() => {
+            const flush = () => navigator.serviceWorker.ready.then(r => r.active && r.active.postMessage('flush'));
+            window.addEventListener('online', flush);
+            if (navigator.onLine) flush();
+        }).catch(() => {});
+    }
+    </script>"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_note;
+    use axum::response::Html;
+    use std::fs;
+
+    fn note(dir: &std::path::Path, name: &str, text: &str) -> Note {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        load_note(&path, &dir.to_path_buf()).unwrap()
+    }
+
+    #[test]
+    fn test_change_log() {
+        let dir = TempDir::new("pwa");
+        let db = Db::temporary();
+        let a = note(&dir, "a.md", "---\ntitle: A\n---\none\n");
+        let b = note(&dir, "b.md", "---\ntitle: B\n---\ntwo\n");
+
+        let all = changes_since(&db, &[a.clone(), b.clone()], 0).unwrap();
+        assert_eq!(all.changes.len(), 2);
+        assert_eq!(all.cursor, "2");
+        assert!(changes_since(&db, &[a.clone(), b.clone()], 2).unwrap().changes.is_empty());
+
+        // An edit moves the note to the end of the log; a vanished note
+        // becomes a tombstone
+        let a2 = note(&dir, "a.md", "---\ntitle: A\n---\none, edited\n");
+        let page = changes_since(&db, std::slice::from_ref(&a2), 2).unwrap();
+        let keys: Vec<(&str, bool)> = page.changes.iter().map(|c| (c.key.as_str(), c.deleted)).collect();
+        assert_eq!(keys, vec![(a.key.as_str(), false), (b.key.as_str(), true)]);
+        assert_eq!(page.changes[0].hash, Some(note_hash(&a2)));
+        assert_eq!(page.cursor, "4");
+        assert_eq!(db.open_tree(LOG_TREE).unwrap().len(), 2);
+
+        // Results are remembered per op id; failures are not
+        let ok = OpResult::applied("op-1", &a.key, None);
+        save_result(&db, &ok).unwrap();
+        assert_eq!(load_result(&db, "op-1").unwrap().status, OpStatus::Applied);
+        assert!(load_result(&db, "op-2").is_none());
+        prune_results(&db, Utc::now() + Duration::days(OP_DAYS + 1));
+        assert!(load_result(&db, "op-1").is_none());
+
+        assert!(new_note_path(&dir, "sub/new.md").is_ok());
+        assert!(new_note_path(&dir, "../escape.md").is_err());
+        assert!(new_note_path(&dir, ".hidden.md").is_err());
+        assert!(new_note_path(&dir, "note.txt").is_err());
+    }
+
+    #[test]
+    fn test_batch_needs_base_hash() {
+        let dir = TempDir::new("pwa-batch");
+        let state = crate::test_util::app_state(&dir);
+        let a = note(&state.notes_dir, "a.md", "---\ntitle: A\n---\none\n");
+        state.invalidate_notes_cache();
+        let ops: Vec<Op> = serde_json::from_value(serde_json::json!([
+            { "id": "1", "op": "save", "key": a.key, "content": "---\ntitle: A\n---\nblind\n" },
+            { "id": "2", "op": "delete", "key": a.key },
+            { "id": "3", "op": "save", "key": a.key, "content": "---\ntitle: A\n---\nstale\n", "base_hash": "0" },
+            { "id": "4", "op": "save", "key": a.key, "content": "---\ntitle: A\n---\ntwo\n", "base_hash": note_hash(&a) },
+        ]))
+        .unwrap();
+        let (results, paths) = apply_batch(&state, &ops);
+        let statuses: Vec<OpStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [OpStatus::Failed, OpStatus::Failed, OpStatus::Conflict, OpStatus::Applied]);
+        assert_eq!(results[2].current.as_deref(), Some("---\ntitle: A\n---\none\n"));
+        assert_eq!(paths, std::slice::from_ref(&a.path));
+        assert_eq!(fs::read_to_string(state.notes_dir.join("a.md")).unwrap(), "---\ntitle: A\n---\ntwo\n");
+
+        let page = || Html("page").into_response();
+        assert!(no_store_if_sealed(&a, page()).headers().get(header::CACHE_CONTROL).is_none());
+        let sealed = Note { raw_content: "-----BEGIN ENCRYPTED NOTE-----\n".to_string(), ..a };
+        assert_eq!(no_store_if_sealed(&sealed, page()).headers()[header::CACHE_CONTROL], "no-store");
+    }
+}
@@ -0,0 +1,184 @@
+//! The reading queue.
+//!
+//! Papers tagged `toread` (or with `status: to-read`) are queued. `/queue`
+//! orders them by `priority:` (1 first; unprioritized papers last), then
+//! oldest first, and keeps a running total of their `est_minutes:` so a
+//! reading session can be planned. With `?budget=MINUTES` the papers that
+//! fit are marked.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::archive::is_archived;
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::html_escape;
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Tag that puts a paper in the queue.
+pub const QUEUE_TAG: &str = "toread";
+
+/// Status that puts a paper in the queue.
+pub const QUEUE_STATUS: &str = "to-read";
+
+/// A queued paper and the estimated minutes up to and including it.
+#[derive(Debug)]
+pub struct QueueEntry<'a> {
+    pub note: &'a Note,
+    pub running_minutes: u32,
+}
+
+pub fn is_queued(note: &Note) -> bool {
+    matches!(note.note_type, NoteType::Paper(_))
+        && !is_archived(note)
+        && (note.tags.iter().any(|t| t == QUEUE_TAG) || note.status.as_deref() == Some(QUEUE_STATUS))
+}
+
+/// The queue in reading order. Papers without an estimate add nothing to
+/// the running total.
+pub fn reading_queue(notes: &[Note]) -> Vec<QueueEntry<'_>> {
+    let mut queued: Vec<&Note> = notes.iter().filter(|n| is_queued(n)).collect();
+    queued.sort_by_key(|n| {
+        (
+            n.priority.unwrap_or(u8::MAX),
+            n.created.unwrap_or_else(|| crate::timezone::local(n.modified).date_naive()),
+            n.title.to_lowercase(),
+        )
+    });
+    let mut total = 0;
+    queued
+        .into_iter()
+        .map(|note| {
+            total += note.est_minutes.unwrap_or(0);
+            QueueEntry {
+                note,
+                running_minutes: total,
+            }
+        })
+        .collect()
+}
+
+fn hours_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct QueueQuery {
+    /// Minutes available for reading.
+    pub budget: Option<u32>,
+}
+
+/// `GET /queue`: to-read papers by priority with running reading time.
+pub async fn queue_page(
+    Query(query): Query<QueueQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let queue = reading_queue(&notes);
+    let total = queue.last().map_or(0, |e| e.running_minutes);
+    let unestimated = queue.iter().filter(|e| e.note.est_minutes.is_none()).count();
+
+    let mut html = String::from("<h1>Reading queue</h1>");
+    let mut summary = format!(
+        "{} papers queued, about {} of reading",
+        queue.len(),
+        hours_minutes(total)
+    );
+    if unestimated > 0 {
+        summary.push_str(&format!(" ({} without <code>est_minutes:</code>)", unestimated));
+    }
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{}.</p>
+<form method="get" action="/queue" class="time-breadcrumb">
+    <label>Time available: <input type="number" name="budget" min="0" step="15" value="{}"> minutes</label>
+    <button type="submit">Plan</button>
+</form>"#,
+        summary,
+        query.budget.map(|b| b.to_string()).unwrap_or_default()
+    ));
+
+    if queue.is_empty() {
+        html.push_str(&format!(
+            "<p>Nothing queued. Tag a paper <code>{}</code> to add it.</p>",
+            QUEUE_TAG
+        ));
+    } else {
+        html.push_str(
+            r#"<table class="time-table"><tr><th>#</th><th>Priority</th><th>Paper</th><th>Estimate</th><th>Running total</th></tr>"#,
+        );
+        for (i, entry) in queue.iter().enumerate() {
+            let fits = query.budget.is_some_and(|b| entry.running_minutes <= b);
+            let style = match query.budget {
+                Some(_) if fits => r#" style="font-weight: bold""#,
+                Some(_) => r#" style="opacity: 0.5""#,
+                None => "",
+            };
+            html.push_str(&format!(
+                r#"<tr{}><td>{}</td><td>{}</td><td><a href="/note/{}">{}</a></td><td>{}</td><td>{}</td></tr>"#,
+                style,
+                i + 1,
+                entry.note.priority.map(|p| p.to_string()).unwrap_or_default(),
+                entry.note.key,
+                html_escape(&entry.note.title),
+                entry.note.est_minutes.map(hours_minutes).unwrap_or_else(|| "?".to_string()),
+                hours_minutes(entry.running_minutes),
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    Html(base_html("Reading queue", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_reading_queue_order() {
+        let dir = TempDir::new("queue");
+        let paper = |name: &str, extra: &str| {
+            fs::write(
+                dir.join(format!("{}.md", name)),
+                format!("---\ntitle: {}\n{}bibtex: |\n  @article{{{}, title = {{{}}}}}\n---\n", name, extra, name, name),
+            )
+            .unwrap();
+        };
+        paper("Later", "created: 2026-01-01\ntags: [toread]\npriority: 2\nest_minutes: 30\n");
+        paper("Urgent", "tags: [toread, pl]\npriority: high\nest_minutes: 60\n");
+        paper("Older", "created: 2025-06-01\nstatus: to-read\npriority: 2\n");
+        paper("Someday", "tags: [toread]\nest_minutes: 45\n");
+        paper("Read", "tags: [pl]\npriority: 1\n");
+        paper("Shelved", "tags: [toread]\nstatus: archived\npriority: 1\n");
+        fs::write(dir.join("idea.md"), "---\ntitle: Idea\ntags: [toread]\npriority: 1\n---\n").unwrap();
+
+        let notes = load_all_notes(&dir);
+        let queue = reading_queue(&notes);
+        let order: Vec<(&str, u32)> = queue.iter().map(|e| (e.note.title.as_str(), e.running_minutes)).collect();
+        assert_eq!(order, vec![("Urgent", 60), ("Older", 60), ("Later", 90), ("Someday", 135)]);
+        assert_eq!(hours_minutes(135), "2h 15m");
+    }
+}
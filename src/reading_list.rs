@@ -0,0 +1,258 @@
+//! Reading lists for syllabi.
+//!
+//! `/note/{key}/reading-list.{md,html,bib}` lists the papers below a note
+//! (a course, a project) for pasting into a syllabus or handout. Papers are
+//! ordered by `week:`, then `order:`, then title; either field may sit on
+//! the paper or on a note between it and the root, such as a lecture, and
+//! the nearest one wins. With any weeks set, the list is grouped under a
+//! heading per week, papers without one last. Hidden notes, and what is
+//! below them, are left out unless logged in.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, NoteType};
+use crate::notes::{frontmatter_field, html_escape};
+use crate::print::citation;
+use crate::AppState;
+
+/// A paper on the list, with the `week:` and `order:` that apply to it.
+#[derive(Debug)]
+pub struct Reading<'a> {
+    pub note: &'a Note,
+    pub week: Option<u32>,
+    pub order: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingListFormat {
+    Markdown,
+    Html,
+    Bibtex,
+}
+
+/// The papers below `root` in reading order.
+pub fn reading_list<'a>(root: &Note, notes_map: &'a HashMap<String, Note>, include_hidden: bool) -> Vec<Reading<'a>> {
+    let mut children: HashMap<&str, Vec<&Note>> = HashMap::new();
+    for note in notes_map.values().filter(|n| include_hidden || !n.hidden) {
+        for parent in &note.parents {
+            children.entry(parent.as_str()).or_default().push(note);
+        }
+    }
+    let field = |note: &Note, name: &str| frontmatter_field(&note.full_file_content, name);
+
+    let mut seen = std::collections::HashSet::from([root.key.as_str()]);
+    let mut queue = VecDeque::from([(root.key.as_str(), None, None)]);
+    let mut readings = Vec::new();
+    while let Some((key, week, order)) = queue.pop_front() {
+        let mut kids = children.get(key).cloned().unwrap_or_default();
+        kids.sort_by(|a, b| a.key.cmp(&b.key));
+        for child in kids {
+            if !seen.insert(child.key.as_str()) {
+                continue;
+            }
+            let week = field(child, "week").and_then(|w| w.parse::<u32>().ok()).or(week);
+            let order = field(child, "order").and_then(|o| o.parse::<f64>().ok()).or(order);
+            if matches!(child.note_type, NoteType::Paper(_)) {
+                readings.push(Reading { note: child, week, order });
+            }
+            queue.push_back((child.key.as_str(), week, order));
+        }
+    }
+    readings.sort_by(|a, b| {
+        (a.week.is_none(), a.week)
+            .cmp(&(b.week.is_none(), b.week))
+            .then_with(|| match (a.order, b.order) {
+                (Some(x), Some(y)) => x.total_cmp(&y),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            })
+            .then_with(|| a.note.title.to_lowercase().cmp(&b.note.title.to_lowercase()))
+    });
+    readings
+}
+
+/// `readings` split at each change of week, with the heading of each part;
+/// one untitled part when no paper has a week.
+fn sections<'r, 'a>(readings: &'r [Reading<'a>]) -> Vec<(Option<String>, &'r [Reading<'a>])> {
+    if readings.iter().all(|r| r.week.is_none()) {
+        return vec![(None, readings)];
+    }
+    readings
+        .chunk_by(|a, b| a.week == b.week)
+        .map(|part| {
+            let heading = match part[0].week {
+                Some(week) => format!("Week {}", week),
+                None => "Other".to_string(),
+            };
+            (Some(heading), part)
+        })
+        .collect()
+}
+
+fn reference(note: &Note) -> String {
+    citation(note).unwrap_or_else(|| note.title.clone())
+}
+
+/// A reference as HTML, its trailing DOI or arXiv URL as a link.
+fn reference_html(note: &Note) -> String {
+    let text = reference(note);
+    match text.rsplit_once(' ') {
+        Some((rest, url)) if url.starts_with("https://") => {
+            format!(r#"{} <a href="{}">{}</a>"#, html_escape(rest), html_escape(url), html_escape(url))
+        }
+        _ => html_escape(&text),
+    }
+}
+
+/// Render the list of the papers below `root`.
+pub fn render(root: &Note, readings: &[Reading], format: ReadingListFormat) -> String {
+    let title = format!("{}: Reading list", root.title);
+    let mut out = String::new();
+    match format {
+        ReadingListFormat::Markdown => {
+            out.push_str(&format!("# {}\n", title));
+            for (heading, part) in sections(readings) {
+                if let Some(heading) = heading {
+                    out.push_str(&format!("\n## {}\n", heading));
+                }
+                out.push('\n');
+                for r in part {
+                    out.push_str(&format!("- {}\n", reference(r.note)));
+                }
+            }
+        }
+        ReadingListFormat::Html => {
+            out.push_str(&format!(
+                "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"UTF-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n",
+                html_escape(&title),
+                html_escape(&title)
+            ));
+            for (heading, part) in sections(readings) {
+                if let Some(heading) = heading {
+                    out.push_str(&format!("<h2>{}</h2>\n", heading));
+                }
+                out.push_str("<ul>\n");
+                for r in part {
+                    out.push_str(&format!("<li>{}</li>\n", reference_html(r.note)));
+                }
+                out.push_str("</ul>\n");
+            }
+            out.push_str("</body>\n</html>\n");
+        }
+        ReadingListFormat::Bibtex => {
+            out.push_str(&format!("% {}\n", title));
+            for (heading, part) in sections(readings) {
+                if let Some(heading) = heading {
+                    out.push_str(&format!("\n% {}\n", heading));
+                }
+                for r in part {
+                    if let NoteType::Paper(ref paper) = r.note.note_type {
+                        if let Some(entry) = paper.canonical_bibtex() {
+                            out.push_str(&format!("\n{}\n", entry.trim()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+fn reading_list_response(key: &str, state: &AppState, jar: &CookieJar, format: ReadingListFormat) -> Response {
+    let logged_in = is_logged_in(jar, &state.db);
+    let notes = state.notes_map();
+    let Some(root) = notes.get(key).filter(|n| logged_in || !n.hidden) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    let readings = reading_list(root, &notes, logged_in);
+    let content_type = match format {
+        ReadingListFormat::Html => "text/html; charset=utf-8",
+        ReadingListFormat::Markdown | ReadingListFormat::Bibtex => "text/plain; charset=utf-8",
+    };
+    ([(header::CONTENT_TYPE, content_type)], render(root, &readings, format)).into_response()
+}
+
+/// `GET /note/{key}/reading-list.md`
+pub async fn reading_list_md(Path(key): Path<String>, State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    reading_list_response(&key, &state, &jar, ReadingListFormat::Markdown)
+}
+
+/// `GET /note/{key}/reading-list.html`
+pub async fn reading_list_html(Path(key): Path<String>, State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    reading_list_response(&key, &state, &jar, ReadingListFormat::Html)
+}
+
+/// `GET /note/{key}/reading-list.bib`
+pub async fn reading_list_bib(Path(key): Path<String>, State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    reading_list_response(&key, &state, &jar, ReadingListFormat::Bibtex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_reading_list_order_and_formats() {
+        let dir = TempDir::new("reading-list");
+        let write = |name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+        let paper = |name: &str, extra: &str| {
+            write(
+                &format!("{}.md", name),
+                &format!(
+                    "---\ntitle: {}\nid: {}\n{}bibtex: |\n  @article{{{}, title = {{{}}}, author = {{Doe, Jane}}, year = {{2020}}}}\n---\n",
+                    name, name, extra, name, name
+                ),
+            )
+        };
+        write("course.md", "---\ntitle: PL Seminar\nid: course\ntype: course\n---\n");
+        write("lec2.md", "---\ntitle: Lecture 2\nid: lec2\nparent: course\nweek: 2\n---\n");
+        paper("Types", "parent: lec2\norder: 2\n");
+        paper("Lambda", "parent: lec2\norder: 1\n");
+        paper("Intro", "parent: course\nweek: 1\n");
+        paper("Extra", "parent: course\n");
+        paper("Secret", "parent: course\nweek: 1\nhidden: true\n");
+        paper("Elsewhere", "week: 1\n");
+
+        let notes: HashMap<String, Note> = load_all_notes(&dir).into_iter().map(|n| (n.key.clone(), n)).collect();
+        let root = &notes["course"];
+        let order = |readings: &[Reading]| readings.iter().map(|r| (r.note.title.clone(), r.week)).collect::<Vec<_>>();
+        let readings = reading_list(root, &notes, false);
+        assert_eq!(
+            order(&readings),
+            vec![
+                ("Intro".to_string(), Some(1)),
+                ("Lambda".to_string(), Some(2)),
+                ("Types".to_string(), Some(2)),
+                ("Extra".to_string(), None),
+            ]
+        );
+        assert_eq!(reading_list(root, &notes, true).len(), 5);
+
+        let md = render(root, &readings, ReadingListFormat::Markdown);
+        assert_eq!(
+            md,
+            "# PL Seminar: Reading list\n\n## Week 1\n\n- Jane Doe. 2020. Intro.\n\n## Week 2\n\n- Jane Doe. 2020. Lambda.\n\
+             - Jane Doe. 2020. Types.\n\n## Other\n\n- Jane Doe. 2020. Extra.\n"
+        );
+        let html = render(root, &readings, ReadingListFormat::Html);
+        assert!(html.contains("<h2>Week 2</h2>\n<ul>\n<li>Jane Doe. 2020. Lambda.</li>"));
+        let bib = render(root, &readings, ReadingListFormat::Bibtex);
+        assert!(bib.starts_with("% PL Seminar: Reading list\n\n% Week 1\n\n@article{Intro,"));
+        assert!(bib.find("@article{Lambda").unwrap() < bib.find("@article{Types").unwrap());
+    }
+}
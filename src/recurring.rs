@@ -0,0 +1,319 @@
+//! Recurring notes.
+//!
+//! Each `[[recurring]]` table in `notes.toml` defines a series (a weekly
+//! 1:1, a monthly review) by its schedule and template. The server checks
+//! the series every hour and creates the current period's instance when it
+//! is missing, at `<dir>/YYYY-MM-DD.md`, with `prev:` pointing at the
+//! instance before it and `parent:` at the series' parent note, if any.
+//! Missed periods are not backfilled.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::journal::dated_note;
+use crate::models::Note;
+use crate::note_templates::{self, TemplateVars};
+use crate::notes::{is_valid_note_id, new_note_frontmatter, new_note_key};
+use crate::AppState;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Every {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// A `[[recurring]]` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RecurringNote {
+    /// Names the series; instances go in a directory of this name unless
+    /// `dir` is set.
+    pub name: String,
+    /// Title of each instance, with `{{date}}` and `{{title}}` (the series
+    /// name) filled in. Defaults to `<name> {{date}}`.
+    #[serde(default)]
+    pub title: Option<String>,
+    pub every: Every,
+    /// Day of the week of a weekly series.
+    #[serde(default = "default_weekday")]
+    pub weekday: Weekday,
+    /// Day of the month of a monthly series; past the end of a short month,
+    /// its last day.
+    #[serde(default = "default_day")]
+    pub day: u32,
+    /// Note template each instance starts from.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Key of the note the instances are filed under.
+    #[serde(default)]
+    pub parent: Option<String>,
+    /// Directory for the instances, relative to the notes directory.
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+}
+
+fn default_weekday() -> Weekday {
+    Weekday::Mon
+}
+
+fn default_day() -> u32 {
+    1
+}
+
+/// `day` of the given month, or the month's last day if it is shorter.
+fn day_of_month(year: i32, month: u32, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .expect("every month has a first day")
+}
+
+impl RecurringNote {
+    /// Directory of the instances, relative to the notes directory.
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| PathBuf::from(&self.name))
+    }
+
+    /// Date of the instance for the period containing `today`: the latest
+    /// scheduled date on or before it.
+    pub fn occurrence(&self, today: NaiveDate) -> NaiveDate {
+        match self.every {
+            Every::Daily => today,
+            Every::Weekly => {
+                let back = (7 + today.weekday().num_days_from_monday() - self.weekday.num_days_from_monday()) % 7;
+                today - Days::new(back as u64)
+            }
+            Every::Monthly => {
+                let this_month = day_of_month(today.year(), today.month(), self.day);
+                if this_month <= today {
+                    return this_month;
+                }
+                let last_month = today.with_day(1).unwrap() - Days::new(1);
+                day_of_month(last_month.year(), last_month.month(), self.day)
+            }
+        }
+    }
+
+    fn title_for(&self, date: &str) -> String {
+        let vars = TemplateVars {
+            title: &self.name,
+            date,
+            bib_key: "",
+        };
+        match self.title {
+            Some(ref title) => note_templates::render_template(title, &vars),
+            None => format!("{} {}", self.name, date),
+        }
+    }
+}
+
+/// Check the series when the config is loaded.
+pub fn validate(series: &[RecurringNote]) -> Result<(), String> {
+    let mut dirs = HashSet::new();
+    for s in series {
+        if !is_valid_note_id(&s.name) {
+            return Err(format!("recurring: invalid name '{}'", s.name));
+        }
+        if !(1..=31).contains(&s.day) {
+            return Err(format!("recurring '{}': day must be 1-31", s.name));
+        }
+        let dir = s.dir();
+        if !dir.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("recurring '{}': dir must be a relative path inside the notes directory", s.name));
+        }
+        if !dirs.insert(dir) {
+            return Err(format!("recurring '{}': another series uses the same dir", s.name));
+        }
+        if let Some(ref parent) = s.parent {
+            if !is_valid_note_id(parent) {
+                return Err(format!("recurring '{}': invalid parent key '{}'", s.name, parent));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Create the instance of `series` for the period containing `now`
+/// unless it exists. Returns the new note's key.
+pub fn create_current(
+    notes_dir: &Path,
+    templates_dir: &Path,
+    series: &RecurringNote,
+    notes: &[Note],
+    now: DateTime<FixedOffset>,
+) -> Result<Option<String>, String> {
+    let date = series.occurrence(now.date_naive());
+    let dir = series.dir();
+    let relative = dir.join(format!("{}.md", date.format("%Y-%m-%d")));
+    let file_path = notes_dir.join(&relative);
+    if file_path.exists() || notes.iter().any(|n| n.path == relative) {
+        return Ok(None);
+    }
+
+    let template = note_templates::load_optional(templates_dir, series.template.as_deref())?;
+    let prev = notes
+        .iter()
+        .filter_map(|n| Some((dated_note(n, &dir)?, n)))
+        .filter(|(d, _)| *d < date)
+        .max_by_key(|(d, _)| *d)
+        .map(|(_, n)| n.key.clone());
+
+    let day = date.format("%Y-%m-%d").to_string();
+    let title = series.title_for(&day);
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let mut frontmatter = new_note_frontmatter(&title, &key, now);
+    frontmatter.push_str(&format!("date: {}\n", day));
+    if let Some(ref parent) = series.parent {
+        frontmatter.push_str(&format!("parent: {}\n", parent));
+    }
+    if let Some(ref prev) = prev {
+        frontmatter.push_str(&format!("prev: {}\n", prev));
+    }
+    let content = match template {
+        Some(ref template) => {
+            let vars = TemplateVars {
+                title: &title,
+                date: &day,
+                bib_key: "",
+            };
+            note_templates::instantiate(&frontmatter, template, &vars)
+        }
+        None => frontmatter + "---\n\n",
+    };
+
+    fs::create_dir_all(notes_dir.join(&dir)).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    crate::encryption::write_file(&file_path, &content).map_err(|e| format!("{}: {}", relative.display(), e))?;
+    Ok(Some(key))
+}
+
+/// Create every series' current instance that is missing.
+pub fn create_due(state: &AppState, now: DateTime<FixedOffset>) -> Vec<String> {
+    let notes = state.load_notes();
+    let mut created = Vec::new();
+    for series in &state.config.recurring {
+        match create_current(&state.notes_dir, &state.config.templates_dir, series, &notes, now) {
+            Ok(Some(key)) => {
+                tracing::info!(series = %series.name, key = %key, "Created recurring note");
+                created.push(key);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(series = %series.name, error = %e, "Recurring note failed"),
+        }
+    }
+    if !created.is_empty() {
+        state.invalidate_notes_cache();
+        for key in &created {
+            state.reindex_graph_note(key);
+        }
+    }
+    created
+}
+
+/// Check the series every hour, starting now. Spawned by the server unless
+/// it is read-only or no series are configured.
+pub async fn create_hourly(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        let task_state = state.clone();
+        state.spawn_background(move || {
+            create_due(&task_state, crate::timezone::now());
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::config::Config;
+    use crate::notes::load_all_notes;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    const CONFIG: &str = r#"
+[[recurring]]
+name = "alice-1on1"
+title = "1:1 with Alice, {{date}}"
+every = "weekly"
+weekday = "thu"
+template = "one-on-one"
+parent = "3fa9c01b22d4"
+dir = "meetings/alice"
+
+[[recurring]]
+name = "review"
+every = "monthly"
+day = 31
+"#;
+
+    #[test]
+    fn test_occurrence() {
+        let config = Config::from_toml(CONFIG).unwrap();
+        let (weekly, monthly) = (&config.recurring[0], &config.recurring[1]);
+        // 2026-10-17 is a Saturday
+        assert_eq!(weekly.occurrence(date(2026, 10, 17)), date(2026, 10, 15));
+        assert_eq!(weekly.occurrence(date(2026, 10, 15)), date(2026, 10, 15));
+        assert_eq!(weekly.occurrence(date(2026, 10, 14)), date(2026, 10, 8));
+        assert_eq!(monthly.occurrence(date(2026, 10, 17)), date(2026, 9, 30));
+        assert_eq!(monthly.occurrence(date(2027, 2, 28)), date(2027, 2, 28));
+        assert_eq!(monthly.occurrence(date(2027, 3, 5)), date(2027, 2, 28));
+        assert!(validate(&config.recurring).is_ok());
+
+        let mut bad = config.recurring.clone();
+        bad[1].dir = Some(PathBuf::from("../elsewhere"));
+        assert!(validate(&bad).is_err());
+        bad[1].dir = Some(PathBuf::from("meetings/alice"));
+        assert!(validate(&bad).is_err());
+    }
+
+    #[test]
+    fn test_create_current_links_previous() {
+        let dir = TempDir::new("recurring");
+        let notes_dir = dir.join("content");
+        let templates_dir = dir.join("templates");
+        fs::create_dir_all(notes_dir.join("meetings/alice")).unwrap();
+        fs::create_dir_all(&templates_dir).unwrap();
+        fs::write(templates_dir.join("one-on-one.md"), "## Since {{date}}\n").unwrap();
+        fs::write(
+            notes_dir.join("meetings/alice/2026-10-08.md"),
+            "---\ntitle: Last week\nid: aaaaaaaaaaaa\n---\n",
+        )
+        .unwrap();
+        fs::write(notes_dir.join("meetings/alice/2026-09-24.md"), "---\ntitle: Earlier\n---\n").unwrap();
+
+        let config = Config::from_toml(CONFIG).unwrap();
+        let series = &config.recurring[0];
+        let notes = load_all_notes(&notes_dir);
+        let key = create_current(&notes_dir, &templates_dir, series, &notes, DateTime::parse_from_rfc3339("2026-10-17T09:30:00-04:00").unwrap())
+            .unwrap()
+            .unwrap();
+        let text = fs::read_to_string(notes_dir.join("meetings/alice/2026-10-15.md")).unwrap();
+        assert_eq!(
+            text,
+            format!(
+                "---\ntitle: 1:1 with Alice, 2026-10-15\nid: {}\ncreated: 2026-10-17T09:30:00-04:00\ndate: 2026-10-15\nparent: 3fa9c01b22d4\n\
+                 prev: aaaaaaaaaaaa\n---\n\n## Since 2026-10-15\n",
+                key
+            )
+        );
+
+        let notes = load_all_notes(&notes_dir);
+        let created = notes.iter().find(|n| n.key == key).unwrap();
+        assert_eq!(created.prev_key.as_deref(), Some("aaaaaaaaaaaa"));
+        assert_eq!(
+            create_current(&notes_dir, &templates_dir, series, &notes, DateTime::parse_from_rfc3339("2026-10-20T09:30:00-04:00").unwrap()).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Related notes.
+//!
+//! Every note's title and body become a TF-IDF vector over the corpus;
+//! notes whose vectors point the same way use the same uncommon words.
+//! The note view lists the closest few under "Related", whether or not the
+//! notes link to each other, and `GET /api/note/{key}/related` returns
+//! them as JSON. The index lives in `AppState` and is rebuilt after the
+//! notes cache is invalidated.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Serialize;
+
+use crate::auth::is_logged_in;
+use crate::models::Note;
+use crate::notes::html_escape;
+use crate::AppState;
+
+/// Related notes shown and returned.
+pub const RELATED_COUNT: usize = 5;
+
+/// Similarity below which a note isn't considered related.
+const MIN_SCORE: f32 = 0.02;
+
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "also", "and", "are", "because", "been", "but", "can", "could", "does", "each", "for",
+    "from", "has", "have", "how", "into", "its", "more", "most", "not", "only", "other", "our", "should",
+    "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "those", "through", "use", "used", "using", "was", "were", "what", "when", "where", "which", "while",
+    "who", "why", "will", "with", "would", "you", "your",
+];
+
+/// Lowercase words of three or more letters, without stop words. Keys in
+/// `[@key]` links and other tokens with digits are left out.
+fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3 && w.chars().all(char::is_alphabetic))
+        .map(str::to_lowercase)
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+}
+
+/// TF-IDF vectors of every note, unit length, as sorted (term, weight)
+/// pairs.
+#[derive(Debug, Default)]
+pub struct RelatedIndex {
+    vectors: HashMap<String, Vec<(u32, f32)>>,
+    /// Term → (id, IDF), for scoring text that isn't a note.
+    terms: HashMap<String, (u32, f32)>,
+}
+
+impl RelatedIndex {
+    pub fn build(notes: &[Note]) -> Self {
+        let mut term_ids: HashMap<String, u32> = HashMap::new();
+        let counts: Vec<(&str, HashMap<u32, u32>)> = notes
+            .iter()
+            .map(|note| {
+                let mut tf: HashMap<u32, u32> = HashMap::new();
+                for word in tokens(&note.title).chain(tokens(&note.raw_content)) {
+                    let next = term_ids.len() as u32;
+                    *tf.entry(*term_ids.entry(word).or_insert(next)).or_default() += 1;
+                }
+                (note.key.as_str(), tf)
+            })
+            .collect();
+
+        let mut doc_freq: HashMap<u32, u32> = HashMap::new();
+        for (_, tf) in &counts {
+            for term in tf.keys() {
+                *doc_freq.entry(*term).or_default() += 1;
+            }
+        }
+
+        let n = notes.len() as f32;
+        let idf = |term: u32| (n / doc_freq[&term] as f32).ln();
+        let vectors = counts
+            .into_iter()
+            .map(|(key, tf)| {
+                let vector = tf
+                    .into_iter()
+                    .map(|(term, count)| (term, (1.0 + (count as f32).ln()) * idf(term)))
+                    .collect();
+                (key.to_string(), unit_vector(vector))
+            })
+            .collect();
+        let terms = term_ids.into_iter().map(|(word, id)| (word, (id, idf(id)))).collect();
+        RelatedIndex { vectors, terms }
+    }
+
+    /// Keys of the notes that best match `text` (a question, say), best
+    /// first, with their similarity.
+    pub fn search(&self, text: &str, limit: usize) -> Vec<(String, f32)> {
+        let mut tf: HashMap<u32, (u32, f32)> = HashMap::new();
+        for word in tokens(text) {
+            if let Some(&(id, idf)) = self.terms.get(&word) {
+                tf.entry(id).or_insert((0, idf)).0 += 1;
+            }
+        }
+        let query = unit_vector(
+            tf.into_iter()
+                .map(|(id, (count, idf))| (id, (1.0 + (count as f32).ln()) * idf))
+                .collect(),
+        );
+        self.ranked(&query, None, limit)
+    }
+
+    /// Keys of the notes most similar to `key`, best first, with their
+    /// similarity.
+    pub fn related(&self, key: &str, limit: usize) -> Vec<(String, f32)> {
+        match self.vectors.get(key) {
+            Some(target) => self.ranked(target, Some(key), limit),
+            None => Vec::new(),
+        }
+    }
+
+    fn ranked(&self, target: &[(u32, f32)], skip: Option<&str>, limit: usize) -> Vec<(String, f32)> {
+        let mut scored: Vec<(String, f32)> = self
+            .vectors
+            .iter()
+            .filter(|(k, _)| Some(k.as_str()) != skip)
+            .map(|(k, v)| (k.clone(), dot(target, v)))
+            .filter(|(_, score)| *score >= MIN_SCORE)
+            .collect();
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(limit);
+        scored
+    }
+}
+
+/// Drop zero weights, scale to unit length and sort by term.
+fn unit_vector(mut vector: Vec<(u32, f32)>) -> Vec<(u32, f32)> {
+    vector.retain(|(_, w)| *w > 0.0);
+    let norm = vector.iter().map(|(_, w)| w * w).sum::<f32>().sqrt();
+    for (_, w) in &mut vector {
+        *w /= norm;
+    }
+    vector.sort_unstable_by_key(|(term, _)| *term);
+    vector
+}
+
+/// Dot product of two sparse vectors sorted by term.
+fn dot(a: &[(u32, f32)], b: &[(u32, f32)]) -> f32 {
+    let (mut i, mut j, mut sum) = (0, 0, 0.0);
+    while i < a.len() && j < b.len() {
+        match a[i].0.cmp(&b[j].0) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                sum += a[i].1 * b[j].1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    sum
+}
+
+/// The "Related" panel of the note view; empty when nothing is related.
+pub fn related_html(related: &[(String, f32)], notes_map: &HashMap<String, Note>) -> String {
+    let items: Vec<String> = related
+        .iter()
+        .filter_map(|(key, _)| notes_map.get(key))
+        .map(|n| format!("<li><a href=\"/note/{}\">{}</a></li>", n.key, html_escape(&n.title)))
+        .collect();
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("<div class=\"sub-notes\"><h3>Related</h3><ul>{}</ul></div>", items.concat())
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct RelatedNote {
+    pub key: String,
+    pub title: String,
+    pub score: f32,
+}
+
+/// `GET /api/note/{key}/related`: the most similar notes, best first.
+pub async fn related_notes(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    if !notes_map.contains_key(&key) {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    }
+    let related: Vec<RelatedNote> = state
+        .related_index()
+        .related(&key, RELATED_COUNT)
+        .into_iter()
+        .filter_map(|(key, score)| {
+            let note = notes_map.get(&key)?;
+            Some(RelatedNote {
+                key,
+                title: note.title.clone(),
+                score,
+            })
+        })
+        .collect();
+    axum::Json(related).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_related_notes() {
+        let dir = TempDir::new("related");
+        let note = |id: &str, title: &str, body: &str| {
+            fs::write(dir.join(format!("{}.md", id)), format!("---\ntitle: {}\nid: {}\n---\n\n{}\n", title, id, body))
+                .unwrap();
+        };
+        note("aaaaaaaaaaaa", "Gradual typing", "Gradual typing mixes static and dynamic checks. See [@bbbbbbbbbbbb].");
+        note("bbbbbbbbbbbb", "Blame calculus", "Blame tracks casts between static and dynamic code in gradual typing.");
+        note("cccccccccccc", "Sourdough", "Starter, flour and water; the dough rises overnight.");
+        note("dddddddddddd", "Baking log", "This loaf used more water and the dough rose well.");
+
+        let index = RelatedIndex::build(&load_all_notes(&dir));
+        let keys = |key: &str| -> Vec<String> { index.related(key, RELATED_COUNT).into_iter().map(|(k, _)| k).collect() };
+        assert_eq!(keys("aaaaaaaaaaaa"), vec!["bbbbbbbbbbbb"]);
+        assert_eq!(keys("cccccccccccc"), vec!["dddddddddddd"]);
+        assert!(keys("missing").is_empty());
+        let found: Vec<String> = index.search("how does blame work?", 5).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(found, vec!["bbbbbbbbbbbb"]);
+        assert_eq!(
+            tokens("The [@3fa9c01b22d4] type-checker, v2!").collect::<Vec<_>>(),
+            vec!["type", "checker"]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Reminders.
+//!
+//! A note with `remind: 2025-03-01 09:00` (local time) has a reminder sent
+//! through the `[notify]` channel (webhook, ntfy or email) when that time
+//! comes. The server checks every minute; a reminder missed while it was
+//! down still goes out if it is less than a day late. Sent reminders are
+//! recorded in sled so each goes out once, and a new `remind:` time is a
+//! new reminder. `/reminders` lists upcoming and recent ones.
+
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta};
+use tokio::io::AsyncWriteExt;
+
+use crate::archive::is_archived;
+use crate::auth::is_logged_in;
+use crate::config::{NotifyChannel, NotifyConfig};
+use crate::models::Note;
+use crate::notes::{html_escape, REMIND_FORMAT};
+use crate::templates::base_html;
+use crate::AppState;
+
+const SENT_TREE: &str = "reminders_sent";
+
+/// How late a reminder may still be sent.
+const GRACE_HOURS: i64 = 24;
+
+/// Days of past reminders `/reminders` shows.
+const RECENT_DAYS: i64 = 7;
+
+fn sent_key(note_key: &str, at: NaiveDateTime) -> String {
+    format!("{}|{}", note_key, at.format(REMIND_FORMAT))
+}
+
+pub fn is_sent(db: &crate::store::Db, note_key: &str, at: NaiveDateTime) -> bool {
+    db.open_tree(SENT_TREE)
+        .and_then(|t| t.contains_key(sent_key(note_key, at).as_bytes()))
+        .unwrap_or(false)
+}
+
+fn mark_sent(db: &crate::store::Db, note_key: &str, at: NaiveDateTime) -> Result<(), String> {
+    let tree = db.open_tree(SENT_TREE).map_err(|e| e.to_string())?;
+    tree.insert(sent_key(note_key, at).as_bytes(), chrono::Utc::now().to_rfc3339().as_bytes())
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Every reminder of a note that isn't archived, soonest first.
+pub fn reminders(notes: &[Note]) -> Vec<(NaiveDateTime, &Note)> {
+    let mut all: Vec<(NaiveDateTime, &Note)> = notes
+        .iter()
+        .filter(|n| !is_archived(n))
+        .filter_map(|n| Some((n.remind?, n)))
+        .collect();
+    all.sort_by_key(|(at, _)| *at);
+    all
+}
+
+/// Reminders to send at `now`: due, less than a day late, and not sent.
+pub fn due_reminders(
+    notes: &[Note],
+    now: NaiveDateTime,
+    sent: impl Fn(&str, NaiveDateTime) -> bool,
+) -> Vec<(NaiveDateTime, &Note)> {
+    let earliest = now - TimeDelta::hours(GRACE_HOURS);
+    reminders(notes)
+        .into_iter()
+        .filter(|(at, n)| *at <= now && *at > earliest && !sent(&n.key, *at))
+        .collect()
+}
+
+/// Text of a reminder: the note's title, when it was due, and a link when
+/// `base_url` is set.
+pub fn reminder_text(config: &NotifyConfig, note: &Note, at: NaiveDateTime) -> String {
+    let mut text = format!("{} ({})", note.title, at.format(REMIND_FORMAT));
+    if let Some(link) = note_link(config, note) {
+        text.push('\n');
+        text.push_str(&link);
+    }
+    text
+}
+
+fn note_link(config: &NotifyConfig, note: &Note) -> Option<String> {
+    let base = config.base_url.as_deref()?.trim_end_matches('/');
+    Some(format!("{}/note/{}", base, note.key))
+}
+
+/// Send one reminder through the configured channel.
+pub async fn send(
+    config: &NotifyConfig,
+    client: &reqwest::Client,
+    note: &Note,
+    at: NaiveDateTime,
+) -> Result<(), String> {
+    let text = reminder_text(config, note, at);
+    let url = config.url.as_deref().unwrap_or_default();
+    let request = match config.channel {
+        None => return Ok(()),
+        Some(NotifyChannel::Email) => {
+            return send_email(config, &format!("Reminder: {}", note.title), &text).await;
+        }
+        Some(NotifyChannel::Webhook) => client.post(url).json(&serde_json::json!({
+            "key": note.key,
+            "title": note.title,
+            "remind": at.format(REMIND_FORMAT).to_string(),
+            "link": note_link(config, note),
+            "text": text,
+        })),
+        Some(NotifyChannel::Ntfy) => {
+            let request = client.post(url).header("Title", "Reminder").body(text);
+            match note_link(config, note) {
+                Some(link) => request.header("Click", link),
+                None => request,
+            }
+        }
+    };
+    let response = request.send().await.map_err(|e| format!("Failed to send reminder: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Reminder rejected: HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+/// Send a message other than a reminder (e.g. the weekly digest) through
+/// the configured channel. Webhooks get `{title, text}`.
+pub async fn send_text(
+    config: &NotifyConfig,
+    client: &reqwest::Client,
+    title: &str,
+    text: &str,
+) -> Result<(), String> {
+    let url = config.url.as_deref().unwrap_or_default();
+    let request = match config.channel {
+        None => return Ok(()),
+        Some(NotifyChannel::Email) => return send_email(config, title, text).await,
+        Some(NotifyChannel::Webhook) => client.post(url).json(&serde_json::json!({
+            "title": title,
+            "text": text,
+        })),
+        Some(NotifyChannel::Ntfy) => client
+            .post(url)
+            .header("Title", title.replace(['\r', '\n'], " "))
+            .header("Markdown", "yes")
+            .body(text.to_string()),
+    };
+    let response = request.send().await.map_err(|e| format!("Failed to send message: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Message rejected: HTTP {}", response.status()));
+    }
+    Ok(())
+}
+
+/// A header value, as RFC 2047 base64 encoded-words when it isn't ASCII;
+/// each word holds at most 45 bytes of text so lines stay under 78.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for c in value.chars() {
+        if chunk.len() + c.len_utf8() > 45 {
+            words.push(format!("=?utf-8?B?{}?=", BASE64.encode(&chunk)));
+            chunk.clear();
+        }
+        chunk.push(c);
+    }
+    words.push(format!("=?utf-8?B?{}?=", BASE64.encode(&chunk)));
+    words.join("\n ")
+}
+
+/// The message handed to `sendmail -t`.
+fn email_message(config: &NotifyConfig, subject: &str, text: &str, date: DateTime<FixedOffset>) -> String {
+    let one_line = |s: &str| s.replace(['\r', '\n'], " ");
+    let mut message = format!("To: {}\n", one_line(config.to.as_deref().unwrap_or_default()));
+    if let Some(ref from) = config.from {
+        message.push_str(&format!("From: {}\n", one_line(from)));
+    }
+    message.push_str(&format!(
+        "Subject: {}\nDate: {}\nMIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\n\
+         Content-Transfer-Encoding: 8bit\n\n{}\n",
+        encode_header(&one_line(subject)),
+        date.to_rfc2822(),
+        text
+    ));
+    message
+}
+
+async fn send_email(config: &NotifyConfig, subject: &str, text: &str) -> Result<(), String> {
+    let message = email_message(config, subject, text, crate::timezone::now());
+
+    let mut child = tokio::process::Command::new(&config.sendmail)
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", config.sendmail.display(), e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes()).await.map_err(|e| e.to_string())?;
+    }
+    let status = child.wait().await.map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", config.sendmail.display(), status));
+    }
+    Ok(())
+}
+
+/// Send every due reminder that hasn't gone out. Returns how many were sent.
+pub async fn send_due(state: &AppState, client: &reqwest::Client, now: NaiveDateTime) -> usize {
+    let notes = state.load_notes();
+    let mut sent = 0;
+    for (at, note) in due_reminders(&notes, now, |key, at| is_sent(&state.db, key, at)) {
+        match send(&state.config.notify, client, note, at).await {
+            Ok(()) => {
+                if let Err(e) = mark_sent(&state.db, &note.key, at) {
+                    tracing::warn!(key = %note.key, error = %e, "Failed to record reminder");
+                }
+                tracing::info!(key = %note.key, "Sent reminder");
+                sent += 1;
+            }
+            // Tried again next minute, until the grace period runs out
+            Err(e) => tracing::warn!(key = %note.key, error = %e, "Reminder failed"),
+        }
+    }
+    sent
+}
+
+/// Check for due reminders every minute. Spawned by the server when a
+/// channel is configured, unless it is read-only.
+pub async fn check_minutely(state: Arc<AppState>) {
+    let client = state.http.clone();
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        interval.tick().await;
+        send_due(&state, &client, crate::timezone::now().naive_local()).await;
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /reminders`: upcoming reminders and the past week's.
+pub async fn reminders_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let now = crate::timezone::now().naive_local();
+    let all = reminders(&notes);
+    let (past, upcoming): (Vec<_>, Vec<_>) = all.into_iter().partition(|(at, _)| *at <= now);
+    let recent: Vec<_> = past
+        .into_iter()
+        .rev()
+        .filter(|(at, _)| *at > now - TimeDelta::days(RECENT_DAYS))
+        .collect();
+
+    let mut html = String::from("<h1>Reminders</h1>");
+    let channel = match state.config.notify.channel {
+        Some(NotifyChannel::Webhook) => "by webhook",
+        Some(NotifyChannel::Ntfy) => "through ntfy",
+        Some(NotifyChannel::Email) => "by email",
+        None => "nowhere: no <code>[notify]</code> channel is configured",
+    };
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">Set <code>remind: YYYY-MM-DD HH:MM</code> on a note. Reminders are sent {}.</p>"#,
+        channel
+    ));
+
+    let row = |at: &NaiveDateTime, note: &Note, status: &str| {
+        format!(
+            r#"<tr><td>{}</td><td><a href="/note/{}">{}</a></td><td>{}</td></tr>"#,
+            at.format("%a %Y-%m-%d %H:%M"),
+            note.key,
+            html_escape(&note.title),
+            status
+        )
+    };
+    html.push_str("<h2>Upcoming</h2>");
+    if upcoming.is_empty() {
+        html.push_str("<p>Nothing scheduled.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>When</th><th>Note</th><th></th></tr>"#);
+        for (at, note) in &upcoming {
+            html.push_str(&row(at, note, ""));
+        }
+        html.push_str("</table>");
+    }
+    if !recent.is_empty() {
+        html.push_str(&format!(
+            r#"<h2>Past {} days</h2><table class="time-table"><tr><th>When</th><th>Note</th><th>Status</th></tr>"#,
+            RECENT_DAYS
+        ));
+        for (at, note) in &recent {
+            let status = if is_sent(&state.db, &note.key, *at) { "sent" } else { "not sent" };
+            html.push_str(&row(at, note, status));
+        }
+        html.push_str("</table>");
+    }
+
+    Html(base_html("Reminders", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::{load_all_notes, parse_remind};
+    use std::fs;
+
+    #[test]
+    fn test_due_reminders() {
+        let dir = TempDir::new("reminders");
+        fs::write(dir.join("a.md"), "---\ntitle: Call Bob\nid: aaaaaaaaaaaa\nremind: 2026-10-17 09:00\n---\n").unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: Later\nremind: 2026-10-17T18:30\n---\n").unwrap();
+        fs::write(dir.join("c.md"), "---\ntitle: Long ago\nremind: 2026-10-10 09:00\n---\n").unwrap();
+        fs::write(dir.join("d.md"), "---\ntitle: Archived\nremind: 2026-10-17 08:00\nstatus: archived\n---\n").unwrap();
+        fs::write(dir.join("e.md"), "---\ntitle: Bad\nremind: tomorrow\n---\n").unwrap();
+        let notes = load_all_notes(&dir);
+
+        let now = parse_remind("2026-10-17 12:00").unwrap();
+        let titles = |sent: &dyn Fn(&str, NaiveDateTime) -> bool| -> Vec<String> {
+            due_reminders(&notes, now, sent).iter().map(|(_, n)| n.title.clone()).collect()
+        };
+        assert_eq!(titles(&|_, _| false), vec!["Call Bob"]);
+        assert!(titles(&|key, _| key == "aaaaaaaaaaaa").is_empty());
+        assert_eq!(reminders(&notes).len(), 3);
+
+        let bob = notes.iter().find(|n| n.title == "Call Bob").unwrap();
+        let config = NotifyConfig {
+            base_url: Some("https://notes.example/".to_string()),
+            ..NotifyConfig::default()
+        };
+        assert_eq!(
+            reminder_text(&config, bob, bob.remind.unwrap()),
+            "Call Bob (2026-10-17 09:00)\nhttps://notes.example/note/aaaaaaaaaaaa"
+        );
+
+        let db = crate::store::Db::temporary();
+        assert!(!is_sent(&db, &bob.key, bob.remind.unwrap()));
+        mark_sent(&db, &bob.key, bob.remind.unwrap()).unwrap();
+        assert!(is_sent(&db, &bob.key, bob.remind.unwrap()));
+    }
+
+    #[test]
+    fn test_email_message() {
+        let config = NotifyConfig {
+            to: Some("me@example.org".to_string()),
+            ..NotifyConfig::default()
+        };
+        let date = DateTime::parse_from_rfc3339("2026-10-17T09:00:00-04:00").unwrap();
+        let message = email_message(&config, "Reminder: Call Bob", "Body", date);
+        assert_eq!(
+            message,
+            "To: me@example.org\nSubject: Reminder: Call Bob\nDate: Sat, 17 Oct 2026 09:00:00 -0400\n\
+             MIME-Version: 1.0\nContent-Type: text/plain; charset=utf-8\nContent-Transfer-Encoding: 8bit\n\nBody\n"
+        );
+
+        let subject = "Reminder: Relire l'article de Gödel sur l'incomplétude avant jeudi";
+        let encoded = encode_header(subject);
+        assert!(encoded.lines().all(|l| l.len() < 78 && l.trim_start().starts_with("=?utf-8?B?")));
+        let decoded: Vec<u8> = encoded
+            .split_whitespace()
+            .flat_map(|w| BASE64.decode(w.trim_start_matches("=?utf-8?B?").trim_end_matches("?=")).unwrap())
+            .collect();
+        assert_eq!(String::from_utf8(decoded).unwrap(), subject);
+    }
+}
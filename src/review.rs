@@ -0,0 +1,491 @@
+//! Weekly review.
+//!
+//! `/review/weekly` summarizes the seven days ending today (or `?end=`):
+//! notes created and edited, papers added, time per category against the
+//! `weekly_hours` goals of `[[time_categories]]`, tasks checked off, and
+//! this week's notes that nothing links to. Tasks count as checked off when
+//! the week's git history adds them as `- [x]` lines and they are still
+//! checked. The summary is markdown with `[@key]` links, so
+//! `POST /api/review/weekly` can save it as `reviews/YYYY-MM-DD.md`. The
+//! page also offers the week's AI-written digest (see `digest`).
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{Days, NaiveDate};
+use serde::Deserialize;
+
+use crate::archive::is_archived;
+use crate::auth::is_logged_in;
+use crate::graph_index;
+use crate::journal::daily_date;
+use crate::models::{Note, NoteType, TimeCategory, TimeCategoryDef};
+use crate::notes::{new_note_frontmatter, new_note_key, process_crosslinks, render_markdown};
+use crate::tasks::{extract_tasks, parse_checkbox, split_due};
+use crate::templates::base_html;
+use crate::time_categories::find_category;
+use crate::AppState;
+
+/// Directory under the notes directory that holds saved reviews.
+pub const REVIEW_DIR: &str = "reviews";
+
+/// Days a review covers, ending on its `end` date.
+const REVIEW_DAYS: u64 = 7;
+
+/// Minutes logged to a category in the week, and its goal in hours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategoryTime {
+    pub category: TimeCategory,
+    pub minutes: u32,
+    pub goal_hours: Option<f64>,
+}
+
+#[derive(Debug)]
+pub struct WeeklyReview<'a> {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub created: Vec<&'a Note>,
+    pub edited: Vec<&'a Note>,
+    pub papers: Vec<&'a Note>,
+    pub time: Vec<CategoryTime>,
+    /// Tasks checked off in the week, with the note each is in.
+    pub completed: Vec<(&'a Note, String)>,
+    /// Notes created or edited in the week with no links in or out.
+    pub orphans: Vec<&'a Note>,
+}
+
+/// First day of the week ending on `end`.
+pub fn week_start(end: NaiveDate) -> NaiveDate {
+    end - Days::new(REVIEW_DAYS - 1)
+}
+
+/// Path of the saved review for the week ending on `end`, relative to the
+/// notes directory.
+pub fn review_path(end: NaiveDate) -> PathBuf {
+    Path::new(REVIEW_DIR).join(format!("{}.md", end.format("%Y-%m-%d")))
+}
+
+/// Saved reviews and digests, which only summarize other notes.
+fn is_review(note: &Note) -> bool {
+    note.path
+        .parent()
+        .is_some_and(|dir| dir == Path::new(REVIEW_DIR) || dir == Path::new(crate::digest::DIGEST_DIR))
+}
+
+/// Checked task lines the notes directory's git history added between
+/// `start` and `end` (inclusive), as (file path, task text). Empty when
+/// the directory isn't a git repository.
+pub fn checked_in_git(notes_dir: &Path, start: NaiveDate, end: NaiveDate) -> Vec<(PathBuf, String)> {
+    let output = std::process::Command::new("git")
+        .args(["log", "-p", "-U0", "--format=", "--no-renames", "--no-color", "--relative"])
+        .arg(format!("--since={} 00:00", start.format("%Y-%m-%d")))
+        .arg(format!("--until={} 00:00", (end + Days::new(1)).format("%Y-%m-%d")))
+        .args(["--", "*.md"])
+        .current_dir(notes_dir)
+        .output();
+    let output = match output {
+        Ok(o) if o.status.success() => o,
+        _ => return Vec::new(),
+    };
+    parse_checked_lines(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The `+- [x] ...` lines of a unified diff, with the file each is in.
+fn parse_checked_lines(diff: &str) -> Vec<(PathBuf, String)> {
+    let mut file: Option<PathBuf> = None;
+    let mut checked = Vec::new();
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            file = path.strip_prefix("b/").map(PathBuf::from);
+        } else if let (Some(added), Some(file)) = (line.strip_prefix('+'), &file) {
+            if let Some((true, text)) = parse_checkbox(added) {
+                checked.push((file.clone(), split_due(text).0));
+            }
+        }
+    }
+    checked
+}
+
+/// Build the review of the week ending on `end`. `linked` holds every note
+/// key with a link in or out; `checked` comes from `checked_in_git`.
+pub fn weekly_review<'a>(
+    notes: &'a [Note],
+    end: NaiveDate,
+    categories: &[TimeCategoryDef],
+    linked: &HashSet<String>,
+    checked: &[(PathBuf, String)],
+) -> WeeklyReview<'a> {
+    let start = week_start(end);
+    let in_week = |d: NaiveDate| d >= start && d <= end;
+    let mut notes: Vec<&Note> = notes.iter().filter(|n| !is_review(n)).collect();
+    notes.sort_by_key(|n| n.title.to_lowercase());
+
+    let created: Vec<&Note> = notes.iter().copied().filter(|n| n.created.is_some_and(in_week)).collect();
+    let edited: Vec<&Note> = notes
+        .iter()
+        .copied()
+        .filter(|n| !created.iter().any(|c| c.key == n.key))
+        .filter(|n| in_week(crate::timezone::local(n.modified).date_naive()))
+        .collect();
+    let papers = created
+        .iter()
+        .copied()
+        .filter(|n| matches!(n.note_type, NoteType::Paper(_)))
+        .collect();
+
+    let mut minutes: BTreeMap<String, (TimeCategory, u32)> = BTreeMap::new();
+    for entry in notes.iter().flat_map(|n| &n.time_entries).filter(|e| in_week(e.date)) {
+        minutes.entry(entry.category.to_string()).or_insert((entry.category.clone(), 0)).1 += entry.minutes;
+    }
+    // Configured categories first, in config order, so goals with no time
+    // logged still show; then anything else alphabetically
+    let mut time: Vec<CategoryTime> = categories
+        .iter()
+        .filter_map(|d| {
+            let logged = minutes.remove(&d.name).map_or(0, |(_, m)| m);
+            (logged > 0 || d.weekly_hours.is_some()).then(|| CategoryTime {
+                category: TimeCategory::from_name(&d.name),
+                minutes: logged,
+                goal_hours: d.weekly_hours,
+            })
+        })
+        .collect();
+    time.extend(minutes.into_values().map(|(category, minutes)| CategoryTime {
+        goal_hours: find_category(categories, &category).and_then(|d| d.weekly_hours),
+        category,
+        minutes,
+    }));
+
+    let checked: HashSet<(&Path, &str)> = checked.iter().map(|(p, t)| (p.as_path(), t.as_str())).collect();
+    let mut completed = Vec::new();
+    for note in &notes {
+        for task in extract_tasks(note).into_iter().filter(|t| t.done) {
+            if checked.contains(&(note.path.as_path(), task.text.as_str())) {
+                completed.push((*note, task.text));
+            }
+        }
+    }
+
+    let orphans = created
+        .iter()
+        .chain(&edited)
+        .copied()
+        .filter(|n| !linked.contains(&n.key) && daily_date(n).is_none() && !is_archived(n))
+        .collect();
+
+    WeeklyReview {
+        start,
+        end,
+        created,
+        edited,
+        papers,
+        time,
+        completed,
+        orphans,
+    }
+}
+
+fn hours_minutes(minutes: u32) -> String {
+    format!("{}h {}m", minutes / 60, minutes % 60)
+}
+
+/// The review as markdown, linking notes as `[@key]`.
+pub fn review_markdown(review: &WeeklyReview) -> String {
+    let list = |heading: &str, notes: &[&Note]| {
+        let mut md = format!("## {} ({})\n\n", heading, notes.len());
+        if notes.is_empty() {
+            md.push_str("None.\n");
+        }
+        for note in notes {
+            md.push_str(&format!("- [@{}]\n", note.key));
+        }
+        md + "\n"
+    };
+
+    let mut md = list("Notes created", &review.created);
+    md.push_str(&list("Notes edited", &review.edited));
+    md.push_str(&list("Papers added", &review.papers));
+
+    let total: u32 = review.time.iter().map(|t| t.minutes).sum();
+    md.push_str(&format!("## Time ({})\n\n", hours_minutes(total)));
+    if review.time.is_empty() {
+        md.push_str("None logged.\n");
+    }
+    for t in &review.time {
+        md.push_str(&format!("- {}: {}", t.category, hours_minutes(t.minutes)));
+        if let Some(goal) = t.goal_hours {
+            let pct = t.minutes as f64 / (goal * 60.0) * 100.0;
+            md.push_str(&format!(" of {}h goal ({:.0}%)", goal, pct));
+        }
+        md.push('\n');
+    }
+    md.push('\n');
+
+    md.push_str(&format!("## Tasks completed ({})\n\n", review.completed.len()));
+    if review.completed.is_empty() {
+        md.push_str("None.\n");
+    }
+    // Not written as checkboxes, so saved reviews don't show up as tasks
+    for (note, text) in &review.completed {
+        md.push_str(&format!("- {} ([@{}])\n", text, note.key));
+    }
+    md.push('\n');
+
+    md.push_str(&list("Notes needing links", &review.orphans));
+    md
+}
+
+/// Save the review as `reviews/YYYY-MM-DD.md`. Returns the key of the new
+/// note, or of the one already saved for that week (and false).
+pub fn save_review(
+    notes_dir: &Path,
+    notes: &[Note],
+    review: &WeeklyReview,
+) -> Result<(String, bool), String> {
+    let relative = review_path(review.end);
+    if let Some(note) = notes.iter().find(|n| n.path == relative) {
+        return Ok((note.key.clone(), false));
+    }
+    let file_path = notes_dir.join(&relative);
+    if file_path.exists() {
+        return Err(format!("{} exists but isn't loaded as a note", relative.display()));
+    }
+
+    let title = format!(
+        "Weekly review, {} to {}",
+        review.start.format("%Y-%m-%d"),
+        review.end.format("%Y-%m-%d")
+    );
+    let key = new_note_key(|k| notes.iter().any(|n| n.key == k));
+    let content = new_note_frontmatter(&title, &key, crate::timezone::now())
+        + &format!("date: {}\n---\n\n", review.end.format("%Y-%m-%d"))
+        + &review_markdown(review);
+
+    fs::create_dir_all(notes_dir.join(REVIEW_DIR))
+        .map_err(|e| format!("Failed to create {}: {}", REVIEW_DIR, e))?;
+    crate::encryption::write_file(&file_path, &content)?;
+    Ok((key, true))
+}
+
+/// Every note key with an edge in the graph index. Saved reviews link to
+/// the orphans they list, so their edges don't count.
+fn linked_keys(db: &crate::store::Db, notes: &[Note]) -> HashSet<String> {
+    let reviews: HashSet<&str> = notes.iter().filter(|n| is_review(n)).map(|n| n.key.as_str()).collect();
+    graph_index::load_all_edges(db)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|e| !reviews.contains(e.source.as_str()) && !reviews.contains(e.target.as_str()))
+        .flat_map(|e| [e.source, e.target])
+        .collect()
+}
+
+pub(crate) fn build_review<'a>(state: &AppState, notes: &'a [Note], end: NaiveDate) -> WeeklyReview<'a> {
+    let checked = checked_in_git(&state.notes_dir, week_start(end), end);
+    weekly_review(notes, end, &state.time_categories, &linked_keys(&state.db, notes), &checked)
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQuery {
+    pub end: Option<NaiveDate>,
+}
+
+impl ReviewQuery {
+    fn end(&self) -> NaiveDate {
+        self.end.unwrap_or_else(crate::timezone::today)
+    }
+}
+
+/// `GET /review/weekly`: the review of the week ending today or on `end`.
+pub async fn weekly_review_page(
+    Query(query): Query<ReviewQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let end = query.end();
+    let review = build_review(&state, &notes, end);
+    let notes_map = state.notes_map();
+
+    let mut html = String::from("<h1>Weekly review</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb"><a href="/review/weekly?end={}">&larr; Earlier</a> · {} to {} · <a href="/review/weekly?end={}">Later &rarr;</a></p>"#,
+        (end - Days::new(REVIEW_DAYS)).format("%Y-%m-%d"),
+        review.start.format("%a %b %-d"),
+        end.format("%a %b %-d, %Y"),
+        (end + Days::new(REVIEW_DAYS)).format("%Y-%m-%d"),
+    ));
+    if !state.config.read_only {
+        let saved = notes.iter().find(|n| n.path == review_path(end));
+        html.push_str(&match saved {
+            Some(note) => format!(r#"<p><a href="/note/{}">Saved review</a></p>"#, note.key),
+            None => format!(
+                r#"<p><button id="save-review">Save as note</button></p>
+<script>
+document.getElementById('save-review').addEventListener('click', async () => {{
+    const resp = await fetch('/api/review/weekly', {{
+        method: 'POST',
+        headers: {{'Content-Type': 'application/json'}},
+        body: JSON.stringify({{end: '{}'}}),
+    }});
+    if (resp.ok) {{
+        const data = await resp.json();
+        window.location.href = '/note/' + data.key;
+    }} else {{
+        alert('Save failed: ' + await resp.text());
+    }}
+}});
+</script>"#,
+                end.format("%Y-%m-%d")
+            ),
+        });
+        let digest = notes.iter().find(|n| n.path == crate::digest::digest_path(end));
+        html.push_str(&match digest {
+            Some(note) => format!(r#"<p><a href="/note/{}">Weekly digest</a></p>"#, note.key),
+            None => format!(
+                r#"<p><button id="write-digest">Write digest with AI</button></p>
+<script>
+document.getElementById('write-digest').addEventListener('click', async (e) => {{
+    e.target.disabled = true;
+    e.target.textContent = 'Writing...';
+    const resp = await fetch('/api/digest', {{
+        method: 'POST',
+        headers: {{'Content-Type': 'application/json'}},
+        body: JSON.stringify({{end: '{}'}}),
+    }});
+    if (resp.ok) {{
+        const data = await resp.json();
+        window.location.href = '/note/' + data.key;
+    }} else {{
+        alert('Digest failed: ' + await resp.text());
+        e.target.disabled = false;
+        e.target.textContent = 'Write digest with AI';
+    }}
+}});
+</script>"#,
+                end.format("%Y-%m-%d")
+            ),
+        });
+    }
+    html.push_str(r#"<div class="note-content">"#);
+    html.push_str(&render_markdown(&process_crosslinks(&review_markdown(&review), &notes_map)));
+    html.push_str("</div>");
+
+    Html(base_html("Weekly review", &html, None, true)).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SaveReviewRequest {
+    pub end: Option<NaiveDate>,
+}
+
+/// `POST /api/review/weekly`: save the review as a note; returns `{key}`.
+pub async fn save_review_handler(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<SaveReviewRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes = state.load_notes();
+    let end = body.end.unwrap_or_else(crate::timezone::today);
+    let review = build_review(&state, &notes, end);
+    match save_review(&state.notes_dir, &notes, &review) {
+        Ok((key, created)) => {
+            if created {
+                state.invalidate_notes_cache();
+                state.reindex_graph_note(&key);
+            }
+            axum::Json(serde_json::json!({ "key": key, "created": created })).into_response()
+        }
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    #[test]
+    fn test_weekly_review() {
+        let dir = TempDir::new("review");
+        fs::create_dir_all(dir.join("journal")).unwrap();
+        fs::write(
+            dir.join("grant.md"),
+            "---\ntitle: Grant\nid: aaaaaaaaaaaa\ncreated: 2026-10-14\ntime:\n  - date: 2026-10-15\n    minutes: 90\n    category: writing\n\
+             \x20 - date: 2026-10-01\n    minutes: 30\n    category: writing\n---\n\n- [x] budget @due(2026-10-12)\n- [x] letters\n- [ ] draft\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("paper.md"),
+            "---\ntitle: A Paper\nid: bbbbbbbbbbbb\ncreated: 2026-10-16\nbibtex: |\n  @article{x, title = {X}}\n---\n",
+        )
+        .unwrap();
+        fs::write(dir.join("old.md"), "---\ntitle: Old\ncreated: 2026-01-01\n---\n").unwrap();
+        fs::write(dir.join("journal/2026-10-15.md"), "---\ntitle: Day\ncreated: 2026-10-15\n---\n").unwrap();
+        let mut notes = load_all_notes(&dir.to_path_buf());
+        let long_ago = chrono::DateTime::parse_from_rfc3339("2026-01-01T12:00:00Z").unwrap().into();
+        for note in notes.iter_mut() {
+            note.modified = long_ago;
+        }
+
+        let categories = vec![
+            TimeCategoryDef { name: "writing".to_string(), color: String::new(), weekly_hours: Some(6.0) },
+            TimeCategoryDef { name: "reading".to_string(), color: String::new(), weekly_hours: Some(4.0) },
+            TimeCategoryDef { name: "teaching".to_string(), color: String::new(), weekly_hours: None },
+        ];
+        let linked: HashSet<String> = ["bbbbbbbbbbbb".to_string()].into();
+        let checked = parse_checked_lines(
+            "diff --git a/grant.md b/grant.md\n--- a/grant.md\n+++ b/grant.md\n@@ -8 +8 @@\n\
+             -- [ ] budget @due(2026-10-12)\n+- [x] budget @due(2026-10-12)\n",
+        );
+        assert_eq!(checked, vec![(PathBuf::from("grant.md"), "budget".to_string())]);
+
+        let end = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let review = weekly_review(&notes, end, &categories, &linked, &checked);
+        let titles = |ns: &[&Note]| ns.iter().map(|n| n.title.clone()).collect::<Vec<_>>();
+        assert_eq!(review.start, NaiveDate::from_ymd_opt(2026, 10, 11).unwrap());
+        assert_eq!(titles(&review.created), vec!["A Paper", "Day", "Grant"]);
+        assert!(review.edited.is_empty());
+        assert_eq!(titles(&review.papers), vec!["A Paper"]);
+        assert_eq!(titles(&review.orphans), vec!["Grant"]);
+        assert_eq!(review.completed.len(), 1);
+        assert_eq!(review.completed[0].1, "budget");
+        assert_eq!(
+            review.time,
+            vec![
+                CategoryTime { category: TimeCategory::from_name("writing"), minutes: 90, goal_hours: Some(6.0) },
+                CategoryTime { category: TimeCategory::from_name("reading"), minutes: 0, goal_hours: Some(4.0) },
+            ]
+        );
+
+        let md = review_markdown(&review);
+        assert!(md.contains("- writing: 1h 30m of 6h goal (25%)"));
+        assert!(md.contains("- budget ([@aaaaaaaaaaaa])"));
+
+        let (key, created) = save_review(&dir, &notes, &review).unwrap();
+        assert!(created);
+        let saved = fs::read_to_string(dir.join("reviews/2026-10-17.md")).unwrap();
+        assert!(saved.contains(&format!("id: {}", key)));
+        assert!(saved.contains("## Notes needing links (1)"));
+    }
+}
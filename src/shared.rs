@@ -110,27 +110,27 @@ const CONTRIBUTOR_COLORS: &[&str] = &[
 // Sled helpers
 // ============================================================================
 
-fn meta_tree(db: &sled::Db) -> sled::Tree {
+fn meta_tree(db: &crate::store::Db) -> crate::store::Tree {
     db.open_tree("shared:meta").expect("open shared:meta tree")
 }
 
-fn doc_tree(db: &sled::Db, token: &str) -> sled::Tree {
+fn doc_tree(db: &crate::store::Db, token: &str) -> crate::store::Tree {
     db.open_tree(format!("shared:doc:{}", token))
         .expect("open shared:doc tree")
 }
 
-fn attrib_tree(db: &sled::Db, token: &str) -> sled::Tree {
+fn attrib_tree(db: &crate::store::Db, token: &str) -> crate::store::Tree {
     db.open_tree(format!("shared:attrib:{}", token))
         .expect("open shared:attrib tree")
 }
 
-fn save_meta(db: &sled::Db, meta: &SharedNoteMeta) {
+fn save_meta(db: &crate::store::Db, meta: &SharedNoteMeta) {
     let tree = meta_tree(db);
     let json = serde_json::to_vec(meta).unwrap();
     tree.insert(meta.share_token.as_bytes(), json).ok();
 }
 
-fn load_meta(db: &sled::Db, token: &str) -> Option<SharedNoteMeta> {
+fn load_meta(db: &crate::store::Db, token: &str) -> Option<SharedNoteMeta> {
     let tree = meta_tree(db);
     tree.get(token.as_bytes())
         .ok()
@@ -138,23 +138,40 @@ fn load_meta(db: &sled::Db, token: &str) -> Option<SharedNoteMeta> {
         .and_then(|v| serde_json::from_slice(&v).ok())
 }
 
-fn save_doc_bytes(db: &sled::Db, token: &str, bytes: &[u8]) {
+/// Point shares at their source note's new key after notes are moved.
+/// Returns the number of shares updated.
+pub fn rename_source_keys(db: &crate::store::Db, renames: &HashMap<String, String>) -> usize {
+    let tree = meta_tree(db);
+    let mut count = 0;
+    for (_, v) in tree.iter().flatten() {
+        if let Ok(mut meta) = serde_json::from_slice::<SharedNoteMeta>(&v) {
+            if let Some(new_key) = renames.get(&meta.source_note_key) {
+                meta.source_note_key = new_key.clone();
+                save_meta(db, &meta);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn save_doc_bytes(db: &crate::store::Db, token: &str, bytes: &[u8]) {
     let tree = doc_tree(db, token);
     tree.insert("doc", bytes).ok();
 }
 
-fn load_doc_bytes(db: &sled::Db, token: &str) -> Option<Vec<u8>> {
+fn load_doc_bytes(db: &crate::store::Db, token: &str) -> Option<Vec<u8>> {
     let tree = doc_tree(db, token);
     tree.get("doc").ok().flatten().map(|v| v.to_vec())
 }
 
-fn save_attribution(db: &sled::Db, token: &str, attrib: &LineAttribution) {
+fn save_attribution(db: &crate::store::Db, token: &str, attrib: &LineAttribution) {
     let tree = attrib_tree(db, token);
     let json = serde_json::to_vec(attrib).unwrap();
     tree.insert("lines", json).ok();
 }
 
-fn load_attribution(db: &sled::Db, token: &str) -> LineAttribution {
+fn load_attribution(db: &crate::store::Db, token: &str) -> LineAttribution {
     let tree = attrib_tree(db, token);
     tree.get("lines")
         .ok()
@@ -200,8 +217,8 @@ fn compute_block_attribution(text: &str, attribution: &LineAttribution) -> Vec<L
     // Group body lines into blocks (consecutive non-blank lines)
     let mut blocks: Vec<Vec<usize>> = vec![];
     let mut current: Vec<usize> = vec![];
-    for i in body_start..lines.len() {
-        if lines[i].trim().is_empty() {
+    for (i, line) in lines.iter().enumerate().skip(body_start) {
+        if line.trim().is_empty() {
             if !current.is_empty() {
                 blocks.push(std::mem::take(&mut current));
             }
@@ -239,6 +256,7 @@ fn build_note_from_text(meta: &SharedNoteMeta, text: &str) -> Note {
             bibtex_entries: fm.bibtex_entries,
             canonical_key: fm.canonical_key,
             sources: fm.sources,
+            versions: fm.versions,
         })
     } else {
         NoteType::Note
@@ -249,13 +267,22 @@ fn build_note_from_text(meta: &SharedNoteMeta, text: &str) -> Note {
         title: fm.title.unwrap_or_else(|| meta.title.clone()),
         date: fm.date,
         note_type,
-        parent_key: None,
+        parents: vec![],
+        prev_key: None,
+        created: None,
+        due: None,
+        remind: None,
+        status: None,
         time_entries: vec![],
         raw_content: body,
         full_file_content: text.to_string(),
         modified: meta.updated_at,
         pdf: fm.pdf,
         hidden: false,
+        tags: fm.tags,
+        hourly_rate: None,
+        priority: None,
+        est_minutes: None,
     }
 }
 
@@ -315,6 +342,9 @@ pub async fn create_shared_note(
         Some(n) => n,
         None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
     };
+    if crate::encryption::is_sealed(&note.raw_content) {
+        return (StatusCode::BAD_REQUEST, "Encrypted notes can't be shared").into_response();
+    }
 
     let token = generate_token();
 
@@ -382,17 +412,15 @@ pub async fn list_shared_notes(
     let tree = meta_tree(&state.db);
     let mut shares: Vec<SharedNoteMeta> = vec![];
 
-    for entry in tree.iter() {
-        if let Ok((_, v)) = entry {
-            if let Ok(meta) = serde_json::from_slice::<SharedNoteMeta>(&v) {
-                if meta.source_note_key == note_key {
-                    shares.push(meta);
-                }
+    for (_, v) in tree.iter().flatten() {
+        if let Ok(meta) = serde_json::from_slice::<SharedNoteMeta>(&v) {
+            if meta.source_note_key == note_key {
+                shares.push(meta);
             }
         }
     }
 
-    shares.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    shares.sort_by_key(|s| std::cmp::Reverse(s.created_at));
     Json(shares).into_response()
 }
 
@@ -1093,8 +1121,8 @@ This paper introduces the transformer architecture.
         let text = "---\ntitle: Test\nparent: some-parent\n---\n\nBody.\n";
         let note = build_note_from_text(&meta, text);
 
-        // parent_key is always None in shared context
-        assert!(note.parent_key.is_none());
+        // parents is always empty in shared context
+        assert!(note.parents.is_empty());
     }
 
     #[test]
@@ -1110,7 +1138,7 @@ This paper introduces the transformer architecture.
 
     #[test]
     fn test_save_and_load_meta() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let meta = make_meta();
         save_meta(&db, &meta);
 
@@ -1125,14 +1153,14 @@ This paper introduces the transformer architecture.
 
     #[test]
     fn test_load_meta_not_found() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let loaded = load_meta(&db, "nonexistent");
         assert!(loaded.is_none());
     }
 
     #[test]
     fn test_save_and_load_doc_bytes() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let token = "test_token";
         let data = b"test document bytes";
         save_doc_bytes(&db, token, data);
@@ -1143,14 +1171,14 @@ This paper introduces the transformer architecture.
 
     #[test]
     fn test_load_doc_bytes_not_found() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let loaded = load_doc_bytes(&db, "nonexistent");
         assert!(loaded.is_none());
     }
 
     #[test]
     fn test_save_and_load_attribution() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let token = "test_token";
         let attrib = LineAttribution {
             lines: vec![
@@ -1174,7 +1202,7 @@ This paper introduces the transformer architecture.
 
     #[test]
     fn test_load_attribution_default_empty() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
         let loaded = load_attribution(&db, "nonexistent");
         assert!(loaded.lines.is_empty());
     }
@@ -1469,7 +1497,7 @@ This paper introduces the transformer architecture.
 
     #[test]
     fn test_meta_tree_isolation() {
-        let db = sled::Config::new().temporary(true).open().unwrap();
+        let db = crate::store::Db::temporary();
 
         let mut meta1 = make_meta();
         meta1.share_token = "token_aaa_1234567890123456".to_string();
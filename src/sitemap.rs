@@ -0,0 +1,115 @@
+//! `sitemap.xml` and `robots.txt`.
+//!
+//! A public viewer (`notes publish`) serves a sitemap of its pages and
+//! non-hidden notes, and a `robots.txt` pointing at it that keeps crawlers
+//! off hidden notes and the routes only the main server has. The main
+//! server's `robots.txt` disallows everything. Both are built from the
+//! corpus on each request.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
+};
+
+use crate::export::link_base;
+use crate::models::Note;
+use crate::notes::html_escape;
+use crate::AppState;
+
+/// Listing pages a public viewer serves besides the notes.
+const PUBLIC_PAGES: &[&str] = &["/", "/papers", "/graph"];
+
+/// Route prefixes that are never for crawlers.
+const PRIVATE_ROUTES: &[&str] = &["/api/", "/login", "/logout", "/new", "/search", "/shared/", "/pdfs/", "/export/"];
+
+/// The sitemap of the public pages and non-hidden notes under `base`.
+pub fn sitemap_xml(notes: &HashMap<String, Note>, base: &str) -> String {
+    let mut visible: Vec<&Note> = notes.values().filter(|n| !n.hidden).collect();
+    visible.sort_by(|a, b| a.key.cmp(&b.key));
+    let mut urls: String = PUBLIC_PAGES
+        .iter()
+        .map(|page| format!("  <url><loc>{}{}</loc></url>\n", html_escape(base), page))
+        .collect();
+    for note in visible {
+        urls.push_str(&format!(
+            "  <url><loc>{}/note/{}</loc><lastmod>{}</lastmod></url>\n",
+            html_escape(base),
+            html_escape(&note.key),
+            note.modified.format("%Y-%m-%d"),
+        ));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n{}</urlset>\n",
+        urls
+    )
+}
+
+/// `robots.txt` for a public viewer: the private routes and each hidden
+/// note are disallowed, and the sitemap is linked. Without `published`,
+/// nothing may be crawled.
+pub fn robots_txt(notes: &HashMap<String, Note>, base: &str, published: bool) -> String {
+    if !published {
+        return "User-agent: *\nDisallow: /\n".to_string();
+    }
+    let mut hidden: Vec<&str> = notes.values().filter(|n| n.hidden).map(|n| n.key.as_str()).collect();
+    hidden.sort();
+    let mut robots = String::from("User-agent: *\n");
+    for route in PRIVATE_ROUTES {
+        robots.push_str(&format!("Disallow: {}\n", route));
+    }
+    for key in hidden {
+        robots.push_str(&format!("Disallow: /note/{}\n", key));
+    }
+    robots.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base));
+    robots
+}
+
+pub async fn sitemap(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let base = link_base(&state, &headers).unwrap_or_default();
+    let xml = sitemap_xml(&state.notes_map(), &base);
+    ([(header::CONTENT_TYPE, "application/xml; charset=utf-8")], xml).into_response()
+}
+
+pub async fn robots(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let base = link_base(&state, &headers).unwrap_or_default();
+    let robots = robots_txt(&state.notes_map(), &base, state.config.publish);
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], robots).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn note(key: &str, hidden: bool) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: key.to_string(),
+            modified: chrono::DateTime::parse_from_rfc3339("2026-10-01T10:00:00Z").unwrap().into(),
+            hidden,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sitemap_and_robots() {
+        let notes: HashMap<String, Note> =
+            [note("pub1", false), note("sec1", true)].into_iter().map(|n| (n.key.clone(), n)).collect();
+        let xml = sitemap_xml(&notes, "https://notes.example");
+        assert!(xml.contains("<url><loc>https://notes.example/</loc></url>"));
+        assert!(xml.contains("<url><loc>https://notes.example/note/pub1</loc><lastmod>2026-10-01</lastmod></url>"));
+        assert!(!xml.contains("sec1"));
+
+        let robots = robots_txt(&notes, "https://notes.example", true);
+        assert!(robots.contains("Disallow: /api/\n"));
+        assert!(robots.contains("Disallow: /note/sec1\n"));
+        assert!(!robots.contains("pub1"));
+        assert!(robots.ends_with("Sitemap: https://notes.example/sitemap.xml\n"));
+        assert_eq!(robots_txt(&notes, "https://notes.example", false), "User-agent: *\nDisallow: /\n");
+    }
+}
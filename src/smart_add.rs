@@ -5,31 +5,37 @@
 //! - Query external APIs (arXiv, CrossRef) for metadata
 //! - Extract metadata from web pages
 //! - Create paper notes with proper frontmatter
+//!
+//! Smart paste: `extract_identifiers` finds every DOI and arXiv ID in pasted
+//! text, and `/api/smart-add/extract` looks up a bounded number of them,
+//! matching local notes by identifier and then by title.
 
 use crate::auth::is_logged_in;
 use crate::models::{
     AttachSourceRequest, BibImportAnalysis, BibImportConflict, BibImportCreatedNote,
     BibImportEntry, BibImportExecuteRequest, BibImportExecuteResult, BibImportExisting,
-    BibImportUpdatedNote, ExternalResult, InputType, LocalMatch, Note, NoteType,
-    QuickNoteRequest, SmartAddCreateRequest, SmartAddRequest, SmartAddResult,
+    BibImportUpdatedNote, ExternalResult, InputType, LocalMatch, Note, NoteType, PastedReference,
+    QuickNoteRequest, SmartAddCreateRequest, SmartAddRequest, SmartAddResult, SmartExtractRequest,
+    SmartExtractResult, source_match_id,
 };
-use crate::notes::{generate_key, normalize_bibtex, normalize_title, parse_bibtex, split_bib_file};
+use crate::note_templates::{self, TemplateVars};
+use crate::notes::{new_note_frontmatter, new_note_key, normalize_bibtex, normalize_title, parse_bibtex, split_bib_file};
 use crate::{validate_path_within, AppState};
 use axum::{
     extract::{Multipart, State},
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::CookieJar;
-use chrono::Utc;
+use futures_util::StreamExt;
 use regex::Regex;
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
-use crate::url_validator::validate_url;
+use crate::page_cache;
+use crate::url_validator::{canonicalize, UrlPolicy};
 
 // ============================================================================
 // Input Detection
@@ -114,7 +120,7 @@ pub fn extract_doi(input: &str) -> Option<String> {
                     // Clean up the DOI (remove trailing punctuation)
                     let doi = m
                         .as_str()
-                        .trim_end_matches(|c| c == '.' || c == ',' || c == ';');
+                        .trim_end_matches(['.', ',', ';']);
                     return Some(doi.to_string());
                 }
             }
@@ -123,6 +129,69 @@ pub fn extract_doi(input: &str) -> Option<String> {
     None
 }
 
+/// Most identifiers looked up from one pasted text.
+pub const MAX_EXTRACTED: usize = 20;
+
+/// Lookups run at once for pasted text, to stay polite to arXiv and CrossRef.
+const EXTRACT_CONCURRENCY: usize = 4;
+
+/// Every arXiv ID and DOI in `text` (a related-work paragraph, an email),
+/// in order of appearance and without repeats. arXiv IDs need an
+/// `arxiv.org/abs/` or `arXiv:` prefix, since bare ones look like version
+/// numbers; arXiv's own DOIs (`10.48550/arXiv.…`) count as arXiv IDs.
+pub fn extract_identifiers(text: &str) -> Vec<InputType> {
+    let re = Regex::new(
+        r#"(?i)(?:arxiv\.org/(?:abs|pdf)/|arxiv:\s*)(\d{4}\.\d{4,5}|[a-z-]+(?:\.[a-z]{2})?/\d{7})|\b(10\.\d{4,}/[^\s"'<>]+)"#,
+    )
+    .expect("identifier regex");
+    let mut seen = HashSet::new();
+    let mut found = Vec::new();
+    for caps in re.captures_iter(text) {
+        let id = match (caps.get(1), caps.get(2)) {
+            (Some(arxiv), _) => InputType::ArxivUrl { arxiv_id: arxiv.as_str().to_string() },
+            (None, Some(doi)) => {
+                let doi = trim_doi(doi.as_str());
+                match doi.to_lowercase().strip_prefix("10.48550/arxiv.") {
+                    Some(arxiv_id) => InputType::ArxivUrl { arxiv_id: arxiv_id.to_string() },
+                    None => InputType::DoiUrl { doi: doi.to_string() },
+                }
+            }
+            _ => continue,
+        };
+        let dedupe_key = match &id {
+            InputType::ArxivUrl { arxiv_id } => format!("arxiv:{}", arxiv_id.to_lowercase()),
+            InputType::DoiUrl { doi } => format!("doi:{}", doi.to_lowercase()),
+            _ => continue,
+        };
+        if seen.insert(dedupe_key) {
+            found.push(id);
+        }
+    }
+    found
+}
+
+/// A DOI found in running text without the punctuation after it: trailing
+/// `.`, `,`, `;` and `:`, and closing brackets that don't close one in the
+/// DOI itself, as in `(doi:10.1145/123)`.
+fn trim_doi(doi: &str) -> &str {
+    let mut doi = doi;
+    loop {
+        let trimmed = doi.trim_end_matches(['.', ',', ';', ':']);
+        let unbalanced = |open: char, close: char| {
+            trimmed.ends_with(close) && trimmed.matches(close).count() > trimmed.matches(open).count()
+        };
+        let trimmed = if unbalanced('(', ')') || unbalanced('[', ']') {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed.len() == doi.len() {
+            return doi;
+        }
+        doi = trimmed;
+    }
+}
+
 // ============================================================================
 // Local Search
 // ============================================================================
@@ -139,10 +208,10 @@ pub fn search_local_for_match(
     // Helper to check if a note has a matching source
     let check_source = |note: &Note, source_type: &str, identifier: &str| -> bool {
         if let NoteType::Paper(ref paper) = note.note_type {
-            for source in &paper.sources {
-                if source.source_type == source_type && source.identifier == identifier {
-                    return true;
-                }
+            // Any version counts; DOIs ignore case, arXiv ids the revision
+            let wanted = source_match_id(source_type, identifier);
+            if paper.all_sources().any(|s| s.source_type == source_type && s.match_id() == wanted) {
+                return true;
             }
         }
         // Also check content for backwards compatibility
@@ -188,6 +257,22 @@ pub fn search_local_for_match(
         }
     }
 
+    // For any URL, compare canonical forms with the `url` sources
+    if let Some(canonical) = canonicalize(input) {
+        if let Some(note) = notes.par_iter().find_first(|note| match note.note_type {
+            NoteType::Paper(ref paper) => paper
+                .all_sources()
+                .any(|s| s.source_type == "url" && canonicalize(&s.identifier).as_deref() == Some(canonical.as_str())),
+            _ => false,
+        }) {
+            return Some(LocalMatch {
+                key: note.key.clone(),
+                title: note.title.clone(),
+                match_type: "url".to_string(),
+            });
+        }
+    }
+
     // Check for exact title matches
     if let Some(note) = notes.par_iter().find_first(|note| {
         note.title.to_lowercase() == input_lower
@@ -282,25 +367,26 @@ pub fn generate_suggested_filename(title: &str) -> String {
 // External API Integration
 // ============================================================================
 
-pub async fn query_arxiv_api(arxiv_id: &str) -> Option<ExternalResult> {
-    let url = format!("https://export.arxiv.org/api/query?id_list={}", arxiv_id);
+/// Timeout for each arXiv/CrossRef API request.
+const API_TIMEOUT: Duration = Duration::from_secs(10);
 
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-    {
-        Ok(c) => c,
-        Err(_) => return None,
-    };
+pub async fn query_arxiv_api(client: &reqwest::Client, arxiv_id: &str) -> Option<ExternalResult> {
+    let url = format!("https://export.arxiv.org/api/query?id_list={}", arxiv_id);
 
-    let response = match client.get(&url).send().await {
+    crate::http::throttle(&url).await;
+    let response = client.get(&url).timeout(API_TIMEOUT).send().await;
+    crate::telemetry::record_http_call("arxiv", &response);
+    let response = match response {
         Ok(r) => r,
         Err(_) => return None,
     };
 
     let text = match response.text().await {
         Ok(t) => t,
-        Err(_) => return None,
+        Err(e) => {
+            tracing::warn!(api = "arxiv", error = %e, "Failed to read response body");
+            return None;
+        }
     };
 
     // Parse XML response - need to extract from <entry> not the feed
@@ -350,30 +436,28 @@ pub async fn query_arxiv_api(arxiv_id: &str) -> Option<ExternalResult> {
     })
 }
 
-pub async fn query_crossref_api(doi: &str) -> Option<ExternalResult> {
+pub async fn query_crossref_api(client: &reqwest::Client, doi: &str) -> Option<ExternalResult> {
     let url = format!("https://api.crossref.org/works/{}", doi);
 
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-    {
-        Ok(c) => c,
-        Err(_) => return None,
-    };
-
-    let response = match client
+    crate::http::throttle(&url).await;
+    let response = client
         .get(&url)
         .header("User-Agent", "NotesApp/1.0 (mailto:user@example.com)")
+        .timeout(API_TIMEOUT)
         .send()
-        .await
-    {
+        .await;
+    crate::telemetry::record_http_call("crossref", &response);
+    let response = match response {
         Ok(r) => r,
         Err(_) => return None,
     };
 
     let json: serde_json::Value = match response.json().await {
         Ok(j) => j,
-        Err(_) => return None,
+        Err(e) => {
+            tracing::warn!(api = "crossref", error = %e, "Invalid JSON response");
+            return None;
+        }
     };
 
     let message = json.get("message")?;
@@ -454,34 +538,32 @@ pub async fn query_crossref_api(doi: &str) -> Option<ExternalResult> {
     })
 }
 
-pub async fn query_crossref_by_title(title: &str) -> Option<ExternalResult> {
+pub async fn query_crossref_by_title(client: &reqwest::Client, title: &str) -> Option<ExternalResult> {
     let encoded_title = urlencoding::encode(title);
     let url = format!(
         "https://api.crossref.org/works?query.title={}&rows=1",
         encoded_title
     );
 
-    let client = match reqwest::Client::builder()
-        .timeout(Duration::from_secs(10))
-        .build()
-    {
-        Ok(c) => c,
-        Err(_) => return None,
-    };
-
-    let response = match client
+    crate::http::throttle(&url).await;
+    let response = client
         .get(&url)
         .header("User-Agent", "NotesApp/1.0 (mailto:user@example.com)")
+        .timeout(API_TIMEOUT)
         .send()
-        .await
-    {
+        .await;
+    crate::telemetry::record_http_call("crossref", &response);
+    let response = match response {
         Ok(r) => r,
         Err(_) => return None,
     };
 
     let json: serde_json::Value = match response.json().await {
         Ok(j) => j,
-        Err(_) => return None,
+        Err(e) => {
+            tracing::warn!(api = "crossref", error = %e, "Invalid JSON response");
+            return None;
+        }
     };
 
     let items = json.get("message")?.get("items")?.as_array()?;
@@ -503,28 +585,29 @@ pub async fn query_crossref_by_title(title: &str) -> Option<ExternalResult> {
     }
 
     let doi = item.get("DOI").and_then(|d| d.as_str())?;
-    query_crossref_api(doi).await
+    query_crossref_api(client, doi).await
 }
 
-/// Fetch a URL and extract paper metadata from HTML meta tags
-pub async fn fetch_and_extract_metadata(url: &str) -> Option<ExternalResult> {
-    // Validate URL for SSRF protection
-    if validate_url(url).is_err() {
-        return None;
-    }
-
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(15))
-        .user_agent("Mozilla/5.0 (compatible; NotesApp/1.0)")
-        .build()
-        .ok()?;
-
-    let response = client.get(url).send().await.ok()?;
-    let html = response.text().await.ok()?;
+/// Fetch a URL and extract paper metadata from HTML meta tags. The page
+/// itself goes through `UrlPolicy::get`, which pins each hop's addresses on
+/// its own client, and is revalidated against `cache` (the sled db) when
+/// given; `client` is for the CrossRef lookup.
+pub async fn fetch_and_extract_metadata(
+    client: &reqwest::Client,
+    cache: Option<&crate::store::Db>,
+    url: &str,
+) -> Option<ExternalResult> {
+    // Fetched through the Smart Add URL policy (SSRF protection), which
+    // validates the URL and every redirect
+    let page = page_cache::fetch(cache, &UrlPolicy::smart_add(), "url_metadata", url, Duration::from_secs(15), |r| {
+        r.header(reqwest::header::USER_AGENT, "Mozilla/5.0 (compatible; NotesApp/1.0)")
+    })
+    .await;
+    let html = page.ok()?.body?;
 
     // First, try to extract DOI from the page and use CrossRef
     if let Some(doi) = extract_doi_from_html(&html) {
-        if let Some(result) = query_crossref_api(&doi).await {
+        if let Some(result) = query_crossref_api(client, &doi).await {
             return Some(result);
         }
     }
@@ -629,7 +712,7 @@ fn extract_doi_from_html(html: &str) -> Option<String> {
             if let Some(m) = caps.get(1) {
                 return Some(
                     m.as_str()
-                        .trim_end_matches(|c| c == '.' || c == ',')
+                        .trim_end_matches(['.', ','])
                         .to_string(),
                 );
             }
@@ -639,7 +722,7 @@ fn extract_doi_from_html(html: &str) -> Option<String> {
     None
 }
 
-fn extract_meta_content(html: &str, names: &[&str]) -> Option<String> {
+pub fn extract_meta_content(html: &str, names: &[&str]) -> Option<String> {
     for name in names {
         // Try both name="X" content="Y" and content="Y" name="X" orders
         let patterns = [
@@ -699,7 +782,7 @@ fn extract_all_meta_content(html: &str, name: &str) -> Option<String> {
     }
 }
 
-fn extract_html_title(html: &str) -> Option<String> {
+pub fn extract_html_title(html: &str) -> Option<String> {
     let pattern = r"(?i)<title[^>]*>([^<]+)</title>";
     if let Ok(re) = Regex::new(pattern) {
         if let Some(caps) = re.captures(html) {
@@ -734,7 +817,7 @@ fn html_entity_decode(s: &str) -> String {
         .replace("&nbsp;", " ")
 }
 
-pub async fn query_claude_for_url(url: &str) -> Option<ExternalResult> {
+pub async fn query_claude_for_url(client: &reqwest::Client, url: &str) -> Option<ExternalResult> {
     let prompt = format!(
         "Extract paper/article metadata from this URL: {}\n\n\
         Return ONLY a JSON object with these fields (no other text):\n\
@@ -743,18 +826,7 @@ pub async fn query_claude_for_url(url: &str) -> Option<ExternalResult> {
         url
     );
 
-    let output = tokio::task::spawn_blocking(move || {
-        Command::new("claude").args(["-p", &prompt]).output()
-    })
-    .await
-    .ok()?
-    .ok()?;
-
-    if !output.status.success() {
-        return None;
-    }
-
-    let response = String::from_utf8_lossy(&output.stdout);
+    let response = crate::llm::complete(client, prompt, None).await.ok()?;
 
     // Try to extract JSON from response
     let json_start = response.find('{')?;
@@ -838,6 +910,138 @@ fn extract_all_xml_tags(xml: &str, tag: &str) -> Vec<String> {
     results
 }
 
+// ============================================================================
+// Lookup and Creation
+// ============================================================================
+
+/// Query the external source for `input_type`: arXiv, CrossRef or the page
+/// itself, falling back to Claude for URLs. Shared by the smart-add handler
+/// and `notes add`. `cache` is the sled db for the page cache; `notes add`
+/// goes without, as the server may hold the db.
+pub async fn lookup_external(
+    client: &reqwest::Client,
+    cache: Option<&crate::store::Db>,
+    input_type: &InputType,
+) -> Option<ExternalResult> {
+    match input_type {
+        InputType::ArxivUrl { arxiv_id } => {
+            let arxiv_id = arxiv_id.clone();
+            // Try arXiv API, fallback to Claude, then None
+            match query_arxiv_api(client, &arxiv_id).await {
+                Some(r) => Some(r),
+                None => {
+                    // Claude fallback is optional - don't fail if it's not available
+                    query_claude_for_url(client, &format!("https://arxiv.org/abs/{}", arxiv_id)).await
+                }
+            }
+        }
+        InputType::DoiUrl { doi } => {
+            let doi = doi.clone();
+            // Try CrossRef API, fallback to Claude
+            match query_crossref_api(client, &doi).await {
+                Some(r) => Some(r),
+                None => query_claude_for_url(client, &format!("https://doi.org/{}", doi)).await,
+            }
+        }
+        InputType::GenericUrl { url } => {
+            // Try to fetch and extract metadata from the page
+            match fetch_and_extract_metadata(client, cache, url).await {
+                Some(r) => Some(r),
+                None => query_claude_for_url(client, url).await,
+            }
+        }
+        InputType::PlainText { text } => {
+            // Try CrossRef title search
+            query_crossref_by_title(client, text).await
+        }
+    }
+}
+
+/// Write a new paper note from a smart-add create request and return its
+/// key. `filename` is relative to `notes_dir` and must not exist yet;
+/// `req.template` names a template in `templates_dir`; `taken` reports keys
+/// already in use.
+pub fn create_paper_note(
+    notes_dir: &Path,
+    templates_dir: &Path,
+    req: &SmartAddCreateRequest,
+    taken: impl Fn(&str) -> bool,
+) -> Result<String, String> {
+    // BibTeX is required and must be parseable
+    let bibtex = req.bibtex.trim();
+    if bibtex.is_empty() {
+        return Err("BibTeX is required".to_string());
+    }
+    let parsed = parse_bibtex(bibtex).ok_or_else(|| "Could not parse BibTeX entry".to_string())?;
+
+    let title = parsed.title.unwrap_or_else(|| parsed.cite_key.clone());
+    let filename = req.filename.trim();
+    let template = note_templates::load_optional(templates_dir, req.template.as_deref())?;
+
+    // Validate filename
+    if filename.is_empty() || !filename.ends_with(".md") {
+        return Err("Filename must end with .md".to_string());
+    }
+
+    // Check for path traversal: reject .., absolute paths, and null bytes
+    if filename.contains("..") || filename.starts_with('/') || filename.contains('\0') {
+        return Err("Invalid filename".to_string());
+    }
+
+    let file_path = notes_dir.join(filename);
+
+    // Validate the path stays within notes_dir
+    if validate_path_within(&notes_dir.to_path_buf(), &file_path).is_err() {
+        return Err("Invalid filename".to_string());
+    }
+
+    if file_path.exists() {
+        return Err(format!("A note with filename '{}' already exists", filename));
+    }
+
+    // Create parent directories if needed
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    // Build frontmatter — title and bibtex are the key fields;
+    // all other metadata (authors, year, venue) is derived from bibtex at read time
+    let key = new_note_key(taken);
+    let today = crate::timezone::today().format("%Y-%m-%d");
+    let mut frontmatter = new_note_frontmatter(&title, &key, crate::timezone::now());
+    frontmatter.push_str(&format!("date: {}\ntype: paper\nbibtex: |\n", today));
+    for line in bibtex.lines() {
+        frontmatter.push_str(&format!("  {}\n", line));
+    }
+    if let Some(ref arxiv_id) = req.arxiv_id {
+        if !arxiv_id.is_empty() {
+            frontmatter.push_str(&format!("arxiv: {}\n", arxiv_id));
+        }
+    }
+    if let Some(ref doi) = req.doi {
+        if !doi.is_empty() {
+            frontmatter.push_str(&format!("doi: {}\n", doi));
+        }
+    }
+
+    let content = match template {
+        Some(ref template) => {
+            let date = today.to_string();
+            let vars = TemplateVars {
+                title: &title,
+                date: &date,
+                bib_key: &parsed.cite_key,
+            };
+            note_templates::instantiate(&frontmatter, template, &vars)
+        }
+        None => frontmatter + "---\n\n## Summary\n\n## Key Contributions\n\n## Notes\n\n",
+    };
+
+    crate::encryption::write_file(&file_path, &content)?;
+
+    Ok(key)
+}
+
 // ============================================================================
 // Route Handlers
 // ============================================================================
@@ -876,38 +1080,7 @@ pub async fn smart_add_lookup(
     let local_match = search_local_for_match(&notes, &input, &input_type);
 
     // Query external APIs based on input type (with error handling)
-    let external_result = match &input_type {
-        InputType::ArxivUrl { arxiv_id } => {
-            let arxiv_id = arxiv_id.clone();
-            // Try arXiv API, fallback to Claude, then None
-            match query_arxiv_api(&arxiv_id).await {
-                Some(r) => Some(r),
-                None => {
-                    // Claude fallback is optional - don't fail if it's not available
-                    query_claude_for_url(&format!("https://arxiv.org/abs/{}", arxiv_id)).await
-                }
-            }
-        }
-        InputType::DoiUrl { doi } => {
-            let doi = doi.clone();
-            // Try CrossRef API, fallback to Claude
-            match query_crossref_api(&doi).await {
-                Some(r) => Some(r),
-                None => query_claude_for_url(&format!("https://doi.org/{}", doi)).await,
-            }
-        }
-        InputType::GenericUrl { url } => {
-            // Try to fetch and extract metadata from the page
-            match fetch_and_extract_metadata(url).await {
-                Some(r) => Some(r),
-                None => query_claude_for_url(url).await,
-            }
-        }
-        InputType::PlainText { text } => {
-            // Try CrossRef title search
-            query_crossref_by_title(text).await
-        }
-    };
+    let external_result = lookup_external(&state.http, Some(&state.db), &input_type).await;
 
     let input_type_str = match &input_type {
         InputType::ArxivUrl { .. } => "arxiv",
@@ -926,146 +1099,104 @@ pub async fn smart_add_lookup(
     axum::Json(result).into_response()
 }
 
-#[derive(Serialize)]
-pub struct SmartAddCreateResponse {
-    pub key: Option<String>,
-    pub error: Option<String>,
+/// Look up one identifier from pasted text. A paper that isn't in the
+/// notes under this identifier may still be there by title.
+async fn resolve_pasted(state: &AppState, notes: &[Note], id: InputType) -> PastedReference {
+    let external_result = lookup_external(&state.http, Some(&state.db), &id).await;
+    let (input_type, identifier) = match &id {
+        InputType::ArxivUrl { arxiv_id } => ("arxiv", arxiv_id.clone()),
+        InputType::DoiUrl { doi } => ("doi", doi.clone()),
+        InputType::GenericUrl { url } => ("url", url.clone()),
+        InputType::PlainText { text } => ("text", text.clone()),
+    };
+    let local_match = search_local_for_match(notes, &identifier, &id).or_else(|| {
+        let title = &external_result.as_ref()?.title;
+        search_local_for_match(notes, title, &InputType::PlainText { text: title.clone() })
+    });
+    PastedReference {
+        input_type: input_type.to_string(),
+        identifier,
+        local_match,
+        external_result,
+    }
 }
 
-pub async fn smart_add_create(
+/// `POST /api/smart-add/extract`: every DOI and arXiv ID in `{text}`,
+/// looked up, for the multi-select Smart Add dialog.
+pub async fn smart_add_extract(
     State(state): State<Arc<AppState>>,
     jar: CookieJar,
-    axum::Json(body): axum::Json<SmartAddCreateRequest>,
+    axum::Json(body): axum::Json<SmartExtractRequest>,
 ) -> Response {
-    if !is_logged_in(&jar, &state.db) {
-        return axum::Json(SmartAddCreateResponse {
-            key: None,
-            error: Some("Not logged in".to_string()),
+    let error = |e: &str| {
+        axum::Json(SmartExtractResult {
+            references: Vec::new(),
+            skipped: 0,
+            error: Some(e.to_string()),
         })
-        .into_response();
-    }
-
-    // BibTeX is required and must be parseable
-    let bibtex = body.bibtex.trim().to_string();
-    if bibtex.is_empty() {
-        return axum::Json(SmartAddCreateResponse {
-            key: None,
-            error: Some("BibTeX is required".to_string()),
-        })
-        .into_response();
-    }
-
-    let parsed = match parse_bibtex(&bibtex) {
-        Some(p) => p,
-        None => {
-            return axum::Json(SmartAddCreateResponse {
-                key: None,
-                error: Some("Could not parse BibTeX entry".to_string()),
-            })
-            .into_response();
-        }
+        .into_response()
     };
-
-    let title = parsed.title.unwrap_or_else(|| parsed.cite_key.clone());
-    let filename = body.filename.trim();
-
-    // Validate filename
-    if filename.is_empty() || !filename.ends_with(".md") {
-        return axum::Json(SmartAddCreateResponse {
-            key: None,
-            error: Some("Filename must end with .md".to_string()),
-        })
-        .into_response();
+    if !is_logged_in(&jar, &state.db) {
+        return error("Not logged in");
     }
 
-    // Check for path traversal: reject .., absolute paths, and null bytes
-    if filename.contains("..") || filename.starts_with('/') || filename.contains('\0') {
-        return axum::Json(SmartAddCreateResponse {
-            key: None,
-            error: Some("Invalid filename".to_string()),
-        })
-        .into_response();
+    let mut ids = extract_identifiers(&body.text);
+    if ids.is_empty() {
+        return error("No DOIs or arXiv IDs found");
     }
+    let skipped = ids.len().saturating_sub(MAX_EXTRACTED);
+    ids.truncate(MAX_EXTRACTED);
 
-    let file_path = state.notes_dir.join(filename);
+    let notes = state.load_notes();
+    let references = futures_util::stream::iter(ids)
+        .map(|id| resolve_pasted(&state, &notes, id))
+        .buffered(EXTRACT_CONCURRENCY)
+        .collect()
+        .await;
+    axum::Json(SmartExtractResult {
+        references,
+        skipped,
+        error: None,
+    })
+    .into_response()
+}
 
-    // Validate the path stays within notes_dir
-    if let Err(_) = validate_path_within(&state.notes_dir, &file_path) {
-        return axum::Json(SmartAddCreateResponse {
-            key: None,
-            error: Some("Invalid filename".to_string()),
-        })
-        .into_response();
-    }
+#[derive(Serialize)]
+pub struct SmartAddCreateResponse {
+    pub key: Option<String>,
+    pub error: Option<String>,
+}
 
-    // Check if file exists
-    if file_path.exists() {
+pub async fn smart_add_create(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<SmartAddCreateRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
         return axum::Json(SmartAddCreateResponse {
             key: None,
-            error: Some(format!(
-                "A note with filename '{}' already exists",
-                filename
-            )),
+            error: Some("Not logged in".to_string()),
         })
         .into_response();
     }
 
-    // Create parent directories if needed
-    if let Some(parent) = file_path.parent() {
-        if let Err(e) = fs::create_dir_all(parent) {
-            return axum::Json(SmartAddCreateResponse {
-                key: None,
-                error: Some(format!("Failed to create directory: {}", e)),
+    let notes_map = state.notes_map();
+    match create_paper_note(&state.notes_dir, &state.config.templates_dir, &body, |k| notes_map.contains_key(k)) {
+        Ok(key) => {
+            state.invalidate_notes_cache();
+            state.reindex_graph_note(&key);
+            axum::Json(SmartAddCreateResponse {
+                key: Some(key),
+                error: None,
             })
-            .into_response();
-        }
-    }
-
-    // Build frontmatter — title and bibtex are the key fields;
-    // all other metadata (authors, year, venue) is derived from bibtex at read time
-    let today = Utc::now().format("%Y-%m-%d");
-    let mut frontmatter = format!(
-        "---\ntitle: {}\ndate: {}\ntype: paper\nbibtex: |\n",
-        title, today
-    );
-    for line in bibtex.lines() {
-        frontmatter.push_str(&format!("  {}\n", line));
-    }
-    if let Some(ref arxiv_id) = body.arxiv_id {
-        if !arxiv_id.is_empty() {
-            frontmatter.push_str(&format!("arxiv: {}\n", arxiv_id));
-        }
-    }
-    if let Some(ref doi) = body.doi {
-        if !doi.is_empty() {
-            frontmatter.push_str(&format!("doi: {}\n", doi));
+            .into_response()
         }
-    }
-
-    frontmatter.push_str("---\n\n## Summary\n\n## Key Contributions\n\n## Notes\n\n");
-
-    // Write the file
-    if let Err(e) = fs::write(&file_path, &frontmatter) {
-        return axum::Json(SmartAddCreateResponse {
+        Err(e) => axum::Json(SmartAddCreateResponse {
             key: None,
-            error: Some(format!("Failed to create note: {}", e)),
+            error: Some(e),
         })
-        .into_response();
+        .into_response(),
     }
-
-    state.invalidate_notes_cache();
-
-    // Generate key for the new note
-    let relative_path = PathBuf::from(filename);
-    let key = generate_key(&relative_path);
-
-    state.reindex_graph_note(&key);
-
-    axum::Json(SmartAddCreateResponse {
-        key: Some(key),
-        error: None,
-    })
-    .into_response()
 }
 
 pub async fn quick_note_create(
@@ -1125,7 +1256,7 @@ pub async fn quick_note_create(
 
     let file_path = state.notes_dir.join(&filename);
 
-    if let Err(_) = validate_path_within(&state.notes_dir, &file_path) {
+    if validate_path_within(&state.notes_dir, &file_path).is_err() {
         return axum::Json(SmartAddCreateResponse {
             key: None,
             error: Some("Invalid filename".to_string()),
@@ -1155,24 +1286,44 @@ pub async fn quick_note_create(
         .date
         .as_deref()
         .filter(|d| !d.is_empty())
-        .unwrap_or(&Utc::now().format("%Y-%m-%d").to_string())
+        .unwrap_or(&crate::timezone::today().format("%Y-%m-%d").to_string())
         .to_string();
 
-    let frontmatter = format!("---\ntitle: {}\ndate: {}\n---\n\n", title, date);
+    let template = match note_templates::load_optional(&state.config.templates_dir, body.template.as_deref()) {
+        Ok(t) => t,
+        Err(e) => {
+            return axum::Json(SmartAddCreateResponse {
+                key: None,
+                error: Some(e),
+            })
+            .into_response();
+        }
+    };
+
+    let notes_map = state.notes_map();
+    let key = new_note_key(|k| notes_map.contains_key(k));
+    let frontmatter = new_note_frontmatter(&title, &key, crate::timezone::now()) + &format!("date: {}\n", date);
+    let frontmatter = match template {
+        Some(ref template) => {
+            let vars = TemplateVars {
+                title: &title,
+                date: &date,
+                bib_key: "",
+            };
+            note_templates::instantiate(&frontmatter, template, &vars)
+        }
+        None => frontmatter + "---\n\n",
+    };
 
-    if let Err(e) = fs::write(&file_path, &frontmatter) {
+    if let Err(e) = crate::encryption::write_file(&file_path, &frontmatter) {
         return axum::Json(SmartAddCreateResponse {
             key: None,
-            error: Some(format!("Failed to create note: {}", e)),
+            error: Some(e),
         })
         .into_response();
     }
 
     state.invalidate_notes_cache();
-
-    let relative_path = PathBuf::from(&filename);
-    let key = generate_key(&relative_path);
-
     state.reindex_graph_note(&key);
 
     axum::Json(SmartAddCreateResponse {
@@ -1257,10 +1408,10 @@ pub async fn smart_add_attach(
     };
 
     // Write the updated content
-    if let Err(e) = fs::write(&full_path, &new_content) {
+    if let Err(e) = crate::encryption::write_file(&full_path, &new_content) {
         return axum::Json(AttachSourceResponse {
             success: false,
-            error: Some(format!("Failed to write note: {}", e)),
+            error: Some(e),
         })
         .into_response();
     }
@@ -1268,6 +1419,15 @@ pub async fn smart_add_attach(
     state.invalidate_notes_cache();
     state.reindex_graph_note(&body.note_key);
 
+    let is_url = !matches!(body.source_type.as_str(), "arxiv" | "doi");
+    if is_url && state.config.links.wayback && body.identifier.starts_with("http") {
+        tokio::spawn(crate::wayback::archive_source(
+            state.clone(),
+            body.note_key.clone(),
+            body.identifier.clone(),
+        ));
+    }
+
     axum::Json(AttachSourceResponse {
         success: true,
         error: None,
@@ -1280,7 +1440,7 @@ pub async fn smart_add_attach(
 // ============================================================================
 
 /// Insert a text block before the closing `---` of frontmatter.
-fn insert_before_frontmatter_end(content: &str, block: &str) -> Option<String> {
+pub(crate) fn insert_before_frontmatter_end(content: &str, block: &str) -> Option<String> {
     let first = content.find("---")?;
     let second = content[first + 3..].find("---").map(|i| first + 3 + i)?;
     let mut new = content[..second].to_string();
@@ -1489,6 +1649,7 @@ pub async fn bib_import_execute(
     };
 
     // Process create items
+    let existing = state.notes_map();
     for item in &body.create {
         let bibtex = item.bibtex.trim();
         let filename = item.filename.trim();
@@ -1514,7 +1675,7 @@ pub async fn bib_import_execute(
         let title = parsed.title.unwrap_or_else(|| parsed.cite_key.clone());
         let file_path = state.notes_dir.join(filename);
 
-        if let Err(_) = validate_path_within(&state.notes_dir, &file_path) {
+        if validate_path_within(&state.notes_dir, &file_path).is_err() {
             result.errors.push(format!("Invalid filename: {}", filename));
             continue;
         }
@@ -1531,11 +1692,12 @@ pub async fn bib_import_execute(
             }
         }
 
-        let today = chrono::Utc::now().format("%Y-%m-%d");
-        let mut frontmatter = format!(
-            "---\ntitle: {}\ndate: {}\ntype: paper\nbibtex: |\n",
-            title, today
-        );
+        let key = new_note_key(|k| {
+            existing.contains_key(k) || result.created.iter().any(|c| c.key == k)
+        });
+        let today = crate::timezone::today().format("%Y-%m-%d");
+        let mut frontmatter = new_note_frontmatter(&title, &key, crate::timezone::now());
+        frontmatter.push_str(&format!("date: {}\ntype: paper\nbibtex: |\n", today));
         for line in bibtex.lines() {
             frontmatter.push_str(&format!("  {}\n", line));
         }
@@ -1549,14 +1711,11 @@ pub async fn bib_import_execute(
 
         frontmatter.push_str("---\n\n## Summary\n\n## Key Contributions\n\n## Notes\n\n");
 
-        if let Err(e) = fs::write(&file_path, &frontmatter) {
-            result.errors.push(format!("Failed to write {}: {}", filename, e));
+        if let Err(e) = crate::encryption::write_file(&file_path, &frontmatter) {
+            result.errors.push(format!("{}: {}", filename, e));
             continue;
         }
 
-        let relative_path = PathBuf::from(filename);
-        let key = generate_key(&relative_path);
-
         result.created.push(BibImportCreatedNote {
             key,
             filename: filename.to_string(),
@@ -1598,8 +1757,8 @@ pub async fn bib_import_execute(
             }
         };
 
-        if let Err(e) = fs::write(&full_path, &new_content) {
-            result.errors.push(format!("Failed to update {}: {}", note.title, e));
+        if let Err(e) = crate::encryption::write_file(&full_path, &new_content) {
+            result.errors.push(format!("{}: {}", note.title, e));
             continue;
         }
 
@@ -1621,3 +1780,63 @@ pub async fn bib_import_execute(
 
     axum::Json(result).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_identifiers() {
+        let text = "Gradual typing [1] builds on blame (doi:10.1145/1480881.1480903). See also \
+                    https://arxiv.org/abs/2301.00001v2, arXiv:2301.00001, https://doi.org/10.48550/arXiv.2402.12345 \
+                    and 10.1016/0022-0000(78)90014-4; old-style arxiv.org/abs/hep-th/9901001. Version 2023.1234 is not one.";
+        let ids: Vec<String> = extract_identifiers(text)
+            .into_iter()
+            .map(|id| match id {
+                InputType::ArxivUrl { arxiv_id } => format!("arxiv {}", arxiv_id),
+                InputType::DoiUrl { doi } => format!("doi {}", doi),
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![
+                "doi 10.1145/1480881.1480903",
+                "arxiv 2301.00001",
+                "arxiv 2402.12345",
+                "doi 10.1016/0022-0000(78)90014-4",
+                "arxiv hep-th/9901001",
+            ]
+        );
+        assert!(extract_identifiers("nothing to see here").is_empty());
+    }
+
+    #[test]
+    fn test_paper_versions() {
+        let content = "---\ntitle: Gradual Datalog\ntype: paper\narxiv: 2301.00001v2\nversions:\n  - label: preprint\n    arxiv: arXiv:2301.00001v1\n  - doi: 10.1145/3591234\n    label: camera-ready\n  - https://example.org/tr.pdf\n  - label: no source\n---\n";
+        let mut note = crate::notes::note_from_content("papers/gd.md".into(), content.to_string(), chrono::Utc::now());
+        let NoteType::Paper(ref paper) = note.note_type else { panic!("not a paper") };
+        let versions: Vec<(Option<&str>, &str, &str)> = paper
+            .versions
+            .iter()
+            .map(|v| (v.label.as_deref(), v.source.source_type.as_str(), v.source.identifier.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                (Some("preprint"), "arxiv", "arXiv:2301.00001v1"),
+                (Some("camera-ready"), "doi", "10.1145/3591234"),
+                (None, "url", "https://example.org/tr.pdf"),
+            ]
+        );
+
+        // Match on the parsed sources, not the file text
+        note.full_file_content.clear();
+        let notes = vec![note];
+        for input in ["https://arxiv.org/abs/2301.00001v3", "https://doi.org/10.1145/3591234", "https://example.org/tr.pdf"] {
+            let found = search_local_for_match(&notes, input, &detect_input_type(input));
+            assert_eq!(found.map(|m| m.key), Some(notes[0].key.clone()), "{}", input);
+        }
+        assert!(search_local_for_match(&notes, "2301.00002", &detect_input_type("2301.00002")).is_none());
+    }
+}
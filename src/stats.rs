@@ -0,0 +1,322 @@
+//! Corpus statistics, note view counts and the `/stats` page.
+//!
+//! `/stats` shows totals, notes created per month, papers per year and
+//! words written per week (from the `habits` edit log), all from data
+//! already loaded. Every view of a note also bumps its count in the
+//! `view_counts` sled tree (note key → big-endian u64); logged-in views
+//! are left out unless `[stats] count_logged_in`, so the counts show what
+//! visitors read. The page lists the most-viewed notes and the ones never
+//! viewed.
+//!
+//! `length_label` (words and reading time) is the meta block's Length row.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::{Datelike, Days, NaiveDate};
+
+use crate::auth::is_logged_in;
+use crate::habits::{load_days, word_count, DayActivity};
+use crate::models::{Note, NoteType};
+use crate::notes::html_escape;
+use crate::templates::base_html;
+use crate::AppState;
+
+const COUNTS_TREE: &str = "view_counts";
+
+/// Notes listed under "Most viewed".
+const MOST_VIEWED: usize = 20;
+
+/// Reading speed behind the meta block's reading time.
+const WORDS_PER_MINUTE: u32 = 230;
+
+/// Weeks of "Words written" shown.
+const SHOWN_WEEKS: u64 = 12;
+
+/// Minutes to read `words`, at least one.
+pub fn reading_minutes(words: u32) -> u32 {
+    words.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
+/// The meta block's length line, e.g. `1200 words · 6 min read`.
+pub fn length_label(note: &Note) -> String {
+    let words = word_count(note);
+    format!(
+        "{} {} · {} min read",
+        words,
+        if words == 1 { "word" } else { "words" },
+        reading_minutes(words)
+    )
+}
+
+/// Totals and distributions over the corpus.
+#[derive(Debug, Default, PartialEq)]
+pub struct CorpusStats {
+    pub notes: usize,
+    pub papers: usize,
+    pub hidden: usize,
+    pub words: u64,
+    /// Notes by the `(year, month)` of `created:`.
+    pub per_month: BTreeMap<(i32, u32), usize>,
+    /// Papers by their BibTeX year.
+    pub papers_per_year: BTreeMap<i32, usize>,
+}
+
+pub fn corpus_stats(notes: &[Note]) -> CorpusStats {
+    let mut stats = CorpusStats { notes: notes.len(), ..Default::default() };
+    for note in notes {
+        stats.words += u64::from(word_count(note));
+        if note.hidden {
+            stats.hidden += 1;
+        }
+        if let Some(created) = note.created {
+            *stats.per_month.entry((created.year(), created.month())).or_default() += 1;
+        }
+        if let NoteType::Paper(ref paper) = note.note_type {
+            stats.papers += 1;
+            if let Some(year) = paper.effective_metadata(&note.title).year {
+                *stats.papers_per_year.entry(year).or_default() += 1;
+            }
+        }
+    }
+    stats
+}
+
+/// Words written in each of the `weeks` weeks (Monday to Sunday) up to the
+/// one with `today`, oldest first, keyed by the week's Monday.
+pub fn words_per_week(days: &BTreeMap<NaiveDate, DayActivity>, today: NaiveDate, weeks: u64) -> Vec<(NaiveDate, u32)> {
+    let monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+    (0..weeks)
+        .rev()
+        .map(|back| {
+            let start = monday - Days::new(7 * back);
+            let words = days.range(start..start + Days::new(7)).map(|(_, d)| d.words).sum();
+            (start, words)
+        })
+        .collect()
+}
+
+/// A table of labelled counts, each with a bar scaled to the largest.
+fn bar_table(heading: &str, label: &str, rows: &[(String, u64)]) -> String {
+    let max = rows.iter().map(|(_, n)| *n).max().unwrap_or(0).max(1);
+    let mut html = format!(
+        r#"<h3>{}</h3><table class="time-table stats-table"><tr><th>{}</th><th></th><th>Count</th></tr>"#,
+        heading, label
+    );
+    for (name, n) in rows {
+        html.push_str(&format!(
+            r#"<tr><td>{}</td><td class="stats-bar-cell"><div class="stats-bar" style="width: {}%"></div></td><td>{}</td></tr>"#,
+            html_escape(name),
+            n * 100 / max,
+            n
+        ));
+    }
+    html.push_str("</table>");
+    html
+}
+
+/// The corpus sections of `/stats`.
+fn corpus_html(stats: &CorpusStats, weeks: &[(NaiveDate, u32)]) -> String {
+    let mut html = format!(
+        r#"<h2>Corpus</h2>
+        <p class="time-breadcrumb">{} notes ({} papers, {} hidden) · {} words · {} hours of reading</p>"#,
+        stats.notes,
+        stats.papers,
+        stats.hidden,
+        stats.words,
+        stats.words / u64::from(WORDS_PER_MINUTE) / 60
+    );
+    let weeks: Vec<(String, u64)> =
+        weeks.iter().map(|(monday, words)| (format!("Week of {}", monday.format("%Y-%m-%d")), u64::from(*words))).collect();
+    html.push_str(&bar_table("Words written per week", "Week", &weeks));
+    if !stats.per_month.is_empty() {
+        let months: Vec<(String, u64)> = stats
+            .per_month
+            .iter()
+            .rev()
+            .map(|((y, m), n)| (format!("{}-{:02}", y, m), *n as u64))
+            .collect();
+        html.push_str(&bar_table("Notes created per month", "Month", &months));
+    }
+    if !stats.papers_per_year.is_empty() {
+        let years: Vec<(String, u64)> =
+            stats.papers_per_year.iter().rev().map(|(y, n)| (y.to_string(), *n as u64)).collect();
+        html.push_str(&bar_table("Papers per year", "Year", &years));
+    }
+    html
+}
+
+fn decode(value: &[u8]) -> u64 {
+    value.try_into().map(u64::from_be_bytes).unwrap_or(0)
+}
+
+/// Add one view of `key`.
+pub fn record_view(db: &crate::store::Db, key: &str) -> Result<(), String> {
+    let tree = db.open_tree(COUNTS_TREE).map_err(|e| e.to_string())?;
+    tree.update_and_fetch(key.as_bytes(), |old| {
+        Some((old.map(decode).unwrap_or(0) + 1).to_be_bytes().to_vec())
+    })
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// View counts by note key.
+pub fn view_counts(db: &crate::store::Db) -> HashMap<String, u64> {
+    let Ok(tree) = db.open_tree(COUNTS_TREE) else {
+        return HashMap::new();
+    };
+    tree.iter()
+        .filter_map(|item| {
+            let (key, value) = item.ok()?;
+            Some((String::from_utf8(key.to_vec()).ok()?, decode(&value)))
+        })
+        .collect()
+}
+
+/// The viewed notes, most views first, and the notes never viewed, most
+/// recently modified first. Counts of deleted notes are left out.
+pub fn rank<'a>(notes: &'a HashMap<String, Note>, counts: &HashMap<String, u64>) -> (Vec<(&'a Note, u64)>, Vec<&'a Note>) {
+    let mut viewed: Vec<(&Note, u64)> = Vec::new();
+    let mut never: Vec<&Note> = Vec::new();
+    for note in notes.values() {
+        match counts.get(&note.key) {
+            Some(&count) if count > 0 => viewed.push((note, count)),
+            _ => never.push(note),
+        }
+    }
+    viewed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.title.cmp(&b.0.title)));
+    never.sort_by(|a, b| b.modified.cmp(&a.modified).then_with(|| a.key.cmp(&b.key)));
+    (viewed, never)
+}
+
+fn note_link(note: &Note) -> String {
+    format!(r#"<a href="/note/{}">{}</a>"#, note.key, html_escape(&note.title))
+}
+
+/// The view count sections of `/stats`.
+fn views_html(notes: &HashMap<String, Note>, counts: &HashMap<String, u64>, count_logged_in: bool) -> String {
+    let (viewed, never) = rank(notes, counts);
+    let total: u64 = viewed.iter().map(|(_, c)| c).sum();
+    let whose = if count_logged_in { "all views" } else { "views while not logged in" };
+    let mut html = format!(
+        r#"<h2>Views</h2>
+        <p class="time-breadcrumb">{} {} of {} notes ({}).</p>"#,
+        total,
+        if total == 1 { "view" } else { "views" },
+        viewed.len(),
+        whose
+    );
+
+    if !viewed.is_empty() {
+        html.push_str(r#"<h3>Most viewed</h3><table class="time-table"><tr><th>Note</th><th>Views</th></tr>"#);
+        for (note, count) in viewed.iter().take(MOST_VIEWED) {
+            html.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>", note_link(note), count));
+        }
+        html.push_str("</table>");
+    }
+
+    if !never.is_empty() {
+        let links: Vec<String> = never.iter().map(|n| note_link(n)).collect();
+        html.push_str(&format!(
+            "<h3>Never viewed ({})</h3><p>{}</p>",
+            never.len(),
+            links.join(" &middot; ")
+        ));
+    }
+    html
+}
+
+pub async fn stats_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+    let all = state.load_notes();
+    let weeks = words_per_week(&load_days(&state.db), crate::timezone::today(), SHOWN_WEEKS);
+    let notes: HashMap<String, Note> = all.iter().map(|n| (n.key.clone(), n.clone())).collect();
+    let counts = view_counts(&state.db);
+    let html = format!(
+        "<h1>Stats</h1>{}{}",
+        corpus_html(&corpus_stats(&all), &weeks),
+        views_html(&notes, &counts, state.config.stats.count_logged_in)
+    );
+    Html(base_html("Stats", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::NoteType;
+    use std::path::PathBuf;
+
+    fn note(key: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: format!("Note {}", key),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_view_counts() {
+        let db = crate::store::Db::temporary();
+        for key in ["a", "b", "a", "gone", "a"] {
+            record_view(&db, key).unwrap();
+        }
+        let counts = view_counts(&db);
+        assert_eq!(counts["a"], 3);
+        assert_eq!(counts["b"], 1);
+
+        let notes: HashMap<String, Note> = ["a", "b", "c"].into_iter().map(|k| (k.to_string(), note(k))).collect();
+        let (viewed, never) = rank(&notes, &counts);
+        let viewed: Vec<(&str, u64)> = viewed.iter().map(|(n, c)| (n.key.as_str(), *c)).collect();
+        assert_eq!(viewed, [("a", 3), ("b", 1)]);
+        assert_eq!(never.iter().map(|n| n.key.as_str()).collect::<Vec<_>>(), ["c"]);
+
+        let html = views_html(&notes, &counts, false);
+        assert!(html.contains("4 views of 2 notes (views while not logged in)"));
+        assert!(html.contains(r#"<h3>Never viewed (1)</h3><p><a href="/note/c">Note c</a></p>"#));
+    }
+
+    #[test]
+    fn test_corpus_stats() {
+        let mut a = note("a");
+        a.raw_content = "one two three".to_string();
+        a.created = NaiveDate::from_ymd_opt(2026, 9, 3);
+        let mut b = note("b");
+        b.created = NaiveDate::from_ymd_opt(2026, 9, 20);
+        b.hidden = true;
+        let mut p = note("p");
+        p.note_type = NoteType::Paper(crate::models::PaperMeta {
+            bibtex_entries: vec!["@article{x2019,\n  title={X},\n  year={2019}\n}".to_string()],
+            canonical_key: None,
+            sources: vec![],
+            versions: vec![],
+        });
+        let stats = corpus_stats(&[a.clone(), b, p]);
+        assert_eq!((stats.notes, stats.papers, stats.hidden, stats.words), (3, 1, 1, 3));
+        assert_eq!(stats.per_month, BTreeMap::from([((2026, 9), 2)]));
+        assert_eq!(stats.papers_per_year, BTreeMap::from([(2019, 1)]));
+
+        assert_eq!(length_label(&a), "3 words · 1 min read");
+        assert_eq!(reading_minutes(461), 3);
+
+        let day = |d: u32, words: u32| {
+            (NaiveDate::from_ymd_opt(2026, 10, d).unwrap(), DayActivity { words, notes: vec![] })
+        };
+        // Saturday 2026-10-17; its week starts Monday the 12th
+        let days = BTreeMap::from([day(4, 50), day(5, 10), day(12, 7), day(17, 3)]);
+        let weeks = words_per_week(&days, NaiveDate::from_ymd_opt(2026, 10, 17).unwrap(), 3);
+        let expected: Vec<(NaiveDate, u32)> = [(28, 9, 50), (5, 10, 10), (12, 10, 10)]
+            .iter()
+            .map(|&(d, m, w)| (NaiveDate::from_ymd_opt(2026, m, d).unwrap(), w))
+            .collect();
+        assert_eq!(weeks, expected);
+    }
+}
@@ -0,0 +1,521 @@
+//! The key-value store behind sessions, caches, the graph index and every
+//! other piece of server state.
+//!
+//! Code uses `Db` and `Tree`, which mirror the parts of sled's API the app
+//! needs: named trees of byte keys and values kept in key order, with prefix
+//! scans and atomic read-modify-write. Behind them is a `KvStore`: sled (the
+//! default), or SQLite (`db_backend = "sqlite"`, in builds with the
+//! `sqlite` feature), which keeps every tree in one table of
+//! `notes.sqlite3` inside `db_path`. `notes migrate-db` copies
+//! one into the other.
+//!
+//! SQLite runs in WAL mode with one `kv(tree, key, value)` table, and
+//! updates run in `BEGIN IMMEDIATE` transactions. `Db::temporary` gives
+//! tests a throwaway store.
+
+use std::fmt;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::DbBackend;
+
+/// File the SQLite backend keeps in `db_path`.
+pub const SQLITE_FILE: &str = "notes.sqlite3";
+
+/// sled's name for the tree `Db` itself reads and writes.
+pub const DEFAULT_TREE: &[u8] = b"__sled__default";
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<tokio::task::JoinError> for Error {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Error(e.to_string())
+    }
+}
+
+impl From<sled::Error> for Error {
+    fn from(e: sled::Error) -> Self {
+        Error(e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// One key/value pair.
+pub type Entry = (Vec<u8>, Vec<u8>);
+
+/// A database of named trees.
+pub trait KvStore: Send + Sync {
+    fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>>;
+    /// Every tree ever opened, including the default one.
+    fn tree_names(&self) -> Vec<Vec<u8>>;
+    fn flush(&self) -> Result<()>;
+    fn size_on_disk(&self) -> Result<u64>;
+}
+
+/// One tree: byte keys to byte values, in key order.
+pub trait KvTree: Send + Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    /// Returns the previous value.
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>>;
+    /// Returns the previous value.
+    fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    /// Entries whose keys start with `prefix` (all for an empty one), in
+    /// key order.
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<Entry>>;
+    fn clear(&self) -> Result<()>;
+    /// Atomically replace the value with `f(old)`, removing it on `None`.
+    /// Returns the old and new values.
+    #[allow(clippy::type_complexity)]
+    fn update(
+        &self,
+        key: &[u8],
+        f: &mut dyn FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)>;
+}
+
+/// An open database; derefs to its default tree, like `sled::Db`.
+#[derive(Clone)]
+pub struct Db {
+    store: Arc<dyn KvStore>,
+    default: Tree,
+}
+
+/// A tree of a `Db`.
+#[derive(Clone)]
+pub struct Tree(Arc<dyn KvTree>);
+
+/// Open the database at `path` with `backend`.
+pub fn open(backend: DbBackend, path: &Path) -> Result<Db> {
+    match backend {
+        DbBackend::Sled => Db::new(Arc::new(SledStore(sled::open(path)?))),
+        #[cfg(feature = "sqlite")]
+        DbBackend::Sqlite => {
+            std::fs::create_dir_all(path).map_err(|e| Error(format!("{}: {}", path.display(), e)))?;
+            Db::new(Arc::new(sqlite::SqliteStore::open(&path.join(SQLITE_FILE))?))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        DbBackend::Sqlite => Err(Error("This build has no SQLite support (build with --features sqlite)".to_string())),
+    }
+}
+
+impl Db {
+    pub fn new(store: Arc<dyn KvStore>) -> Result<Self> {
+        let default = Tree(store.open_tree(DEFAULT_TREE)?);
+        Ok(Db { store, default })
+    }
+
+    /// A throwaway in-memory database.
+    pub fn temporary() -> Self {
+        let sled = sled::Config::new().temporary(true).open().expect("temporary sled database");
+        Db::new(Arc::new(SledStore(sled))).expect("temporary sled database")
+    }
+
+    pub fn open_tree<N: AsRef<[u8]>>(&self, name: N) -> Result<Tree> {
+        Ok(Tree(self.store.open_tree(name.as_ref())?))
+    }
+
+    pub fn tree_names(&self) -> Vec<Vec<u8>> {
+        self.store.tree_names()
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.store.flush()
+    }
+
+    pub fn size_on_disk(&self) -> Result<u64> {
+        self.store.size_on_disk()
+    }
+}
+
+impl Deref for Db {
+    type Target = Tree;
+
+    fn deref(&self) -> &Tree {
+        &self.default
+    }
+}
+
+impl Tree {
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.0.get(key.as_ref())
+    }
+
+    pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> Result<Option<Vec<u8>>> {
+        self.0.insert(key.as_ref(), value.as_ref())
+    }
+
+    pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Vec<u8>>> {
+        self.0.remove(key.as_ref())
+    }
+
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// Every entry, in key order.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Entry>> {
+        self.scan_prefix(b"")
+    }
+
+    pub fn scan_prefix<P: AsRef<[u8]>>(&self, prefix: P) -> impl Iterator<Item = Result<Entry>> {
+        let (entries, error) = match self.0.scan_prefix(prefix.as_ref()) {
+            Ok(entries) => (entries, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        error.map(Err).into_iter().chain(entries.into_iter().map(Ok))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.scan_prefix(&[]).map_or(0, |entries| entries.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        self.0.clear()
+    }
+
+    /// Set the value to `f(old)` and return the new value.
+    pub fn update_and_fetch<K, V, F>(&self, key: K, mut f: F) -> Result<Option<Vec<u8>>>
+    where
+        K: AsRef<[u8]>,
+        V: Into<Vec<u8>>,
+        F: FnMut(Option<&[u8]>) -> Option<V>,
+    {
+        Ok(self.0.update(key.as_ref(), &mut |old| f(old).map(Into::into))?.1)
+    }
+
+    /// Set the value to `f(old)` and return the old value.
+    pub fn fetch_and_update<K, V, F>(&self, key: K, mut f: F) -> Result<Option<Vec<u8>>>
+    where
+        K: AsRef<[u8]>,
+        V: Into<Vec<u8>>,
+        F: FnMut(Option<&[u8]>) -> Option<V>,
+    {
+        Ok(self.0.update(key.as_ref(), &mut |old| f(old).map(Into::into))?.0)
+    }
+}
+
+/// Copy every tree of `from` into `to`, which should be empty. Returns the
+/// number of entries copied.
+pub fn copy_all(from: &Db, to: &Db) -> Result<usize> {
+    let mut copied = 0;
+    for name in from.tree_names() {
+        let source = from.open_tree(&name)?;
+        let target = to.open_tree(&name)?;
+        for entry in source.iter() {
+            let (key, value) = entry?;
+            target.insert(key, value)?;
+            copied += 1;
+        }
+    }
+    to.flush()?;
+    Ok(copied)
+}
+
+// ============================================================================
+// sled
+// ============================================================================
+
+struct SledStore(sled::Db);
+
+struct SledTree(sled::Tree);
+
+impl KvStore for SledStore {
+    fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>> {
+        Ok(Arc::new(SledTree(self.0.open_tree(name)?)))
+    }
+
+    fn tree_names(&self) -> Vec<Vec<u8>> {
+        self.0.tree_names().into_iter().map(|n| n.to_vec()).collect()
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.0.flush()?;
+        Ok(())
+    }
+
+    fn size_on_disk(&self) -> Result<u64> {
+        Ok(self.0.size_on_disk()?)
+    }
+}
+
+impl KvTree for SledTree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.get(key)?.map(|v| v.to_vec()))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.insert(key, value)?.map(|v| v.to_vec()))
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.remove(key)?.map(|v| v.to_vec()))
+    }
+
+    fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<Entry>> {
+        self.0
+            .scan_prefix(prefix)
+            .map(|item| {
+                let (key, value) = item?;
+                Ok((key.to_vec(), value.to_vec()))
+            })
+            .collect()
+    }
+
+    fn clear(&self) -> Result<()> {
+        Ok(self.0.clear()?)
+    }
+
+    fn update(
+        &self,
+        key: &[u8],
+        f: &mut dyn FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+    ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)> {
+        let mut new = None;
+        let old = self.0.fetch_and_update(key, |old| {
+            new = f(old);
+            new.clone()
+        })?;
+        Ok((old.map(|v| v.to_vec()), new))
+    }
+}
+
+// ============================================================================
+// SQLite
+// ============================================================================
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+
+    use super::{Entry, Error, KvStore, KvTree, Result};
+
+    impl From<rusqlite::Error> for Error {
+        fn from(e: rusqlite::Error) -> Self {
+            Error(format!("SQLite: {}", e))
+        }
+    }
+
+    /// Every tree in one `kv` table; `trees` remembers names opened while
+    /// empty, as sled does.
+    pub(super) struct SqliteStore {
+        conn: Arc<Mutex<Connection>>,
+        path: PathBuf,
+    }
+
+    struct SqliteTree {
+        conn: Arc<Mutex<Connection>>,
+        name: Vec<u8>,
+    }
+
+    impl SqliteStore {
+        pub(super) fn open(path: &Path) -> Result<Self> {
+            let conn = Connection::open(path)?;
+            conn.busy_timeout(std::time::Duration::from_secs(5))?;
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS trees (name BLOB PRIMARY KEY) WITHOUT ROWID;
+                 CREATE TABLE IF NOT EXISTS kv (tree BLOB NOT NULL, key BLOB NOT NULL, value BLOB NOT NULL,
+                     PRIMARY KEY (tree, key)) WITHOUT ROWID;",
+            )?;
+            Ok(SqliteStore { conn: Arc::new(Mutex::new(conn)), path: path.to_path_buf() })
+        }
+    }
+
+    fn lock(conn: &Mutex<Connection>) -> MutexGuard<'_, Connection> {
+        conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The smallest key greater than every key starting with `prefix`, if any.
+    fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+        let mut end = prefix.to_vec();
+        while let Some(last) = end.pop() {
+            if last < 0xff {
+                end.push(last + 1);
+                return Some(end);
+            }
+        }
+        None
+    }
+
+    fn value(conn: &Connection, tree: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(conn
+            .query_row("SELECT value FROM kv WHERE tree = ?1 AND key = ?2", params![tree, key], |row| row.get(0))
+            .optional()?)
+    }
+
+    fn set(conn: &Connection, tree: &[u8], key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        match value {
+            Some(value) => conn.execute(
+                "INSERT INTO kv (tree, key, value) VALUES (?1, ?2, ?3) \
+                 ON CONFLICT (tree, key) DO UPDATE SET value = excluded.value",
+                params![tree, key, value],
+            )?,
+            None => conn.execute("DELETE FROM kv WHERE tree = ?1 AND key = ?2", params![tree, key])?,
+        };
+        Ok(())
+    }
+
+    impl SqliteTree {
+        /// Run `f` in a `BEGIN IMMEDIATE` transaction.
+        fn transaction<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+            let mut conn = lock(&self.conn);
+            let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+            let value = f(&tx)?;
+            tx.commit()?;
+            Ok(value)
+        }
+    }
+
+    impl KvStore for SqliteStore {
+        fn open_tree(&self, name: &[u8]) -> Result<Arc<dyn KvTree>> {
+            lock(&self.conn).execute("INSERT OR IGNORE INTO trees (name) VALUES (?1)", [name])?;
+            Ok(Arc::new(SqliteTree { conn: self.conn.clone(), name: name.to_vec() }))
+        }
+
+        fn tree_names(&self) -> Vec<Vec<u8>> {
+            let conn = lock(&self.conn);
+            let names = conn.prepare("SELECT name FROM trees ORDER BY name").and_then(|mut stmt| {
+                stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<Vec<u8>>>>()
+            });
+            names.unwrap_or_default()
+        }
+
+        fn flush(&self) -> Result<()> {
+            // Committed transactions are already durable in the WAL
+            Ok(())
+        }
+
+        fn size_on_disk(&self) -> Result<u64> {
+            let mut wal = self.path.clone().into_os_string();
+            wal.push("-wal");
+            let size = |p: &Path| std::fs::metadata(p).map_or(0, |m| m.len());
+            Ok(size(&self.path) + size(Path::new(&wal)))
+        }
+    }
+
+    impl KvTree for SqliteTree {
+        fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            value(&lock(&self.conn), &self.name, key)
+        }
+
+        fn insert(&self, key: &[u8], new: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.transaction(|conn| {
+                let old = value(conn, &self.name, key)?;
+                set(conn, &self.name, key, Some(new))?;
+                Ok(old)
+            })
+        }
+
+        fn remove(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+            self.transaction(|conn| {
+                let old = value(conn, &self.name, key)?;
+                set(conn, &self.name, key, None)?;
+                Ok(old)
+            })
+        }
+
+        fn scan_prefix(&self, prefix: &[u8]) -> Result<Vec<Entry>> {
+            let conn = lock(&self.conn);
+            let row = |row: &rusqlite::Row<'_>| Ok((row.get(0)?, row.get(1)?));
+            let entries = match prefix_end(prefix) {
+                Some(end) => conn
+                    .prepare("SELECT key, value FROM kv WHERE tree = ?1 AND key >= ?2 AND key < ?3 ORDER BY key")?
+                    .query_map(params![self.name, prefix, end], row)?
+                    .collect::<rusqlite::Result<_>>()?,
+                None => conn
+                    .prepare("SELECT key, value FROM kv WHERE tree = ?1 AND key >= ?2 ORDER BY key")?
+                    .query_map(params![self.name, prefix], row)?
+                    .collect::<rusqlite::Result<_>>()?,
+            };
+            Ok(entries)
+        }
+
+        fn clear(&self) -> Result<()> {
+            lock(&self.conn).execute("DELETE FROM kv WHERE tree = ?1", [&self.name])?;
+            Ok(())
+        }
+
+        fn update(
+            &self,
+            key: &[u8],
+            f: &mut dyn FnMut(Option<&[u8]>) -> Option<Vec<u8>>,
+        ) -> Result<(Option<Vec<u8>>, Option<Vec<u8>>)> {
+            self.transaction(|conn| {
+                let old = value(conn, &self.name, key)?;
+                let new = f(old.as_deref());
+                set(conn, &self.name, key, new.as_deref())?;
+                Ok((old, new))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(db: &Db) {
+        let tree = db.open_tree("t").unwrap();
+        assert!(tree.is_empty());
+        assert_eq!(tree.insert(b"b:2", b"two").unwrap(), None);
+        assert_eq!(tree.insert("b:2", "deux").unwrap(), Some(b"two".to_vec()));
+        tree.insert(b"a", b"").unwrap();
+        tree.insert([b'b', b':', 0xff], b"max").unwrap();
+        tree.insert(b"c", b"3").unwrap();
+        assert_eq!(tree.get(b"a").unwrap(), Some(Vec::new()));
+        assert!(tree.contains_key("c").unwrap());
+
+        let keys = |entries: Vec<Result<Entry>>| -> Vec<Vec<u8>> {
+            entries.into_iter().map(|e| e.unwrap().0).collect()
+        };
+        assert_eq!(keys(tree.iter().collect()), [b"a".to_vec(), b"b:2".to_vec(), vec![b'b', b':', 0xff], b"c".to_vec()]);
+        assert_eq!(keys(tree.scan_prefix("b:").collect()), [b"b:2".to_vec(), vec![b'b', b':', 0xff]]);
+
+        let count = |old: Option<&[u8]>| Some(vec![old.map_or(0, |v| v[0]) + 1]);
+        assert_eq!(tree.update_and_fetch("n", count).unwrap(), Some(vec![1]));
+        assert_eq!(tree.fetch_and_update("n", count).unwrap(), Some(vec![1]));
+        assert_eq!(tree.get("n").unwrap(), Some(vec![2]));
+        tree.update_and_fetch("n", |_| None::<Vec<u8>>).unwrap();
+        assert_eq!(tree.remove("n").unwrap(), None);
+        assert_eq!(tree.remove("c").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(tree.len(), 3);
+
+        db.insert("session", "1").unwrap();
+        assert!(db.tree_names().contains(&b"t".to_vec()));
+        let copy = Db::temporary();
+        assert_eq!(copy_all(db, &copy).unwrap(), 4);
+        assert_eq!(copy.open_tree("t").unwrap().get("b:2").unwrap(), Some(b"deux".to_vec()));
+        tree.clear().unwrap();
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_backends() {
+        exercise(&Db::temporary());
+        if cfg!(not(feature = "sqlite")) {
+            assert!(open(DbBackend::Sqlite, Path::new("unused")).is_err());
+            return;
+        }
+        let dir = crate::test_util::TempDir::new("store");
+        exercise(&open(DbBackend::Sqlite, &dir).unwrap());
+    }
+}
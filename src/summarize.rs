@@ -0,0 +1,327 @@
+//! AI summaries.
+//!
+//! `POST /api/note/{key}/summarize` has the language model summarize a
+//! note's body or the text of its PDF, and writes the result into the note
+//! as a `## AI Summary` section at the top of the body, between HTML comment
+//! markers so a new summary can replace it. The section says where it came
+//! from and is ordinary markdown otherwise, so it can be edited or deleted
+//! like the rest of the note.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::citations::extract_pdf_text;
+use crate::handlers::rewrite_note_file;
+use crate::notes::body_line_offset;
+use crate::AppState;
+
+pub const SUMMARY_HEADING: &str = "## AI Summary";
+const BEGIN_MARKER: &str = "<!-- BEGIN AI SUMMARY -->";
+const END_MARKER: &str = "<!-- END AI SUMMARY -->";
+
+/// Characters of input sent to the model; longer text is cut off.
+const MAX_INPUT_CHARS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SummarySource {
+    #[default]
+    Note,
+    Pdf,
+}
+
+impl SummarySource {
+    fn label(self) -> &'static str {
+        match self {
+            SummarySource::Note => "note",
+            SummarySource::Pdf => "PDF",
+        }
+    }
+}
+
+/// Lines `[start, end)` of a note file holding its AI summary section,
+/// markers included.
+fn summary_range(lines: &[&str]) -> Option<(usize, usize)> {
+    let start = lines.iter().position(|l| l.trim() == BEGIN_MARKER)?;
+    let end = lines[start..].iter().position(|l| l.trim() == END_MARKER)?;
+    Some((start, start + end + 1))
+}
+
+pub fn has_summary(content: &str) -> bool {
+    summary_range(&content.lines().collect::<Vec<_>>()).is_some()
+}
+
+/// A note's body without its AI summary, to summarize afresh.
+fn body_without_summary(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().skip(body_line_offset(content)).collect();
+    match summary_range(&lines) {
+        Some((start, end)) => [&lines[..start], &lines[end..]].concat().join("\n").trim_start().to_string(),
+        None => lines.join("\n"),
+    }
+}
+
+/// Put `summary` into the note as its AI summary section, replacing the
+/// one there only when `replace` is set. Headings in the summary are
+/// demoted so the section stays in one piece.
+pub fn insert_summary(
+    content: &str,
+    summary: &str,
+    source: SummarySource,
+    date: NaiveDate,
+    replace: bool,
+) -> Result<String, String> {
+    let summary: Vec<String> = summary
+        .trim()
+        .lines()
+        .map(|l| match l.strip_prefix("# ").or_else(|| l.strip_prefix("## ")) {
+            Some(rest) => format!("### {}", rest),
+            None => l.to_string(),
+        })
+        .collect();
+    let section = format!(
+        "{}\n{}\n\n*Generated from the {} on {}; edit or delete freely.*\n\n{}\n{}\n",
+        BEGIN_MARKER,
+        SUMMARY_HEADING,
+        source.label(),
+        date.format("%Y-%m-%d"),
+        summary.join("\n"),
+        END_MARKER
+    );
+
+    let lines: Vec<&str> = content.lines().collect();
+    let (start, end) = match summary_range(&lines) {
+        Some(_) if !replace => return Err("Note already has an AI summary".to_string()),
+        Some(range) => range,
+        None => {
+            // At the top of the body, after the blank line that follows
+            // the frontmatter
+            let mut at = body_line_offset(content);
+            while lines.get(at).is_some_and(|l| l.trim().is_empty()) {
+                at += 1;
+            }
+            (at, at)
+        }
+    };
+    let mut out: Vec<&str> = lines[..start].to_vec();
+    if start > 0 && !lines[start - 1].trim().is_empty() {
+        out.push("");
+    }
+    let mut result = out.join("\n");
+    if !result.is_empty() {
+        result.push('\n');
+    }
+    result.push_str(&section);
+    if end < lines.len() {
+        result.push('\n');
+        let rest = lines[end..].iter().skip_while(|l| l.trim().is_empty()).copied().collect::<Vec<_>>();
+        result.push_str(&rest.join("\n"));
+        if content.ends_with('\n') {
+            result.push('\n');
+        }
+    }
+    Ok(result)
+}
+
+fn prompt(source: SummarySource) -> String {
+    format!(
+        "Summarize the {} provided for my research notes in one short paragraph \
+         followed by three to five bullet points of key ideas. Reply with markdown only, \
+         no headings and no preamble.",
+        match source {
+            SummarySource::Note => "note",
+            SummarySource::Pdf => "paper",
+        }
+    )
+}
+
+fn truncate(mut text: String) -> String {
+    if let Some((i, _)) = text.char_indices().nth(MAX_INPUT_CHARS) {
+        text.truncate(i);
+    }
+    text
+}
+
+/// Script for the note view defining `summarizeNote(source, btn)`, which
+/// asks for a summary of the note or its PDF, offers to replace one that
+/// is already there, and reloads.
+pub fn summarize_script(key: &str) -> String {
+    format!(
+        r#"<script>
+async function summarizeNote(source, btn) {{
+    const request = async (replace) => fetch('/api/note/{}/summarize', {{
+        method: 'POST',
+        headers: {{'Content-Type': 'application/json'}},
+        body: JSON.stringify({{source, replace}}),
+    }});
+    const label = btn.textContent;
+    btn.disabled = true;
+    btn.textContent = 'Summarizing...';
+    let resp = await request(false);
+    if (resp.status === 409 && confirm('Replace the existing AI summary?')) {{
+        resp = await request(true);
+    }}
+    if (resp.ok) {{
+        window.location.reload();
+        return;
+    }}
+    if (resp.status !== 409) {{
+        alert('Summary failed: ' + await resp.text());
+    }}
+    btn.disabled = false;
+    btn.textContent = label;
+}}
+</script>"#,
+        key
+    )
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SummarizeRequest {
+    #[serde(default)]
+    pub source: SummarySource,
+    /// Replace an existing summary instead of refusing with 409.
+    #[serde(default)]
+    pub replace: bool,
+}
+
+/// `POST /api/note/{key}/summarize`: returns `{summary}`.
+pub async fn summarize_note(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<SummarizeRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let Some(note) = notes_map.get(&key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    // The model reads the decrypted body of an encrypted note
+    let opened = match crate::encryption::open_note(note) {
+        Ok(opened) => opened,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+    // Checked again when writing, but spare the model call
+    if has_summary(&opened.full_file_content) && !req.replace {
+        return (StatusCode::CONFLICT, "Note already has an AI summary").into_response();
+    }
+
+    let input = match req.source {
+        SummarySource::Note => body_without_summary(&opened.full_file_content),
+        SummarySource::Pdf => {
+            let Some(ref pdf) = note.pdf else {
+                return (StatusCode::BAD_REQUEST, "Note has no PDF").into_response();
+            };
+            let file = match state.storage.local_file(pdf).await {
+                Ok(file) => file,
+                Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+            };
+            match tokio::task::spawn_blocking(move || extract_pdf_text(file.path())).await {
+                Ok(Ok(text)) => text,
+                Ok(Err(e)) => return (StatusCode::UNPROCESSABLE_ENTITY, e).into_response(),
+                Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            }
+        }
+    };
+    if input.trim().is_empty() {
+        return (StatusCode::BAD_REQUEST, format!("The {} has no text", req.source.label())).into_response();
+    }
+
+    let summary = match crate::llm::complete(&state.http, prompt(req.source), Some(truncate(input))).await {
+        Ok(s) if !s.trim().is_empty() => s,
+        Ok(_) => return (StatusCode::BAD_GATEWAY, "Empty summary").into_response(),
+        Err(e) => {
+            tracing::warn!(key = %key, error = %e, "Summary failed");
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+    };
+
+    let now = crate::timezone::now();
+    let commit_msg = format!("AI summary of '{}': {}", note.title, now.format("%a %b %d, %-I:%M%p"));
+    match rewrite_note_file(&state, note, commit_msg, |c| {
+        insert_summary(c, &summary, req.source, now.date_naive(), req.replace)
+    }) {
+        Ok(_) => axum::Json(serde_json::json!({ "summary": summary.trim() })).into_response(),
+        Err((StatusCode::BAD_REQUEST, e)) => (StatusCode::CONFLICT, e).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{app_state, TempDir};
+
+    #[test]
+    fn test_insert_summary() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let note = "---\ntitle: X\n---\n\nIntro.\n\n## Notes\n\nMore.\n";
+
+        let once = insert_summary(note, "Short.\n\n## Bullets\n- a\n", SummarySource::Pdf, date, false).unwrap();
+        assert_eq!(
+            once,
+            "---\ntitle: X\n---\n\n<!-- BEGIN AI SUMMARY -->\n## AI Summary\n\n*Generated from the PDF on 2026-10-17; edit or delete freely.*\n\n\
+             Short.\n\n### Bullets\n- a\n<!-- END AI SUMMARY -->\n\nIntro.\n\n## Notes\n\nMore.\n"
+        );
+        assert!(has_summary(&once));
+        assert_eq!(body_without_summary(&once), "Intro.\n\n## Notes\n\nMore.");
+        assert!(insert_summary(&once, "Again.", SummarySource::Note, date, false).is_err());
+
+        let twice = insert_summary(&once, "Again.", SummarySource::Note, date, true).unwrap();
+        assert_eq!(
+            twice,
+            "---\ntitle: X\n---\n\n<!-- BEGIN AI SUMMARY -->\n## AI Summary\n\n*Generated from the note on 2026-10-17; edit or delete freely.*\n\n\
+             Again.\n<!-- END AI SUMMARY -->\n\nIntro.\n\n## Notes\n\nMore.\n"
+        );
+
+        assert_eq!(
+            insert_summary("---\ntitle: Empty\n---\n", "S.", SummarySource::Note, date, false).unwrap(),
+            "---\ntitle: Empty\n---\n\n<!-- BEGIN AI SUMMARY -->\n## AI Summary\n\n*Generated from the note on 2026-10-17; edit or delete freely.*\n\nS.\n<!-- END AI SUMMARY -->\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_summarize_encrypted_note() {
+        let dir = TempDir::new("summarize");
+        let passphrase_env = "NOTES_TEST_SUMMARIZE_PASSPHRASE";
+        std::env::set_var(passphrase_env, "correct horse");
+        crate::encryption::configure(&crate::config::EncryptionConfig { passphrase_env: Some(passphrase_env.to_string()) });
+        let state = app_state(&dir);
+        let path = state.notes_dir.join("medical.md");
+        let plain = "---\ntitle: Medical\nencrypted: true\n---\n\nBlood type: O-\n";
+        crate::encryption::write_file(&path, plain).unwrap();
+        state.invalidate_notes_cache();
+
+        let note = state.load_notes().into_iter().next().unwrap();
+        let date = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        rewrite_note_file(&state, &note, "summary".to_string(), |c| {
+            assert_eq!(c, plain);
+            insert_summary(c, "Type O-.", SummarySource::Note, date, false)
+        })
+        .unwrap();
+
+        // The summary is sealed along with the rest of the body
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(!written.contains(SUMMARY_HEADING) && !written.contains("Blood type"));
+        let note = state.load_notes().into_iter().next().unwrap();
+        let opened = crate::encryption::open_note(&note).unwrap();
+        assert!(has_summary(&opened.full_file_content));
+        assert_eq!(body_without_summary(&opened.full_file_content), "Blood type: O-");
+    }
+}
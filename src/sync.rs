@@ -0,0 +1,636 @@
+//! Syncing the notes directory with another instance.
+//!
+//! `POST /api/sync` compares this instance's files with the `[sync]` peer's
+//! and with the state both had after their last sync (the base, kept in
+//! sled as hashes plus the git commit holding its contents). A file changed
+//! on one side only is copied to the other; a file changed on both is
+//! merged with `git merge-file` against the base, leaving conflict markers
+//! where the edits overlap (binaries and encrypted notes keep this
+//! instance's copy and are reported as conflicts); a file deleted on one
+//! side and edited on the other is kept. Each side commits what it received
+//! and records the new base. The peer answers on `/api/sync/manifest`,
+//! `/api/sync/fetch` and `/api/sync/apply`, which take the shared token as
+//! a bearer token rather than a login. Dotfiles (`.git`, `.trash`) are
+//! never synced.
+//!
+//! One sync, or peer apply, runs per vault at a time (`AppState::syncing`).
+//! Every write carries the hash it expects to replace; a file that changed
+//! in the meantime is skipped and keeps its old base.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::auth::is_logged_in;
+use crate::config::Config;
+use crate::AppState;
+
+const SYNC_TREE: &str = "sync";
+const BASE_KEY: &str = "base";
+const DEFAULT_TOKEN_ENV: &str = "NOTES_SYNC_TOKEN";
+/// Files fetched from the peer per request.
+const FETCH_CHUNK: usize = 64;
+/// Body limit of the peer endpoints, which carry whole files.
+pub const MAX_BODY: usize = 256 * 1024 * 1024;
+const APPLY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Relative path (`/`-separated) to SHA-256 hex of the contents.
+type Manifest = BTreeMap<String, String>;
+
+/// The files as of the last sync, and the commit holding them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Base {
+    commit: Option<String>,
+    files: Manifest,
+}
+
+/// What one path needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Pull,
+    Push,
+    DeleteLocal,
+    DeleteRemote,
+    Merge,
+}
+
+/// A file to write (`content`, base64) or delete, if its hash is still
+/// `expected` (`None`: it must not exist).
+#[derive(Debug, Serialize, Deserialize)]
+struct Change {
+    path: String,
+    expected: Option<String>,
+    content: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FetchRequest {
+    paths: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FetchResponse {
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApplyRequest {
+    changes: Vec<Change>,
+    summary: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApplyResponse {
+    skipped: Vec<String>,
+}
+
+/// What `POST /api/sync` did.
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    pub pulled: Vec<String>,
+    pub pushed: Vec<String>,
+    pub merged: Vec<String>,
+    /// Merged with conflict markers, or (binary files, encrypted notes)
+    /// left as this instance's copy on both sides.
+    pub conflicts: Vec<String>,
+    /// Changed on one side while the sync ran; picked up next time.
+    pub skipped: Vec<String>,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A synced path as a relative filesystem path: no dotfiles, no `..`.
+fn safe_path(path: &str) -> Option<PathBuf> {
+    let valid = !path.is_empty()
+        && path
+            .split('/')
+            .all(|s| !s.is_empty() && !s.starts_with('.') && !s.contains(['\\', '\0']));
+    valid.then(|| PathBuf::from(path))
+}
+
+fn manifest(notes_dir: &Path) -> Manifest {
+    WalkDir::new(notes_dir)
+        .into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let rel = e.path().strip_prefix(notes_dir).ok()?;
+            let path = rel.components().map(|c| c.as_os_str().to_str()).collect::<Option<Vec<_>>>()?.join("/");
+            Some((path, sha256_hex(&fs::read(e.path()).ok()?)))
+        })
+        .collect()
+}
+
+/// What to do with each path that differs, from the three hashes.
+fn plan(local: &Manifest, remote: &Manifest, base: &Manifest) -> Vec<(String, Action)> {
+    let paths: BTreeSet<&String> = local.keys().chain(remote.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let (l, r, b) = (local.get(path), remote.get(path), base.get(path));
+            let action = match (l == r, l == b, r == b) {
+                (true, _, _) => return None,
+                (_, true, _) if r.is_some() => Action::Pull,
+                (_, true, _) => Action::DeleteLocal,
+                (_, _, true) if l.is_some() => Action::Push,
+                (_, _, true) => Action::DeleteRemote,
+                // Changed on both sides: an edit beats a delete
+                _ if l.is_none() => Action::Pull,
+                _ if r.is_none() => Action::Push,
+                _ => Action::Merge,
+            };
+            Some((path.clone(), action))
+        })
+        .collect()
+}
+
+fn load_base(db: &crate::store::Db) -> Base {
+    db.open_tree(SYNC_TREE)
+        .ok()
+        .and_then(|t| t.get(BASE_KEY).ok().flatten())
+        .and_then(|v| serde_json::from_slice(&v).ok())
+        .unwrap_or_default()
+}
+
+fn save_base(db: &crate::store::Db, base: &Base) -> Result<(), String> {
+    let tree = db.open_tree(SYNC_TREE).map_err(|e| e.to_string())?;
+    let value = serde_json::to_vec(base).map_err(|e| e.to_string())?;
+    tree.insert(BASE_KEY, value).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn git_head(notes_dir: &Path) -> Option<String> {
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).current_dir(notes_dir).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The base contents of `path`, if the base commit still has them.
+fn base_content(notes_dir: &Path, base: &Base, path: &str) -> Option<Vec<u8>> {
+    let commit = base.commit.as_ref()?;
+    let output = Command::new("git")
+        .args(["show", &format!("{}:{}", commit, path)])
+        .current_dir(notes_dir)
+        .output()
+        .ok()?;
+    let matches = output.status.success() && base.files.get(path) == Some(&sha256_hex(&output.stdout));
+    matches.then_some(output.stdout)
+}
+
+/// Three-way merge with `git merge-file`: the result and whether it has
+/// conflict markers, or `None` for files git won't merge (binaries) and
+/// encrypted notes, whose ciphertext can't be merged line by line.
+fn merge(local: &[u8], base: &[u8], remote: &[u8], peer: &str) -> Option<(Vec<u8>, bool)> {
+    let sealed = |bytes: &[u8]| std::str::from_utf8(bytes).is_ok_and(crate::encryption::is_sealed_file);
+    if [local, base, remote].into_iter().any(sealed) {
+        return None;
+    }
+    let dir = std::env::temp_dir().join(format!("notes-sync-{:016x}", rand::random::<u64>()));
+    fs::create_dir(&dir).ok()?;
+    let files = [("local", local), ("base", base), ("remote", remote)];
+    let written = files.iter().all(|(name, bytes)| fs::write(dir.join(name), bytes).is_ok());
+    let output = written
+        .then(|| {
+            Command::new("git")
+                .args(["merge-file", "-p", "-L", "this instance", "-L", "last sync", "-L", peer])
+                .args(["local", "base", "remote"])
+                .current_dir(&dir)
+                .output()
+                .ok()
+        })
+        .flatten();
+    let _ = fs::remove_dir_all(&dir);
+    let output = output?;
+    // The exit code is the number of conflicts; negative codes are errors
+    match output.status.code() {
+        Some(code) if (0..128).contains(&code) => Some((output.stdout, code > 0)),
+        _ => None,
+    }
+}
+
+/// Write or delete each change whose file is still as expected; returns the
+/// paths skipped because they changed meanwhile.
+fn apply_changes(notes_dir: &Path, changes: &[Change]) -> Vec<String> {
+    let mut skipped = Vec::new();
+    for change in changes {
+        let Some(rel) = safe_path(&change.path) else {
+            skipped.push(change.path.clone());
+            continue;
+        };
+        let path = notes_dir.join(rel);
+        let current = fs::read(&path).ok().map(|b| sha256_hex(&b));
+        if current != change.expected {
+            skipped.push(change.path.clone());
+            continue;
+        }
+        let result = match change.content {
+            Some(ref content) => BASE64
+                .decode(content)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                .and_then(|bytes| {
+                    fs::create_dir_all(path.parent().unwrap_or(notes_dir))?;
+                    crate::encryption::write_bytes(&path, &bytes).map_err(std::io::Error::other)
+                }),
+            None => fs::remove_file(&path),
+        };
+        if let Err(e) = result {
+            tracing::warn!(path = %change.path, error = %e, "Sync could not apply a change");
+            skipped.push(change.path.clone());
+        }
+    }
+    skipped
+}
+
+/// After applying changes: refresh the cache and graph, commit, and record
+/// the new base (keeping the old one for skipped paths).
+fn finish(state: &AppState, changed: &[String], skipped: &[String], summary: &str) -> Result<(), String> {
+    let paths: Vec<PathBuf> = changed.iter().filter(|p| !skipped.contains(p)).map(PathBuf::from).collect();
+    if !paths.is_empty() {
+        state.invalidate_notes_cache();
+        crate::graph_index::reconcile(&state.db, &state.load_notes())?;
+        crate::trash::commit_paths(&state.notes_dir, &paths, summary);
+    }
+    let old = load_base(&state.db);
+    let mut files = manifest(&state.notes_dir);
+    for path in skipped {
+        match old.files.get(path) {
+            Some(hash) => files.insert(path.clone(), hash.clone()),
+            None => files.remove(path),
+        };
+    }
+    save_base(&state.db, &Base { commit: git_head(&state.notes_dir), files })
+}
+
+fn token(config: &Config) -> Option<String> {
+    let var = config.sync.token_env.as_deref().unwrap_or(DEFAULT_TOKEN_ENV);
+    std::env::var(var).ok().filter(|t| !t.is_empty())
+}
+
+/// Whether the request carries the shared token.
+fn peer_authorized(state: &AppState, headers: &HeaderMap) -> bool {
+    let Some(expected) = token(&state.config) else {
+        return false;
+    };
+    let given = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    // Compare digests so the comparison time doesn't depend on the token
+    given.is_some_and(|g| Sha256::digest(g.as_bytes()) == Sha256::digest(expected.as_bytes()))
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "Invalid sync token").into_response()
+}
+
+fn blocking_error(e: tokio::task::JoinError) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Sync task failed: {}", e)).into_response()
+}
+
+/// `GET /api/sync/manifest` (peer): the hash of every synced file.
+pub async fn peer_manifest(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    if !peer_authorized(&state, &headers) {
+        return unauthorized();
+    }
+    let notes_dir = state.notes_dir.clone();
+    match tokio::task::spawn_blocking(move || manifest(&notes_dir)).await {
+        Ok(files) => Json(files).into_response(),
+        Err(e) => blocking_error(e),
+    }
+}
+
+/// `POST /api/sync/fetch` (peer): the contents of the requested files.
+pub async fn peer_fetch(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<FetchRequest>,
+) -> Response {
+    if !peer_authorized(&state, &headers) {
+        return unauthorized();
+    }
+    let files = body
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let bytes = fs::read(state.notes_dir.join(safe_path(path)?)).ok()?;
+            Some((path.clone(), BASE64.encode(bytes)))
+        })
+        .collect();
+    Json(FetchResponse { files }).into_response()
+}
+
+/// `POST /api/sync/apply` (peer): write the changes, commit them and record
+/// the new base.
+pub async fn peer_apply(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(body): Json<ApplyRequest>,
+) -> Response {
+    if !peer_authorized(&state, &headers) {
+        return unauthorized();
+    }
+    let Ok(_guard) = state.syncing.try_lock() else {
+        return (StatusCode::CONFLICT, "A sync is already running").into_response();
+    };
+    let task_state = state.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let skipped = apply_changes(&task_state.notes_dir, &body.changes);
+        let changed: Vec<String> = body.changes.into_iter().map(|c| c.path).collect();
+        finish(&task_state, &changed, &skipped, &body.summary).map(|()| skipped)
+    })
+    .await;
+    match result {
+        Ok(Ok(skipped)) => Json(ApplyResponse { skipped }).into_response(),
+        Ok(Err(e)) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+        Err(e) => blocking_error(e),
+    }
+}
+
+/// The configured peer's sync endpoints.
+struct Peer<'a> {
+    http: &'a reqwest::Client,
+    url: String,
+    token: String,
+}
+
+impl Peer<'_> {
+    fn name(&self) -> String {
+        url::Url::parse(&self.url).ok().and_then(|u| u.host_str().map(String::from)).unwrap_or_else(|| "peer".to_string())
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T, String> {
+        let response = request
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| format!("Peer unreachable: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(format!("Peer answered {}: {}", status, text.trim()));
+        }
+        response.json().await.map_err(|e| format!("Invalid peer response: {}", e))
+    }
+
+    async fn manifest(&self) -> Result<Manifest, String> {
+        self.send(self.http.get(format!("{}/api/sync/manifest", self.url))).await
+    }
+
+    async fn fetch(&self, paths: &[String]) -> Result<BTreeMap<String, Vec<u8>>, String> {
+        let mut files = BTreeMap::new();
+        for chunk in paths.chunks(FETCH_CHUNK) {
+            let request = self
+                .http
+                .post(format!("{}/api/sync/fetch", self.url))
+                .json(&FetchRequest { paths: chunk.to_vec() });
+            let response: FetchResponse = self.send(request).await?;
+            for (path, content) in response.files {
+                let bytes = BASE64.decode(content).map_err(|e| format!("Invalid peer response: {}", e))?;
+                files.insert(path, bytes);
+            }
+        }
+        Ok(files)
+    }
+
+    async fn apply(&self, changes: Vec<Change>, summary: String) -> Result<Vec<String>, String> {
+        let request = self
+            .http
+            .post(format!("{}/api/sync/apply", self.url))
+            .timeout(APPLY_TIMEOUT)
+            .json(&ApplyRequest { changes, summary });
+        let response: ApplyResponse = self.send(request).await?;
+        Ok(response.skipped)
+    }
+}
+
+/// `POST /api/sync`: sync with the configured peer.
+pub async fn sync(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let Some(url) = state.config.sync.peer.clone() else {
+        return (StatusCode::BAD_REQUEST, "No [sync] peer configured").into_response();
+    };
+    let Some(token) = token(&state.config) else {
+        return (StatusCode::BAD_REQUEST, "No sync token set").into_response();
+    };
+    let Ok(_guard) = state.syncing.try_lock() else {
+        return (StatusCode::CONFLICT, "A sync is already running").into_response();
+    };
+    let peer = Peer { http: &state.http, url: url.trim_end_matches('/').to_string(), token };
+    match run(&state, &peer).await {
+        Ok(report) => {
+            tracing::info!(
+                pulled = report.pulled.len(),
+                pushed = report.pushed.len(),
+                merged = report.merged.len(),
+                conflicts = report.conflicts.len(),
+                "Synced with peer"
+            );
+            Json(report).into_response()
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Sync failed");
+            (StatusCode::BAD_GATEWAY, e).into_response()
+        }
+    }
+}
+
+async fn run(state: &Arc<AppState>, peer: &Peer<'_>) -> Result<SyncReport, String> {
+    let notes_dir = state.notes_dir.clone();
+    let local = tokio::task::spawn_blocking(move || manifest(&notes_dir)).await.map_err(|e| e.to_string())?;
+    let remote = peer.manifest().await?;
+    let base = load_base(&state.db);
+    let actions = plan(&local, &remote, &base.files);
+
+    let wanted: Vec<String> = actions
+        .iter()
+        .filter(|(_, a)| matches!(a, Action::Pull | Action::Merge))
+        .map(|(p, _)| p.clone())
+        .collect();
+    let mut remote_files = peer.fetch(&wanted).await?;
+    let peer_name = peer.name();
+
+    let task_state = state.clone();
+    let merge_label = peer_name.clone();
+    let (mut report, remote_changes, local_changes) = tokio::task::spawn_blocking(move || {
+        let notes_dir = &task_state.notes_dir;
+        let mut report = SyncReport::default();
+        let (mut local_changes, mut remote_changes) = (Vec::new(), Vec::new());
+        let read = |path: &str| safe_path(path).and_then(|p| fs::read(notes_dir.join(p)).ok());
+        for (path, action) in actions {
+            let (l, r) = (local.get(&path).cloned(), remote.get(&path).cloned());
+            match action {
+                Action::Pull | Action::Merge if !remote_files.contains_key(&path) => {
+                    report.skipped.push(path);
+                }
+                Action::Pull => {
+                    let content = remote_files.remove(&path).map(|b| BASE64.encode(b));
+                    local_changes.push(Change { path: path.clone(), expected: l, content });
+                    report.pulled.push(path);
+                }
+                Action::DeleteLocal => {
+                    local_changes.push(Change { path: path.clone(), expected: l, content: None });
+                    report.pulled.push(path);
+                }
+                Action::Push => {
+                    let Some(bytes) = read(&path) else {
+                        report.skipped.push(path);
+                        continue;
+                    };
+                    remote_changes.push(Change { path: path.clone(), expected: r, content: Some(BASE64.encode(bytes)) });
+                    report.pushed.push(path);
+                }
+                Action::DeleteRemote => {
+                    remote_changes.push(Change { path: path.clone(), expected: r, content: None });
+                    report.pushed.push(path);
+                }
+                Action::Merge => {
+                    let (Some(ours), Some(theirs)) = (read(&path), remote_files.remove(&path)) else {
+                        report.skipped.push(path);
+                        continue;
+                    };
+                    let ancestor = base_content(notes_dir, &base, &path).unwrap_or_default();
+                    match merge(&ours, &ancestor, &theirs, &merge_label) {
+                        Some((merged, conflicted)) => {
+                            let content = BASE64.encode(merged);
+                            local_changes.push(Change { path: path.clone(), expected: l, content: Some(content.clone()) });
+                            remote_changes.push(Change { path: path.clone(), expected: r, content: Some(content) });
+                            if conflicted {
+                                report.conflicts.push(path.clone());
+                            }
+                            report.merged.push(path);
+                        }
+                        None => {
+                            remote_changes.push(Change { path: path.clone(), expected: r, content: Some(BASE64.encode(ours)) });
+                            report.conflicts.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        let skipped = apply_changes(notes_dir, &local_changes);
+        remote_changes.retain(|c| !skipped.contains(&c.path));
+        report.skipped.extend(skipped);
+        (report, remote_changes, local_changes)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let remote_summary = format!("sync: {} changed on a peer", remote_changes.len());
+    let remote_skipped = peer.apply(remote_changes, remote_summary).await?;
+    report.skipped.extend(remote_skipped);
+    report.skipped.sort();
+    report.skipped.dedup();
+
+    let summary = format!(
+        "sync with {}: {} pulled, {} merged",
+        peer_name,
+        report.pulled.len(),
+        report.merged.len()
+    );
+    let changed: Vec<String> = local_changes.into_iter().map(|c| c.path).collect();
+    let skipped = report.skipped.clone();
+    let task_state = state.clone();
+    tokio::task::spawn_blocking(move || finish(&task_state, &changed, &skipped, &summary))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    const SEALED: &str = "---\ntitle: S\nencrypted: true\n---\n-----BEGIN ENCRYPTED NOTE-----\nVersion: 2\n\nAAAA\n-----END ENCRYPTED NOTE-----\n";
+
+    #[test]
+    fn test_plan() {
+        let manifest = |pairs: &[(&str, &str)]| -> Manifest {
+            pairs.iter().map(|(p, h)| (p.to_string(), h.to_string())).collect()
+        };
+        let base = manifest(&[("same.md", "1"), ("ours.md", "1"), ("theirs.md", "1"), ("both.md", "1"), ("gone.md", "1"), ("edited.md", "1")]);
+        let local = manifest(&[("same.md", "1"), ("ours.md", "2"), ("theirs.md", "1"), ("both.md", "2"), ("edited.md", "2"), ("new.md", "1")]);
+        let remote = manifest(&[("same.md", "1"), ("ours.md", "1"), ("theirs.md", "3"), ("both.md", "3"), ("gone.md", "1")]);
+        let actions: BTreeMap<String, Action> = plan(&local, &remote, &base).into_iter().collect();
+        assert_eq!(actions.get("same.md"), None);
+        assert_eq!(actions["ours.md"], Action::Push);
+        assert_eq!(actions["theirs.md"], Action::Pull);
+        assert_eq!(actions["both.md"], Action::Merge);
+        assert_eq!(actions["gone.md"], Action::DeleteRemote);
+        // Deleted on the peer, edited here: the edit is kept
+        assert_eq!(actions["edited.md"], Action::Push);
+        assert_eq!(actions["new.md"], Action::Push);
+
+        assert!(safe_path("papers/a.md").is_some());
+        assert!(safe_path("../a.md").is_none() && safe_path(".git/config").is_none() && safe_path("/a.md").is_none());
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = b"---\ntitle: A\n---\none\ntwo\nthree\n";
+        let ours = b"---\ntitle: A\n---\nONE\ntwo\nthree\n";
+        let theirs = b"---\ntitle: A\n---\none\ntwo\nTHREE\n";
+        let (merged, conflicted) = merge(ours, base, theirs, "peer").unwrap();
+        assert_eq!(merged, b"---\ntitle: A\n---\nONE\ntwo\nTHREE\n");
+        assert!(!conflicted);
+
+        let clash = b"---\ntitle: A\n---\nUno\ntwo\nthree\n";
+        let (merged, conflicted) = merge(ours, base, clash, "peer").unwrap();
+        assert!(conflicted);
+        let merged = String::from_utf8(merged).unwrap();
+        assert!(merged.contains("<<<<<<< this instance") && merged.contains(">>>>>>> peer"));
+
+        // Ciphertext isn't merged: the caller keeps the local copy
+        let sealed = SEALED.replace("AAAA", "BBBB");
+        assert_eq!(merge(SEALED.as_bytes(), SEALED.as_bytes(), sealed.as_bytes(), "peer"), None);
+        assert_eq!(merge(ours, base, SEALED.as_bytes(), "peer"), None);
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let dir = TempDir::new("sync");
+        fs::write(dir.join("a.md"), "old").unwrap();
+        fs::write(dir.join("gone.md"), "bye").unwrap();
+        let change = |path: &str, expected: Option<&str>, content: Option<&str>| Change {
+            path: path.to_string(),
+            expected: expected.map(|e| sha256_hex(e.as_bytes())),
+            content: content.map(|c| BASE64.encode(c)),
+        };
+        let changes = [
+            change("a.md", Some("old"), Some("new")),
+            change("papers/b.md", None, Some("b")),
+            change("gone.md", Some("bye"), None),
+            // Changed here since the manifest was taken
+            change("a.md", Some("old"), Some("newer")),
+            change("../escape.md", None, Some("x")),
+            // Already sealed by the peer: written as received
+            change("secret.md", None, Some(SEALED)),
+        ];
+        let skipped = apply_changes(&dir, &changes);
+        assert_eq!(skipped, ["a.md", "../escape.md"]);
+        assert_eq!(fs::read_to_string(dir.join("a.md")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dir.join("papers/b.md")).unwrap(), "b");
+        assert!(!dir.join("gone.md").exists());
+        assert!(!dir.parent().unwrap().join("escape.md").exists());
+        assert_eq!(fs::read_to_string(dir.join("secret.md")).unwrap(), SEALED);
+    }
+}
@@ -0,0 +1,218 @@
+//! Tag suggestions.
+//!
+//! `GET /api/note/{key}/suggest-tags` proposes tags the note doesn't have:
+//! tags of the notes most similar to it (by the related-notes TF-IDF
+//! index), weighted by similarity, and tags whose words the note uses
+//! often. With `?llm=true` the language model is asked as well. The editor
+//! shows them after each save, and a click adds one to `tags:`.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::is_logged_in;
+use crate::models::Note;
+use crate::related::RelatedIndex;
+use crate::AppState;
+
+/// Suggestions returned.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Similar notes whose tags are considered.
+const NEIGHBOURS: usize = 10;
+
+/// Score per mention of a tag's words in the note.
+const MENTION_WEIGHT: f32 = 0.05;
+
+/// Mentions counted per tag, so one long note can't swamp the rest.
+const MAX_MENTIONS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    /// Why it was suggested, for a tooltip.
+    pub reason: String,
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Times the words of `tag` (split on `-`, `_`, `/`) appear in a row in
+/// `text_words`.
+fn mentions(tag: &str, text_words: &[String]) -> usize {
+    let tag_words = words(tag);
+    if tag_words.is_empty() || tag_words.iter().all(|w| w.len() < 3) {
+        return 0;
+    }
+    text_words.windows(tag_words.len()).filter(|w| *w == tag_words.as_slice()).count()
+}
+
+/// Tags used elsewhere in the corpus that `note` should perhaps have, best
+/// first.
+pub fn suggest_tags(note: &Note, notes: &[Note], index: &RelatedIndex) -> Vec<TagSuggestion> {
+    let by_key: HashMap<&str, &Note> = notes.iter().map(|n| (n.key.as_str(), n)).collect();
+    let has: HashSet<&str> = note.tags.iter().map(String::as_str).collect();
+    let mut scores: HashMap<&str, (f32, usize, usize)> = HashMap::new();
+
+    for (key, score) in index.related(&note.key, NEIGHBOURS) {
+        let Some(other) = by_key.get(key.as_str()) else { continue };
+        for tag in other.tags.iter().filter(|t| !has.contains(t.as_str())) {
+            let entry = scores.entry(tag.as_str()).or_default();
+            entry.0 += score;
+            entry.1 += 1;
+        }
+    }
+
+    let text_words = words(&format!("{}\n{}", note.title, note.raw_content));
+    let all_tags: HashSet<&str> = notes.iter().flat_map(|n| n.tags.iter().map(String::as_str)).collect();
+    for tag in all_tags.into_iter().filter(|t| !has.contains(t)) {
+        let count = mentions(tag, &text_words).min(MAX_MENTIONS);
+        if count > 0 {
+            let entry = scores.entry(tag).or_default();
+            entry.0 += count as f32 * MENTION_WEIGHT;
+            entry.2 = count;
+        }
+    }
+
+    let mut ranked: Vec<(&str, (f32, usize, usize))> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0).then_with(|| a.0.cmp(b.0)));
+    ranked
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(tag, (_, similar, mentioned))| {
+            let mut reasons = Vec::new();
+            if similar > 0 {
+                reasons.push(format!("on {} similar note{}", similar, if similar == 1 { "" } else { "s" }));
+            }
+            if mentioned > 0 {
+                reasons.push(format!("mentioned {}×", mentioned));
+            }
+            TagSuggestion {
+                tag: tag.to_string(),
+                reason: reasons.join(", "),
+            }
+        })
+        .collect()
+}
+
+/// A tag as frontmatter writes it: lowercase, words joined by `-`.
+fn normalize_tag(tag: &str) -> String {
+    words(tag.trim().trim_start_matches('#')).join("-")
+}
+
+/// Tags from the language model, preferring ones already in use.
+async fn llm_tags(client: &reqwest::Client, note: &Note, notes: &[Note]) -> Result<Vec<String>, String> {
+    let mut existing: Vec<&str> = notes.iter().flat_map(|n| n.tags.iter().map(String::as_str)).collect();
+    existing.sort_unstable();
+    existing.dedup();
+    let prompt = format!(
+        "Suggest up to {} tags for the note provided. Prefer these existing tags where they fit: {}. \
+         Reply with the tags only, comma-separated, lowercase, with hyphens between words.",
+        MAX_SUGGESTIONS,
+        existing.join(", ")
+    );
+    let reply = crate::llm::complete(client, prompt, Some(format!("# {}\n\n{}", note.title, note.raw_content))).await?;
+    Ok(reply
+        .split([',', '\n'])
+        .map(normalize_tag)
+        .filter(|t| !t.is_empty() && !note.tags.contains(t))
+        .take(MAX_SUGGESTIONS)
+        .collect())
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestTagsQuery {
+    /// Also ask the language model.
+    #[serde(default)]
+    pub llm: bool,
+}
+
+/// `GET /api/note/{key}/suggest-tags[?llm=true]`: `[{tag, reason}]`.
+pub async fn suggest_tags_api(
+    Path(key): Path<String>,
+    Query(query): Query<SuggestTagsQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes = state.load_notes();
+    let Some(note) = notes.iter().find(|n| n.key == key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    let mut suggestions = suggest_tags(note, &notes, &state.related_index());
+
+    if query.llm {
+        match llm_tags(&state.http, note, &notes).await {
+            Ok(tags) => {
+                for tag in tags {
+                    match suggestions.iter_mut().find(|s| s.tag == tag) {
+                        Some(s) => s.reason.push_str(", suggested by AI"),
+                        None => suggestions.push(TagSuggestion {
+                            tag,
+                            reason: "suggested by AI".to_string(),
+                        }),
+                    }
+                }
+            }
+            Err(e) => return (StatusCode::BAD_GATEWAY, e).into_response(),
+        }
+    }
+    axum::Json(suggestions).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_suggest_tags() {
+        let dir = TempDir::new("tag-suggest");
+        let note = |id: &str, tags: &str, body: &str| {
+            fs::write(
+                dir.join(format!("{}.md", id)),
+                format!("---\ntitle: {}\nid: {}\ntags: [{}]\n---\n\n{}\n", id, id, tags, body),
+            )
+            .unwrap();
+        };
+        note("aaaaaaaaaaaa", "", "Gradual typing with blame; the type system checks casts.");
+        note("bbbbbbbbbbbb", "pl, types", "Blame tracks casts in gradual typing.");
+        note("cccccccccccc", "pl", "Gradual typing and casts, again.");
+        note("dddddddddddd", "baking", "Sourdough starter.");
+        note("eeeeeeeeeeee", "type-system", "Unrelated.");
+
+        let notes = load_all_notes(&dir);
+        let index = RelatedIndex::build(&notes);
+        let target = notes.iter().find(|n| n.key == "aaaaaaaaaaaa").unwrap();
+        let suggested = suggest_tags(target, &notes, &index);
+        let tags: Vec<&str> = suggested.iter().map(|s| s.tag.as_str()).collect();
+        assert_eq!(tags, vec!["pl", "types", "type-system"]);
+        assert_eq!(suggested[0].reason, "on 2 similar notes");
+        assert_eq!(suggested[2].reason, "mentioned 1×");
+
+        // Tags the note has aren't suggested again
+        let tagged = notes.iter().find(|n| n.key == "cccccccccccc").unwrap();
+        assert!(suggest_tags(tagged, &notes, &index).iter().all(|s| s.tag != "pl"));
+        assert_eq!(normalize_tag(" #Type Systems"), "type-systems");
+    }
+}
@@ -0,0 +1,470 @@
+//! Tasks.
+//!
+//! Every markdown checkbox in a note body (`- [ ] call Bob`, `* [x] done`)
+//! is a task. `/tasks` collects the open ones from all notes, grouped by
+//! note or by tag, and `/api/tasks` returns them as JSON. Checkboxes in
+//! fenced code blocks don't count. A task may carry a deadline as
+//! `@due(2025-03-01)`; see `agenda`.
+//!
+//! Logged in, the viewer renders checkboxes with their file line, and
+//! ticking one rewrites that line with `toggle_task_line`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::models::{Note, Task};
+use crate::handlers::rewrite_note_file_with;
+use crate::notes::{body_line_offset, content_hash, html_escape};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Where a list item's checkbox starts on its line (the byte offset of its
+/// `[`) and whether it is checked.
+fn checkbox(line: &str) -> Option<(usize, bool)> {
+    let item = line.trim_start();
+    let after_marker = match item.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let rest = item.trim_start_matches(|c: char| c.is_ascii_digit());
+            if rest.len() == item.len() {
+                return None;
+            }
+            rest.strip_prefix(['.', ')'])?
+        }
+    };
+    let rest = after_marker.strip_prefix(' ')?;
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    if !rest[3..].is_empty() && !rest[3..].starts_with(' ') {
+        return None;
+    }
+    Some((line.len() - rest.len(), done))
+}
+
+/// The task on a line, as `(done, text)`, if the line is a checkbox item.
+pub(crate) fn parse_checkbox(line: &str) -> Option<(bool, &str)> {
+    let (start, done) = checkbox(line)?;
+    let text = line[start + 3..].trim();
+    (!text.is_empty()).then_some((done, text))
+}
+
+/// Lines of a note file's body outside fenced code blocks, numbered from 1.
+pub(crate) fn body_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut fence: Option<&str> = None;
+    content
+        .lines()
+        .enumerate()
+        .skip(body_line_offset(content))
+        .filter(move |(_, line)| {
+            let trimmed = line.trim_start();
+            if let Some(marker) = fence {
+                if trimmed.starts_with(marker) {
+                    fence = None;
+                }
+                return false;
+            }
+            if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+                fence = Some(marker);
+                return false;
+            }
+            true
+        })
+        .map(|(i, line)| (i + 1, line))
+}
+
+/// Split an `@due(YYYY-MM-DD)` annotation off a task's text. Malformed
+/// annotations are left in the text.
+pub(crate) fn split_due(text: &str) -> (String, Option<NaiveDate>) {
+    let Some(start) = text.find("@due(") else {
+        return (text.to_string(), None);
+    };
+    let rest = &text[start + "@due(".len()..];
+    let Some(end) = rest.find(')') else {
+        return (text.to_string(), None);
+    };
+    match NaiveDate::parse_from_str(rest[..end].trim(), "%Y-%m-%d") {
+        Ok(due) => {
+            let stripped = format!("{} {}", &text[..start], &rest[end + 1..]);
+            (stripped.split_whitespace().collect::<Vec<_>>().join(" "), Some(due))
+        }
+        Err(_) => (text.to_string(), None),
+    }
+}
+
+/// The checkboxes in a note, in file order.
+pub fn extract_tasks(note: &Note) -> Vec<Task> {
+    body_lines(&note.full_file_content)
+        .filter_map(|(line, text)| {
+            let (done, text) = parse_checkbox(text)?;
+            let (text, due) = split_due(text);
+            Some(Task {
+                note_key: note.key.clone(),
+                note_title: note.title.clone(),
+                line,
+                text,
+                done,
+                due,
+                tags: note.tags.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Check or uncheck the checkbox on `line` (from 1) of a note file.
+/// Returns the new contents and whether the box is now checked.
+pub fn toggle_task_line(content: &str, line: usize) -> Result<(String, bool), String> {
+    let (_, text) = body_lines(content)
+        .find(|(n, _)| *n == line)
+        .ok_or_else(|| format!("Line {} is not in the note body", line))?;
+    let (start, done) = checkbox(text).ok_or_else(|| format!("Line {} is not a task", line))?;
+    let toggled = format!("{}{}{}", &text[..start], if done { "[ ]" } else { "[x]" }, &text[start + 3..]);
+
+    let mut out = String::with_capacity(content.len());
+    for (i, raw) in content.split_inclusive('\n').enumerate() {
+        if i + 1 == line {
+            out.push_str(&toggled);
+            out.push_str(&raw[text.len()..]);
+        } else {
+            out.push_str(raw);
+        }
+    }
+    Ok((out, !done))
+}
+
+/// Tasks across `notes`, optionally with the finished ones and limited to
+/// notes with `tag` (case-insensitive).
+pub fn collect_tasks(notes: &[Note], include_done: bool, tag: Option<&str>) -> Vec<Task> {
+    notes
+        .iter()
+        .filter(|n| tag.is_none_or(|t| n.tags.iter().any(|nt| nt.eq_ignore_ascii_case(t))))
+        .flat_map(extract_tasks)
+        .filter(|t| include_done || !t.done)
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TasksQuery {
+    pub done: Option<String>,
+    pub tag: Option<String>,
+    /// `tag` to group by tag instead of by note.
+    pub group: Option<String>,
+}
+
+impl TasksQuery {
+    fn include_done(&self) -> bool {
+        self.done.as_deref() == Some("true")
+    }
+
+    fn tag(&self) -> Option<&str> {
+        self.tag.as_deref().map(str::trim).filter(|t| !t.is_empty())
+    }
+}
+
+fn task_row(task: &Task, show_note: bool) -> String {
+    let note = if show_note {
+        format!(
+            r#" <span class="key">&mdash; <a href="/note/{}">{}</a></span>"#,
+            task.note_key,
+            html_escape(&task.note_title)
+        )
+    } else {
+        String::new()
+    };
+    let due = match task.due {
+        Some(due) => format!(r#" <span class="type-badge">due {}</span>"#, due.format("%Y-%m-%d")),
+        None => String::new(),
+    };
+    format!(
+        r#"<li><input type="checkbox" disabled{}> {}{}{} <span class="key">line {}</span></li>"#,
+        if task.done { " checked" } else { "" },
+        html_escape(&task.text),
+        due,
+        note,
+        task.line,
+    )
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `GET /tasks`: open tasks from every note, grouped by note or by tag.
+pub async fn tasks_page(
+    Query(query): Query<TasksQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let notes = state.load_notes();
+    let tasks = collect_tasks(&notes, query.include_done(), query.tag());
+    let by_tag = query.group.as_deref() == Some("tag");
+
+    let mut html = String::from("<h1>Tasks</h1>");
+    let mut params = Vec::new();
+    if let Some(tag) = query.tag() {
+        params.push(format!("tag={}", urlencoding::encode(tag)));
+    }
+    let url = |extra: &[&str]| {
+        let all: Vec<&str> = params
+            .iter()
+            .map(String::as_str)
+            .chain(extra.iter().copied().filter(|p| !p.is_empty()))
+            .collect();
+        match all.is_empty() {
+            true => "/tasks".to_string(),
+            false => format!("/tasks?{}", all.join("&")),
+        }
+    };
+    let group_param = if by_tag { "group=tag" } else { "" };
+    let done_param = if query.include_done() { "done=true" } else { "" };
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} open{} &middot; group by {} &middot; <a href="{}">{}</a>{}</p>"#,
+        tasks.iter().filter(|t| !t.done).count(),
+        match query.tag() {
+            Some(tag) => format!(" tagged <strong>{}</strong>", html_escape(tag)),
+            None => String::new(),
+        },
+        if by_tag {
+            format!(r#"<a href="{}">note</a> / <strong>tag</strong>"#, url(&[done_param]))
+        } else {
+            format!(r#"<strong>note</strong> / <a href="{}">tag</a>"#, url(&[done_param, "group=tag"]))
+        },
+        url(&[group_param, if query.include_done() { "" } else { "done=true" }]),
+        if query.include_done() { "hide done" } else { "show done" },
+        if query.tag().is_some() {
+            format!(r#" &middot; <a href="/tasks{}">all tags</a>"#, if by_tag { "?group=tag" } else { "" })
+        } else {
+            String::new()
+        },
+    ));
+
+    if tasks.is_empty() {
+        html.push_str("<p>No tasks.</p>");
+        return Html(base_html("Tasks", &html, None, true)).into_response();
+    }
+
+    if by_tag {
+        let mut groups: BTreeMap<String, Vec<&Task>> = BTreeMap::new();
+        for task in &tasks {
+            if task.tags.is_empty() {
+                groups.entry(String::new()).or_default().push(task);
+            }
+            for tag in &task.tags {
+                groups.entry(tag.to_lowercase()).or_default().push(task);
+            }
+        }
+        // Untagged last
+        let untagged = groups.remove("");
+        for (tag, group) in groups.iter().map(|(t, g)| (Some(t), g)).chain(untagged.iter().map(|g| (None, g))) {
+            let heading = match tag {
+                Some(tag) => format!(
+                    r#"<a href="/tasks?tag={}&group=tag">{}</a>"#,
+                    urlencoding::encode(tag),
+                    html_escape(tag)
+                ),
+                None => "Untagged".to_string(),
+            };
+            html.push_str(&format!("<h2>{}</h2><ul>", heading));
+            for task in group {
+                html.push_str(&task_row(task, true));
+            }
+            html.push_str("</ul>");
+        }
+    } else {
+        let mut current: Option<&str> = None;
+        for task in &tasks {
+            if current != Some(task.note_key.as_str()) {
+                if current.is_some() {
+                    html.push_str("</ul>");
+                }
+                html.push_str(&format!(
+                    r#"<h2><a href="/note/{}">{}</a></h2><ul>"#,
+                    task.note_key,
+                    html_escape(&task.note_title)
+                ));
+                current = Some(&task.note_key);
+            }
+            html.push_str(&task_row(task, false));
+        }
+        html.push_str("</ul>");
+    }
+
+    Html(base_html("Tasks", &html, None, true)).into_response()
+}
+
+/// `GET /api/tasks`: the tasks as JSON; takes `done` and `tag` like `/tasks`.
+pub async fn tasks_api(
+    Query(query): Query<TasksQuery>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let notes = state.load_notes();
+    axum::Json(collect_tasks(&notes, query.include_done(), query.tag())).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToggleTaskRequest {
+    /// `content_hash` of the file as the viewer saw it.
+    pub hash: String,
+}
+
+/// `POST /api/note/{key}/task/{line}`: flip the checkbox on a line of the
+/// note's file. Refused with 409 if the file has changed since the viewer
+/// loaded it.
+pub async fn toggle_task(
+    Path((key, line)): Path<(String, usize)>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<ToggleTaskRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let notes_map = state.notes_map();
+    let note = match notes_map.get(&key) {
+        Some(n) => n,
+        None => return (StatusCode::NOT_FOUND, "Note not found").into_response(),
+    };
+    let now = crate::timezone::now();
+    let commit_msg = format!("updated task in '{}': {}", note.title, now.format("%a %b %d, %-I:%M%p"));
+    let mut result = None;
+    // The hash is checked on the same read that is rewritten, so an edit
+    // landing in between can't be overwritten
+    let rewritten = rewrite_note_file_with(&state, note, commit_msg, |c| {
+        if content_hash(c) != req.hash {
+            return Err((StatusCode::CONFLICT, "The note has changed; reload it".to_string()));
+        }
+        let (toggled, done) = toggle_task_line(c, line).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        result = Some((done, content_hash(&toggled)));
+        Ok(toggled)
+    });
+    match (rewritten, result) {
+        (Ok(_), Some((done, hash))) => axum::Json(serde_json::json!({ "done": done, "hash": hash })).into_response(),
+        (Err((StatusCode::CONFLICT, e)), _) => {
+            // The cache may predate an edit made outside the app
+            state.invalidate_notes_cache();
+            (StatusCode::CONFLICT, e).into_response()
+        }
+        (Err(e), _) => e.into_response(),
+        (Ok(_), None) => (StatusCode::INTERNAL_SERVER_ERROR, "Task not toggled").into_response(),
+    }
+}
+
+/// Script that makes a rendered note's checkboxes toggle their tasks.
+pub fn toggle_script(key: &str, hash: &str) -> String {
+    format!(
+        r#"<script>
+        (function() {{
+            let hash = '{hash}';
+            for (const box of document.querySelectorAll('.note-content input[data-line]')) {{
+                box.disabled = false;
+                box.addEventListener('change', async () => {{
+                    box.disabled = true;
+                    const resp = await fetch('/api/note/{key}/task/' + box.dataset.line, {{
+                        method: 'POST',
+                        headers: {{'Content-Type': 'application/json'}},
+                        body: JSON.stringify({{hash}})
+                    }});
+                    box.disabled = false;
+                    if (resp.ok) {{
+                        const result = await resp.json();
+                        hash = result.hash;
+                        box.checked = result.done;
+                    }} else if (resp.status === 409) {{
+                        alert(await resp.text());
+                        location.reload();
+                    }} else {{
+                        box.checked = !box.checked;
+                        alert('Failed to update task: ' + await resp.text());
+                    }}
+                }});
+            }}
+        }})();
+        </script>"#,
+        key = key,
+        hash = hash,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_extract_tasks() {
+        let dir = TempDir::new("tasks");
+        fs::write(
+            dir.join("a.md"),
+            "---\ntitle: Meeting\ntags: [clientx]\n---\n\n- [ ] send slides\n  * [x] book room\n\
+             - [] not a task\n- [ ]\n```\n- [ ] in code\n```\n+ [X] Done too\n- [ ] file @due(2025-03-01) taxes\n\
+             - [ ] odd @due(soon)\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b.md"), "---\ntitle: Other\n---\n- [ ] review draft\n").unwrap();
+
+        let notes = load_all_notes(&dir);
+        let meeting = notes.iter().find(|n| n.title == "Meeting").unwrap();
+        let tasks = extract_tasks(meeting);
+        let summary: Vec<(usize, &str, bool)> = tasks.iter().map(|t| (t.line, t.text.as_str(), t.done)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (6, "send slides", false),
+                (7, "book room", true),
+                (13, "Done too", true),
+                (14, "file taxes", false),
+                (15, "odd @due(soon)", false)
+            ]
+        );
+        assert_eq!(tasks[3].due, NaiveDate::from_ymd_opt(2025, 3, 1));
+
+        // The viewer's checkboxes carry the same lines
+        let offset = body_line_offset(&meeting.full_file_content);
+        let html = crate::notes::render_markdown_with_tasks(&meeting.raw_content, Some(offset));
+        for task in &tasks {
+            assert!(html.contains(&format!(r#"data-line="{}""#, task.line)), "line {}", task.line);
+        }
+        assert!(!html.contains(r#"data-line="12""#));
+
+        let (toggled, done) = toggle_task_line(&meeting.full_file_content, 6).unwrap();
+        assert!(done);
+        assert!(toggled.contains("\n- [x] send slides\n  * [x] book room\n"));
+        let (toggled, done) = toggle_task_line(&toggled, 7).unwrap();
+        assert!(!done);
+        assert!(toggled.contains("\n  * [ ] book room\n"));
+        assert!(toggle_task_line(&toggled, 2).is_err());
+        assert!(toggle_task_line(&toggled, 12).is_err());
+        assert!(toggle_task_line("1. [ ] numbered\r\nnext\r\n", 1).unwrap().0 == "1. [x] numbered\r\nnext\r\n");
+        assert_eq!(tasks[4].due, None);
+        assert_eq!(tasks[0].tags, vec!["clientx"]);
+
+        assert_eq!(collect_tasks(&notes, false, None).len(), 4);
+        assert_eq!(collect_tasks(&notes, true, None).len(), 6);
+        let tagged = collect_tasks(&notes, false, Some("ClientX"));
+        assert_eq!(tagged.len(), 3);
+        assert_eq!(tagged[0].text, "send slides");
+    }
+}
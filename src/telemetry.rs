@@ -0,0 +1,143 @@
+//! Logging and Prometheus metrics.
+//!
+//! Logs go through `tracing`: `init_logging` installs a text or JSON
+//! subscriber filtered by `[log] level`, and `trace_layer` wraps every
+//! request in a span carrying its method and path (plus client IP and
+//! scheme, filled in by `proxy::rewrite_urls`).
+//!
+//! HTTP request counts and latencies per matched route come from
+//! `axum-prometheus`; the helpers here record application metrics
+//! (corpus size and load time, external API calls, database size) through the
+//! `metrics` facade so they appear on the same `/metrics` page.
+
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::Request;
+use axum_prometheus::metrics_exporter_prometheus::PrometheusHandle;
+use axum_prometheus::{MetricLayerBuilder, PrometheusMetricLayer};
+use tower_http::classify::{ServerErrorsAsFailures, SharedClassifier};
+use tower_http::trace::{DefaultOnRequest, DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::{Level, Span};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{LogConfig, LogFormat};
+
+// ============================================================================
+// Logging
+// ============================================================================
+
+/// Install the global subscriber. Must be called at most once per process.
+pub fn init_logging(config: &LogConfig) -> Result<(), String> {
+    init_subscriber(config, BoxMakeWriter::new(std::io::stdout))
+}
+
+/// Logging for CLI subcommands: same filter and format, but on stderr so
+/// it never mixes with command output.
+pub fn init_cli_logging(config: &LogConfig) -> Result<(), String> {
+    init_subscriber(config, BoxMakeWriter::new(std::io::stderr))
+}
+
+fn init_subscriber(config: &LogConfig, writer: BoxMakeWriter) -> Result<(), String> {
+    let filter = EnvFilter::try_new(&config.level)
+        .map_err(|e| format!("Invalid log level '{}': {}", config.level, e))?;
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+    };
+    result.map_err(|e| format!("Failed to initialize logging: {}", e))
+}
+
+fn request_span(request: &Request<Body>) -> Span {
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        client_ip = tracing::field::Empty,
+        scheme = tracing::field::Empty,
+    )
+}
+
+pub type RequestTraceLayer = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    fn(&Request<Body>) -> Span,
+    DefaultOnRequest,
+    DefaultOnResponse,
+>;
+
+/// Per-request spans; each response is logged with its status and latency.
+pub fn trace_layer() -> RequestTraceLayer {
+    TraceLayer::new_for_http()
+        .make_span_with(request_span as fn(&Request<Body>) -> Span)
+        .on_request(DefaultOnRequest::new().level(Level::DEBUG))
+        .on_response(
+            DefaultOnResponse::new()
+                .level(Level::INFO)
+                .latency_unit(LatencyUnit::Millis),
+        )
+}
+
+// ============================================================================
+// Metrics
+// ============================================================================
+
+/// Build the request-metrics layer and install the global recorder.
+/// Must be called at most once per process.
+pub fn metrics_layer() -> (PrometheusMetricLayer<'static>, PrometheusHandle) {
+    MetricLayerBuilder::new()
+        .with_prefix("notes")
+        .with_ignore_patterns(&["/metrics", "/healthz", "/readyz"])
+        .with_default_metrics()
+        .build_pair()
+}
+
+/// Render the exposition text, refreshing gauges that are sampled on read.
+pub fn render_metrics(handle: &PrometheusHandle, db: &crate::store::Db) -> String {
+    if let Ok(size) = db.size_on_disk() {
+        metrics::gauge!("notes_db_size_bytes").set(size as f64);
+    }
+    handle.render()
+}
+
+/// Record a full corpus load from disk.
+pub fn record_corpus_load(count: usize, elapsed: Duration) {
+    metrics::gauge!("notes_count").set(count as f64);
+    metrics::histogram!("notes_corpus_load_seconds").record(elapsed.as_secs_f64());
+}
+
+/// Count an outbound call to an external service (arXiv, CrossRef, ...).
+pub fn record_external_call(api: &'static str, ok: bool) {
+    if !ok {
+        tracing::warn!(api, "External API call failed");
+    }
+    count_external_call(api, ok);
+}
+
+fn count_external_call(api: &'static str, ok: bool) {
+    let outcome = if ok { "ok" } else { "failure" };
+    metrics::counter!("notes_external_api_requests_total", "api" => api, "outcome" => outcome)
+        .increment(1);
+}
+
+/// Count an HTTP call, treating transport errors and non-2xx responses as
+/// failures. Failures are also logged, since most callers reduce them to
+/// `None`.
+pub fn record_http_call(api: &'static str, result: &Result<reqwest::Response, reqwest::Error>) {
+    let ok = match result {
+        Ok(r) if r.status().is_success() => true,
+        Ok(r) => {
+            tracing::warn!(api, status = r.status().as_u16(), url = %r.url(), "External API returned an error status");
+            false
+        }
+        Err(e) => {
+            tracing::warn!(api, error = %e, "External API request failed");
+            false
+        }
+    };
+    count_external_call(api, ok);
+}
@@ -2,7 +2,7 @@
 //!
 //! Contains navigation bar, Smart Add modal, and base HTML template.
 
-use crate::auth::is_auth_enabled;
+use crate::auth::{is_auth_enabled, is_published};
 use crate::notes::html_escape;
 
 use super::styles::STYLE;
@@ -14,19 +14,38 @@ use super::styles::STYLE;
 pub fn nav_bar(search_query: Option<&str>, logged_in: bool) -> String {
     let query_val = search_query.unwrap_or("");
     let auth_link = if logged_in {
-        r#"<a href="/logout">Logout</a>"#
+        r#"<a href="/problems">Problems</a>
+            <a href="/trash">Trash</a>
+            <a href="/logout">Logout</a>"#
     } else if is_auth_enabled() {
         r#"<a href="/login">Login</a>"#
     } else {
         ""
     };
 
+    // Time tracking, the journal, tasks, the agenda, the board, reminders, reviews, habits and courses aren't served by `notes publish`
+    let time_link = if is_published() {
+        ""
+    } else {
+        r#"<a href="/journal">Journal</a>
+            <a href="/tasks">Tasks</a>
+            <a href="/agenda">Agenda</a>
+            <a href="/board">Board</a>
+            <a href="/reminders">Reminders</a>
+            <a href="/review/weekly">Review</a>
+            <a href="/habits">Habits</a>
+            <a href="/courses">Courses</a>
+            <a href="/time">Time</a>"#
+    };
+
     format!(
         r#"<nav class="nav-bar">
             <a href="/">All</a>
+            <a href="/browse">Browse</a>
             <a href="/papers">Papers</a>
-            <a href="/time">Time</a>
+            {}
             <a href="/graph">Graph</a>
+            <a href="/random">Random</a>
             <a href="/bibliography.bib">Bib</a>
             <span class="spacer"></span>
             <form class="search-box" action="/search" method="get">
@@ -34,8 +53,13 @@ pub fn nav_bar(search_query: Option<&str>, logged_in: bool) -> String {
                 <button type="submit">Go</button>
             </form>
             {}
+            {}
+            {}
         </nav>"#,
+        time_link,
         html_escape(query_val),
+        crate::vaults::switcher_html(),
+        crate::theme::picker_html(logged_in),
         auth_link
     )
 }
@@ -69,8 +93,8 @@ pub fn smart_add_html() -> &'static str {
                 <div class="smart-input-group">
                     <label for="smart-input">Paste URL, arXiv ID, DOI, or paper title</label>
                     <input type="text" id="smart-input" placeholder="https://arxiv.org/abs/... or 10.1000/... or paper title"
-                           onkeydown="if(event.key==='Enter')performSmartLookup()">
-                    <small>Press Enter or wait to auto-detect</small>
+                           onkeydown="if(event.key==='Enter')performSmartLookup()" onpaste="smartPaste(event)">
+                    <small>Press Enter or wait to auto-detect; paste a paragraph citing several papers to add them all</small>
                 </div>
 
                 <div class="smart-loading" id="smart-loading">
@@ -103,6 +127,10 @@ pub fn smart_add_html() -> &'static str {
                             <input type="text" id="smart-filename" placeholder="authorYYYYkeyword.md">
                             <small>Auto-generated from bib key; editable</small>
                         </div>
+                        <div class="smart-input-group">
+                            <label for="smart-template">Template</label>
+                            <select id="smart-template" class="template-picker"><option value="">Default</option></select>
+                        </div>
                     </div>
 
                     <div class="smart-result-actions">
@@ -129,6 +157,10 @@ pub fn smart_add_html() -> &'static str {
                         <input type="text" id="note-subdir" placeholder="projects/">
                         <small>Optional subfolder</small>
                     </div>
+                    <div class="smart-input-group">
+                        <label for="note-template">Template</label>
+                        <select id="note-template" class="template-picker"><option value="">None</option></select>
+                    </div>
                 </div>
                 <div class="smart-result-actions">
                     <button class="btn" onclick="createQuickNote()">Create Note</button>
@@ -187,6 +219,29 @@ pub fn smart_add_html() -> &'static str {
         document.getElementById('note-subdir').value = '';
         window.detectedArxivId = null;
         window.detectedDoi = null;
+        loadTemplates();
+    }
+
+    // Fill the template pickers from /api/templates (once per page)
+    let templatesLoaded = false;
+    async function loadTemplates() {
+        if (templatesLoaded) return;
+        templatesLoaded = true;
+        try {
+            const response = await fetch('/api/templates');
+            if (!response.ok) return;
+            const names = await response.json();
+            for (const select of document.querySelectorAll('.template-picker')) {
+                for (const name of names) {
+                    const option = document.createElement('option');
+                    option.value = name;
+                    option.textContent = name;
+                    select.appendChild(option);
+                }
+            }
+        } catch (e) {
+            templatesLoaded = false;
+        }
     }
 
     function closeSmartAdd() {
@@ -282,6 +337,10 @@ pub fn smart_add_html() -> &'static str {
     async function performSmartLookup() {
         const input = document.getElementById('smart-input').value.trim();
         if (!input) return;
+        if (countReferences(input) > 1) {
+            performSmartExtract(input);
+            return;
+        }
 
         const loading = document.getElementById('smart-loading');
         const result = document.getElementById('smart-result');
@@ -399,6 +458,173 @@ pub fn smart_add_html() -> &'static str {
         }
     }
 
+    // DOIs and arXiv links in a pasted text; the server does the real extraction
+    function countReferences(text) {
+        return (text.match(/arxiv\.org\/(?:abs|pdf)\/|arxiv:|\b10\.\d{4,}\//gi) || []).length;
+    }
+
+    // A single-line input would glue the lines of a pasted paragraph
+    // together, so text citing several papers is taken straight from the clipboard
+    function smartPaste(event) {
+        const text = event.clipboardData.getData('text');
+        if (countReferences(text) < 2) return;
+        event.preventDefault();
+        event.target.value = text.replace(/\s+/g, ' ').trim();
+        performSmartExtract(text);
+    }
+
+    let smartExtractData = null;
+
+    async function performSmartExtract(text) {
+        const loading = document.getElementById('smart-loading');
+        const result = document.getElementById('smart-result');
+        loading.classList.add('active');
+        result.classList.remove('active');
+        document.getElementById('smart-form').classList.remove('active');
+
+        try {
+            const response = await fetch('/api/smart-add/extract', {
+                method: 'POST',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ text: text })
+            });
+            const data = await response.json();
+            loading.classList.remove('active');
+            if (data.error) {
+                result.innerHTML = '<h3>Error</h3><p>' + escapeHtml(data.error) + '</p>';
+                result.className = 'smart-result active error';
+                return;
+            }
+            renderSmartExtract(data);
+        } catch (e) {
+            loading.classList.remove('active');
+            showManualEntryOption(result, 'Network error: ' + e.message);
+        }
+    }
+
+    function renderSmartExtract(data) {
+        smartExtractData = data;
+        const result = document.getElementById('smart-result');
+        const refs = data.references;
+        const isNew = function(r) { return r.external_result && r.external_result.bibtex && !r.local_match; };
+        const nNew = refs.filter(isNew).length;
+        const nExisting = refs.filter(function(r) { return r.local_match; }).length;
+        const nMissing = refs.filter(function(r) { return !r.local_match && !(r.external_result && r.external_result.bibtex); }).length;
+
+        let html = '<h3>' + refs.length + ' references found</h3><div class="bib-badges">';
+        if (nNew > 0) html += '<span class="bib-badge new">' + nNew + ' new</span>';
+        if (nExisting > 0) html += '<span class="bib-badge existing">' + nExisting + ' in your notes</span>';
+        if (nMissing > 0) html += '<span class="bib-badge error">' + nMissing + ' not found</span>';
+        if (data.skipped > 0) html += '<span class="bib-badge conflict">' + data.skipped + ' more skipped</span>';
+        html += '</div>';
+
+        refs.forEach(function(r, i) {
+            const ext = r.external_result;
+            const label = (r.input_type === 'arxiv' ? 'arXiv:' : 'doi:') + r.identifier;
+            let details = '';
+            if (ext) {
+                details = ' &mdash; ' + escapeHtml(ext.title);
+                if (ext.authors) details += '<br><small>' + escapeHtml(ext.authors) + (ext.year ? ' (' + ext.year + ')' : '') + '</small>';
+            }
+            if (r.local_match) {
+                const m = r.local_match;
+                html += '<div class="bib-import-item existing"><code>' + escapeHtml(label) + '</code>' + details;
+                html += '<br><small>In your notes: <a href="/note/' + escapeHtml(m.key) + '">' + escapeHtml(m.title) + '</a> (by ' + escapeHtml(m.match_type) + ')</small>';
+                // A title match doesn't have this identifier yet
+                if (m.match_type !== r.input_type) {
+                    html += '<div class="bib-conflict-actions"><label><input type="checkbox" class="extract-attach" data-index="' + i + '" checked> Attach ' + escapeHtml(label) + ' to it</label></div>';
+                }
+                html += '</div>';
+            } else if (ext && ext.bibtex) {
+                html += '<div class="bib-import-item new"><label><input type="checkbox" class="extract-create" data-index="' + i + '" checked> ';
+                html += '<code>' + escapeHtml(label) + '</code></label>' + details;
+                html += '<div class="bib-filename-row"><label>Filename: <input type="text" class="extract-filename" data-index="' + i + '" value="' + escapeHtml(ext.bib_key + '.md') + '"></label></div>';
+                html += '</div>';
+            } else {
+                html += '<div class="bib-import-item error-item"><code>' + escapeHtml(label) + '</code> &mdash; <small>no metadata found</small></div>';
+            }
+        });
+
+        if (nNew > 0 || refs.some(function(r) { return r.local_match && r.local_match.match_type !== r.input_type; })) {
+            html += '<div class="smart-result-actions" style="margin-top:1rem">';
+            html += '<button class="btn" onclick="executeSmartExtract()">Add Selected</button>';
+            html += '<button class="btn secondary" onclick="closeSmartAdd()">Cancel</button>';
+            html += '</div>';
+        }
+
+        result.innerHTML = html;
+        result.className = 'smart-result active';
+    }
+
+    async function executeSmartExtract() {
+        if (!smartExtractData) return;
+        const refs = smartExtractData.references;
+        const template = document.getElementById('smart-template').value || null;
+        const tasks = [];
+        document.querySelectorAll('.extract-create:checked').forEach(function(cb) {
+            const r = refs[parseInt(cb.dataset.index)];
+            const filename = document.querySelector('.extract-filename[data-index="' + cb.dataset.index + '"]').value.trim();
+            tasks.push({ kind: 'create', ref: r, body: {
+                bibtex: r.external_result.bibtex,
+                filename: filename,
+                arxiv_id: r.input_type === 'arxiv' ? r.identifier : null,
+                doi: r.input_type === 'doi' ? r.identifier : null,
+                template: template
+            }});
+        });
+        document.querySelectorAll('.extract-attach:checked').forEach(function(cb) {
+            const r = refs[parseInt(cb.dataset.index)];
+            tasks.push({ kind: 'attach', ref: r, body: {
+                note_key: r.local_match.key,
+                source_type: r.input_type,
+                identifier: r.identifier
+            }});
+        });
+        if (tasks.length === 0) {
+            alert('No references selected.');
+            return;
+        }
+
+        const result = document.getElementById('smart-result');
+        result.innerHTML = '<div class="smart-loading active"><div class="smart-spinner"></div><span>Adding...</span></div>';
+        const created = [], attached = [], errors = [];
+        for (const t of tasks) {
+            try {
+                const response = await fetch(t.kind === 'create' ? '/api/smart-add/create' : '/api/smart-add/attach', {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify(t.body)
+                });
+                const data = await response.json();
+                if (data.error) {
+                    errors.push(t.ref.identifier + ': ' + data.error);
+                } else if (t.kind === 'create') {
+                    created.push({ key: data.key, title: t.ref.external_result.title });
+                } else {
+                    attached.push({ key: t.ref.local_match.key, title: t.ref.local_match.title });
+                }
+            } catch (e) {
+                errors.push(t.ref.identifier + ': ' + e.message);
+            }
+        }
+
+        const list = function(items) {
+            return '<ul>' + items.map(function(n) { return '<li><a href="/note/' + escapeHtml(n.key) + '">' + escapeHtml(n.title) + '</a></li>'; }).join('') + '</ul>';
+        };
+        let html = '<h3>Done</h3>';
+        if (created.length > 0) html += '<p>' + created.length + ' notes created:</p>' + list(created);
+        if (attached.length > 0) html += '<p>' + attached.length + ' notes given a new source:</p>' + list(attached);
+        if (errors.length > 0) {
+            html += '<p class="message error">' + errors.length + ' errors:</p><ul>';
+            for (const e of errors) html += '<li>' + escapeHtml(e) + '</li>';
+            html += '</ul>';
+        }
+        html += '<div class="smart-result-actions" style="margin-top:1rem">';
+        html += '<button class="btn secondary" onclick="closeSmartAdd(); location.reload();">Close</button>';
+        html += '</div>';
+        result.innerHTML = html;
+    }
+
     function escapeHtml(str) {
         if (!str) return '';
         return str.replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;').replace(/"/g, '&quot;');
@@ -501,7 +727,8 @@ pub fn smart_add_html() -> &'static str {
             bibtex: bibtex,
             filename: filename,
             arxiv_id: window.detectedArxivId || null,
-            doi: window.detectedDoi || null
+            doi: window.detectedDoi || null,
+            template: document.getElementById('smart-template').value || null
         };
 
         try {
@@ -536,7 +763,8 @@ pub fn smart_add_html() -> &'static str {
         const data = {
             title: title,
             date: document.getElementById('note-date').value || null,
-            subdirectory: document.getElementById('note-subdir').value || null
+            subdirectory: document.getElementById('note-subdir').value || null,
+            template: document.getElementById('note-template').value || null
         };
 
         try {
@@ -819,6 +1047,18 @@ pub fn smart_add_html() -> &'static str {
 
 pub fn base_html(title: &str, content: &str, search_query: Option<&str>, logged_in: bool) -> String {
     let fab_html = if logged_in { smart_add_html() } else { "" };
+    // A published site is static files, with no service worker to register
+    let (app_head, app_script) = if is_published() {
+        ("", "")
+    } else {
+        (
+            r##"<link rel="manifest" href="/manifest.webmanifest">
+    <link rel="icon" href="/icon.svg" type="image/svg+xml">
+    <meta name="theme-color" content="#1f2937">"##,
+            crate::pwa::REGISTER_SCRIPT,
+        )
+    };
+    let custom = super::custom::current();
 
     format!(
         r#"<!DOCTYPE html>
@@ -827,14 +1067,19 @@ pub fn base_html(title: &str, content: &str, search_query: Option<&str>, logged_
     <meta charset="UTF-8">
     <meta name="viewport" content="width=device-width, initial-scale=1.0">
     <title>{title}</title>
-    <style>{STYLE}</style>
+    <link rel="alternate" type="application/atom+xml" title="Notes" href="/feed.xml">
+    {app_head}
+    <style>{theme_css}{STYLE}{custom_css}</style>
 </head>
 <body>
     {nav}
+    {custom_header}
     <div class="container">
         {content}
     </div>
+    {custom_footer}
     {fab}
+    {app_script}
     <script>
     // Copy BibTeX to clipboard
     function copyBibtex(elementId) {{
@@ -900,7 +1145,7 @@ pub fn base_html(title: &str, content: &str, search_query: Option<&str>, logged_
 
     // Confirm and delete note
     async function confirmDelete(key, title) {{
-        const confirmed = confirm('Delete "' + title + '"?\n\nThis will remove the note file and create a git commit. You can recover it from git history if needed.');
+        const confirmed = confirm('Move "' + title + '" to the trash?\n\nYou can restore it from the Trash page until it is purged.');
         if (!confirmed) return;
 
         try {{
@@ -926,5 +1171,11 @@ pub fn base_html(title: &str, content: &str, search_query: Option<&str>, logged_
         title = html_escape(title),
         nav = nav_bar(search_query, logged_in),
         fab = fab_html,
+        app_head = app_head,
+        app_script = app_script,
+        theme_css = crate::theme::current().css(),
+        custom_css = custom.css,
+        custom_header = custom.header,
+        custom_footer = custom.footer,
     )
 }
@@ -0,0 +1,64 @@
+//! Appearance overrides for self-hosters.
+//!
+//! `custom.css`, `header.html` and `footer.html` in the data directory (the
+//! working directory without `--data-dir`) are read once at startup and
+//! added to every `base_html` page and the note viewer: the CSS after the
+//! built-in styles, so its rules win, the header above the page content and
+//! the footer below it. The files are the operator's own, so the HTML goes
+//! in as written. Each one is optional.
+
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+pub const CSS_FILE: &str = "custom.css";
+pub const HEADER_FILE: &str = "header.html";
+pub const FOOTER_FILE: &str = "footer.html";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Customization {
+    pub css: String,
+    pub header: String,
+    pub footer: String,
+}
+
+static CUSTOM: RwLock<Option<Arc<Customization>>> = RwLock::new(None);
+
+/// Read the override files in `dir`; a missing file is left empty.
+pub fn load(dir: &Path) -> Customization {
+    let read = |name: &str| match fs::read_to_string(dir.join(name)) {
+        Ok(text) => {
+            tracing::info!(file = %dir.join(name).display(), "Loaded appearance override");
+            text
+        }
+        Err(_) => String::new(),
+    };
+    Customization { css: read(CSS_FILE), header: read(HEADER_FILE), footer: read(FOOTER_FILE) }
+}
+
+/// Use `custom` for every later page (set at startup).
+pub fn configure(custom: Customization) {
+    *CUSTOM.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(custom));
+}
+
+pub fn current() -> Arc<Customization> {
+    CUSTOM.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_load_customization() {
+        let dir = TempDir::new("custom");
+        assert_eq!(load(&dir), Customization::default());
+        fs::write(dir.join(CSS_FILE), "body { font-size: 18px; }").unwrap();
+        fs::write(dir.join(FOOTER_FILE), "<footer>Hosted by me</footer>").unwrap();
+        let custom = load(&dir);
+        assert_eq!(custom.css, "body { font-size: 18px; }");
+        assert_eq!(custom.header, "");
+        assert_eq!(custom.footer, "<footer>Hosted by me</footer>");
+    }
+}
@@ -18,6 +18,7 @@ struct NoteSuggestion {
 }
 
 pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in: bool) -> String {
+    let theme = crate::theme::current();
     // Use serde_json for proper escaping
     let content_json = serde_json::to_string(&note.full_file_content)
         .unwrap_or_else(|_| "\"\"".to_string());
@@ -68,6 +69,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
     <meta name="viewport" content="width=device-width, initial-scale=1.0, maximum-scale=1.0, user-scalable=no">
     <title>Editing: {title}</title>
     <style>
+        {theme_css}
         * {{ box-sizing: border-box; margin: 0; padding: 0; }}
         body {{ overflow: hidden; }}
 
@@ -77,7 +79,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             left: 0;
             right: 0;
             bottom: 0;
-            background: #fdf6e3;
+            background: var(--base3);
         }}
 
         .editor-header {{
@@ -86,8 +88,8 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             left: 0;
             right: 0;
             height: 48px;
-            background: #eee8d5;
-            border-bottom: 1px solid #93a1a1;
+            background: var(--base2);
+            border-bottom: 1px solid var(--base1);
             display: flex;
             align-items: center;
             padding: 0 1rem;
@@ -99,7 +101,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             margin: 0;
             font-size: 1rem;
             font-weight: 500;
-            color: #657b83;
+            color: var(--base00);
             flex: 1;
             white-space: nowrap;
             overflow: hidden;
@@ -107,7 +109,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         }}
 
         .back-link {{
-            color: #268bd2;
+            color: var(--blue);
             text-decoration: none;
             font-size: 0.85rem;
             white-space: nowrap;
@@ -118,43 +120,64 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
 
         .btn {{
             padding: 0.4rem 0.8rem;
-            border: 1px solid #93a1a1;
+            border: 1px solid var(--base1);
             border-radius: 4px;
-            background: #fdf6e3;
-            color: #657b83;
+            background: var(--base3);
+            color: var(--base00);
             cursor: pointer;
             font-size: 0.85rem;
             font-family: inherit;
             text-decoration: none;
             display: inline-block;
         }}
-        .btn:hover {{ background: #eee8d5; }}
+        .btn:hover {{ background: var(--base2); }}
         .btn.primary {{
-            background: #268bd2;
-            color: #fdf6e3;
-            border-color: #268bd2;
+            background: var(--blue);
+            color: var(--base3);
+            border-color: var(--blue);
         }}
         .btn.primary:hover {{ background: #1a6fa3; }}
 
         .editor-status {{
             font-size: 0.8rem;
-            color: #93a1a1;
+            color: var(--base1);
             display: flex;
             align-items: center;
             gap: 0.5rem;
             min-width: 120px;
         }}
-        .editor-status.saving {{ color: #268bd2; }}
-        .editor-status.saved {{ color: #859900; }}
-        .editor-status.error {{ color: #dc322f; }}
-        .editor-status.pending {{ color: #b58900; }}
+        .editor-status.saving {{ color: var(--blue); }}
+        .editor-status.saved {{ color: var(--green); }}
+        .editor-status.error {{ color: var(--red); }}
+        .editor-status.pending {{ color: var(--yellow); }}
+
+        .tag-suggestions {{
+            display: flex;
+            align-items: center;
+            gap: 0.3rem;
+            font-size: 0.75rem;
+            color: var(--base1);
+            white-space: nowrap;
+            overflow: hidden;
+        }}
+        .tag-suggestions:empty {{ display: none; }}
+        .tag-suggestion {{
+            padding: 0.1rem 0.45rem;
+            border: 1px dashed var(--base1);
+            border-radius: 10px;
+            background: none;
+            color: var(--base00);
+            font-size: 0.75rem;
+            cursor: pointer;
+        }}
+        .tag-suggestion:hover {{ border-style: solid; color: var(--blue); border-color: var(--blue); }}
 
         .emacs-badge {{
             font-size: 0.65rem;
             font-weight: 600;
             padding: 0.2rem 0.4rem;
-            background: #6c71c4;
-            color: #fdf6e3;
+            background: var(--violet);
+            color: var(--base3);
             border-radius: 3px;
             font-family: monospace;
             letter-spacing: 0.05em;
@@ -166,7 +189,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             align-items: center;
             gap: 0.5rem;
             font-size: 0.8rem;
-            color: #93a1a1;
+            color: var(--base1);
         }}
         .toggle-switch {{
             position: relative;
@@ -186,7 +209,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             left: 0;
             right: 0;
             bottom: 0;
-            background-color: #93a1a1;
+            background-color: var(--base1);
             transition: 0.3s;
             border-radius: 20px;
         }}
@@ -197,12 +220,12 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             width: 14px;
             left: 3px;
             bottom: 3px;
-            background-color: #fdf6e3;
+            background-color: var(--base3);
             transition: 0.3s;
             border-radius: 50%;
         }}
         input:checked + .toggle-slider {{
-            background-color: #859900;
+            background-color: var(--green);
         }}
         input:checked + .toggle-slider:before {{
             transform: translateX(16px);
@@ -217,7 +240,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             align-items: baseline;
             gap: 0.15rem;
             font-family: inherit;
-            color: #93a1a1;
+            color: var(--base1);
         }}
         .font-size-controls label {{
             cursor: pointer;
@@ -227,13 +250,13 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             line-height: 1;
         }}
         .font-size-controls label:hover {{
-            color: #657b83;
+            color: var(--base00);
         }}
         .font-size-controls input[type="radio"] {{
             display: none;
         }}
         .font-size-controls input[type="radio"]:checked + span {{
-            color: #268bd2;
+            color: var(--blue);
         }}
         .font-size-controls .size-tiny {{ font-size: 0.7rem; }}
         .font-size-controls .size-small {{ font-size: 0.85rem; }}
@@ -275,7 +298,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             top: 0;
             bottom: 0;
             width: 6px;
-            background: #93a1a1;
+            background: var(--base1);
             cursor: col-resize;
             z-index: 100;
             display: none;
@@ -283,7 +306,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         }}
         #split-divider:hover,
         #split-divider.dragging {{
-            background: #268bd2;
+            background: var(--blue);
         }}
         #split-divider.active {{
             display: block;
@@ -298,7 +321,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             bottom: 0;
             display: none;
             border-left: none;
-            background: #586e75;
+            background: var(--base01);
             flex-direction: column;
         }}
         #pdf-viewer-pane.active {{
@@ -308,8 +331,8 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         /* PDF Toolbar */
         .pdf-toolbar {{
             height: 40px;
-            background: #eee8d5;
-            border-bottom: 1px solid #93a1a1;
+            background: var(--base2);
+            border-bottom: 1px solid var(--base1);
             display: flex;
             align-items: center;
             padding: 0 0.5rem;
@@ -319,16 +342,16 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
 
         .pdf-toolbar button {{
             padding: 0.3rem 0.5rem;
-            border: 1px solid #93a1a1;
+            border: 1px solid var(--base1);
             border-radius: 3px;
-            background: #fdf6e3;
-            color: #657b83;
+            background: var(--base3);
+            color: var(--base00);
             cursor: pointer;
             font-size: 0.8rem;
             font-family: inherit;
         }}
         .pdf-toolbar button:hover {{
-            background: #eee8d5;
+            background: var(--base2);
         }}
         .pdf-toolbar button:disabled {{
             opacity: 0.5;
@@ -337,7 +360,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
 
         .pdf-page-info {{
             font-size: 0.8rem;
-            color: #657b83;
+            color: var(--base00);
             margin-left: auto;
         }}
 
@@ -345,7 +368,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         .pdf-canvas-container {{
             flex: 1;
             overflow: auto;
-            background: #586e75;
+            background: var(--base01);
             touch-action: pan-x pan-y; /* Allow scroll, capture pinch for custom zoom */
             position: relative;
             -webkit-overflow-scrolling: touch;
@@ -417,15 +440,15 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             align-items: center;
             justify-content: center;
             height: 100%;
-            color: #fdf6e3;
+            color: var(--base3);
             font-size: 0.9rem;
         }}
 
         .pdf-loading .spinner {{
             width: 24px;
             height: 24px;
-            border: 3px solid #93a1a1;
-            border-top-color: #fdf6e3;
+            border: 3px solid var(--base1);
+            border-top-color: var(--base3);
             border-radius: 50%;
             animation: spin 1s linear infinite;
             margin-right: 0.5rem;
@@ -440,7 +463,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             align-items: center;
             justify-content: center;
             height: 100%;
-            color: #dc322f;
+            color: var(--red);
             font-size: 0.9rem;
             padding: 1rem;
             text-align: center;
@@ -449,13 +472,13 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         /* PDF Status in header */
         .pdf-status {{
             font-size: 0.75rem;
-            color: #93a1a1;
+            color: var(--base1);
             display: flex;
             align-items: center;
             gap: 0.5rem;
         }}
         .pdf-status .pdf-link {{
-            color: #268bd2;
+            color: var(--blue);
             text-decoration: none;
             font-size: 0.8rem;
             padding: 0.2rem 0.4rem;
@@ -463,25 +486,25 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             transition: background 0.15s;
         }}
         .pdf-status .pdf-link:hover {{
-            background: #eee8d5;
+            background: var(--base2);
             text-decoration: none;
         }}
         .pdf-status .pdf-toggle-btn {{
             padding: 0.2rem 0.5rem;
-            border: 1px solid #93a1a1;
+            border: 1px solid var(--base1);
             border-radius: 3px;
             background: transparent;
-            color: #93a1a1;
+            color: var(--base1);
             cursor: pointer;
             font-size: 0.75rem;
         }}
         .pdf-status .pdf-toggle-btn:hover {{
-            background: #eee8d5;
+            background: var(--base2);
         }}
         .pdf-status .pdf-toggle-btn.active {{
-            background: #268bd2;
-            color: #fdf6e3;
-            border-color: #268bd2;
+            background: var(--blue);
+            color: var(--base3);
+            border-color: var(--blue);
         }}
 
         /* Note Type Dropdown */
@@ -491,23 +514,23 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         }}
         .note-type-dropdown .dropdown-btn {{
             padding: 0.2rem 0.5rem;
-            border: 1px solid #93a1a1;
+            border: 1px solid var(--base1);
             border-radius: 3px;
             background: transparent;
-            color: #93a1a1;
+            color: var(--base1);
             cursor: pointer;
             font-size: 0.75rem;
         }}
         .note-type-dropdown .dropdown-btn:hover {{
-            background: #eee8d5;
+            background: var(--base2);
         }}
         .note-type-dropdown .dropdown-content {{
             display: none;
             position: absolute;
             top: 100%;
             left: 0;
-            background: #fdf6e3;
-            border: 1px solid #93a1a1;
+            background: var(--base3);
+            border: 1px solid var(--base1);
             border-radius: 4px;
             box-shadow: 0 2px 8px rgba(0,0,0,0.15);
             z-index: 100;
@@ -523,13 +546,13 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             padding: 0.4rem 0.6rem;
             border: none;
             background: none;
-            color: #657b83;
+            color: var(--base00);
             font-size: 0.75rem;
             text-align: left;
             cursor: pointer;
         }}
         .note-type-dropdown .dropdown-item:hover {{
-            background: #eee8d5;
+            background: var(--base2);
         }}
         .note-type-dropdown .dropdown-item:first-child {{
             border-radius: 3px 3px 0 0;
@@ -555,7 +578,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             display: flex;
         }}
         .pdf-upload-modal {{
-            background: #fdf6e3;
+            background: var(--base3);
             border-radius: 8px;
             width: 90%;
             max-width: 500px;
@@ -566,30 +589,30 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             justify-content: space-between;
             align-items: center;
             padding: 1rem 1.5rem;
-            border-bottom: 1px solid #eee8d5;
+            border-bottom: 1px solid var(--base2);
         }}
         .pdf-upload-header h3 {{
             margin: 0;
             font-size: 1.1rem;
-            color: #657b83;
+            color: var(--base00);
         }}
         .pdf-upload-close {{
             background: none;
             border: none;
             font-size: 1.5rem;
             cursor: pointer;
-            color: #93a1a1;
+            color: var(--base1);
             padding: 0;
             line-height: 1;
         }}
         .pdf-upload-close:hover {{
-            color: #657b83;
+            color: var(--base00);
         }}
         .pdf-upload-body {{
             padding: 1.5rem;
         }}
         .pdf-dropzone {{
-            border: 2px dashed #93a1a1;
+            border: 2px dashed var(--base1);
             border-radius: 8px;
             padding: 2rem;
             text-align: center;
@@ -599,8 +622,8 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         }}
         .pdf-dropzone:hover,
         .pdf-dropzone.dragover {{
-            border-color: #268bd2;
-            background: #eee8d5;
+            border-color: var(--blue);
+            background: var(--base2);
         }}
         .pdf-dropzone-icon {{
             font-size: 2rem;
@@ -608,11 +631,11 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         }}
         .pdf-dropzone-text {{
             font-size: 0.9rem;
-            color: #657b83;
+            color: var(--base00);
         }}
         .pdf-dropzone-hint {{
             font-size: 0.8rem;
-            color: #93a1a1;
+            color: var(--base1);
             margin-top: 0.5rem;
         }}
         .pdf-upload-divider {{
@@ -620,14 +643,14 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             align-items: center;
             gap: 1rem;
             margin: 1rem 0;
-            color: #93a1a1;
+            color: var(--base1);
             font-size: 0.8rem;
         }}
         .pdf-upload-divider::before,
         .pdf-upload-divider::after {{
             content: '';
             flex: 1;
-            border-top: 1px solid #eee8d5;
+            border-top: 1px solid var(--base2);
         }}
         .pdf-url-input {{
             display: flex;
@@ -636,14 +659,14 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         .pdf-url-input input {{
             flex: 1;
             padding: 0.6rem 0.8rem;
-            border: 1px solid #93a1a1;
+            border: 1px solid var(--base1);
             border-radius: 4px;
-            background: #fdf6e3;
-            color: #657b83;
+            background: var(--base3);
+            color: var(--base00);
             font-size: 0.9rem;
         }}
         .pdf-url-input input::placeholder {{
-            color: #93a1a1;
+            color: var(--base1);
         }}
         .pdf-upload-status {{
             margin-top: 1rem;
@@ -656,8 +679,8 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             display: block;
         }}
         .pdf-upload-status.loading {{
-            background: #eee8d5;
-            color: #657b83;
+            background: var(--base2);
+            color: var(--base00);
         }}
         .pdf-upload-status.success {{
             background: #d5e8d5;
@@ -692,6 +715,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
                 <span class="editor-status-dot"></span>
                 <span id="status-text">Ready</span>
             </div>
+            <div class="tag-suggestions" id="tag-suggestions"></div>
             <button class="btn primary" onclick="saveNote(false)">Save</button>
             <div class="pdf-status" id="pdf-status">{pdf_status_html}</div>
             <a href="#" onclick="goBack('/note/{key}')" class="btn">Done</a>
@@ -1737,42 +1761,43 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         require.config({{ paths: {{ vs: 'https://cdnjs.cloudflare.com/ajax/libs/monaco-editor/0.45.0/min/vs' }} }});
 
         require(['vs/editor/editor.main'], function() {{
-            // Define solarized-light theme
-            monaco.editor.defineTheme('solarized-light', {{
-                base: 'vs',
+            // Editor colors from the page's theme (see templates::styles)
+            const cssColor = (name) => getComputedStyle(document.documentElement).getPropertyValue('--' + name).trim().replace('#', '');
+            monaco.editor.defineTheme('notes', {{
+                base: {monaco_base},
                 inherit: true,
                 rules: [
-                    {{ token: '', foreground: '657b83', background: 'fdf6e3' }},
-                    {{ token: 'comment', foreground: '93a1a1', fontStyle: 'italic' }},
-                    {{ token: 'keyword', foreground: '859900' }},
-                    {{ token: 'string', foreground: '2aa198' }},
-                    {{ token: 'number', foreground: 'd33682' }},
-                    {{ token: 'type', foreground: 'b58900' }},
-                    {{ token: 'function', foreground: '268bd2' }},
-                    {{ token: 'variable', foreground: '268bd2' }},
-                    {{ token: 'constant', foreground: 'cb4b16' }},
-                    {{ token: 'markup.heading', foreground: 'cb4b16', fontStyle: 'bold' }},
+                    {{ token: '', foreground: cssColor('base00'), background: cssColor('base3') }},
+                    {{ token: 'comment', foreground: cssColor('base1'), fontStyle: 'italic' }},
+                    {{ token: 'keyword', foreground: cssColor('green') }},
+                    {{ token: 'string', foreground: cssColor('cyan') }},
+                    {{ token: 'number', foreground: cssColor('magenta') }},
+                    {{ token: 'type', foreground: cssColor('yellow') }},
+                    {{ token: 'function', foreground: cssColor('blue') }},
+                    {{ token: 'variable', foreground: cssColor('blue') }},
+                    {{ token: 'constant', foreground: cssColor('orange') }},
+                    {{ token: 'markup.heading', foreground: cssColor('orange'), fontStyle: 'bold' }},
                     {{ token: 'markup.bold', fontStyle: 'bold' }},
                     {{ token: 'markup.italic', fontStyle: 'italic' }},
                     {{ token: 'markup.underline', fontStyle: 'underline' }},
                 ],
                 colors: {{
-                    'editor.background': '#fdf6e3',
-                    'editor.foreground': '#657b83',
-                    'editor.lineHighlightBackground': '#eee8d5',
-                    'editor.selectionBackground': '#eee8d5',
-                    'editorCursor.foreground': '#657b83',
-                    'editorLineNumber.foreground': '#93a1a1',
-                    'editorLineNumber.activeForeground': '#657b83',
-                    'editorIndentGuide.background': '#eee8d5',
-                    'editorWhitespace.foreground': '#eee8d5',
+                    'editor.background': '#' + cssColor('base3'),
+                    'editor.foreground': '#' + cssColor('base00'),
+                    'editor.lineHighlightBackground': '#' + cssColor('base2'),
+                    'editor.selectionBackground': '#' + cssColor('base2'),
+                    'editorCursor.foreground': '#' + cssColor('base00'),
+                    'editorLineNumber.foreground': '#' + cssColor('base1'),
+                    'editorLineNumber.activeForeground': '#' + cssColor('base00'),
+                    'editorIndentGuide.background': '#' + cssColor('base2'),
+                    'editorWhitespace.foreground': '#' + cssColor('base2'),
                 }}
             }});
 
             editor = monaco.editor.create(document.getElementById('monaco-editor'), {{
                 value: {content_json},
                 language: 'markdown',
-                theme: 'solarized-light',
+                theme: 'notes',
                 fontSize: currentFontSize,
                 lineNumbers: 'on',
                 wordWrap: 'on',
@@ -1888,7 +1913,9 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
                     updateStatus('pending', 'Unsaved changes');
                     scheduleAutoSave();
                 }}
+                scheduleValidate();
             }});
+            validateNote();
 
             // Ctrl/Cmd+S to save (works alongside Emacs C-x C-s)
             editor.addCommand(monaco.KeyMod.CtrlCmd | monaco.KeyCode.KeyS, function() {{
@@ -1991,6 +2018,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
                     const timeStr = now.toLocaleTimeString('en-US', {{ hour: 'numeric', minute: '2-digit' }});
                     const commitNote = shouldCommit ? ' (committed)' : '';
                     updateStatus('saved', 'Saved at ' + timeStr + commitNote);
+                    loadTagSuggestions(false);
                 }} else {{
                     const err = await response.text();
                     updateStatus('error', 'Save failed');
@@ -2002,6 +2030,102 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
             }}
         }}
 
+        // Frontmatter diagnostics: the server's checks on the unsaved text,
+        // shown as markers; problems without a line go on line 1
+        let validateTimer = null;
+        function scheduleValidate() {{
+            if (validateTimer) clearTimeout(validateTimer);
+            validateTimer = setTimeout(validateNote, 800);
+        }}
+        async function validateNote() {{
+            if (!editor) return;
+            try {{
+                const resp = await fetch('/api/note/' + noteKey + '/validate', {{
+                    method: 'POST',
+                    headers: {{ 'Content-Type': 'application/json' }},
+                    body: JSON.stringify({{ content: editor.getValue() }})
+                }});
+                if (!resp.ok) return;
+                const data = await resp.json();
+                const model = editor.getModel();
+                const markers = data.issues.map(issue => {{
+                    const line = Math.min(issue.line || 1, model.getLineCount());
+                    return {{
+                        severity: issue.severity === 'error' ? monaco.MarkerSeverity.Error : monaco.MarkerSeverity.Warning,
+                        message: issue.message + ' [' + issue.check + ']',
+                        startLineNumber: line,
+                        startColumn: 1,
+                        endLineNumber: line,
+                        endColumn: model.getLineMaxColumn(line)
+                    }};
+                }});
+                monaco.editor.setModelMarkers(model, 'notes', markers);
+            }} catch (e) {{
+                console.error('Validate error:', e);
+            }}
+        }}
+
+        // Tag suggestions: shown after each save, a click adds the tag to
+        // the frontmatter's tags: line
+        async function loadTagSuggestions(llm) {{
+            if (window.sharedMode) return;
+            const box = document.getElementById('tag-suggestions');
+            try {{
+                const resp = await fetch('/api/note/' + noteKey + '/suggest-tags' + (llm ? '?llm=true' : ''));
+                if (!resp.ok) {{
+                    if (llm) updateStatus('error', 'Tag suggestions failed');
+                    return;
+                }}
+                const suggestions = await resp.json();
+                box.innerHTML = '';
+                if (suggestions.length > 0) box.append('Tags?');
+                for (const s of suggestions) {{
+                    const btn = document.createElement('button');
+                    btn.className = 'tag-suggestion';
+                    btn.textContent = '+ ' + s.tag;
+                    btn.title = s.reason;
+                    btn.onclick = () => {{ addTag(s.tag); btn.remove(); }};
+                    box.append(btn);
+                }}
+                if (!llm) {{
+                    const ask = document.createElement('button');
+                    ask.className = 'tag-suggestion';
+                    ask.textContent = 'Ask AI';
+                    ask.title = 'Ask the language model for tags';
+                    ask.onclick = () => {{ ask.textContent = '...'; loadTagSuggestions(true); }};
+                    box.append(ask);
+                }}
+            }} catch (e) {{
+                console.error('Tag suggestions failed:', e);
+            }}
+        }}
+
+        function addTag(tag) {{
+            if (!editor) return;
+            const lines = editor.getValue().split('\n');
+            if (lines[0] !== '---') return;
+            const end = lines.indexOf('---', 1);
+            if (end < 0) return;
+            const at = lines.slice(1, end).findIndex(l => /^tags:/.test(l)) + 1;
+            // Monaco lines are 1-based
+            let edit;
+            if (at > 0) {{
+                const tags = lines[at].slice(5).trim().replace(/^\[|\]$/g, '')
+                    .split(',').map(t => t.trim()).filter(t => t);
+                if (tags.includes(tag)) return;
+                tags.push(tag);
+                edit = {{
+                    range: new monaco.Range(at + 1, 1, at + 1, lines[at].length + 1),
+                    text: 'tags: [' + tags.join(', ') + ']',
+                }};
+            }} else {{
+                edit = {{ range: new monaco.Range(end + 1, 1, end + 1, 1), text: 'tags: [' + tag + ']\n' }};
+            }}
+            editor.executeEdits('tag-suggestion', [edit]);
+        }}
+
+        loadTagSuggestions(false);
+
         // Navigate back, auto-saving if needed
         async function goBack(url) {{
             if (hasUnsavedChanges) {{
@@ -2029,5 +2153,7 @@ pub fn render_editor(note: &Note, notes_map: &HashMap<String, Note>, _logged_in:
         pdf_filename_json = pdf_filename_json,
         pdf_status_html = pdf_status_html,
         notes_json = notes_json,
+        theme_css = theme.css(),
+        monaco_base = if theme.dark { "'vs-dark'" } else { "'vs'" },
     )
 }
@@ -5,12 +5,14 @@
 //!
 //! ## Module Structure
 //!
-//! - `styles` - CSS constants and theme definitions
+//! - `styles` - CSS constants and theme definitions (`Theme`, `THEMES`)
 //! - `components` - Shared HTML components (nav bar, Smart Add, base template)
 //! - `editor` - Monaco-based editor with PDF viewing
 //! - `viewer` - View mode template with PDF support
+//! - `custom` - `custom.css` and header/footer snippets from the data directory
 
 mod styles;
+pub mod custom;
 mod components;
 mod editor;
 pub mod shared_editor;
@@ -18,7 +20,7 @@ mod viewer;
 pub mod graph_js;
 
 // Re-export public items for backward compatibility
-pub use styles::STYLE;
+pub use styles::{find_theme, Theme, NEUTRAL, SOLARIZED_DARK, SOLARIZED_LIGHT, STYLE, THEMES};
 pub use components::{nav_bar, smart_add_html, base_html};
 pub use editor::render_editor;
 pub use viewer::render_viewer;
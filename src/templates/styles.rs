@@ -1,44 +1,134 @@
 //! CSS styles for the notes application.
 //!
-//! Contains the main STYLE constant with all CSS for the web interface.
+//! Colors are data: each `Theme` lists the values of the palette custom
+//! properties (`--base03`..`--base3`, the accent colors, and a few
+//! surfaces), and `Theme::css` turns one into a `:root` block. `STYLE`, the
+//! rest of the CSS, only refers to the properties. Solarized themes swap the
+//! base tones between light and dark, so `var(--base3)` is always the
+//! background tone; the semantic properties in `SEMANTIC_VARS` build on that.
 
 // ============================================================================
-// CSS Styles
+// Themes
 // ============================================================================
 
-pub const STYLE: &str = r#"
-/* Solarized Light Theme */
-:root {
-    --base03: #002b36;
-    --base02: #073642;
-    --base01: #586e75;
-    --base00: #657b83;
-    --base0: #839496;
-    --base1: #93a1a1;
-    --base2: #eee8d5;
-    --base3: #fdf6e3;
-
-    --yellow: #b58900;
-    --orange: #cb4b16;
-    --red: #dc322f;
-    --magenta: #d33682;
-    --violet: #6c71c4;
-    --blue: #268bd2;
-    --cyan: #2aa198;
-    --green: #859900;
-
-    --bg: var(--base3);
-    --fg: var(--base00);
-    --muted: var(--base1);
-    --border: var(--base2);
-    --link: var(--blue);
-    --link-hover: var(--cyan);
-    --accent: var(--base2);
-    --paper-bg: #f5ecd5;
-    --code-bg: var(--base2);
-    --highlight: #f7f2e2;
+/// A color theme, chosen per browser (see `crate::theme`).
+#[derive(Debug, PartialEq)]
+pub struct Theme {
+    /// Cookie and setting value.
+    pub name: &'static str,
+    pub label: &'static str,
+    pub dark: bool,
+    /// Custom properties, without the leading `--`.
+    pub vars: &'static [(&'static str, &'static str)],
+}
+
+/// Properties the CSS uses for surfaces and text, in terms of the palette.
+const SEMANTIC_VARS: &[(&str, &str)] = &[
+    ("bg", "var(--base3)"),
+    ("fg", "var(--base00)"),
+    ("muted", "var(--base1)"),
+    ("border", "var(--base2)"),
+    ("link", "var(--blue)"),
+    ("link-hover", "var(--cyan)"),
+    ("accent", "var(--base2)"),
+    ("code-bg", "var(--base2)"),
+];
+
+const SOLARIZED_ACCENTS: &[(&str, &str)] = &[
+    ("yellow", "#b58900"),
+    ("orange", "#cb4b16"),
+    ("red", "#dc322f"),
+    ("magenta", "#d33682"),
+    ("violet", "#6c71c4"),
+    ("blue", "#268bd2"),
+    ("cyan", "#2aa198"),
+    ("green", "#859900"),
+];
+
+pub const SOLARIZED_LIGHT: Theme = Theme {
+    name: "solarized-light",
+    label: "Solarized Light",
+    dark: false,
+    vars: &[
+        ("base03", "#002b36"),
+        ("base02", "#073642"),
+        ("base01", "#586e75"),
+        ("base00", "#657b83"),
+        ("base0", "#839496"),
+        ("base1", "#93a1a1"),
+        ("base2", "#eee8d5"),
+        ("base3", "#fdf6e3"),
+        ("paper-bg", "#f5ecd5"),
+        ("highlight", "#f7f2e2"),
+    ],
+};
+
+pub const SOLARIZED_DARK: Theme = Theme {
+    name: "solarized-dark",
+    label: "Solarized Dark",
+    dark: true,
+    vars: &[
+        ("base03", "#fdf6e3"),
+        ("base02", "#eee8d5"),
+        ("base01", "#93a1a1"),
+        ("base00", "#839496"),
+        ("base0", "#657b83"),
+        ("base1", "#586e75"),
+        ("base2", "#073642"),
+        ("base3", "#002b36"),
+        ("paper-bg", "#0a3440"),
+        ("highlight", "#08303b"),
+    ],
+};
+
+pub const NEUTRAL: Theme = Theme {
+    name: "neutral",
+    label: "Neutral",
+    dark: false,
+    vars: &[
+        ("base03", "#111111"),
+        ("base02", "#222222"),
+        ("base01", "#555555"),
+        ("base00", "#333333"),
+        ("base0", "#777777"),
+        ("base1", "#8a8a8a"),
+        ("base2", "#e8e8e8"),
+        ("base3", "#ffffff"),
+        ("paper-bg", "#f4f6f8"),
+        ("highlight", "#f5f5f5"),
+        ("blue", "#1f6feb"),
+        ("cyan", "#0b7285"),
+    ],
+};
+
+/// Every theme; the first is the default.
+pub const THEMES: &[&Theme] = &[&SOLARIZED_LIGHT, &SOLARIZED_DARK, &NEUTRAL];
+
+/// The theme called `name`, if there is one.
+pub fn find_theme(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().copied().find(|t| t.name == name)
+}
+
+impl Theme {
+    /// The theme's `:root` block. Its own values override the shared
+    /// accents.
+    pub fn css(&self) -> String {
+        let mut css = format!(":root {{\n    color-scheme: {};\n", if self.dark { "dark" } else { "light" });
+        let overridden = |name: &str| self.vars.iter().any(|(n, _)| *n == name);
+        let accents = SOLARIZED_ACCENTS.iter().filter(|(n, _)| !overridden(n));
+        for (name, value) in self.vars.iter().chain(accents).chain(SEMANTIC_VARS) {
+            css.push_str(&format!("    --{}: {};\n", name, value));
+        }
+        css.push_str("}\n");
+        css
+    }
 }
 
+// ============================================================================
+// CSS Styles
+// ============================================================================
+
+pub const STYLE: &str = r#"
 * { box-sizing: border-box; margin: 0; padding: 0; }
 
 body {
@@ -110,6 +200,15 @@ h1 { font-size: 1.5rem; }
     font-size: 0.9rem;
 }
 
+.theme-select {
+    padding: 0.3rem 0.4rem;
+    border: 1px solid var(--border);
+    border-radius: 4px;
+    background: var(--bg);
+    color: var(--fg);
+    font-size: 0.8rem;
+}
+
 .note-list { list-style: none; }
 
 .note-item {
@@ -251,12 +350,21 @@ h1 { font-size: 1.5rem; }
 .sub-notes { margin-top: 1rem; padding-top: 1rem; border-top: 1px solid var(--border); }
 .sub-notes h3 { font-size: 1rem; margin-top: 0; }
 
+.link-card { display: inline-flex; align-items: center; gap: 0.6rem; max-width: 100%; padding: 0.5rem 0.75rem; border: 1px solid var(--border); border-radius: 6px; color: var(--fg); }
+.link-card:hover { background: var(--accent); text-decoration: none; }
+.link-card-icon { flex-shrink: 0; }
+.link-card-text { display: flex; flex-direction: column; min-width: 0; }
+.link-card-title { font-weight: 600; overflow: hidden; text-overflow: ellipsis; white-space: nowrap; }
+.link-card-site { font-size: 0.8rem; color: var(--muted); }
+
 .time-summary { margin-top: 2rem; }
 .time-bar { display: flex; height: 24px; border-radius: 4px; overflow: hidden; margin: 0.5rem 0; }
 .time-segment { height: 100%; }
 .time-legend { display: flex; flex-wrap: wrap; gap: 1rem; font-size: 0.8rem; margin-top: 0.5rem; }
 .time-legend-item { display: flex; align-items: center; gap: 0.3rem; }
 .time-legend-color { width: 12px; height: 12px; border-radius: 2px; }
+.time-breadcrumb { font-size: 0.85rem; color: var(--muted); }
+.invoice-filter { display: flex; flex-wrap: wrap; gap: 0.5rem; align-items: center; margin: 1rem 0; font-size: 0.85rem; }
 
 .cat-programming { background: var(--blue); }
 .cat-teaching { background: var(--green); }
@@ -291,7 +399,7 @@ h1 { font-size: 1.5rem; }
     right: 0;
     bottom: 0;
     z-index: 500;
-    background: #fdf6e3; /* solarized-light base3 */
+    background: var(--bg);
 }
 
 .editor-header {
@@ -300,8 +408,8 @@ h1 { font-size: 1.5rem; }
     left: 0;
     right: 0;
     height: 48px;
-    background: #eee8d5; /* solarized-light base2 */
-    border-bottom: 1px solid #93a1a1;
+    background: var(--accent);
+    border-bottom: 1px solid var(--base1);
     display: flex;
     align-items: center;
     padding: 0 1rem;
@@ -313,7 +421,7 @@ h1 { font-size: 1.5rem; }
     margin: 0;
     font-size: 1rem;
     font-weight: 500;
-    color: #657b83; /* solarized-light base00 */
+    color: var(--fg);
     flex: 1;
     white-space: nowrap;
     overflow: hidden;
@@ -327,16 +435,16 @@ h1 { font-size: 1.5rem; }
 
 .editor-status {
     font-size: 0.8rem;
-    color: #93a1a1; /* solarized-light base1 */
+    color: var(--muted);
     display: flex;
     align-items: center;
     gap: 0.5rem;
 }
 
-.editor-status.saving { color: #268bd2; } /* solarized blue */
-.editor-status.saved { color: #859900; } /* solarized green */
-.editor-status.error { color: #dc322f; } /* solarized red */
-.editor-status.pending { color: #b58900; } /* solarized yellow */
+.editor-status.saving { color: var(--blue); }
+.editor-status.saved { color: var(--green); }
+.editor-status.error { color: var(--red); }
+.editor-status.pending { color: var(--yellow); }
 
 .editor-status-dot {
     width: 8px;
@@ -901,4 +1009,35 @@ h1 { font-size: 1.5rem; }
     color: var(--fg);
     text-decoration: none;
 }
+
+.recently-viewed {
+    margin-bottom: 1.5rem;
+}
+
+.browse-section {
+    margin: 0.25rem 0 0.25rem 1rem;
+}
+.browse-section > summary {
+    cursor: pointer;
+    font-weight: 600;
+    padding: 0.2rem 0;
+}
+.stats-table td:first-child {
+    white-space: nowrap;
+}
+.stats-bar-cell {
+    width: 60%;
+}
+.stats-bar {
+    height: 0.7rem;
+    min-width: 1px;
+    background: var(--blue);
+    border-radius: 2px;
+}
+
+.browse-count {
+    color: var(--muted);
+    font-size: 0.75rem;
+    font-weight: normal;
+}
 "#;
@@ -2,6 +2,7 @@
 //!
 //! Contains the view mode with PDF viewing support.
 
+use crate::auth::is_published;
 use crate::models::Note;
 use crate::notes::html_escape;
 use super::graph_js::{render_graph_js, graph_css, GraphRendererConfig, GraphDataSource};
@@ -10,6 +11,7 @@ use super::graph_js::{render_graph_js, graph_css, GraphRendererConfig, GraphData
 // Viewer Template (View mode with PDF support)
 // ============================================================================
 
+#[allow(clippy::too_many_arguments)]
 pub fn render_viewer(
     note: &Note,
     rendered_content: &str,
@@ -20,12 +22,14 @@ pub fn render_viewer(
     logged_in: bool,
     is_paper: bool,
 ) -> String {
-    let pdf_filename = note.pdf.as_deref().unwrap_or("");
+    // `notes publish` doesn't serve /pdfs
+    let pdf = note.pdf.as_deref().filter(|_| !is_published());
+    let pdf_filename = pdf.unwrap_or("");
     let pdf_filename_json = serde_json::to_string(pdf_filename)
         .unwrap_or_else(|_| "\"\"".to_string());
-    let has_pdf = note.pdf.is_some();
+    let has_pdf = pdf.is_some();
 
-    let pdf_status_html = if let Some(ref pdf) = note.pdf {
+    let pdf_status_html = if let Some(pdf) = pdf {
         let unlink_btn = if logged_in {
             r#" <button class="pdf-toggle-btn" onclick="unlinkPdf()" title="Remove PDF link from this note">Unlink</button>"#
         } else {
@@ -38,13 +42,19 @@ pub fn render_viewer(
         } else {
             ""
         };
+        let summarize_btn = if logged_in {
+            r#" <button class="pdf-toggle-btn" onclick="summarizeNote('pdf', this)" title="Add an AI summary of the PDF">Summarize</button>"#
+        } else {
+            ""
+        };
         format!(
             r#"<a href="/pdfs/{}" target="_blank">📄 {}</a>
-               <button class="pdf-toggle-btn" id="pdf-toggle-btn" onclick="togglePdfViewer()">View PDF</button>{}{}"#,
+               <button class="pdf-toggle-btn" id="pdf-toggle-btn" onclick="togglePdfViewer()">View PDF</button>{}{}{}"#,
             html_escape(pdf),
             html_escape(pdf),
             unlink_btn,
-            cite_btn
+            cite_btn,
+            summarize_btn
         )
     } else if is_paper && logged_in {
         r#"<button class="pdf-toggle-btn" id="pdf-toggle-btn" onclick="togglePdfViewer()">Find PDF</button> <button class="pdf-toggle-btn" onclick="openCitationManager()" title="Manage citations manually">Cite</button>"#.to_string()
@@ -60,6 +70,7 @@ pub fn render_viewer(
                 <button class="active">View</button>
                 <button onclick="window.location.href='/note/{}?edit=true'">Edit</button>
                 <button onclick="openSharePanel('{}')" title="Create collaborative copy">Share</button>
+                <button onclick="attachAudio()" title="Attach an audio memo">Audio</button>
                 <button class="delete-btn" onclick="confirmDelete('{}', '{}')">Delete</button>
             </div>"#,
             note.key,
@@ -70,6 +81,7 @@ pub fn render_viewer(
     } else {
         String::new()
     };
+    let custom = super::custom::current();
 
     format!(
         r##"<!DOCTYPE html>
@@ -79,32 +91,7 @@ pub fn render_viewer(
     <meta name="viewport" content="width=device-width, initial-scale=1.0, maximum-scale=1.0, user-scalable=no">
     <title>{title}</title>
     <style>
-        :root {{
-            --base03: #002b36;
-            --base02: #073642;
-            --base01: #586e75;
-            --base00: #657b83;
-            --base0: #839496;
-            --base1: #93a1a1;
-            --base2: #eee8d5;
-            --base3: #fdf6e3;
-            --yellow: #b58900;
-            --orange: #cb4b16;
-            --red: #dc322f;
-            --magenta: #d33682;
-            --violet: #6c71c4;
-            --blue: #268bd2;
-            --cyan: #2aa198;
-            --green: #859900;
-            --bg: var(--base3);
-            --fg: var(--base00);
-            --muted: var(--base1);
-            --border: var(--base2);
-            --link: var(--blue);
-            --link-hover: var(--cyan);
-            --accent: var(--base2);
-            --code-bg: var(--base2);
-        }}
+        {theme_css}
 
         * {{ box-sizing: border-box; margin: 0; padding: 0; }}
         body {{
@@ -1012,9 +999,11 @@ pub fn render_viewer(
             font-size: 0.8rem;
             color: var(--muted);
         }}
+        {custom_css}
     </style>
 </head>
 <body>
+    {custom_header}
     <div class="viewer-container">
         <div class="viewer-header">
             <a href="/" class="back-link">&larr; All Notes</a>
@@ -2456,6 +2445,7 @@ pub fn render_viewer(
         }});
     </script>
     {mini_graph_script}
+    {custom_footer}
 </body>
 </html>"##,
         title = html_escape(&note.title),
@@ -2486,5 +2476,9 @@ pub fn render_viewer(
             },
             notes_json: None,
         }),
+        theme_css = crate::theme::current().css(),
+        custom_css = custom.css,
+        custom_header = custom.header,
+        custom_footer = custom.footer,
     )
 }
@@ -0,0 +1,55 @@
+//! Fixtures shared by the unit tests.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::AppState;
+
+/// An empty scratch directory under the system temp directory, deleted on
+/// drop, so a failing assertion doesn't leave it behind.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// A new directory named `notes-<name>-test-<pid>-<random>`.
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "notes-{}-test-{}-{:08x}",
+            name,
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Server state whose notes, PDFs and database all live in `dir`.
+pub fn app_state(dir: &Path) -> AppState {
+    AppState::new(Config {
+        notes_dir: dir.join("content"),
+        pdfs_dir: dir.join("pdfs"),
+        db_path: dir.join("db"),
+        ..Default::default()
+    })
+}
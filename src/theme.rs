@@ -0,0 +1,136 @@
+//! Theme selection.
+//!
+//! The theme is picked per browser from the nav bar and kept in the `theme`
+//! cookie. A logged-in pick is also saved in sled as the default for
+//! browsers without the cookie (visitors to public notes, a new device).
+//! The `select_theme` middleware resolves the theme once per request and
+//! scopes it to the handler, so templates read it with `current` without
+//! threading it through every page function. The themes themselves are
+//! data in `templates::styles`.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::templates::{find_theme, Theme, THEMES};
+use crate::AppState;
+
+pub const COOKIE: &str = "theme";
+
+const SETTINGS_TREE: &str = "settings";
+const DEFAULT_KEY: &str = "theme";
+
+tokio::task_local! {
+    static THEME: &'static Theme;
+}
+
+/// The theme of the request being handled; the first theme outside one.
+pub fn current() -> &'static Theme {
+    THEME.try_with(|t| *t).unwrap_or(THEMES[0])
+}
+
+/// The saved default theme, if one was picked while logged in.
+pub fn saved_default(db: &crate::store::Db) -> Option<&'static Theme> {
+    let value = db.open_tree(SETTINGS_TREE).ok()?.get(DEFAULT_KEY).ok()??;
+    find_theme(std::str::from_utf8(&value).ok()?)
+}
+
+fn save_default(db: &crate::store::Db, theme: &Theme) -> Result<(), String> {
+    let tree = db.open_tree(SETTINGS_TREE).map_err(|e| e.to_string())?;
+    tree.insert(DEFAULT_KEY, theme.name.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// The cookie's theme, else the saved default, else the first.
+pub fn resolve(cookie: Option<&str>, db: &crate::store::Db) -> &'static Theme {
+    cookie.and_then(find_theme).or_else(|| saved_default(db)).unwrap_or(THEMES[0])
+}
+
+/// Middleware: resolve the request's theme for `current`.
+pub async fn select_theme(State(state): State<Arc<AppState>>, jar: CookieJar, request: Request, next: Next) -> Response {
+    let theme = resolve(jar.get(COOKIE).map(|c| c.value()), &state.db);
+    THEME.scope(theme, next.run(request)).await
+}
+
+/// The nav bar's theme menu. Picking a theme sets the cookie (which works
+/// in read-only mode too) and, when logged in, saves it as the default.
+pub fn picker_html(logged_in: bool) -> String {
+    let current = current();
+    let options: String = THEMES
+        .iter()
+        .map(|t| {
+            let selected = if t.name == current.name { " selected" } else { "" };
+            format!(r#"<option value="{}"{}>{}</option>"#, t.name, selected, t.label)
+        })
+        .collect();
+    format!(
+        r#"<select class="theme-select" title="Theme" aria-label="Theme" onchange="setTheme(this.value)">{options}</select>
+<script>
+async function setTheme(name) {{
+    document.cookie = '{COOKIE}=' + name + ';path=/;max-age=31536000;SameSite=Lax';
+    if ({logged_in}) {{
+        await fetch('/api/theme', {{method: 'POST', headers: {{'Content-Type': 'application/json'}}, body: JSON.stringify({{theme: name}})}});
+    }}
+    location.reload();
+}}
+</script>"#
+    )
+}
+
+#[derive(Deserialize)]
+pub struct ThemeRequest {
+    pub theme: String,
+}
+
+/// `POST /api/theme`: save `{theme}` as the default theme.
+pub async fn set_default_theme(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<ThemeRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let Some(theme) = find_theme(&req.theme) else {
+        return (StatusCode::BAD_REQUEST, format!("Unknown theme '{}'", req.theme)).into_response();
+    };
+    match save_default(&state.db, theme) {
+        Ok(()) => axum::Json(serde_json::json!({ "theme": theme.name })).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::{NEUTRAL, SOLARIZED_DARK, SOLARIZED_LIGHT};
+
+    #[test]
+    fn test_resolve_theme() {
+        let db = crate::store::Db::temporary();
+        assert_eq!(resolve(None, &db), &SOLARIZED_LIGHT);
+        assert_eq!(resolve(Some("solarized-dark"), &db), &SOLARIZED_DARK);
+        assert_eq!(resolve(Some("no-such-theme"), &db), &SOLARIZED_LIGHT);
+        save_default(&db, &NEUTRAL).unwrap();
+        assert_eq!(resolve(None, &db), &NEUTRAL);
+        assert_eq!(resolve(Some("solarized-dark"), &db), &SOLARIZED_DARK);
+
+        let css = SOLARIZED_DARK.css();
+        assert!(css.contains("color-scheme: dark;"));
+        assert!(css.contains("--base3: #002b36;"));
+        assert!(css.contains("--bg: var(--base3);"));
+        // Neutral's own blue replaces the Solarized one
+        let css = NEUTRAL.css();
+        assert_eq!(css.matches("--blue:").count(), 1);
+        assert!(css.contains("--blue: #1f6feb;"));
+        assert!(css.contains("--red: #dc322f;"));
+    }
+}
@@ -0,0 +1,178 @@
+//! User-defined time tracking categories.
+//!
+//! Categories and their display colors come from `[[time_categories]]` in
+//! `notes.toml`, or else `time_categories.conf` (one `name: color` pair per
+//! line, `#` comments). When neither defines any, the built-in Solarized
+//! palette is used. The resulting list drives the `/time` page and
+//! normalizes the graph's `category:` filter; the frontmatter parser itself
+//! accepts any name.
+
+use std::fs;
+use std::path::Path;
+
+use crate::models::{category_slug, TimeCategory, TimeCategoryDef};
+
+/// The built-in categories, in display order.
+pub fn default_time_categories() -> Vec<TimeCategoryDef> {
+    [
+        ("programming", "var(--blue)"),
+        ("teaching", "var(--green)"),
+        ("reading", "var(--orange)"),
+        ("writing", "var(--magenta)"),
+        ("service", "var(--base1)"),
+        ("other", "var(--base0)"),
+    ]
+    .iter()
+    .map(|(name, color)| TimeCategoryDef {
+        name: name.to_string(),
+        color: color.to_string(),
+        weekly_hours: None,
+    })
+    .collect()
+}
+
+/// Load the category list from `path`, falling back to the defaults when
+/// the file is missing or defines no categories.
+pub fn load_time_categories(path: &Path) -> Vec<TimeCategoryDef> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let defs = parse_time_categories(&text);
+            if defs.is_empty() {
+                tracing::warn!(path = %path.display(), "No time categories defined, using defaults");
+                default_time_categories()
+            } else {
+                defs
+            }
+        }
+        Err(_) => default_time_categories(),
+    }
+}
+
+/// Parse `name: color` lines. Names are lowercased; lines with an unsafe
+/// color value are skipped so the value can be emitted into a style block.
+pub fn parse_time_categories(text: &str) -> Vec<TimeCategoryDef> {
+    let pairs = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| match l.split_once(':') {
+            Some((n, c)) => (n.trim(), c.trim(), None),
+            None => (l, "", None),
+        });
+    normalize_definitions(pairs)
+}
+
+/// Categories from the `[[time_categories]]` config table take precedence
+/// over `time_categories.conf`.
+pub fn resolve_time_categories(configured: &[TimeCategoryDef], path: &Path) -> Vec<TimeCategoryDef> {
+    let defs = normalize_definitions(
+        configured
+            .iter()
+            .map(|d| (d.name.as_str(), d.color.as_str(), d.weekly_hours)),
+    );
+    if defs.is_empty() {
+        load_time_categories(path)
+    } else {
+        defs
+    }
+}
+
+fn normalize_definitions<'a>(
+    entries: impl Iterator<Item = (&'a str, &'a str, Option<f64>)>,
+) -> Vec<TimeCategoryDef> {
+    let mut defs: Vec<TimeCategoryDef> = Vec::new();
+
+    for (name, color, weekly_hours) in entries {
+        let name = TimeCategory::from_name(name).to_string();
+        if name.is_empty() || defs.iter().any(|d| d.name == name) {
+            continue;
+        }
+
+        let color = if color.is_empty() {
+            "var(--base0)".to_string()
+        } else if is_safe_color(color) {
+            color.to_string()
+        } else {
+            tracing::warn!(category = %name, color, "Ignoring time category with invalid color");
+            continue;
+        };
+
+        let weekly_hours = match weekly_hours {
+            Some(h) if !(h.is_finite() && h > 0.0) => {
+                tracing::warn!(category = %name, weekly_hours = h, "Ignoring invalid weekly goal");
+                None
+            }
+            h => h,
+        };
+
+        defs.push(TimeCategoryDef { name, color, weekly_hours });
+    }
+
+    defs
+}
+
+/// Only allow characters that can appear in hex, rgb()/hsl() and var() colors.
+fn is_safe_color(color: &str) -> bool {
+    color
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "#(),.%- ".contains(c))
+}
+
+/// Find the configured definition for a category, if any.
+pub fn find_category<'a>(
+    defs: &'a [TimeCategoryDef],
+    category: &TimeCategory,
+) -> Option<&'a TimeCategoryDef> {
+    let name = category.to_string();
+    defs.iter().find(|d| d.name == name)
+}
+
+/// Generate `.cat-{slug}` rules for every configured category.
+pub fn category_css(defs: &[TimeCategoryDef]) -> String {
+    defs.iter()
+        .map(|d| format!(".cat-{} {{ background: {}; }}\n", category_slug(&d.name), d.color))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_categories() {
+        let defs = parse_time_categories(
+            "# my categories\nResearch: #268bd2\n\nadmin: rgb(1, 2, 3)\nteaching\n",
+        );
+        assert_eq!(defs.len(), 3);
+        assert_eq!(defs[0].name, "research");
+        assert_eq!(defs[0].color, "#268bd2");
+        assert_eq!(defs[1].color, "rgb(1, 2, 3)");
+        assert_eq!(defs[2].name, "teaching");
+        assert_eq!(defs[2].color, "var(--base0)");
+    }
+
+    #[test]
+    fn test_parse_rejects_unsafe_color_and_duplicates() {
+        let defs = parse_time_categories("a: red; } body { display:none\nb: red\nB: blue\n");
+        assert_eq!(defs.len(), 1);
+        assert_eq!(defs[0].name, "b");
+        assert_eq!(defs[0].color, "red");
+    }
+
+    #[test]
+    fn test_from_name_is_case_insensitive() {
+        assert_eq!(TimeCategory::from_name(" Reading "), TimeCategory::Reading);
+        assert_eq!(
+            TimeCategory::from_name("Grant Writing"),
+            TimeCategory::Other("grant writing".to_string())
+        );
+        assert_eq!(TimeCategory::from_name("Grant Writing").css_slug(), "grant-writing");
+    }
+
+    #[test]
+    fn test_category_css() {
+        let css = category_css(&default_time_categories());
+        assert!(css.contains(".cat-programming { background: var(--blue); }"));
+        assert!(css.contains(".cat-other { background: var(--base0); }"));
+    }
+}
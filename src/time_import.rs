@@ -0,0 +1,550 @@
+//! Time entry import from Toggl Track and Clockify CSV exports.
+//!
+//! Each row is matched to a note by comparing its project (then task,
+//! client, and tags) against note titles. Matched rows are appended to the
+//! note's `time:` block; rows that match nothing, or more than one note,
+//! are reported back instead of guessed at.
+
+use std::collections::HashMap;
+use std::fs;
+use std::process::Command;
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDate;
+
+use crate::auth::is_logged_in;
+use crate::models::{
+    Note, TimeCategory, TimeCategoryDef, TimeEntry, TimeImportMatch, TimeImportRequest,
+    TimeImportResult, TimeImportUnmatched,
+};
+use crate::notes::{append_time_entry, normalize_title};
+use crate::templates::base_html;
+use crate::AppState;
+
+// ============================================================================
+// CSV Parsing
+// ============================================================================
+
+/// Parse RFC 4180 CSV (quoted fields, doubled quotes, embedded newlines).
+/// Returns each record with the 1-based line number it started on.
+pub fn parse_csv(text: &str) -> Vec<(usize, Vec<String>)> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push((record_line, std::mem::take(&mut record)));
+                } else {
+                    record.clear();
+                }
+                line += 1;
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+
+    records
+}
+
+// ============================================================================
+// Row Interpretation
+// ============================================================================
+
+/// A CSV row reduced to the fields needed to build a time entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub line: usize,
+    pub project: String,
+    pub task: String,
+    pub client: String,
+    pub tags: Vec<String>,
+    pub description: String,
+    pub date: NaiveDate,
+    pub minutes: u32,
+}
+
+fn parse_import_date(s: &str) -> Option<NaiveDate> {
+    let s = s.trim();
+    ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"]
+        .iter()
+        .find_map(|fmt| NaiveDate::parse_from_str(s, fmt).ok())
+}
+
+/// Parse `HH:MM:SS` / `HH:MM` durations, rounding to the nearest minute.
+fn parse_clock_minutes(s: &str) -> Option<u32> {
+    let parts: Vec<u32> = s
+        .trim()
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let seconds = match parts.as_slice() {
+        [h, m, s] => h * 3600 + m * 60 + s,
+        [h, m] => h * 3600 + m * 60,
+        _ => return None,
+    };
+    Some((seconds + 30) / 60)
+}
+
+/// Interpret parsed CSV records using the header row. Rows that can't be
+/// read are returned as errors rather than dropped.
+pub fn interpret_rows(
+    records: &[(usize, Vec<String>)],
+) -> Result<(Vec<ImportRow>, Vec<String>), String> {
+    let (_, header) = records.first().ok_or("CSV is empty")?;
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    let col = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|n| header.iter().position(|h| h == n))
+    };
+
+    let project_col = col(&["project"]).ok_or("CSV has no Project column")?;
+    let date_col = col(&["start date"]).ok_or("CSV has no Start date column")?;
+    let clock_col = col(&["duration", "duration (h)"]);
+    let decimal_col = col(&["duration (decimal)"]);
+    if clock_col.is_none() && decimal_col.is_none() {
+        return Err("CSV has no Duration column".to_string());
+    }
+    let task_col = col(&["task"]);
+    let client_col = col(&["client"]);
+    let tags_col = col(&["tags"]);
+    let desc_col = col(&["description"]);
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line, record) in &records[1..] {
+        let get = |c: Option<usize>| {
+            c.and_then(|i| record.get(i))
+                .map(|v| v.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let date_str = get(Some(date_col));
+        let Some(date) = parse_import_date(&date_str) else {
+            errors.push(format!("Line {}: unrecognized date '{}'", line, date_str));
+            continue;
+        };
+
+        let minutes = clock_col
+            .and_then(|_| parse_clock_minutes(&get(clock_col)))
+            .or_else(|| {
+                get(decimal_col)
+                    .parse::<f64>()
+                    .ok()
+                    .map(|h| (h * 60.0).round() as u32)
+            });
+        let Some(minutes) = minutes else {
+            errors.push(format!("Line {}: unrecognized duration", line));
+            continue;
+        };
+
+        rows.push(ImportRow {
+            line: *line,
+            project: get(Some(project_col)),
+            task: get(task_col),
+            client: get(client_col),
+            tags: get(tags_col)
+                .split(',')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            description: get(desc_col),
+            date,
+            minutes,
+        });
+    }
+
+    Ok((rows, errors))
+}
+
+// ============================================================================
+// Matching
+// ============================================================================
+
+enum RowMatch<'a> {
+    Note(&'a Note),
+    Ambiguous(String),
+    None,
+}
+
+/// Match a row against note titles, trying project, task, client, then tags.
+fn match_row<'a>(row: &ImportRow, by_title: &HashMap<String, Vec<&'a Note>>) -> RowMatch<'a> {
+    let candidates = [&row.project, &row.task, &row.client]
+        .into_iter()
+        .chain(row.tags.iter());
+
+    for name in candidates {
+        let norm = normalize_title(name);
+        if norm.is_empty() {
+            continue;
+        }
+        match by_title.get(&norm).map(|v| v.as_slice()) {
+            Some([note]) => return RowMatch::Note(note),
+            Some(notes) if notes.len() > 1 => {
+                return RowMatch::Ambiguous(format!("'{}' matches {} notes", name, notes.len()))
+            }
+            _ => {}
+        }
+    }
+    RowMatch::None
+}
+
+/// The first tag that names a configured category, otherwise `other`.
+fn row_category(row: &ImportRow, categories: &[TimeCategoryDef]) -> TimeCategory {
+    row.tags
+        .iter()
+        .map(|t| TimeCategory::from_name(t))
+        .find(|c| categories.iter().any(|d| d.name == c.to_string()))
+        .unwrap_or_else(|| TimeCategory::from_name("other"))
+}
+
+/// Build the import plan: which entries go to which note, which rows are
+/// duplicates of existing entries, and which could not be matched.
+pub fn plan_import(
+    rows: &[ImportRow],
+    notes: &[Note],
+    categories: &[TimeCategoryDef],
+) -> TimeImportResult {
+    let mut by_title: HashMap<String, Vec<&Note>> = HashMap::new();
+    for note in notes {
+        by_title
+            .entry(normalize_title(&note.title))
+            .or_default()
+            .push(note);
+    }
+
+    let mut result = TimeImportResult::default();
+
+    for row in rows {
+        let note = match match_row(row, &by_title) {
+            RowMatch::Note(n) => n,
+            RowMatch::Ambiguous(reason) => {
+                result.unmatched.push(TimeImportUnmatched {
+                    row: row.line,
+                    project: row.project.clone(),
+                    reason,
+                });
+                continue;
+            }
+            RowMatch::None => {
+                result.unmatched.push(TimeImportUnmatched {
+                    row: row.line,
+                    project: row.project.clone(),
+                    reason: "no note with a matching title".to_string(),
+                });
+                continue;
+            }
+        };
+
+        if row.minutes == 0 {
+            result.unmatched.push(TimeImportUnmatched {
+                row: row.line,
+                project: row.project.clone(),
+                reason: "zero duration".to_string(),
+            });
+            continue;
+        }
+
+        let entry = TimeEntry {
+            date: row.date,
+            minutes: row.minutes,
+            category: row_category(row, categories),
+            description: Some(row.description.clone()).filter(|d| !d.is_empty()),
+        };
+
+        let is_duplicate = note.time_entries.iter().any(|e| {
+            e.date == entry.date && e.minutes == entry.minutes && e.description == entry.description
+        });
+        if is_duplicate {
+            result.duplicates += 1;
+            continue;
+        }
+
+        result.imported.push(TimeImportMatch {
+            row: row.line,
+            note_key: note.key.clone(),
+            note_title: note.title.clone(),
+            entry,
+        });
+    }
+
+    result
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+pub async fn time_import(
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(body): axum::Json<TimeImportRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let records = parse_csv(&body.csv);
+    let (rows, errors) = match interpret_rows(&records) {
+        Ok(r) => r,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let notes = state.load_notes();
+    let mut result = plan_import(&rows, &notes, &state.time_categories);
+    result.errors = errors;
+
+    if body.dry_run || result.imported.is_empty() {
+        return axum::Json(result).into_response();
+    }
+
+    // Group entries per note so each file is rewritten once
+    let mut by_note: HashMap<String, Vec<TimeEntry>> = HashMap::new();
+    for m in &result.imported {
+        by_note
+            .entry(m.note_key.clone())
+            .or_default()
+            .push(m.entry.clone());
+    }
+
+    let notes_map = state.notes_map();
+    let mut written = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+
+    for (key, entries) in &by_note {
+        let Some(note) = notes_map.get(key) else {
+            continue;
+        };
+        let full_path = state.notes_dir.join(&note.path);
+        let outcome = fs::read_to_string(&full_path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                entries
+                    .iter()
+                    .try_fold(content, |c, entry| append_time_entry(&c, entry))
+            })
+            .and_then(|content| crate::encryption::write_file(&full_path, &content).map(drop));
+
+        match outcome {
+            Ok(()) => written.push(note.path.clone()),
+            Err(e) => {
+                result
+                    .errors
+                    .push(format!("Failed to update {}: {}", note.title, e));
+                failed.push(key.clone());
+            }
+        }
+    }
+    result.imported.retain(|m| !failed.contains(&m.note_key));
+
+    if !written.is_empty() {
+        state.invalidate_notes_cache();
+        for key in by_note.keys() {
+            state.reindex_graph_note(key);
+        }
+
+        let notes_dir = state.notes_dir.clone();
+        let count = result.imported.len();
+        state.spawn_background(move || {
+            let now = crate::timezone::now();
+            let commit_msg = format!(
+                "imported {} time entries: {}",
+                count,
+                now.format("%a %b %d, %-I:%M%p")
+            );
+            for path in &written {
+                let _ = Command::new("git")
+                    .args(["add", &path.to_string_lossy()])
+                    .current_dir(&notes_dir)
+                    .output();
+            }
+            let _ = Command::new("git")
+                .args(["commit", "-m", &commit_msg])
+                .current_dir(&notes_dir)
+                .output();
+        });
+    }
+
+    axum::Json(result).into_response()
+}
+
+pub async fn time_import_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let html = r##"<h1>Import Time Entries</h1>
+        <p class="time-breadcrumb">Paste or upload a Toggl Track or Clockify CSV export.
+        Rows are matched to notes by project, task, client, or tag name.</p>
+        <input type="file" id="csv-file" accept=".csv,text/csv">
+        <textarea id="csv-text" rows="10" style="width: 100%; margin-top: 0.5rem;"></textarea>
+        <div class="invoice-filter">
+            <button onclick="runImport(true)">Preview</button>
+            <button onclick="runImport(false)">Import</button>
+        </div>
+        <div id="import-result"></div>
+        <script>
+        document.getElementById('csv-file').addEventListener('change', async (e) => {
+            const file = e.target.files[0];
+            if (file) document.getElementById('csv-text').value = await file.text();
+        });
+        function esc(s) {
+            const d = document.createElement('div');
+            d.textContent = s == null ? '' : String(s);
+            return d.innerHTML;
+        }
+        async function runImport(dryRun) {
+            const out = document.getElementById('import-result');
+            const resp = await fetch('/api/time/import', {
+                method: 'POST',
+                headers: {'Content-Type': 'application/json'},
+                body: JSON.stringify({csv: document.getElementById('csv-text').value, dry_run: dryRun})
+            });
+            if (!resp.ok) { out.innerHTML = '<p>' + esc(await resp.text()) + '</p>'; return; }
+            const r = await resp.json();
+            let html = '<h2>' + (dryRun ? 'Would import ' : 'Imported ') + r.imported.length + ' entries</h2>';
+            if (r.duplicates) html += '<p>' + r.duplicates + ' duplicate rows skipped.</p>';
+            html += '<table class="time-table"><tr><th>Line</th><th>Note</th><th>Date</th><th>Minutes</th><th>Category</th><th>Description</th></tr>';
+            for (const m of r.imported) {
+                html += '<tr><td>' + m.row + '</td><td><a href="/note/' + esc(m.note_key) + '">' + esc(m.note_title) + '</a></td><td>' +
+                    esc(m.entry.date) + '</td><td>' + m.entry.minutes + '</td><td>' + esc(typeof m.entry.category === 'string' ? m.entry.category : m.entry.category.other) +
+                    '</td><td>' + esc(m.entry.description || '-') + '</td></tr>';
+            }
+            html += '</table>';
+            if (r.unmatched.length) {
+                html += '<h2>Unmatched rows</h2><table class="time-table"><tr><th>Line</th><th>Project</th><th>Reason</th></tr>';
+                for (const u of r.unmatched) {
+                    html += '<tr><td>' + u.row + '</td><td>' + esc(u.project) + '</td><td>' + esc(u.reason) + '</td></tr>';
+                }
+                html += '</table>';
+            }
+            for (const e of r.errors) html += '<p>' + esc(e) + '</p>';
+            out.innerHTML = html;
+        }
+        </script>"##;
+
+    Html(base_html("Import Time", html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time_categories::default_time_categories;
+    use std::path::PathBuf;
+
+    fn note(key: &str, title: &str) -> Note {
+        Note {
+            key: key.to_string(),
+            path: PathBuf::from(format!("{}.md", key)),
+            title: title.to_string(),
+            modified: chrono::Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    const TOGGL: &str = "\u{feff}User,Email,Client,Project,Task,Description,Billable,Start date,Start time,End date,End time,Duration,Tags\r\n\
+        Me,me@x.org,,Datalog Engine,,\"Fix joins, again\",No,2024-03-01,09:00:00,2024-03-01,10:30:00,01:30:00,\"Programming, urgent\"\r\n\
+        Me,me@x.org,,Unknown,,Misc,No,2024-03-02,09:00:00,2024-03-02,09:10:00,00:10:00,\r\n";
+
+    #[test]
+    fn test_parse_csv_quotes_and_newlines() {
+        let records = parse_csv("a,b\n\"x, \"\"y\"\"\",\"multi\nline\"\nlast,row");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].1, vec!["x, \"y\"", "multi\nline"]);
+        assert_eq!(records[2].0, 4);
+    }
+
+    #[test]
+    fn test_toggl_rows_match_by_project() {
+        let (rows, errors) = interpret_rows(&parse_csv(TOGGL)).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(rows[0].minutes, 90);
+        assert_eq!(rows[0].tags, vec!["Programming", "urgent"]);
+
+        let notes = vec![note("dl", "Datalog engine")];
+        let result = plan_import(&rows, &notes, &default_time_categories());
+        assert_eq!(result.imported.len(), 1);
+        assert_eq!(result.imported[0].note_key, "dl");
+        assert_eq!(result.imported[0].entry.category, TimeCategory::Programming);
+        assert_eq!(
+            result.imported[0].entry.description.as_deref(),
+            Some("Fix joins, again")
+        );
+        assert_eq!(result.unmatched.len(), 1);
+        assert_eq!(result.unmatched[0].row, 3);
+    }
+
+    #[test]
+    fn test_clockify_decimal_duration_and_duplicates() {
+        let csv = "Project,Client,Description,Tags,Start Date,Duration (decimal)\n\
+                   Acme,,Review,,03/04/2024,0.25\n";
+        let (rows, _) = interpret_rows(&parse_csv(csv)).unwrap();
+        assert_eq!(rows[0].minutes, 15);
+        assert_eq!(rows[0].date, NaiveDate::from_ymd_opt(2024, 3, 4).unwrap());
+
+        let mut acme = note("acme", "Acme");
+        acme.time_entries.push(TimeEntry {
+            date: rows[0].date,
+            minutes: 15,
+            category: TimeCategory::from_name("other"),
+            description: Some("Review".to_string()),
+        });
+        let result = plan_import(&rows, &[acme], &default_time_categories());
+        assert!(result.imported.is_empty());
+        assert_eq!(result.duplicates, 1);
+    }
+
+    #[test]
+    fn test_ambiguous_title_is_reported() {
+        let (rows, _) = interpret_rows(&parse_csv(TOGGL)).unwrap();
+        let notes = vec![note("a", "Datalog Engine"), note("b", "datalog engine")];
+        let result = plan_import(&rows[..1], &notes, &default_time_categories());
+        assert!(result.imported.is_empty());
+        assert!(result.unmatched[0].reason.contains("matches 2 notes"));
+    }
+
+    #[test]
+    fn test_missing_columns_rejected() {
+        assert!(interpret_rows(&parse_csv("Name,Hours\nx,1\n")).is_err());
+        assert!(interpret_rows(&[]).is_err());
+    }
+}
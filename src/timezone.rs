@@ -0,0 +1,65 @@
+//! The display timezone.
+//!
+//! File mtimes and other stored instants are UTC. Pages show them, and
+//! decide what "today" is, in the IANA zone named by `timezone` in the
+//! config (e.g. `America/New_York`), or the server's local zone when it is
+//! unset. New notes get `created:` as an RFC3339 timestamp in that zone.
+
+use std::sync::RwLock;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, SecondsFormat, Utc};
+use chrono_tz::Tz;
+
+static TIMEZONE: RwLock<Option<Tz>> = RwLock::new(None);
+
+/// Parse an IANA timezone name.
+pub fn parse(name: &str) -> Result<Tz, String> {
+    name.trim().parse::<Tz>().map_err(|_| format!("Unknown timezone '{}' (expected e.g. Europe/Berlin)", name))
+}
+
+/// Show times in `tz`, or the server's zone for `None` (set at startup).
+pub fn configure(tz: Option<Tz>) {
+    *TIMEZONE.write().unwrap_or_else(|e| e.into_inner()) = tz;
+}
+
+/// `at` in the display timezone.
+pub fn local(at: DateTime<Utc>) -> DateTime<FixedOffset> {
+    match *TIMEZONE.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(tz) => at.with_timezone(&tz).fixed_offset(),
+        None => at.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    local(Utc::now())
+}
+
+/// Today's date in the display timezone.
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
+
+/// `at` formatted with `fmt` in the display timezone.
+pub fn format(at: DateTime<Utc>, fmt: &str) -> String {
+    local(at).format(fmt).to_string()
+}
+
+/// `at` as written to `created:`, to the second.
+pub fn timestamp(at: DateTime<FixedOffset>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_timezone() {
+        assert!(parse("Mars/Olympus_Mons").is_err());
+        let tz = parse("America/New_York").unwrap();
+        let at = DateTime::parse_from_rfc3339("2026-10-17T02:30:00Z").unwrap().with_timezone(&Utc);
+        let shown = at.with_timezone(&tz).fixed_offset();
+        assert_eq!(shown.date_naive(), NaiveDate::from_ymd_opt(2026, 10, 16).unwrap());
+        assert_eq!(timestamp(shown), "2026-10-16T22:30:00-04:00");
+    }
+}
@@ -0,0 +1,139 @@
+//! Optional native HTTPS via rustls.
+//!
+//! Either serves a certificate/key pair from disk (`[tls] cert`/`key`), or
+//! generates a self-signed certificate for LAN use (`[tls] self_signed`).
+//! The self-signed pair is kept in `Config::tls_dir`, outside the database
+//! so backups never carry the private key, and reused so browsers only need
+//! to accept it once.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::Config;
+
+/// Where older versions kept the self-signed pair; cleared on startup.
+const LEGACY_TLS_TREE: &str = "tls";
+
+/// Subject alternative names for a self-signed certificate: loopback
+/// names, the bind host when it is a concrete address, and any configured
+/// extra hostnames.
+pub fn self_signed_names(config: &Config) -> Vec<String> {
+    let mut names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    if let Some((host, _)) = config.bind.rsplit_once(':') {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if !host.is_empty() && host != "0.0.0.0" && host != "::" {
+            names.push(host.to_string());
+        }
+    }
+    for name in &config.tls.hostnames {
+        names.push(name.trim().to_string());
+    }
+    names.retain(|n| !n.is_empty());
+    names.dedup();
+    names
+}
+
+/// Write `data` to `path`, readable only by the owner.
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(data)
+}
+
+/// Load the stored self-signed pair, regenerating it when missing or when
+/// the set of names has changed.
+fn self_signed_pem(dir: &Path, names: &[String]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let (names_path, cert_path, key_path) = (dir.join("names"), dir.join("cert.pem"), dir.join("key.pem"));
+    let names_key = names.join(",");
+
+    if let (Ok(stored), Ok(cert), Ok(key)) = (fs::read(&names_path), fs::read(&cert_path), fs::read(&key_path)) {
+        if stored == names_key.as_bytes() {
+            return Ok((cert, key));
+        }
+    }
+
+    let certified = rcgen::generate_simple_self_signed(names.to_vec())
+        .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+    let cert = certified.cert.pem().into_bytes();
+    let key = certified.key_pair.serialize_pem().into_bytes();
+
+    fs::create_dir_all(dir)
+        .and_then(|_| write_private(&key_path, &key))
+        .and_then(|_| fs::write(&cert_path, &cert))
+        .and_then(|_| fs::write(&names_path, names_key.as_bytes()))
+        .map_err(|e| format!("Failed to store certificate: {}", e))?;
+
+    Ok((cert, key))
+}
+
+/// Build the rustls server config, or `None` when TLS is not enabled.
+pub async fn rustls_config(config: &Config, db: &crate::store::Db) -> Result<Option<RustlsConfig>, String> {
+    let tls = &config.tls;
+    match (&tls.cert, &tls.key) {
+        (Some(cert), Some(key)) => RustlsConfig::from_pem_file(cert, key)
+            .await
+            .map(Some)
+            .map_err(|e| format!("Failed to load TLS certificate: {}", e)),
+        (Some(_), None) | (None, Some(_)) => {
+            Err("TLS needs both a certificate and a key".to_string())
+        }
+        (None, None) if tls.self_signed => {
+            if let Ok(legacy) = db.open_tree(LEGACY_TLS_TREE) {
+                let _ = legacy.clear();
+            }
+            let names = self_signed_names(config);
+            let (cert, key) = self_signed_pem(&config.tls_dir(), &names)?;
+            RustlsConfig::from_pem(cert, key)
+                .await
+                .map(Some)
+                .map_err(|e| format!("Failed to load self-signed certificate: {}", e))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_self_signed_names() {
+        let mut config = Config::default();
+        assert_eq!(self_signed_names(&config), vec!["localhost", "127.0.0.1", "::1"]);
+
+        config.bind = "192.168.1.20:3000".to_string();
+        config.tls.hostnames = vec!["notes.lan".to_string()];
+        let names = self_signed_names(&config);
+        assert!(names.contains(&"192.168.1.20".to_string()));
+        assert!(names.contains(&"notes.lan".to_string()));
+    }
+
+    #[test]
+    fn test_self_signed_pair_is_reused() {
+        let dir = TempDir::new("tls");
+        let names = vec!["localhost".to_string()];
+        let first = self_signed_pem(&dir, &names).unwrap();
+        let second = self_signed_pem(&dir, &names).unwrap();
+        assert_eq!(first, second);
+        assert!(String::from_utf8(first.0).unwrap().contains("BEGIN CERTIFICATE"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join("key.pem")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let other = self_signed_pem(&dir, &["notes.lan".to_string()]).unwrap();
+        assert_ne!(other.0, second.0);
+    }
+}
@@ -0,0 +1,423 @@
+//! Audio memos and their transcripts.
+//!
+//! `POST /api/note/{key}/audio` attaches a recording to a note. Memos are
+//! kept with the PDFs, in `pdfs/audio/`, named `<key>-<timestamp>.<ext>`
+//! so a note's memos are found by prefix, and `/pdfs/audio/{file}` serves
+//! them to logged-in users only. With `[transcription]` configured,
+//! "Transcribe" on the note view runs whisper.cpp (after converting the
+//! memo to 16 kHz WAV with `ffmpeg`) or an OpenAI-compatible API and
+//! appends the text to the note under `## Transcript`.
+
+use std::fs;
+use std::path::{Path as FsPath, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::config::{TranscriptionBackend, TranscriptionConfig};
+use crate::handlers::rewrite_note_file;
+use crate::notes::html_escape;
+use crate::storage::Storage;
+use crate::AppState;
+
+/// Subdirectory of the PDFs directory holding memos.
+pub const AUDIO_SUBDIR: &str = "audio";
+
+pub const TRANSCRIPT_HEADING: &str = "## Transcript";
+
+const DEFAULT_WHISPER: &str = "whisper-cli";
+const DEFAULT_API_URL: &str = "https://api.openai.com/v1/audio/transcriptions";
+/// Long memos take a while to upload and transcribe.
+const API_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Extensions accepted as memos, with the content type they're served as.
+const AUDIO_TYPES: &[(&str, &str)] = &[
+    ("aac", "audio/aac"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("webm", "audio/webm"),
+];
+
+pub fn is_enabled(config: &TranscriptionConfig) -> bool {
+    config.backend.is_some()
+}
+
+fn extension(file: &str) -> Option<String> {
+    FsPath::new(file).extension().map(|e| e.to_string_lossy().to_lowercase())
+}
+
+pub fn content_type(file: &str) -> Option<&'static str> {
+    let ext = extension(file)?;
+    AUDIO_TYPES.iter().find(|(e, _)| *e == ext).map(|(_, t)| *t)
+}
+
+/// Storage name of the memo `file`.
+pub fn memo_name(file: &str) -> String {
+    format!("{}/{}", AUDIO_SUBDIR, file)
+}
+
+/// Name for a memo uploaded as `original`, or an error when it isn't a
+/// known audio type.
+fn memo_filename(key: &str, original: &str, now: chrono::NaiveDateTime) -> Result<String, String> {
+    let ext = extension(original)
+        .filter(|_| content_type(original).is_some())
+        .ok_or_else(|| format!("Not an audio file: {}", original))?;
+    Ok(format!("{}-{}.{}", key, now.format("%Y%m%d-%H%M%S"), ext))
+}
+
+/// Whether `file` is a memo name (no directories) belonging to `key`.
+fn is_memo_of(key: &str, file: &str) -> bool {
+    file.strip_prefix(key).is_some_and(|rest| rest.starts_with('-'))
+        && !file.contains(['/', '\\'])
+        && content_type(file).is_some()
+}
+
+/// File names of the memos attached to `key`, oldest first.
+pub async fn audio_memos(storage: &Storage, key: &str) -> Vec<String> {
+    let names = storage.list(AUDIO_SUBDIR).await.unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Cannot list audio memos");
+        Vec::new()
+    });
+    let mut memos: Vec<String> = names
+        .iter()
+        .filter_map(|name| name.strip_prefix(AUDIO_SUBDIR)?.strip_prefix('/'))
+        .filter(|name| is_memo_of(key, name))
+        .map(str::to_string)
+        .collect();
+    memos.sort();
+    memos
+}
+
+/// `content` with `transcript` appended under its own heading, which says
+/// which memo it came from.
+pub fn append_transcript(content: &str, file: &str, transcript: &str, date: NaiveDate) -> String {
+    format!(
+        "{}\n\n{}\n\n*From `{}`, transcribed {}.*\n\n{}\n",
+        content.trim_end(),
+        TRANSCRIPT_HEADING,
+        file,
+        date.format("%Y-%m-%d"),
+        transcript.trim()
+    )
+}
+
+/// whisper.cpp on a copy of the memo converted to the 16 kHz mono WAV it
+/// expects.
+fn run_whisper(config: &TranscriptionConfig, path: &FsPath) -> Result<String, String> {
+    let wav = std::env::temp_dir().join(format!(
+        "notes-transcribe-{}-{:08x}.wav",
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    let converted = Command::new("ffmpeg")
+        .args(["-nostdin", "-loglevel", "error", "-y", "-i"])
+        .arg(path)
+        .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(&wav)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+    if !converted.status.success() {
+        fs::remove_file(&wav).ok();
+        return Err(format!("ffmpeg failed: {}", String::from_utf8_lossy(&converted.stderr).trim()));
+    }
+
+    let command = config.command.as_deref().unwrap_or(DEFAULT_WHISPER);
+    let output = Command::new(command)
+        .args(["-nt", "-np", "-m", config.model.as_deref().unwrap_or_default()])
+        .args(["-l", config.language.as_deref().unwrap_or("auto")])
+        .arg("-f")
+        .arg(&wav)
+        .output();
+    fs::remove_file(&wav).ok();
+    let output = output.map_err(|e| format!("Failed to run {}: {}", command, e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", command, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+async fn call_api(client: &reqwest::Client, config: &TranscriptionConfig, path: &FsPath) -> Result<String, String> {
+    let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let part = reqwest::multipart::Part::bytes(bytes)
+        .file_name(name.clone())
+        .mime_str(content_type(&name).unwrap_or("application/octet-stream"))
+        .map_err(|e| e.to_string())?;
+    let mut form = reqwest::multipart::Form::new()
+        .part("file", part)
+        .text("model", config.model.clone().unwrap_or_default());
+    if let Some(ref language) = config.language {
+        form = form.text("language", language.clone());
+    }
+
+    let mut request = client
+        .post(config.url.as_deref().unwrap_or(DEFAULT_API_URL))
+        .timeout(API_TIMEOUT)
+        .multipart(form);
+    if let Some(key) = config.api_key_env.as_deref().and_then(|var| std::env::var(var).ok()) {
+        request = request.bearer_auth(key);
+    }
+    let response = request
+        .send()
+        .await
+        .map_err(|e| format!("Transcription request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Transcription service returned HTTP {}", response.status()));
+    }
+    let body: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    body["text"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "Unexpected response from the transcription service".to_string())
+}
+
+/// The text spoken in the memo at `path`; API calls go through `client`.
+pub async fn transcribe(client: &reqwest::Client, config: &TranscriptionConfig, path: PathBuf) -> Result<String, String> {
+    match config.backend {
+        None => Err("Transcription is not configured".to_string()),
+        Some(TranscriptionBackend::Whisper) => {
+            let config = config.clone();
+            let result = tokio::task::spawn_blocking(move || run_whisper(&config, &path))
+                .await
+                .map_err(|e| e.to_string())?;
+            crate::telemetry::record_external_call("whisper", result.is_ok());
+            result
+        }
+        Some(TranscriptionBackend::OpenAI) => {
+            let result = call_api(client, config, &path).await;
+            crate::telemetry::record_external_call("transcription_api", result.is_ok());
+            result
+        }
+    }
+}
+
+/// The "Audio memos" panel of the note view: a player per memo, with a
+/// Transcribe button when transcription is configured. Empty without memos.
+pub fn audio_html(memos: &[String], can_transcribe: bool) -> String {
+    if memos.is_empty() {
+        return String::new();
+    }
+    let items: Vec<String> = memos
+        .iter()
+        .map(|file| {
+            let name = html_escape(file);
+            let button = if can_transcribe {
+                format!(" <button onclick=\"transcribeAudio('{}', this)\">Transcribe</button>", name)
+            } else {
+                String::new()
+            };
+            format!(
+                "<li><audio controls preload=\"none\" src=\"/pdfs/audio/{0}\"></audio> <small>{0}</small>{1}</li>",
+                name, button
+            )
+        })
+        .collect();
+    format!("<div class=\"sub-notes\"><h3>Audio memos</h3><ul>{}</ul></div>", items.concat())
+}
+
+/// Script for the note view defining `attachAudio()`, which picks a memo
+/// and uploads it, and `transcribeAudio(file, btn)`.
+pub fn audio_script(key: &str) -> String {
+    format!(
+        r#"<input type="file" id="audio-input" accept="audio/*" hidden onchange="uploadAudio(this)">
+<script>
+function attachAudio() {{
+    document.getElementById('audio-input').click();
+}}
+async function uploadAudio(input) {{
+    if (!input.files.length) return;
+    const form = new FormData();
+    form.append('file', input.files[0]);
+    const resp = await fetch('/api/note/{0}/audio', {{method: 'POST', body: form}});
+    if (!resp.ok) {{
+        alert('Upload failed: ' + await resp.text());
+        return;
+    }}
+    location.reload();
+}}
+async function transcribeAudio(file, btn) {{
+    btn.disabled = true;
+    btn.textContent = 'Transcribing...';
+    const resp = await fetch('/api/note/{0}/transcribe', {{
+        method: 'POST',
+        headers: {{'Content-Type': 'application/json'}},
+        body: JSON.stringify({{file}}),
+    }});
+    if (!resp.ok) {{
+        alert('Transcription failed: ' + await resp.text());
+        btn.disabled = false;
+        btn.textContent = 'Transcribe';
+        return;
+    }}
+    location.reload();
+}}
+</script>"#,
+        key
+    )
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+/// `POST /api/note/{key}/audio` (multipart `file`): returns `{file}`.
+pub async fn upload_audio(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    mut multipart: Multipart,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if !state.notes_map().contains_key(&key) {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    }
+
+    let mut upload = None;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        if field.name() == Some("file") {
+            let original = field.file_name().unwrap_or_default().to_string();
+            match field.bytes().await {
+                Ok(bytes) => upload = Some((original, bytes)),
+                Err(e) => return (StatusCode::BAD_REQUEST, format!("Failed to read file: {}", e)).into_response(),
+            }
+            break;
+        }
+    }
+    let Some((original, bytes)) = upload.filter(|(_, b)| !b.is_empty()) else {
+        return (StatusCode::BAD_REQUEST, "No file uploaded").into_response();
+    };
+
+    let file = match memo_filename(&key, &original, crate::timezone::now().naive_local()) {
+        Ok(file) => file,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    if let Err(e) = state.storage.put(&memo_name(&file), bytes.to_vec()).await {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save memo: {}", e)).into_response();
+    }
+    axum::Json(serde_json::json!({ "file": file })).into_response()
+}
+
+/// `GET /pdfs/audio/{file}`: a memo, for logged-in users only.
+pub async fn serve_audio(
+    Path(file): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let Some(mime) = content_type(&file).filter(|_| !file.contains(['/', '\\'])) else {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+    if let Some(url) = state.storage.presigned_url(&memo_name(&file)) {
+        return Redirect::temporary(&url).into_response();
+    }
+    let Ok(local) = state.storage.local_file(&memo_name(&file)).await else {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    };
+    match tokio::fs::read(local.path()).await {
+        Ok(bytes) => ([(header::CONTENT_TYPE, mime)], bytes).into_response(),
+        Err(_) => (StatusCode::NOT_FOUND, "Not found").into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranscribeRequest {
+    pub file: String,
+}
+
+/// `POST /api/note/{key}/transcribe` `{file}`: appends the memo's
+/// transcript to the note and returns `{transcript}`.
+pub async fn transcribe_memo(
+    Path(key): Path<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    axum::Json(req): axum::Json<TranscribeRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    if !is_enabled(&state.config.transcription) {
+        return (StatusCode::BAD_REQUEST, "Transcription is not configured").into_response();
+    }
+    let notes_map = state.notes_map();
+    let Some(note) = notes_map.get(&key) else {
+        return (StatusCode::NOT_FOUND, "Note not found").into_response();
+    };
+    if !is_memo_of(&key, &req.file) {
+        return (StatusCode::NOT_FOUND, "Memo not found").into_response();
+    }
+    let Ok(local) = state.storage.local_file(&memo_name(&req.file)).await else {
+        return (StatusCode::NOT_FOUND, "Memo not found").into_response();
+    };
+
+    let transcript = match transcribe(&state.http, &state.config.transcription, local.path().to_path_buf()).await {
+        Ok(t) if !t.trim().is_empty() => t,
+        Ok(_) => return (StatusCode::BAD_GATEWAY, "Empty transcript").into_response(),
+        Err(e) => {
+            tracing::warn!(key = %key, file = %req.file, error = %e, "Transcription failed");
+            return (StatusCode::BAD_GATEWAY, e).into_response();
+        }
+    };
+
+    let now = crate::timezone::now();
+    let commit_msg = format!("Transcript of '{}': {}", note.title, now.format("%a %b %d, %-I:%M%p"));
+    match rewrite_note_file(&state, note, commit_msg, |c| {
+        Ok(append_transcript(c, &req.file, &transcript, now.date_naive()))
+    }) {
+        Ok(_) => axum::Json(serde_json::json!({ "transcript": transcript.trim() })).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[tokio::test]
+    async fn test_memos_and_transcript() {
+        let dir = TempDir::new("transcribe");
+        let now = NaiveDate::from_ymd_opt(2026, 3, 5).unwrap().and_hms_opt(14, 30, 0).unwrap();
+        let file = memo_filename("aaaaaaaaaaaa", "Talk Memo.M4A", now).unwrap();
+        assert_eq!(file, "aaaaaaaaaaaa-20260305-143000.m4a");
+        assert!(memo_filename("aaaaaaaaaaaa", "slides.pdf", now).is_err());
+
+        let storage = Storage::Local(dir.clone());
+        assert!(audio_memos(&storage, "aaaaaaaaaaaa").await.is_empty());
+        for name in [&file, "aaaaaaaaaaaa-20260101-090000.mp3", "bbbbbbbbbbbb-20260101-090000.mp3", "aaaaaaaaaaaa-notes.txt"] {
+            storage.put(&memo_name(name), b"x".to_vec()).await.unwrap();
+        }
+        assert_eq!(audio_memos(&storage, "aaaaaaaaaaaa").await, vec!["aaaaaaaaaaaa-20260101-090000.mp3", file.as_str()]);
+        assert!(!is_memo_of("aaaaaaaaaaaa", "bbbbbbbbbbbb-20260101-090000.mp3"));
+        assert!(!is_memo_of("aaaaaaaaaaaa", "aaaaaaaaaaaa-/../x.mp3"));
+
+        let content = "---\ntitle: Talk\n---\n\nNotes from the talk.\n\n";
+        assert_eq!(
+            append_transcript(content, &file, " So, gradual typing.\n", now.date()),
+            "---\ntitle: Talk\n---\n\nNotes from the talk.\n\n## Transcript\n\n\
+             *From `aaaaaaaaaaaa-20260305-143000.m4a`, transcribed 2026-03-05.*\n\nSo, gradual typing.\n"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Soft delete.
+//!
+//! Deleting a note moves it to `.trash/<stamp>-<key>/<original path>` inside
+//! the notes directory: the entry's name records when it was deleted and
+//! which key it had, and the path under it records where to restore it to,
+//! so no other bookkeeping is needed and git sees a rename. `/trash` lists
+//! the entries with restore and delete-forever actions; the server purges
+//! entries older than `trash_days` once a day.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::auth::is_logged_in;
+use crate::formats::is_note_file;
+use crate::models::Note;
+use crate::notes::{html_escape, is_valid_note_id, load_note};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Directory under the notes directory that holds deleted notes. Skipped
+/// when loading notes.
+pub const TRASH_DIR: &str = ".trash";
+
+const STAMP_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// A note in the trash.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashedNote {
+    /// Name of the entry's directory under `.trash/`.
+    pub id: String,
+    /// The note's key before it was deleted.
+    pub key: String,
+    pub title: String,
+    /// Where the note is restored to, relative to the notes directory.
+    pub path: PathBuf,
+    pub deleted: NaiveDateTime,
+}
+
+impl TrashedNote {
+    /// The entry's directory, relative to the notes directory.
+    pub fn dir(&self) -> PathBuf {
+        Path::new(TRASH_DIR).join(&self.id)
+    }
+}
+
+/// Move `note` into the trash.
+pub fn trash_note(notes_dir: &Path, note: &Note, now: NaiveDateTime) -> Result<TrashedNote, String> {
+    let trashed = TrashedNote {
+        id: format!("{}-{}", now.format(STAMP_FORMAT), note.key),
+        key: note.key.clone(),
+        title: note.title.clone(),
+        path: note.path.clone(),
+        deleted: now,
+    };
+    let entry = notes_dir.join(trashed.dir());
+    if entry.exists() {
+        return Err(format!("{} is already in the trash", trashed.dir().display()));
+    }
+    let target = entry.join(&note.path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    fs::rename(notes_dir.join(&note.path), &target)
+        .map_err(|e| format!("Failed to move {} to the trash: {}", note.path.display(), e))?;
+    Ok(trashed)
+}
+
+/// Read the trash entry named `id`, if there is one.
+fn read_entry(notes_dir: &Path, id: &str) -> Option<TrashedNote> {
+    if !is_valid_note_id(id) {
+        return None;
+    }
+    let (stamp, key) = id.split_once('-')?;
+    let deleted = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).ok()?;
+    let entry = notes_dir.join(TRASH_DIR).join(id);
+    let file = WalkDir::new(&entry)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_type().is_file() && is_note_file(e.path()))?;
+    let note = load_note(&file.path().to_path_buf(), &entry)?;
+    Some(TrashedNote {
+        id: id.to_string(),
+        key: key.to_string(),
+        title: note.title,
+        path: note.path,
+        deleted,
+    })
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn list_trash(notes_dir: &Path) -> Vec<TrashedNote> {
+    let Ok(entries) = fs::read_dir(notes_dir.join(TRASH_DIR)) else {
+        return vec![];
+    };
+    let mut trashed: Vec<TrashedNote> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| read_entry(notes_dir, &e.file_name().to_string_lossy()))
+        .collect();
+    trashed.sort_by(|a, b| b.deleted.cmp(&a.deleted).then_with(|| a.id.cmp(&b.id)));
+    trashed
+}
+
+/// Move a trashed note back to where it was. Refuses if something else
+/// has taken its place since.
+pub fn restore(notes_dir: &Path, id: &str) -> Result<TrashedNote, String> {
+    let trashed = read_entry(notes_dir, id).ok_or_else(|| format!("'{}' is not in the trash", id))?;
+    let target = notes_dir.join(&trashed.path);
+    if target.exists() {
+        return Err(format!("{} already exists; move it away first", trashed.path.display()));
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+    }
+    let entry = notes_dir.join(trashed.dir());
+    fs::rename(entry.join(&trashed.path), &target)
+        .map_err(|e| format!("Failed to restore {}: {}", trashed.path.display(), e))?;
+    let _ = fs::remove_dir_all(&entry);
+    Ok(trashed)
+}
+
+/// Delete a trashed note for good.
+pub fn purge(notes_dir: &Path, id: &str) -> Result<TrashedNote, String> {
+    let trashed = read_entry(notes_dir, id).ok_or_else(|| format!("'{}' is not in the trash", id))?;
+    fs::remove_dir_all(notes_dir.join(trashed.dir()))
+        .map_err(|e| format!("Failed to delete {}: {}", trashed.path.display(), e))?;
+    Ok(trashed)
+}
+
+/// Delete entries that have been in the trash for `days` days or more.
+pub fn purge_expired(notes_dir: &Path, days: u32, now: NaiveDateTime) -> Vec<TrashedNote> {
+    let cutoff = now - chrono::Duration::days(days.into());
+    list_trash(notes_dir)
+        .into_iter()
+        .filter(|t| t.deleted <= cutoff)
+        .filter_map(|t| match purge(notes_dir, &t.id) {
+            Ok(t) => Some(t),
+            Err(e) => {
+                tracing::warn!(error = %e, "Trash purge failed");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Stage `paths` (additions and removals alike) and commit them. Each path
+/// is staged on its own, so one that git never saw doesn't drop the rest.
+pub fn commit_paths(notes_dir: &Path, paths: &[PathBuf], summary: &str) {
+    let now = crate::timezone::now();
+    let commit_msg = format!("{}: {}", summary, now.format("%a %b %d, %-I:%M%p"));
+    for path in paths {
+        let _ = Command::new("git")
+            .args(["add", "-A", "--"])
+            .arg(path)
+            .current_dir(notes_dir)
+            .output();
+    }
+    let _ = Command::new("git")
+        .args(["commit", "-m", &commit_msg])
+        .current_dir(notes_dir)
+        .output();
+}
+
+/// Purge expired trash once a day, starting now. Spawned by the server
+/// unless it is read-only or `trash_days` is 0.
+pub async fn purge_daily(state: Arc<AppState>) {
+    let days = state.config.trash_days;
+    let mut interval = tokio::time::interval(Duration::from_secs(24 * 60 * 60));
+    loop {
+        interval.tick().await;
+        let notes_dir = state.notes_dir.clone();
+        state.spawn_background(move || {
+            let purged = purge_expired(&notes_dir, days, crate::timezone::now().naive_local());
+            if purged.is_empty() {
+                return;
+            }
+            tracing::info!(count = purged.len(), days, "Purged expired trash");
+            let paths: Vec<PathBuf> = purged.iter().map(|t| t.dir()).collect();
+            let summary = format!("purged {} notes from the trash after {} days", purged.len(), days);
+            commit_paths(&notes_dir, &paths, &summary);
+        });
+    }
+}
+
+// ============================================================================
+// HTTP Handlers
+// ============================================================================
+
+pub async fn trash_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let trashed = list_trash(&state.notes_dir);
+    let days = state.config.trash_days;
+    let purge_note = match days {
+        0 => "Deleted notes stay here until you delete them for good.".to_string(),
+        days => format!("Deleted notes are purged {} days after they were deleted.", days),
+    };
+
+    let mut html = format!(
+        r#"<h1>Trash</h1>
+        <p class="time-breadcrumb">{}</p>"#,
+        purge_note
+    );
+    if trashed.is_empty() {
+        html.push_str("<p>The trash is empty.</p>");
+    } else {
+        html.push_str(r#"<table class="time-table"><tr><th>Title</th><th>Was at</th><th>Deleted</th><th></th></tr>"#);
+        for t in &trashed {
+            let id = html_escape(&t.id);
+            html.push_str(&format!(
+                r#"<tr><td>{}</td><td>{}</td><td>{}</td><td>
+                <button onclick="restoreNote('{}')">Restore</button>
+                <button onclick="purgeNote('{}', this)">Delete forever</button></td></tr>"#,
+                html_escape(&t.title),
+                html_escape(&t.path.to_string_lossy()),
+                t.deleted.format("%Y-%m-%d %H:%M"),
+                id,
+                id,
+            ));
+        }
+        html.push_str("</table>");
+    }
+    html.push_str(
+        r#"<script>
+        async function restoreNote(id) {
+            const resp = await fetch('/api/trash/' + id + '/restore', {method: 'POST'});
+            if (!resp.ok) { alert('Failed to restore: ' + await resp.text()); return; }
+            const r = await resp.json();
+            window.location.href = '/note/' + r.key;
+        }
+        async function purgeNote(id, btn) {
+            if (!confirm('Delete this note for good? It can then only be recovered from git history.')) return;
+            const resp = await fetch('/api/trash/' + id, {method: 'DELETE'});
+            if (!resp.ok) { alert('Failed to delete: ' + await resp.text()); return; }
+            btn.closest('tr').remove();
+        }
+        </script>"#,
+    );
+
+    Html(base_html("Trash", &html, None, true)).into_response()
+}
+
+pub async fn restore_note(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let trashed = match restore(&state.notes_dir, &id) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::CONFLICT, e).into_response(),
+    };
+
+    state.invalidate_notes_cache();
+    let key = state
+        .load_notes()
+        .into_iter()
+        .find(|n| n.path == trashed.path)
+        .map(|n| n.key)
+        .unwrap_or_else(|| trashed.key.clone());
+    state.reindex_graph_note(&key);
+
+    let notes_dir = state.notes_dir.clone();
+    let paths = vec![trashed.path.clone(), trashed.dir()];
+    let summary = format!("restored note '{}'", trashed.title);
+    state.spawn_background(move || commit_paths(&notes_dir, &paths, &summary));
+
+    axum::Json(serde_json::json!({ "key": key })).into_response()
+}
+
+pub async fn delete_forever(
+    AxumPath(id): AxumPath<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+
+    let trashed = match purge(&state.notes_dir, &id) {
+        Ok(t) => t,
+        Err(e) => return (StatusCode::NOT_FOUND, e).into_response(),
+    };
+
+    let notes_dir = state.notes_dir.clone();
+    let paths = vec![trashed.dir()];
+    let summary = format!("emptied '{}' from the trash", trashed.title);
+    state.spawn_background(move || commit_paths(&notes_dir, &paths, &summary));
+
+    (StatusCode::OK, "Deleted").into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+
+    fn scratch_dir() -> TempDir {
+        let dir = TempDir::new("trash");
+        fs::create_dir_all(dir.join("papers")).unwrap();
+        dir
+    }
+
+    fn at(day: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(9, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge() {
+        let dir = scratch_dir();
+        fs::write(dir.join("papers/datalog.md"), "---\ntitle: Datalog\n---\nBody\n").unwrap();
+        fs::write(dir.join("idea.md"), "---\ntitle: Idea\nid: 3fa9c01b22d4\n---\n").unwrap();
+        let notes = load_all_notes(&dir);
+        let datalog = notes.iter().find(|n| n.title == "Datalog").unwrap();
+        let idea = notes.iter().find(|n| n.title == "Idea").unwrap();
+
+        let trashed = trash_note(&dir, datalog, at(1)).unwrap();
+        trash_note(&dir, idea, at(10)).unwrap();
+        assert!(!dir.join("papers/datalog.md").exists());
+        assert!(load_all_notes(&dir).is_empty());
+
+        let listed = list_trash(&dir);
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].id, "20261010T093000-3fa9c01b22d4");
+        assert_eq!(listed[1].path, PathBuf::from("papers/datalog.md"));
+        assert_eq!(listed[1].key, datalog.key);
+        assert_eq!(listed[1].title, "Datalog");
+
+        fs::write(dir.join("papers/datalog.md"), "---\ntitle: Replacement\n---\n").unwrap();
+        assert!(restore(&dir, &trashed.id).is_err());
+        fs::remove_file(dir.join("papers/datalog.md")).unwrap();
+        restore(&dir, &trashed.id).unwrap();
+        assert_eq!(load_all_notes(&dir)[0].key, datalog.key);
+        assert!(!dir.join(trashed.dir()).exists());
+
+        assert!(restore(&dir, "../papers").is_err());
+        assert!(purge_expired(&dir, 30, at(31)).is_empty());
+        assert_eq!(purge_expired(&dir, 30, at(10) + chrono::Duration::days(30)).len(), 1);
+        assert!(list_trash(&dir).is_empty());
+    }
+}
@@ -2,14 +2,33 @@
 //!
 //! This module provides URL validation to prevent Server-Side Request Forgery (SSRF)
 //! attacks by enforcing:
-//! - HTTPS-only connections
+//! - Allowed schemes (HTTPS-only for Smart Add page fetches by default)
 //! - Domain allowlist for trusted sources
+//! - Denied hosts and CIDR ranges from `[url_policy]`
 //! - Internal IP address blocking (private ranges, loopback, link-local)
 //! - DNS rebinding protection
+//!
+//! Smart Add fetches and PDF downloads each get a `UrlPolicy` built from the
+//! `[url_policy]` table, which is set once at startup like the `[llm]` config.
+//! `UrlPolicy::get` fetches through a policy: redirects are followed by hand
+//! (at most `MAX_REDIRECTS`), every hop is validated again, and each
+//! connection is pinned to the addresses that were just checked. Pinned
+//! clients are kept per host and address set (`pinned_client`), so their
+//! connections are pooled across requests.
+//!
+//! `canonicalize` normalizes URLs (https, no `www.`, tracking parameters or
+//! fragment; DOI and arXiv URLs in one form) so duplicate detection can
+//! match paper `url` sources; DOI and arXiv sources match by
+//! `PaperSource::match_id`.
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::time::Duration;
 use url::Url;
 
+use crate::config::UrlPolicyConfig;
+
 /// Allowed domains for URL fetching (academic sources and publishers)
 const ALLOWED_DOMAINS: &[&str] = &[
     // Academic repositories
@@ -61,10 +80,12 @@ const ALLOWED_DOMAINS: &[&str] = &[
 pub enum UrlValidationError {
     /// URL is malformed or cannot be parsed
     InvalidUrl(String),
-    /// URL uses non-HTTPS scheme
-    NotHttps,
+    /// URL uses a scheme the policy doesn't allow
+    SchemeNotAllowed(String),
     /// Domain is not in the allowlist
     DomainNotAllowed(String),
+    /// Host or resolved IP is denied by `[url_policy]`
+    HostDenied(String),
     /// Resolved IP is a private/internal address
     InternalIpAddress(String),
     /// DNS resolution failed
@@ -75,10 +96,13 @@ impl std::fmt::Display for UrlValidationError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             UrlValidationError::InvalidUrl(msg) => write!(f, "Invalid URL: {}", msg),
-            UrlValidationError::NotHttps => write!(f, "Only HTTPS URLs are allowed"),
+            UrlValidationError::SchemeNotAllowed(scheme) => {
+                write!(f, "URL scheme not allowed: {}", scheme)
+            }
             UrlValidationError::DomainNotAllowed(domain) => {
                 write!(f, "Domain not in allowlist: {}", domain)
             }
+            UrlValidationError::HostDenied(host) => write!(f, "Host is denied: {}", host),
             UrlValidationError::InternalIpAddress(ip) => {
                 write!(f, "Internal IP addresses are not allowed: {}", ip)
             }
@@ -139,77 +163,274 @@ fn is_internal_ip(ip: &IpAddr) -> bool {
     }
 }
 
-/// Check if a domain is in the allowlist
+/// Whether `host` is `domain` or one of its subdomains (e.g. "www.arxiv.org"
+/// matches "arxiv.org").
+fn host_matches(host: &str, domain: &str) -> bool {
+    let host = host.to_lowercase();
+    let domain = domain.trim_start_matches('.').to_lowercase();
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// Check if a domain is in the built-in allowlist
 fn is_domain_allowed(host: &str) -> bool {
-    let host_lower = host.to_lowercase();
+    ALLOWED_DOMAINS.iter().any(|allowed| host_matches(host, allowed))
+}
 
-    for allowed in ALLOWED_DOMAINS {
-        // Exact match
-        if host_lower == *allowed {
-            return true;
-        }
-        // Subdomain match (e.g., "www.arxiv.org" matches "arxiv.org")
-        if host_lower.ends_with(&format!(".{}", allowed)) {
-            return true;
+/// An IP range such as `203.0.113.0/24` or `2001:db8::/32`. A bare address
+/// is a range of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| format!("invalid address in CIDR '{}'", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p.parse().ok().filter(|&p| p <= max).ok_or_else(|| format!("invalid prefix in CIDR '{}'", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = match (self.addr, ip) {
+            (IpAddr::V4(_), IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => return false,
+            },
+            _ => *ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
         }
     }
+}
 
-    false
+/// The rules one kind of outbound fetch is checked against.
+#[derive(Debug, Clone)]
+pub struct UrlPolicy {
+    /// Allowed URL schemes, e.g. `["https"]`.
+    pub schemes: Vec<String>,
+    /// Only fetch from the built-in academic domains and `allowed_domains`.
+    pub allowlist_only: bool,
+    /// Domains allowed on top of the built-in list.
+    pub allowed_domains: Vec<String>,
+    /// Hosts (and their subdomains) never fetched.
+    pub denied_hosts: Vec<String>,
+    /// Ranges never fetched, on top of the internal ones.
+    pub denied_cidrs: Vec<Cidr>,
 }
 
-/// Validate a URL for safe fetching (SSRF protection)
-///
-/// This function performs the following checks:
-/// 1. Parses the URL and validates it's well-formed
-/// 2. Ensures the scheme is HTTPS
-/// 3. Checks the domain against the allowlist
-/// 4. Resolves the domain and checks the IP isn't internal
-///
-/// # Arguments
-/// * `url_str` - The URL string to validate
-///
-/// # Returns
-/// * `Ok(Url)` - The parsed and validated URL
-/// * `Err(UrlValidationError)` - If validation fails
-pub fn validate_url(url_str: &str) -> Result<Url, UrlValidationError> {
-    // Parse the URL
-    let url = Url::parse(url_str).map_err(|e| UrlValidationError::InvalidUrl(e.to_string()))?;
+/// Redirects `UrlPolicy::get` follows before giving up.
+pub const MAX_REDIRECTS: usize = 5;
+
+static POLICY: RwLock<Option<UrlPolicyConfig>> = RwLock::new(None);
+
+/// Host and checked addresses (empty: not pinned) → client.
+type PinnedClients = HashMap<(String, Vec<SocketAddr>), reqwest::Client>;
+
+static PINNED_CLIENTS: LazyLock<Mutex<PinnedClients>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Pinned clients kept before the cache starts over.
+const MAX_PINNED_CLIENTS: usize = 64;
+
+/// A client that doesn't follow redirects and connects to `host` only at
+/// `addrs`, reused while the host keeps resolving to the same addresses.
+fn pinned_client(host: Option<&str>, addrs: &[SocketAddr]) -> Result<reqwest::Client, String> {
+    let mut addrs = addrs.to_vec();
+    addrs.sort();
+    let host = host.filter(|_| !addrs.is_empty());
+    let key = (host.unwrap_or_default().to_string(), if host.is_some() { addrs } else { Vec::new() });
+    let mut clients = PINNED_CLIENTS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let mut builder = crate::http::client_builder().redirect(reqwest::redirect::Policy::none());
+    if let Some(host) = host {
+        builder = builder.resolve_to_addrs(host, &key.1);
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+    if clients.len() >= MAX_PINNED_CLIENTS {
+        clients.clear();
+    }
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Use `config` for every later check (set from config at startup).
+pub fn configure(config: &UrlPolicyConfig) {
+    *POLICY.write().unwrap_or_else(|e| e.into_inner()) = Some(config.clone());
+}
+
+fn policy_config() -> UrlPolicyConfig {
+    POLICY.read().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+impl UrlPolicy {
+    fn from_config(config: &UrlPolicyConfig, schemes: &[String], allowlist_only: bool) -> Self {
+        Self {
+            schemes: schemes.iter().map(|s| s.to_lowercase()).collect(),
+            allowlist_only,
+            allowed_domains: config.allow.clone(),
+            denied_hosts: config.deny_hosts.clone(),
+            // Checked by `UrlPolicyConfig::validate` at startup
+            denied_cidrs: config.deny_cidrs.iter().filter_map(|c| Cidr::parse(c).ok()).collect(),
+        }
+    }
+
+    /// The configured policy for pages fetched by Smart Add.
+    pub fn smart_add() -> Self {
+        let config = policy_config();
+        Self::from_config(&config, &config.schemes, config.allowlist_only)
+    }
+
+    /// The configured policy for PDFs downloaded by URL.
+    pub fn pdf_download() -> Self {
+        let config = policy_config();
+        Self::from_config(&config, &config.pdf_schemes, config.pdf_allowlist_only)
+    }
+
+    /// The configured policy for the dead link checker: the links are the
+    /// user's own, so any domain over HTTP(S), minus the denied ones.
+    pub fn link_check() -> Self {
+        let config = policy_config();
+        Self::from_config(&config, &["https".to_string(), "http".to_string()], false)
+    }
 
-    // Check scheme is HTTPS
-    if url.scheme() != "https" {
-        return Err(UrlValidationError::NotHttps);
+    /// Whether `host` passes the host rules (before DNS resolution).
+    fn check_host(&self, host: &str) -> Result<(), UrlValidationError> {
+        if self.denied_hosts.iter().any(|d| host_matches(host, d)) {
+            return Err(UrlValidationError::HostDenied(host.to_string()));
+        }
+        if self.allowlist_only
+            && !is_domain_allowed(host)
+            && !self.allowed_domains.iter().any(|d| host_matches(host, d))
+        {
+            return Err(UrlValidationError::DomainNotAllowed(host.to_string()));
+        }
+        Ok(())
     }
 
-    // Get the host
-    let host = url
-        .host_str()
-        .ok_or_else(|| UrlValidationError::InvalidUrl("No host in URL".to_string()))?;
+    /// Whether a resolved address may be fetched.
+    fn check_ip(&self, ip: &IpAddr) -> Result<(), UrlValidationError> {
+        if is_internal_ip(ip) {
+            return Err(UrlValidationError::InternalIpAddress(ip.to_string()));
+        }
+        if self.denied_cidrs.iter().any(|c| c.contains(ip)) {
+            return Err(UrlValidationError::HostDenied(ip.to_string()));
+        }
+        Ok(())
+    }
 
-    // Check domain allowlist
-    if !is_domain_allowed(host) {
-        return Err(UrlValidationError::DomainNotAllowed(host.to_string()));
+    /// Validate a URL for safe fetching (SSRF protection)
+    ///
+    /// This function performs the following checks:
+    /// 1. Parses the URL and validates it's well-formed
+    /// 2. Ensures the scheme is allowed
+    /// 3. Checks the host against the denylist and, in allowlist-only
+    ///    mode, the allowlist
+    /// 4. Resolves the domain and checks the IPs aren't internal or denied
+    pub fn validate(&self, url_str: &str) -> Result<Url, UrlValidationError> {
+        self.validate_resolved(url_str).map(|(url, _)| url)
     }
 
-    // DNS resolution and IP check (DNS rebinding protection)
-    let port = url.port().unwrap_or(443);
-    let socket_addr = format!("{}:{}", host, port);
-
-    match socket_addr.to_socket_addrs() {
-        Ok(addrs) => {
-            for addr in addrs {
-                if is_internal_ip(&addr.ip()) {
-                    return Err(UrlValidationError::InternalIpAddress(
-                        addr.ip().to_string(),
-                    ));
+    /// `validate`, also returning the addresses the host resolved to.
+    fn validate_resolved(&self, url_str: &str) -> Result<(Url, Vec<SocketAddr>), UrlValidationError> {
+        let url = Url::parse(url_str).map_err(|e| UrlValidationError::InvalidUrl(e.to_string()))?;
+
+        if !self.schemes.iter().any(|s| s == url.scheme()) {
+            return Err(UrlValidationError::SchemeNotAllowed(url.scheme().to_string()));
+        }
+
+        let host = url
+            .host_str()
+            .ok_or_else(|| UrlValidationError::InvalidUrl("No host in URL".to_string()))?;
+        self.check_host(host)?;
+
+        // DNS resolution and IP check (DNS rebinding protection)
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = match (host.trim_start_matches('[').trim_end_matches(']'), port).to_socket_addrs() {
+            Ok(addrs) => addrs.collect(),
+            // Behind a proxy, outside names may only resolve on the proxy;
+            // the host rules above still apply
+            Err(_) if crate::http::proxy_for(&url).is_some() => return Ok((url, Vec::new())),
+            Err(e) => return Err(UrlValidationError::DnsResolutionFailed(e.to_string())),
+        };
+        if addrs.is_empty() {
+            return Err(UrlValidationError::DnsResolutionFailed(format!("no addresses for {}", host)));
+        }
+        for addr in &addrs {
+            self.check_ip(&addr.ip())?;
+        }
+
+        Ok((url, addrs))
+    }
+
+    /// GET `url` through the policy. Redirects are followed here rather
+    /// than by reqwest, so each hop is validated; the client for a hop may
+    /// only connect to the addresses checked for it, so a DNS answer that
+    /// changes after the check (rebinding) can't reach an internal address.
+    /// `request` adds headers. The final response is recorded as `api`.
+    pub async fn get(
+        &self,
+        api: &'static str,
+        url: &str,
+        timeout: Duration,
+        request: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, String> {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let policy = self.clone();
+            let target = url.clone();
+            let (checked, addrs) = tokio::task::spawn_blocking(move || policy.validate_resolved(&target))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| e.to_string())?;
+
+            let client = pinned_client(checked.domain(), &addrs)?;
+            crate::http::throttle(checked.as_str()).await;
+            let response = request(client.get(checked.clone()).timeout(timeout)).send().await;
+
+            let next = match response {
+                Ok(ref r) if r.status().is_redirection() => r
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .and_then(|l| checked.join(l).ok()),
+                _ => None,
+            };
+            match next {
+                Some(next) => url = next.to_string(),
+                None => {
+                    crate::telemetry::record_http_call(api, &response);
+                    return response.map_err(|e| e.to_string());
                 }
             }
         }
-        Err(e) => {
-            return Err(UrlValidationError::DnsResolutionFailed(e.to_string()));
-        }
+        crate::telemetry::record_external_call(api, false);
+        Err(format!("More than {} redirects", MAX_REDIRECTS))
     }
+}
 
-    Ok(url)
+/// Validate a URL fetched by Smart Add against the configured policy
+/// (by default: HTTPS, allowlisted domains only, no internal IPs).
+pub fn validate_url(url_str: &str) -> Result<Url, UrlValidationError> {
+    UrlPolicy::smart_add().validate(url_str)
 }
 
 /// Validate a URL, allowing HTTP for specific trusted API endpoints
@@ -218,40 +439,84 @@ pub fn validate_url(url_str: &str) -> Result<Url, UrlValidationError> {
 /// This is a more permissive validator that should only be used for
 /// known-safe API calls.
 pub fn validate_api_url(url_str: &str) -> Result<Url, UrlValidationError> {
-    let url = Url::parse(url_str).map_err(|e| UrlValidationError::InvalidUrl(e.to_string()))?;
+    let mut policy = UrlPolicy::smart_add();
+    policy.allowlist_only = true;
+    policy.schemes = vec!["https".to_string(), "http".to_string()];
+    policy.validate(url_str)
+}
 
-    // Allow both HTTP and HTTPS for API endpoints
-    if url.scheme() != "https" && url.scheme() != "http" {
-        return Err(UrlValidationError::NotHttps);
-    }
+/// Validate a PDF download URL against the configured policy (by default:
+/// HTTP(S), any domain, no internal IPs). PDF links found by smart-find can
+/// point at any publisher or CDN, and the user picks them explicitly.
+pub fn validate_pdf_url(url_str: &str) -> Result<Url, UrlValidationError> {
+    UrlPolicy::pdf_download().validate(url_str)
+}
 
-    let host = url
-        .host_str()
-        .ok_or_else(|| UrlValidationError::InvalidUrl("No host in URL".to_string()))?;
+/// Query parameters that only track where a click came from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_hsenc", "_hsmi", "igshid", "ref", "ref_src",
+    "source", "via",
+];
 
-    if !is_domain_allowed(host) {
-        return Err(UrlValidationError::DomainNotAllowed(host.to_string()));
+/// The form of `url_str` used to tell whether two URLs are the same page:
+/// https, lowercase host without `www.`, no fragment, tracking parameters
+/// (`utm_*` and `TRACKING_PARAMS`) or trailing slash. `dx.doi.org` becomes
+/// `doi.org` with the DOI lowercased (DOIs ignore case), and arXiv
+/// abstract, PDF and versioned links all become `https://arxiv.org/abs/<id>`.
+/// None for anything but an http(s) URL with a host.
+pub fn canonicalize(url_str: &str) -> Option<String> {
+    let mut url = Url::parse(url_str.trim()).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
     }
+    let host = url.host_str()?.trim_end_matches('.').to_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
 
-    let port = url.port().unwrap_or(if url.scheme() == "https" { 443 } else { 80 });
-    let socket_addr = format!("{}:{}", host, port);
-
-    match socket_addr.to_socket_addrs() {
-        Ok(addrs) => {
-            for addr in addrs {
-                if is_internal_ip(&addr.ip()) {
-                    return Err(UrlValidationError::InternalIpAddress(
-                        addr.ip().to_string(),
-                    ));
-                }
-            }
+    match host.as_str() {
+        "doi.org" | "dx.doi.org" => {
+            let doi = urlencoding::decode(url.path().trim_start_matches('/')).ok()?.to_lowercase();
+            return Some(format!("https://doi.org/{}", doi.trim_end_matches('/')));
         }
-        Err(e) => {
-            return Err(UrlValidationError::DnsResolutionFailed(e.to_string()));
+        "arxiv.org" | "export.arxiv.org" => {
+            let path = url.path().trim_end_matches('/');
+            let id = path.strip_prefix("/abs/").or_else(|| path.strip_prefix("/pdf/"));
+            if let Some(id) = id {
+                let id = id.strip_suffix(".pdf").unwrap_or(id);
+                let id = match id.rsplit_once('v') {
+                    Some((base, version)) if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) => base,
+                    _ => id,
+                };
+                return Some(format!("https://arxiv.org/abs/{}", id));
+            }
         }
+        _ => {}
     }
 
-    Ok(url)
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| {
+            let k = k.to_lowercase();
+            !k.starts_with("utm_") && !TRACKING_PARAMS.contains(&k.as_str())
+        })
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.set_fragment(None);
+    url.set_query(None);
+    if !query.is_empty() {
+        url.query_pairs_mut().extend_pairs(query);
+    }
+    url.set_scheme("https").ok()?;
+    url.set_host(Some(&host)).ok()?;
+    url.set_port(None).ok()?;
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&path);
+
+    let canonical = url.to_string();
+    // An empty path still prints as "/"
+    Some(match canonical.strip_suffix('/') {
+        Some(rest) if url.query().is_none() => rest.to_string(),
+        _ => canonical,
+    })
 }
 
 #[cfg(test)]
@@ -286,7 +551,7 @@ mod tests {
     #[test]
     fn test_validate_url_rejects_http() {
         let result = validate_url("http://arxiv.org/abs/1234.5678");
-        assert!(matches!(result, Err(UrlValidationError::NotHttps)));
+        assert!(matches!(result, Err(UrlValidationError::SchemeNotAllowed(_))));
     }
 
     #[test]
@@ -297,4 +562,66 @@ mod tests {
             Err(UrlValidationError::DomainNotAllowed(_))
         ));
     }
+
+    #[test]
+    fn test_canonicalize() {
+        let same = |a: &str, b: &str| assert_eq!(canonicalize(a), canonicalize(b), "{} vs {}", a, b);
+        same("https://arxiv.org/abs/2301.00001", "http://www.arxiv.org/pdf/2301.00001v2.pdf");
+        same("https://arxiv.org/abs/2301.00001", "https://export.arxiv.org/abs/2301.00001v3/");
+        assert_eq!(canonicalize("https://arxiv.org/pdf/2301.00001v2").unwrap(), "https://arxiv.org/abs/2301.00001");
+        same("https://doi.org/10.1145/3453483.3454036", "http://dx.doi.org/10.1145/3453483.3454036");
+        same("https://doi.org/10.1145/ABC", "https://doi.org/10.1145%2Fabc");
+        same(
+            "https://Blog.Example.com/post/?utm_source=rss&utm_medium=feed&id=7#comments",
+            "https://blog.example.com/post?id=7",
+        );
+        same("https://www.example.com/", "https://example.com");
+        same("https://example.com:443/a?fbclid=xyz", "http://example.com/a");
+        assert_eq!(canonicalize("https://example.com/a?fbclid=xyz").unwrap(), "https://example.com/a");
+        assert_ne!(canonicalize("https://example.com/a?id=1"), canonicalize("https://example.com/a?id=2"));
+        assert_ne!(canonicalize("https://example.com/a"), canonicalize("https://example.com/b"));
+        assert!(canonicalize("ftp://example.com/a").is_none());
+        assert!(canonicalize("not a url").is_none());
+    }
+
+    #[test]
+    fn test_configured_policy() {
+        let config = UrlPolicyConfig {
+            allow: vec!["example.edu".to_string()],
+            deny_hosts: vec!["tracker.example.com".to_string()],
+            deny_cidrs: vec!["8.8.8.0/24".to_string()],
+            ..UrlPolicyConfig::default()
+        };
+        let smart_add = UrlPolicy::from_config(&config, &config.schemes, config.allowlist_only);
+        assert!(smart_add.check_host("cs.example.edu").is_ok());
+        assert!(matches!(smart_add.check_host("evil.com"), Err(UrlValidationError::DomainNotAllowed(_))));
+
+        let pdf = UrlPolicy::from_config(&config, &config.pdf_schemes, config.pdf_allowlist_only);
+        assert!(pdf.check_host("evil.com").is_ok());
+        assert!(matches!(pdf.check_host("a.tracker.example.com"), Err(UrlValidationError::HostDenied(_))));
+        assert!(matches!(
+            pdf.validate("ftp://example.edu/paper.pdf"),
+            Err(UrlValidationError::SchemeNotAllowed(_))
+        ));
+        assert!(matches!(pdf.check_ip(&"8.8.8.8".parse().unwrap()), Err(UrlValidationError::HostDenied(_))));
+        assert!(pdf.check_ip(&"8.8.4.4".parse().unwrap()).is_ok());
+        assert!(pdf.check_ip(&"10.0.0.1".parse().unwrap()).is_err());
+
+        let v6 = Cidr::parse("2001:db8::/32").unwrap();
+        assert!(v6.contains(&"2001:db8::1".parse().unwrap()));
+        assert!(!v6.contains(&"2001:db9::1".parse().unwrap()));
+        assert!(Cidr::parse("8.8.8.8").unwrap().contains(&"::ffff:8.8.8.8".parse().unwrap()));
+        assert!(Cidr::parse("0.0.0.0/0").unwrap().contains(&"1.2.3.4".parse().unwrap()));
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("example.com/8").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_refuses_internal_hosts() {
+        let policy = UrlPolicy::pdf_download();
+        for url in ["http://127.0.0.1:9/paper.pdf", "http://[::1]:9/paper.pdf", "http://localhost:9/"] {
+            let err = policy.get("pdf_download", url, Duration::from_secs(1), |r| r).await.unwrap_err();
+            assert!(err.starts_with("Internal IP addresses are not allowed"), "{}: {}", url, err);
+        }
+    }
 }
@@ -0,0 +1,135 @@
+//! Frontmatter validation for the editor and `/problems`.
+//!
+//! `validate` runs the file-level `doctor` checks (dates that aren't
+//! YYYY-MM-DD, unknown time categories, non-numeric BibTeX years, unbalanced
+//! braces...) on unsaved editor content; the editor shows the results as
+//! diagnostics. `/problems` lists the whole-corpus `doctor` report.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::auth::is_logged_in;
+use crate::doctor::{self, Issue, Severity};
+use crate::models::TimeCategoryDef;
+use crate::notes::{html_escape, note_from_content};
+use crate::templates::base_html;
+use crate::AppState;
+
+/// Problems in `content`, as if it were saved at `path`.
+pub fn validate(path: &Path, content: &str, pdfs_dir: Option<&Path>, categories: &[TimeCategoryDef]) -> Vec<Issue> {
+    let note = note_from_content(path.to_path_buf(), content.to_string(), Utc::now());
+    let mut issues = doctor::check_file(&note, pdfs_dir, categories);
+    issues.sort_by_key(|i| i.line);
+    issues
+}
+
+#[derive(Deserialize)]
+pub struct ValidateRequest {
+    pub content: String,
+}
+
+/// `POST /api/note/{key}/validate`: `{issues}` for the editor's unsaved
+/// `content`.
+pub async fn validate_note(
+    AxumPath(key): AxumPath<String>,
+    State(state): State<Arc<AppState>>,
+    jar: CookieJar,
+    Json(req): Json<ValidateRequest>,
+) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return (StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    let path = state
+        .load_notes()
+        .into_iter()
+        .find(|n| n.key == key)
+        .map(|n| n.path)
+        .unwrap_or_else(|| PathBuf::from(format!("{}.md", key)));
+    let issues = validate(&path, &req.content, state.storage.local_dir(), &state.time_categories);
+    Json(serde_json::json!({ "issues": issues })).into_response()
+}
+
+/// `GET /problems`: every doctor issue in the corpus, grouped by note.
+pub async fn problems_page(State(state): State<Arc<AppState>>, jar: CookieJar) -> Response {
+    if !is_logged_in(&jar, &state.db) {
+        return Redirect::to("/login").into_response();
+    }
+
+    let report = doctor::check_corpus(&state.notes_dir, state.storage.local_dir(), &state.time_categories);
+    let keys: HashMap<PathBuf, String> = state.load_notes().into_iter().map(|n| (n.path, n.key)).collect();
+    let mut by_path: BTreeMap<&Path, Vec<&Issue>> = BTreeMap::new();
+    for issue in &report.issues {
+        by_path.entry(issue.path.as_path()).or_default().push(issue);
+    }
+
+    let mut html = String::from("<h1>Problems</h1>");
+    html.push_str(&format!(
+        r#"<p class="time-breadcrumb">{} errors, {} warnings in {} of {} files</p>"#,
+        report.errors,
+        report.warnings,
+        by_path.len(),
+        report.files_checked,
+    ));
+    if by_path.is_empty() {
+        html.push_str("<p>No problems found.</p>");
+    }
+    for (path, issues) in &by_path {
+        let name = html_escape(&path.display().to_string());
+        match keys.get(*path) {
+            Some(key) => html.push_str(&format!(
+                r#"<h3><a href="/note/{0}">{1}</a> <small><a href="/note/{0}?edit=true">edit</a></small></h3>"#,
+                key, name
+            )),
+            None => html.push_str(&format!("<h3>{}</h3>", name)),
+        }
+        html.push_str(r#"<table class="time-table"><tr><th>Line</th><th>Severity</th><th>Check</th><th>Problem</th></tr>"#);
+        for issue in issues {
+            let severity = match issue.severity {
+                Severity::Error => r#"<strong>error</strong>"#,
+                Severity::Warning => "warning",
+            };
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                issue.line.map_or(String::new(), |l| l.to_string()),
+                severity,
+                issue.check,
+                html_escape(&issue.message),
+            ));
+        }
+        html.push_str("</table>");
+    }
+
+    Html(base_html("Problems", &html, None, true)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let categories = vec![TimeCategoryDef { name: "reading".to_string(), color: String::new(), weekly_hours: None }];
+        let content = "---\ntitle: A\ntype: paper\ndue: next week\nbibtex: |\n  @article{smith2020a,\n    title = {A {B},\n    year = {20xx}\n  }\ntime:\n  - date: 2024-01-02\n    minutes: 30\n    category: napping\n---\n";
+        let issues = validate(Path::new("a.md"), content, Some(Path::new("/nonexistent")), &categories);
+        let found: Vec<(Option<usize>, &str)> = issues.iter().map(|i| (i.line, i.check)).collect();
+        assert!(found.contains(&(Some(4), "frontmatter")), "{:?}", issues);
+        assert!(found.contains(&(None, "time-category")), "{:?}", issues);
+        assert!(issues.iter().any(|i| i.message.contains("unclosed")), "{:?}", issues);
+
+        let clean = "---\ntitle: A\ntype: paper\nbibtex: |\n  @article{smith2020a,\n    title = {A \\{ B},\n    year = {20xx}\n  }\n---\n";
+        let issues = validate(Path::new("a.md"), clean, Some(Path::new("/nonexistent")), &categories);
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert!(issues[0].message.contains("'20xx'"));
+    }
+}
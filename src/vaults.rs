@@ -0,0 +1,156 @@
+//! Several vaults in one server.
+//!
+//! The configured `notes_dir` is the primary vault; each `[[vaults]]` table
+//! adds another with its own content, PDFs and sled database, so keys, the
+//! graph index and the bibliography are all per vault. `serve` builds one
+//! `AppState` and router per vault and `dispatch` forwards each request to
+//! the vault named by the `vault` cookie, which the nav bar switcher sets.
+//! Logins happen in the primary vault and are mirrored into the others.
+//! Set once at startup by `configure`.
+//!
+//! `/login` and `/logout` always go to the primary. A vault with
+//! `require_login` is hidden from and closed to visitors. Background tasks
+//! other than the trash purge run for the primary only, and CLI commands
+//! take `--vault`.
+
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_extra::extract::CookieJar;
+use tower::ServiceExt;
+
+use crate::auth::{is_logged_in, mirror_session};
+use crate::config::Config;
+use crate::notes::html_escape;
+use crate::AppState;
+
+pub const COOKIE: &str = "vault";
+
+/// Paths a `require_login` vault still serves to logged-out visitors.
+const PUBLIC_PATHS: &[&str] = &[
+    "/login",
+    "/logout",
+    "/healthz",
+    "/readyz",
+    "/manifest.webmanifest",
+    "/sw.js",
+    "/icon.svg",
+    "/api/sync/manifest",
+    "/api/sync/fetch",
+    "/api/sync/apply",
+];
+
+static NAMES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// Record the vault names, primary first (set at startup).
+pub fn configure(config: &Config) {
+    *NAMES.write().unwrap_or_else(|e| e.into_inner()) =
+        config.vault_configs().into_iter().map(|c| c.vault_name).collect();
+}
+
+/// The name of the vault handling the request, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|name| name.clone()).ok()
+}
+
+/// Middleware: scope the vault's name for `current` and keep logged-out
+/// visitors out of `require_login` vaults.
+pub async fn vault_guard(State(state): State<Arc<AppState>>, jar: CookieJar, request: Request, next: Next) -> Response {
+    if state.config.require_login
+        && !PUBLIC_PATHS.contains(&request.uri().path())
+        && !is_logged_in(&jar, &state.db)
+    {
+        return Redirect::to("/login").into_response();
+    }
+    CURRENT.scope(state.config.vault_name.clone(), next.run(request)).await
+}
+
+/// The nav bar's vault menu; empty with a single vault. Picking a vault
+/// sets the cookie and opens its index.
+pub fn switcher_html() -> String {
+    let names = NAMES.read().unwrap_or_else(|e| e.into_inner());
+    if names.len() < 2 {
+        return String::new();
+    }
+    let current = current();
+    let options: String = names
+        .iter()
+        .map(|name| {
+            let selected = if current.as_deref() == Some(name.as_str()) { " selected" } else { "" };
+            format!(r#"<option value="{0}"{1}>{0}</option>"#, html_escape(name), selected)
+        })
+        .collect();
+    format!(
+        r#"<select class="theme-select" title="Vault" aria-label="Vault" onchange="document.cookie = '{COOKIE}=' + this.value + ';path=/;max-age=31536000;SameSite=Lax'; location.href = '/';">{options}</select>"#
+    )
+}
+
+/// Each vault's state and fully layered router, primary first.
+pub struct Vaults {
+    pub vaults: Vec<(Arc<AppState>, Router)>,
+}
+
+/// The vault a request goes to: always the primary for logging in and out,
+/// else the cookie's vault, else the primary.
+fn select(names: &[&str], cookie: Option<&str>, path: &str) -> usize {
+    if path == "/login" || path == "/logout" {
+        return 0;
+    }
+    names.iter().position(|name| Some(*name) == cookie).unwrap_or(0)
+}
+
+/// Fallback of the server's top router: hand the request to its vault.
+pub async fn dispatch(State(vaults): State<Arc<Vaults>>, request: Request) -> Response {
+    let jar = CookieJar::from_headers(request.headers());
+    let names: Vec<&str> = vaults.vaults.iter().map(|(s, _)| s.config.vault_name.as_str()).collect();
+    let index = select(&names, jar.get(COOKIE).map(|c| c.value()), request.uri().path());
+    let (state, router) = &vaults.vaults[index];
+    if index > 0 {
+        mirror_session(&jar, &vaults.vaults[0].0.db, &state.db);
+    }
+    match router.clone().oneshot(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let names = ["research", "teaching"];
+        assert_eq!(select(&names, None, "/"), 0);
+        assert_eq!(select(&names, Some("teaching"), "/note/abc"), 1);
+        assert_eq!(select(&names, Some("missing"), "/"), 0);
+        assert_eq!(select(&names, Some("teaching"), "/login"), 0);
+    }
+
+    #[test]
+    fn test_mirror_session() {
+        let (primary, vault) = (crate::store::Db::temporary(), crate::store::Db::temporary());
+        let id = crate::auth::create_session(&primary).unwrap();
+        let jar = CookieJar::new().add(axum_extra::extract::cookie::Cookie::new(crate::auth::SESSION_COOKIE, id.clone()));
+        let stored = |db: &crate::store::Db| db.open_tree("sessions").unwrap().get(&id).unwrap();
+
+        mirror_session(&jar, &primary, &vault);
+        assert!(stored(&vault).is_some());
+        assert_eq!(stored(&vault), stored(&primary));
+        mirror_session(&jar, &primary, &vault);
+        assert_eq!(stored(&vault), stored(&primary));
+
+        crate::auth::delete_session(&id, &primary);
+        mirror_session(&jar, &primary, &vault);
+        assert_eq!(stored(&vault), None);
+    }
+}
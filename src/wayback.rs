@@ -0,0 +1,161 @@
+//! Wayback Machine snapshots of URL sources.
+//!
+//! With `[links] wayback` set, attaching a `url:` source to a note asks the
+//! Wayback Machine's Save Page Now to archive the page, in the background,
+//! and records the snapshot as a `wayback:` line next to it in the
+//! frontmatter. The snapshot URL embeds the original, which is how the two
+//! are paired. When saving fails (it is rate limited), the latest existing
+//! snapshot is used instead, if there is one.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::handlers::rewrite_note_file;
+use crate::models::{Note, NoteType};
+use crate::smart_add::insert_before_frontmatter_end;
+use crate::AppState;
+
+const SAVE_URL: &str = "https://web.archive.org/save/";
+const AVAILABLE_URL: &str = "https://archive.org/wayback/available";
+const WAYBACK_ORIGIN: &str = "https://web.archive.org";
+
+/// Save Page Now often takes most of a minute.
+const SAVE_TIMEOUT: Duration = Duration::from_secs(120);
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; NotesApp/1.0)";
+
+/// The snapshot URL in a Wayback response's location (absolute, or a path
+/// such as `/web/20261017093000/https://example.com/`), if it is one.
+pub fn snapshot_url(location: &str) -> Option<String> {
+    let path = location.strip_prefix(WAYBACK_ORIGIN).unwrap_or(location);
+    let rest = path.strip_prefix("/web/")?;
+    let (timestamp, original) = rest.split_once('/')?;
+    let is_timestamp = timestamp.len() >= 8 && timestamp.chars().all(|c| c.is_ascii_digit());
+    (is_timestamp && original.starts_with("http")).then(|| format!("{}{}", WAYBACK_ORIGIN, path))
+}
+
+/// Whether the note already records a snapshot of `url`.
+pub fn has_snapshot(note: &Note, url: &str) -> bool {
+    let NoteType::Paper(ref paper) = note.note_type else {
+        return false;
+    };
+    paper
+        .sources
+        .iter()
+        .any(|s| s.source_type == "wayback" && s.identifier.ends_with(url))
+}
+
+/// Ask Save Page Now to archive `url`; returns the snapshot URL.
+async fn save_page(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    crate::http::throttle(SAVE_URL).await;
+    let response = client
+        .get(format!("{}{}", SAVE_URL, url))
+        .timeout(SAVE_TIMEOUT)
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await
+        .map_err(|e| format!("Save Page Now failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("Save Page Now returned HTTP {}", response.status()));
+    }
+    let location = response
+        .headers()
+        .get(reqwest::header::CONTENT_LOCATION)
+        .and_then(|l| l.to_str().ok())
+        .and_then(snapshot_url);
+    location
+        .or_else(|| snapshot_url(response.url().as_str()))
+        .ok_or_else(|| "Save Page Now didn't return a snapshot".to_string())
+}
+
+/// The latest existing snapshot of `url`, if any.
+async fn latest_snapshot(client: &reqwest::Client, url: &str) -> Result<Option<String>, String> {
+    crate::http::throttle(AVAILABLE_URL).await;
+    let response = client
+        .get(AVAILABLE_URL)
+        .query(&[("url", url)])
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .send()
+        .await
+        .map_err(|e| format!("Wayback lookup failed: {}", e))?;
+    let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+    let closest = &json["archived_snapshots"]["closest"];
+    Ok(closest["url"]
+        .as_str()
+        .filter(|_| closest["available"].as_bool() == Some(true))
+        .map(|u| u.replacen("http://", "https://", 1)))
+}
+
+/// Archive `url`, falling back to the latest existing snapshot.
+pub async fn request_snapshot(client: &reqwest::Client, url: &str) -> Result<String, String> {
+    let saved = save_page(client, url).await;
+    crate::telemetry::record_external_call("wayback_save", saved.is_ok());
+    match saved {
+        Ok(snapshot) => Ok(snapshot),
+        Err(e) => {
+            let latest = latest_snapshot(client, url).await;
+            crate::telemetry::record_external_call("wayback_available", latest.is_ok());
+            latest?.ok_or(e)
+        }
+    }
+}
+
+/// Snapshot `url`, a source just attached to the note `key`, and record it
+/// as `wayback:`. Spawned by Smart Add; failures are only logged.
+pub async fn archive_source(state: Arc<AppState>, key: String, url: String) {
+    let snapshot = match request_snapshot(&state.http, &url).await {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::warn!(key = %key, url = %url, error = %e, "Wayback snapshot failed");
+            return;
+        }
+    };
+
+    let notes_map = state.notes_map();
+    let Some(note) = notes_map.get(&key) else {
+        return;
+    };
+    if has_snapshot(note, &url) {
+        return;
+    }
+    let commit_msg = format!("archived link in '{}'", note.title);
+    let line = format!("wayback: {}", snapshot);
+    match rewrite_note_file(&state, note, commit_msg, |c| {
+        insert_before_frontmatter_end(c, &line).ok_or_else(|| "Could not find frontmatter".to_string())
+    }) {
+        Ok(_) => tracing::info!(key = %key, snapshot = %snapshot, "Recorded Wayback snapshot"),
+        Err((_, e)) => tracing::warn!(key = %key, error = %e, "Failed to record Wayback snapshot"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::notes::load_all_notes;
+    use std::fs;
+
+    #[test]
+    fn test_snapshot_urls() {
+        assert_eq!(
+            snapshot_url("/web/20261017093000/https://example.com/post").as_deref(),
+            Some("https://web.archive.org/web/20261017093000/https://example.com/post")
+        );
+        assert_eq!(
+            snapshot_url("https://web.archive.org/web/20261017093000/http://example.com/").as_deref(),
+            Some("https://web.archive.org/web/20261017093000/http://example.com/")
+        );
+        assert_eq!(snapshot_url("https://web.archive.org/save/https://example.com/"), None);
+        assert_eq!(snapshot_url("/web/latest/https://example.com/"), None);
+
+        let dir = TempDir::new("wayback");
+        fs::write(
+            dir.join("p.md"),
+            "---\ntitle: P\nid: aaaaaaaaaaaa\nbibtex: |\n  @misc{p, title = {P}}\nurl: https://example.com/post\n\
+             wayback: https://web.archive.org/web/20261017093000/https://example.com/post\n---\n",
+        )
+        .unwrap();
+        let notes = load_all_notes(&dir.to_path_buf());
+        assert!(has_snapshot(&notes[0], "https://example.com/post"));
+        assert!(!has_snapshot(&notes[0], "https://example.com/other"));
+    }
+}
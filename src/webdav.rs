@@ -0,0 +1,633 @@
+//! WebDAV access to the notes directory at `/dav/`.
+//!
+//! The subset of RFC 4918 that editors and file managers use: OPTIONS,
+//! PROPFIND (depth 0 or 1), GET/HEAD, PUT, DELETE, MKCOL, COPY, MOVE, and
+//! LOCK/UNLOCK that hand out tokens without locking. Clients log in with
+//! HTTP Basic and the password (any user name) and get a session cookie
+//! scoped to `/dav`; a browser session works too. There is no file watcher,
+//! so each change is handled like a web edit: the notes cache is dropped,
+//! changed notes are reindexed and the change is committed; DELETE moves
+//! notes to the trash. Dotfiles (`.git`, `.trash`) are neither listed nor
+//! reachable; the `._*` and `.DS_Store` files macOS writes are accepted and
+//! dropped.
+//!
+//! DELETE refuses non-empty directories, and a note replaced by COPY or MOVE
+//! goes to the trash. Moving a note rewrites references to its key as
+//! `notes mv` does. XML hrefs carry `base_path` themselves, since
+//! `rewrite_urls` only touches HTML.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Utc};
+
+use crate::auth::{create_session, is_auth_enabled, is_logged_in, verify_password, SESSION_COOKIE, SESSION_TTL_HOURS};
+use crate::formats::check_writable;
+use crate::models::Note;
+use crate::notes::html_escape;
+use crate::{note_move, AppState};
+
+/// Largest file a PUT may upload.
+const MAX_UPLOAD: usize = 50 * 1024 * 1024;
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+const XML_HEADER: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
+
+/// The path segments of a `/dav/...` request path, percent-decoded.
+fn dav_path(path: &str) -> Result<Vec<String>, StatusCode> {
+    let rest = path.strip_prefix("/dav").ok_or(StatusCode::NOT_FOUND)?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let mut segments = Vec::new();
+    for segment in rest.split('/').filter(|s| !s.is_empty()) {
+        let decoded = urlencoding::decode(segment).map_err(|_| StatusCode::BAD_REQUEST)?;
+        if decoded == "." || decoded == ".." || decoded.contains(['/', '\\', '\0']) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        segments.push(decoded.into_owned());
+    }
+    Ok(segments)
+}
+
+fn is_hidden(segments: &[String]) -> bool {
+    segments.iter().any(|s| s.starts_with('.'))
+}
+
+/// macOS metadata files: accepted, never stored.
+fn is_ignored(segments: &[String]) -> bool {
+    match segments.split_last() {
+        Some((name, dirs)) => (name.starts_with("._") || name == ".DS_Store") && !is_hidden(dirs),
+        None => false,
+    }
+}
+
+fn fs_path(state: &AppState, segments: &[String]) -> PathBuf {
+    state.notes_dir.join(segments.join("/"))
+}
+
+fn href(state: &AppState, segments: &[String], is_dir: bool) -> String {
+    let encoded: Vec<String> = segments.iter().map(|s| urlencoding::encode(s).into_owned()).collect();
+    let mut href = format!("{}/dav/{}", state.config.base_path, encoded.join("/"));
+    if is_dir && !segments.is_empty() {
+        href.push('/');
+    }
+    href
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, e)| e.to_lowercase()).as_deref() {
+        Some("md" | "markdown") => "text/markdown; charset=utf-8",
+        Some("txt" | "org") => "text/plain; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn etag(meta: &fs::Metadata) -> String {
+    let modified = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs());
+    format!("\"{:x}-{:x}\"", meta.len(), modified)
+}
+
+/// One `<D:response>` of a PROPFIND multistatus.
+fn prop_response(href: &str, name: &str, meta: &fs::Metadata) -> String {
+    let modified: DateTime<Utc> = meta.modified().map(DateTime::from).unwrap_or_else(|_| Utc::now());
+    let kind = if meta.is_dir() {
+        "<D:resourcetype><D:collection/></D:resourcetype>".to_string()
+    } else {
+        format!(
+            "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+             <D:getcontenttype>{}</D:getcontenttype><D:getetag>{}</D:getetag>",
+            meta.len(),
+            content_type(name),
+            html_escape(&etag(meta))
+        )
+    };
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+         <D:displayname>{}</D:displayname><D:getlastmodified>{}</D:getlastmodified>{}\
+         <D:supportedlock><D:lockentry><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:locktype><D:write/></D:locktype></D:lockentry></D:supportedlock>\
+         </D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        html_escape(href),
+        html_escape(name),
+        http_date(modified),
+        kind
+    )
+}
+
+fn multistatus(body: String) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        format!(r#"{}<D:multistatus xmlns:D="DAV:">{}</D:multistatus>"#, XML_HEADER, body),
+    )
+        .into_response()
+}
+
+/// The password of an `Authorization: Basic` header.
+fn basic_password(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let encoded = value.strip_prefix("Basic ").or_else(|| value.strip_prefix("basic "))?;
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+    Some(decoded.split_once(':')?.1.to_string())
+}
+
+enum Access {
+    Denied,
+    Session,
+    /// Basic credentials verified; the new session to hand back, so clients
+    /// that keep cookies skip the Argon2 check on later requests.
+    Basic(Option<String>),
+}
+
+/// A session cookie (from the browser or an earlier Basic login), or Basic
+/// credentials with the password, subject to the login rate limit.
+async fn authorized(state: &AppState, headers: &HeaderMap) -> Access {
+    if is_logged_in(&CookieJar::from_headers(headers), &state.db) {
+        return Access::Session;
+    }
+    let (Some(hash), Some(password)) = (state.password_hash.clone(), basic_password(headers)) else {
+        return Access::Denied;
+    };
+    if state.login_rate_limit.lock().unwrap().is_locked() {
+        return Access::Denied;
+    }
+    let valid = tokio::task::spawn_blocking(move || verify_password(&password, &hash))
+        .await
+        .unwrap_or(false);
+    let mut rate_limit = state.login_rate_limit.lock().unwrap();
+    if !valid {
+        rate_limit.record_failure();
+        return Access::Denied;
+    }
+    rate_limit.reset();
+    Access::Basic(create_session(&state.db))
+}
+
+/// The session cookie for a Basic login, scoped to `/dav` so it doesn't log
+/// in the browser.
+fn session_cookie(state: &AppState, session: &str) -> String {
+    format!(
+        "{}={}; Path={}/dav; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE,
+        session,
+        state.config.base_path,
+        SESSION_TTL_HOURS * 3600
+    )
+}
+
+/// After a change: refresh the cache and graph, then commit `paths`.
+fn record_change(state: &Arc<AppState>, paths: Vec<PathBuf>, removed: bool, summary: String) {
+    state.invalidate_notes_cache();
+    let notes = state.load_notes();
+    for path in &paths {
+        if let Some(note) = notes.iter().find(|n| &n.path == path) {
+            state.reindex_graph_note(&note.key);
+        }
+    }
+    if removed {
+        if let Err(e) = crate::graph_index::reconcile(&state.db, &notes) {
+            tracing::error!(error = %e, "Graph reconcile failed");
+        }
+    }
+    let notes_dir = state.notes_dir.clone();
+    state.spawn_background(move || crate::trash::commit_paths(&notes_dir, &paths, &summary));
+}
+
+/// Every WebDAV method on `/dav/...`.
+pub async fn handle(State(state): State<Arc<AppState>>, request: Request) -> Response {
+    if !state.config.webdav {
+        return (StatusCode::NOT_FOUND, "Not found").into_response();
+    }
+    if !is_auth_enabled() {
+        return (StatusCode::FORBIDDEN, "WebDAV needs NOTES_PASSWORD").into_response();
+    }
+    let (parts, body) = request.into_parts();
+    if parts.method == Method::OPTIONS {
+        return (
+            StatusCode::OK,
+            [(header::ALLOW, ALLOW), (header::HeaderName::from_static("dav"), "1, 2")],
+        )
+            .into_response();
+    }
+    let session = match authorized(&state, &parts.headers).await {
+        Access::Denied => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, r#"Basic realm="notes", charset="UTF-8""#)],
+                "Not logged in",
+            )
+                .into_response()
+        }
+        Access::Session | Access::Basic(None) => None,
+        Access::Basic(Some(session)) => Some(session),
+    };
+    let mut response = respond(&state, parts, body).await;
+    if let Some(cookie) = session.and_then(|s| session_cookie(&state, &s).parse().ok()) {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    response
+}
+
+async fn respond(state: &Arc<AppState>, parts: axum::http::request::Parts, body: Body) -> Response {
+    let segments = match dav_path(parts.uri.path()) {
+        Ok(segments) => segments,
+        Err(status) => return status.into_response(),
+    };
+
+    let method = parts.method.as_str();
+    if is_hidden(&segments) {
+        return match method {
+            "PUT" | "DELETE" | "UNLOCK" if is_ignored(&segments) => StatusCode::NO_CONTENT.into_response(),
+            "LOCK" if is_ignored(&segments) => lock(state, &segments),
+            _ => StatusCode::NOT_FOUND.into_response(),
+        };
+    }
+    match method {
+        "PROPFIND" => {
+            let depth_zero = parts.headers.get("depth").and_then(|v| v.to_str().ok()) == Some("0");
+            propfind(state, &segments, !depth_zero)
+        }
+        "PROPPATCH" => multistatus(format!(
+            "<D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status>\
+             </D:propstat></D:response>",
+            html_escape(&href(state, &segments, false))
+        )),
+        "GET" | "HEAD" => get(state, &segments),
+        "PUT" => put(state, &segments, body).await,
+        "DELETE" => delete(state, &segments),
+        "MKCOL" => mkcol(state, &segments),
+        "COPY" | "MOVE" => copy_or_move(state, &segments, &parts.headers, method == "MOVE"),
+        "LOCK" => lock(state, &segments),
+        "UNLOCK" => StatusCode::NO_CONTENT.into_response(),
+        _ => (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response(),
+    }
+}
+
+fn propfind(state: &AppState, segments: &[String], with_children: bool) -> Response {
+    let path = fs_path(state, segments);
+    let Ok(meta) = fs::metadata(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let name = segments.last().map_or("", String::as_str);
+    let mut body = prop_response(&href(state, segments, meta.is_dir()), name, &meta);
+    if meta.is_dir() && with_children {
+        let mut children: Vec<(String, fs::Metadata)> = fs::read_dir(&path)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| {
+                let e = e.ok()?;
+                let name = e.file_name().into_string().ok()?;
+                let meta = fs::metadata(e.path()).ok()?;
+                Some((name, meta))
+            })
+            .filter(|(name, _)| !name.starts_with('.'))
+            .collect();
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        for (child, meta) in children {
+            let mut child_segments = segments.to_vec();
+            child_segments.push(child.clone());
+            body.push_str(&prop_response(&href(state, &child_segments, meta.is_dir()), &child, &meta));
+        }
+    }
+    multistatus(body)
+}
+
+fn get(state: &AppState, segments: &[String]) -> Response {
+    let path = fs_path(state, segments);
+    let Ok(meta) = fs::metadata(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if meta.is_dir() {
+        return (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response();
+    }
+    let Ok(bytes) = fs::read(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let modified: DateTime<Utc> = meta.modified().map(DateTime::from).unwrap_or_else(|_| Utc::now());
+    (
+        [
+            (header::CONTENT_TYPE, content_type(&path.to_string_lossy()).to_string()),
+            (header::ETAG, etag(&meta)),
+            (header::LAST_MODIFIED, http_date(modified)),
+        ],
+        bytes,
+    )
+        .into_response()
+}
+
+async fn put(state: &Arc<AppState>, segments: &[String], body: Body) -> Response {
+    let path = fs_path(state, segments);
+    if segments.is_empty() || path.is_dir() {
+        return (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response();
+    }
+    let rel = PathBuf::from(segments.join("/"));
+    if let Err(e) = check_writable(&rel) {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return StatusCode::CONFLICT.into_response();
+    }
+    let bytes = match to_bytes(body, MAX_UPLOAD).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let existed = path.exists();
+    if existed && fs::read(&path).is_ok_and(|old| old == bytes) {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    if let Err(e) = crate::encryption::write_bytes(&path, &bytes) {
+        return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response();
+    }
+    let summary = format!("WebDAV: {} {}", if existed { "update" } else { "add" }, rel.display());
+    record_change(state, vec![rel], false, summary);
+    if existed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::CREATED.into_response()
+    }
+}
+
+/// Remove `rel` for DELETE or an overwriting COPY/MOVE. Notes go to the
+/// trash, as when deleted in the app, and their trash entry is returned;
+/// other files are removed. Directories must be emptied first, so one
+/// request can't take a whole folder of notes with it.
+fn remove(state: &AppState, notes: &[Note], rel: &Path) -> Result<Option<PathBuf>, (StatusCode, String)> {
+    let path = state.notes_dir.join(rel);
+    let Ok(meta) = fs::symlink_metadata(&path) else {
+        return Err((StatusCode::NOT_FOUND, String::new()));
+    };
+    let removed = if meta.is_dir() {
+        if fs::read_dir(&path).is_ok_and(|mut entries| entries.next().is_some()) {
+            return Err((StatusCode::FORBIDDEN, "Delete the files in the folder first".to_string()));
+        }
+        fs::remove_dir(&path).map(|_| None).map_err(|e| format!("Failed to delete: {}", e))
+    } else if let Some(note) = notes.iter().find(|n| n.path == rel) {
+        check_writable(rel).map_err(|e| (StatusCode::FORBIDDEN, e))?;
+        crate::trash::trash_note(&state.notes_dir, note, crate::timezone::now().naive_local())
+            .map(|trashed| Some(trashed.dir()))
+    } else {
+        fs::remove_file(&path).map(|_| None).map_err(|e| format!("Failed to delete: {}", e))
+    };
+    removed.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+fn delete(state: &Arc<AppState>, segments: &[String]) -> Response {
+    if segments.is_empty() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let rel = PathBuf::from(segments.join("/"));
+    let mut paths = vec![rel.clone()];
+    match remove(state, &state.load_notes(), &rel) {
+        Ok(trashed) => paths.extend(trashed),
+        Err(response) => return response.into_response(),
+    }
+    let summary = format!("WebDAV: delete {}", rel.display());
+    record_change(state, paths, true, summary);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+fn mkcol(state: &AppState, segments: &[String]) -> Response {
+    let path = fs_path(state, segments);
+    if segments.is_empty() || path.exists() {
+        return (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response();
+    }
+    if !path.parent().is_some_and(Path::is_dir) {
+        return StatusCode::CONFLICT.into_response();
+    }
+    match fs::create_dir(&path) {
+        Ok(()) => StatusCode::CREATED.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create: {}", e)).into_response(),
+    }
+}
+
+/// Segments of a `Destination` header, which may be a full URL.
+fn destination(state: &AppState, headers: &HeaderMap) -> Result<Vec<String>, StatusCode> {
+    let value = headers
+        .get("destination")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let path = match url::Url::parse(value) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => value.to_string(),
+    };
+    let path = path.strip_prefix(state.config.base_path.as_str()).ok_or(StatusCode::BAD_GATEWAY)?;
+    dav_path(path).map_err(|_| StatusCode::BAD_GATEWAY)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_or_move(state: &Arc<AppState>, segments: &[String], headers: &HeaderMap, is_move: bool) -> Response {
+    let dest_segments = match destination(state, headers) {
+        Ok(dest) => dest,
+        Err(status) => return status.into_response(),
+    };
+    if is_ignored(&dest_segments) {
+        return StatusCode::CREATED.into_response();
+    }
+    if segments.is_empty() || dest_segments.is_empty() || is_hidden(&dest_segments) || dest_segments == segments {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if dest_segments.starts_with(segments) {
+        return StatusCode::CONFLICT.into_response();
+    }
+    let from = fs_path(state, segments);
+    let to = fs_path(state, &dest_segments);
+    let Ok(meta) = fs::metadata(&from) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !to.parent().is_some_and(Path::is_dir) {
+        return StatusCode::CONFLICT.into_response();
+    }
+    let (from_rel, to_rel) = (PathBuf::from(segments.join("/")), PathBuf::from(dest_segments.join("/")));
+    let notes = state.load_notes();
+    // A move changes the path of every note under the source
+    let moved = notes.iter().filter(|n| is_move && n.path.starts_with(&from_rel));
+    let writable = moved.map(|n| n.path.as_path()).chain([from_rel.as_path(), to_rel.as_path()]).try_for_each(check_writable);
+    if let Err(e) = writable {
+        return (StatusCode::FORBIDDEN, e).into_response();
+    }
+    let mut paths = if is_move { vec![from_rel.clone(), to_rel.clone()] } else { vec![to_rel.clone()] };
+    let existed = to.exists();
+    if existed {
+        if headers.get("overwrite").and_then(|v| v.to_str().ok()) == Some("F") {
+            return StatusCode::PRECONDITION_FAILED.into_response();
+        }
+        match remove(state, &notes, &to_rel) {
+            Ok(trashed) => paths.extend(trashed),
+            Err(response) => return response.into_response(),
+        }
+    }
+    let result = match (is_move, meta.is_dir()) {
+        // Moved notes keyed by their path get new keys; `[@key]` and
+        // `parent:` references to them are rewritten as `notes mv` does
+        (true, _) => match note_move::plan_move(&state.notes_dir, &notes, &from_rel, &to_rel) {
+            Ok(plan) => {
+                paths.extend(plan.rewrites.iter().map(|(path, _)| path.clone()));
+                note_move::apply_move(&state.notes_dir, &plan)
+            }
+            Err(_) => fs::rename(&from, &to).map_err(|e| e.to_string()),
+        },
+        (false, true) => copy_dir(&from, &to).map_err(|e| e.to_string()),
+        (false, false) => fs::copy(&from, &to).map(|_| ()).map_err(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        return (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to {}: {}", if is_move { "move" } else { "copy" }, e))
+            .into_response();
+    }
+
+    let summary = if is_move {
+        format!("WebDAV: move {} to {}", from_rel.display(), to_rel.display())
+    } else {
+        format!("WebDAV: copy {} to {}", from_rel.display(), to_rel.display())
+    };
+    record_change(state, paths, is_move || existed, summary);
+    if existed {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        StatusCode::CREATED.into_response()
+    }
+}
+
+/// Grant a lock without enforcing it; clients like Finder won't write
+/// without one.
+fn lock(state: &AppState, segments: &[String]) -> Response {
+    let token = format!("opaquelocktoken:{:032x}", rand::random::<u128>());
+    let body = format!(
+        r#"{}<D:prop xmlns:D="DAV:"><D:lockdiscovery><D:activelock><D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope><D:depth>0</D:depth><D:timeout>Second-3600</D:timeout><D:locktoken><D:href>{}</D:href></D:locktoken><D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock></D:lockdiscovery></D:prop>"#,
+        XML_HEADER,
+        token,
+        html_escape(&href(state, segments, false))
+    );
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+            (header::HeaderName::from_static("lock-token"), format!("<{}>", token)),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{app_state, TempDir};
+
+    #[test]
+    fn test_dav_paths() {
+        assert_eq!(dav_path("/dav").unwrap(), Vec::<String>::new());
+        assert_eq!(dav_path("/dav/papers/Smith%20Notes.md").unwrap(), ["papers", "Smith Notes.md"]);
+        assert_eq!(dav_path("/dav/a/%2e%2e/b"), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(dav_path("/dav/a%2Fb"), Err(StatusCode::BAD_REQUEST));
+        assert_eq!(dav_path("/davx"), Err(StatusCode::NOT_FOUND));
+
+        let segments = |p: &str| dav_path(p).unwrap();
+        assert!(is_hidden(&segments("/dav/.git/config")));
+        assert!(!is_ignored(&segments("/dav/.git/config")));
+        assert!(is_ignored(&segments("/dav/papers/._a.md")) && is_ignored(&segments("/dav/.DS_Store")));
+
+        let headers: HeaderMap = [(header::AUTHORIZATION, "Basic dXNlcjpwYTpzcw==".parse().unwrap())].into_iter().collect();
+        assert_eq!(basic_password(&headers).as_deref(), Some("pa:ss"));
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth_sets_session() {
+        use argon2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+
+        let dir = TempDir::new("webdav-auth");
+        let mut state = app_state(&dir);
+        let salt = SaltString::generate(&mut OsRng);
+        state.password_hash = Some(argon2::Argon2::default().hash_password(b"secret", &salt).unwrap().to_string());
+        let basic = |password: &str| -> HeaderMap {
+            let value = format!("Basic {}", BASE64.encode(format!("me:{}", password)));
+            [(header::AUTHORIZATION, value.parse().unwrap())].into_iter().collect()
+        };
+
+        assert!(matches!(authorized(&state, &basic("wrong")).await, Access::Denied));
+        let Access::Basic(Some(session)) = authorized(&state, &basic("secret")).await else {
+            panic!("Basic auth with the password should create a session");
+        };
+        assert!(crate::auth::verify_session(&session, &state.db));
+        assert!(session_cookie(&state, &session).contains("Path=/dav;"));
+    }
+
+    #[tokio::test]
+    async fn test_delete_goes_to_trash() {
+        let dir = TempDir::new("webdav");
+        let state = Arc::new(app_state(&dir));
+        fs::create_dir_all(state.notes_dir.join("papers")).unwrap();
+        fs::write(state.notes_dir.join("papers/a.md"), "---\ntitle: A\n---\n\nBody\n").unwrap();
+        state.invalidate_notes_cache();
+        let segments = |p: &str| dav_path(p).unwrap();
+
+        assert_eq!(delete(&state, &segments("/dav/papers")).status(), StatusCode::FORBIDDEN);
+        assert_eq!(delete(&state, &segments("/dav/papers/a.md")).status(), StatusCode::NO_CONTENT);
+        assert!(!state.notes_dir.join("papers/a.md").exists());
+        let trashed = crate::trash::list_trash(&state.notes_dir);
+        assert_eq!(trashed.len(), 1);
+        crate::trash::restore(&state.notes_dir, &trashed[0].id).unwrap();
+        assert_eq!(fs::read_to_string(state.notes_dir.join("papers/a.md")).unwrap(), "---\ntitle: A\n---\n\nBody\n");
+    }
+
+    #[tokio::test]
+    async fn test_move_rewrites_keys_and_trashes_replaced_note() {
+        let dir = TempDir::new("webdav-move");
+        let state = Arc::new(app_state(&dir));
+        let key = crate::notes::generate_key(Path::new("a.md"));
+        fs::create_dir_all(&state.notes_dir).unwrap();
+        fs::write(state.notes_dir.join("a.md"), "---\ntitle: A\n---\n").unwrap();
+        fs::write(state.notes_dir.join("b.md"), "---\ntitle: B\n---\n").unwrap();
+        fs::write(state.notes_dir.join("index.md"), format!("---\ntitle: Index\n---\n[@{}]\n", key)).unwrap();
+        fs::write(state.notes_dir.join("outline.org"), "#+title: Outline\n").unwrap();
+        state.invalidate_notes_cache();
+        let segments = |p: &str| dav_path(p).unwrap();
+        let to = |dest: &str| -> HeaderMap { [(header::HeaderName::from_static("destination"), dest.parse().unwrap())].into_iter().collect() };
+
+        let response = copy_or_move(&state, &segments("/dav/outline.org"), &to("/dav/moved.org"), true);
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = copy_or_move(&state, &segments("/dav/a.md"), &to("/dav/outline.org"), false);
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = copy_or_move(&state, &segments("/dav/a.md"), &to("/dav/b.md"), true);
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(fs::read_to_string(state.notes_dir.join("b.md")).unwrap(), "---\ntitle: A\n---\n");
+        let index = fs::read_to_string(state.notes_dir.join("index.md")).unwrap();
+        assert!(index.contains(&format!("[@{}]", crate::notes::generate_key(Path::new("b.md")))));
+        let trashed = crate::trash::list_trash(&state.notes_dir);
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].title, "B");
+    }
+}